@@ -0,0 +1,471 @@
+// The globals of the headless compositor and the state they share
+//
+// This module is also built by the `headless_compositor` test, which runs the compositor
+// in-process and connects a client to it.
+
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::{Arc, Mutex};
+
+use nix::sys::uio::pread;
+use nix::unistd;
+
+use wayland_protocols::xdg_shell::server::xdg_popup;
+use wayland_protocols::xdg_shell::server::xdg_positioner::{self, XdgPositioner};
+use wayland_protocols::xdg_shell::server::xdg_surface::{self, XdgSurface};
+use wayland_protocols::xdg_shell::server::xdg_toplevel::{self, XdgToplevel};
+use wayland_protocols::xdg_shell::server::xdg_wm_base::{self, XdgWmBase};
+
+use wayland_server::protocol::wl_buffer::{self, WlBuffer};
+use wayland_server::protocol::wl_callback::{self, WlCallback};
+use wayland_server::protocol::wl_compositor::{self, WlCompositor};
+use wayland_server::protocol::wl_keyboard;
+use wayland_server::protocol::wl_seat::{self, WlSeat};
+use wayland_server::protocol::wl_shm::{self, Format, WlShm};
+use wayland_server::protocol::wl_shm_pool::{self, WlShmPool};
+use wayland_server::protocol::wl_surface::{self, WlSurface};
+use wayland_server::{Display, Resource};
+
+// the formats supported by the compositor, both with 4 bytes per pixel
+const FORMATS: [Format; 2] = [Format::Argb8888, Format::Xrgb8888];
+
+/// The contents of a surface, copied from its last committed buffer
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contents {
+    pub width: i32,
+    pub height: i32,
+    /// The pixels, row after row without padding
+    pub data: Vec<u8>,
+}
+
+/// A toplevel window, with the contents of its surface
+pub struct Window {
+    pub title: String,
+    pub contents: Option<Contents>,
+}
+
+// the xdg-shell role of a surface
+enum Role {
+    None,
+    Toplevel(Window),
+    Popup,
+}
+
+struct Surface {
+    surface: Resource<WlSurface>,
+    // the buffer attached since the last commit, `Some(None)` if it was removed
+    pending_buffer: Option<Option<Resource<WlBuffer>>>,
+    pending_frames: Vec<Resource<WlCallback>>,
+    xdg_surface: Option<Resource<XdgSurface>>,
+    // the serial of the last configure, and whether it was acknowledged
+    configure: Option<(u32, bool)>,
+    role: Role,
+}
+
+/// The state of the compositor, available to all implementations as the dispatch state
+pub struct State {
+    surfaces: Vec<Surface>,
+    // the frame callbacks of the committed surfaces, done at the next frame
+    frames: Vec<Resource<WlCallback>>,
+    serial: u32,
+}
+
+impl State {
+    pub fn new() -> State {
+        State {
+            surfaces: Vec::new(),
+            frames: Vec::new(),
+            serial: 0,
+        }
+    }
+
+    /// The toplevel windows, in the order they were created
+    pub fn windows(&self) -> Vec<&Window> {
+        self.surfaces
+            .iter()
+            .filter_map(|s| match s.role {
+                Role::Toplevel(ref window) => Some(window),
+                _ => None,
+            }).collect()
+    }
+
+    /// Present a frame: the callbacks of the surfaces committed since the last one are done
+    ///
+    /// A headless compositor has no vertical blank to follow, it calls this at a fixed rate.
+    /// Returns whether some callbacks were done, `time` being given to them in milliseconds.
+    pub fn send_frames(&mut self, time: u32) -> bool {
+        let drawn = !self.frames.is_empty();
+        for callback in self.frames.drain(..) {
+            callback.send(wl_callback::Event::Done { callback_data: time });
+        }
+        self.surfaces.retain(|s| s.surface.is_alive());
+        drawn
+    }
+
+    fn next_serial(&mut self) -> u32 {
+        self.serial = self.serial.wrapping_add(1);
+        self.serial
+    }
+
+    fn surface(&mut self, surface: &Resource<WlSurface>) -> Option<&mut Surface> {
+        self.surfaces.iter_mut().find(|s| s.surface.equals(surface))
+    }
+
+    fn surface_of(&mut self, xdg_surface: &Resource<XdgSurface>) -> Option<&mut Surface> {
+        self.surfaces.iter_mut().find(|s| match s.xdg_surface {
+            Some(ref other) => other.equals(xdg_surface),
+            None => false,
+        })
+    }
+}
+
+/// Create the globals of the compositor
+///
+/// The requests are dispatched with a `State` as dispatch state, which must be the data of
+/// the event loop `display` was inserted in.
+pub fn create_globals(display: &mut Display) {
+    display.create_global::<WlCompositor, _>(4, |new, _| {
+        new.implement_with_state(compositor_request, None::<fn(_)>, ());
+    });
+    display.create_global::<WlShm, _>(1, |new, _| {
+        let shm = new.implement(shm_request, None::<fn(_)>, ());
+        for &format in &FORMATS {
+            shm.send(wl_shm::Event::Format { format });
+        }
+    });
+    display.create_global::<WlSeat, _>(5, |new, _| {
+        let seat = new.implement(seat_request, None::<fn(_)>, ());
+        // there are no input devices, but clients expect a pointer and a keyboard
+        seat.send(wl_seat::Event::Capabilities {
+            capabilities: wl_seat::Capability::Pointer | wl_seat::Capability::Keyboard,
+        });
+        if seat.version() >= 2 {
+            seat.send(wl_seat::Event::Name {
+                name: "headless".into(),
+            });
+        }
+    });
+    display.create_global::<XdgWmBase, _>(1, |new, _| {
+        new.implement_with_state(wm_base_request, None::<fn(_)>, ());
+    });
+}
+
+/*
+ * Surfaces
+ */
+
+fn compositor_request(request: wl_compositor::Request, _: Resource<WlCompositor>, state: &mut State) {
+    match request {
+        wl_compositor::Request::CreateSurface { id } => {
+            let surface = id.implement_with_state(surface_request, None::<fn(_)>, ());
+            state.surfaces.push(Surface {
+                surface,
+                pending_buffer: None,
+                pending_frames: Vec::new(),
+                xdg_surface: None,
+                configure: None,
+                role: Role::None,
+            });
+        }
+        // regions only matter to input and to the optimization of rendering
+        wl_compositor::Request::CreateRegion { id } => {
+            id.implement(|_, _| {}, None::<fn(_)>, ());
+        }
+    }
+}
+
+fn surface_request(request: wl_surface::Request, surface: Resource<WlSurface>, state: &mut State) {
+    let frames = match state.surface(&surface) {
+        Some(data) => data.handle(request),
+        None => return,
+    };
+    state.frames.extend(frames);
+}
+
+impl Surface {
+    // handle a request, returns the frame callbacks of a commit
+    fn handle(&mut self, request: wl_surface::Request) -> Vec<Resource<WlCallback>> {
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => self.pending_buffer = Some(buffer),
+            wl_surface::Request::Frame { callback } => {
+                let callback = callback.implement(|request, _| match request {}, None::<fn(_)>, ());
+                self.pending_frames.push(callback);
+            }
+            wl_surface::Request::Commit => {
+                if let Some(buffer) = self.pending_buffer.take() {
+                    self.commit_buffer(buffer);
+                }
+                return self.pending_frames.drain(..).collect();
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    fn commit_buffer(&mut self, buffer: Option<Resource<WlBuffer>>) {
+        if let (Some(xdg_surface), Some((_, false))) = (self.xdg_surface.as_ref(), self.configure) {
+            if buffer.is_some() {
+                xdg_surface.post_error_enum(
+                    xdg_surface::Error::UnconfiguredBuffer,
+                    "A buffer was committed before the first configure was acknowledged.".into(),
+                );
+                return;
+            }
+        }
+        let contents = buffer.and_then(|buffer| {
+            let contents = buffer.user_data::<ShmBuffer>().map(ShmBuffer::read);
+            // the contents were copied, the client can reuse the buffer right away
+            buffer.send(wl_buffer::Event::Release);
+            contents
+        });
+        if let Role::Toplevel(ref mut window) = self.role {
+            window.contents = contents;
+        }
+    }
+}
+
+/*
+ * Shared memory
+ */
+
+// a memory file shared by a client, closed once its pool and all their buffers are destroyed
+struct Pool {
+    file: File,
+    size: i32,
+}
+
+// a buffer of a pool, read by the compositor whenever it is committed
+struct ShmBuffer {
+    pool: Arc<Mutex<Pool>>,
+    offset: i32,
+    width: i32,
+    height: i32,
+    stride: i32,
+}
+
+impl ShmBuffer {
+    fn read(&self) -> Contents {
+        let pool = self.pool.lock().unwrap();
+        let row = self.width as usize * 4;
+        let mut data = vec![0u8; row * self.height as usize];
+        for (i, line) in data.chunks_mut(row).enumerate() {
+            let offset = self.offset as i64 + i as i64 * self.stride as i64;
+            // the client may have truncated its file, the missing pixels are left black
+            let _ = pread(pool.file.as_raw_fd(), line, offset);
+        }
+        Contents {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
+
+fn shm_request(request: wl_shm::Request, shm: Resource<WlShm>) {
+    let wl_shm::Request::CreatePool { id, fd, size } = request;
+    if size <= 0 {
+        let _ = unistd::close(fd);
+        shm.post_error_enum(
+            wl_shm::Error::InvalidStride,
+            format!("Invalid pool size {}.", size),
+        );
+        return;
+    }
+    let pool = Pool {
+        file: unsafe { File::from_raw_fd(fd) },
+        size,
+    };
+    id.implement(pool_request, None::<fn(_)>, Arc::new(Mutex::new(pool)));
+}
+
+fn pool_request(request: wl_shm_pool::Request, pool: Resource<WlShmPool>) {
+    let shared = pool.user_data::<Arc<Mutex<Pool>>>().unwrap().clone();
+    match request {
+        wl_shm_pool::Request::CreateBuffer {
+            id,
+            offset,
+            width,
+            height,
+            stride,
+            format,
+        } => {
+            if !FORMATS.contains(&format) {
+                pool.post_error_enum(
+                    wl_shm::Error::InvalidFormat,
+                    format!("Unsupported format {:?}.", format),
+                );
+                return;
+            }
+            let end = offset as i64 + stride as i64 * height as i64;
+            let size = shared.lock().unwrap().size;
+            if offset < 0 || width <= 0 || height <= 0 || stride < width * 4 || end > size as i64 {
+                pool.post_error_enum(
+                    wl_shm::Error::InvalidStride,
+                    format!(
+                        "Invalid buffer of {}x{} with offset {} and stride {} in a pool of {} bytes.",
+                        width, height, offset, stride, size
+                    ),
+                );
+                return;
+            }
+            let buffer = ShmBuffer {
+                pool: shared,
+                offset,
+                width,
+                height,
+                stride,
+            };
+            id.implement(|_, _| {}, None::<fn(_)>, buffer);
+        }
+        wl_shm_pool::Request::Resize { size } => {
+            let mut shared = shared.lock().unwrap();
+            if size < shared.size {
+                pool.post_error_enum(wl_shm::Error::InvalidStride, "Pools can not shrink.".into());
+                return;
+            }
+            shared.size = size;
+        }
+        wl_shm_pool::Request::Destroy => {}
+    }
+}
+
+/*
+ * Seat
+ */
+
+fn seat_request(request: wl_seat::Request, _: Resource<WlSeat>) {
+    match request {
+        wl_seat::Request::GetPointer { id } => {
+            id.implement(|_, _| {}, None::<fn(_)>, ());
+        }
+        wl_seat::Request::GetKeyboard { id } => {
+            let keyboard = id.implement(|_, _| {}, None::<fn(_)>, ());
+            // without a keymap, the clients interpret the key codes themselves
+            if let Ok(null) = File::open("/dev/null") {
+                keyboard.send(wl_keyboard::Event::Keymap {
+                    format: wl_keyboard::KeymapFormat::NoKeymap,
+                    fd: null.as_raw_fd(),
+                    size: 0,
+                });
+            }
+            if keyboard.version() >= 4 {
+                keyboard.send(wl_keyboard::Event::RepeatInfo { rate: 0, delay: 0 });
+            }
+        }
+        // the seat has no touch capability
+        wl_seat::Request::GetTouch { id } => {
+            id.implement(|_, _| {}, None::<fn(_)>, ());
+        }
+        wl_seat::Request::Release => {}
+    }
+}
+
+/*
+ * xdg-shell
+ */
+
+fn wm_base_request(request: xdg_wm_base::Request, wm_base: Resource<XdgWmBase>, state: &mut State) {
+    match request {
+        xdg_wm_base::Request::CreatePositioner { id } => {
+            id.implement(positioner_request, None::<fn(_)>, Mutex::new((0, 0)));
+        }
+        xdg_wm_base::Request::GetXdgSurface { id, surface } => {
+            let data = match state.surface(&surface) {
+                Some(data) => data,
+                None => return,
+            };
+            if data.xdg_surface.is_some() {
+                wm_base.post_error_enum(xdg_wm_base::Error::Role, "The surface already has a role.".into());
+                return;
+            }
+            data.xdg_surface = Some(id.implement_with_state(xdg_surface_request, None::<fn(_)>, ()));
+        }
+        xdg_wm_base::Request::Pong { .. } | xdg_wm_base::Request::Destroy => {}
+    }
+}
+
+// the positioners only record the size of their popup
+fn positioner_request(request: xdg_positioner::Request, positioner: Resource<XdgPositioner>) {
+    if let xdg_positioner::Request::SetSize { width, height } = request {
+        *positioner.user_data::<Mutex<(i32, i32)>>().unwrap().lock().unwrap() = (width, height);
+    }
+}
+
+fn xdg_surface_request(request: xdg_surface::Request, xdg_surface: Resource<XdgSurface>, state: &mut State) {
+    use self::xdg_surface::Request;
+
+    let creates_role = match request {
+        Request::GetToplevel { .. } | Request::GetPopup { .. } => true,
+        _ => false,
+    };
+    // a new role is configured right away
+    let serial = if creates_role { state.next_serial() } else { 0 };
+    let data = match state.surface_of(&xdg_surface) {
+        Some(data) => data,
+        None => return,
+    };
+    let has_role = match data.role {
+        Role::None => false,
+        _ => true,
+    };
+    if creates_role && has_role {
+        xdg_surface.post_error_enum(
+            xdg_surface::Error::AlreadyConstructed,
+            "The role is already set.".into(),
+        );
+        return;
+    }
+    match request {
+        Request::GetToplevel { id } => {
+            let toplevel = id.implement_with_state(toplevel_request, None::<fn(_)>, xdg_surface.clone());
+            // the size is left to the client
+            toplevel.send(xdg_toplevel::Event::Configure {
+                width: 0,
+                height: 0,
+                states: Vec::new(),
+            });
+            data.role = Role::Toplevel(Window {
+                title: String::new(),
+                contents: None,
+            });
+        }
+        Request::GetPopup { id, positioner, .. } => {
+            let (width, height) = *positioner
+                .user_data::<Mutex<(i32, i32)>>()
+                .unwrap()
+                .lock()
+                .unwrap();
+            let popup = id.implement(|_, _| {}, None::<fn(_)>, ());
+            // there is no output to constrain the popups to
+            popup.send(xdg_popup::Event::Configure {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            });
+            data.role = Role::Popup;
+        }
+        Request::AckConfigure { serial } => {
+            if let Some((last, ref mut acked)) = data.configure {
+                *acked |= serial == last;
+            }
+            return;
+        }
+        Request::SetWindowGeometry { .. } | Request::Destroy => return,
+    }
+    xdg_surface.send(xdg_surface::Event::Configure { serial });
+    data.configure = Some((serial, false));
+}
+
+fn toplevel_request(request: xdg_toplevel::Request, toplevel: Resource<XdgToplevel>, state: &mut State) {
+    let xdg_surface = toplevel.user_data::<Resource<XdgSurface>>().unwrap();
+    if let xdg_toplevel::Request::SetTitle { title } = request {
+        if let Some(&mut Surface {
+            role: Role::Toplevel(ref mut window),
+            ..
+        }) = state.surface_of(xdg_surface)
+        {
+            window.title = title;
+        }
+    }
+}
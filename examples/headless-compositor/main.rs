@@ -0,0 +1,65 @@
+extern crate nix;
+extern crate wayland_protocols;
+extern crate wayland_server;
+
+mod compositor;
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use wayland_server::calloop::EventLoop;
+use wayland_server::Display;
+
+// A compositor without any output nor input device, which can run on CI machines
+//
+// It accepts clients on the socket given as argument, or on the first free `wayland-N`
+// socket, and supports wl_shm buffers, xdg-shell windows and frame callbacks: enough for
+// clients to be tested against it. The contents of the windows are copied from their
+// buffers, and their sizes are printed whenever a client draws a new frame.
+//
+// The module `compositor` contains the globals of the compositor, and can be used as a
+// template for the core of a new one.
+
+fn main() {
+    let mut event_loop = EventLoop::<compositor::State>::new().unwrap();
+    let mut display = Display::new(event_loop.handle());
+
+    let socket_name = match env::args_os().nth(1) {
+        Some(name) => {
+            display.add_socket(Some(&name)).unwrap();
+            name
+        }
+        None => display.add_socket_auto().unwrap(),
+    };
+    println!("Listening on {}", socket_name.to_string_lossy());
+
+    compositor::create_globals(&mut display);
+
+    let mut state = compositor::State::new();
+    let start = Instant::now();
+    // the frames are presented at 60Hz, there is no display to synchronize with
+    let frame = Duration::from_millis(16);
+    let mut next_frame = start + frame;
+    loop {
+        let now = Instant::now();
+        let timeout = if next_frame > now {
+            next_frame - now
+        } else {
+            Duration::from_millis(0)
+        };
+        event_loop.dispatch(Some(timeout), &mut state).unwrap();
+        if Instant::now() >= next_frame {
+            let elapsed = start.elapsed();
+            let time = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos()) / 1_000_000;
+            if state.send_frames(time as u32) {
+                for window in state.windows() {
+                    if let Some(ref contents) = window.contents {
+                        println!("\"{}\": {}x{}", window.title, contents.width, contents.height);
+                    }
+                }
+            }
+            next_frame += frame;
+        }
+        display.flush_clients();
+    }
+}
@@ -0,0 +1,38 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use wayland_client::protocol::wl_display::RequestsTrait;
+use wayland_client::{Display, GlobalManager};
+
+// The list_globals example of wayland-client, with the event queue dispatched from the
+// smol executor rather than by a blocking roundtrip
+
+fn main() {
+    // Connect to the server
+    let (display, mut event_queue) = Display::connect_to_env().unwrap();
+
+    let globals = GlobalManager::new(&display);
+
+    // The server answers a sync request once it sent us the global list
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    let token = event_queue.get_token();
+    display
+        .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+        .unwrap();
+
+    // Register the connection in the reactor of smol, which tells the event queue when
+    // the connection is readable
+    let fd = smol::Async::new(event_queue.connection_fd()).unwrap();
+    smol::block_on(async {
+        while !done.get() {
+            // The requests are flushed by the future before it waits for the events
+            event_queue.dispatch_async(|| fd.readable()).await.unwrap();
+        }
+    });
+
+    // Print the list
+    for (id, interface, version) in globals.list() {
+        println!("{}: {} (version {})", id, interface, version);
+    }
+}
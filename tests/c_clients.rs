@@ -0,0 +1,158 @@
+// Interoperability of the server with libwayland-client
+//
+// The C clients of `tests/c_clients` are compiled at test time and run against a test
+// server. They load `libwayland-client.so` with `dlopen()`, so that only a C compiler is
+// needed to build them: the tests are skipped if it or the library is not available.
+
+mod helpers;
+
+use helpers::{ways, TestServer};
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::io::FromRawFd;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+
+use ways::protocol::wl_output::{Event as OutputEvent, Request as OutputRequest, WlOutput as ServerOutput};
+use ways::protocol::wl_shm::{Request as ShmRequest, WlShm as ServerShm};
+
+// the exit status of the clients which could not load libwayland-client
+const SKIP_STATUS: i32 = 77;
+
+const CFLAGS: &[&str] = &["-std=c99", "-D_POSIX_C_SOURCE=200809L", "-Wall"];
+
+// compile a client, `None` if there is no C compiler
+fn build_client(name: &str) -> Option<PathBuf> {
+    let source = PathBuf::from(format!("./tests/c_clients/{}.c", name));
+    let binary = env::temp_dir().join(format!("wayland-rs-c-client-{}-{}", name, ::std::process::id()));
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = match Command::new(&compiler)
+        .args(CFLAGS)
+        .arg("-o")
+        .arg(&binary)
+        .arg(&source)
+        .arg("-ldl")
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Skipping the C client {}, {} is not available: {}", name, compiler, e);
+            return None;
+        }
+    };
+    assert!(status.success(), "Failed to compile the C client {}.", name);
+    Some(binary)
+}
+
+// run a client against the server until it exits, `None` if it was skipped
+fn run_client(server: &mut TestServer, name: &str) -> Option<String> {
+    let binary = build_client(name)?;
+    let mut child = Command::new(&binary)
+        .env("WAYLAND_DISPLAY", &server.socket_name)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    while child.try_wait().unwrap().is_none() {
+        server.answer();
+    }
+    let Output { status, stdout, stderr } = child.wait_with_output().unwrap();
+    let _ = ::std::fs::remove_file(&binary);
+    let stderr = String::from_utf8_lossy(&stderr);
+    if status.code() == Some(SKIP_STATUS) {
+        eprintln!("Skipping the C client {}: {}", name, stderr);
+        return None;
+    }
+    assert!(status.success(), "The C client {} failed: {}", name, stderr);
+    Some(String::from_utf8(stdout).unwrap())
+}
+
+#[test]
+fn c_client_globals() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(OutputEvent::Mode {
+            flags: ways::protocol::wl_output::Mode::Current,
+            width: 1920,
+            height: 1080,
+            refresh: 60000,
+        });
+        output.send(OutputEvent::Scale { factor: 2 });
+        output.send(OutputEvent::Done);
+    });
+
+    let stdout = match run_client(&mut server, "globals") {
+        Some(stdout) => stdout,
+        None => return,
+    };
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["global wl_output 2", "mode 1 1920 1080 60000", "scale 2", "done"]
+    );
+}
+
+#[test]
+fn c_client_fd_passing() {
+    let contents = Arc::new(Mutex::new(None));
+    let contents2 = contents.clone();
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerShm, _>(1, move |shm, _| {
+        let contents = contents2.clone();
+        shm.implement(
+            move |req, _| {
+                let ShmRequest::CreatePool { id, fd, size } = req;
+                assert_eq!(size, 4096);
+                let mut file = unsafe { File::from_raw_fd(fd) };
+                // the fd shares its offset with the one of the client, which wrote to it
+                file.seek(SeekFrom::Start(0)).unwrap();
+                let mut buffer = String::new();
+                file.read_to_string(&mut buffer).unwrap();
+                *contents.lock().unwrap() = Some(buffer);
+                id.implement(|_, _| {}, None::<fn(_)>, ());
+            },
+            None::<fn(_)>,
+            (),
+        );
+    });
+
+    let stdout = match run_client(&mut server, "fd_passing") {
+        Some(stdout) => stdout,
+        None => return,
+    };
+    assert_eq!(stdout.lines().last(), Some("pool sent"));
+    let contents = contents.lock().unwrap().take().unwrap();
+    assert!(contents.starts_with("wayland-rs\0"));
+    assert_eq!(contents.len(), 4096);
+}
+
+#[test]
+fn c_client_protocol_error() {
+    let output_id = Arc::new(Mutex::new(0));
+    let output_id2 = output_id.clone();
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(3, move |output, _| {
+        let output = output.implement(
+            |req, output| match req {
+                OutputRequest::Release => output.post_error(42, "injected error".into()),
+            },
+            None::<fn(_)>,
+            (),
+        );
+        *output_id2.lock().unwrap() = output.id();
+    });
+
+    let stdout = match run_client(&mut server, "protocol_error") {
+        Some(stdout) => stdout,
+        None => return,
+    };
+    assert_eq!(
+        stdout.lines().last(),
+        Some(&format!("error 42 wl_output {}", *output_id.lock().unwrap())[..])
+    );
+}
@@ -0,0 +1,158 @@
+/*
+ * Loading of libwayland-client for the C test clients
+ *
+ * The library is opened with dlopen() rather than linked, like the `dlopen` feature of
+ * wayland-sys does, so that the clients build without the development files of
+ * libwayland. The few types of its ABI they need are declared here.
+ *
+ * A client exits with status 77 if the library cannot be loaded, the test is then
+ * skipped.
+ */
+
+#include <dlfcn.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define SKIP_STATUS 77
+
+struct wl_display;
+struct wl_proxy;
+
+struct wl_message {
+    const char *name;
+    const char *signature;
+    const struct wl_interface **types;
+};
+
+struct wl_interface {
+    const char *name;
+    int version;
+    int method_count;
+    const struct wl_message *methods;
+    int event_count;
+    const struct wl_message *events;
+};
+
+typedef void (*wl_listener_func)(void);
+
+static struct wl_display *(*wl_display_connect)(const char *name);
+static void (*wl_display_disconnect)(struct wl_display *display);
+static int (*wl_display_roundtrip)(struct wl_display *display);
+static int (*wl_display_get_error)(struct wl_display *display);
+static uint32_t (*wl_display_get_protocol_error)(struct wl_display *display,
+                                                 const struct wl_interface **interface, uint32_t *id);
+static struct wl_proxy *(*wl_proxy_marshal_constructor)(struct wl_proxy *proxy, uint32_t opcode,
+                                                        const struct wl_interface *interface, ...);
+static struct wl_proxy *(*wl_proxy_marshal_constructor_versioned)(struct wl_proxy *proxy, uint32_t opcode,
+                                                                  const struct wl_interface *interface,
+                                                                  uint32_t version, ...);
+static void (*wl_proxy_marshal)(struct wl_proxy *proxy, uint32_t opcode, ...);
+static int (*wl_proxy_add_listener)(struct wl_proxy *proxy, wl_listener_func *implementation, void *data);
+static uint32_t (*wl_proxy_get_id)(struct wl_proxy *proxy);
+
+static const struct wl_interface *wl_registry_interface;
+static const struct wl_interface *wl_output_interface;
+static const struct wl_interface *wl_shm_interface;
+static const struct wl_interface *wl_shm_pool_interface;
+
+static void *load_symbol(void *lib, const char *name) {
+    void *symbol = dlsym(lib, name);
+    if (!symbol) {
+        fprintf(stderr, "missing symbol %s\n", name);
+        exit(SKIP_STATUS);
+    }
+    return symbol;
+}
+
+#define LOAD(lib, name) (*(void **)&name = load_symbol(lib, #name))
+
+static void load_libwayland(void) {
+    void *lib = dlopen("libwayland-client.so.0", RTLD_NOW);
+    if (!lib) {
+        fprintf(stderr, "%s\n", dlerror());
+        exit(SKIP_STATUS);
+    }
+    LOAD(lib, wl_display_connect);
+    LOAD(lib, wl_display_disconnect);
+    LOAD(lib, wl_display_roundtrip);
+    LOAD(lib, wl_display_get_error);
+    LOAD(lib, wl_display_get_protocol_error);
+    LOAD(lib, wl_proxy_marshal_constructor);
+    LOAD(lib, wl_proxy_marshal_constructor_versioned);
+    LOAD(lib, wl_proxy_marshal);
+    LOAD(lib, wl_proxy_add_listener);
+    LOAD(lib, wl_proxy_get_id);
+    LOAD(lib, wl_registry_interface);
+    LOAD(lib, wl_output_interface);
+    LOAD(lib, wl_shm_interface);
+    LOAD(lib, wl_shm_pool_interface);
+}
+
+/* the opcodes of the requests used by the clients */
+#define WL_DISPLAY_GET_REGISTRY 1
+#define WL_REGISTRY_BIND 0
+#define WL_SHM_CREATE_POOL 0
+#define WL_OUTPUT_RELEASE 0
+
+struct registry_listener {
+    void (*global)(void *data, struct wl_proxy *registry, uint32_t name, const char *interface,
+                   uint32_t version);
+    void (*global_remove)(void *data, struct wl_proxy *registry, uint32_t name);
+};
+
+/* the last advertised global of each interface the clients bind */
+struct globals {
+    struct wl_proxy *registry;
+    uint32_t output;
+    uint32_t shm;
+};
+
+static void global(void *data, struct wl_proxy *registry, uint32_t name, const char *interface,
+                   uint32_t version) {
+    struct globals *globals = data;
+    (void)registry;
+    printf("global %s %u\n", interface, version);
+    if (strcmp(interface, "wl_output") == 0) {
+        globals->output = name;
+    } else if (strcmp(interface, "wl_shm") == 0) {
+        globals->shm = name;
+    }
+}
+
+static void global_remove(void *data, struct wl_proxy *registry, uint32_t name) {
+    (void)data;
+    (void)registry;
+    (void)name;
+}
+
+static const struct registry_listener registry_listener = {global, global_remove};
+
+/* connect to the server and list its globals, exits with status 1 on failure */
+static struct wl_display *connect_with_globals(struct globals *globals) {
+    struct wl_display *display;
+    struct wl_proxy *registry;
+
+    load_libwayland();
+    display = wl_display_connect(NULL);
+    if (!display) {
+        fprintf(stderr, "failed to connect to the server\n");
+        exit(1);
+    }
+    registry = wl_proxy_marshal_constructor((struct wl_proxy *)display, WL_DISPLAY_GET_REGISTRY,
+                                            wl_registry_interface, NULL);
+    wl_proxy_add_listener(registry, (wl_listener_func *)&registry_listener, globals);
+    if (wl_display_roundtrip(display) < 0) {
+        fprintf(stderr, "failed to get the globals\n");
+        exit(1);
+    }
+    globals->registry = registry;
+    return display;
+}
+
+static struct wl_proxy *bind_global(struct globals *globals, uint32_t name,
+                                    const struct wl_interface *interface, uint32_t version) {
+    return wl_proxy_marshal_constructor_versioned(globals->registry, WL_REGISTRY_BIND, interface, version,
+                                                  name, interface->name, version, NULL);
+}
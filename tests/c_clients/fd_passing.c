@@ -0,0 +1,38 @@
+/* create a shm pool, passing the fd of a file to the server */
+
+#include <unistd.h>
+
+#include "common.h"
+
+#define CONTENTS "wayland-rs"
+#define POOL_SIZE 4096
+
+int main(void) {
+    struct globals globals = {0};
+    struct wl_display *display = connect_with_globals(&globals);
+    char path[] = "/tmp/wayland-rs-c-client-XXXXXX";
+    struct wl_proxy *shm;
+    int fd;
+
+    if (!globals.shm) {
+        fprintf(stderr, "no wl_shm global\n");
+        return 1;
+    }
+    fd = mkstemp(path);
+    if (fd < 0 || unlink(path) < 0 || write(fd, CONTENTS, strlen(CONTENTS)) < 0 ||
+        ftruncate(fd, POOL_SIZE) < 0) {
+        perror("failed to create the pool file");
+        return 1;
+    }
+    shm = bind_global(&globals, globals.shm, wl_shm_interface, 1);
+    wl_proxy_marshal_constructor(shm, WL_SHM_CREATE_POOL, wl_shm_pool_interface, NULL, fd, POOL_SIZE);
+    /* the fd is duplicated by the library, the server gets its own */
+    close(fd);
+    if (wl_display_roundtrip(display) < 0) {
+        fprintf(stderr, "roundtrip failed\n");
+        return 1;
+    }
+    printf("pool sent\n");
+    wl_display_disconnect(display);
+    return 0;
+}
@@ -0,0 +1,59 @@
+/* list the globals, then bind an output and print the events it receives */
+
+#include "common.h"
+
+static void geometry(void *data, struct wl_proxy *output, int32_t x, int32_t y, int32_t physical_width,
+                     int32_t physical_height, int32_t subpixel, const char *make, const char *model,
+                     int32_t transform) {
+    (void)data;
+    (void)output;
+    (void)subpixel;
+    (void)transform;
+    printf("geometry %d %d %d %d %s %s\n", x, y, physical_width, physical_height, make, model);
+}
+
+static void mode(void *data, struct wl_proxy *output, uint32_t flags, int32_t width, int32_t height,
+                 int32_t refresh) {
+    (void)data;
+    (void)output;
+    printf("mode %u %d %d %d\n", flags, width, height, refresh);
+}
+
+static void done(void *data, struct wl_proxy *output) {
+    (void)data;
+    (void)output;
+    printf("done\n");
+}
+
+static void scale(void *data, struct wl_proxy *output, int32_t factor) {
+    (void)data;
+    (void)output;
+    printf("scale %d\n", factor);
+}
+
+static const struct {
+    void (*geometry)(void *, struct wl_proxy *, int32_t, int32_t, int32_t, int32_t, int32_t, const char *,
+                     const char *, int32_t);
+    void (*mode)(void *, struct wl_proxy *, uint32_t, int32_t, int32_t, int32_t);
+    void (*done)(void *, struct wl_proxy *);
+    void (*scale)(void *, struct wl_proxy *, int32_t);
+} output_listener = {geometry, mode, done, scale};
+
+int main(void) {
+    struct globals globals = {0};
+    struct wl_display *display = connect_with_globals(&globals);
+    struct wl_proxy *output;
+
+    if (!globals.output) {
+        fprintf(stderr, "no wl_output global\n");
+        return 1;
+    }
+    output = bind_global(&globals, globals.output, wl_output_interface, 2);
+    wl_proxy_add_listener(output, (wl_listener_func *)&output_listener, NULL);
+    if (wl_display_roundtrip(display) < 0) {
+        fprintf(stderr, "roundtrip failed\n");
+        return 1;
+    }
+    wl_display_disconnect(display);
+    return 0;
+}
@@ -0,0 +1,32 @@
+/* send a request the server answers with a protocol error, and print the error */
+
+#include <errno.h>
+
+#include "common.h"
+
+int main(void) {
+    struct globals globals = {0};
+    struct wl_display *display = connect_with_globals(&globals);
+    const struct wl_interface *interface = NULL;
+    struct wl_proxy *output;
+    uint32_t code, id = 0;
+
+    if (!globals.output) {
+        fprintf(stderr, "no wl_output global\n");
+        return 1;
+    }
+    output = bind_global(&globals, globals.output, wl_output_interface, 3);
+    wl_proxy_marshal(output, WL_OUTPUT_RELEASE);
+    if (wl_display_roundtrip(display) >= 0) {
+        fprintf(stderr, "the roundtrip succeeded\n");
+        return 1;
+    }
+    if (wl_display_get_error(display) != EPROTO) {
+        fprintf(stderr, "not a protocol error: %d\n", wl_display_get_error(display));
+        return 1;
+    }
+    code = wl_display_get_protocol_error(display, &interface, &id);
+    printf("error %u %s %u\n", code, interface ? interface->name : "(none)", id);
+    wl_display_disconnect(display);
+    return 0;
+}
@@ -0,0 +1,154 @@
+// The async dispatching requires rust 1.36, and is thus behind its own feature
+#![cfg(feature = "async")]
+
+extern crate nix;
+
+mod helpers;
+
+use helpers::{roundtrip, TestClient, TestServer};
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use helpers::wayc::protocol::wl_display::RequestsTrait;
+
+// a minimal reactor for the tests: the connection is readable once
+// `poll()` says so, and the executor polls its future in a loop
+mod reactor {
+    use std::future::Future;
+    use std::io;
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::pin::Pin;
+    use std::ptr;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    use nix::poll::{poll, EventFlags, PollFd};
+
+    pub struct Readable(pub RawFd);
+
+    impl Future for Readable {
+        type Output = io::Result<()>;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            let mut fds = [PollFd::new(self.0, EventFlags::POLLIN)];
+            match poll(&mut fds, 0) {
+                Ok(0) => Poll::Pending,
+                Ok(_) => Poll::Ready(Ok(())),
+                Err(e) => Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, e))),
+            }
+        }
+    }
+
+    pub fn readable<F: AsRawFd>(fd: &F) -> impl FnMut() -> Readable {
+        let fd = fd.as_raw_fd();
+        move || Readable(fd)
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(clone(ptr::null())) }
+    }
+
+    pub fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        let waker = noop_waker();
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    // poll the future until completion, running `between` after each pending poll
+    pub fn block_on<F: Future + Unpin, B: FnMut()>(mut future: F, mut between: B) -> F::Output {
+        loop {
+            if let Poll::Ready(output) = poll_once(&mut future) {
+                return output;
+            }
+            between();
+        }
+    }
+}
+
+#[test]
+fn client_dispatch_async() {
+    let mut server = TestServer::new();
+    let mut client = TestClient::new(&server.socket_name);
+
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    let token = client.event_queue.get_token();
+    client
+        .display
+        .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+        .unwrap();
+
+    // the future flushes the request itself
+    let readable = reactor::readable(&client.event_queue.connection_fd());
+    let future = client.event_queue.dispatch_async(readable);
+    let dispatched = reactor::block_on(future, || server.answer()).unwrap();
+    assert!(dispatched > 0);
+    assert!(done.get());
+}
+
+#[test]
+fn client_dispatch_async_cancel() {
+    let mut server = TestServer::new();
+    let mut client = TestClient::new(&server.socket_name);
+
+    {
+        // the server does not answer, the future waits for the connection
+        let readable = reactor::readable(&client.event_queue.connection_fd());
+        let mut future = client.event_queue.dispatch_async(readable);
+        assert!(reactor::poll_once(&mut future).is_pending());
+        assert!(reactor::poll_once(&mut future).is_pending());
+    }
+
+    // the read intention of the dropped future did not keep the connection from being read
+    roundtrip(&mut client, &mut server).unwrap();
+}
+
+#[test]
+fn client_dispatch_async_available_data() {
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    // a reactor which lost the readiness of the connection
+    struct Never;
+
+    impl Future for Never {
+        type Output = io::Result<()>;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+    }
+
+    let mut server = TestServer::new();
+    let mut client = TestClient::new(&server.socket_name);
+
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    let token = client.event_queue.get_token();
+    client
+        .display
+        .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+        .unwrap();
+    client.display.flush().unwrap();
+    server.answer();
+
+    // the answer is already in the socket, it is read without waiting on the reactor
+    let waits = Rc::new(Cell::new(0));
+    let waits2 = waits.clone();
+    let mut future = client.event_queue.dispatch_async(move || {
+        waits2.set(waits2.get() + 1);
+        Never
+    });
+    match reactor::poll_once(&mut future) {
+        Poll::Ready(Ok(n)) => assert!(n > 0),
+        _ => panic!("The available events were not dispatched."),
+    }
+    assert_eq!(waits.get(), 0);
+    assert!(done.get());
+}
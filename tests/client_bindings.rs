@@ -0,0 +1,188 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ways::protocol::wl_compositor::WlCompositor as ServerCompositor;
+use ways::protocol::wl_output::WlOutput as ServerOutput;
+
+use wayc::bindings::{self, ForeignArgument, ForeignProxy};
+use wayc::protocol::wl_compositor;
+use wayc::protocol::wl_output;
+
+static OUTPUT_DESTROYED: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" fn output_handler(
+    data: *mut c_void,
+    _proxy: *const ForeignProxy,
+    opcode: u32,
+    args: *const ForeignArgument,
+    nargs: usize,
+) {
+    let events = &*(data as *const RefCell<Vec<String>>);
+    let args = ::std::slice::from_raw_parts(args, nargs);
+    match opcode {
+        0 => {
+            let make = CStr::from_ptr(args[5].data as *const c_char);
+            events
+                .borrow_mut()
+                .push(format!("geometry {} {}", make.to_str().unwrap(), args[0].int));
+        }
+        1 => events
+            .borrow_mut()
+            .push(format!("mode {} {}x{}", args[0].uint, args[1].int, args[2].int)),
+        _ => events.borrow_mut().push(format!("event {}", opcode)),
+    }
+}
+
+unsafe extern "C" fn output_destroy(data: *mut c_void) {
+    let _ = Box::from_raw(data as *mut RefCell<Vec<String>>);
+    OUTPUT_DESTROYED.store(true, Ordering::SeqCst);
+}
+
+#[test]
+fn foreign_events() {
+    use wayc::protocol::wl_output::RequestsTrait;
+    use ways::protocol::wl_output::{Event, Mode, Subpixel, Transform};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(3, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(Event::Geometry {
+            x: 42,
+            y: 0,
+            physical_width: 0,
+            physical_height: 0,
+            subpixel: Subpixel::Unknown,
+            make: "Foreign".into(),
+            model: "Output".into(),
+            transform: Transform::Normal,
+        });
+        output.send(Event::Mode {
+            flags: Mode::Current,
+            width: 1920,
+            height: 1080,
+            refresh: 60000,
+        });
+        output.send(Event::Done);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let events = Box::into_raw(Box::new(RefCell::new(Vec::<String>::new())));
+    let token = client.event_queue.get_token();
+    let output = manager
+        .instantiate_exact::<wl_output::WlOutput, _>(3, |newp| unsafe {
+            bindings::implement_foreign(
+                newp,
+                output_handler,
+                events as *mut c_void,
+                Some(output_destroy),
+                &token,
+            )
+        }).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(
+        unsafe { &*(*events).as_ptr() },
+        &[
+            "geometry Foreign 42".to_owned(),
+            "mode 1 1920x1080".to_owned(),
+            "event 2".to_owned()
+        ]
+    );
+
+    output.release();
+    drop(output);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(OUTPUT_DESTROYED.load(Ordering::SeqCst));
+}
+
+#[test]
+fn foreign_requests() {
+    use wayc::protocol::wl_compositor::RequestsTrait;
+    use ways::protocol::wl_surface;
+
+    let mut server = TestServer::new();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let server_requests = requests.clone();
+    server.display.create_global::<ServerCompositor, _>(1, move |compositor, _| {
+        let requests = server_requests.clone();
+        compositor.implement(
+            move |req, _| {
+                if let ways::protocol::wl_compositor::Request::CreateSurface { id } = req {
+                    let requests = requests.clone();
+                    id.implement(
+                        move |req, _| match req {
+                            wl_surface::Request::Damage { x, y, width, height } => requests
+                                .lock()
+                                .unwrap()
+                                .push(format!("damage {} {} {} {}", x, y, width, height)),
+                            wl_surface::Request::Destroy => {
+                                requests.lock().unwrap().push("destroy".to_owned())
+                            }
+                            _ => {}
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                }
+            },
+            None::<fn(_)>,
+            (),
+        );
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_auto::<wl_compositor::WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let handle = bindings::foreign_proxy(&surface);
+    unsafe {
+        assert_eq!(bindings::wlrs_proxy_id(handle), surface.id());
+        let mut args = [ForeignArgument {
+            int: 0,
+            uint: 0,
+            fd: -1,
+            data: ::std::ptr::null(),
+            len: 0,
+        }; 4];
+        for (i, arg) in args.iter_mut().enumerate() {
+            arg.int = i as i32 + 1;
+        }
+        // wl_surface.damage
+        assert_eq!(bindings::wlrs_proxy_send(handle, 2, args.as_ptr(), 4), 0);
+        // wrong number of arguments
+        assert_eq!(bindings::wlrs_proxy_send(handle, 2, args.as_ptr(), 2), -1);
+        // wl_surface.frame creates an object
+        assert_eq!(bindings::wlrs_proxy_send(handle, 3, args.as_ptr(), 1), -1);
+        // wl_surface.destroy
+        assert_eq!(bindings::wlrs_proxy_send(handle, 0, ::std::ptr::null(), 0), 0);
+        bindings::wlrs_proxy_release(handle);
+    }
+    assert!(!surface.is_alive());
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(
+        &*requests.lock().unwrap(),
+        &["damage 1 2 3 4".to_owned(), "destroy".to_owned()]
+    );
+}
@@ -0,0 +1,36 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::wl_output::WlOutput as ServerOutput;
+
+use std::path::PathBuf;
+
+fn main() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(1, |_, _| {});
+
+    let mut socket_path: PathBuf = ::std::env::var_os("XDG_RUNTIME_DIR").unwrap().into();
+    socket_path.push(&server.socket_name);
+
+    // the path is used as-is
+    ::std::env::remove_var("XDG_RUNTIME_DIR");
+
+    assert!(match wayc::Display::connect_to_socket(socket_path.with_extension("none")) {
+        Err(wayc::ConnectError::NoCompositorListening) => true,
+        _ => false,
+    });
+
+    let (display, event_queue) = wayc::Display::connect_to_socket(&socket_path).unwrap();
+    let mut client = TestClient {
+        display,
+        event_queue,
+    };
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+    // check that we connected to the right compositor
+    let globals = manager.list();
+    assert!(globals.len() == 1);
+    assert_eq!(globals[0], (1, "wl_output".into(), 1));
+}
@@ -0,0 +1,136 @@
+extern crate wayland_client as wayc;
+
+use wayc::coords::{Buffer, InvalidState, Output, Point, Rect, Size, Surface, SurfaceSpaces, Window};
+use wayc::protocol::wl_output::Transform;
+
+fn assert_close(a: (f64, f64), b: (f64, f64)) {
+    assert!(
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9,
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+fn xy<S: wayc::coords::Space>(point: Point<S>) -> (f64, f64) {
+    (point.x, point.y)
+}
+
+#[test]
+fn transformed_buffer() {
+    // a 100x50 surface, rendered rotated into a 100x200 buffer with a scale of 2
+    let mut spaces = SurfaceSpaces::new(100, 200).unwrap();
+    spaces.set_buffer_transform(Transform::_90);
+    spaces.set_buffer_scale(2).unwrap();
+    assert_eq!(spaces.surface_size(), Size::new(100.0, 50.0));
+
+    let origin: Point<Buffer> = spaces.convert_point(Point::<Surface>::new(0.0, 0.0)).unwrap();
+    assert_close(xy(origin), (0.0, 200.0));
+    let back: Point<Surface> = spaces.convert_point(origin).unwrap();
+    assert_close(xy(back), (0.0, 0.0));
+
+    // the damage of the whole surface is the whole buffer
+    let damage: Rect<Buffer> = spaces.convert_rect(spaces.surface_rect()).unwrap();
+    assert_eq!(damage, Rect::new(0.0, 0.0, 100.0, 200.0));
+    let size: Size<Buffer> = spaces.convert_size(Size::<Surface>::new(10.0, 20.0)).unwrap();
+    assert_eq!(size, Size::new(40.0, 20.0));
+}
+
+#[test]
+fn viewport() {
+    // the center of a 200x200 buffer, stretched on a 50x100 surface
+    let mut spaces = SurfaceSpaces::new(200, 200).unwrap();
+    spaces.set_viewport_source(Some((50.0, 50.0, 100.0, 100.0))).unwrap();
+    assert_eq!(spaces.surface_size(), Size::new(100.0, 100.0));
+    spaces.set_viewport_destination(Some((50, 100))).unwrap();
+    assert_eq!(spaces.surface_size(), Size::new(50.0, 100.0));
+
+    let corner: Point<Buffer> = spaces.convert_point(Point::<Surface>::new(50.0, 100.0)).unwrap();
+    assert_close(xy(corner), (150.0, 150.0));
+    let center: Point<Surface> = spaces.convert_point(Point::<Buffer>::new(100.0, 100.0)).unwrap();
+    assert_close(xy(center), (25.0, 50.0));
+
+    // without a source, the whole buffer is stretched
+    spaces.set_viewport_source(None).unwrap();
+    let corner: Point<Buffer> = spaces.convert_point(Point::<Surface>::new(50.0, 100.0)).unwrap();
+    assert_close(xy(corner), (200.0, 200.0));
+}
+
+#[test]
+fn window_geometry_and_output() {
+    // a 120x120 surface, with a 10 pixels shadow around its window
+    let mut spaces = SurfaceSpaces::new(240, 240).unwrap();
+    spaces.set_buffer_scale(2).unwrap();
+    assert_eq!(spaces.window_geometry(), Rect::new(0.0, 0.0, 120.0, 120.0));
+    spaces.set_window_geometry(Some((10, 10, 100, 100)));
+    spaces.set_output_location(Point::new(300.0, 200.0));
+
+    // an anchor rectangle relative to the window geometry
+    let anchor: Rect<Surface> = spaces.convert_rect(Rect::<Window>::new(0.0, 90.0, 20.0, 10.0)).unwrap();
+    assert_eq!(anchor, Rect::new(10.0, 100.0, 20.0, 10.0));
+
+    let pointer: Point<Surface> = spaces.convert_point(Point::<Output>::new(305.0, 205.0)).unwrap();
+    assert_close(xy(pointer), (5.0, 5.0));
+    // on the shadow of the surface, out of the window
+    assert!(spaces.surface_rect().contains(pointer));
+    assert!(!spaces.window_geometry().contains(pointer));
+    let window: Point<Window> = spaces.convert_point(pointer).unwrap();
+    assert_close(xy(window), (-5.0, -5.0));
+    let buffer: Point<Buffer> = spaces.convert_point(Point::<Output>::new(305.0, 205.0)).unwrap();
+    assert_close(xy(buffer), (10.0, 10.0));
+
+    // the geometry is kept when the buffer changes, unless it is reset
+    spaces.set_buffer_size(400, 400).unwrap();
+    assert_eq!(spaces.window_geometry(), Rect::new(10.0, 10.0, 100.0, 100.0));
+    spaces.set_window_geometry(None);
+    assert_eq!(spaces.window_geometry(), Rect::new(0.0, 0.0, 200.0, 200.0));
+}
+
+#[test]
+fn rect_edges() {
+    let rect = Rect::<Surface>::new(0.0, 0.0, 10.0, 10.0);
+    assert!(rect.contains(Point::new(0.0, 0.0)));
+    assert!(rect.contains(Point::new(9.5, 9.5)));
+    assert!(!rect.contains(Point::new(10.0, 5.0)));
+    assert!(!rect.contains(Point::new(5.0, -0.5)));
+}
+
+#[test]
+fn invalid_state() {
+    // no buffer is attached to the surface yet
+    assert_eq!(SurfaceSpaces::new(0, 0).err(), Some(InvalidState::BufferSize(0, 0)));
+
+    // the invalid values sent by a compositor are rejected, and leave the spaces unchanged
+    let mut spaces = SurfaceSpaces::new(100, 100).unwrap();
+    let previous = spaces.clone();
+    assert_eq!(spaces.set_buffer_size(100, -1), Err(InvalidState::BufferSize(100, -1)));
+    assert_eq!(spaces.set_buffer_scale(0), Err(InvalidState::BufferScale(0)));
+    assert_eq!(
+        spaces.set_viewport_source(Some((0.0, 0.0, 0.0, 10.0))),
+        Err(InvalidState::ViewportSource(0.0, 10.0))
+    );
+    assert_eq!(
+        spaces.set_viewport_destination(Some((0, 0))),
+        Err(InvalidState::ViewportDestination(0, 0))
+    );
+    assert_eq!(spaces, previous);
+}
+
+#[test]
+fn unrepresentable_conversion() {
+    let mut spaces = SurfaceSpaces::new(100, 100).unwrap();
+    // a tiny part of the buffer, stretched on the whole surface
+    spaces.set_viewport_source(Some((0.0, 0.0, 1e-305, 1e-305))).unwrap();
+    spaces.set_viewport_destination(Some((100_000, 100_000))).unwrap();
+    assert!(spaces.matrix::<Buffer, Surface>().is_none());
+    assert!(
+        spaces
+            .convert_point::<Surface, Buffer>(Point::new(1.0, 1.0))
+            .is_none()
+    );
+    // the other spaces are not affected
+    let window: Point<Window> = spaces
+        .convert_point(Point::<Surface>::new(1.0, 1.0))
+        .unwrap();
+    assert_close(xy(window), (1.0, 1.0));
+}
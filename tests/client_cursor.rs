@@ -0,0 +1,120 @@
+// the cursor module wraps libwayland-cursor.so, which is only usable with native_lib
+#![cfg(feature = "native_lib")]
+
+extern crate nix;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::sync::{Arc, Mutex};
+
+use wayc::cursor::{self, CursorThemeCache};
+use wayc::protocol::wl_shm::WlShm;
+use wayc::Proxy;
+
+// a wl_shm global counting the buffers created by its clients
+fn insert_shm(server: &mut TestServer) -> Arc<Mutex<usize>> {
+    use ways::protocol::{wl_shm, wl_shm_pool};
+
+    let buffers = Arc::new(Mutex::new(0));
+    let shm_buffers = buffers.clone();
+    server
+        .display
+        .create_global::<wl_shm::WlShm, _>(1, move |shm, _| {
+            let shm_buffers = shm_buffers.clone();
+            shm.implement(
+                move |req, _| {
+                    let wl_shm::Request::CreatePool { id, fd, .. } = req;
+                    let _ = ::nix::unistd::close(fd);
+                    let pool_buffers = shm_buffers.clone();
+                    id.implement(
+                        move |req, _| {
+                            if let wl_shm_pool::Request::CreateBuffer { id, .. } = req {
+                                id.implement(|_, _| {}, None::<fn(_)>, ());
+                                *pool_buffers.lock().unwrap() += 1;
+                            }
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    buffers
+}
+
+fn bind_shm(client: &mut TestClient, server: &mut TestServer) -> Proxy<WlShm> {
+    let manager = wayc::GlobalManager::new(&client.display);
+    roundtrip(client, server).unwrap();
+    manager
+        .instantiate_exact::<WlShm, _>(1, |shm| shm.implement(|_, _| {}, ()))
+        .unwrap()
+}
+
+#[test]
+fn cursor_theme_cache() {
+    if !cursor::is_available() {
+        // libwayland-cursor.so is not installed
+        return;
+    }
+
+    let mut server = TestServer::new();
+    insert_shm(&mut server);
+    let mut client = TestClient::new(&server.socket_name);
+    let shm = bind_shm(&mut client, &mut server);
+    let other_shm = bind_shm(&mut client, &mut server);
+
+    let cache = CursorThemeCache::new();
+    let theme = cache.load(None, 24, &shm);
+    // the theme is shared while it is in use
+    assert!(Arc::ptr_eq(&theme, &cache.load(None, 24, &shm)));
+    // but not for an other size, name or wl_shm
+    assert!(!Arc::ptr_eq(&theme, &cache.load(None, 32, &shm)));
+    assert!(!Arc::ptr_eq(&theme, &cache.load(Some("wayland-rs-test"), 24, &shm)));
+    assert!(!Arc::ptr_eq(&theme, &cache.load(None, 24, &other_shm)));
+
+    // the cache does not keep the themes alive
+    let weak = Arc::downgrade(&theme);
+    drop(theme);
+    assert!(weak.upgrade().is_none());
+    let theme = cache.load(None, 24, &shm);
+    assert!(Arc::ptr_eq(&theme, &cache.load(None, 24, &shm)));
+}
+
+#[test]
+fn cursor_theme_threads() {
+    if !cursor::is_available() {
+        // libwayland-cursor.so is not installed
+        return;
+    }
+
+    let mut server = TestServer::new();
+    let buffers = insert_shm(&mut server);
+    let mut client = TestClient::new(&server.socket_name);
+    let shm = bind_shm(&mut client, &mut server);
+
+    // the default theme of libwayland-cursor.so is used if no theme is installed
+    let theme = Arc::new(cursor::load_theme(Some("wayland-rs-test"), 24, &shm));
+    let threads = (0..4)
+        .map(|_| {
+            let theme = theme.clone();
+            ::std::thread::spawn(move || {
+                let cursor = theme.get_cursor("left_ptr").unwrap();
+                assert!(cursor.image_count() > 0);
+                assert!(cursor.frame_buffer(0).is_some());
+                cursor.frame_info(0).unwrap()
+            })
+        }).collect::<Vec<_>>();
+    let infos = threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect::<Vec<_>>();
+    assert!(infos.iter().all(|info| *info == infos[0]));
+
+    // the buffer of the frame was created once, and shared by the threads
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(*buffers.lock().unwrap(), 1);
+}
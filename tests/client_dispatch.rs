@@ -1,6 +1,8 @@
+extern crate nix;
+
 mod helpers;
 
-use helpers::{wayc, ways, TestClient};
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
 
 use std::cell::Cell;
 use std::ffi::OsStr;
@@ -89,3 +91,890 @@ fn client_dispatch() {
 
     server_thread.join().unwrap();
 }
+
+#[test]
+fn client_inject_events() {
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+
+    use self::wayc::protocol::wl_keyboard::{self, WlKeyboard};
+    use self::wayc::protocol::wl_pointer::{self, RequestsTrait as PointerRequests, WlPointer};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::wayc::protocol::wl_seat::{RequestsTrait as SeatRequests, WlSeat};
+
+    // no server on the other side of this connection, it is never flushed
+    let (client_socket, _server_socket) = UnixStream::pair().unwrap();
+    let mut client = unsafe { TestClient::from_fd(client_socket.into_raw_fd()) };
+
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let seat = registry
+        .bind::<WlSeat, _>(5, 1, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let motions = Rc::new(Cell::new(0));
+    let implem_motions = motions.clone();
+    let pointer = seat
+        .get_pointer(|newp| unsafe {
+            newp.implement_nonsend(
+                move |event, _| {
+                    if let wl_pointer::Event::Motion { surface_x, .. } = event {
+                        assert_eq!(surface_x, 4.0);
+                        implem_motions.set(implem_motions.get() + 1);
+                    }
+                },
+                (),
+                &client.event_queue.get_token(),
+            )
+        }).unwrap();
+
+    for time in 0..3 {
+        client
+            .event_queue
+            .inject_event::<WlPointer>(
+                &pointer,
+                wl_pointer::Event::Motion {
+                    time,
+                    surface_x: 4.0,
+                    surface_y: 2.0,
+                },
+            ).unwrap();
+    }
+    assert!(client.event_queue.prepare_read().is_none());
+    assert_eq!(client.event_queue.dispatch_pending().unwrap(), 3);
+    assert_eq!(motions.get(), 3);
+    assert_eq!(client.event_queue.dispatch_pending().unwrap(), 0);
+
+    // injecting to an object of an other queue fails
+    let other_queue = client.display.create_event_queue();
+    let keyboard = seat.get_keyboard(|newp| newp.implement(|_, _| {}, ())).unwrap();
+    let other_keyboard = seat
+        .get_keyboard(|newp| unsafe { newp.implement_nonsend(|_, _| {}, (), &other_queue.get_token()) })
+        .unwrap();
+    let repeat = || wl_keyboard::Event::RepeatInfo { rate: 25, delay: 600 };
+    assert!(other_queue.inject_event::<WlKeyboard>(&keyboard, repeat()).is_err());
+    assert!(client.event_queue.inject_event::<WlKeyboard>(&other_keyboard, repeat()).is_err());
+    assert!(other_queue.inject_event::<WlKeyboard>(&other_keyboard, repeat()).is_ok());
+
+    // as does injecting to a dead object
+    pointer.release();
+    let motion = wl_pointer::Event::Motion {
+        time: 0,
+        surface_x: 4.0,
+        surface_y: 2.0,
+    };
+    assert!(client.event_queue.inject_event::<WlPointer>(&pointer, motion).is_err());
+    assert_eq!(client.event_queue.dispatch_pending().unwrap(), 0);
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn client_dispatch_threads() {
+    use std::thread::{self, ThreadId};
+
+    use self::wayc::protocol::wl_output::{self, WlOutput};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        for factor in 0..50 {
+            output.send(ServerEvent::Scale { factor });
+        }
+        output.send(ServerEvent::Done);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    client.event_queue.set_dispatch_threads(3);
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let events: Arc<Mutex<Vec<(usize, i32, ThreadId)>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut outputs = Vec::new();
+    for output in 0..4 {
+        let events = events.clone();
+        let output = registry
+            .bind::<WlOutput, _>(2, 1, move |newp| {
+                newp.implement(
+                    move |event, _| {
+                        if let wl_output::Event::Scale { factor } = event {
+                            events.lock().unwrap().push((output, factor, thread::current().id()));
+                        }
+                    },
+                    (),
+                )
+            }).unwrap();
+        outputs.push(output);
+    }
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // all the events were processed once the dispatching returned
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 4 * 50);
+    // in order for each object
+    for output in 0..4 {
+        let factors = events
+            .iter()
+            .filter(|&&(o, _, _)| o == output)
+            .map(|&(_, factor, _)| factor)
+            .collect::<Vec<_>>();
+        assert_eq!(factors, (0..50).collect::<Vec<_>>());
+    }
+    // by the threads of the pool
+    let main_thread = thread::current().id();
+    assert!(events.iter().all(|&(_, _, thread)| thread != main_thread));
+}
+
+#[test]
+fn client_calloop_source() {
+    use self::wayc::protocol::wl_output::{self, WlOutput};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        // more than what is read from the socket at once
+        for factor in 0..2000 {
+            output.send(ServerEvent::Scale { factor });
+        }
+        output.send(ServerEvent::Done);
+    });
+
+    let TestClient { display, event_queue } = TestClient::new(&server.socket_name);
+    let registry = display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let scales = Arc::new(Mutex::new(0));
+    let output_scales = scales.clone();
+    let _output = registry
+        .bind::<WlOutput, _>(2, 1, move |newp| {
+            newp.implement(
+                move |event, _| {
+                    if let wl_output::Event::Scale { .. } = event {
+                        *output_scales.lock().unwrap() += 1;
+                    }
+                },
+                (),
+            )
+        }).unwrap();
+    display.flush().unwrap();
+    server.answer();
+
+    let mut event_loop = ways::calloop::EventLoop::<()>::new().unwrap();
+    let _source = event_loop
+        .handle()
+        .insert_source(event_queue, |e, &mut ()| panic!("Dispatching failed: {}", e))
+        .unwrap();
+    // a single readiness of the connection is enough to dispatch all the events
+    event_loop
+        .dispatch(Some(Duration::from_millis(100)), &mut ())
+        .unwrap();
+    assert_eq!(*scales.lock().unwrap(), 2000);
+}
+
+#[test]
+fn client_calloop_source_error() {
+    let server = TestServer::new();
+    let TestClient { display, event_queue } = TestClient::new(&server.socket_name);
+    display.flush().unwrap();
+
+    let errors = Rc::new(Cell::new(0));
+    let errors2 = errors.clone();
+    let mut event_loop = ways::calloop::EventLoop::<()>::new().unwrap();
+    let _source = event_loop
+        .handle()
+        .insert_source(event_queue, move |_, &mut ()| errors2.set(errors2.get() + 1))
+        .unwrap();
+
+    // the server goes away, reading the connection fails
+    drop(server);
+    event_loop
+        .dispatch(Some(Duration::from_millis(100)), &mut ())
+        .unwrap();
+    assert_eq!(errors.get(), 1);
+}
+
+#[test]
+fn client_claim_interface() {
+    use self::wayc::protocol::wl_output::{self, WlOutput};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(ServerEvent::Scale { factor: 2 });
+        output.send(ServerEvent::Done);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let claimed = Rc::new(Cell::new(0));
+    let claimed2 = claimed.clone();
+    client
+        .event_queue
+        .claim_interface::<WlOutput, _>(move |event, output| {
+            if let wl_output::Event::Scale { factor } = event {
+                assert_eq!(factor, 2);
+                assert!(output.is_alive());
+                claimed2.set(claimed2.get() + 1);
+            }
+        });
+
+    let own = Arc::new(Mutex::new(0));
+    let bind_output = |own: &Arc<Mutex<u32>>| {
+        let own = own.clone();
+        registry
+            .bind::<WlOutput, _>(2, 1, move |newp| {
+                newp.implement(
+                    move |event, _| {
+                        if let wl_output::Event::Scale { .. } = event {
+                            *own.lock().unwrap() += 1;
+                        }
+                    },
+                    (),
+                )
+            }).unwrap()
+    };
+
+    // the claim takes precedence over the implementations of the proxies
+    let _first = bind_output(&own);
+    let _second = bind_output(&own);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(claimed.get(), 2);
+    assert_eq!(*own.lock().unwrap(), 0);
+
+    // once released, the events go back to the implementations of the proxies
+    assert!(client.event_queue.release_interface::<WlOutput>());
+    assert!(!client.event_queue.release_interface::<WlOutput>());
+    let _third = bind_output(&own);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(claimed.get(), 2);
+    assert_eq!(*own.lock().unwrap(), 1);
+}
+
+#[test]
+fn client_scoped_claim() {
+    use self::wayc::protocol::wl_output::{self, WlOutput};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(ServerEvent::Scale { factor: 2 });
+        output.send(ServerEvent::Done);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let claimed = Rc::new(Cell::new(0));
+    let own = Arc::new(Mutex::new(0));
+    let bind_output = |own: &Arc<Mutex<u32>>| {
+        let own = own.clone();
+        registry
+            .bind::<WlOutput, _>(2, 1, move |newp| {
+                newp.implement(
+                    move |event, _| {
+                        if let wl_output::Event::Scale { .. } = event {
+                            *own.lock().unwrap() += 1;
+                        }
+                    },
+                    (),
+                )
+            }).unwrap()
+    };
+    let register = |client: &mut TestClient| {
+        let claimed = claimed.clone();
+        client.event_queue.register::<WlOutput, _>(move |event, _| {
+            if let wl_output::Event::Scale { .. } = event {
+                claimed.set(claimed.get() + 1);
+            }
+        })
+    };
+
+    // the events are claimed while the registration is alive
+    let registration = register(&mut client);
+    assert_eq!(registration.interface(), "wl_output");
+    let output = bind_output(&own);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(claimed.get(), 1);
+    assert_eq!(*own.lock().unwrap(), 0);
+
+    // dropping it gives them back to the implementations of the proxies, which outlive it
+    drop(registration);
+    assert!(!client.event_queue.release_interface::<WlOutput>());
+    let _second = bind_output(&own);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(output.is_alive());
+    assert_eq!(claimed.get(), 1);
+    assert_eq!(*own.lock().unwrap(), 1);
+
+    // a registration replaced by a newer claim does not release it
+    let stale = register(&mut client);
+    let _current = register(&mut client);
+    drop(stale);
+    let _third = bind_output(&own);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(claimed.get(), 2);
+    assert_eq!(*own.lock().unwrap(), 1);
+}
+
+// libwayland aborts if an implementation panics
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn client_claim_panic() {
+    use self::wayc::protocol::wl_output::{self, WlOutput};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::wayc::protocol::wl_seat::WlSeat;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(ServerEvent::Scale { factor: 2 });
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let alive = Rc::new(());
+    let alive2 = alive.clone();
+    client
+        .event_queue
+        .claim_interface::<WlSeat, _>(move |_, _| {
+            let _ = &alive2;
+        });
+    client
+        .event_queue
+        .claim_interface::<WlOutput, _>(|event, _| {
+            if let wl_output::Event::Scale { .. } = event {
+                panic!("Claim panicked.");
+            }
+        });
+    let _output = registry
+        .bind::<WlOutput, _>(2, 1, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    client.display.flush().unwrap();
+    server.answer();
+    let event_queue = &mut client.event_queue;
+    let dispatched = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| event_queue.dispatch()));
+    assert!(dispatched.is_err());
+
+    // the claims are released by the panic, and dropped with their event queue
+    drop(client);
+    assert_eq!(Rc::strong_count(&alive), 1);
+}
+
+#[test]
+fn client_dedup_events() {
+    use self::wayc::protocol::wl_output::{self, RequestsTrait as OutputRequests, WlOutput};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    let outputs = Arc::new(Mutex::new(Vec::new()));
+    let server_outputs = outputs.clone();
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(3, move |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        server_outputs.lock().unwrap().push(output);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    client.event_queue.dedup_events::<WlOutput>("done");
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let bind_output = |events: &Arc<Mutex<Vec<String>>>| {
+        let events = events.clone();
+        registry
+            .bind::<WlOutput, _>(3, 1, move |newp| {
+                newp.implement(
+                    move |event, output: wayc::Proxy<WlOutput>| {
+                        events.lock().unwrap().push(match event {
+                            wl_output::Event::Scale { factor } => format!("{} scale {}", output.id(), factor),
+                            wl_output::Event::Done => format!("{} done", output.id()),
+                            _ => format!("{} other", output.id()),
+                        })
+                    },
+                    (),
+                )
+            }).unwrap()
+    };
+    let first = bind_output(&events);
+    roundtrip(&mut client, &mut server).unwrap();
+    let first_id = first.id();
+
+    let send_state = |outputs: &Arc<Mutex<Vec<ways::Resource<ServerOutput>>>>, factor| {
+        for output in outputs.lock().unwrap().iter() {
+            output.send(ServerEvent::Scale { factor });
+            output.send(ServerEvent::Done);
+        }
+    };
+
+    // the first state is delivered, as well as its changes, but not its repetitions
+    for &factor in &[2, 2, 3, 3, 2] {
+        send_state(&outputs, factor);
+        roundtrip(&mut client, &mut server).unwrap();
+    }
+    let expected = ["scale 2", "done", "scale 3", "done", "scale 2", "done"];
+    assert_eq!(
+        *events.lock().unwrap(),
+        expected
+            .iter()
+            .map(|event| format!("{} {}", first_id, event))
+            .collect::<Vec<_>>()
+    );
+
+    // a new object, which may reuse the id of a destroyed one, does not inherit its state
+    first.release();
+    roundtrip(&mut client, &mut server).unwrap();
+    outputs.lock().unwrap().clear();
+    events.lock().unwrap().clear();
+    let second = bind_output(&events);
+    roundtrip(&mut client, &mut server).unwrap();
+    send_state(&outputs, 2);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        [format!("{} scale 2", second.id()), format!("{} done", second.id())]
+    );
+
+    // once stopped, the repetitions are delivered
+    assert!(client.event_queue.stop_dedup_events::<WlOutput>());
+    assert!(!client.event_queue.stop_dedup_events::<WlOutput>());
+    send_state(&outputs, 2);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(events.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn client_flush_policy() {
+    use std::io::Read;
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+
+    use self::wayc::FlushPolicy;
+
+    fn pending_bytes(socket: &mut UnixStream) -> usize {
+        let mut buffer = [0u8; 512];
+        match socket.read(&mut buffer) {
+            Ok(n) => n,
+            Err(ref e) if e.kind() == ::std::io::ErrorKind::WouldBlock => 0,
+            Err(e) => panic!("Failed to read the socket: {}", e),
+        }
+    }
+
+    let (client_socket, mut server_socket) = UnixStream::pair().unwrap();
+    server_socket.set_nonblocking(true).unwrap();
+    let mut client = unsafe { TestClient::from_fd(client_socket.into_raw_fd()) };
+    assert_eq!(client.display.flush_policy(), FlushPolicy::Manual);
+
+    // by default, dispatching does not send the requests
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    client.event_queue.dispatch_pending().unwrap();
+    assert_eq!(pending_bytes(&mut server_socket), 0);
+    client.display.flush().unwrap();
+    assert!(pending_bytes(&mut server_socket) > 0);
+
+    // they are sent at the end of the dispatching
+    client.display.set_flush_policy(FlushPolicy::OnDispatch);
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    assert_eq!(pending_bytes(&mut server_socket), 0);
+    client.event_queue.dispatch_pending().unwrap();
+    assert!(pending_bytes(&mut server_socket) > 0);
+
+    // or by the flushing thread
+    client
+        .display
+        .set_flush_policy(FlushPolicy::Deadline(Duration::from_millis(10)));
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    ::std::thread::sleep(Duration::from_millis(100));
+    assert!(pending_bytes(&mut server_socket) > 0);
+}
+
+#[test]
+fn client_cork() {
+    use std::io::Read;
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+
+    use self::wayc::FlushPolicy;
+
+    fn pending_bytes(socket: &mut UnixStream) -> usize {
+        let mut buffer = [0u8; 512];
+        match socket.read(&mut buffer) {
+            Ok(n) => n,
+            Err(ref e) if e.kind() == ::std::io::ErrorKind::WouldBlock => 0,
+            Err(e) => panic!("Failed to read the socket: {}", e),
+        }
+    }
+
+    let (client_socket, mut server_socket) = UnixStream::pair().unwrap();
+    server_socket.set_nonblocking(true).unwrap();
+    let mut client = unsafe { TestClient::from_fd(client_socket.into_raw_fd()) };
+    client.display.set_flush_policy(FlushPolicy::OnDispatch);
+
+    // the corked requests are not sent by the dispatching
+    client.display.cork();
+    client.display.cork();
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    client.event_queue.dispatch_pending().unwrap();
+    assert_eq!(pending_bytes(&mut server_socket), 0);
+    client.display.uncork().unwrap();
+    client.event_queue.dispatch_pending().unwrap();
+    assert_eq!(pending_bytes(&mut server_socket), 0);
+
+    // but by the last uncork
+    client.display.uncork().unwrap();
+    assert!(pending_bytes(&mut server_socket) > 0);
+
+    let sent = client
+        .display
+        .with_batch(|| {
+            for _ in 0..3 {
+                client
+                    .display
+                    .get_registry(|newp| newp.implement(|_, _| {}, ()))
+                    .unwrap();
+            }
+            assert_eq!(pending_bytes(&mut server_socket), 0);
+            3
+        }).unwrap();
+    assert_eq!(sent, 3);
+    // a get_registry request is 12 bytes long
+    assert_eq!(pending_bytes(&mut server_socket), 36);
+
+    // a panicking batch and an unbalanced uncork do not leave the connection corked
+    let display = &client.display;
+    let batch = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+        display.with_batch(|| panic!("failed batch")).unwrap();
+    }));
+    assert!(batch.is_err());
+    let uncork = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| display.uncork()));
+    assert!(uncork.is_err());
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    client.event_queue.dispatch_pending().unwrap();
+    assert_eq!(pending_bytes(&mut server_socket), 12);
+}
+
+#[test]
+fn client_flush_deadline_virtual_clock() {
+    use std::io::Read;
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+    use self::wayc::clock::VirtualClock;
+    use self::wayc::FlushPolicy;
+
+    let (client_socket, mut server_socket) = UnixStream::pair().unwrap();
+    let client = unsafe { TestClient::from_fd(client_socket.into_raw_fd()) };
+    let clock = VirtualClock::new();
+    client.display.set_clock(clock.clone());
+
+    // the flushing thread waits for an hour of virtual time
+    client
+        .display
+        .set_flush_policy(FlushPolicy::Deadline(Duration::from_secs(3600)));
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    server_socket.set_nonblocking(true).unwrap();
+    let mut buffer = [0u8; 512];
+    assert!(server_socket.read(&mut buffer).is_err());
+
+    // and flushes once it has passed
+    clock.advance(Duration::from_secs(3600));
+    server_socket.set_nonblocking(false).unwrap();
+    server_socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert!(server_socket.read(&mut buffer).unwrap() > 0);
+}
+
+#[test]
+fn client_flush_thread_stops() {
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+    use self::wayc::clock::{Clock, VirtualClock};
+    use self::wayc::FlushPolicy;
+
+    // a virtual clock counting the threads sleeping on it
+    struct CountedClock(VirtualClock, Arc<AtomicUsize>);
+
+    impl Clock for CountedClock {
+        fn now(&self) -> Instant {
+            self.0.now()
+        }
+
+        fn sleep_until(&self, deadline: Instant) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0.sleep_until(deadline);
+            self.1.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn wait_sleepers(sleepers: &AtomicUsize, expected: usize) {
+        let start = Instant::now();
+        while sleepers.load(Ordering::SeqCst) != expected {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    let (client_socket, _server_socket) = UnixStream::pair().unwrap();
+    let client = unsafe { TestClient::from_fd(client_socket.into_raw_fd()) };
+    let clock = VirtualClock::new();
+    let sleepers = Arc::new(AtomicUsize::new(0));
+    client
+        .display
+        .set_clock(CountedClock(clock.clone(), sleepers.clone()));
+
+    // the flushing thread stops when the policy changes, although the clock does not advance
+    client
+        .display
+        .set_flush_policy(FlushPolicy::Deadline(Duration::from_secs(3600)));
+    wait_sleepers(&sleepers, 1);
+    client
+        .display
+        .set_flush_policy(FlushPolicy::Deadline(Duration::from_secs(3600)));
+    ::std::thread::sleep(Duration::from_millis(50));
+    wait_sleepers(&sleepers, 1);
+    client.display.set_flush_policy(FlushPolicy::OnDispatch);
+    wait_sleepers(&sleepers, 0);
+
+    // and when the display is dropped, even if its event queue is still alive
+    client
+        .display
+        .set_flush_policy(FlushPolicy::Deadline(Duration::from_secs(3600)));
+    wait_sleepers(&sleepers, 1);
+    let TestClient { display, event_queue } = client;
+    drop(display);
+    wait_sleepers(&sleepers, 0);
+
+    // the stopped threads do not sleep again once the clock advances
+    clock.advance(Duration::from_secs(3600));
+    ::std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(sleepers.load(Ordering::SeqCst), 0);
+    drop(event_queue);
+}
+
+#[test]
+fn client_dispatch_timeout() {
+    use std::time::Instant;
+
+    let mut server = TestServer::new();
+    let mut client = TestClient::new(&server.socket_name);
+
+    // nothing to dispatch, the timeout expires
+    let start = Instant::now();
+    assert_eq!(
+        client
+            .event_queue
+            .dispatch_timeout(Duration::from_millis(20))
+            .unwrap(),
+        0
+    );
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    let token = client.event_queue.get_token();
+    client
+        .display
+        .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+        .unwrap();
+
+    // the request is flushed, but the server did not answer yet
+    assert_eq!(
+        client
+            .event_queue
+            .dispatch_timeout(Duration::from_millis(10))
+            .unwrap(),
+        0
+    );
+    assert!(!done.get());
+
+    server.answer();
+    let dispatched = client
+        .event_queue
+        .dispatch_timeout(Duration::from_secs(5))
+        .unwrap();
+    assert!(dispatched > 0);
+    assert!(done.get());
+
+    // a timeout too large for an Instant waits without a deadline
+    done.set(false);
+    let done2 = done.clone();
+    let token = client.event_queue.get_token();
+    client
+        .display
+        .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+        .unwrap();
+    client.display.flush().unwrap();
+    server.answer();
+    let dispatched = client
+        .event_queue
+        .dispatch_timeout(Duration::new(u64::max_value(), 999_999_999))
+        .unwrap();
+    assert!(dispatched > 0);
+    assert!(done.get());
+}
+
+#[test]
+fn client_dispatch_with_state() {
+    use std::thread::{self, ThreadId};
+
+    use self::wayc::protocol::wl_output::{self, WlOutput};
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    type Scales = Vec<(usize, i32, ThreadId)>;
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(ServerEvent::Scale { factor: 2 });
+        output.send(ServerEvent::Done);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    // the stateful implementations are not dispatched by the pool
+    client.event_queue.set_dispatch_threads(2);
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let mut outputs = Vec::new();
+    for output in 0..2 {
+        let output = registry
+            .bind::<WlOutput, _>(2, 1, move |newp| {
+                newp.implement_with_state(
+                    move |event, _, state: &mut Scales| {
+                        if let wl_output::Event::Scale { factor } = event {
+                            state.push((output, factor, thread::current().id()));
+                        }
+                    },
+                    (),
+                )
+            }).unwrap();
+        outputs.push(output);
+    }
+
+    // receive the events of the outputs, and dispatch them with a state
+    let mut state: Scales = Vec::new();
+    client.display.flush().unwrap();
+    server.answer();
+    ::std::thread::sleep(::std::time::Duration::from_millis(100));
+    client.event_queue.prepare_read().unwrap().read_events().unwrap();
+    client.event_queue.dispatch_pending_with_state(&mut state).unwrap();
+
+    let main_thread = thread::current().id();
+    assert_eq!(state, [(0, 2, main_thread), (1, 2, main_thread)]);
+}
+
+// libwayland-client.so aborts when an implementation panics
+#[cfg(not(feature = "native_lib"))]
+#[test]
+#[should_panic(expected = "without the state of its implementation")]
+fn client_dispatch_without_state() {
+    use self::wayc::protocol::wl_output::WlOutput;
+    use self::wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use self::ways::protocol::wl_output::{Event as ServerEvent, WlOutput as ServerOutput};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(ServerEvent::Done);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    registry
+        .bind::<WlOutput, _>(2, 1, |newp| {
+            newp.implement_with_state(|_, _, state: &mut Vec<()>| state.push(()), ())
+        }).unwrap();
+
+    // the event can not be dispatched without state
+    roundtrip(&mut client, &mut server).unwrap();
+}
+
+#[test]
+fn client_dispatch_wakeup() {
+    use std::time::Instant;
+
+    let mut server = TestServer::new();
+    let mut client = TestClient::new(&server.socket_name);
+    let handle = client.event_queue.wakeup_handle().unwrap();
+
+    // a wakeup sent from an other thread interrupts the blocking dispatch
+    let handle2 = handle.clone();
+    let thread = ::std::thread::spawn(move || {
+        ::std::thread::sleep(Duration::from_millis(100));
+        handle2.wakeup().unwrap();
+    });
+    assert_eq!(client.event_queue.dispatch().unwrap(), 0);
+    thread.join().unwrap();
+
+    // the wakeups sent before the dispatch are not lost, and only interrupt it once
+    handle.wakeup().unwrap();
+    handle.wakeup().unwrap();
+    assert_eq!(client.event_queue.dispatch().unwrap(), 0);
+    let start = Instant::now();
+    assert_eq!(client.event_queue.dispatch_timeout(Duration::from_millis(50)).unwrap(), 0);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    // the events are still dispatched
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    let token = client.event_queue.get_token();
+    client
+        .display
+        .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+        .unwrap();
+    client.display.flush().unwrap();
+    server.answer();
+    while !done.get() {
+        client.event_queue.dispatch().unwrap();
+    }
+}
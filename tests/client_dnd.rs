@@ -0,0 +1,371 @@
+extern crate nix;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::fs::File;
+use std::io::Read;
+use std::io::ErrorKind;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use wayc::dnd::{DataOffer, DragSource, DragState, MimePolicy, MimePreference};
+use wayc::protocol::wl_data_device::Event as CDDEvt;
+use wayc::protocol::wl_data_device_manager::{
+    DndAction, RequestsTrait as DDMgrRequests, WlDataDeviceManager as ClientDDMgr,
+};
+use wayc::protocol::wl_data_offer::RequestsTrait as OfferRequests;
+use wayc::protocol::wl_seat::WlSeat as ClientSeat;
+
+use ways::protocol::wl_data_device::Event as SDDEvt;
+use ways::protocol::wl_data_device_manager::{Request as SDDMReq, WlDataDeviceManager as ServerDDMgr};
+use ways::protocol::wl_data_offer::{Event as SDOEvt, Request as SDOReq, WlDataOffer as ServerOffer};
+use ways::protocol::wl_data_source::{Event as SDSEvt, Request as SDSReq, WlDataSource as ServerSource};
+use ways::protocol::wl_seat::WlSeat as ServerSeat;
+use ways::Resource;
+
+type Sources = Arc<Mutex<Vec<Resource<ServerSource>>>>;
+type Requests = Arc<Mutex<Vec<String>>>;
+
+// a data device manager recording the requests of its data sources
+fn data_device_manager(server: &mut TestServer) -> (Sources, Requests) {
+    let sources = Arc::new(Mutex::new(Vec::new()));
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let sources2 = sources.clone();
+    let requests2 = requests.clone();
+    server
+        .display
+        .create_global::<ServerDDMgr, _>(3, move |new_resource, _| {
+            let sources = sources2.clone();
+            let requests = requests2.clone();
+            new_resource.implement(
+                move |request, _| match request {
+                    SDDMReq::CreateDataSource { id } => {
+                        let requests = requests.clone();
+                        let source = id.implement(
+                            move |request, _| {
+                                requests.lock().unwrap().push(match request {
+                                    SDSReq::Offer { mime_type } => format!("offer {}", mime_type),
+                                    SDSReq::SetActions { dnd_actions } => {
+                                        format!("set_actions {}", dnd_actions)
+                                    }
+                                    SDSReq::Destroy => "destroy".into(),
+                                })
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                        sources.lock().unwrap().push(source);
+                    }
+                    _ => unimplemented!(),
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    (sources, requests)
+}
+
+// request the contents in a MIME type, returns the reader of the pipe
+fn request_contents(
+    client: &mut TestClient,
+    server: &mut TestServer,
+    source: &Resource<ServerSource>,
+    mime_type: &str,
+) -> ::std::thread::JoinHandle<Vec<u8>> {
+    let (read_fd, write_fd) = nix::unistd::pipe().unwrap();
+    source.send(SDSEvt::Send {
+        mime_type: mime_type.into(),
+        fd: write_fd,
+    });
+    roundtrip(client, server).unwrap();
+    // the client received its own copy of the pipe
+    nix::unistd::close(write_fd).unwrap();
+    ::std::thread::spawn(move || {
+        let mut contents = Vec::new();
+        let mut file = unsafe { File::from_raw_fd(read_fd) };
+        file.read_to_end(&mut contents).unwrap();
+        contents
+    })
+}
+
+#[test]
+fn drag_negotiation_and_transfer() {
+    let mut server = TestServer::new();
+    let (sources, requests) = data_device_manager(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let ddmgr = manager
+        .instantiate_auto::<ClientDDMgr, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    // larger than the buffer of a pipe, to be written in several times
+    let text = (0..256 * 1024).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    let drag = DragSource::new(
+        &ddmgr,
+        DndAction::Copy | DndAction::Move,
+        vec![("text/plain".into(), text.clone()), ("text/html".into(), b"<b>text</b>".to_vec())],
+    ).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(
+        *requests.lock().unwrap(),
+        ["offer text/plain", "offer text/html", "set_actions 3"]
+    );
+    let source = sources.lock().unwrap()[0].clone();
+
+    source.send(SDSEvt::Target {
+        mime_type: Some("text/plain".into()),
+    });
+    source.send(SDSEvt::Action {
+        dnd_action: DndAction::Move.to_raw(),
+    });
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(drag.target(), Some("text/plain".into()));
+    assert_eq!(drag.action(), DndAction::Move);
+    assert_eq!(drag.state(), DragState::Dragging);
+
+    source.send(SDSEvt::DndDropPerformed);
+    let reader = request_contents(&mut client, &mut server, &source, "text/plain");
+
+    assert_eq!(drag.state(), DragState::Dropped);
+    assert_eq!(drag.pending_fds().len(), 1);
+    while drag.write_pending() {
+        ::std::thread::sleep(::std::time::Duration::from_millis(1));
+    }
+    assert!(drag.pending_fds().is_empty());
+    assert!(reader.join().unwrap() == text);
+
+    source.send(SDSEvt::DndFinished);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(drag.state(), DragState::Finished(DndAction::Move));
+    assert!(!drag.source().is_alive());
+    // the source was destroyed once the drag ended
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(requests.lock().unwrap().last().unwrap(), "destroy");
+}
+
+#[test]
+fn drag_cancelled() {
+    let mut server = TestServer::new();
+    let (sources, requests) = data_device_manager(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let ddmgr = manager
+        .instantiate_auto::<ClientDDMgr, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let text = vec![42u8; 256 * 1024];
+    let drag = DragSource::new(&ddmgr, DndAction::Copy, vec![("text/plain".into(), text.clone())]).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let source = sources.lock().unwrap()[0].clone();
+
+    // a type which was not offered gets no contents
+    let reader = request_contents(&mut client, &mut server, &source, "image/png");
+    assert!(reader.join().unwrap().is_empty());
+
+    let reader = request_contents(&mut client, &mut server, &source, "text/plain");
+    assert_eq!(drag.pending_fds().len(), 1);
+
+    source.send(SDSEvt::Cancelled);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(drag.state(), DragState::Cancelled);
+    // the pending transfer was abandoned
+    assert!(drag.pending_fds().is_empty());
+    assert!(reader.join().unwrap().len() < text.len());
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(requests.lock().unwrap().last().unwrap(), "destroy");
+
+    // this is a no-op once the drag ended
+    drag.cancel();
+    assert_eq!(requests.lock().unwrap().len(), 3);
+}
+
+fn mime_types(types: &[&str]) -> Vec<String> {
+    types.iter().map(|&mime_type| mime_type.into()).collect()
+}
+
+#[test]
+fn mime_preferences() {
+    let offered = mime_types(&["text/html", "STRING", "text/plain; charset=UTF-8", "text/uri-list"]);
+    // the types are matched regardless of case and spaces
+    assert_eq!(
+        MimePreference::text().choose(&offered),
+        Some("text/plain; charset=UTF-8".into())
+    );
+    assert_eq!(MimePreference::uri_list().choose(&offered), Some("text/uri-list".into()));
+    assert_eq!(MimePreference::text().choose(&mime_types(&["STRING"])), Some("STRING".into()));
+    assert_eq!(MimePreference::images().choose(&offered), None);
+
+    // the wildcard accepts the images which are not listed
+    let offered = mime_types(&["image/gif", "image/jpeg"]);
+    assert_eq!(MimePreference::images().choose(&offered), Some("image/jpeg".into()));
+    assert_eq!(MimePreference::images().choose(&offered[..1]), Some("image/gif".into()));
+    let preference = MimePreference::new(mime_types(&["image/GIF", "image/png"]));
+    assert_eq!(preference.types(), ["image/gif", "image/png"]);
+    assert_eq!(preference.choose(&offered), Some("image/gif".into()));
+
+    let last = |offered: &[String]| offered.last().cloned();
+    assert_eq!(last.choose(&offered), Some("image/jpeg".into()));
+}
+
+type Receives = Arc<Mutex<Vec<(String, RawFd)>>>;
+
+// a data device manager setting a selection offered in some MIME types, and recording the
+// pipes its receive requests are given
+fn selection_device_manager(server: &mut TestServer, offered: Vec<String>) -> Receives {
+    let receives = Arc::new(Mutex::new(Vec::new()));
+    let receives2 = receives.clone();
+    server.display.create_global::<ServerSeat, _>(1, |_, _| {});
+    server
+        .display
+        .create_global::<ServerDDMgr, _>(3, move |new_resource, _| {
+            let offered = offered.clone();
+            let receives = receives2.clone();
+            new_resource.implement(
+                move |request, _| match request {
+                    SDDMReq::GetDataDevice { id, .. } => {
+                        let ddevice = id.implement(|_, _| {}, None::<fn(_)>, ());
+                        let receives = receives.clone();
+                        let offer = ddevice
+                            .client()
+                            .unwrap()
+                            .create_resource::<ServerOffer>(ddevice.version())
+                            .unwrap()
+                            .implement(
+                                move |request, _| {
+                                    if let SDOReq::Receive { mime_type, fd } = request {
+                                        receives.lock().unwrap().push((mime_type, fd));
+                                    }
+                                },
+                                None::<fn(_)>,
+                                (),
+                            );
+                        ddevice.send(SDDEvt::DataOffer { id: offer.clone() });
+                        for mime_type in &offered {
+                            offer.send(SDOEvt::Offer {
+                                mime_type: mime_type.clone(),
+                            });
+                        }
+                        ddevice.send(SDDEvt::Selection { id: Some(offer) });
+                    }
+                    _ => unimplemented!(),
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    receives
+}
+
+// get the data device, returns the offer of its selection
+fn selection(client: &mut TestClient, server: &mut TestServer) -> DataOffer {
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(client, server).unwrap();
+
+    let seat = manager
+        .instantiate_auto::<ClientSeat, _>(|newseat| newseat.implement(|_, _| {}, ()))
+        .unwrap();
+    let ddmgr = manager
+        .instantiate_auto::<ClientDDMgr, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let selection = Arc::new(Mutex::new(None));
+    let selection2 = selection.clone();
+    ddmgr
+        .get_data_device(&seat, move |newdd| {
+            newdd.implement(
+                move |evt, _| match evt {
+                    CDDEvt::DataOffer { id } => {
+                        DataOffer::new(id);
+                    }
+                    CDDEvt::Selection { id } => {
+                        *selection2.lock().unwrap() = id.as_ref().and_then(DataOffer::from_proxy);
+                    }
+                    _ => unimplemented!(),
+                },
+                (),
+            )
+        }).unwrap();
+
+    roundtrip(client, server).unwrap();
+
+    let offer = selection.lock().unwrap().take();
+    offer.unwrap()
+}
+
+#[test]
+fn offer_receive() {
+    let mut server = TestServer::new();
+    let offered = mime_types(&["text/html", "text/plain;charset=utf-8", "text/uri-list"]);
+    let receives = selection_device_manager(&mut server, offered.clone());
+
+    let mut client = TestClient::new(&server.socket_name);
+    let offer = selection(&mut client, &mut server);
+
+    assert_eq!(offer.mime_types(), offered);
+    let mime_type = offer.choose(&MimePreference::text()).unwrap();
+    assert_eq!(mime_type, "text/plain;charset=utf-8");
+
+    let reader = offer.receive(&mime_type, 1024).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let (requested, fd) = receives.lock().unwrap().pop().unwrap();
+    assert_eq!(requested, mime_type);
+    nix::unistd::write(fd, b"hello").unwrap();
+    nix::unistd::close(fd).unwrap();
+
+    assert_eq!(reader.read_to_end(Duration::from_secs(5)).unwrap(), b"hello");
+}
+
+#[test]
+fn offer_receive_limits() {
+    let mut server = TestServer::new();
+    let receives = selection_device_manager(&mut server, mime_types(&["text/plain"]));
+
+    let mut client = TestClient::new(&server.socket_name);
+    let offer = selection(&mut client, &mut server);
+
+    // the contents are larger than the limit
+    let mut reader = offer.receive("text/plain", 4).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    let (_, fd) = receives.lock().unwrap().pop().unwrap();
+    nix::unistd::write(fd, b"hel").unwrap();
+    assert!(!reader.read_available().unwrap());
+    assert_eq!(reader.contents(), b"hel");
+    nix::unistd::write(fd, b"lo").unwrap();
+    nix::unistd::close(fd).unwrap();
+    assert_eq!(reader.read_available().unwrap_err().kind(), ErrorKind::InvalidData);
+
+    // the source never finishes writing
+    let reader = offer.receive("text/plain", 1024).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    let (_, fd) = receives.lock().unwrap().pop().unwrap();
+    nix::unistd::write(fd, b"hello").unwrap();
+    let err = reader.read_to_end(Duration::from_millis(50)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    nix::unistd::close(fd).unwrap();
+
+    offer.offer().destroy();
+    assert_eq!(
+        offer.receive("text/plain", 1024).err().unwrap().kind(),
+        ErrorKind::NotConnected
+    );
+}
@@ -0,0 +1,77 @@
+mod helpers;
+
+use helpers::{wayc, TestClient, TestServer};
+
+use wayc::clock::VirtualClock;
+use wayc::keepalive::{Keepalive, Liveness};
+
+use std::thread;
+use std::time::Duration;
+
+// poll the keepalive until the server answer was dispatched
+fn poll_answer(keepalive: &mut Keepalive, server: &mut TestServer) -> Liveness {
+    for _ in 0..20 {
+        server.answer();
+        thread::sleep(Duration::from_millis(10));
+        let liveness = keepalive.poll().unwrap();
+        if let Liveness::Responsive(_) = liveness {
+            return liveness;
+        }
+    }
+    panic!("the server did not answer");
+}
+
+#[test]
+fn keepalive_responsive() {
+    let mut server = TestServer::new();
+    let client = TestClient::new(&server.socket_name);
+    let clock = VirtualClock::new();
+    client.display.set_clock(clock.clone());
+
+    let mut keepalive =
+        Keepalive::new(&client.display, Duration::from_secs(1), Duration::from_secs(5)).unwrap();
+    assert_eq!(keepalive.poll().unwrap(), Liveness::Unknown);
+
+    clock.advance(Duration::from_millis(30));
+    assert_eq!(
+        poll_answer(&mut keepalive, &mut server),
+        Liveness::Responsive(Duration::from_millis(30))
+    );
+    assert_eq!(keepalive.latency(), Some(Duration::from_millis(30)));
+
+    // the next roundtrip is issued after the interval, and answered faster
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(
+        keepalive.poll().unwrap(),
+        Liveness::Responsive(Duration::from_millis(30))
+    );
+    clock.advance(Duration::from_millis(10));
+    assert_eq!(
+        poll_answer(&mut keepalive, &mut server),
+        Liveness::Responsive(Duration::from_millis(10))
+    );
+}
+
+#[test]
+fn keepalive_hung() {
+    let mut server = TestServer::new();
+    let client = TestClient::new(&server.socket_name);
+    let clock = VirtualClock::new();
+    client.display.set_clock(clock.clone());
+
+    let mut keepalive =
+        Keepalive::new(&client.display, Duration::from_secs(1), Duration::from_secs(5)).unwrap();
+    assert_eq!(keepalive.poll().unwrap(), Liveness::Unknown);
+
+    // the server does not process the requests
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(keepalive.poll().unwrap(), Liveness::Unknown);
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(keepalive.poll().unwrap(), Liveness::Hung(Duration::from_secs(6)));
+
+    // it recovers
+    assert_eq!(
+        poll_answer(&mut keepalive, &mut server),
+        Liveness::Responsive(Duration::from_secs(6))
+    );
+}
@@ -0,0 +1,205 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::{wl_pointer as server_pointer, wl_seat as server_seat};
+
+use wayc::latency::{mark_handled, EventTiming};
+use wayc::protocol::wl_seat::{self, RequestsTrait as SeatRequests};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn input_latency_hook() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<server_seat::WlSeat, _>(1, |newseat, _| {
+            newseat.implement(
+                |request, _| {
+                    if let server_seat::Request::GetPointer { id } = request {
+                        let pointer = id.implement(|_, _| {}, None::<fn(_)>, ());
+                        pointer.send(server_pointer::Event::Motion {
+                            time: 0,
+                            surface_x: 1.0,
+                            surface_y: 2.0,
+                        });
+                        pointer.send(server_pointer::Event::Motion {
+                            time: 1,
+                            surface_x: 3.0,
+                            surface_y: 4.0,
+                        });
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let timings = Rc::new(RefCell::new(Vec::<EventTiming>::new()));
+    let timings2 = timings.clone();
+    client
+        .event_queue
+        .set_latency_hook(Some(move |timing: &EventTiming| timings2.borrow_mut().push(timing.clone())));
+
+    let seat = manager
+        .instantiate_auto::<wl_seat::WlSeat, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let mut motions = 0;
+    seat.get_pointer(|newp| {
+        newp.implement(
+            move |_, _| {
+                // only mark the first event as handled
+                if motions == 0 {
+                    mark_handled();
+                }
+                motions += 1;
+            },
+            (),
+        )
+    }).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // only the input events are reported
+    let timings = timings.borrow();
+    assert_eq!(timings.len(), 2);
+    for timing in timings.iter() {
+        assert_eq!(timing.interface(), "wl_pointer");
+        assert_eq!(timing.event(), "motion");
+        assert!(timing.dispatched() >= timing.received());
+    }
+    assert!(timings[0].handled_latency().unwrap() >= timings[0].dispatch_latency());
+    assert!(timings[1].handled().is_none());
+}
+
+#[test]
+fn input_latency_virtual_clock() {
+    use std::time::Duration;
+    use wayc::clock::VirtualClock;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<server_seat::WlSeat, _>(1, |newseat, _| {
+            newseat.implement(
+                |request, _| {
+                    if let server_seat::Request::GetPointer { id } = request {
+                        let pointer = id.implement(|_, _| {}, None::<fn(_)>, ());
+                        pointer.send(server_pointer::Event::Motion {
+                            time: 0,
+                            surface_x: 1.0,
+                            surface_y: 2.0,
+                        });
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let clock = VirtualClock::new();
+    client.display.set_clock(clock.clone());
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let timings = Rc::new(RefCell::new(Vec::<EventTiming>::new()));
+    let timings2 = timings.clone();
+    client
+        .event_queue
+        .set_latency_hook(Some(move |timing: &EventTiming| timings2.borrow_mut().push(timing.clone())));
+
+    let seat = manager
+        .instantiate_auto::<wl_seat::WlSeat, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    seat.get_pointer(|newp| {
+        newp.implement(
+            move |_, _| {
+                // handling the event takes 5ms of virtual time
+                clock.advance(Duration::from_millis(5));
+                mark_handled();
+            },
+            (),
+        )
+    }).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let timings = timings.borrow();
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].dispatch_latency(), Duration::from_millis(0));
+    assert_eq!(timings[0].handled_latency(), Some(Duration::from_millis(5)));
+}
+
+#[test]
+fn input_latency_hook_unset() {
+    use std::time::Duration;
+    use wayc::clock::VirtualClock;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<server_seat::WlSeat, _>(1, |newseat, _| {
+            newseat.implement(
+                |request, _| {
+                    if let server_seat::Request::GetPointer { id } = request {
+                        let pointer = id.implement(|_, _| {}, None::<fn(_)>, ());
+                        pointer.send(server_pointer::Event::Motion {
+                            time: 0,
+                            surface_x: 1.0,
+                            surface_y: 2.0,
+                        });
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let clock = VirtualClock::new();
+    client.display.set_clock(clock.clone());
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let seat = manager
+        .instantiate_auto::<wl_seat::WlSeat, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let get_pointer = |seat: &wayc::Proxy<wl_seat::WlSeat>| {
+        seat.get_pointer(|newp| newp.implement(|_, _| {}, ())).unwrap();
+    };
+
+    let timings = Rc::new(RefCell::new(Vec::<EventTiming>::new()));
+    let read_then_dispatch = |client: &mut TestClient, server: &mut TestServer| {
+        client.display.flush().unwrap();
+        server.answer();
+        client.event_queue.prepare_read().unwrap().read_events().unwrap();
+        clock.advance(Duration::from_millis(10));
+        let timings2 = timings.clone();
+        client
+            .event_queue
+            .set_latency_hook(Some(move |timing: &EventTiming| timings2.borrow_mut().push(timing.clone())));
+        client.event_queue.dispatch_pending().unwrap();
+    };
+
+    // the event was read while no hook was set, its reception was not stamped
+    get_pointer(&seat);
+    read_then_dispatch(&mut client, &mut server);
+    assert_eq!(timings.borrow()[0].dispatch_latency(), Duration::from_millis(0));
+
+    // it is once a hook is set
+    get_pointer(&seat);
+    read_then_dispatch(&mut client, &mut server);
+    #[cfg(not(feature = "native_lib"))]
+    assert_eq!(timings.borrow()[1].dispatch_latency(), Duration::from_millis(10));
+    assert_eq!(timings.borrow().len(), 2);
+}
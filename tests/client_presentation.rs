@@ -0,0 +1,101 @@
+extern crate wayland_protocols;
+
+mod helpers;
+
+use helpers::wayc;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use wayc::clock::{Clock, VirtualClock};
+
+use wayland_protocols::xdg_shell::client::xdg_toplevel::State;
+use wayland_protocols::xdg_shell::PresentationTracker;
+
+// the array of states of a configure event, as received from the wire
+fn states(states: &[State]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for state in states {
+        let raw = state.to_raw();
+        for i in 0..4 {
+            let shift = if cfg!(target_endian = "little") { 8 * i } else { 24 - 8 * i };
+            bytes.push((raw >> shift) as u8);
+        }
+    }
+    bytes
+}
+
+// a tracker recording the values given to its callback
+fn tracker(clock: &VirtualClock) -> (PresentationTracker, Rc<RefCell<Vec<bool>>>) {
+    let mut tracker = PresentationTracker::new(clock.clone(), Duration::from_secs(1));
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let changes2 = changes.clone();
+    tracker.set_callback(move |presenting| changes2.borrow_mut().push(presenting));
+    (tracker, changes)
+}
+
+#[test]
+fn presentation_suspended() {
+    let clock = VirtualClock::new();
+    let (mut tracker, changes) = tracker(&clock);
+    assert!(tracker.is_presenting());
+
+    tracker.configure(&states(&[State::Activated, State::Suspended]));
+    assert!(tracker.is_suspended());
+    assert!(!tracker.is_presenting());
+    // the callback is only invoked on changes
+    tracker.configure(&states(&[State::Suspended]));
+    tracker.configure(&states(&[State::Maximized]));
+    assert!(!tracker.is_suspended());
+    assert!(tracker.is_presenting());
+    tracker.configure(&[]);
+    assert_eq!(*changes.borrow(), [false, true]);
+
+    // truncated states are ignored
+    let mut truncated = states(&[State::Suspended]);
+    truncated.pop();
+    tracker.configure(&truncated);
+    assert!(tracker.is_presenting());
+}
+
+#[test]
+fn presentation_frame_starvation() {
+    let clock = VirtualClock::new();
+    let (mut tracker, changes) = tracker(&clock);
+    assert_eq!(tracker.deadline(), None);
+
+    // a frame callback delivered in time
+    tracker.frame_requested();
+    assert_eq!(tracker.deadline(), Some(clock.now() + Duration::from_secs(1)));
+    clock.advance(Duration::from_millis(500));
+    tracker.update();
+    tracker.frame_done();
+    assert_eq!(tracker.deadline(), None);
+    assert!(tracker.is_presenting());
+
+    // only the oldest pending frame callback counts
+    tracker.frame_requested();
+    let deadline = tracker.deadline().unwrap();
+    clock.advance(Duration::from_millis(600));
+    tracker.frame_requested();
+    assert_eq!(tracker.deadline(), Some(deadline));
+    clock.advance(Duration::from_millis(300));
+    tracker.update();
+    assert!(tracker.is_presenting());
+
+    // the starvation is noticed by update(), once the deadline is reached
+    clock.advance(Duration::from_millis(100));
+    assert_eq!(tracker.deadline(), None);
+    assert!(tracker.is_presenting());
+    tracker.update();
+    assert!(!tracker.is_presenting());
+
+    // the delivery of the callback ends it, unless the toplevel is suspended
+    tracker.configure(&states(&[State::Suspended]));
+    tracker.frame_done();
+    assert!(!tracker.is_presenting());
+    tracker.configure(&[]);
+    assert!(tracker.is_presenting());
+    assert_eq!(*changes.borrow(), [false, true]);
+}
@@ -1,7 +1,11 @@
 mod helpers;
 
+extern crate tempfile;
+
 use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
 
+use std::sync::{Arc, Mutex};
+
 use ways::protocol::wl_compositor::WlCompositor as ServerCompositor;
 use ways::protocol::wl_output::WlOutput as ServerOutput;
 
@@ -84,6 +88,65 @@ fn proxy_user_data_wrong_thread() {
     .unwrap();
 }
 
+#[test]
+fn proxy_additional_user_data() {
+    use std::rc::Rc;
+    use wayc::protocol::wl_compositor::RequestsTrait;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<ServerCompositor, _>(1, |compositor, _| {
+            compositor.implement(
+                |req, _| {
+                    if let ways::protocol::wl_compositor::Request::CreateSurface { id } = req {
+                        id.implement(|_, _| {}, None::<fn(_)>, ());
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_auto::<wl_compositor::WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, 42u32))
+        .unwrap();
+
+    // two independent modules attach their data to the same surface
+    #[derive(Debug, PartialEq)]
+    struct ToolkitData(&'static str);
+    #[derive(Debug, PartialEq)]
+    struct EglData(usize);
+    assert!(surface.set_user_data(ToolkitData("toplevel")));
+    assert!(surface.set_user_data(EglData(1)));
+
+    // the data of a type can only be attached once, and is shared by all handles
+    let other = surface.clone();
+    assert!(!other.set_user_data(EglData(2)));
+    assert!(!other.set_user_data(7u32));
+    assert_eq!(other.user_data::<u32>(), Some(&42));
+    assert_eq!(other.user_data::<ToolkitData>(), Some(&ToolkitData("toplevel")));
+    assert_eq!(other.user_data::<EglData>(), Some(&EglData(1)));
+    assert_eq!(other.user_data::<usize>(), None);
+
+    // the non-Send data is only accessible from its thread
+    assert!(surface.set_user_data_nonsend(Rc::new(5i32)));
+    assert_eq!(surface.user_data::<Rc<i32>>().map(|v| **v), Some(5));
+    ::std::thread::spawn(move || {
+        assert!(other.user_data::<Rc<i32>>().is_none());
+        assert_eq!(other.user_data::<EglData>(), Some(&EglData(1)));
+    }).join()
+    .unwrap();
+}
+
 #[test]
 fn proxy_wrapper() {
     let mut server = TestServer::new();
@@ -104,6 +167,110 @@ fn proxy_wrapper() {
     assert!(manager.list().len() == 1);
 }
 
+#[test]
+fn implement_on_queue_handle() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use wayc::protocol::wl_display::RequestsTrait;
+
+    let mut server = TestServer::new();
+    let mut client = TestClient::new(&server.socket_name);
+
+    let mut event_queue_2 = client.display.create_event_queue();
+    let handle = event_queue_2.get_handle();
+    let display = (*client.display).clone();
+    let done = Arc::new(AtomicBool::new(false));
+    let thread_done = done.clone();
+
+    ::std::thread::spawn(move || {
+        display
+            .sync(|newp| {
+                newp.implement_on(
+                    move |_, _| thread_done.store(true, Ordering::SeqCst),
+                    (),
+                    &handle,
+                )
+            }).unwrap();
+    }).join()
+    .unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the callback was assigned to event_queue_2, which has not been dispatched
+    assert!(!done.load(Ordering::SeqCst));
+
+    event_queue_2.dispatch_pending().unwrap();
+
+    assert!(done.load(Ordering::SeqCst));
+}
+
+#[test]
+fn object_quotas() {
+    use std::sync::{Arc, Mutex};
+    use wayc::protocol::wl_callback::WlCallback;
+    use wayc::protocol::wl_compositor::RequestsTrait as CompositorRequests;
+    use wayc::protocol::wl_surface::{RequestsTrait as SurfaceRequests, WlSurface};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerCompositor, _>(1, |compositor, _| {
+        compositor.implement(
+            |req, _| {
+                if let ways::protocol::wl_compositor::Request::CreateSurface { id } = req {
+                    id.implement(|_, _| {}, None::<fn(_)>, ());
+                }
+            },
+            None::<fn(_)>,
+            (),
+        );
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    let exceeded = Arc::new(Mutex::new(Vec::new()));
+    let hook_exceeded = exceeded.clone();
+    client.display.set_quota_hook(move |e| {
+        hook_exceeded
+            .lock()
+            .unwrap()
+            .push((e.interface(), e.live(), e.limit()))
+    });
+    client.display.set_object_quota::<WlSurface>(Some(2));
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the callback of the roundtrip was destroyed by the server
+    assert_eq!(client.display.live_objects::<WlCallback>(), 0);
+
+    let compositor = manager
+        .instantiate_auto::<wl_compositor::WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surfaces = (0..4)
+        .map(|_| {
+            compositor
+                .create_surface(|newp| newp.implement(|_, _| {}, ()))
+                .unwrap()
+        }).collect::<Vec<_>>();
+
+    assert_eq!(client.display.live_objects::<WlSurface>(), 4);
+    // the hook is only invoked when crossing the limit
+    assert_eq!(&*exceeded.lock().unwrap(), &[("wl_surface", 3, 2)]);
+
+    surfaces[0].destroy();
+    surfaces[1].destroy();
+    assert_eq!(client.display.live_objects::<WlSurface>(), 2);
+
+    let _surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    assert_eq!(
+        &*exceeded.lock().unwrap(),
+        &[("wl_surface", 3, 2), ("wl_surface", 3, 2)]
+    );
+
+    roundtrip(&mut client, &mut server).unwrap();
+}
+
 #[test]
 fn dead_proxies() {
     use self::wl_output::RequestsTrait;
@@ -136,3 +303,451 @@ fn dead_proxies() {
     assert!(!output.is_alive());
     assert!(!output2.is_alive());
 }
+
+#[test]
+fn concurrent_requests() {
+    use std::sync::{Arc, Mutex};
+    use wayc::protocol::wl_compositor::RequestsTrait as CompositorRequests;
+    use wayc::protocol::wl_surface::RequestsTrait as SurfaceRequests;
+    use ways::protocol::wl_surface;
+
+    let mut server = TestServer::new();
+    let damages = Arc::new(Mutex::new(Vec::new()));
+    let server_damages = damages.clone();
+    server.display.create_global::<ServerCompositor, _>(1, move |compositor, _| {
+        let damages = server_damages.clone();
+        compositor.implement(
+            move |req, _| {
+                if let ways::protocol::wl_compositor::Request::CreateSurface { id } = req {
+                    let damages = damages.clone();
+                    id.implement(
+                        move |req, _| {
+                            if let wl_surface::Request::Damage { x, y, .. } = req {
+                                damages.lock().unwrap().push((x, y));
+                            }
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                }
+            },
+            None::<fn(_)>,
+            (),
+        );
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_auto::<wl_compositor::WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let threads = (0..4)
+        .map(|t| {
+            let surface = compositor
+                .create_surface(|newp| newp.implement(|_, _| {}, ()))
+                .unwrap();
+            ::std::thread::spawn(move || {
+                for i in 0..20 {
+                    surface.damage(t, i, 1, 1);
+                }
+            })
+        }).collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let damages = damages.lock().unwrap();
+    assert_eq!(damages.len(), 80);
+    // the requests of each thread were received in order
+    for t in 0..4 {
+        let received = damages.iter().filter(|&&(x, _)| x == t).map(|&(_, y)| y).collect::<Vec<_>>();
+        assert_eq!(received, (0..20).collect::<Vec<_>>());
+    }
+}
+
+// a compositor logging the damages of its surfaces
+fn damage_server(server: &mut TestServer, log: Arc<Mutex<Vec<String>>>) {
+    use ways::protocol::wl_surface;
+
+    server.display.create_global::<ServerCompositor, _>(1, move |compositor, _| {
+        let log = log.clone();
+        compositor.implement(
+            move |req, _| {
+                if let ways::protocol::wl_compositor::Request::CreateSurface { id } = req {
+                    let log = log.clone();
+                    id.implement(
+                        move |req, _| {
+                            if let wl_surface::Request::Damage { x, y, .. } = req {
+                                log.lock().unwrap().push(format!("damage {} {}", x, y));
+                            }
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                }
+            },
+            None::<fn(_)>,
+            (),
+        );
+    });
+}
+
+#[test]
+fn requests_with_fds() {
+    use std::fs::File;
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use wayc::protocol::wl_compositor::RequestsTrait as CompositorRequests;
+    use wayc::protocol::wl_shm::RequestsTrait as ShmRequests;
+    use wayc::protocol::wl_surface::RequestsTrait as SurfaceRequests;
+    use ways::protocol::wl_shm::{Request as ShmRequest, WlShm as ServerShm};
+
+    let mut server = TestServer::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    damage_server(&mut server, log.clone());
+    let shm_log = log.clone();
+    server.display.create_global::<ServerShm, _>(1, move |shm, _| {
+        let log = shm_log.clone();
+        shm.implement(
+            move |req, _| {
+                let ShmRequest::CreatePool { id, fd, size } = req;
+                let file = unsafe { File::from_raw_fd(fd) };
+                let inode = file.metadata().unwrap().ino();
+                log.lock().unwrap().push(format!("create_pool {} {}", inode, size));
+                id.implement(|_, _| {}, None::<fn(_)>, ());
+            },
+            None::<fn(_)>,
+            (),
+        );
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_auto::<wl_compositor::WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let shm = manager
+        .instantiate_auto::<wayc::protocol::wl_shm::WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let file = tempfile::tempfile().unwrap();
+    let inode = file.metadata().unwrap().ino();
+    surface.damage(0, 0, 1, 1);
+    shm.create_pool(file.as_raw_fd(), 42, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    // the fd can be closed as soon as the request is sent
+    drop(file);
+    surface.damage(1, 0, 1, 1);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the request with a fd is ordered with the ones buffered around it
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "damage 0 0".to_owned(),
+            format!("create_pool {} 42", inode),
+            "damage 1 0".to_owned(),
+        ]
+    );
+}
+
+// the buffering of the requests is left to libwayland-client.so with native_lib
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn outgoing_limit() {
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+    use wayc::protocol::wl_compositor::RequestsTrait as CompositorRequests;
+    use wayc::protocol::wl_surface::RequestsTrait as SurfaceRequests;
+
+    const COUNT: i32 = 100_000;
+
+    let mut server = TestServer::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    damage_server(&mut server, log.clone());
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_auto::<wl_compositor::WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let (sender, done) = channel();
+    let thread = ::std::thread::spawn(move || {
+        for i in 0..COUNT {
+            surface.damage(0, i, 1, 1);
+        }
+        sender.send(()).unwrap();
+    });
+
+    // the requests do not fit in the socket: as the server does not read them, the
+    // sender waits rather than buffering them all
+    assert!(done.recv_timeout(Duration::from_millis(500)).is_err());
+    // it does not hold the connection while it waits
+    match client.display.flush() {
+        Err(ref e) if e.kind() == ::std::io::ErrorKind::WouldBlock => {}
+        other => panic!("Unexpected flush result: {:?}", other),
+    }
+    while done.try_recv().is_err() {
+        server.answer();
+        ::std::thread::sleep(Duration::from_millis(10));
+    }
+    thread.join().unwrap();
+
+    // the last requests may still not fit in the socket
+    loop {
+        match client.display.flush() {
+            Ok(()) => break,
+            Err(ref e) if e.kind() == ::std::io::ErrorKind::WouldBlock => server.answer(),
+            Err(e) => panic!("Flush failed: {}", e),
+        }
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let expected = (0..COUNT).map(|i| format!("damage 0 {}", i)).collect::<Vec<_>>();
+    assert!(*log.lock().unwrap() == expected);
+}
+
+#[test]
+fn rebind_proxy() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use wayc::protocol::wl_display::RequestsTrait as DisplayRequests;
+    use wayc::protocol::wl_registry::{Event as RegistryEvent, RequestsTrait as RegistryRequests};
+    use ways::protocol::wl_output::Event;
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(Event::Done);
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = Arc::new(AtomicUsize::new(0));
+    let outputs = Arc::new(AtomicUsize::new(0));
+    let globals2 = globals.clone();
+    let registry = client
+        .display
+        .get_registry(|newp| {
+            newp.implement(
+                move |evt, _| {
+                    if let RegistryEvent::Global { .. } = evt {
+                        globals2.fetch_add(1, Ordering::SeqCst);
+                    }
+                },
+                (),
+            )
+        }).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(globals.load(Ordering::SeqCst), 1);
+
+    let outputs2 = outputs.clone();
+    registry
+        .bind::<wl_output::WlOutput, _>(2, 1, |newp| {
+            newp.implement(
+                move |_, _| {
+                    outputs2.fetch_add(1, Ordering::SeqCst);
+                },
+                (),
+            )
+        }).unwrap();
+
+    // move the registry and the output to an other queue
+    let mut event_queue_2 = client.display.create_event_queue();
+    unsafe { registry.rebind(&event_queue_2.get_handle(), true) };
+
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(outputs.load(Ordering::SeqCst), 0);
+
+    event_queue_2.dispatch_pending().unwrap();
+    assert_eq!(outputs.load(Ordering::SeqCst), 1);
+
+    // the new global is announced to event_queue_2, which is not dispatched
+    server.display.create_global::<ServerCompositor, _>(1, |_, _| {});
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(globals.load(Ordering::SeqCst), 1);
+
+    // move the registry back, but not the output
+    unsafe { registry.rebind(&client.event_queue.get_handle(), false) };
+
+    // the pending event was moved along with the registry
+    #[cfg(not(feature = "native_lib"))]
+    {
+        client.event_queue.dispatch_pending().unwrap();
+        assert_eq!(event_queue_2.dispatch_pending().unwrap(), 0);
+    }
+    #[cfg(feature = "native_lib")]
+    {
+        event_queue_2.dispatch_pending().unwrap();
+    }
+    assert_eq!(globals.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn implement_stream() {
+    use wayc::protocol::wl_display::RequestsTrait;
+    use wayc::protocol::wl_registry;
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerCompositor, _>(1, |_, _| {});
+    server.display.create_global::<ServerOutput, _>(2, |_, _| {});
+
+    let mut client = TestClient::new(&server.socket_name);
+    let mut stream = None;
+    client
+        .display
+        .get_registry(|newp| {
+            let (registry, events) = newp.implement_stream();
+            stream = Some(events);
+            registry
+        }).unwrap();
+    let mut stream = stream.unwrap();
+    assert!(stream.is_empty());
+
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(stream.len(), 2);
+    let mut interfaces = Vec::new();
+    for event in &mut stream {
+        match event {
+            wl_registry::Event::Global { interface, version, .. } => interfaces.push((interface, version)),
+            _ => panic!("Unexpected event."),
+        }
+    }
+    assert_eq!(
+        interfaces,
+        vec![("wl_compositor".to_owned(), 1), ("wl_output".to_owned(), 2)]
+    );
+    assert!(stream.next().is_none());
+
+    // the new events are yielded once dispatched
+    server.display.create_global::<ServerCompositor, _>(1, |_, _| {});
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(stream.count(), 1);
+
+    // the events are discarded once the stream is dropped
+    server.display.create_global::<ServerCompositor, _>(1, |_, _| {});
+    roundtrip(&mut client, &mut server).unwrap();
+}
+
+#[test]
+fn implement_stream_nonsend() {
+    use wayc::protocol::wl_display::RequestsTrait;
+    use wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+    use wayc::protocol::wl_seat;
+    use ways::protocol::wl_seat::{Capability, Event as SeatEvent, WlSeat as ServerSeat};
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerSeat, _>(1, |newseat, _| {
+        let seat = newseat.implement(|_, _| {}, None::<fn(_)>, ());
+        seat.send(SeatEvent::Capabilities {
+            capabilities: Capability::Pointer,
+        });
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let mut stream = None;
+    let token = client.event_queue.get_token();
+    registry
+        .bind::<wl_seat::WlSeat, _>(1, 1, |newp| unsafe {
+            let (seat, events) = newp.implement_stream_nonsend(&token);
+            stream = Some(events);
+            seat
+        }).unwrap();
+    let mut stream = stream.unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+    match stream.next() {
+        Some(wl_seat::Event::Capabilities { capabilities }) => {
+            assert_eq!(capabilities, wl_seat::Capability::Pointer)
+        }
+        _ => panic!("Unexpected event."),
+    }
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn string_arguments() {
+    use std::sync::{Arc, Mutex};
+
+    use ways::protocol::wl_data_device_manager::{Request as SDDMReq, WlDataDeviceManager as ServerDDMgr};
+    use ways::protocol::wl_data_source::Request as SDSReq;
+
+    use wayc::protocol::wl_data_device_manager::{RequestsTrait, WlDataDeviceManager as ClientDDMgr};
+    use wayc::protocol::wl_data_source::RequestsTrait as DataSourceRequests;
+
+    let mime_types = Arc::new(Mutex::new(Vec::new()));
+    let mime_types2 = mime_types.clone();
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<ServerDDMgr, _>(3, move |new_resource, _| {
+            let mime_types = mime_types2.clone();
+            new_resource.implement(
+                move |request, _| match request {
+                    SDDMReq::CreateDataSource { id } => {
+                        let mime_types = mime_types.clone();
+                        id.implement(
+                            move |request, _| match request {
+                                SDSReq::Offer { mime_type } => mime_types.lock().unwrap().push(mime_type),
+                                _ => unimplemented!(),
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                    _ => unimplemented!(),
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let ddmgr = manager
+        .instantiate_auto::<ClientDDMgr, _>(|newddmgr| newddmgr.implement(|_, _| {}, ()))
+        .unwrap();
+    let source = ddmgr
+        .create_data_source(|newds| newds.implement(|_, _| {}, ()))
+        .unwrap();
+
+    assert!(source.offer("text/plain").is_ok());
+    // a string with a nul byte cannot be sent, and is not
+    let err = source.offer("text/\0plain").unwrap_err();
+    assert_eq!(err.nul_position(), 5);
+    assert!(source.offer("").is_ok());
+    // owned strings are accepted as well
+    assert!(source.offer(String::from("text/html")).is_ok());
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(*mime_types.lock().unwrap(), ["text/plain", "", "text/html"]);
+}
@@ -0,0 +1,46 @@
+extern crate wayland_client as wayc;
+
+use wayc::quirks::{detect, Compositor, Quirks, QuirksDb};
+
+#[test]
+fn detect_from_globals() {
+    let mut globals = vec![(1, "wl_compositor".to_owned(), 4), (2, "wl_shm".to_owned(), 1)];
+    // the core protocol does not identify anything
+    assert_eq!(detect(&globals), Compositor::Unknown);
+
+    globals.push((3, "zwlr_layer_shell_v1".to_owned(), 1));
+    assert_eq!(detect(&globals), Compositor::Wlroots);
+}
+
+#[test]
+fn detect_fingerprint_priority() {
+    // KWin also exposes some wlroots protocols
+    let globals = vec![
+        (1, "wl_compositor".to_owned(), 4),
+        (2, "zwlr_layer_shell_v1".to_owned(), 1),
+        (3, "org_kde_plasma_shell".to_owned(), 6),
+    ];
+    assert_eq!(detect(&globals), Compositor::KWin);
+}
+
+#[test]
+fn compositor_names() {
+    assert_eq!(Compositor::from_name("Mutter"), Compositor::Mutter);
+    assert_eq!(Compositor::from_name("sway"), Compositor::Wlroots);
+    assert_eq!(Compositor::from_name("niri"), Compositor::Other("niri".into()));
+    assert_eq!(Compositor::from_name(""), Compositor::Unknown);
+}
+
+#[test]
+fn quirks_database() {
+    let mut db = QuirksDb::new();
+    assert!(db.quirks(&Compositor::Mutter).contains(Quirks::NO_SERVER_DECORATIONS));
+    assert_eq!(db.quirks(&Compositor::Unknown), Quirks::empty());
+
+    // entries can be added and overridden
+    db.set(Compositor::Other("niri".into()), Quirks::SLOW_BUFFER_RELEASE);
+    db.set(Compositor::Mutter, Quirks::empty());
+    assert_eq!(db.quirks(&Compositor::Other("niri".into())), Quirks::SLOW_BUFFER_RELEASE);
+    assert_eq!(db.quirks(&Compositor::Mutter), Quirks::empty());
+    assert_eq!(QuirksDb::empty().quirks(&Compositor::Weston), Quirks::empty());
+}
@@ -0,0 +1,135 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+use wayc::protocol::wl_output::WlOutput;
+use wayc::protocol::wl_surface::WlSurface;
+use wayc::reconnect::VirtualProxies;
+
+use std::sync::{Arc, Mutex};
+
+// a compositor global counting the surfaces created by its clients
+fn insert_compositor(server: &mut TestServer) -> Arc<Mutex<u32>> {
+    use ways::protocol::{wl_compositor, wl_surface};
+
+    let surfaces = Arc::new(Mutex::new(0));
+    let surfaces2 = surfaces.clone();
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(1, move |compositor, _| {
+            let surfaces = surfaces2.clone();
+            compositor.implement(
+                move |request, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = request {
+                        id.implement(|_: wl_surface::Request, _| {}, None::<fn(_)>, ());
+                        *surfaces.lock().unwrap() += 1;
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    surfaces
+}
+
+// connect to a server, with a roundtrip listing its globals
+fn connect(server: &mut TestServer) -> (TestClient, wayc::GlobalManager) {
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, server).unwrap();
+    (client, globals)
+}
+
+fn register(proxies: &mut VirtualProxies) -> (wayc::reconnect::VirtualProxy<WlSurface>, Arc<Mutex<u32>>) {
+    let compositor = proxies.add(|globals| {
+        globals
+            .instantiate_exact::<WlCompositor, _>(1, |compositor| compositor.implement(|_, _| {}, ()))
+            .ok()
+    });
+    let recipes = Arc::new(Mutex::new(0));
+    let recipes2 = recipes.clone();
+    let surface = proxies.add(move |_| {
+        *recipes2.lock().unwrap() += 1;
+        compositor
+            .get()?
+            .create_surface(|surface| surface.implement(|_, _| {}, ()))
+            .ok()
+    });
+    (surface, recipes)
+}
+
+#[test]
+fn reconnect_recreates() {
+    let mut server = TestServer::new();
+    let surfaces = insert_compositor(&mut server);
+    let (mut client, globals) = connect(&mut server);
+
+    let mut proxies = VirtualProxies::new(&globals);
+    let (surface, recipes) = register(&mut proxies);
+    let old = surface.get().unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(*surfaces.lock().unwrap(), 1);
+
+    // the compositor restarts
+    drop(server);
+    let mut server = TestServer::new();
+    let surfaces = insert_compositor(&mut server);
+    let (mut client, globals) = connect(&mut server);
+
+    assert!(proxies.reconnect(&globals).is_empty());
+    assert_eq!(*recipes.lock().unwrap(), 2);
+    let new = surface.get().unwrap();
+    assert!(new.is_alive());
+    assert!(!new.equals(&old));
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(*surfaces.lock().unwrap(), 1);
+}
+
+#[test]
+fn reconnect_missing_global() {
+    let mut server = TestServer::new();
+    insert_compositor(&mut server);
+    let (_client, globals) = connect(&mut server);
+
+    let mut proxies = VirtualProxies::new(&globals);
+    let (surface, _) = register(&mut proxies);
+    let output = proxies.add(|globals| {
+        globals
+            .instantiate_exact::<WlOutput, _>(1, |output| output.implement(|_, _| {}, ()))
+            .ok()
+    });
+    assert!(surface.get().is_some());
+    assert!(output.get().is_none());
+
+    // the new compositor does not advertise a wl_compositor
+    let mut server2 = TestServer::new();
+    let (_client2, globals2) = connect(&mut server2);
+    assert_eq!(proxies.reconnect(&globals2), vec!["wl_compositor", "wl_surface", "wl_output"]);
+    assert!(surface.get().is_none());
+
+    // the objects are re-created once the global is back
+    assert!(proxies.reconnect(&globals).len() == 1);
+    assert!(surface.get().is_some());
+}
+
+#[test]
+fn reconnect_dropped_handles() {
+    let mut server = TestServer::new();
+    insert_compositor(&mut server);
+    let (_client, globals) = connect(&mut server);
+
+    let mut proxies = VirtualProxies::new(&globals);
+    let (surface, recipes) = register(&mut proxies);
+    let surface2 = surface.clone();
+    drop(surface);
+
+    // a clone of the handle keeps the object
+    assert!(proxies.reconnect(&globals).is_empty());
+    assert_eq!(*recipes.lock().unwrap(), 2);
+    assert!(surface2.get().is_some());
+
+    drop(surface2);
+    assert!(proxies.reconnect(&globals).is_empty());
+    assert_eq!(*recipes.lock().unwrap(), 2);
+}
@@ -0,0 +1,175 @@
+mod helpers;
+
+use helpers::{wayc, ways, TestClient, TestServer};
+
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::io::FromRawFd;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use wayc::protocol::wl_display::RequestsTrait;
+use wayc::protocol::wl_output::WlOutput as ClientOutput;
+use wayc::protocol::wl_shm::Format;
+use wayc::router::{ConnectionId, Router, SharedBuffer};
+use wayc::GlobalError;
+
+use ways::protocol::wl_output::WlOutput as ServerOutput;
+use ways::protocol::{wl_buffer, wl_shm, wl_shm_pool};
+use ways::Resource;
+
+// like `helpers::roundtrip()`, for a connection of a router
+fn roundtrip(router: &mut Router, id: ConnectionId, server: &mut TestServer) {
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    let token = router.event_queue(id).get_token();
+    router
+        .display(id)
+        .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+        .unwrap();
+    while !done.get() {
+        router.flush_all().unwrap();
+        ::std::thread::sleep(::std::time::Duration::from_millis(100));
+        server.answer();
+        ::std::thread::sleep(::std::time::Duration::from_millis(100));
+        let event_queue = router.event_queue(id);
+        event_queue.dispatch_pending().unwrap();
+        event_queue.prepare_read().unwrap().read_events().unwrap();
+        event_queue.dispatch_pending().unwrap();
+    }
+}
+
+fn connect(router: &mut Router, server: &mut TestServer) -> ConnectionId {
+    let client = TestClient::new(&server.socket_name);
+    let id = router.add(client.display, client.event_queue);
+    roundtrip(router, id, server);
+    id
+}
+
+// the contents of a pool, the width, height and stride of its buffer, and the buffer
+type ReceivedBuffers = Arc<Mutex<Vec<(Vec<u8>, i32, i32, i32, Resource<wl_buffer::WlBuffer>)>>>;
+
+// a wl_shm global storing the buffers it receives
+fn insert_shm(server: &mut TestServer) -> ReceivedBuffers {
+    let pools = Arc::new(Mutex::new(Vec::new()));
+    let pools2 = pools.clone();
+    server
+        .display
+        .create_global::<wl_shm::WlShm, _>(1, move |shm, _| {
+            let pools = pools2.clone();
+            shm.implement(
+                move |req, _| {
+                    let wl_shm::Request::CreatePool { id, fd, size } = req;
+                    let mut file = unsafe { File::from_raw_fd(fd) };
+                    file.seek(SeekFrom::Start(0)).unwrap();
+                    let mut contents = Vec::new();
+                    file.read_to_end(&mut contents).unwrap();
+                    assert_eq!(contents.len(), size as usize);
+                    let pools = pools.clone();
+                    id.implement(
+                        move |req, _| {
+                            if let wl_shm_pool::Request::CreateBuffer {
+                                id, width, height, stride, ..
+                            } = req
+                            {
+                                let buffer = id.implement(|_, _| {}, None::<fn(_)>, ());
+                                pools
+                                    .lock()
+                                    .unwrap()
+                                    .push((contents.clone(), width, height, stride, buffer));
+                            }
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    pools
+}
+
+#[test]
+fn router_rebind() {
+    let mut host = TestServer::new();
+    host.display.create_global::<ServerOutput, _>(3, |_, _| {});
+    let mut nested = TestServer::new();
+    nested.display.create_global::<ServerOutput, _>(2, |_, _| {});
+
+    let mut router = Router::new();
+    let host_id = connect(&mut router, &mut host);
+    let nested_id = connect(&mut router, &mut nested);
+    assert_eq!(router.connections().collect::<Vec<_>>(), [host_id, nested_id]);
+
+    let output = router
+        .globals(nested_id)
+        .instantiate_auto::<ClientOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let rebound = router
+        .rebind(&output, host_id, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    assert_eq!(rebound.version(), 2);
+    assert!(!rebound.equals(&output));
+
+    // the nested server does not have the version of the host
+    let output = router
+        .globals(host_id)
+        .instantiate_auto::<ClientOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    match router.rebind(&output, nested_id, |newp| newp.implement(|_, _| {}, ())) {
+        Err(GlobalError::VersionTooLow(2)) => {}
+        _ => panic!("The output was rebound."),
+    }
+}
+
+#[test]
+fn router_shared_buffer() {
+    let mut host = TestServer::new();
+    let host_pools = insert_shm(&mut host);
+    let mut nested = TestServer::new();
+    let nested_pools = insert_shm(&mut nested);
+
+    let mut router = Router::new();
+    let host_id = connect(&mut router, &mut host);
+    let nested_id = connect(&mut router, &mut nested);
+
+    let mut buffer = SharedBuffer::new(4, 2, Format::Argb8888).unwrap();
+    assert_eq!(buffer.stride(), 16);
+    buffer.data()[0] = 42;
+
+    let host_buffer = buffer.buffer(&mut router, host_id).unwrap();
+    roundtrip(&mut router, host_id, &mut host);
+    let nested_buffer = buffer.buffer(&mut router, nested_id).unwrap();
+    roundtrip(&mut router, nested_id, &mut nested);
+    // the buffers are only created once per connection
+    assert!(buffer.buffer(&mut router, host_id).unwrap().equals(&host_buffer));
+
+    for pools in &[&host_pools, &nested_pools] {
+        let pools = pools.lock().unwrap();
+        assert_eq!(pools.len(), 1);
+        let (ref contents, width, height, stride, _) = pools[0];
+        assert_eq!((width, height, stride), (4, 2, 16));
+        assert_eq!(contents.len(), 32);
+        assert_eq!(contents[0], 42);
+    }
+
+    // the buffer is released once both servers released it
+    assert!(!buffer.is_released());
+    host_pools.lock().unwrap()[0].4.send(wl_buffer::Event::Release);
+    roundtrip(&mut router, host_id, &mut host);
+    assert!(!buffer.is_released());
+    nested_pools.lock().unwrap()[0].4.send(wl_buffer::Event::Release);
+    roundtrip(&mut router, nested_id, &mut nested);
+    assert!(buffer.is_released());
+    // and held again when handed out
+    buffer.buffer(&mut router, host_id).unwrap();
+    assert!(!buffer.is_released());
+
+    buffer.forget(nested_id);
+    assert!(!nested_buffer.is_alive());
+    assert!(host_buffer.is_alive());
+    drop(buffer);
+    assert!(!host_buffer.is_alive());
+}
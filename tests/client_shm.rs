@@ -0,0 +1,234 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::FromRawFd;
+use std::sync::{Arc, Mutex};
+
+use wayc::protocol::wl_compositor::RequestsTrait as CompositorRequests;
+use wayc::protocol::wl_shm::Format;
+use wayc::protocol::wl_surface::RequestsTrait as SurfaceRequests;
+use wayc::shm::BufferPool;
+
+use ways::protocol::wl_buffer::{Event as BufferEvent, WlBuffer as ServerBuffer};
+use ways::Resource;
+
+struct ShmState {
+    pools: Vec<(File, i32)>,
+    // the buffers, with their pool, offset, width and height
+    buffers: Vec<(Resource<ServerBuffer>, usize, i32, i32, i32)>,
+    attached: Vec<Resource<ServerBuffer>>,
+}
+
+fn insert_globals(server: &mut TestServer) -> Arc<Mutex<ShmState>> {
+    use ways::protocol::{wl_compositor, wl_shm, wl_shm_pool, wl_surface};
+
+    let state = Arc::new(Mutex::new(ShmState {
+        pools: Vec::new(),
+        buffers: Vec::new(),
+        attached: Vec::new(),
+    }));
+
+    let compositor_state = state.clone();
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(1, move |compositor, _| {
+            let compositor_state = compositor_state.clone();
+            compositor.implement(
+                move |req, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = req {
+                        let surface_state = compositor_state.clone();
+                        id.implement(
+                            move |req, _| {
+                                if let wl_surface::Request::Attach { buffer: Some(buffer), .. } = req {
+                                    surface_state.lock().unwrap().attached.push(buffer);
+                                }
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let shm_state = state.clone();
+    server
+        .display
+        .create_global::<wl_shm::WlShm, _>(1, move |shm, _| {
+            let shm_state = shm_state.clone();
+            shm.implement(
+                move |req, _| {
+                    let wl_shm::Request::CreatePool { id, fd, size } = req;
+                    let pool_state = shm_state.clone();
+                    let pool = {
+                        let mut state = shm_state.lock().unwrap();
+                        state.pools.push((unsafe { File::from_raw_fd(fd) }, size));
+                        state.pools.len() - 1
+                    };
+                    id.implement(
+                        move |req, _| {
+                            if let wl_shm_pool::Request::CreateBuffer {
+                                id,
+                                offset,
+                                width,
+                                height,
+                                ..
+                            } = req
+                            {
+                                let buffer = id.implement(|_, _| {}, None::<fn(_)>, ());
+                                pool_state
+                                    .lock()
+                                    .unwrap()
+                                    .buffers
+                                    .push((buffer, pool, offset, width, height));
+                            }
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    state
+}
+
+#[test]
+fn buffer_pool_double_buffering() {
+    let mut server = TestServer::new();
+    let state = insert_globals(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let shm = manager
+        .instantiate_exact::<wayc::protocol::wl_shm::WlShm, _>(1, |shm| shm.implement(|_, _| {}, ()))
+        .unwrap();
+    let compositor = manager
+        .instantiate_exact::<wayc::protocol::wl_compositor::WlCompositor, _>(1, |comp| {
+            comp.implement(|_, _| {}, ())
+        }).unwrap();
+    let surface = compositor
+        .create_surface(|surface| surface.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let mut pool = BufferPool::new(&shm, 2);
+
+    // draw to both buffers
+    let mut ids = Vec::new();
+    for &color in &[0xFF00_00FFu32, 0xFF00_FF00] {
+        let mut buffer = pool.next_buffer(16, 8, Format::Argb8888).unwrap().unwrap();
+        assert_eq!(buffer.stride(), 64);
+        ids.push(buffer.buffer().id());
+        for pixel in buffer.pixels() {
+            *pixel = color;
+        }
+        buffer.attach(&surface, 0, 0);
+        surface.commit();
+    }
+    assert_eq!(pool.busy(), 2);
+    assert!(pool.next_buffer(16, 8, Format::Argb8888).unwrap().is_none());
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    {
+        let state = state.lock().unwrap();
+        assert_eq!(state.pools.len(), 1);
+        assert_eq!(state.buffers.len(), 2);
+        assert_eq!(state.attached.len(), 2);
+        let (ref file, size) = state.pools[0];
+        for (i, &(ref buffer, pool_index, offset, width, height)) in state.buffers.iter().enumerate() {
+            assert!(state.attached[i] == *buffer);
+            assert_eq!((pool_index, width, height), (0, 16, 8));
+            assert!(offset + 16 * 8 * 4 <= size);
+            let mut pixel = [0u8; 4];
+            file.read_at(&mut pixel, offset as u64 + 4 * 42).unwrap();
+            let expected = if i == 0 { 0xFF00_00FFu32 } else { 0xFF00_FF00 };
+            let value = pixel
+                .iter()
+                .enumerate()
+                .fold(0, |value, (i, &byte)| value | u32::from(byte) << (8 * i));
+            // the pixels are stored in native endianness
+            assert_eq!(u32::from_le(value), expected);
+        }
+        // release the first buffer
+        state.buffers[0].0.send(BufferEvent::Release);
+    }
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(pool.busy(), 1);
+    {
+        let buffer = pool.next_buffer(16, 8, Format::Argb8888).unwrap().unwrap();
+        // the same buffer object is reused
+        assert_eq!(buffer.buffer().id(), ids[0]);
+        buffer.attach(&surface, 0, 0);
+        surface.commit();
+    }
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.buffers.len(), 2);
+    assert!(state.attached[2] == state.buffers[0].0);
+}
+
+#[test]
+fn buffer_pool_grows() {
+    let mut server = TestServer::new();
+    let state = insert_globals(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let shm = manager
+        .instantiate_exact::<wayc::protocol::wl_shm::WlShm, _>(1, |shm| shm.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let mut pool = BufferPool::new(&shm, 3);
+    {
+        let mut buffer = pool.next_buffer(10, 10, Format::Xrgb8888).unwrap().unwrap();
+        assert_eq!(buffer.data().len(), 400);
+    }
+    // a smaller buffer fits in the current slots
+    {
+        let buffer = pool.next_buffer(5, 5, Format::Rgb565).unwrap().unwrap();
+        assert_eq!(buffer.stride(), 10);
+    }
+    // a larger one requires a new pool
+    {
+        let mut buffer = pool.next_buffer(100, 100, Format::Argb8888).unwrap().unwrap();
+        assert_eq!(buffer.pixels().len(), 10_000);
+    }
+    assert!(pool.next_buffer(10, 10, Format::Yuyv).is_err());
+    // neither the stride nor the size of the pool can overflow the i32 of the protocol
+    let err = pool.next_buffer(i32::max_value(), 1, Format::Argb8888).err().unwrap();
+    assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidInput);
+    let err = pool.next_buffer(20_000, 20_000, Format::Argb8888).err().unwrap();
+    assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidInput);
+    // and the current pool is kept
+    assert!(pool.next_buffer(100, 100, Format::Argb8888).unwrap().is_some());
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.pools.len(), 2);
+    assert!(state.pools[1].1 >= 3 * 100 * 100 * 4);
+    assert_eq!(state.buffers.len(), 3);
+    assert_eq!(state.buffers[2].1, 1);
+    // the buffers were recreated, the previous ones destroyed
+    assert!(!state.buffers[0].0.is_alive());
+    assert!(!state.buffers[1].0.is_alive());
+    assert!(state.buffers[2].0.is_alive());
+}
@@ -0,0 +1,263 @@
+extern crate nix;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::os::unix::io::{IntoRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+use wayc::protocol::wl_registry::WlRegistry;
+use wayc::protocol::wl_surface::{RequestsTrait as SurfaceRequests, WlSurface};
+use wayc::takeover::{TakeoverError, TakeoverObject, TakeoverState};
+
+use ways::protocol::{wl_compositor, wl_surface};
+
+// the ids of the surfaces of the client, and of the committed ones
+fn insert_compositor(server: &mut TestServer) -> Arc<Mutex<(Vec<u32>, Vec<u32>)>> {
+    let surfaces = Arc::new(Mutex::new((Vec::new(), Vec::new())));
+    let surfaces2 = surfaces.clone();
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(4, move |compositor, _| {
+            let surfaces = surfaces2.clone();
+            compositor.implement(
+                move |req, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = req {
+                        let commits = surfaces.clone();
+                        let surface = id.implement(
+                            move |req, surface: ways::Resource<_>| {
+                                if let wl_surface::Request::Commit = req {
+                                    commits.lock().unwrap().1.push(surface.id());
+                                }
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                        surfaces.lock().unwrap().0.push(surface.id());
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    surfaces
+}
+
+fn object(interface: &str, id: u32, version: u32) -> TakeoverObject {
+    TakeoverObject {
+        interface: interface.into(),
+        id,
+        version,
+    }
+}
+
+// the inode of the file of a fd, which tells whether the fd was reused by the tests running
+// in parallel after being closed
+fn inode(fd: RawFd) -> Option<u64> {
+    ::nix::sys::stat::fstat(fd).ok().map(|stat| stat.st_ino as u64)
+}
+
+#[test]
+fn takeover_state_serialization() {
+    let text = "7 wl_registry:2:1 wl_compositor:3:4";
+    let state = TakeoverState::deserialize(text).unwrap();
+    assert_eq!(state.fd(), 7);
+    assert_eq!(
+        state.objects(),
+        &[object("wl_registry", 2, 1), object("wl_compositor", 3, 4)][..]
+    );
+    assert_eq!(state.serialize(), text);
+    // the fd 7 is not the one of a connection
+    let _ = state.into_raw_fd();
+
+    for invalid in &["", "fd", "7 wl_registry:2", "7 wl_registry:2:1:0", "7 wl_registry:two:1"] {
+        match TakeoverState::deserialize(invalid) {
+            Err(TakeoverError::Invalid) => {}
+            other => panic!("{:?} was parsed as {:?}", invalid, other),
+        }
+    }
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn takeover_adopt_objects() {
+    let mut server = TestServer::new();
+    let surfaces = insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_exact::<WlCompositor, _>(4, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let state = client.display.prepare_takeover().unwrap();
+    assert_eq!(
+        state.objects(),
+        &[
+            object("wl_registry", 2, 1),
+            object("wl_compositor", 3, 4),
+            object("wl_surface", 4, 4),
+        ][..]
+    );
+    let text = state.serialize();
+
+    // the old process is replaced, without closing its copy of the socket
+    let _ = state.into_raw_fd();
+    drop(surface);
+    drop(compositor);
+    drop(manager);
+    drop(client);
+
+    let state = TakeoverState::deserialize(&text).unwrap();
+    let (display, event_queue, mut adoption) = unsafe { wayc::Display::adopt(state) }.unwrap();
+    let mut client = TestClient {
+        display,
+        event_queue,
+    };
+    // the interface must match
+    assert!(adoption.proxy::<WlSurface>(3).is_none());
+    adoption
+        .proxy::<WlRegistry>(2)
+        .unwrap()
+        .implement(|_, _| {}, ());
+    let compositor = adoption
+        .proxy::<WlCompositor>(3)
+        .unwrap()
+        .implement(|_, _| {}, ());
+    let surface = adoption
+        .proxy::<WlSurface>(4)
+        .unwrap()
+        .implement(|_, _| {}, ());
+    assert!(adoption.proxy::<WlSurface>(4).is_none());
+    adoption.finish().unwrap();
+    assert_eq!(compositor.version(), 4);
+    assert_eq!(client.display.live_objects::<WlSurface>(), 1);
+
+    // the connection goes on, without reusing the ids of the adopted objects
+    let new_surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    assert_eq!(new_surface.id(), 5);
+    surface.commit();
+    new_surface.commit();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let (ref created, ref committed) = *surfaces.lock().unwrap();
+    assert_eq!(created.len(), 2);
+    assert_eq!(committed, created);
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn takeover_pending_events() {
+    let mut server = TestServer::new();
+    insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let _manager = wayc::GlobalManager::new(&client.display);
+
+    client.display.flush().unwrap();
+    server.answer();
+    client.event_queue.prepare_read().unwrap().read_events().unwrap();
+
+    // the globals were read but not dispatched
+    match client.display.prepare_takeover() {
+        Err(TakeoverError::Pending) => {}
+        other => panic!("unexpected takeover: {:?}", other),
+    }
+
+    client.event_queue.dispatch_pending().unwrap();
+    let state = client.display.prepare_takeover().unwrap();
+    assert_eq!(state.objects(), &[object("wl_registry", 2, 1)][..]);
+    // without exec, the copy of the socket is closed with the state
+    let (fd, socket) = (state.fd(), inode(state.fd()));
+    drop(state);
+    assert_ne!(inode(fd), socket);
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn takeover_invalid_ids() {
+    let mut server = TestServer::new();
+
+    let mut client = TestClient::new(&server.socket_name);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the display, a duplicated id, the first id after the client ones, and ids larger than
+    // any map of libwayland
+    for objects in &[
+        "wl_registry:1:1",
+        "wl_registry:2:1 wl_compositor:2:4",
+        "wl_registry:4278190080:1",
+        "wl_registry:16000000:1",
+        "wl_registry:4294967295:1",
+    ] {
+        let state = client.display.prepare_takeover().unwrap();
+        let (fd, socket) = (state.fd(), inode(state.fd()));
+        let text = format!("{} {}", fd, objects);
+        let _ = state.into_raw_fd();
+        let state = TakeoverState::deserialize(&text).unwrap();
+        match unsafe { wayc::Display::adopt(state) } {
+            Err(TakeoverError::Invalid) => {}
+            Err(e) => panic!("{:?} was rejected with {:?}", objects, e),
+            Ok(_) => panic!("{:?} was adopted", objects),
+        }
+        // the copy of the socket was closed
+        assert_ne!(inode(fd), socket);
+    }
+
+    // the ids of the server namespace are adopted as well
+    let state = client.display.prepare_takeover().unwrap();
+    let text = format!("{} wl_registry:2:1 wl_callback:4278190082:1", state.fd());
+    let _ = state.into_raw_fd();
+    let state = TakeoverState::deserialize(&text).unwrap();
+    let (_display, _event_queue, adoption) = unsafe { wayc::Display::adopt(state) }.unwrap();
+    assert_eq!(adoption.remaining().len(), 2);
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn takeover_not_adopted() {
+    let mut server = TestServer::new();
+    insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let _manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let state = client.display.prepare_takeover().unwrap();
+    drop(client);
+
+    let (_display, _event_queue, adoption) = unsafe { wayc::Display::adopt(state) }.unwrap();
+    assert_eq!(adoption.remaining(), &[object("wl_registry", 2, 1)][..]);
+    match adoption.finish() {
+        Err(TakeoverError::NotAdopted(objects)) => assert_eq!(objects, [object("wl_registry", 2, 1)]),
+        other => panic!("unexpected adoption: {:?}", other),
+    }
+}
+
+#[cfg(feature = "native_lib")]
+#[test]
+fn takeover_unsupported() {
+    let mut server = TestServer::new();
+
+    let mut client = TestClient::new(&server.socket_name);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    match client.display.prepare_takeover() {
+        Err(TakeoverError::Unsupported) => {}
+        other => panic!("unexpected takeover: {:?}", other),
+    }
+}
@@ -0,0 +1,94 @@
+extern crate wayland_client as wayc;
+
+use wayc::protocol::wl_output::Transform;
+use wayc::transform::{compose, inverse, matrix2, transformed_size, Matrix3};
+
+const TRANSFORMS: [Transform; 8] = [
+    Transform::Normal,
+    Transform::_90,
+    Transform::_180,
+    Transform::_270,
+    Transform::Flipped,
+    Transform::Flipped90,
+    Transform::Flipped180,
+    Transform::Flipped270,
+];
+
+fn assert_close(a: (f64, f64), b: (f64, f64)) {
+    assert!(
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9,
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+#[test]
+fn rotations() {
+    // the x axis is rotated counter-clockwise, towards the top of the screen
+    assert_eq!(matrix2(Transform::_90), [[0.0, 1.0], [-1.0, 0.0]]);
+    assert_eq!(compose(Transform::_90, Transform::_90), Transform::_180);
+    assert_eq!(compose(Transform::_90, Transform::_180), Transform::_270);
+    assert_eq!(compose(Transform::Flipped, Transform::_90), Transform::Flipped90);
+    assert_eq!(compose(Transform::Flipped, Transform::Flipped), Transform::Normal);
+}
+
+#[test]
+fn inverses() {
+    for &t in &TRANSFORMS {
+        assert_eq!(compose(t, inverse(t)), Transform::Normal);
+        assert_eq!(compose(inverse(t), t), Transform::Normal);
+        let m = Matrix3::from_transform(t);
+        assert_eq!(m.inverse().unwrap(), Matrix3::from_transform(inverse(t)));
+    }
+    assert_eq!(Matrix3::scale(0.0, 1.0).inverse(), None);
+}
+
+#[test]
+fn transformed_rectangle() {
+    assert_eq!(transformed_size(Transform::_90, 200, 100), (100, 200));
+    assert_eq!(transformed_size(Transform::Flipped180, 200, 100), (200, 100));
+
+    // the corners of the rectangle stay in the transformed rectangle
+    for &t in &TRANSFORMS {
+        let m = Matrix3::from_transform_in(t, 200.0, 100.0);
+        let (w, h) = transformed_size(t, 200, 100);
+        for &(x, y) in &[(0.0, 0.0), (200.0, 0.0), (0.0, 100.0), (200.0, 100.0)] {
+            let (tx, ty) = m.apply(x, y);
+            assert!(tx == 0.0 || tx == f64::from(w), "{:?}: {}", t, tx);
+            assert!(ty == 0.0 || ty == f64::from(h), "{:?}: {}", t, ty);
+        }
+    }
+    // rotated counter-clockwise, the top-left corner ends up at the bottom-left
+    assert_close(Matrix3::from_transform_in(Transform::_90, 200.0, 100.0).apply(0.0, 0.0), (0.0, 200.0));
+}
+
+#[test]
+fn buffer_and_surface() {
+    // a 100x50 surface, rendered rotated into a 100x200 buffer with a scale of 2
+    let to_buffer = Matrix3::surface_to_buffer(Transform::_90, 2, 100.0, 50.0);
+    let to_surface = Matrix3::buffer_to_surface(Transform::_90, 2, 100.0, 200.0);
+    assert_close(to_buffer.apply(0.0, 0.0), (0.0, 200.0));
+    assert_close(to_buffer.apply(100.0, 50.0), (100.0, 0.0));
+    for &t in &TRANSFORMS {
+        let (bw, bh) = transformed_size(t, 200, 100);
+        let to_buffer = Matrix3::surface_to_buffer(t, 2, 100.0, 50.0);
+        let to_surface = Matrix3::buffer_to_surface(t, 2, f64::from(bw), f64::from(bh));
+        let roundtrip = to_surface.multiply(&to_buffer);
+        for &(x, y) in &[(0.0, 0.0), (10.0, 20.0), (100.0, 50.0)] {
+            assert_close(roundtrip.apply(x, y), (x, y));
+        }
+    }
+    assert_close(to_surface.apply(0.0, 200.0), (0.0, 0.0));
+}
+
+#[test]
+fn flips() {
+    let gl = Matrix3::flip_vertical(100.0);
+    assert_close(gl.apply(10.0, 0.0), (10.0, 100.0));
+    assert_eq!(gl.multiply(&gl), Matrix3::identity());
+    assert_eq!(
+        Matrix3::flip_horizontal(200.0),
+        Matrix3::from_transform_in(Transform::Flipped, 200.0, 100.0)
+    );
+}
@@ -0,0 +1,49 @@
+// The window handles are only available with the C implementation
+#![cfg(feature = "window_handle")]
+
+extern crate raw_window_handle;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle};
+
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+use wayc::window_handle::WaylandHandle;
+
+use ways::protocol::wl_compositor::WlCompositor as ServerCompositor;
+
+#[test]
+fn window_handle() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<ServerCompositor, _>(1, |compositor, _| {
+            compositor.implement(|_, _| {}, None::<fn(_)>, ());
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_exact::<WlCompositor, _>(1, |compositor| compositor.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|surface| surface.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let handle = WaylandHandle::new(&client.display, &surface);
+    assert!(handle.surface().equals(&surface));
+    match handle.raw_window_handle() {
+        RawWindowHandle::Wayland(window) => assert_eq!(window.surface, surface.c_ptr() as *mut _),
+        _ => panic!("not a wayland window handle"),
+    }
+    match handle.raw_display_handle() {
+        RawDisplayHandle::Wayland(display) => {
+            assert_eq!(display.display, client.display.get_display_ptr() as *mut _)
+        }
+        _ => panic!("not a wayland display handle"),
+    }
+}
@@ -0,0 +1,193 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use wayc::protocol::wl_shm::WlShm;
+use wayc::xcursor::{parse_xcursor, CursorTheme, XcursorImage};
+
+struct ShmState {
+    pools: Vec<File>,
+    // the buffers, with their pool, offset, width, height and stride
+    buffers: Vec<(usize, i32, i32, i32, i32)>,
+}
+
+fn insert_shm(server: &mut TestServer) -> Arc<Mutex<ShmState>> {
+    use ways::protocol::{wl_shm, wl_shm_pool};
+
+    let state = Arc::new(Mutex::new(ShmState {
+        pools: Vec::new(),
+        buffers: Vec::new(),
+    }));
+    let shm_state = state.clone();
+    server
+        .display
+        .create_global::<wl_shm::WlShm, _>(1, move |shm, _| {
+            let shm_state = shm_state.clone();
+            shm.implement(
+                move |req, _| {
+                    let wl_shm::Request::CreatePool { id, fd, .. } = req;
+                    let pool_state = shm_state.clone();
+                    let pool = {
+                        let mut state = shm_state.lock().unwrap();
+                        state.pools.push(unsafe { File::from_raw_fd(fd) });
+                        state.pools.len() - 1
+                    };
+                    id.implement(
+                        move |req, _| {
+                            if let wl_shm_pool::Request::CreateBuffer {
+                                id,
+                                offset,
+                                width,
+                                height,
+                                stride,
+                                ..
+                            } = req
+                            {
+                                id.implement(|_, _| {}, None::<fn(_)>, ());
+                                pool_state
+                                    .lock()
+                                    .unwrap()
+                                    .buffers
+                                    .push((pool, offset, width, height, stride));
+                            }
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    state
+}
+
+// an Xcursor file holding these images, the pixels of which are all set to their delay
+fn xcursor_file(images: &[(u32, u32, u32)]) -> Vec<u8> {
+    let mut words = vec![0x7275_6358, 16, 0x1_0000, images.len() as u32];
+    let mut position = 16 + 12 * images.len() as u32;
+    for &(size, dimension, _) in images {
+        words.extend_from_slice(&[0xfffd_0002, size, position]);
+        position += 36 + 4 * dimension * dimension;
+    }
+    for &(size, dimension, delay) in images {
+        words.extend_from_slice(&[36, 0xfffd_0002, size, 1, dimension, dimension, dimension / 2, 0, delay]);
+        words.extend(vec![delay; (dimension * dimension) as usize]);
+    }
+    words
+        .iter()
+        .flat_map(|word| (0..4).map(move |i| (word >> (8 * i)) as u8))
+        .collect()
+}
+
+fn write_theme(dir: &Path, theme: &str, inherits: Option<&str>, cursors: &[(&str, Vec<u8>)]) {
+    let theme = dir.join(theme);
+    fs::create_dir_all(theme.join("cursors")).unwrap();
+    if let Some(inherits) = inherits {
+        let mut index = File::create(theme.join("index.theme")).unwrap();
+        write!(index, "[Icon Theme]\nInherits = {}\n", inherits).unwrap();
+    }
+    for &(name, ref data) in cursors {
+        File::create(theme.join("cursors").join(name))
+            .unwrap()
+            .write_all(data)
+            .unwrap();
+    }
+}
+
+fn main() {
+    // decoding
+    let data = xcursor_file(&[(16, 2, 0), (32, 4, 50)]);
+    let images = parse_xcursor(&data).unwrap();
+    assert_eq!(images.len(), 2);
+    assert_eq!(
+        images[0],
+        XcursorImage {
+            size: 16,
+            width: 2,
+            height: 2,
+            xhot: 1,
+            yhot: 0,
+            delay: 0,
+            pixels: vec![0; 16],
+        }
+    );
+    assert_eq!((images[1].size, images[1].width, images[1].delay), (32, 4, 50));
+    assert!(parse_xcursor(&data[..data.len() - 1]).is_none());
+    assert!(parse_xcursor(b"not a cursor").is_none());
+
+    // a theme inheriting its cursors, with an animated one
+    let dir = env::temp_dir().join(format!("wayland-rs-xcursor-{}", ::std::process::id()));
+    write_theme(&dir, "child", Some("base"), &[]);
+    write_theme(
+        &dir,
+        "base",
+        None,
+        &[("left_ptr", xcursor_file(&[(16, 2, 0), (32, 4, 50), (32, 4, 30)]))],
+    );
+    write_theme(&dir, "default", None, &[("text", xcursor_file(&[(24, 3, 0)]))]);
+    env::set_var("XCURSOR_PATH", &dir);
+    env::set_var("XCURSOR_THEME", "child");
+    env::set_var("XCURSOR_SIZE", "30");
+
+    let mut server = TestServer::new();
+    let state = insert_shm(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let shm = manager
+        .instantiate_exact::<WlShm, _>(1, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let mut theme = CursorTheme::load(&shm);
+    assert_eq!((theme.name(), theme.size()), ("child", 30));
+
+    {
+        // the images of the closest size are loaded
+        let cursor = theme.get_cursor("left_ptr").unwrap();
+        assert_eq!(cursor.name(), "left_ptr");
+        let images = cursor.images();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].dimensions(), (4, 4));
+        assert_eq!(images[0].hotspot(), (2, 0));
+        assert_eq!((images[0].delay(), images[1].delay()), (50, 30));
+        assert_eq!(cursor.frame_and_duration(0), (0, 50));
+        assert_eq!(cursor.frame_and_duration(60), (1, 20));
+        assert_eq!(cursor.frame_and_duration(90), (0, 40));
+    }
+    // the cursors missing from the theme are taken from `default`
+    assert_eq!(theme.get_cursor("text").unwrap().frame_and_duration(1000), (0, 0));
+    assert!(theme.get_cursor("missing").is_none());
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    {
+        let state = state.lock().unwrap();
+        assert_eq!(state.pools.len(), 2);
+        assert_eq!(
+            state.buffers,
+            [(0, 0, 4, 4, 16), (0, 64, 4, 4, 16), (1, 0, 3, 3, 12)]
+        );
+        // the pixels of the second frame
+        let mut pixel = [0u8; 4];
+        state.pools[0].read_at(&mut pixel, 64).unwrap();
+        assert_eq!(pixel, [30, 0, 0, 0]);
+    }
+
+    // the cursors are only loaded once
+    theme.get_cursor("left_ptr").unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(state.lock().unwrap().buffers.len(), 3);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -118,3 +118,147 @@ fn client_destructor_cleanup() {
 
     assert!(*destructor_called.lock().unwrap());
 }
+
+#[test]
+fn assigned_destructors() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let calls_global = calls.clone();
+    let outputs = Arc::new(Mutex::new(Vec::new()));
+    let outputs_global = outputs.clone();
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<ServerOutput, _>(3, move |newo, _| {
+            let calls_resource = calls_global.clone();
+            let output = newo.implement(
+                |_, _| {},
+                Some(move |_| {
+                    let thread = ::std::thread::current().id();
+                    calls_resource.lock().unwrap().push(("implementation", thread));
+                }),
+                (),
+            );
+            outputs_global.lock().unwrap().push(output);
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let output = manager
+        .instantiate_auto::<WlOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    manager
+        .instantiate_auto::<WlOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the destructors can be assigned from any thread
+    let server_outputs = outputs.lock().unwrap().clone();
+    let calls_thread = calls.clone();
+    ::std::thread::spawn(move || {
+        for &name in &["first", "second"] {
+            let calls_destructor = calls_thread.clone();
+            assert!(server_outputs[0].assign_destructor(move |resource| {
+                assert!(!resource.is_alive());
+                calls_destructor.lock().unwrap().push((name, ::std::thread::current().id()));
+            }));
+        }
+        let calls_destructor = calls_thread.clone();
+        assert!(server_outputs[1].assign_destructor(move |_| {
+            calls_destructor.lock().unwrap().push(("cleanup", ::std::thread::current().id()));
+        }));
+    }).join()
+    .unwrap();
+
+    // destroyed by a request
+    output.release();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let main_thread = ::std::thread::current().id();
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [
+            ("implementation", main_thread),
+            ("first", main_thread),
+            ("second", main_thread)
+        ]
+    );
+    assert!(!outputs.lock().unwrap()[0].assign_destructor(|_| panic!("Destructor of a dead resource run.")));
+
+    // destroyed by the disconnection of the client
+    ::std::mem::drop(output);
+    ::std::mem::drop(manager);
+    ::std::mem::drop(client);
+
+    server.answer();
+
+    assert_eq!(
+        calls.lock().unwrap()[3..],
+        [("implementation", main_thread), ("cleanup", main_thread)]
+    );
+}
+
+#[test]
+fn assigned_destructors_race() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let outputs = Arc::new(Mutex::new(Vec::new()));
+    let outputs_global = outputs.clone();
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<ServerOutput, _>(3, move |newo, _| {
+            let output = newo.implement(|_, _| {}, None::<fn(_)>, ());
+            outputs_global.lock().unwrap().push(output);
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let client_outputs = (0..100)
+        .map(|_| {
+            manager
+                .instantiate_auto::<WlOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+                .unwrap()
+        }).collect::<Vec<_>>();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the destructors assigned while the resources are destroyed are either refused or run
+    let assigned = Arc::new(AtomicUsize::new(0));
+    let run = Arc::new(AtomicUsize::new(0));
+    let server_outputs = outputs.lock().unwrap().clone();
+    let (thread_assigned, thread_run) = (assigned.clone(), run.clone());
+    let thread = ::std::thread::spawn(move || {
+        for output in server_outputs.iter().rev() {
+            let run = thread_run.clone();
+            if output.assign_destructor(move |_| {
+                run.fetch_add(1, Ordering::SeqCst);
+            }) {
+                thread_assigned.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    });
+    for output in &client_outputs {
+        output.release();
+        client.display.flush().unwrap();
+        server.answer();
+    }
+    thread.join().unwrap();
+
+    // the remaining resources are destroyed with their client
+    ::std::mem::drop(client_outputs);
+    ::std::mem::drop(manager);
+    ::std::mem::drop(client);
+    server.answer();
+
+    assert_eq!(run.load(Ordering::SeqCst), assigned.load(Ordering::SeqCst));
+}
@@ -0,0 +1,218 @@
+mod helpers;
+
+extern crate wayland_commons;
+
+use helpers::{ways, TestServer};
+
+use std::ffi::CString;
+use std::fs::File;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+
+use wayland_commons::dynamic::{DynError, DynObjectMap, Protocol, Side};
+use wayland_commons::socket::{BufferedSocket, Socket};
+use wayland_commons::trace::Direction;
+use wayland_commons::wire::{Argument, ArgumentType, Message};
+
+use ways::protocol::wl_output::{Event as OutputEvent, WlOutput as ServerOutput};
+
+fn core_protocol() -> Protocol {
+    Protocol::parse(File::open("./wayland-client/wayland.xml").unwrap()).unwrap()
+}
+
+#[test]
+fn parse_core_protocol() {
+    let protocol = core_protocol();
+    assert_eq!(protocol.name, "wayland");
+
+    let registry = protocol.interface("wl_registry").unwrap();
+    assert_eq!(registry.version, 1);
+    let (opcode, bind) = registry.message("bind", Side::Client, Direction::Sent).unwrap();
+    assert_eq!(opcode, 0);
+    // the generic new_id is preceded by the interface and version of the object
+    assert_eq!(
+        bind.signature(),
+        &[
+            ArgumentType::Uint,
+            ArgumentType::Str,
+            ArgumentType::Uint,
+            ArgumentType::NewId
+        ]
+    );
+    let names = bind.args.iter().map(|arg| &arg.name[..]).collect::<Vec<_>>();
+    assert_eq!(names, ["name", "interface", "version", "id"]);
+
+    let surface = protocol.interface("wl_surface").unwrap();
+    let (opcode, attach) = surface.message("attach", Side::Client, Direction::Sent).unwrap();
+    assert_eq!(opcode, 1);
+    assert_eq!(attach.args[0].interface.as_ref().map(|s| &s[..]), Some("wl_buffer"));
+    assert!(attach.args[0].allow_null);
+    let (_, damage_buffer) = surface.message("damage_buffer", Side::Client, Direction::Sent).unwrap();
+    assert_eq!(damage_buffer.since, 4);
+    assert!(surface.message("destroy", Side::Client, Direction::Sent).unwrap().1.destructor);
+
+    let callback = protocol.interface("wl_callback").unwrap();
+    assert!(callback.message("done", Side::Client, Direction::Received).unwrap().1.destructor);
+}
+
+#[test]
+fn parse_invalid_protocol() {
+    let xml = r#"<protocol name="test"><interface name="test_iface" version="1">
+        <request name="req"><arg name="a" type="float"/></request>
+    </interface></protocol>"#;
+    assert!(Protocol::parse(xml.as_bytes()).is_err());
+    assert!(Protocol::parse(&b"<protocol name=\"test\">"[..]).is_err());
+    assert!(Protocol::parse(&b"<interface name=\"test\" version=\"1\"/>"[..]).is_err());
+}
+
+#[test]
+fn build_checks_arguments() {
+    let mut map = DynObjectMap::new(&[core_protocol()], Side::Client);
+    assert_eq!(
+        map.build(1, "sync", vec![Argument::Uint(2)], Direction::Sent)
+            .unwrap_err(),
+        DynError::InvalidArguments
+    );
+    assert_eq!(
+        map.build(1, "frobnicate", vec![], Direction::Sent)
+            .unwrap_err(),
+        DynError::UnknownMessage {
+            interface: "wl_display".into(),
+            message: "frobnicate".into(),
+        }
+    );
+    assert_eq!(
+        map.build(2, "sync", vec![Argument::NewId(3)], Direction::Sent)
+            .unwrap_err(),
+        DynError::UnknownObject(2)
+    );
+    let msg = map
+        .build(1, "sync", vec![Argument::NewId(2)], Direction::Sent)
+        .unwrap();
+    assert_eq!(msg.to_string(), "wl_display@1.sync(new id @2)");
+    assert_eq!(map.object(2).unwrap().0.name, "wl_callback");
+    // the id is now in use
+    assert_eq!(
+        map.build(1, "sync", vec![Argument::NewId(2)], Direction::Sent)
+            .unwrap_err(),
+        DynError::InvalidNewId(2)
+    );
+}
+
+#[test]
+fn server_side_messages() {
+    let mut map = DynObjectMap::new(&[core_protocol()], Side::Server);
+    // a server receives requests and sends events
+    assert_eq!(map.signature(1, 0, Direction::Received), Some(&[ArgumentType::NewId][..]));
+    assert_eq!(map.signature(1, 1, Direction::Sent), Some(&[ArgumentType::Uint][..]));
+    let msg = map
+        .receive(
+            Message {
+                sender_id: 1,
+                opcode: 1,
+                args: vec![Argument::NewId(2)],
+            },
+            Direction::Received,
+        ).unwrap();
+    assert_eq!(msg.to_string(), "wl_display@1.get_registry(new id @2)");
+    assert_eq!(map.object(2).unwrap().0.name, "wl_registry");
+    let msg = map
+        .build(1, "delete_id", vec![Argument::Uint(2)], Direction::Sent)
+        .unwrap();
+    assert_eq!(msg.to_string(), "wl_display@1.delete_id(2)");
+    assert!(map.object(2).is_none());
+}
+
+#[test]
+fn dynamic_client() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |output, _| {
+        let output = output.implement(|_, _| {}, None::<fn(_)>, ());
+        output.send(OutputEvent::Scale { factor: 2 });
+        output.send(OutputEvent::Done);
+    });
+
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+    let _client = unsafe { server.display.create_client(server_socket.into_raw_fd()) };
+    let mut socket = BufferedSocket::new(unsafe { Socket::from_raw_fd(client_socket.into_raw_fd()) });
+
+    let mut map = DynObjectMap::new(&[core_protocol()], Side::Client);
+    let mut events = Vec::new();
+
+    let mut roundtrip = |map: &mut DynObjectMap, socket: &mut BufferedSocket, requests: Vec<_>| {
+        for (id, name, args) in requests {
+            let msg = map.build(id, name, args, Direction::Sent).unwrap();
+            socket.write_message(&msg.into_raw()).unwrap();
+        }
+        socket.flush().unwrap();
+        server.answer();
+        socket.fill_incoming_buffers().unwrap();
+        let mut received = Vec::new();
+        socket
+            .read_messages(
+                |id, opcode| map.signature(id, opcode, Direction::Received),
+                |msg| {
+                    received.push(msg);
+                    true
+                },
+            ).unwrap()
+            .unwrap();
+        let received = received
+            .into_iter()
+            .map(|msg| map.receive(msg, Direction::Received).unwrap())
+            .collect::<Vec<_>>();
+        events.extend(received.iter().map(|msg| msg.to_string()));
+        received
+    };
+
+    let received = roundtrip(
+        &mut map,
+        &mut socket,
+        vec![
+            (1, "get_registry", vec![Argument::NewId(2)]),
+            (1, "sync", vec![Argument::NewId(3)]),
+        ],
+    );
+    let global = received
+        .iter()
+        .find(|msg| msg.desc().name == "global")
+        .unwrap();
+    assert_eq!(
+        global.arg("interface"),
+        Some(&Argument::Str(CString::new("wl_output").unwrap()))
+    );
+    let name = match global.arg("name") {
+        Some(&Argument::Uint(name)) => name,
+        _ => panic!("Invalid global event."),
+    };
+    // the callback was destroyed by its event, then its id released by the server
+    assert!(map.object(3).is_none());
+
+    roundtrip(
+        &mut map,
+        &mut socket,
+        vec![(
+            2,
+            "bind",
+            vec![
+                Argument::Uint(name),
+                Argument::Str(CString::new("wl_output").unwrap()),
+                Argument::Uint(2),
+                Argument::NewId(3),
+            ],
+        )],
+    );
+    let (interface, version) = map.object(3).unwrap();
+    assert_eq!((&interface.name[..], version), ("wl_output", 2));
+
+    assert_eq!(
+        events,
+        [
+            format!("wl_registry@2.global({}, \"wl_output\", 2)", name),
+            "wl_callback@3.done(0)".into(),
+            "wl_display@1.delete_id(3)".into(),
+            "wl_output@3.scale(2)".into(),
+            "wl_output@3.done()".into(),
+        ]
+    );
+}
@@ -1,3 +1,5 @@
+extern crate nix;
+
 mod helpers;
 
 use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
@@ -106,6 +108,50 @@ fn global_manager_cb() {
     assert!(*counter.lock().unwrap() == 3);
 }
 
+#[test]
+fn denied_global() {
+    use wayc::protocol::wl_display::RequestsTrait as DisplayRequests;
+    use wayc::protocol::wl_output::WlOutput;
+    use wayc::protocol::wl_registry::Event as RegistryEvent;
+    use wayc::GlobalError;
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerCompositor, _>(1, |_, _| {});
+
+    let mut client = TestClient::new(&server.socket_name);
+    client.display.deny_global("wl_output");
+    let manager = wayc::GlobalManager::new(&client.display);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events2 = events.clone();
+    client
+        .display
+        .get_registry(|newp| {
+            newp.implement(
+                move |event, _| match event {
+                    RegistryEvent::Global { interface, .. } => events2.lock().unwrap().push(interface),
+                    RegistryEvent::GlobalRemove { name } => {
+                        events2.lock().unwrap().push(format!("removed {}", name))
+                    }
+                },
+                (),
+            )
+        }).unwrap();
+
+    let output = server.display.create_global::<ServerOutput, _>(1, |_, _| {});
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(manager.list(), vec![(1, "wl_compositor".into(), 1)]);
+    assert!(
+        manager.instantiate_auto::<WlOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+            == Err(GlobalError::Missing)
+    );
+
+    // the removal of the denied global is hidden too
+    output.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(*events.lock().unwrap(), vec!["wl_compositor".to_string()]);
+}
+
 #[test]
 fn auto_instanciate() {
     use wayc::protocol::wl_compositor::WlCompositor;
@@ -145,6 +191,41 @@ fn auto_instanciate() {
     );
 }
 
+#[test]
+fn range_instanciate() {
+    use wayc::protocol::wl_compositor::WlCompositor;
+    use wayc::protocol::wl_output::WlOutput;
+    use wayc::GlobalError;
+
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerCompositor, _>(3, |_, _| {});
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // capped by the server
+    let compositor = manager
+        .instantiate_range::<WlCompositor, _>(1, 4, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    assert!(compositor.version() == 3);
+    // capped by the client
+    let compositor = manager
+        .instantiate_range::<WlCompositor, _>(1, 2, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    assert!(compositor.version() == 2);
+
+    assert!(
+        manager.instantiate_range::<WlCompositor, _>(4, 4, |newp| newp.implement(|_, _| {}, ()))
+            == Err(GlobalError::VersionTooLow(3))
+    );
+    assert!(
+        manager.instantiate_range::<WlOutput, _>(1, 3, |newp| newp.implement(|_, _| {}, ()))
+            == Err(GlobalError::Missing)
+    );
+}
+
 #[test]
 #[should_panic]
 fn wrong_version_create_global() {
@@ -278,3 +359,105 @@ fn two_step_binding() {
 
     roundtrip(&mut client, &mut server).unwrap();
 }
+
+#[test]
+fn fixes_destroy_registry() {
+    use wayc::protocol::wl_display::RequestsTrait as DisplayRequests;
+    use wayc::protocol::wl_fixes::{RequestsTrait as FixesRequests, WlFixes};
+    use wayc::protocol::wl_registry::{self, RequestsTrait as RegistryRequests};
+
+    let mut server = TestServer::new();
+    server.display.create_fixes_global();
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = Arc::new(Mutex::new(0));
+    let registry_globals = globals.clone();
+    let registry = client
+        .display
+        .get_registry(move |newp| {
+            newp.implement(
+                move |evt, _| {
+                    if let wl_registry::Event::Global { .. } = evt {
+                        *registry_globals.lock().unwrap() += 1;
+                    }
+                },
+                (),
+            )
+        }).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(*globals.lock().unwrap(), 1);
+
+    let fixes = registry
+        .bind::<WlFixes, _>(1, 1, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    fixes.destroy_registry(&registry);
+    assert!(!registry.is_alive());
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the destroyed registry is no longer notified of new globals
+    server.display.create_global::<ServerCompositor, _>(1, |_, _| {});
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(*globals.lock().unwrap(), 1);
+
+    // but a new one is
+    let manager = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(manager.list().len(), 2);
+
+    fixes.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "native_lib"))]
+fn announce_globals_small_socket_buffer() {
+    use nix::sys::socket::{setsockopt, sockopt};
+    use std::io;
+    use std::os::unix::io::{AsRawFd, IntoRawFd};
+    use std::os::unix::net::UnixStream;
+
+    let mut server = TestServer::new();
+    for _ in 0..300 {
+        server.display.create_global::<ServerOutput, _>(3, |_, _| {});
+    }
+
+    // the announcements do not fit at once in the socket of the client
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+    setsockopt(server_socket.as_raw_fd(), sockopt::SndBuf, &4096).unwrap();
+    unsafe { server.display.create_client(server_socket.into_raw_fd()) };
+    let mut client = unsafe { TestClient::from_fd(client_socket.into_raw_fd()) };
+    let manager = wayc::GlobalManager::new(&client.display);
+    client.display.flush().unwrap();
+
+    let mut created = false;
+    for _ in 0..100 {
+        if manager.list().len() == 301 {
+            break;
+        }
+        server.answer();
+        if !created {
+            // created while the announcement is still in progress
+            server.display.create_global::<ServerCompositor, _>(1, |_, _| {});
+            created = true;
+        }
+        if let Some(guard) = client.event_queue.prepare_read() {
+            match guard.read_events() {
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("Failed to read events: {:?}", e),
+            }
+        }
+        client.event_queue.dispatch_pending().unwrap();
+    }
+
+    // all the globals were received, exactly once
+    let mut globals = manager.list();
+    assert_eq!(globals.len(), 301);
+    globals.sort();
+    for (i, &(id, ref interface, _)) in globals.iter().enumerate() {
+        assert_eq!(id, i as u32 + 1);
+        assert_eq!(interface, if id == 301 { "wl_compositor" } else { "wl_output" });
+    }
+}
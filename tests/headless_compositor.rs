@@ -0,0 +1,261 @@
+extern crate nix;
+extern crate wayland_protocols;
+extern crate wayland_server;
+
+mod helpers;
+
+#[path = "../examples/headless-compositor/compositor.rs"]
+mod compositor;
+
+use helpers::{wayc, ways, TestClient};
+
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::io;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use wayc::protocol::wl_callback::Event as CallbackEvent;
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+use wayc::protocol::wl_shm::{Format, WlShm};
+use wayc::protocol::wl_surface::{RequestsTrait as SurfaceRequests, WlSurface};
+use wayc::shm::BufferPool;
+use wayc::{GlobalManager, Proxy};
+
+use wayland_protocols::xdg_shell::client::xdg_surface::{
+    self, RequestsTrait as XdgSurfaceRequests, XdgSurface,
+};
+use wayland_protocols::xdg_shell::client::xdg_toplevel::RequestsTrait as ToplevelRequests;
+use wayland_protocols::xdg_shell::client::xdg_wm_base::{RequestsTrait as WmBaseRequests, XdgWmBase};
+use wayland_protocols::xdg_shell::{ConfigureError, ConfigureTracker};
+
+use compositor::{Contents, State};
+
+// the example compositor, dispatched by the tests
+struct Headless {
+    event_loop: ways::calloop::EventLoop<State>,
+    display: ways::Display,
+    state: State,
+    socket_name: OsString,
+}
+
+impl Headless {
+    fn new() -> Headless {
+        let event_loop = ways::calloop::EventLoop::<State>::new().unwrap();
+        let mut display = ways::Display::new(event_loop.handle());
+        let socket_name = display.add_socket_auto().unwrap();
+        compositor::create_globals(&mut display);
+        Headless {
+            event_loop,
+            display,
+            state: State::new(),
+            socket_name,
+        }
+    }
+
+    fn roundtrip(&mut self, client: &mut TestClient) -> io::Result<()> {
+        use wayc::protocol::wl_display::RequestsTrait;
+        let done = Rc::new(Cell::new(false));
+        let done2 = done.clone();
+        let token = client.event_queue.get_token();
+        client
+            .display
+            .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+            .unwrap();
+        while !done.get() {
+            client.display.flush()?;
+            for _ in 0..2 {
+                self.event_loop
+                    .dispatch(Some(Duration::from_millis(10)), &mut self.state)
+                    .unwrap();
+                self.display.flush_clients();
+            }
+            client.event_queue.dispatch_pending()?;
+            client.event_queue.prepare_read().unwrap().read_events()?;
+            client.event_queue.dispatch_pending()?;
+        }
+        Ok(())
+    }
+}
+
+// a toplevel, with the serial of its last configure
+fn create_toplevel(
+    manager: &GlobalManager,
+    title: &str,
+) -> (Proxy<WlSurface>, Proxy<XdgSurface>, Arc<Mutex<Option<u32>>>) {
+    let compositor = manager
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let wm_base = manager
+        .instantiate_auto::<XdgWmBase, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let configure = Arc::new(Mutex::new(None));
+    let configure2 = configure.clone();
+    let xdg_surface = wm_base
+        .get_xdg_surface(&surface, |newp| {
+            newp.implement(
+                move |event, _| {
+                    let xdg_surface::Event::Configure { serial } = event;
+                    *configure2.lock().unwrap() = Some(serial);
+                },
+                (),
+            )
+        }).unwrap();
+    let toplevel = xdg_surface
+        .get_toplevel(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    toplevel.set_title(title).unwrap();
+    surface.commit();
+    (surface, xdg_surface, configure)
+}
+
+#[test]
+fn headless_window() {
+    let mut headless = Headless::new();
+
+    let mut client = TestClient::new(&headless.socket_name);
+    let manager = GlobalManager::new(&client.display);
+
+    headless.roundtrip(&mut client).unwrap();
+
+    let mut interfaces = manager.list().into_iter().map(|(_, i, _)| i).collect::<Vec<_>>();
+    interfaces.sort();
+    assert_eq!(interfaces, ["wl_compositor", "wl_seat", "wl_shm", "xdg_wm_base"]);
+
+    let (surface, xdg_surface, configure) = create_toplevel(&manager, "window");
+
+    headless.roundtrip(&mut client).unwrap();
+
+    xdg_surface.ack_configure(configure.lock().unwrap().unwrap());
+    let shm = manager
+        .instantiate_auto::<WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let mut pool = BufferPool::new(&shm, 1);
+    {
+        let mut buffer = pool.next_buffer(4, 2, Format::Argb8888).unwrap().unwrap();
+        for (i, pixel) in buffer.pixels().iter_mut().enumerate() {
+            *pixel = i as u32;
+        }
+        buffer.attach(&surface, 0, 0);
+    }
+    let done = Arc::new(Mutex::new(None));
+    let done2 = done.clone();
+    surface
+        .frame(|newp| {
+            newp.implement(
+                move |event, _| {
+                    let CallbackEvent::Done { callback_data } = event;
+                    *done2.lock().unwrap() = Some(callback_data);
+                },
+                (),
+            )
+        }).unwrap();
+    surface.commit();
+
+    headless.roundtrip(&mut client).unwrap();
+
+    // the buffer was copied and released
+    assert_eq!(pool.busy(), 0);
+    {
+        let windows = headless.state.windows();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].title, "window");
+        let data = (0..8u32).flat_map(|i| (0..4).map(move |b| (i >> (8 * b)) as u8)).collect();
+        assert_eq!(
+            windows[0].contents,
+            Some(Contents {
+                width: 4,
+                height: 2,
+                data,
+            })
+        );
+    }
+    assert_eq!(*done.lock().unwrap(), None);
+
+    assert!(headless.state.send_frames(42));
+    headless.roundtrip(&mut client).unwrap();
+
+    assert_eq!(*done.lock().unwrap(), Some(42));
+    assert!(!headless.state.send_frames(43));
+}
+
+#[test]
+fn headless_unconfigured_buffer() {
+    let mut headless = Headless::new();
+
+    let mut client = TestClient::new(&headless.socket_name);
+    let manager = GlobalManager::new(&client.display);
+
+    headless.roundtrip(&mut client).unwrap();
+
+    let (surface, xdg_surface, _) = create_toplevel(&manager, "window");
+    let shm = manager
+        .instantiate_auto::<WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let mut pool = BufferPool::new(&shm, 1);
+    pool.next_buffer(4, 2, Format::Argb8888)
+        .unwrap()
+        .unwrap()
+        .attach(&surface, 0, 0);
+    surface.commit();
+
+    assert!(headless.roundtrip(&mut client).is_err());
+
+    let error = client.display.protocol_error().unwrap();
+    assert_eq!(error.code, xdg_surface::Error::UnconfiguredBuffer.to_raw());
+    assert_eq!(error.object_id, xdg_surface.id());
+}
+
+#[test]
+fn headless_configure_tracker() {
+    let mut headless = Headless::new();
+
+    let mut client = TestClient::new(&headless.socket_name);
+    let manager = GlobalManager::new(&client.display);
+
+    headless.roundtrip(&mut client).unwrap();
+
+    let (surface, xdg_surface, configure) = create_toplevel(&manager, "window");
+    let mut tracker = ConfigureTracker::new(xdg_surface, surface.clone());
+    assert_eq!(tracker.ack(), Err(ConfigureError::NoPendingConfigure));
+
+    headless.roundtrip(&mut client).unwrap();
+
+    let serial = configure.lock().unwrap().unwrap();
+    tracker.configure(serial);
+    assert!(!tracker.is_configured());
+    assert_eq!(tracker.check_commit(), Err(ConfigureError::UnackedConfigure(serial)));
+
+    // the ack is sent along with the commit, so that the buffer is accepted
+    let shm = manager
+        .instantiate_auto::<WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let mut pool = BufferPool::new(&shm, 1);
+    pool.next_buffer(4, 2, Format::Argb8888)
+        .unwrap()
+        .unwrap()
+        .attach(&surface, 0, 0);
+    assert_eq!(tracker.commit(), Some(serial));
+    assert!(tracker.is_configured());
+    assert_eq!(tracker.pending(), None);
+    assert_eq!(tracker.check_commit(), Ok(()));
+
+    headless.roundtrip(&mut client).unwrap();
+    assert!(client.display.protocol_error().is_none());
+    assert!(headless.state.windows()[0].contents.is_some());
+
+    // a configure acked for a commit done without the tracker
+    tracker.configure(serial + 1);
+    assert_eq!(tracker.ack(), Ok(serial + 1));
+    tracker.configure(serial + 2);
+    assert_eq!(tracker.ack(), Err(ConfigureError::AckWithoutCommit(serial + 1)));
+    assert_eq!(tracker.check_commit(), Err(ConfigureError::UnackedConfigure(serial + 2)));
+    surface.commit();
+    assert_eq!(tracker.committed(), Some(serial + 1));
+    assert_eq!(tracker.commit(), Some(serial + 2));
+    assert_eq!(tracker.commit(), None);
+}
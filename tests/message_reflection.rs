@@ -0,0 +1,138 @@
+extern crate wayland_client as wayc;
+#[macro_use]
+extern crate wayland_commons;
+extern crate wayland_server as ways;
+
+use wayland_commons::wire::{ArgumentType, FdKind};
+use wayland_commons::MessageGroup;
+
+#[test]
+fn opcode_name_lookup() {
+    use wayc::protocol::wl_surface::Request;
+
+    assert_eq!(Request::name(0), Some("destroy"));
+    assert_eq!(Request::name(6), Some("commit"));
+    assert_eq!(Request::name(42), None);
+
+    assert_eq!(Request::opcode_from_name("commit"), Some(6));
+    assert_eq!(Request::opcode_from_name("damage_buffer"), Some(9));
+    assert_eq!(Request::opcode_from_name("no_such_request"), None);
+
+    assert_eq!(
+        Request::Commit.opcode(),
+        Request::opcode_from_name("commit").unwrap()
+    );
+}
+
+#[test]
+fn message_descriptors() {
+    use ways::protocol::wl_surface::Request;
+
+    let attach = Request::message_desc(Request::opcode_from_name("attach").unwrap()).unwrap();
+    assert_eq!(
+        attach.signature,
+        &[ArgumentType::Object, ArgumentType::Int, ArgumentType::Int]
+    );
+    assert_eq!(attach.since, 1);
+    assert!(!attach.destructor);
+
+    let scale = Request::message_desc(Request::opcode_from_name("set_buffer_scale").unwrap()).unwrap();
+    assert_eq!(scale.signature, &[ArgumentType::Int]);
+    assert_eq!(scale.since, 3);
+
+    assert!(Request::message_desc(0).unwrap().destructor);
+    assert!(Request::message_desc(42).is_none());
+}
+
+#[test]
+fn fd_kind_hints() {
+    use wayc::protocol::wl_keyboard::Event as KeyboardEvent;
+    use ways::protocol::wl_shm::Request as ShmRequest;
+
+    let keymap = KeyboardEvent::message_desc(KeyboardEvent::opcode_from_name("keymap").unwrap()).unwrap();
+    assert_eq!(keymap.fd_kinds, &[FdKind::ReadOnlyMappable]);
+
+    // no expectations about the fd of shm pools
+    let create_pool = ShmRequest::message_desc(ShmRequest::opcode_from_name("create_pool").unwrap()).unwrap();
+    assert!(create_pool.fd_kinds.is_empty());
+}
+
+#[test]
+fn events_lookup() {
+    use wayc::protocol::wl_output::Event;
+
+    assert_eq!(
+        Event::name(Event::opcode_from_name("geometry").unwrap()),
+        Some("geometry")
+    );
+    assert_eq!(Event::opcode_from_name("done"), Some(2));
+    assert_eq!(Event::message_desc(3).unwrap().signature, &[ArgumentType::Int]);
+}
+
+#[test]
+fn bitfield_enums_round_trip() {
+    use wayc::protocol::wl_seat::Capability;
+    use ways::protocol::wl_output::Mode;
+
+    let caps = Capability::Pointer | Capability::Touch;
+    assert_eq!(caps.to_raw(), 5);
+    assert_eq!(Capability::from_raw(caps.to_raw()), Some(caps));
+    assert!(Capability::from_raw(5).unwrap().contains(Capability::Touch));
+    assert!(!Capability::from_raw(5).unwrap().contains(Capability::Keyboard));
+    assert_eq!(Capability::from_raw(0), Some(Capability::empty()));
+    // the bits unknown to this version of the protocol are ignored
+    assert_eq!(Capability::from_raw(0x12), Some(Capability::Keyboard));
+
+    for raw in 0..4 {
+        assert_eq!(Mode::from_raw(raw).unwrap().to_raw(), raw);
+    }
+    assert_eq!(Mode::from_raw(3), Some(Mode::Current | Mode::Preferred));
+}
+
+#[test]
+fn enums_round_trip() {
+    use wayc::protocol::wl_shm::Format;
+    use ways::protocol::wl_output::Transform;
+
+    assert_eq!(Format::from_raw(Format::Xrgb8888.to_raw()), Some(Format::Xrgb8888));
+    assert_eq!(Format::Argb8888.to_raw(), 0);
+    assert_eq!(Format::from_raw(0x3432_4241), Some(Format::Abgr8888));
+
+    for raw in 0..8 {
+        assert_eq!(Transform::from_raw(raw).unwrap().to_raw(), raw);
+    }
+    // unlike the bitfields, unknown values are rejected
+    assert_eq!(Transform::from_raw(8), None);
+}
+
+#[test]
+fn interface_fingerprints() {
+    use wayc::protocol::{wl_output as c_output, wl_seat as c_seat};
+    use wayland_commons::Interface;
+    use ways::protocol::{wl_output as s_output, wl_seat as s_seat};
+
+    // both sides are generated from the same protocol file
+    assert_eq!(c_output::WlOutput::fingerprint(), s_output::WlOutput::fingerprint());
+    assert_eq!(c_seat::WlSeat::fingerprint(), s_seat::WlSeat::fingerprint());
+    assert_ne!(c_output::WlOutput::fingerprint(), c_seat::WlSeat::fingerprint());
+}
+
+// generated by the scanners of both crates, from the same protocol file
+#[cfg(feature = "const_checks")]
+assert_protocol_compatible!(wayc::protocol::wl_seat, ways::protocol::wl_seat);
+#[cfg(feature = "const_checks")]
+assert_protocol_compatible!(wayc::protocol::wl_data_offer, ways::protocol::wl_data_offer);
+
+#[test]
+fn protocol_compatibility() {
+    use wayland_commons::wire::messages_compatible;
+
+    assert!(messages_compatible(
+        wayc::protocol::wl_surface::Request::MESSAGES,
+        ways::protocol::wl_surface::Request::MESSAGES
+    ));
+    assert!(!messages_compatible(
+        wayc::protocol::wl_surface::Request::MESSAGES,
+        ways::protocol::wl_region::Request::MESSAGES
+    ));
+}
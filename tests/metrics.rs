@@ -0,0 +1,72 @@
+// The counters are only supported by the rust implementation
+#![cfg(not(feature = "native_lib"))]
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use wayc::protocol::wl_display::RequestsTrait;
+use ways::protocol::wl_output::WlOutput as ServerOutput;
+
+// the samples of a rendering, without the comments
+fn samples(out: Vec<u8>) -> Vec<String> {
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+#[test]
+fn connection_metrics() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |new, _| {
+        new.implement(|_, _| {}, None::<fn(_)>, ());
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // get_registry and sync, answered by global, done and delete_id
+    let client_stats = client.display.connection_stats().unwrap();
+    assert_eq!(client_stats.sent_messages, 2);
+    assert_eq!(client_stats.received_messages, 3);
+    assert_eq!(client_stats.sent_fds, 0);
+    // both ends agree on what was exchanged
+    let server_stats = server.display.connection_stats().unwrap();
+    assert_eq!(server_stats.received_messages, client_stats.sent_messages);
+    assert_eq!(server_stats.sent_messages, client_stats.received_messages);
+    assert_eq!(server_stats.received_bytes, client_stats.sent_bytes);
+    assert_eq!(server_stats.sent_bytes, client_stats.received_bytes);
+    assert_eq!(server.display.client_count(), Some(1));
+
+    assert_eq!(client.event_queue.dispatched_events(), 3);
+    assert_eq!(client.event_queue.pending_events(), Some(0));
+
+    let mut out = Vec::new();
+    wayc::metrics::render_metrics(&client.display, &[("main", &client.event_queue)], &mut out).unwrap();
+    let client_samples = samples(out);
+    assert!(client_samples.contains(&"wayland_client_sent_messages_total 2".to_owned()));
+    assert!(client_samples.contains(&"wayland_client_received_messages_total 3".to_owned()));
+    assert!(
+        client_samples.contains(&"wayland_client_queue_dispatched_events_total{queue=\"main\"} 3".to_owned())
+    );
+    assert!(client_samples.contains(&"wayland_client_queue_pending_events{queue=\"main\"} 0".to_owned()));
+
+    let mut out = Vec::new();
+    ways::metrics::render_metrics(&server.display, &mut out).unwrap();
+    let server_samples = samples(out);
+    assert!(server_samples.contains(&"wayland_server_clients 1".to_owned()));
+    assert!(server_samples.contains(&"wayland_server_received_messages_total 2".to_owned()));
+
+    // the counters of the clients outlive them
+    ::std::mem::drop(client);
+    server.answer();
+    assert_eq!(server.display.client_count(), Some(0));
+    assert_eq!(server.display.connection_stats(), Some(server_stats));
+}
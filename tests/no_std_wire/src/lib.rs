@@ -0,0 +1,12 @@
+//! The wire code generated for the protocol of the scanner tests, in a `no_std` crate
+//!
+//! It is the expected output of `generate_wire_code`, checked by the scanner tests.
+
+#![no_std]
+#![allow(dead_code, non_upper_case_globals, unused_imports, unused_variables)]
+
+extern crate alloc;
+#[macro_use]
+extern crate bitflags;
+
+include!("../../scanner_assets/wire_code.rs");
@@ -0,0 +1,46 @@
+extern crate wayland_no_std_wire as wire;
+
+#[test]
+fn roundtrip() {
+    use wire::wl_foo::Request as FooRequest;
+    use wire::wl_registry::Request as RegistryRequest;
+
+    let foo_it = FooRequest::FooIt {
+        number: -3,
+        unumber: 5,
+        text: "foo".into(),
+        float: 1.5,
+        file: 42,
+    };
+    let bind = RegistryRequest::Bind {
+        name: 7,
+        id: ("wl_foo".into(), 3, 12),
+    };
+    let mut words = Vec::new();
+    let mut fds = Vec::new();
+    foo_it.serialize(4, &mut words, &mut fds);
+    bind.serialize(2, &mut words, &mut fds);
+    assert_eq!(fds, [42]);
+
+    let (sender, opcode, len) = wire::parse_header(&words).unwrap();
+    assert_eq!((sender, opcode, len), (4, 0, 7));
+    let mut fds_iter = fds.into_iter();
+    assert_eq!(FooRequest::parse(opcode, &words[2..len], &mut fds_iter), Ok(foo_it));
+    let words = &words[len..];
+    let (sender, opcode, len) = wire::parse_header(words).unwrap();
+    assert_eq!((sender, opcode, len), (2, 0, words.len()));
+    assert_eq!(RegistryRequest::parse(opcode, &words[2..], &mut fds_iter), Ok(bind));
+
+    // malformed messages are rejected
+    let mut empty = None.into_iter();
+    assert_eq!(
+        FooRequest::parse(1, &[], &mut empty),
+        Err(wire::WireError::MissingArgument)
+    );
+    assert_eq!(
+        FooRequest::parse(1, &[3, 4], &mut empty),
+        Err(wire::WireError::TrailingData)
+    );
+    assert_eq!(FooRequest::parse(2, &[], &mut empty), Err(wire::WireError::UnknownOpcode));
+}
+
@@ -1,6 +1,6 @@
 mod helpers;
 
-use helpers::TestServer;
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
 
 extern crate nix;
 extern crate wayland_commons as wc;
@@ -83,3 +83,227 @@ fn client_wrong_sender() {
     // server should have killed us due to the error
     assert_eq!(socket.flush(), Err(nix::Error::Sys(nix::errno::Errno::EPIPE)));
 }
+
+#[test]
+fn server_typed_error() {
+    use ways::protocol::wl_shm;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<wl_shm::WlShm, _>(1, |newshm, _| {
+            let shm = newshm.implement(|_, _| {}, None::<fn(_)>, ());
+            shm.post_error_enum(wl_shm::Error::InvalidFd, "I don't like your fds.".into());
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    manager
+        .instantiate_auto::<wayc::protocol::wl_shm::WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    // the error is fatal to the client
+    assert!(roundtrip(&mut client, &mut server).is_err());
+}
+
+#[test]
+fn client_protocol_error() {
+    use ways::protocol::wl_shm;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<wl_shm::WlShm, _>(1, |newshm, _| {
+            let shm = newshm.implement(|_, _| {}, None::<fn(_)>, ());
+            shm.post_error_with_summary(wl_shm::Error::InvalidFormat, "42");
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(client.display.protocol_error().is_none());
+
+    let shm = manager
+        .instantiate_auto::<wayc::protocol::wl_shm::WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    assert!(roundtrip(&mut client, &mut server).is_err());
+
+    let error = client.display.protocol_error().unwrap();
+    assert_eq!(error.code, wayc::protocol::wl_shm::Error::InvalidFormat.to_raw());
+    assert_eq!(error.object_id, shm.id());
+    assert_eq!(error.object_interface, "wl_shm");
+    // libwayland-client.so does not keep the message
+    #[cfg(not(feature = "native_lib"))]
+    assert_eq!(error.message, "buffer format is not known: 42");
+    assert_eq!(wl_shm::Error::InvalidFormat.to_string(), "buffer format is not known");
+}
+
+#[test]
+fn server_error_message() {
+    use std::ffi::CString;
+    use wc::wire::ArgumentType;
+    use ways::protocol::wl_shm;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<wl_shm::WlShm, _>(1, |newshm, _| {
+            let shm = newshm.implement(|_, _| {}, None::<fn(_)>, ());
+            let format = 42;
+            shm.post_error_enum(
+                wl_shm::Error::InvalidFormat,
+                format!("100% of %s formats like {} are invalid\0hidden", format),
+            );
+        });
+
+    let mut socket: PathBuf = env::var_os("XDG_RUNTIME_DIR").unwrap().into();
+    socket.push(&server.socket_name);
+    let socket = UnixStream::connect(socket).unwrap();
+
+    let mut socket = BufferedSocket::new(unsafe { Socket::from_raw_fd(socket.into_raw_fd()) });
+    socket
+        .write_message(&Message {
+            sender_id: 1, // wl_display
+            opcode: 1,    // get_registry
+            args: vec![Argument::NewId(2)],
+        }).unwrap();
+    socket.flush().unwrap();
+
+    server.answer();
+
+    socket.fill_incoming_buffers().unwrap();
+    let global = socket
+        .read_one_message(|id, opcode| match (id, opcode) {
+            (2, 0) => Some(&[ArgumentType::Uint, ArgumentType::Str, ArgumentType::Uint]),
+            _ => None,
+        }).unwrap();
+    socket
+        .write_message(&Message {
+            sender_id: 2, // wl_registry
+            opcode: 0,    // bind
+            args: vec![
+                global.args[0].clone(),
+                Argument::Str(CString::new("wl_shm").unwrap()),
+                Argument::Uint(1),
+                Argument::NewId(3),
+            ],
+        }).unwrap();
+    socket.flush().unwrap();
+
+    server.answer();
+
+    socket.fill_incoming_buffers().unwrap();
+    let error = socket
+        .read_one_message(|id, opcode| match (id, opcode) {
+            (1, 0) => Some(&[ArgumentType::Object, ArgumentType::Uint, ArgumentType::Str]),
+            _ => None,
+        }).unwrap();
+    assert_eq!(error.args[0], Argument::Object(3));
+    assert_eq!(error.args[1], Argument::Uint(wl_shm::Error::InvalidFormat.to_raw()));
+    // sent as is, up to the nul byte
+    assert_eq!(
+        error.args[2],
+        Argument::Str(CString::new("100% of %s formats like 42 are invalid").unwrap())
+    );
+}
+
+#[test]
+fn client_invalid_arguments() {
+    use std::ffi::CString;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use wc::wire::ArgumentType;
+    use ways::protocol::wl_compositor;
+
+    let dispatched = Arc::new(AtomicBool::new(false));
+    let mut server = TestServer::new();
+    let surface_dispatched = dispatched.clone();
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(3, move |newcompositor, _| {
+            let surface_dispatched = surface_dispatched.clone();
+            newcompositor.implement(
+                move |req, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = req {
+                        let surface_dispatched = surface_dispatched.clone();
+                        id.implement(
+                            move |_, _| surface_dispatched.store(true, Ordering::SeqCst),
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut socket: PathBuf = env::var_os("XDG_RUNTIME_DIR").unwrap().into();
+    socket.push(&server.socket_name);
+    let socket = UnixStream::connect(socket).unwrap();
+
+    let mut socket = BufferedSocket::new(unsafe { Socket::from_raw_fd(socket.into_raw_fd()) });
+    socket
+        .write_message(&Message {
+            sender_id: 1, // wl_display
+            opcode: 1,    // get_registry
+            args: vec![Argument::NewId(2)],
+        }).unwrap();
+    socket.flush().unwrap();
+
+    server.answer();
+
+    socket.fill_incoming_buffers().unwrap();
+    let global = socket
+        .read_one_message(|id, opcode| match (id, opcode) {
+            (2, 0) => Some(&[ArgumentType::Uint, ArgumentType::Str, ArgumentType::Uint]),
+            _ => None,
+        }).unwrap();
+    for msg in &[
+        Message {
+            sender_id: 2, // wl_registry
+            opcode: 0,    // bind
+            args: vec![
+                global.args[0].clone(),
+                Argument::Str(CString::new("wl_compositor").unwrap()),
+                Argument::Uint(3),
+                Argument::NewId(3),
+            ],
+        },
+        Message {
+            sender_id: 3, // wl_compositor
+            opcode: 0,    // create_surface
+            args: vec![Argument::NewId(4)],
+        },
+        Message {
+            sender_id: 4, // wl_surface
+            opcode: 7,    // set_buffer_transform
+            args: vec![Argument::Int(42)], // not a wl_output.transform
+        },
+    ] {
+        socket.write_message(msg).unwrap();
+    }
+    socket.flush().unwrap();
+
+    server.answer();
+
+    // the request was rejected without being dispatched, and the client told why
+    assert!(!dispatched.load(Ordering::SeqCst));
+    socket.fill_incoming_buffers().unwrap();
+    let error = socket
+        .read_one_message(|id, opcode| match (id, opcode) {
+            (1, 0) => Some(&[ArgumentType::Object, ArgumentType::Uint, ArgumentType::Str]),
+            _ => None,
+        }).unwrap();
+    assert_eq!(error.args[0], Argument::Object(4));
+    assert_eq!(error.args[1], Argument::Uint(1)); // wl_display.error.invalid_method
+    assert_eq!(
+        error.args[2],
+        Argument::Str(CString::new("invalid arguments for wl_surface@4.set_buffer_transform").unwrap())
+    );
+}
@@ -0,0 +1,152 @@
+extern crate wayland_commons;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::sync::{Arc, Mutex};
+
+use wayland_commons::wire::Argument;
+
+use wayc::protocol::wl_display::RequestsTrait;
+#[cfg(not(feature = "native_lib"))]
+use wayc::Direction;
+#[cfg(feature = "native_lib")]
+use ways::Direction;
+use ways::protocol::wl_output::WlOutput as ServerOutput;
+
+type Log = Arc<Mutex<Vec<(Direction, String, u64)>>>;
+
+// record the messages given to a logger, with their trace id
+fn record(log: &Log, direction: Direction, message: &ways::LoggedMessage) {
+    log.lock().unwrap().push((
+        direction,
+        format!("{}@{}.{}", message.interface, message.message.sender_id, message.name),
+        message.trace_id,
+    ));
+}
+
+// the client loggers are only supported by the rust implementation
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn protocol_loggers() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |new, _| {
+        new.implement(|_, _| {}, None::<fn(_)>, ());
+    });
+    let server_log: Log = Arc::new(Mutex::new(Vec::new()));
+    let server_logged = server_log.clone();
+    server
+        .display
+        .set_protocol_logger(Some(move |direction, message: &ways::LoggedMessage| {
+            record(&server_logged, direction, message)
+        }));
+
+    let mut client = TestClient::new(&server.socket_name);
+    let logged: Log = Arc::new(Mutex::new(Vec::new()));
+    let client_logged = logged.clone();
+    client
+        .display
+        .set_protocol_logger(Some(move |direction, message: &wayc::LoggedMessage| {
+            record(&client_logged, direction, message)
+        }));
+
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // in the order they were sent, the client dispatches the events of the display first
+    let mut client_log = logged.lock().unwrap().clone();
+    client_log.sort_by_key(|&(direction, _, trace_id)| (direction == Direction::Received, trace_id));
+    assert_eq!(
+        client_log
+            .iter()
+            .map(|&(direction, ref message, _)| (direction, message.as_str()))
+            .collect::<Vec<_>>(),
+        [
+            (Direction::Sent, "wl_display@1.get_registry"),
+            (Direction::Sent, "wl_display@1.sync"),
+            (Direction::Received, "wl_registry@2.global"),
+            (Direction::Received, "wl_callback@3.done"),
+            (Direction::Received, "wl_display@1.delete_id"),
+        ]
+    );
+    // the server logged the same messages, with the same trace ids
+    let mut server_log = server_log.lock().unwrap().clone();
+    for entry in &mut server_log {
+        entry.0 = match entry.0 {
+            Direction::Sent => Direction::Received,
+            Direction::Received => Direction::Sent,
+        };
+    }
+    server_log.sort_by_key(|&(direction, _, trace_id)| (direction == Direction::Received, trace_id));
+    assert_eq!(server_log, client_log);
+
+    // once removed, the logger no longer receives the messages
+    client
+        .display
+        .set_protocol_logger(None::<fn(Direction, &wayc::LoggedMessage)>);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(logged.lock().unwrap().len(), client_log.len());
+}
+
+#[test]
+fn server_protocol_logger() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerOutput, _>(2, |new, _| {
+        new.implement(|_, _| {}, None::<fn(_)>, ());
+    });
+    let log: Log = Arc::new(Mutex::new(Vec::new()));
+    let logged = log.clone();
+    let global = Arc::new(Mutex::new(None));
+    let logged_global = global.clone();
+    server
+        .display
+        .set_protocol_logger(Some(move |direction, message: &ways::LoggedMessage| {
+            if message.name == "global" {
+                *logged_global.lock().unwrap() = Some(message.message.args.clone());
+            }
+            record(&logged, direction, message)
+        }));
+
+    let mut client = TestClient::new(&server.socket_name);
+    client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let messages = log
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|&(direction, ref message, _)| (direction, message.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            (Direction::Received, "wl_display@1.get_registry".to_owned()),
+            (Direction::Sent, "wl_registry@2.global".to_owned()),
+            (Direction::Received, "wl_display@1.sync".to_owned()),
+            (Direction::Sent, "wl_callback@3.done".to_owned()),
+            (Direction::Sent, "wl_display@1.delete_id".to_owned()),
+        ]
+    );
+    let args = global.lock().unwrap().take().unwrap();
+    assert_eq!(args.len(), 3);
+    match (&args[1], &args[2]) {
+        (&Argument::Str(ref interface), &Argument::Uint(2)) => {
+            assert_eq!(interface.to_str().unwrap(), "wl_output")
+        }
+        _ => panic!("Unexpected arguments: {:?}", args),
+    }
+
+    // once removed, the logger no longer receives the messages
+    server
+        .display
+        .set_protocol_logger(None::<fn(Direction, &ways::LoggedMessage)>);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(log.lock().unwrap().len(), messages.len());
+}
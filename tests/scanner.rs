@@ -1,9 +1,17 @@
+#[macro_use]
+extern crate bitflags;
 extern crate difference;
+extern crate wayland_client;
+extern crate wayland_commons;
 extern crate wayland_scanner;
+extern crate wayland_server;
+extern crate wayland_sys;
 
 use difference::{Changeset, Difference};
 use std::cmp::{max, min};
-use std::io::Cursor;
+use std::env;
+use std::fs::File;
+use std::io::{Cursor, Write};
 use std::str::from_utf8;
 use wayland_scanner::Side;
 
@@ -15,6 +23,54 @@ const CLIENT_C_CODE_TARGET: &'static str = include_str!("./scanner_assets/client
 
 const SERVER_C_CODE_TARGET: &'static str = include_str!("./scanner_assets/server_c_code.rs");
 
+const CLIENT_RUST_CODE_TARGET: &'static str = include_str!("./scanner_assets/client_rust_code.rs");
+
+const CLIENT_RUST_SERDE_CODE_TARGET: &'static str = include_str!("./scanner_assets/client_rust_serde_code.rs");
+
+const SERVER_RUST_CODE_TARGET: &'static str = include_str!("./scanner_assets/server_rust_code.rs");
+
+const WIRE_CODE_TARGET: &'static str = include_str!("./scanner_assets/wire_code.rs");
+
+const CLIENT_RUST_C_INTERFACES_CODE_TARGET: &'static str =
+    include_str!("./scanner_assets/client_rust_c_interfaces_code.rs");
+
+const SERVER_RUST_C_INTERFACES_CODE_TARGET: &'static str =
+    include_str!("./scanner_assets/server_rust_c_interfaces_code.rs");
+
+// `CODEGEN_VERSION` and the hash of the code generated for the test protocol with this
+// version, updated together whenever the generated code changes
+const CODEGEN_HASH: (u32, u64) = (12, 0x5d66_61b6_52f6_60bf);
+
+// the code generated with the `c_interfaces` option, compiled as it would be in a protocol crate
+#[allow(dead_code, non_camel_case_types, non_upper_case_globals, non_snake_case)]
+#[allow(unused_imports, unused_unsafe, unused_variables)]
+mod c_interfaces_option {
+    pub mod c_interfaces {
+        include!("./scanner_assets/c_interfaces.rs");
+    }
+
+    pub mod client {
+        pub(crate) use wayland_client::{NewProxy, Proxy, ProxyMap};
+        pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+        pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, FdKind, Message, MessageDesc};
+        pub(crate) use wayland_commons::wire::{string_argument, InteriorNulError, RequestError};
+        pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
+        pub(crate) use wayland_sys as sys;
+        include!("./scanner_assets/client_rust_c_interfaces_code.rs");
+    }
+
+    pub mod server {
+        pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+        pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, FdKind, Message, MessageDesc};
+        pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
+        pub(crate) use wayland_server::{NewResource, Resource, ResourceMap};
+        pub(crate) use wayland_sys as sys;
+        include!("./scanner_assets/server_rust_c_interfaces_code.rs");
+    }
+}
+
 fn print_diff(diffs: &[Difference]) {
     println!("Partial diffs found:");
     let diffs = flatten_diffs(diffs);
@@ -78,56 +134,308 @@ fn flatten_diffs(diffs: &[Difference]) -> Vec<Difference> {
         }).collect()
 }
 
-#[test]
-fn c_interfaces_generation() {
-    let mut out = Vec::new();
-    wayland_scanner::generate_c_interfaces_streams(Cursor::new(PROTOCOL.as_bytes()), &mut out);
-    let changeset = Changeset::new(
-        C_INTERFACES_TARGET,
-        from_utf8(&out).expect("Output of scanner was not UTF8."),
-        "\n",
-    );
+// Compare the output of the scanner with the expected one
+//
+// Setting the `WAYLAND_SCANNER_BLESS` environment variable replaces the expected output
+// by the actual one in `scanner_assets`, to update it after a change to the generated code.
+fn check_generated(asset: &str, target: &str, out: &[u8]) {
+    let out = from_utf8(out).expect("Output of scanner was not UTF8.");
+    if env::var_os("WAYLAND_SCANNER_BLESS").is_some() {
+        let path = format!("{}/tests/scanner_assets/{}", env!("CARGO_MANIFEST_DIR"), asset);
+        let mut file = File::create(path).unwrap();
+        file.write_all(out.as_bytes()).unwrap();
+        return;
+    }
+    let changeset = Changeset::new(target, out, "\n");
     if changeset.distance != 0 {
         print_diff(&changeset.diffs);
         panic!(
-            "Scanner output does not match expected output: d = {}",
-            changeset.distance
+            "Scanner output does not match expected output of {}: d = {}",
+            asset, changeset.distance
         );
     }
 }
 
+#[test]
+fn c_interfaces_generation() {
+    let mut out = Vec::new();
+    wayland_scanner::generate_c_interfaces_streams(Cursor::new(PROTOCOL.as_bytes()), &mut out);
+    check_generated("c_interfaces.rs", C_INTERFACES_TARGET, &out);
+}
+
 #[test]
 fn client_c_code_generation() {
     let mut out = Vec::new();
     wayland_scanner::generate_c_code_streams(Cursor::new(PROTOCOL.as_bytes()), &mut out, Side::Client);
-    let changeset = Changeset::new(
-        CLIENT_C_CODE_TARGET,
-        from_utf8(&out).expect("Output of scanner was not UTF8."),
-        "\n",
-    );
-    if changeset.distance != 0 {
-        print_diff(&changeset.diffs);
-        panic!(
-            "Scanner output does not match expected output: d = {}",
-            changeset.distance
-        );
-    }
+    check_generated("client_c_code.rs", CLIENT_C_CODE_TARGET, &out);
 }
 
 #[test]
 fn server_c_code_generation() {
     let mut out = Vec::new();
     wayland_scanner::generate_c_code_streams(Cursor::new(PROTOCOL.as_bytes()), &mut out, Side::Server);
-    let changeset = Changeset::new(
-        SERVER_C_CODE_TARGET,
-        from_utf8(&out).expect("Output of scanner was not UTF8."),
-        "\n",
+    check_generated("server_c_code.rs", SERVER_C_CODE_TARGET, &out);
+}
+
+#[test]
+fn client_rust_code_generation() {
+    let mut out = Vec::new();
+    wayland_scanner::generate_rust_code_streams(Cursor::new(PROTOCOL.as_bytes()), &mut out, Side::Client);
+    check_generated("client_rust_code.rs", CLIENT_RUST_CODE_TARGET, &out);
+}
+
+#[test]
+fn server_rust_code_generation() {
+    let mut out = Vec::new();
+    wayland_scanner::generate_rust_code_streams(Cursor::new(PROTOCOL.as_bytes()), &mut out, Side::Server);
+    check_generated("server_rust_code.rs", SERVER_RUST_CODE_TARGET, &out);
+}
+
+#[test]
+fn wire_code_generation() {
+    let mut out = Vec::new();
+    wayland_scanner::generate_wire_code_streams(Cursor::new(PROTOCOL.as_bytes()), &mut out);
+    check_generated("wire_code.rs", WIRE_CODE_TARGET, &out);
+    // the generated code only relies on `core` and `alloc`
+    assert!(!from_utf8(&out).unwrap().contains("std::"));
+}
+
+// the outputs of all the generators for a protocol
+fn generate_all(protocol: &str) -> Vec<Vec<u8>> {
+    let input = || Cursor::new(protocol.as_bytes());
+    let mut outputs = vec![Vec::new(); 6];
+    wayland_scanner::generate_c_interfaces_streams(input(), &mut outputs[0]);
+    wayland_scanner::generate_c_code_streams(input(), &mut outputs[1], Side::Client);
+    wayland_scanner::generate_c_code_streams(input(), &mut outputs[2], Side::Server);
+    wayland_scanner::generate_rust_code_streams(input(), &mut outputs[3], Side::Client);
+    wayland_scanner::generate_rust_code_streams(input(), &mut outputs[4], Side::Server);
+    wayland_scanner::generate_wire_code_streams(input(), &mut outputs[5]);
+    outputs
+}
+
+#[test]
+fn reproducible_generation() {
+    let outputs = generate_all(PROTOCOL);
+    assert!(generate_all(PROTOCOL) == outputs);
+    // the line endings of the protocol file depend on the platform it was checked out on
+    let crlf_protocol = PROTOCOL.replace('\n', "\r\n");
+    assert!(
+        generate_all(&crlf_protocol) == outputs,
+        "Scanner output depends on the line endings of the protocol file"
     );
-    if changeset.distance != 0 {
-        print_diff(&changeset.diffs);
-        panic!(
-            "Scanner output does not match expected output: d = {}",
-            changeset.distance
+}
+
+#[test]
+fn codegen_version_bump() {
+    // FNV-1a, which does not depend on the version of the standard library
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &byte in generate_all(PROTOCOL).iter().flat_map(|out| out.iter()) {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3);
+    }
+    assert_eq!(
+        (wayland_scanner::CODEGEN_VERSION, hash),
+        CODEGEN_HASH,
+        "The generated code changed, bump CODEGEN_VERSION and update CODEGEN_HASH"
+    );
+}
+
+#[test]
+fn version_header_generation() {
+    let options = wayland_scanner::Options {
+        version_header: true,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    wayland_scanner::generate_rust_code_streams_with_options(
+        Cursor::new(PROTOCOL.as_bytes()),
+        &mut out,
+        Side::Client,
+        options,
+    );
+    let code = from_utf8(&out).expect("Output of scanner was not UTF8.");
+    let header = format!(
+        "// wayland-scanner codegen version: {}\n\n",
+        wayland_scanner::CODEGEN_VERSION
+    );
+    assert_eq!(code.matches(&header[..]).count(), 1);
+    // the header is the only difference with the default output
+    assert_eq!(code.replacen(&header[..], "", 1), CLIENT_RUST_CODE_TARGET);
+}
+
+#[test]
+fn serde_derives_generation() {
+    let options = wayland_scanner::Options {
+        serde: true,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    wayland_scanner::generate_rust_code_streams_with_options(
+        Cursor::new(PROTOCOL.as_bytes()),
+        &mut out,
+        Side::Client,
+        options,
+    );
+    check_generated("client_rust_serde_code.rs", CLIENT_RUST_SERDE_CODE_TARGET, &out);
+
+    // the derives are the only difference with the default output
+    let code = from_utf8(&out).unwrap();
+    let stripped = code
+        .lines()
+        .filter(|line| line.trim() != "#[derive(Serialize, Deserialize)]")
+        .map(|line| format!("{}\n", line.replace(",Serialize,Deserialize", "")))
+        .collect::<String>();
+    assert_eq!(stripped, CLIENT_RUST_CODE_TARGET);
+}
+
+#[test]
+fn request_builders_generation() {
+    let options = wayland_scanner::Options {
+        request_builders: true,
+        ..Default::default()
+    };
+    for &generate in &[
+        wayland_scanner::generate_rust_code_streams_with_options::<Cursor<&[u8]>, Vec<u8>>,
+        wayland_scanner::generate_c_code_streams_with_options::<Cursor<&[u8]>, Vec<u8>>,
+    ] {
+        let mut out = Vec::new();
+        generate(Cursor::new(PROTOCOL.as_bytes()), &mut out, Side::Client, options);
+        let code = from_utf8(&out).expect("Output of scanner was not UTF8.");
+
+        let wl_foo = &code[code.find("pub mod wl_foo").unwrap()..code.find("pub mod wl_bar").unwrap()];
+        // wl_foo.foo_it has five arguments
+        assert!(wl_foo.contains("fn foo_it_builder<'a>(&'a self) -> FooItRequestBuilder<'a>;"));
+        assert!(wl_foo.contains("pub struct FooItRequestBuilder<'a> {"));
+        assert!(wl_foo.contains("text: Option<&'a str>,"));
+        assert!(wl_foo.contains("pub fn number(mut self, number: i32) -> Self {"));
+        assert!(wl_foo.contains("pub fn send(self) ->Result<(), super::InteriorNulError> {"));
+        assert!(wl_foo.contains("self.number.expect(\"missing argument `number` of request `foo_it`\"),"));
+        // but wl_foo.create_bar has none
+        assert!(!wl_foo.contains("CreateBarRequestBuilder"));
+    }
+
+    // the server side has no request method
+    let mut out = Vec::new();
+    wayland_scanner::generate_rust_code_streams_with_options(
+        Cursor::new(PROTOCOL.as_bytes()),
+        &mut out,
+        Side::Server,
+        options,
+    );
+    assert_eq!(from_utf8(&out).unwrap(), SERVER_RUST_CODE_TARGET);
+}
+
+#[test]
+fn c_interfaces_option_generation() {
+    let options = wayland_scanner::Options {
+        c_interfaces: true,
+        ..Default::default()
+    };
+    for &(side, asset, target) in &[
+        (
+            Side::Client,
+            "client_rust_c_interfaces_code.rs",
+            CLIENT_RUST_C_INTERFACES_CODE_TARGET,
+        ),
+        (
+            Side::Server,
+            "server_rust_c_interfaces_code.rs",
+            SERVER_RUST_C_INTERFACES_CODE_TARGET,
+        ),
+    ] {
+        let mut out = Vec::new();
+        wayland_scanner::generate_rust_code_streams_with_options(
+            Cursor::new(PROTOCOL.as_bytes()),
+            &mut out,
+            side,
+            options,
+        );
+        check_generated(asset, target, &out);
+        let code = from_utf8(&out).unwrap();
+        assert_eq!(code.matches("pub fn c_interface()").count(), code.matches("impl Interface for").count());
+    }
+
+    // the option has no effect on the code using the C system libs
+    let mut out = Vec::new();
+    wayland_scanner::generate_c_code_streams_with_options(
+        Cursor::new(PROTOCOL.as_bytes()),
+        &mut out,
+        Side::Client,
+        options,
+    );
+    assert_eq!(from_utf8(&out).unwrap(), CLIENT_C_CODE_TARGET);
+}
+
+#[test]
+fn fd_kinds_option_generation() {
+    let options = wayland_scanner::Options {
+        fd_kinds: &[wayland_scanner::FdKindHint {
+            interface: "wl_foo",
+            message: "foo_it",
+            argument: "file",
+            kind: "ReadOnlyMappable",
+        }],
+        ..Default::default()
+    };
+    for &side in &[Side::Client, Side::Server] {
+        let mut rust_code = Vec::new();
+        wayland_scanner::generate_rust_code_streams_with_options(
+            Cursor::new(PROTOCOL.as_bytes()),
+            &mut rust_code,
+            side,
+            options,
+        );
+        let mut c_code = Vec::new();
+        wayland_scanner::generate_c_code_streams_with_options(
+            Cursor::new(PROTOCOL.as_bytes()),
+            &mut c_code,
+            side,
+            options,
         );
+        for code in &[rust_code, c_code] {
+            let code = from_utf8(code).expect("Output of scanner was not UTF8.");
+            let expected = "fd_kinds: &[\n                    super::FdKind::ReadOnlyMappable,\n                ],";
+            // only foo_it is hinted, which is a request of wl_foo
+            assert_eq!(code.matches(expected).count(), 1);
+            assert_eq!(code.matches("super::FdKind::").count(), 1);
+        }
     }
 }
+
+#[test]
+fn c_code_nullable_string_generation() {
+    let protocol = r#"<?xml version="1.0" encoding="UTF-8"?>
+<protocol name="nullable">
+  <interface name="wl_named" version="1">
+    <request name="set_name">
+      <arg name="name" type="string" allow-null="true"/>
+    </request>
+  </interface>
+</protocol>"#;
+    let mut out = Vec::new();
+    wayland_scanner::generate_c_code_streams(Cursor::new(protocol.as_bytes()), &mut out, Side::Client);
+    let code = from_utf8(&out).expect("Output of scanner was not UTF8.");
+    // the pointer borrows the CString, which is kept alive until the request is sent
+    assert!(code.contains("let _arg_0 = name.map(|s| ::std::ffi::CString::new(s).unwrap());"));
+    assert!(code.contains("_args_array[0].s = _arg_0.as_ref().map(|s| s.as_ptr()).unwrap_or(::std::ptr::null());"));
+    assert!(!code.contains("_arg_0.map("));
+}
+
+#[test]
+fn c_interfaces_option_tables() {
+    use c_interfaces_option::{client, server};
+    use std::ffi::CStr;
+
+    let check = |interface: *const wayland_sys::common::wl_interface, name: &str, version: i32| {
+        let interface = unsafe { &*interface };
+        assert_eq!(unsafe { CStr::from_ptr(interface.name) }.to_str(), Ok(name));
+        assert_eq!(interface.version, version);
+    };
+    check(client::wl_foo::WlFoo::c_interface(), "wl_foo", 3);
+    check(client::wl_bar::WlBar::c_interface(), "wl_bar", 1);
+    check(server::wl_foo::WlFoo::c_interface(), "wl_foo", 3);
+    // both sides point to the same tables
+    assert_eq!(
+        client::wl_callback::WlCallback::c_interface(),
+        server::wl_callback::WlCallback::c_interface()
+    );
+}
@@ -0,0 +1,961 @@
+
+//
+// This file was auto-generated, do not edit directly.
+//
+
+/*
+This is an example copyright.
+    It contains several lines.
+    AS WELL AS ALL CAPS TEXT.
+*/
+
+pub mod wl_foo {
+    //! Interface for fooing
+    //!
+    //! This is the dedicated interface for doing foos over any
+    //! kind of other foos.
+    use super::{Proxy, NewProxy, AnonymousObject, Interface, MessageGroup, MessageDesc, ArgumentType, Object, Message, Argument, ObjectMetadata};
+
+    /// Possible cake kinds
+    ///
+    /// List of the possible kind of cake supported by the protocol.
+
+    #[repr(u32)]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub enum CakeKind {
+        /// mild cake without much flavor
+        Basic = 0,
+        /// spicy cake to burn your tongue
+        Spicy = 1,
+        /// fruity cake to get vitamins
+        Fruity = 2,
+    }
+    impl CakeKind {
+        /// The entry of this enum with a given raw value, if any
+        pub fn from_raw(n: u32) -> Option<CakeKind> {
+            match n {
+                0 => Some(CakeKind::Basic),
+                1 => Some(CakeKind::Spicy),
+                2 => Some(CakeKind::Fruity),
+
+                _ => Option::None
+            }
+        }
+        /// The raw value of this entry
+        pub fn to_raw(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    bitflags! {
+        /// possible delivery modes
+        ///
+        pub struct DeliveryKind: u32 {
+            /// pick your cake up yourself
+            const PickUp = 1;
+            /// flying drone delivery
+            const Drone = 2;
+            /// because we fear nothing
+            const Catapult = 4;
+        }
+    }
+    impl DeliveryKind {
+        /// Create a set of flags from its raw value, ignoring the unknown bits
+        pub fn from_raw(n: u32) -> Option<DeliveryKind> {
+            Some(DeliveryKind::from_bits_truncate(n))
+        }
+        /// The raw value of this set of flags
+        pub fn to_raw(&self) -> u32 {
+            self.bits()
+        }
+    }
+
+    pub enum Request {
+        /// do some foo
+        ///
+        /// This will do some foo with its args.
+        FooIt {
+            /// the number that will be fooed
+            number: i32,
+            /// the unsigned number that will be fooed
+            unumber: u32,
+            /// the text that will be fooed
+            text: String,
+            /// the float number that will be fooed
+            float: f64,
+            /// the file descriptor to write all this foo in
+            file: ::std::os::unix::io::RawFd,
+        },
+        /// create a bar
+        ///
+        /// Create a bar which will do its bar job.
+        CreateBar {
+            /// created bar
+            id: Proxy<super::wl_bar::WlBar>,
+        },
+    }
+
+    impl super::MessageGroup for Request {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+            super::MessageDesc {
+                name: "foo_it",
+                since: 1,
+                signature: &[
+                    super::ArgumentType::Int,
+                    super::ArgumentType::Uint,
+                    super::ArgumentType::Str,
+                    super::ArgumentType::Fixed,
+                    super::ArgumentType::Fd,
+                ],
+                destructor: false,
+                fd_kinds: &[],
+            },
+            super::MessageDesc {
+                name: "create_bar",
+                since: 1,
+                signature: &[
+                    super::ArgumentType::NewId,
+                ],
+                destructor: false,
+                fd_kinds: &[],
+            },
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+                _ => false
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+                Request::FooIt { .. } => 0,
+                Request::CreateBar { .. } => 1,
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                1 => Some(Object::from_interface::<super::wl_bar::WlBar>(version, meta.child())),
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            panic!("Request::from_raw can not be used Client-side.")
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            match self {
+                Request::FooIt { number, unumber, text, float, file, } => Message {
+                    sender_id: sender_id,
+                    opcode: 0,
+                    args: vec![
+                        Argument::Int(number),
+                        Argument::Uint(unumber),
+                        Argument::Str(unsafe { ::std::ffi::CString::from_vec_unchecked(text.into()) }),
+                        Argument::Fixed((float * 256.) as i32),
+                        Argument::Fd(file),
+                    ]
+                },
+                Request::CreateBar { id, } => Message {
+                    sender_id: sender_id,
+                    opcode: 1,
+                    args: vec![
+                        Argument::NewId(id.id()),
+                    ]
+                },
+            }
+        }
+
+    }
+
+    pub enum Event {
+        /// a cake is possible
+        ///
+        /// The server advertizes that a kind of cake is available
+        ///
+        /// Only available since version 2 of the interface
+        Cake {
+            /// name of this kind of cake
+            kind: CakeKind,
+            /// amount available
+            amount: u32,
+        },
+    }
+
+    impl super::MessageGroup for Event {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+            super::MessageDesc {
+                name: "cake",
+                since: 2,
+                signature: &[
+                    super::ArgumentType::Uint,
+                    super::ArgumentType::Uint,
+                ],
+                destructor: false,
+                fd_kinds: &[],
+            },
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+                _ => false
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+                Event::Cake { .. } => 0,
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            match msg.opcode {
+                0 => {
+                    let mut args = msg.args.into_iter();
+                    Ok(Event::Cake {
+                        kind: {
+                            if let Some(Argument::Uint(val)) = args.next() {
+                                CakeKind::from_raw(val).ok_or(())?
+                            } else {
+                                return Err(())
+                            }
+                        },
+                        amount: {
+                            if let Some(Argument::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(())
+                            }
+                        },
+                    })
+                },
+                _ => Err(()),
+            }
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            panic!("Event::into_raw can not be used Client-side.")
+        }
+
+    }
+
+
+    pub struct WlFoo;
+
+    impl Interface for WlFoo {
+        type Request = Request;
+        type Event = Event;
+        const NAME: &'static str = "wl_foo";
+        const VERSION: u32 = 3;
+
+    }
+
+    impl WlFoo {
+        /// The C description of this interface
+        pub fn c_interface() -> *const super::sys::common::wl_interface {
+            unsafe { &super::super::c_interfaces::wl_foo_interface }
+        }
+    }
+
+    pub trait RequestsTrait {
+        /// do some foo
+        ///
+        /// This will do some foo with its args.
+        ///
+        /// Arguments:
+        ///
+        /// - `number`: the number that will be fooed
+        /// - `unumber`: the unsigned number that will be fooed
+        /// - `text`: the text that will be fooed
+        /// - `float`: the float number that will be fooed
+        /// - `file`: the file descriptor to write all this foo in
+        fn foo_it<'s, S2: Into<::std::borrow::Cow<'s, str>>>(&self, number: i32, unumber: u32, text: S2, float: f64, file: ::std::os::unix::io::RawFd) ->Result<(), super::InteriorNulError>;
+        /// create a bar
+        ///
+        /// Create a bar which will do its bar job.
+        fn create_bar<F>(&self, implementor: F) ->Result<Proxy<super::wl_bar::WlBar>, ()>
+            where F: FnOnce(NewProxy<super::wl_bar::WlBar>) -> Proxy<super::wl_bar::WlBar>;
+    }
+
+    impl RequestsTrait for Proxy<WlFoo> {
+        fn foo_it<'s, S2: Into<::std::borrow::Cow<'s, str>>>(&self, number: i32, unumber: u32, text: S2, float: f64, file: ::std::os::unix::io::RawFd) ->Result<(), super::InteriorNulError>
+        {
+            let msg = Request::FooIt {
+                number: number,
+                unumber: unumber,
+                text: super::string_argument(text)?,
+                float: float,
+                file: file,
+            };
+            self.send(msg);
+            Ok(())
+        }
+
+        fn create_bar<F>(&self, implementor: F) ->Result<Proxy<super::wl_bar::WlBar>, ()>
+            where F: FnOnce(NewProxy<super::wl_bar::WlBar>) -> Proxy<super::wl_bar::WlBar>
+        {
+            let msg = Request::CreateBar {
+                id: self.child_placeholder(),
+            };
+            self.send_constructor(msg, implementor, None)
+        }
+
+    }
+}
+
+pub mod wl_bar {
+    //! Interface for bars
+    //!
+    //! This interface allows you to bar your foos.
+    use super::{Proxy, NewProxy, AnonymousObject, Interface, MessageGroup, MessageDesc, ArgumentType, Object, Message, Argument, ObjectMetadata};
+
+
+    #[repr(u32)]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub enum Error {
+        /// the bar was delivered without a foo
+        NoFoo = 0,
+        /// the bar was already delivered
+        DoubleDelivery = 1,
+    }
+    impl Error {
+        /// The entry of this enum with a given raw value, if any
+        pub fn from_raw(n: u32) -> Option<Error> {
+            match n {
+                0 => Some(Error::NoFoo),
+                1 => Some(Error::DoubleDelivery),
+
+                _ => Option::None
+            }
+        }
+        /// The raw value of this entry
+        pub fn to_raw(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    impl From<Error> for u32 {
+        fn from(err: Error) -> u32 {
+            err.to_raw()
+        }
+    }
+    impl Error {
+        /// The summary of this error, from the protocol description
+        pub fn summary(&self) -> &'static str {
+            match *self {
+                Error::NoFoo => "the bar was delivered without a foo",
+                Error::DoubleDelivery => "the bar was already delivered",
+            }
+        }
+    }
+    impl ::std::fmt::Display for Error {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            f.write_str(self.summary())
+        }
+    }
+    impl ::std::error::Error for Error {
+        fn description(&self) -> &str {
+            self.summary()
+        }
+    }
+
+    pub enum Request {
+        /// ask for a bar delivery
+        ///
+        /// Proceed to a bar delivery of given foo.
+        ///
+        /// Only available since version 2 of the interface
+        BarDelivery {
+            /// method of delivery allowed
+            kind: super::wl_foo::DeliveryKind,
+            /// bar to deliver
+            target: Proxy<super::wl_foo::WlFoo>,
+            /// delivery metadata
+            metadata: Vec<u8>,
+        },
+        /// release this bar
+        ///
+        /// Notify the compositor that you have finished using this bar.
+        ///
+        /// This is a destructor, once sent this object cannot be used any longer.
+        Release,
+    }
+
+    impl super::MessageGroup for Request {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+            super::MessageDesc {
+                name: "bar_delivery",
+                since: 2,
+                signature: &[
+                    super::ArgumentType::Uint,
+                    super::ArgumentType::Object,
+                    super::ArgumentType::Array,
+                ],
+                destructor: false,
+                fd_kinds: &[],
+            },
+            super::MessageDesc {
+                name: "release",
+                since: 1,
+                signature: &[
+                ],
+                destructor: true,
+                fd_kinds: &[],
+            },
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+                Request::Release => true,
+                _ => false
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+                Request::BarDelivery { .. } => 0,
+                Request::Release => 1,
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            panic!("Request::from_raw can not be used Client-side.")
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            match self {
+                Request::BarDelivery { kind, target, metadata, } => Message {
+                    sender_id: sender_id,
+                    opcode: 0,
+                    args: vec![
+                        Argument::Uint(kind.to_raw()),
+                        Argument::Object(target.id()),
+                        Argument::Array(metadata),
+                    ]
+                },
+                Request::Release => Message {
+                    sender_id: sender_id,
+                    opcode: 1,
+                    args: vec![
+                    ]
+                },
+            }
+        }
+
+    }
+
+    pub enum Event {
+    }
+
+    impl super::MessageGroup for Event {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            match msg.opcode {
+                _ => Err(()),
+            }
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            panic!("Event::into_raw can not be used Client-side.")
+        }
+
+    }
+
+
+    pub struct WlBar;
+
+    impl Interface for WlBar {
+        type Request = Request;
+        type Event = Event;
+        const NAME: &'static str = "wl_bar";
+        const VERSION: u32 = 1;
+
+    }
+
+    impl WlBar {
+        /// The C description of this interface
+        pub fn c_interface() -> *const super::sys::common::wl_interface {
+            unsafe { &super::super::c_interfaces::wl_bar_interface }
+        }
+    }
+
+    pub trait RequestsTrait {
+        /// ask for a bar delivery
+        ///
+        /// Proceed to a bar delivery of given foo.
+        ///
+        /// Only available since version 2 of the interface
+        ///
+        /// Arguments:
+        ///
+        /// - `kind`: method of delivery allowed
+        /// - `target`: bar to deliver
+        /// - `metadata`: delivery metadata
+        fn bar_delivery(&self, kind: super::wl_foo::DeliveryKind, target: &Proxy<super::wl_foo::WlFoo>, metadata: Vec<u8>) ->();
+        /// release this bar
+        ///
+        /// Notify the compositor that you have finished using this bar.
+        ///
+        /// This is a destructor, you cannot send requests to this object any longer once this method is called.
+        fn release(&self) ->();
+    }
+
+    impl RequestsTrait for Proxy<WlBar> {
+        fn bar_delivery(&self, kind: super::wl_foo::DeliveryKind, target: &Proxy<super::wl_foo::WlFoo>, metadata: Vec<u8>) ->()
+        {
+            let msg = Request::BarDelivery {
+                kind: kind,
+                target: target.clone(),
+                metadata: metadata,
+            };
+            self.send(msg);
+        }
+
+        fn release(&self) ->()
+        {
+            let msg = Request::Release;
+            self.send(msg);
+        }
+
+    }
+}
+
+pub mod wl_display {
+    //! core global object
+    //!
+    //! This global is special and should only generate code client-side, not server-side.
+    use super::{Proxy, NewProxy, AnonymousObject, Interface, MessageGroup, MessageDesc, ArgumentType, Object, Message, Argument, ObjectMetadata};
+
+    pub enum Request {
+    }
+
+    impl super::MessageGroup for Request {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            panic!("Request::from_raw can not be used Client-side.")
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            match self {
+            }
+        }
+
+    }
+
+    pub enum Event {
+    }
+
+    impl super::MessageGroup for Event {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            match msg.opcode {
+                _ => Err(()),
+            }
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            panic!("Event::into_raw can not be used Client-side.")
+        }
+
+    }
+
+
+    pub struct WlDisplay;
+
+    impl Interface for WlDisplay {
+        type Request = Request;
+        type Event = Event;
+        const NAME: &'static str = "wl_display";
+        const VERSION: u32 = 1;
+
+    }
+
+    impl WlDisplay {
+        /// The C description of this interface
+        pub fn c_interface() -> *const super::sys::common::wl_interface {
+            unsafe { &super::super::c_interfaces::wl_display_interface }
+        }
+    }
+
+    pub trait RequestsTrait {
+    }
+
+    impl RequestsTrait for Proxy<WlDisplay> {
+    }
+}
+
+pub mod wl_registry {
+    //! global registry object
+    //!
+    //! This global is special and should only generate code client-side, not server-side.
+    use super::{Proxy, NewProxy, AnonymousObject, Interface, MessageGroup, MessageDesc, ArgumentType, Object, Message, Argument, ObjectMetadata};
+
+    pub enum Request {
+        /// bind an object to the display
+        ///
+        /// This request is a special code-path, as its new-id argument as no target type.
+        Bind {
+            /// unique numeric name of the object
+            name: u32,
+            /// bounded object
+            id: (String, u32, Proxy<AnonymousObject>),
+        },
+    }
+
+    impl super::MessageGroup for Request {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+            super::MessageDesc {
+                name: "bind",
+                since: 1,
+                signature: &[
+                    super::ArgumentType::Uint,
+                    super::ArgumentType::NewId,
+                ],
+                destructor: false,
+                fd_kinds: &[],
+            },
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+                _ => false
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+                Request::Bind { .. } => 0,
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            panic!("Request::from_raw can not be used Client-side.")
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            match self {
+                Request::Bind { name, id, } => Message {
+                    sender_id: sender_id,
+                    opcode: 0,
+                    args: vec![
+                        Argument::Uint(name),
+                        Argument::Str(unsafe { ::std::ffi::CString::from_vec_unchecked(id.0.into()) }),
+                        Argument::Uint(id.1),
+                        Argument::NewId(id.2.id()),
+                    ]
+                },
+            }
+        }
+
+    }
+
+    pub enum Event {
+    }
+
+    impl super::MessageGroup for Event {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            match msg.opcode {
+                _ => Err(()),
+            }
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            panic!("Event::into_raw can not be used Client-side.")
+        }
+
+    }
+
+
+    pub struct WlRegistry;
+
+    impl Interface for WlRegistry {
+        type Request = Request;
+        type Event = Event;
+        const NAME: &'static str = "wl_registry";
+        const VERSION: u32 = 1;
+
+    }
+
+    impl WlRegistry {
+        /// The C description of this interface
+        pub fn c_interface() -> *const super::sys::common::wl_interface {
+            unsafe { &super::super::c_interfaces::wl_registry_interface }
+        }
+    }
+
+    pub trait RequestsTrait {
+        /// bind an object to the display
+        ///
+        /// This request is a special code-path, as its new-id argument as no target type.
+        ///
+        /// Arguments:
+        ///
+        /// - `name`: unique numeric name of the object
+        fn bind<T: Interface, F>(&self, version: u32, name: u32, implementor: F) ->Result<Proxy<T>, ()>
+            where F: FnOnce(NewProxy<T>) -> Proxy<T>;
+    }
+
+    impl RequestsTrait for Proxy<WlRegistry> {
+        fn bind<T: Interface, F>(&self, version: u32, name: u32, implementor: F) ->Result<Proxy<T>, ()>
+            where F: FnOnce(NewProxy<T>) -> Proxy<T>
+        {
+            let msg = Request::Bind {
+                name: name,
+                id: (T::NAME.into(), version, self.child_placeholder()),
+            };
+            self.send_constructor(msg, implementor, Some(version))
+        }
+
+    }
+}
+
+pub mod wl_callback {
+    //! callback object
+    //!
+    //! This object has a special behavior regarding its destructor.
+    use super::{Proxy, NewProxy, AnonymousObject, Interface, MessageGroup, MessageDesc, ArgumentType, Object, Message, Argument, ObjectMetadata};
+
+    pub enum Request {
+    }
+
+    impl super::MessageGroup for Request {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            panic!("Request::from_raw can not be used Client-side.")
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            match self {
+            }
+        }
+
+    }
+
+    pub enum Event {
+        /// done event
+        ///
+        /// This event is actually a destructor, but the protocol XML has no wait of specifying it.
+        /// As such, the scanner should consider wl_callback.done as a special case.
+        ///
+        /// This is a destructor, once received this object cannot be used any longer.
+        Done {
+            /// request-specific data for the callback
+            callback_data: u32,
+        },
+    }
+
+    impl super::MessageGroup for Event {
+        const MESSAGES: &'static [super::MessageDesc] = &[
+            super::MessageDesc {
+                name: "done",
+                since: 1,
+                signature: &[
+                    super::ArgumentType::Uint,
+                ],
+                destructor: true,
+                fd_kinds: &[],
+            },
+        ];
+        type Map = super::ProxyMap;
+        fn is_destructor(&self) -> bool {
+            match *self {
+                Event::Done { .. } => true,
+            }
+        }
+
+        fn opcode(&self) -> u16 {
+            match *self {
+                Event::Done { .. } => 0,
+            }
+        }
+
+        fn child<Meta: ObjectMetadata>(opcode: u16, version: u32, meta: &Meta) -> Option<Object<Meta>> {
+            match opcode {
+                _ => None
+            }
+        }
+
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            match msg.opcode {
+                0 => {
+                    let mut args = msg.args.into_iter();
+                    Ok(Event::Done {
+                        callback_data: {
+                            if let Some(Argument::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(())
+                            }
+                        },
+                    })
+                },
+                _ => Err(()),
+            }
+        }
+
+        fn into_raw(self, sender_id: u32) -> Message {
+            panic!("Event::into_raw can not be used Client-side.")
+        }
+
+    }
+
+
+    pub struct WlCallback;
+
+    impl Interface for WlCallback {
+        type Request = Request;
+        type Event = Event;
+        const NAME: &'static str = "wl_callback";
+        const VERSION: u32 = 1;
+
+    }
+
+    impl WlCallback {
+        /// The C description of this interface
+        pub fn c_interface() -> *const super::sys::common::wl_interface {
+            unsafe { &super::super::c_interfaces::wl_callback_interface }
+        }
+    }
+
+    pub trait RequestsTrait {
+    }
+
+    impl RequestsTrait for Proxy<WlCallback> {
+    }
+}
+
+/// The minimum versions of the interfaces supporting the messages and enum entries of this protocol
+pub const VERSION_MATRIX: VersionMatrix = VersionMatrix {
+    requests: &[
+        MessageVersion { interface: "wl_foo", name: "foo_it", since: 1 },
+        MessageVersion { interface: "wl_foo", name: "create_bar", since: 1 },
+        MessageVersion { interface: "wl_bar", name: "bar_delivery", since: 2 },
+        MessageVersion { interface: "wl_bar", name: "release", since: 1 },
+        MessageVersion { interface: "wl_registry", name: "bind", since: 1 },
+    ],
+    events: &[
+        MessageVersion { interface: "wl_foo", name: "cake", since: 2 },
+        MessageVersion { interface: "wl_callback", name: "done", since: 1 },
+    ],
+    enum_entries: &[
+        EnumEntryVersion { interface: "wl_foo", enumeration: "cake_kind", entry: "basic", since: 2 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "cake_kind", entry: "spicy", since: 2 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "cake_kind", entry: "fruity", since: 3 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "delivery_kind", entry: "pick_up", since: 1 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "delivery_kind", entry: "drone", since: 1 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "delivery_kind", entry: "catapult", since: 1 },
+        EnumEntryVersion { interface: "wl_bar", enumeration: "error", entry: "no_foo", since: 1 },
+        EnumEntryVersion { interface: "wl_bar", enumeration: "error", entry: "double_delivery", since: 1 },
+    ],
+};
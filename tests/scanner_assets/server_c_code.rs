@@ -33,6 +33,7 @@ pub mod wl_foo {
         Fruity = 2,
     }
     impl CakeKind {
+        /// The entry of this enum with a given raw value, if any
         pub fn from_raw(n: u32) -> Option<CakeKind> {
             match n {
                 0 => Some(CakeKind::Basic),
@@ -42,6 +43,7 @@ pub mod wl_foo {
                 _ => Option::None
             }
         }
+        /// The raw value of this entry
         pub fn to_raw(&self) -> u32 {
             *self as u32
         }
@@ -60,10 +62,11 @@ pub mod wl_foo {
         }
     }
     impl DeliveryKind {
+        /// Create a set of flags from its raw value, ignoring the unknown bits
         pub fn from_raw(n: u32) -> Option<DeliveryKind> {
             Some(DeliveryKind::from_bits_truncate(n))
-
         }
+        /// The raw value of this set of flags
         pub fn to_raw(&self) -> u32 {
             self.bits()
         }
@@ -73,11 +76,25 @@ pub mod wl_foo {
         /// do some foo
         ///
         /// This will do some foo with its args.
-        FooIt {number: i32, unumber: u32, text: String, float: f64, file: ::std::os::unix::io::RawFd, },
+        FooIt {
+            /// the number that will be fooed
+            number: i32,
+            /// the unsigned number that will be fooed
+            unumber: u32,
+            /// the text that will be fooed
+            text: String,
+            /// the float number that will be fooed
+            float: f64,
+            /// the file descriptor to write all this foo in
+            file: ::std::os::unix::io::RawFd,
+        },
         /// create a bar
         ///
         /// Create a bar which will do its bar job.
-        CreateBar {id: NewResource<super::wl_bar::WlBar>, },
+        CreateBar {
+            /// created bar
+            id: NewResource<super::wl_bar::WlBar>,
+        },
     }
 
     impl super::MessageGroup for Request {
@@ -91,14 +108,18 @@ pub mod wl_foo {
                     super::ArgumentType::Str,
                     super::ArgumentType::Fixed,
                     super::ArgumentType::Fd,
-                ]
+                ],
+                destructor: false,
+                fd_kinds: &[],
             },
             super::MessageDesc {
                 name: "create_bar",
                 since: 1,
                 signature: &[
                     super::ArgumentType::NewId,
-                ]
+                ],
+                destructor: false,
+                fd_kinds: &[],
             },
         ];
         type Map = super::ResourceMap;
@@ -216,7 +237,12 @@ pub mod wl_foo {
         /// The server advertizes that a kind of cake is available
         ///
         /// Only available since version 2 of the interface
-        Cake {kind: CakeKind, amount: u32, },
+        Cake {
+            /// name of this kind of cake
+            kind: CakeKind,
+            /// amount available
+            amount: u32,
+        },
     }
 
     impl super::MessageGroup for Event {
@@ -227,7 +253,9 @@ pub mod wl_foo {
                 signature: &[
                     super::ArgumentType::Uint,
                     super::ArgumentType::Uint,
-                ]
+                ],
+                destructor: false,
+                fd_kinds: &[],
             },
         ];
         type Map = super::ResourceMap;
@@ -307,13 +335,70 @@ pub mod wl_bar {
 
     use super::sys::common::{wl_argument, wl_interface, wl_array};
     use super::sys::server::*;
+
+    #[repr(u32)]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub enum Error {
+        /// the bar was delivered without a foo
+        NoFoo = 0,
+        /// the bar was already delivered
+        DoubleDelivery = 1,
+    }
+    impl Error {
+        /// The entry of this enum with a given raw value, if any
+        pub fn from_raw(n: u32) -> Option<Error> {
+            match n {
+                0 => Some(Error::NoFoo),
+                1 => Some(Error::DoubleDelivery),
+
+                _ => Option::None
+            }
+        }
+        /// The raw value of this entry
+        pub fn to_raw(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    impl From<Error> for u32 {
+        fn from(err: Error) -> u32 {
+            err.to_raw()
+        }
+    }
+    impl Error {
+        /// The summary of this error, from the protocol description
+        pub fn summary(&self) -> &'static str {
+            match *self {
+                Error::NoFoo => "the bar was delivered without a foo",
+                Error::DoubleDelivery => "the bar was already delivered",
+            }
+        }
+    }
+    impl ::std::fmt::Display for Error {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            f.write_str(self.summary())
+        }
+    }
+    impl ::std::error::Error for Error {
+        fn description(&self) -> &str {
+            self.summary()
+        }
+    }
+
     pub enum Request {
         /// ask for a bar delivery
         ///
         /// Proceed to a bar delivery of given foo.
         ///
         /// Only available since version 2 of the interface
-        BarDelivery {kind: super::wl_foo::DeliveryKind, target: Resource<super::wl_foo::WlFoo>, metadata: Vec<u8>, },
+        BarDelivery {
+            /// method of delivery allowed
+            kind: super::wl_foo::DeliveryKind,
+            /// bar to deliver
+            target: Resource<super::wl_foo::WlFoo>,
+            /// delivery metadata
+            metadata: Vec<u8>,
+        },
         /// release this bar
         ///
         /// Notify the compositor that you have finished using this bar.
@@ -331,13 +416,17 @@ pub mod wl_bar {
                     super::ArgumentType::Uint,
                     super::ArgumentType::Object,
                     super::ArgumentType::Array,
-                ]
+                ],
+                destructor: false,
+                fd_kinds: &[],
             },
             super::MessageDesc {
                 name: "release",
                 since: 1,
                 signature: &[
-                ]
+                ],
+                destructor: true,
+                fd_kinds: &[],
             },
         ];
         type Map = super::ResourceMap;
@@ -538,7 +627,10 @@ pub mod wl_callback {
         /// As such, the scanner should consider wl_callback.done as a special case.
         ///
         /// This is a destructor, once sent this object cannot be used any longer.
-        Done {callback_data: u32, },
+        Done {
+            /// request-specific data for the callback
+            callback_data: u32,
+        },
     }
 
     impl super::MessageGroup for Event {
@@ -548,7 +640,9 @@ pub mod wl_callback {
                 since: 1,
                 signature: &[
                     super::ArgumentType::Uint,
-                ]
+                ],
+                destructor: true,
+                fd_kinds: &[],
             },
         ];
         type Map = super::ResourceMap;
@@ -618,3 +712,26 @@ pub mod wl_callback {
     }
 }
 
+/// The minimum versions of the interfaces supporting the messages and enum entries of this protocol
+pub const VERSION_MATRIX: VersionMatrix = VersionMatrix {
+    requests: &[
+        MessageVersion { interface: "wl_foo", name: "foo_it", since: 1 },
+        MessageVersion { interface: "wl_foo", name: "create_bar", since: 1 },
+        MessageVersion { interface: "wl_bar", name: "bar_delivery", since: 2 },
+        MessageVersion { interface: "wl_bar", name: "release", since: 1 },
+    ],
+    events: &[
+        MessageVersion { interface: "wl_foo", name: "cake", since: 2 },
+        MessageVersion { interface: "wl_callback", name: "done", since: 1 },
+    ],
+    enum_entries: &[
+        EnumEntryVersion { interface: "wl_foo", enumeration: "cake_kind", entry: "basic", since: 2 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "cake_kind", entry: "spicy", since: 2 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "cake_kind", entry: "fruity", since: 3 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "delivery_kind", entry: "pick_up", since: 1 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "delivery_kind", entry: "drone", since: 1 },
+        EnumEntryVersion { interface: "wl_foo", enumeration: "delivery_kind", entry: "catapult", since: 1 },
+        EnumEntryVersion { interface: "wl_bar", enumeration: "error", entry: "no_foo", since: 1 },
+        EnumEntryVersion { interface: "wl_bar", enumeration: "error", entry: "double_delivery", since: 1 },
+    ],
+};
@@ -0,0 +1,789 @@
+
+//
+// This file was auto-generated, do not edit directly.
+//
+
+/*
+This is an example copyright.
+    It contains several lines.
+    AS WELL AS ALL CAPS TEXT.
+*/
+
+/// The reasons why a message could not be parsed
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WireError {
+    /// The opcode does not match any message of the interface
+    UnknownOpcode,
+    /// The message ended before all of its arguments were read
+    MissingArgument,
+    /// The message is longer than its arguments
+    TrailingData,
+    /// A string is not nul-terminated or is not valid UTF-8
+    InvalidString,
+    /// A null object or string was given to an argument which does not allow it
+    NullArgument,
+    /// There are less file descriptors than the fd arguments of the message
+    MissingFd,
+}
+
+/// Parse the header of the message at the start of `words`
+///
+/// Returns the id of the sender object, the opcode, and the length of the whole
+/// message in words, or `None` if `words` is shorter than the header.
+pub fn parse_header(words: &[u32]) -> Option<(u32, u16, usize)> {
+    if words.len() < 2 {
+        return None;
+    }
+    Some((words[0], words[1] as u16, (words[1] >> 16) as usize / 4))
+}
+
+// the bytes of a string or an array, stored in native endianness like the words
+fn wire_bytes_from_words(words: &[u32], len: usize) -> ::alloc::vec::Vec<u8> {
+    let mut bytes = ::alloc::vec::Vec::with_capacity(4 * words.len());
+    for &word in words {
+        for i in 0..4 {
+            let shift = if cfg!(target_endian = "little") { 8 * i } else { 24 - 8 * i };
+            bytes.push((word >> shift) as u8);
+        }
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+fn wire_write_array(words: &mut ::alloc::vec::Vec<u32>, bytes: &[u8]) {
+    words.push(bytes.len() as u32);
+    for chunk in bytes.chunks(4) {
+        let mut word = 0;
+        for (i, &byte) in chunk.iter().enumerate() {
+            let shift = if cfg!(target_endian = "little") { 8 * i } else { 24 - 8 * i };
+            word |= u32::from(byte) << shift;
+        }
+        words.push(word);
+    }
+}
+
+fn wire_write_string(words: &mut ::alloc::vec::Vec<u32>, string: Option<&str>) {
+    match string {
+        Some(string) => {
+            let mut bytes = ::alloc::vec::Vec::with_capacity(string.len() + 1);
+            bytes.extend_from_slice(string.as_bytes());
+            bytes.push(0);
+            wire_write_array(words, &bytes);
+        }
+        None => words.push(0),
+    }
+}
+
+// reads the arguments of a message
+struct WireReader<'a> {
+    words: &'a [u32],
+}
+
+impl<'a> WireReader<'a> {
+    fn word(&mut self) -> Result<u32, WireError> {
+        match self.words.split_first() {
+            Some((&word, rest)) => {
+                self.words = rest;
+                Ok(word)
+            }
+            None => Err(WireError::MissingArgument),
+        }
+    }
+
+    fn object(&mut self) -> Result<Option<u32>, WireError> {
+        self.word().map(|id| if id == 0 { None } else { Some(id) })
+    }
+
+    fn array(&mut self) -> Result<::alloc::vec::Vec<u8>, WireError> {
+        let len = self.word()? as usize;
+        let count = (len + 3) / 4;
+        if self.words.len() < count {
+            return Err(WireError::MissingArgument);
+        }
+        let (data, rest) = self.words.split_at(count);
+        self.words = rest;
+        Ok(wire_bytes_from_words(data, len))
+    }
+
+    fn string(&mut self) -> Result<Option<::alloc::string::String>, WireError> {
+        let mut bytes = self.array()?;
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        if bytes.pop() != Some(0) {
+            return Err(WireError::InvalidString);
+        }
+        ::alloc::string::String::from_utf8(bytes)
+            .map(Some)
+            .map_err(|_| WireError::InvalidString)
+    }
+
+    fn finish(&self) -> Result<(), WireError> {
+        if self.words.is_empty() {
+            Ok(())
+        } else {
+            Err(WireError::TrailingData)
+        }
+    }
+}
+
+pub mod wl_foo {
+    //! Interface for fooing
+    //!
+    //! This is the dedicated interface for doing foos over any
+    //! kind of other foos.
+    use ::alloc::string::String;
+    use ::alloc::vec::Vec;
+    use super::{wire_write_array, wire_write_string, WireError, WireReader};
+
+    /// The name of this interface
+    pub const NAME: &'static str = "wl_foo";
+    /// The version of this interface described by the protocol
+    pub const VERSION: u32 = 3;
+
+    /// Possible cake kinds
+    ///
+    /// List of the possible kind of cake supported by the protocol.
+
+    #[repr(u32)]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub enum CakeKind {
+        /// mild cake without much flavor
+        Basic = 0,
+        /// spicy cake to burn your tongue
+        Spicy = 1,
+        /// fruity cake to get vitamins
+        Fruity = 2,
+    }
+    impl CakeKind {
+        /// The entry of this enum with a given raw value, if any
+        pub fn from_raw(n: u32) -> Option<CakeKind> {
+            match n {
+                0 => Some(CakeKind::Basic),
+                1 => Some(CakeKind::Spicy),
+                2 => Some(CakeKind::Fruity),
+
+                _ => Option::None
+            }
+        }
+        /// The raw value of this entry
+        pub fn to_raw(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    bitflags! {
+        /// possible delivery modes
+        ///
+        pub struct DeliveryKind: u32 {
+            /// pick your cake up yourself
+            const PickUp = 1;
+            /// flying drone delivery
+            const Drone = 2;
+            /// because we fear nothing
+            const Catapult = 4;
+        }
+    }
+    impl DeliveryKind {
+        /// Create a set of flags from its raw value, ignoring the unknown bits
+        pub fn from_raw(n: u32) -> Option<DeliveryKind> {
+            Some(DeliveryKind::from_bits_truncate(n))
+        }
+        /// The raw value of this set of flags
+        pub fn to_raw(&self) -> u32 {
+            self.bits()
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Request {
+        /// do some foo
+        ///
+        /// This will do some foo with its args.
+        FooIt {
+            /// the number that will be fooed
+            number: i32,
+            /// the unsigned number that will be fooed
+            unumber: u32,
+            /// the text that will be fooed
+            text: String,
+            /// the float number that will be fooed
+            float: f64,
+            /// the file descriptor to write all this foo in
+            file: i32,
+        },
+        /// create a bar
+        ///
+        /// Create a bar which will do its bar job.
+        CreateBar {
+            /// created bar
+            id: u32,
+        },
+    }
+
+    impl Request {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+                Request::FooIt { .. } => 0,
+                Request::CreateBar { .. } => 1,
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Request, WireError> {
+            let mut reader = WireReader { words: args };
+            let message = match opcode {
+                0 => Request::FooIt {
+                    number: reader.word()? as i32,
+                    unumber: reader.word()?,
+                    text: reader.string()?.ok_or(WireError::NullArgument)?,
+                    float: f64::from(reader.word()? as i32) / 256.0,
+                    file: fds.next().ok_or(WireError::MissingFd)?,
+                },
+                1 => Request::CreateBar {
+                    id: reader.word()?,
+                },
+                _ => return Err(WireError::UnknownOpcode),
+            };
+            reader.finish()?;
+            Ok(message)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            let start = words.len();
+            words.push(sender_id);
+            words.push(0);
+            match *self {
+                Request::FooIt { ref number, ref unumber, ref text, ref float, ref file } => {
+                    words.push(*number as u32);
+                    words.push(*unumber);
+                    wire_write_string(words, Some(&text[..]));
+                    words.push((*float * 256.0) as i32 as u32);
+                    fds.push(*file);
+                }
+                Request::CreateBar { ref id } => {
+                    words.push(*id);
+                }
+            }
+            let size = (4 * (words.len() - start)) as u32;
+            words[start + 1] = (size << 16) | u32::from(self.opcode());
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Event {
+        /// a cake is possible
+        ///
+        /// The server advertizes that a kind of cake is available
+        ///
+        /// Only available since version 2 of the interface
+        Cake {
+            /// name of this kind of cake
+            kind: u32,
+            /// amount available
+            amount: u32,
+        },
+    }
+
+    impl Event {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+                Event::Cake { .. } => 0,
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Event, WireError> {
+            let mut reader = WireReader { words: args };
+            let message = match opcode {
+                0 => Event::Cake {
+                    kind: reader.word()?,
+                    amount: reader.word()?,
+                },
+                _ => return Err(WireError::UnknownOpcode),
+            };
+            reader.finish()?;
+            Ok(message)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            let start = words.len();
+            words.push(sender_id);
+            words.push(0);
+            match *self {
+                Event::Cake { ref kind, ref amount } => {
+                    words.push(*kind);
+                    words.push(*amount);
+                }
+            }
+            let size = (4 * (words.len() - start)) as u32;
+            words[start + 1] = (size << 16) | u32::from(self.opcode());
+        }
+    }
+
+}
+
+pub mod wl_bar {
+    //! Interface for bars
+    //!
+    //! This interface allows you to bar your foos.
+    use ::alloc::string::String;
+    use ::alloc::vec::Vec;
+    use super::{wire_write_array, wire_write_string, WireError, WireReader};
+
+    /// The name of this interface
+    pub const NAME: &'static str = "wl_bar";
+    /// The version of this interface described by the protocol
+    pub const VERSION: u32 = 1;
+
+
+    #[repr(u32)]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub enum Error {
+        /// the bar was delivered without a foo
+        NoFoo = 0,
+        /// the bar was already delivered
+        DoubleDelivery = 1,
+    }
+    impl Error {
+        /// The entry of this enum with a given raw value, if any
+        pub fn from_raw(n: u32) -> Option<Error> {
+            match n {
+                0 => Some(Error::NoFoo),
+                1 => Some(Error::DoubleDelivery),
+
+                _ => Option::None
+            }
+        }
+        /// The raw value of this entry
+        pub fn to_raw(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    impl From<Error> for u32 {
+        fn from(err: Error) -> u32 {
+            err.to_raw()
+        }
+    }
+    impl Error {
+        /// The summary of this error, from the protocol description
+        pub fn summary(&self) -> &'static str {
+            match *self {
+                Error::NoFoo => "the bar was delivered without a foo",
+                Error::DoubleDelivery => "the bar was already delivered",
+            }
+        }
+    }
+    impl ::core::fmt::Display for Error {
+        fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+            f.write_str(self.summary())
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Request {
+        /// ask for a bar delivery
+        ///
+        /// Proceed to a bar delivery of given foo.
+        ///
+        /// Only available since version 2 of the interface
+        BarDelivery {
+            /// method of delivery allowed
+            kind: u32,
+            /// bar to deliver
+            target: u32,
+            /// delivery metadata
+            metadata: Vec<u8>,
+        },
+        /// release this bar
+        ///
+        /// Notify the compositor that you have finished using this bar.
+        Release,
+    }
+
+    impl Request {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+                Request::BarDelivery { .. } => 0,
+                Request::Release => 1,
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Request, WireError> {
+            let mut reader = WireReader { words: args };
+            let message = match opcode {
+                0 => Request::BarDelivery {
+                    kind: reader.word()?,
+                    target: reader.object()?.ok_or(WireError::NullArgument)?,
+                    metadata: reader.array()?,
+                },
+                1 => Request::Release,
+                _ => return Err(WireError::UnknownOpcode),
+            };
+            reader.finish()?;
+            Ok(message)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            let start = words.len();
+            words.push(sender_id);
+            words.push(0);
+            match *self {
+                Request::BarDelivery { ref kind, ref target, ref metadata } => {
+                    words.push(*kind);
+                    words.push(*target);
+                    wire_write_array(words, metadata);
+                }
+                Request::Release => {}
+            }
+            let size = (4 * (words.len() - start)) as u32;
+            words[start + 1] = (size << 16) | u32::from(self.opcode());
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Event {
+    }
+
+    impl Event {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Event, WireError> {
+            Err(WireError::UnknownOpcode)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            match *self {}
+        }
+    }
+
+}
+
+pub mod wl_display {
+    //! core global object
+    //!
+    //! This global is special and should only generate code client-side, not server-side.
+    use ::alloc::string::String;
+    use ::alloc::vec::Vec;
+    use super::{wire_write_array, wire_write_string, WireError, WireReader};
+
+    /// The name of this interface
+    pub const NAME: &'static str = "wl_display";
+    /// The version of this interface described by the protocol
+    pub const VERSION: u32 = 1;
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Request {
+    }
+
+    impl Request {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Request, WireError> {
+            Err(WireError::UnknownOpcode)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            match *self {}
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Event {
+    }
+
+    impl Event {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Event, WireError> {
+            Err(WireError::UnknownOpcode)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            match *self {}
+        }
+    }
+
+}
+
+pub mod wl_registry {
+    //! global registry object
+    //!
+    //! This global is special and should only generate code client-side, not server-side.
+    use ::alloc::string::String;
+    use ::alloc::vec::Vec;
+    use super::{wire_write_array, wire_write_string, WireError, WireReader};
+
+    /// The name of this interface
+    pub const NAME: &'static str = "wl_registry";
+    /// The version of this interface described by the protocol
+    pub const VERSION: u32 = 1;
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Request {
+        /// bind an object to the display
+        ///
+        /// This request is a special code-path, as its new-id argument as no target type.
+        Bind {
+            /// unique numeric name of the object
+            name: u32,
+            /// bounded object
+            id: (String, u32, u32),
+        },
+    }
+
+    impl Request {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+                Request::Bind { .. } => 0,
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Request, WireError> {
+            let mut reader = WireReader { words: args };
+            let message = match opcode {
+                0 => Request::Bind {
+                    name: reader.word()?,
+                    id: (reader.string()?.ok_or(WireError::NullArgument)?, reader.word()?, reader.word()?),
+                },
+                _ => return Err(WireError::UnknownOpcode),
+            };
+            reader.finish()?;
+            Ok(message)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            let start = words.len();
+            words.push(sender_id);
+            words.push(0);
+            match *self {
+                Request::Bind { ref name, ref id } => {
+                    words.push(*name);
+                    wire_write_string(words, Some(&id.0[..]));
+                    words.push(id.1);
+                    words.push(id.2);
+                }
+            }
+            let size = (4 * (words.len() - start)) as u32;
+            words[start + 1] = (size << 16) | u32::from(self.opcode());
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Event {
+    }
+
+    impl Event {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Event, WireError> {
+            Err(WireError::UnknownOpcode)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            match *self {}
+        }
+    }
+
+}
+
+pub mod wl_callback {
+    //! callback object
+    //!
+    //! This object has a special behavior regarding its destructor.
+    use ::alloc::string::String;
+    use ::alloc::vec::Vec;
+    use super::{wire_write_array, wire_write_string, WireError, WireReader};
+
+    /// The name of this interface
+    pub const NAME: &'static str = "wl_callback";
+    /// The version of this interface described by the protocol
+    pub const VERSION: u32 = 1;
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Request {
+    }
+
+    impl Request {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Request, WireError> {
+            Err(WireError::UnknownOpcode)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            match *self {}
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Event {
+        /// done event
+        ///
+        /// This event is actually a destructor, but the protocol XML has no wait of specifying it.
+        /// As such, the scanner should consider wl_callback.done as a special case.
+        Done {
+            /// request-specific data for the callback
+            callback_data: u32,
+        },
+    }
+
+    impl Event {
+        /// The opcode of this message
+        pub fn opcode(&self) -> u16 {
+            match *self {
+                Event::Done { .. } => 0,
+            }
+        }
+
+        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order.
+        pub fn parse<F: Iterator<Item = i32>>(
+            opcode: u16,
+            args: &[u32],
+            fds: &mut F,
+        ) -> Result<Event, WireError> {
+            let mut reader = WireReader { words: args };
+            let message = match opcode {
+                0 => Event::Done {
+                    callback_data: reader.word()?,
+                },
+                _ => return Err(WireError::UnknownOpcode),
+            };
+            reader.finish()?;
+            Ok(message)
+        }
+
+        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`.
+        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {
+            let start = words.len();
+            words.push(sender_id);
+            words.push(0);
+            match *self {
+                Event::Done { ref callback_data } => {
+                    words.push(*callback_data);
+                }
+            }
+            let size = (4 * (words.len() - start)) as u32;
+            words[start + 1] = (size << 16) | u32::from(self.opcode());
+        }
+    }
+
+}
+
@@ -0,0 +1,66 @@
+#![cfg(feature = "scanner_serde")]
+
+#[macro_use]
+extern crate bitflags;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate wayland_client;
+extern crate wayland_commons;
+
+use serde_json::{from_str, to_string};
+
+// the code generated with the `serde` option, compiled as it would be in a protocol crate
+#[allow(dead_code, non_camel_case_types, non_upper_case_globals, non_snake_case)]
+#[allow(unused_imports, unused_variables)]
+mod client {
+    pub(crate) use wayland_client::{NewProxy, Proxy, ProxyMap};
+    pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+    pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
+    pub(crate) use wayland_commons::wire::{Argument, ArgumentType, FdKind, Message, MessageDesc};
+    pub(crate) use wayland_commons::wire::{string_argument, InteriorNulError, RequestError};
+    pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
+    include!("./scanner_assets/client_rust_serde_code.rs");
+}
+
+use client::wl_callback::Event as CallbackEvent;
+use client::wl_foo::{CakeKind, DeliveryKind, Event as FooEvent};
+
+#[test]
+fn serde_enums_roundtrip() {
+    for &kind in &[CakeKind::Basic, CakeKind::Spicy, CakeKind::Fruity] {
+        let json = to_string(&kind).unwrap();
+        assert_eq!(from_str::<CakeKind>(&json).unwrap(), kind);
+    }
+    assert_eq!(to_string(&CakeKind::Spicy).unwrap(), "\"Spicy\"");
+
+    let flags = DeliveryKind::PickUp | DeliveryKind::Catapult;
+    let json = to_string(&flags).unwrap();
+    assert_eq!(json, "{\"bits\":5}");
+    assert_eq!(from_str::<DeliveryKind>(&json).unwrap(), flags);
+}
+
+#[test]
+fn serde_messages_roundtrip() {
+    let cake = FooEvent::Cake {
+        kind: CakeKind::Fruity,
+        amount: 42,
+    };
+    let json = to_string(&cake).unwrap();
+    assert_eq!(json, "{\"Cake\":{\"kind\":\"Fruity\",\"amount\":42}}");
+    match from_str::<FooEvent>(&json).unwrap() {
+        FooEvent::Cake { kind, amount } => assert_eq!((kind, amount), (CakeKind::Fruity, 42)),
+    }
+    // the serialization of the deserialized value is identical
+    assert_eq!(to_string(&from_str::<FooEvent>(&json).unwrap()).unwrap(), json);
+
+    let done = CallbackEvent::Done { callback_data: 7 };
+    let json = to_string(&done).unwrap();
+    match from_str::<CallbackEvent>(&json).unwrap() {
+        CallbackEvent::Done { callback_data } => assert_eq!(callback_data, 7),
+    }
+
+    // unknown variants and missing fields are rejected
+    assert!(from_str::<FooEvent>("{\"Pie\":{\"amount\":42}}").is_err());
+    assert!(from_str::<FooEvent>("{\"Cake\":{\"kind\":\"Fruity\"}}").is_err());
+}
@@ -6,9 +6,17 @@ fn ensure_both<I: Send + Sync>() {}
 #[test]
 fn send_sync_client() {
     ensure_both::<wayc::Display>();
+    ensure_both::<wayc::QueueHandle>();
     ensure_both::<wayc::Proxy<::wayc::protocol::wl_callback::WlCallback>>();
 }
 
+#[cfg(feature = "native_lib")]
+#[test]
+fn send_sync_cursor() {
+    ensure_both::<wayc::cursor::CursorTheme>();
+    ensure_both::<wayc::cursor::CursorThemeCache>();
+}
+
 #[test]
 fn send_sync_server() {
     ensure_both::<ways::Resource<::ways::protocol::wl_callback::WlCallback>>();
@@ -5,7 +5,7 @@ use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
 use ways::protocol::{wl_compositor, wl_output};
 
 use wayc::protocol::wl_compositor::WlCompositor as ClientCompositor;
-use wayc::protocol::wl_output::WlOutput as ClientOutput;
+use wayc::protocol::wl_output::{RequestsTrait as OutputRequests, WlOutput as ClientOutput};
 
 use std::sync::{Arc, Mutex};
 
@@ -77,3 +77,144 @@ fn client_user_data() {
     assert!(clients[1].data_map().get::<HasCompositor>().is_some());
     assert!(clients[1].data_map().get::<HasOutput>().is_some());
 }
+
+#[test]
+fn client_credentials() {
+    let mut server = TestServer::new();
+    let clients = Arc::new(Mutex::new(Vec::new()));
+
+    server.display.create_global::<wl_output::WlOutput, _>(1, {
+        let clients = clients.clone();
+        move |newo, _| {
+            let output = newo.implement(|_, _| {}, None::<fn(_)>, ());
+            clients.lock().unwrap().push(output.client().unwrap());
+        }
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    manager
+        .instantiate_auto::<ClientOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let clients = clients.lock().unwrap();
+    assert!(clients.len() == 1);
+    // the test client lives in this process
+    let credentials = clients[0].credentials().unwrap();
+    assert_eq!(credentials.pid as u32, ::std::process::id());
+
+    // there are none once the client is dead
+    clients[0].kill();
+    assert!(clients[0].credentials().is_none());
+}
+
+#[test]
+fn client_resources() {
+    let mut server = TestServer::new();
+    let clients = Arc::new(Mutex::new(Vec::new()));
+
+    struct Focus(u32);
+
+    server.display.create_global::<wl_output::WlOutput, _>(3, {
+        let clients = clients.clone();
+        move |newo, _| {
+            let output = newo.implement(|_, _| {}, None::<fn(_)>, ());
+            let client = output.client().unwrap();
+            // only the first one is attached
+            client.set_user_data(Focus(output.id()));
+            clients.lock().unwrap().push(client);
+        }
+    });
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(1, |newo, _| {
+            newo.implement(|_, _| {}, None::<fn(_)>, ());
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let output = manager
+        .instantiate_auto::<ClientOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    manager
+        .instantiate_auto::<ClientOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    manager
+        .instantiate_auto::<ClientCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let clients = clients.lock().unwrap();
+    assert!(clients.len() == 2);
+    assert_eq!(clients[1].data::<Focus>().unwrap().0, output.id());
+    assert!(!clients[1].set_user_data(Focus(0)));
+
+    let outputs = clients[0].resources_for_interface::<wl_output::WlOutput>();
+    assert_eq!(outputs.len(), 2);
+    assert!(outputs.iter().any(|o| o.id() == output.id()));
+    assert_eq!(clients[0].resources_for_interface::<wl_compositor::WlCompositor>().len(), 1);
+
+    output.release();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(clients[0].resources_for_interface::<wl_output::WlOutput>().len(), 1);
+
+    clients[0].kill();
+    assert!(clients[0].resources_for_interface::<wl_output::WlOutput>().is_empty());
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn threaded_flush_scheduling() {
+    use std::io::{ErrorKind, Read, Write};
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    use ways::protocol::wl_callback;
+
+    let mut server = TestServer::new();
+    server.display.set_flush_scheduling(ways::FlushScheduling::Threaded);
+
+    // a client which does not read its socket, filled with garbage beforehand
+    let (server_end, mut client_end) = UnixStream::pair().unwrap();
+    let mut filler = server_end.try_clone().unwrap();
+    filler.set_nonblocking(true).unwrap();
+    let mut filled = 0;
+    loop {
+        match filler.write(&[0u8; 4096]) {
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => panic!("Failed to fill the socket: {}", e),
+        }
+    }
+    filler.set_nonblocking(false).unwrap();
+    let slow_client = unsafe { server.display.create_client(server_end.into_raw_fd()) };
+    let callback = slow_client
+        .create_resource::<wl_callback::WlCallback>(1)
+        .unwrap()
+        .implement(|r, _| match r {}, None::<fn(_)>, ());
+    callback.send(wl_callback::Event::Done { callback_data: 42 });
+
+    // the other clients are not blocked behind it
+    let mut client = TestClient::new(&server.socket_name);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // it gets its event once it reads its socket
+    client_end.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut garbage = vec![0u8; filled];
+    client_end.read_exact(&mut garbage).unwrap();
+    let mut done = [0u8; 12];
+    client_end.read_exact(&mut done).unwrap();
+    // header: sender id then opcode 0 and size 12, followed by callback_data
+    assert_eq!(&done[4..8], &[0, 0, 12, 0]);
+    assert_eq!(&done[8..12], &[42, 0, 0, 0]);
+}
@@ -0,0 +1,157 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::coalesce::PointerCoalescer;
+use ways::protocol::{wl_pointer as server_pointer, wl_seat as server_seat};
+
+use wayc::protocol::wl_pointer;
+use wayc::protocol::wl_seat::{self, RequestsTrait as SeatRequests};
+
+use std::sync::{Arc, Mutex};
+
+fn motion(time: u32, x: f64) -> server_pointer::Event {
+    server_pointer::Event::Motion {
+        time,
+        surface_x: x,
+        surface_y: x,
+    }
+}
+
+fn axis(time: u32, value: f64) -> server_pointer::Event {
+    server_pointer::Event::Axis {
+        time,
+        axis: server_pointer::Axis::VerticalScroll,
+        value,
+    }
+}
+
+fn button(time: u32) -> server_pointer::Event {
+    server_pointer::Event::Button {
+        serial: 0,
+        time,
+        button: 0x110,
+        state: server_pointer::ButtonState::Pressed,
+    }
+}
+
+// run the events given by `events` through a coalescer, and collect what the client receives
+fn coalesced<F>(version: u32, events: F) -> Vec<String>
+where
+    F: Fn(&mut PointerCoalescer) + Send + Sync + 'static,
+{
+    let mut server = TestServer::new();
+    let events = Arc::new(events);
+    server
+        .display
+        .create_global::<server_seat::WlSeat, _>(version, move |newseat, _| {
+            let events = events.clone();
+            newseat.implement(
+                move |request, _| {
+                    if let server_seat::Request::GetPointer { id } = request {
+                        let pointer = id.implement(|_, _| {}, None::<fn(_)>, ());
+                        let mut coalescer = PointerCoalescer::new(pointer);
+                        events(&mut coalescer);
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let seat = manager
+        .instantiate_exact::<wl_seat::WlSeat, _>(version, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received2 = received.clone();
+    seat.get_pointer(move |newp| {
+        newp.implement(
+            move |event, _| {
+                let text = match event {
+                    wl_pointer::Event::Motion { time, surface_x, .. } => {
+                        format!("motion {} {}", time, surface_x)
+                    }
+                    wl_pointer::Event::Axis { time, value, .. } => format!("axis {} {}", time, value),
+                    wl_pointer::Event::Button { time, .. } => format!("button {}", time),
+                    wl_pointer::Event::Frame => "frame".into(),
+                    _ => "other".into(),
+                };
+                received2.lock().unwrap().push(text);
+            },
+            (),
+        )
+    }).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let received = received.lock().unwrap().clone();
+    received
+}
+
+#[test]
+fn coalesce_motion_frames() {
+    let received = coalesced(5, |coalescer| {
+        coalescer.send(motion(1, 1.0));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.send(motion(2, 2.0));
+        coalescer.send(axis(2, 1.5));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.send(motion(3, 3.0));
+        coalescer.send(axis(3, 2.5));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.flush();
+    });
+    assert_eq!(received, vec!["motion 3 3", "axis 3 4", "frame"]);
+}
+
+#[test]
+fn coalesce_button_breaks_merge() {
+    let received = coalesced(5, |coalescer| {
+        coalescer.send(motion(1, 1.0));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.send(button(2));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.send(motion(3, 3.0));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.send(motion(4, 4.0));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.flush();
+    });
+    assert_eq!(
+        received,
+        vec!["motion 1 1", "frame", "button 2", "frame", "motion 4 4", "frame"]
+    );
+}
+
+#[test]
+fn coalesce_unframed_pointer() {
+    let received = coalesced(4, |coalescer| {
+        coalescer.send(motion(1, 1.0));
+        coalescer.send(motion(2, 2.0));
+        coalescer.send(axis(2, 1.0));
+        coalescer.send(axis(3, 1.0));
+        coalescer.send(button(4));
+        coalescer.send(motion(5, 5.0));
+        coalescer.flush();
+    });
+    assert_eq!(received, vec!["motion 2 2", "axis 3 2", "button 4", "motion 5 5"]);
+}
+
+#[test]
+fn coalesce_keeps_open_frame() {
+    let received = coalesced(5, |coalescer| {
+        coalescer.send(motion(1, 1.0));
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.send(motion(2, 2.0));
+        // the second frame is not terminated yet, the first one cannot be merged with it
+        coalescer.flush();
+        coalescer.send(server_pointer::Event::Frame);
+        coalescer.flush();
+    });
+    assert_eq!(received, vec!["motion 1 1", "frame", "motion 2 2", "frame"]);
+}
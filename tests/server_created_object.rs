@@ -11,6 +11,7 @@ use ways::protocol::wl_seat::WlSeat as ServerSeat;
 
 use wayc::protocol::wl_data_device::Event as CDDEvt;
 use wayc::protocol::wl_data_device_manager::{RequestsTrait, WlDataDeviceManager as ClientDDMgr};
+use wayc::protocol::wl_data_offer::RequestsTrait as OfferRequests;
 use wayc::protocol::wl_seat::WlSeat as ClientSeat;
 
 #[test]
@@ -75,3 +76,70 @@ fn data_offer() {
 
     assert!(*received.lock().unwrap());
 }
+
+#[test]
+fn data_offer_version() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerSeat, _>(1, |_, _| {});
+    server
+        .display
+        .create_global::<ServerDDMgr, _>(3, |new_resource, version| {
+            assert!(version == 2);
+            new_resource.implement(
+                |request, _| match request {
+                    SDDMReq::GetDataDevice { id, .. } => {
+                        let ddevice = id.implement(|_, _| {}, None::<fn(_)>, ());
+                        let offer = ddevice
+                            .client()
+                            .unwrap()
+                            .create_resource::<ServerDO>(ddevice.version())
+                            .unwrap()
+                            .implement(|_, _| {}, None::<fn(_)>, ());
+                        ddevice.send(SDDEvt::DataOffer { id: offer })
+                    }
+                    _ => unimplemented!(),
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let seat = manager
+        .instantiate_auto::<ClientSeat, _>(|newseat| newseat.implement(|_, _| {}, ()))
+        .unwrap();
+    // the manager is bound at a lower version than the one of the global
+    let ddmgr = manager
+        .instantiate_exact::<ClientDDMgr, _>(2, |newddmgr| newddmgr.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let offers = Arc::new(Mutex::new(Vec::new()));
+    let offers2 = offers.clone();
+
+    let ddevice = ddmgr
+        .get_data_device(&seat, move |newdd| {
+            newdd.implement(
+                move |evt, _| match evt {
+                    CDDEvt::DataOffer { id } => {
+                        offers2.lock().unwrap().push(id.implement(|_, _| {}, ()));
+                    }
+                    _ => unimplemented!(),
+                },
+                (),
+            )
+        }).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let offer = offers.lock().unwrap().pop().unwrap();
+    // the offer has the version of the data device which created it
+    assert_eq!(ddevice.version(), 2);
+    assert_eq!(offer.version(), 2);
+
+    offer.destroy();
+    assert_eq!(offer.version(), 0);
+}
@@ -0,0 +1,184 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::sync::{Arc, Mutex};
+#[cfg(not(feature = "native_lib"))]
+use std::time::{Duration, Instant};
+
+use ways::protocol::{wl_compositor, wl_region};
+use ways::ratelimit::{RateAction, RateLimit, RateLimits};
+#[cfg(feature = "native_lib")]
+use ways::ratelimit::RateLimitsError;
+
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+use wayc::protocol::wl_region::{RequestsTrait as RegionRequests, WlRegion};
+use wayc::Proxy;
+
+// a server recording the `x` of the `wl_region.add` requests it receives, and a client
+// with a region
+fn setup() -> (TestServer, TestClient, Proxy<WlRegion>, Arc<Mutex<Vec<i32>>>) {
+    let mut server = TestServer::new();
+    let added = Arc::new(Mutex::new(Vec::new()));
+    let added2 = added.clone();
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(1, move |compositor, _| {
+            let added = added2.clone();
+            compositor.implement(
+                move |request, _| {
+                    if let wl_compositor::Request::CreateRegion { id } = request {
+                        let added = added.clone();
+                        id.implement(
+                            move |request, _| {
+                                if let wl_region::Request::Add { x, .. } = request {
+                                    added.lock().unwrap().push(x);
+                                }
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let region = compositor
+        .create_region(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    (server, client, region, added)
+}
+
+fn limits(action: RateAction, invoked: &Arc<Mutex<u32>>) -> RateLimits {
+    let invoked = invoked.clone();
+    RateLimits::new(move |client, limited| {
+        assert!(client.alive());
+        assert_eq!(limited.interface, "wl_region");
+        assert_eq!(limited.request, "add");
+        *invoked.lock().unwrap() += 1;
+        action
+    }).limit(&["wl_region"], RateLimit { rate: 20, burst: 5 })
+}
+
+// the rate limits are only supported by the rust implementation
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn ratelimit_throttle() {
+    let (mut server, client, region, added) = setup();
+    let invoked = Arc::new(Mutex::new(0));
+    server
+        .display
+        .set_rate_limits(Some(limits(RateAction::Throttle, &invoked)))
+        .unwrap();
+
+    let start = Instant::now();
+    for x in 0..10 {
+        region.add(x, 0, 1, 1);
+    }
+    client.display.flush().unwrap();
+
+    // the burst is dispatched right away
+    server.answer();
+    assert_eq!(added.lock().unwrap().len(), 5);
+
+    // the others at the rate of the limit
+    while added.lock().unwrap().len() < 10 {
+        assert!(start.elapsed() < Duration::from_secs(5), "the client was not resumed");
+        server.answer();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(*added.lock().unwrap(), (0..10).collect::<Vec<_>>());
+    assert!(*invoked.lock().unwrap() >= 5);
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn ratelimit_allow() {
+    let (mut server, mut client, region, added) = setup();
+    let invoked = Arc::new(Mutex::new(0));
+    server
+        .display
+        .set_rate_limits(Some(limits(RateAction::Allow, &invoked)))
+        .unwrap();
+
+    for x in 0..10 {
+        region.add(x, 0, 1, 1);
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(added.lock().unwrap().len(), 10);
+    assert_eq!(*invoked.lock().unwrap(), 5);
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn ratelimit_kill() {
+    let (mut server, mut client, region, added) = setup();
+    let invoked = Arc::new(Mutex::new(0));
+    server
+        .display
+        .set_rate_limits(Some(limits(RateAction::Kill, &invoked)))
+        .unwrap();
+
+    for x in 0..10 {
+        region.add(x, 0, 1, 1);
+    }
+    assert!(roundtrip(&mut client, &mut server).is_err());
+
+    assert_eq!(added.lock().unwrap().len(), 5);
+    assert_eq!(*invoked.lock().unwrap(), 1);
+}
+
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn ratelimit_other_interfaces() {
+    let (mut server, mut client, region, added) = setup();
+    let invoked = Arc::new(Mutex::new(0));
+    let invoked2 = invoked.clone();
+    let limits = RateLimits::new(move |_, _| {
+        *invoked2.lock().unwrap() += 1;
+        RateAction::Kill
+    }).limit(&["wl_pointer"], RateLimit { rate: 1, burst: 1 });
+    server.display.set_rate_limits(Some(limits)).unwrap();
+
+    // the requests of the interfaces of no class are not limited
+    for x in 0..10 {
+        region.add(x, 0, 1, 1);
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(added.lock().unwrap().len(), 10);
+    assert_eq!(*invoked.lock().unwrap(), 0);
+}
+
+#[cfg(feature = "native_lib")]
+#[test]
+fn ratelimit_unsupported() {
+    let (mut server, mut client, region, added) = setup();
+    let invoked = Arc::new(Mutex::new(0));
+    match server
+        .display
+        .set_rate_limits(Some(limits(RateAction::Kill, &invoked)))
+    {
+        Err(RateLimitsError::Unsupported) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    for x in 0..10 {
+        region.add(x, 0, 1, 1);
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(added.lock().unwrap().len(), 10);
+    assert_eq!(*invoked.lock().unwrap(), 0);
+}
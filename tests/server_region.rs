@@ -0,0 +1,123 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::sync::{Arc, Mutex};
+
+use ways::protocol::wl_compositor;
+use ways::protocol::wl_region::WlRegion as ServerRegion;
+use ways::region::{implement_region, region_of, Rectangle, Region};
+use ways::Resource;
+
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+use wayc::protocol::wl_region::RequestsTrait as RegionRequests;
+
+fn region(rects: &[(i32, i32, i32, i32)]) -> Region {
+    let mut region = Region::new();
+    for &(x, y, width, height) in rects {
+        region.add_rect(Rectangle::new(x, y, width, height));
+    }
+    region
+}
+
+fn rects(region: &Region) -> Vec<(i32, i32, i32, i32)> {
+    region.rects().map(|r| (r.x, r.y, r.width, r.height)).collect()
+}
+
+#[test]
+fn region_union() {
+    // two overlapping squares are split in three bands
+    let union = region(&[(0, 0, 10, 10), (5, 5, 10, 10)]);
+    assert_eq!(rects(&union), [(0, 0, 10, 5), (0, 5, 15, 5), (5, 10, 10, 5)]);
+    assert_eq!(union.extents(), Some(Rectangle::new(0, 0, 15, 15)));
+    assert!(union.contains(12, 7));
+    assert!(!union.contains(12, 2));
+    assert!(!union.contains(15, 10));
+
+    // adjacent rectangles are merged
+    assert_eq!(rects(&region(&[(0, 0, 5, 5), (5, 0, 5, 5), (0, 5, 10, 5)])), [(0, 0, 10, 10)]);
+    // the representation does not depend on the order of the operations
+    assert_eq!(
+        region(&[(0, 0, 10, 10), (5, 5, 10, 10)]),
+        region(&[(5, 5, 10, 10), (0, 0, 10, 10)])
+    );
+    // empty rectangles are ignored
+    assert!(region(&[(0, 0, 0, 10), (0, 0, 10, -1)]).is_empty());
+    assert_eq!(Region::new().extents(), None);
+}
+
+#[test]
+fn region_intersect_subtract() {
+    let a = region(&[(0, 0, 10, 10)]);
+    let b = region(&[(5, 5, 10, 10)]);
+    assert_eq!(rects(&a.intersect(&b)), [(5, 5, 5, 5)]);
+    assert_eq!(rects(&a.subtract(&b)), [(0, 0, 10, 5), (0, 5, 5, 5)]);
+    assert!(a.intersect(&region(&[(20, 20, 5, 5)])).is_empty());
+    assert!(a.subtract(&a).is_empty());
+
+    // a hole in the middle
+    let mut frame = region(&[(0, 0, 30, 30)]);
+    frame.subtract_rect(Rectangle::new(10, 10, 10, 10));
+    assert_eq!(
+        rects(&frame),
+        [(0, 0, 30, 10), (0, 10, 10, 10), (20, 10, 10, 10), (0, 20, 30, 10)]
+    );
+    assert!(!frame.contains(15, 15));
+    // filling it gives back the square
+    frame.add_rect(Rectangle::new(10, 10, 10, 10));
+    assert_eq!(frame, region(&[(0, 0, 30, 30)]));
+
+    let mut damage = region(&[(-5, -5, 10, 10), (95, 20, 10, 10)]);
+    damage.clip(Rectangle::new(0, 0, 100, 100));
+    assert_eq!(rects(&damage), [(0, 0, 5, 5), (95, 20, 5, 10)]);
+    damage.translate(10, 20);
+    assert_eq!(rects(&damage), [(10, 20, 5, 5), (105, 40, 5, 10)]);
+}
+
+#[test]
+fn wl_region_contents() {
+    let mut server = TestServer::new();
+    let regions = Arc::new(Mutex::new(Vec::<Resource<ServerRegion>>::new()));
+    let regions2 = regions.clone();
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(1, move |compositor, _| {
+            let regions = regions2.clone();
+            compositor.implement(
+                move |request, _| {
+                    if let wl_compositor::Request::CreateRegion { id } = request {
+                        regions.lock().unwrap().push(implement_region(id));
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let client_region = compositor
+        .create_region(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    client_region.add(0, 0, 20, 20);
+    client_region.subtract(10, 0, 10, 10);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let contents = region_of(&regions.lock().unwrap()[0]).unwrap();
+    assert_eq!(rects(&contents), [(0, 0, 10, 10), (0, 10, 20, 10)]);
+
+    // the contents were copied
+    client_region.add(10, 0, 10, 10);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(rects(&contents), [(0, 0, 10, 10), (0, 10, 20, 10)]);
+    assert_eq!(
+        region_of(&regions.lock().unwrap()[0]),
+        Some(Region::from_rect(Rectangle::new(0, 0, 20, 20)))
+    );
+}
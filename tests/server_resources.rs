@@ -175,3 +175,210 @@ fn dead_resources() {
         assert!(!cloned.is_alive());
     }
 }
+
+// libwayland-server buffers the events of full sockets before giving up on the client
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn full_socket_kills_client() {
+    let mut server = TestServer::new();
+
+    let outputs = Arc::new(Mutex::new(Vec::new()));
+    let outputs2 = outputs.clone();
+
+    server
+        .display
+        .create_global::<wl_output::WlOutput, _>(2, move |newo, _| {
+            outputs2
+                .lock()
+                .unwrap()
+                .push(newo.implement(|_, _| {}, None::<fn(_)>, ()));
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    manager
+        .instantiate_auto::<ClientOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the client does not read its events, until its socket is full
+    let output = outputs.lock().unwrap()[0].clone();
+    let client_handle = output.client().unwrap();
+    for _ in 0..1_000_000 {
+        output.send(wl_output::Event::Done);
+        if !client_handle.alive() {
+            break;
+        }
+    }
+    assert!(!client_handle.alive());
+}
+
+#[test]
+fn invalidated_global_resources() {
+    use self::wayc::protocol::wl_output::RequestsTrait;
+    let mut server = TestServer::new();
+
+    let outputs = Arc::new(Mutex::new(Vec::new()));
+    let outputs2 = outputs.clone();
+    let requests = Arc::new(Mutex::new(0));
+    let destructed = Arc::new(Mutex::new(false));
+    let requests2 = requests.clone();
+    let destructed2 = destructed.clone();
+
+    let global = server
+        .display
+        .create_global::<wl_output::WlOutput, _>(3, move |newo, _| {
+            let requests3 = requests2.clone();
+            let destructed3 = destructed2.clone();
+            outputs2.lock().unwrap().push(newo.implement(
+                move |_, _| *requests3.lock().unwrap() += 1,
+                Some(move |_| *destructed3.lock().unwrap() = true),
+                (),
+            ));
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let client_output = manager
+        .instantiate_auto::<ClientOutput, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let mut invalidated = Vec::new();
+    global.destroy_and_invalidate(|output| invalidated.push(output));
+
+    assert!(invalidated.len() == 1);
+    assert!(invalidated[0] == outputs.lock().unwrap()[0]);
+    assert!(invalidated[0].is_defunct());
+
+    client_output.release();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the request was not dispatched, but the object was still destroyed
+    assert_eq!(*requests.lock().unwrap(), 0);
+    assert!(*destructed.lock().unwrap());
+    assert!(!invalidated[0].is_alive());
+}
+
+#[test]
+fn resource_dispatch_state() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use ways::protocol::wl_compositor;
+    use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+    use wayc::protocol::wl_display::RequestsTrait as DisplayRequests;
+
+    // the state of the server, shared by all its implementations
+    struct State {
+        surfaces: u32,
+    }
+
+    let mut event_loop = ways::calloop::EventLoop::<State>::new().unwrap();
+    let mut display = ways::Display::new(event_loop.handle());
+    let socket_name = display.add_socket_auto().unwrap();
+    display.create_global::<wl_compositor::WlCompositor, _>(1, |newcompositor, _| {
+        newcompositor.implement_with_state(
+            |request, _, state: &mut State| {
+                if let wl_compositor::Request::CreateSurface { id } = request {
+                    id.implement(|_, _| {}, None::<fn(_)>, ());
+                    state.surfaces += 1;
+                }
+            },
+            None::<fn(_)>,
+            (),
+        );
+    });
+    let mut state = State { surfaces: 0 };
+
+    let mut client = TestClient::new(&socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+    let mut roundtrip = |client: &mut TestClient, state: &mut State| {
+        let done = Rc::new(Cell::new(false));
+        let done2 = done.clone();
+        let token = client.event_queue.get_token();
+        client
+            .display
+            .sync(move |newcb| unsafe { newcb.implement_nonsend(move |_, _| done2.set(true), (), &token) })
+            .unwrap();
+        while !done.get() {
+            client.display.flush().unwrap();
+            for _ in 0..2 {
+                event_loop.dispatch(Some(Duration::from_millis(10)), state).unwrap();
+                display.flush_clients();
+            }
+            client.event_queue.dispatch_pending().unwrap();
+            client.event_queue.prepare_read().unwrap().read_events().unwrap();
+            client.event_queue.dispatch_pending().unwrap();
+        }
+    };
+    roundtrip(&mut client, &mut state);
+
+    let compositor = manager
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    compositor.create_surface(|newp| newp.implement(|_, _| {}, ())).unwrap();
+    compositor.create_surface(|newp| newp.implement(|_, _| {}, ())).unwrap();
+    roundtrip(&mut client, &mut state);
+
+    assert_eq!(state.surfaces, 2);
+}
+
+// the requests received before a client is inserted in the event loop are only processed
+// at its creation by the rust implementation
+#[cfg(not(feature = "native_lib"))]
+#[test]
+fn resource_dispatch_without_state() {
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+
+    use ways::protocol::wl_compositor;
+    use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+    use wayc::protocol::wl_display::RequestsTrait as DisplayRequests;
+    use wayc::protocol::wl_registry::RequestsTrait as RegistryRequests;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<wl_compositor::WlCompositor, _>(1, |newcompositor, _| {
+            newcompositor.implement_with_state(
+                |request, _, _: &mut ()| {
+                    if let wl_compositor::Request::CreateSurface { id } = request {
+                        id.implement(|_, _| {}, None::<fn(_)>, ());
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+    let mut client = unsafe { TestClient::from_fd(client_socket.into_raw_fd()) };
+    let registry = client
+        .display
+        .get_registry(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let compositor = registry
+        .bind::<WlCompositor, _>(1, 1, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    compositor.create_surface(|newp| newp.implement(|_, _| {}, ())).unwrap();
+    client.display.flush().unwrap();
+
+    // the requests are processed outside of the event loop, the request can not be processed
+    unsafe { server.display.create_client(server_socket.into_raw_fd()) };
+    assert!(roundtrip(&mut client, &mut server).is_err());
+
+    let error = client.display.protocol_error().unwrap();
+    assert_eq!(error.code, 1);
+    assert_eq!(error.object_id, compositor.id());
+}
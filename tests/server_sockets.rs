@@ -0,0 +1,165 @@
+mod helpers;
+
+extern crate nix;
+
+use helpers::{roundtrip, wayc, TestClient, TestServer};
+
+use nix::sys::socket;
+
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::process;
+
+#[test]
+fn abstract_socket() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .add_socket_abstract("wayland-rs-test-abstract")
+        .unwrap();
+
+    // the name cannot be used twice
+    assert!(
+        server
+            .display
+            .add_socket_abstract("wayland-rs-test-abstract")
+            .is_err()
+    );
+
+    let fd = socket::socket(
+        socket::AddressFamily::Unix,
+        socket::SockType::Stream,
+        socket::SockFlag::SOCK_CLOEXEC,
+        None,
+    ).unwrap();
+    let addr = socket::UnixAddr::new_abstract(b"wayland-rs-test-abstract").unwrap();
+    socket::connect(fd, &socket::SockAddr::Unix(addr)).unwrap();
+
+    let mut client = unsafe { TestClient::from_fd(fd) };
+
+    roundtrip(&mut client, &mut server).unwrap();
+}
+
+#[test]
+fn refuse_tcp_socket() {
+    let server = TestServer::new();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let ret = unsafe { server.display.add_socket_fd(listener.as_raw_fd()) };
+    assert_eq!(ret.unwrap_err().kind(), ::std::io::ErrorKind::InvalidInput);
+}
+
+// the environment is shared by the tests, so all cases are checked in a single one
+#[test]
+fn socket_activation_env() {
+    use std::io::ErrorKind;
+
+    let mut server = TestServer::new();
+    // the socket activation variables are not set for the test process
+    assert_eq!(server.display.add_sockets_from_env().unwrap(), 0);
+
+    // the sockets passed to an other process are ignored
+    env::set_var("LISTEN_PID", format!("{}", nix::unistd::getppid()));
+    env::set_var("LISTEN_FDS", "1");
+    assert_eq!(server.display.add_sockets_from_env().unwrap(), 0);
+    assert!(env::var_os("LISTEN_FDS").is_some());
+
+    // malformed variables are rejected, and left in the environment
+    let pid = format!("{}", nix::unistd::getpid());
+    for &(listen_pid, listen_fds) in &[
+        ("-1", "1"),
+        ("not a pid", "1"),
+        (&pid[..], "-1"),
+        (&pid[..], "4294967295"),
+        (&pid[..], "one"),
+    ] {
+        env::set_var("LISTEN_PID", listen_pid);
+        env::set_var("LISTEN_FDS", listen_fds);
+        let ret = server.display.add_sockets_from_env();
+        assert_eq!(ret.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(env::var("LISTEN_PID").unwrap(), listen_pid);
+        assert_eq!(env::var("LISTEN_FDS").unwrap(), listen_fds);
+    }
+
+    // the fds are all checked before anything is done, there are not that many open fds
+    env::set_var("LISTEN_PID", &pid);
+    env::set_var("LISTEN_FDS", "100000");
+    assert!(server.display.add_sockets_from_env().is_err());
+    assert_eq!(env::var("LISTEN_FDS").unwrap(), "100000");
+
+    // no sockets passed to us, the variables are consumed
+    env::set_var("LISTEN_PID", &pid);
+    env::set_var("LISTEN_FDS", "0");
+    assert_eq!(server.display.add_sockets_from_env().unwrap(), 0);
+    assert!(env::var_os("LISTEN_PID").is_none());
+    assert!(env::var_os("LISTEN_FDS").is_none());
+}
+
+fn runtime_path(name: &str) -> PathBuf {
+    PathBuf::from(env::var_os("XDG_RUNTIME_DIR").unwrap()).join(name)
+}
+
+#[test]
+fn socket_lock() {
+    let first = format!("wayland-rs-test-lock-{}", process::id());
+    let second = format!("wayland-rs-test-lock-second-{}", process::id());
+
+    let mut server = TestServer::new();
+    server.display.add_socket(Some(&first)).unwrap();
+    server.display.add_socket(Some(&second)).unwrap();
+    assert!(runtime_path(&format!("{}.lock", first)).exists());
+
+    // the names are locked
+    let mut other = TestServer::new();
+    assert!(other.display.add_socket(Some(&first)).is_err());
+
+    // clients can connect to both sockets
+    for name in &[&first, &second] {
+        let mut client = TestClient::new(name.as_ref());
+        roundtrip(&mut client, &mut server).unwrap();
+    }
+
+    drop(server);
+
+    for name in &[&first, &second] {
+        assert!(!runtime_path(name).exists());
+        assert!(!runtime_path(&format!("{}.lock", name)).exists());
+    }
+}
+
+#[test]
+fn stale_socket() {
+    let name = format!("wayland-rs-test-stale-{}", process::id());
+
+    // a compositor crashed, leaving its socket behind
+    drop(UnixListener::bind(runtime_path(&name)).unwrap());
+    assert!(runtime_path(&name).exists());
+
+    let mut server = TestServer::new();
+    server.display.add_socket(Some(&name)).unwrap();
+
+    let mut client = TestClient::new(name.as_ref());
+    roundtrip(&mut client, &mut server).unwrap();
+}
+
+#[test]
+fn socket_from_listener() {
+    let path = env::temp_dir().join(format!("wayland-rs-test-listener-{}", process::id()));
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let mut server = TestServer::new();
+    server.display.add_socket_from(listener).unwrap();
+
+    let (display, event_queue) = wayc::Display::connect_to_socket(&path).unwrap();
+    let mut client = TestClient {
+        display,
+        event_queue,
+    };
+    roundtrip(&mut client, &mut server).unwrap();
+
+    fs::remove_file(&path).unwrap();
+}
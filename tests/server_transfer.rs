@@ -0,0 +1,131 @@
+extern crate nix;
+
+mod helpers;
+
+use helpers::ways;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::unistd::pipe;
+
+use ways::calloop::EventLoop;
+use ways::transfer::{start_transfer, TransferLimits, TransferResult};
+
+// start a transfer between two pipes, returns the write end of the first one, the read end of
+// the second one and the outcome of the transfer once it is over
+fn transfer(
+    event_loop: &EventLoop<()>,
+    limits: TransferLimits,
+) -> (RawFd, RawFd, Rc<RefCell<Option<TransferResult>>>) {
+    let (from, source) = pipe().unwrap();
+    let (destination, to) = pipe().unwrap();
+    let result = Rc::new(RefCell::new(None));
+    let result2 = result.clone();
+    start_transfer(&event_loop.handle(), from, to, limits, move |res, &mut ()| {
+        *result2.borrow_mut() = Some(res);
+    }).unwrap();
+    (source, destination, result)
+}
+
+fn run(event_loop: &mut EventLoop<()>, result: &Rc<RefCell<Option<TransferResult>>>) -> TransferResult {
+    let start = Instant::now();
+    while result.borrow().is_none() {
+        assert!(start.elapsed() < Duration::from_secs(5), "the transfer did not end");
+        event_loop.dispatch(Some(Duration::from_millis(10)), &mut ()).unwrap();
+    }
+    let result = result.borrow_mut().take().unwrap();
+    result
+}
+
+#[test]
+fn transfer_done() {
+    let mut event_loop = EventLoop::<()>::new().unwrap();
+    let (source, destination, result) = transfer(&event_loop, TransferLimits::default());
+
+    // more than the capacity of both pipes
+    let data = (0..300_000).map(|i| i as u8).collect::<Vec<u8>>();
+    let data2 = data.clone();
+    let writer = thread::spawn(move || {
+        let mut source = unsafe { File::from_raw_fd(source) };
+        source.write_all(&data2).unwrap();
+    });
+    let reader = thread::spawn(move || {
+        let mut destination = unsafe { File::from_raw_fd(destination) };
+        let mut received = Vec::new();
+        destination.read_to_end(&mut received).unwrap();
+        received
+    });
+
+    match run(&mut event_loop, &result) {
+        TransferResult::Done(n) => assert_eq!(n, data.len()),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    writer.join().unwrap();
+    assert!(reader.join().unwrap() == data);
+}
+
+#[test]
+fn transfer_too_large() {
+    let mut event_loop = EventLoop::<()>::new().unwrap();
+    let limits = TransferLimits {
+        max_size: Some(10),
+        ..Default::default()
+    };
+    let (source, destination, result) = transfer(&event_loop, limits);
+
+    let mut source = unsafe { File::from_raw_fd(source) };
+    source.write_all(&[0; 100]).unwrap();
+    match run(&mut event_loop, &result) {
+        TransferResult::TooLarge => {}
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    // the destination pipe was closed
+    let mut destination = unsafe { File::from_raw_fd(destination) };
+    let mut received = Vec::new();
+    destination.read_to_end(&mut received).unwrap();
+    assert!(received.len() <= 100);
+}
+
+#[test]
+fn transfer_stalled() {
+    let mut event_loop = EventLoop::<()>::new().unwrap();
+    let limits = TransferLimits {
+        timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let (source, destination, result) = transfer(&event_loop, limits);
+
+    // the source writes some data, but never finishes
+    let mut source = unsafe { File::from_raw_fd(source) };
+    source.write_all(b"partial").unwrap();
+    let start = Instant::now();
+    match run(&mut event_loop, &result) {
+        TransferResult::Stalled(n) => assert_eq!(n, 7),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    let mut destination = unsafe { File::from_raw_fd(destination) };
+    let mut received = Vec::new();
+    destination.read_to_end(&mut received).unwrap();
+    assert_eq!(received, b"partial");
+}
+
+#[test]
+fn transfer_destination_closed() {
+    let mut event_loop = EventLoop::<()>::new().unwrap();
+    let (source, destination, result) = transfer(&event_loop, TransferLimits::default());
+
+    nix::unistd::close(destination).unwrap();
+    let mut source = unsafe { File::from_raw_fd(source) };
+    source.write_all(b"data").unwrap();
+    match run(&mut event_loop, &result) {
+        TransferResult::Failed(_) => {}
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
@@ -0,0 +1,118 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::wl_compositor::{Request as CompReq, WlCompositor as ServerCompositor};
+use ways::protocol::wl_output::WlOutput as ServerOutput;
+use ways::Versioned;
+
+use wayc::protocol::wl_compositor::{RequestsTrait, WlCompositor};
+
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn versioned_dispatch() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let (old_calls, new_calls) = (calls.clone(), calls.clone());
+
+    // the new surface is implemented by the current handler, the older one forwards to it
+    let current = Arc::new(move |request: CompReq, compositor: ways::Resource<ServerCompositor>| {
+        if let CompReq::CreateSurface { id } = request {
+            id.implement(|_, _| {}, None::<fn(_)>, ());
+            new_calls.lock().unwrap().push(("current", compositor.version()));
+        }
+    });
+    let forwarded = current.clone();
+    let handlers = Versioned::new()
+        .handle(1, 2, move |request, compositor| {
+            old_calls.lock().unwrap().push(("old", compositor.version()));
+            forwarded(request, compositor);
+        }).handle(3, 4, move |request, compositor| current(request, compositor));
+    assert!(handlers.handles(2));
+    assert!(!handlers.handles(5));
+    assert_eq!(handlers.max_version(), 4);
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<ServerCompositor, _>(handlers.max_version(), move |new, _| {
+            handlers.implement(new, None::<fn(_)>, ());
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    for &version in &[2, 4] {
+        let compositor = manager
+            .instantiate_exact::<WlCompositor, _>(version, |newp| newp.implement(|_, _| {}, ()))
+            .unwrap();
+        compositor.create_surface(|newp| newp.implement(|_, _| {}, ())).unwrap();
+    }
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [("old", 2), ("current", 2), ("current", 4)]
+    );
+}
+
+#[test]
+fn versioned_unhandled_version() {
+    // the version 2 is advertised, but only the first one is handled
+    let handlers = Versioned::new().handle(1, 1, |request, _| {
+        if let CompReq::CreateSurface { id } = request {
+            id.implement(|_, _| {}, None::<fn(_)>, ());
+        }
+    });
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .create_global::<ServerCompositor, _>(2, move |new, _| {
+            handlers.implement(new, None::<fn(_)>, ());
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager
+        .instantiate_exact::<WlCompositor, _>(2, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    compositor.create_surface(|newp| newp.implement(|_, _| {}, ())).unwrap();
+
+    assert!(roundtrip(&mut client, &mut server).is_err());
+
+    let error = client.display.protocol_error().unwrap();
+    assert_eq!(error.code, 1);
+    assert_eq!(error.object_id, compositor.id());
+}
+
+#[test]
+fn versioned_gaps() {
+    let handlers = Versioned::<ServerOutput>::new().handle(2, 3, |_, _| {});
+    assert_eq!(handlers.max_version(), 0);
+    let handlers = Versioned::<ServerOutput>::new()
+        .handle(1, 1, |_, _| {})
+        .handle(3, 3, |_, _| {});
+    assert_eq!(handlers.max_version(), 1);
+    assert!(!handlers.handles(2));
+}
+
+#[test]
+#[should_panic]
+fn versioned_overlapping_ranges() {
+    Versioned::<ServerOutput>::new()
+        .handle(1, 2, |_, _| {})
+        .handle(2, 3, |_, _| {});
+}
+
+#[test]
+#[should_panic]
+fn versioned_unknown_version() {
+    Versioned::<ServerOutput>::new().handle(1, 10, |_, _| {});
+}
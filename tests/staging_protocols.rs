@@ -0,0 +1,1109 @@
+// The helpers of the staging protocols are only built with their cargo feature
+#![cfg(feature = "staging_protocols")]
+
+extern crate tempfile;
+extern crate wayland_protocols;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols::staging::ext_data_control::{filter_mime_types, is_sensitive, PASSWORD_MANAGER_HINT};
+use wayland_protocols::staging::ext_workspace::v1::client::ext_workspace_group_handle_v1::{
+    self as group_handle, RequestsTrait as GroupRequests,
+};
+use wayland_protocols::staging::ext_workspace::v1::client::ext_workspace_handle_v1::{
+    self as workspace_handle, RequestsTrait as WorkspaceRequests,
+};
+use wayland_protocols::staging::ext_workspace::v1::client::ext_workspace_manager_v1::{
+    self as workspace_manager, ExtWorkspaceManagerV1, RequestsTrait as WorkspaceManagerRequests,
+};
+use wayland_protocols::staging::ext_workspace::v1::server::ext_workspace_group_handle_v1::GroupCapabilities;
+use wayland_protocols::staging::ext_workspace::v1::server::ext_workspace_handle_v1::{
+    State as WorkspaceState, WorkspaceCapabilities,
+};
+use wayland_protocols::staging::ext_workspace::v1::server::ext_workspace_manager_v1::ExtWorkspaceManagerV1 as ServerWorkspaceManager;
+use wayland_protocols::staging::ext_workspace::{WorkspaceManager, WorkspaceRequest};
+use wayland_protocols::staging::fifo::v1::client::wp_fifo_manager_v1::WpFifoManagerV1;
+use wayland_protocols::staging::fifo::v1::server::wp_fifo_manager_v1 as server_fifo_manager;
+use wayland_protocols::staging::fifo::v1::server::wp_fifo_v1 as server_fifo;
+use wayland_protocols::staging::fractional_scale::v1::client::wp_fractional_scale_manager_v1::{
+    RequestsTrait as FractionalManagerRequests, WpFractionalScaleManagerV1,
+};
+use wayland_protocols::staging::fractional_scale::v1::client::wp_fractional_scale_v1;
+use wayland_protocols::staging::fractional_scale::v1::server::wp_fractional_scale_manager_v1 as server_manager;
+use wayland_protocols::staging::fractional_scale::v1::server::wp_fractional_scale_v1::WpFractionalScaleV1;
+use wayland_protocols::staging::fractional_scale::{ScaleNegotiator, ScalePolicy};
+use wayland_protocols::staging::tearing_control::v1::client::wp_tearing_control_manager_v1::WpTearingControlManagerV1;
+use wayland_protocols::staging::tearing_control::v1::server::wp_tearing_control_manager_v1 as server_tearing_manager;
+use wayland_protocols::staging::tearing_control::v1::server::wp_tearing_control_v1 as server_tearing;
+use wayland_protocols::staging::tearing_control::{PresentationMode, SurfacePresentation};
+use wayland_protocols::staging::xdg_dialog::v1::client::xdg_wm_dialog_v1::XdgWmDialogV1;
+use wayland_protocols::staging::xdg_dialog::v1::server::xdg_dialog_v1 as server_dialog;
+use wayland_protocols::staging::xdg_dialog::v1::server::xdg_wm_dialog_v1 as server_wm_dialog;
+use wayland_protocols::staging::xdg_dialog::Dialog;
+use wayland_protocols::staging::xdg_session_management::SessionStore;
+use wayland_protocols::staging::xdg_toplevel_icon::v1::client::xdg_toplevel_icon_manager_v1::XdgToplevelIconManagerV1;
+use wayland_protocols::staging::xdg_toplevel_icon::v1::server::xdg_toplevel_icon_manager_v1 as server_icon_manager;
+use wayland_protocols::staging::xdg_toplevel_icon::v1::server::xdg_toplevel_icon_v1 as server_icon;
+use wayland_protocols::staging::xdg_toplevel_icon::{apply_icon, IconImage, ToplevelIcon};
+use wayland_protocols::xdg_shell::client::xdg_surface::RequestsTrait as XdgSurfaceRequests;
+use wayland_protocols::xdg_shell::client::xdg_toplevel::{RequestsTrait as ToplevelRequests, XdgToplevel};
+use wayland_protocols::xdg_shell::client::xdg_wm_base::{RequestsTrait as WmBaseRequests, XdgWmBase};
+use wayland_protocols::xdg_shell::server::xdg_surface as server_xdg_surface;
+use wayland_protocols::xdg_shell::server::xdg_toplevel as server_toplevel;
+use wayland_protocols::xdg_shell::server::xdg_wm_base as server_wm_base;
+
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+use wayc::protocol::wl_shm::WlShm;
+use wayc::protocol::wl_surface;
+
+use ways::protocol::wl_compositor as server_compositor;
+use ways::protocol::wl_output::Transform;
+use ways::protocol::wl_shm as server_shm;
+use ways::protocol::wl_shm_pool as server_shm_pool;
+use ways::protocol::wl_surface::WlSurface as ServerSurface;
+use ways::Resource;
+
+#[test]
+fn session_store() {
+    let mut store = SessionStore::new();
+    let first = store.create_session();
+    let second = store.create_session();
+    assert!(first != second);
+    assert!(store.has_session(&first));
+
+    // known sessions are restored, others are created
+    assert_eq!(store.get_session(Some(&first)), (first.clone(), true));
+    let (third, restored) = store.get_session(Some("unknown"));
+    assert!(!restored);
+    assert!(third != "unknown" && store.has_session(&third));
+    assert!(!store.get_session(None).1);
+
+    store.set_toplevel(&first, "main", (1, 2));
+    store.set_toplevel(&first, "main", (3, 4));
+    store.set_toplevel("unknown", "main", (5, 6));
+    assert_eq!(store.toplevel(&first, "main"), Some(&(3, 4)));
+    assert_eq!(store.toplevel(&second, "main"), None);
+    assert!(!store.has_session("unknown"));
+
+    // inserting an existing session keeps its toplevels
+    store.insert_session(first.clone());
+    assert_eq!(store.toplevel(&first, "main"), Some(&(3, 4)));
+    store.insert_session("restored".into());
+    store.set_toplevel("restored", "main", (7, 8));
+
+    let saved = store
+        .sessions()
+        .map(|(id, toplevels)| (id.to_owned(), toplevels.clone()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(saved.len(), 5);
+    assert_eq!(saved[&first]["main"], (3, 4));
+    assert_eq!(saved["restored"]["main"], (7, 8));
+    assert!(saved[&second].is_empty());
+
+    assert_eq!(store.remove_toplevel(&first, "main"), Some((3, 4)));
+    assert_eq!(store.remove_toplevel(&first, "main"), None);
+    store.remove_session("restored");
+    assert!(!store.has_session("restored"));
+    assert_eq!(store.toplevel("restored", "main"), None);
+    assert_eq!(store.sessions().count(), 4);
+}
+
+// the scale events received by a client surface, with the scale of `preferred_scale` over 120
+#[derive(Debug, PartialEq)]
+enum ScaleEvent {
+    BufferScale(i32),
+    BufferTransform(u32),
+    Fractional(u32),
+}
+
+type ScaleEvents = Arc<Mutex<Vec<ScaleEvent>>>;
+
+#[test]
+fn scale_negotiator() {
+    let mut server = TestServer::new();
+    let surfaces = Arc::new(Mutex::new(Vec::<Resource<ServerSurface>>::new()));
+    let surfaces2 = surfaces.clone();
+    server
+        .display
+        .create_global::<server_compositor::WlCompositor, _>(6, move |compositor, _| {
+            let surfaces = surfaces2.clone();
+            compositor.implement(
+                move |request, _| {
+                    if let server_compositor::Request::CreateSurface { id } = request {
+                        surfaces
+                            .lock()
+                            .unwrap()
+                            .push(id.implement(|_, _| {}, None::<fn(_)>, ()));
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    let fractionals = Arc::new(Mutex::new(Vec::<Resource<WpFractionalScaleV1>>::new()));
+    let fractionals2 = fractionals.clone();
+    server
+        .display
+        .create_global::<server_manager::WpFractionalScaleManagerV1, _>(1, move |manager, _| {
+            let fractionals = fractionals2.clone();
+            manager.implement(
+                move |request, _| {
+                    if let server_manager::Request::GetFractionalScale { id, .. } = request {
+                        fractionals
+                            .lock()
+                            .unwrap()
+                            .push(id.implement(|_, _| {}, None::<fn(_)>, ()));
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // a surface of version 6 using fractional scales, and a surface of version 5
+    let new_surface = |version: u32, events: &ScaleEvents| {
+        let events = events.clone();
+        manager
+            .instantiate_exact::<WlCompositor, _>(version, |newp| newp.implement(|_, _| {}, ()))
+            .unwrap()
+            .create_surface(move |newp| {
+                newp.implement(
+                    move |event, _| match event {
+                        wl_surface::Event::PreferredBufferScale { factor } => {
+                            events.lock().unwrap().push(ScaleEvent::BufferScale(factor))
+                        }
+                        wl_surface::Event::PreferredBufferTransform { transform } => events
+                            .lock()
+                            .unwrap()
+                            .push(ScaleEvent::BufferTransform(transform.to_raw())),
+                        _ => {}
+                    },
+                    (),
+                )
+            }).unwrap()
+    };
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let surface = new_surface(6, &events);
+    let old_events = Arc::new(Mutex::new(Vec::new()));
+    new_surface(5, &old_events);
+    let fractional_events = events.clone();
+    manager
+        .instantiate_auto::<WpFractionalScaleManagerV1, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap()
+        .get_fractional_scale(&surface, move |newp| {
+            newp.implement(
+                move |event, _| match event {
+                    wp_fractional_scale_v1::Event::PreferredScale { scale } => {
+                        fractional_events.lock().unwrap().push(ScaleEvent::Fractional(scale))
+                    }
+                },
+                (),
+            )
+        }).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let (surface, old_surface) = {
+        let surfaces = surfaces.lock().unwrap();
+        (surfaces[0].clone(), surfaces[1].clone())
+    };
+    let fractional = fractionals.lock().unwrap()[0].clone();
+    let mut negotiator = ScaleNegotiator::new(ScalePolicy::Highest);
+    negotiator.set_output(1, 1.0, Transform::Normal);
+    negotiator.set_output(2, 1.5, Transform::Flipped90);
+    negotiator.add_surface(&surface);
+    negotiator.add_surface(&old_surface);
+
+    // nothing is sent before the surface enters an output
+    negotiator.set_fractional_scale(&surface, &fractional);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(events.lock().unwrap().is_empty());
+    assert_eq!(negotiator.preferred(&surface).unwrap().buffer_scale, 1);
+
+    // on an output of scale 1, only the fractional scale is sent
+    negotiator.surface_enter(&surface, 1);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(*events.lock().unwrap(), [ScaleEvent::Fractional(120)]);
+
+    // the highest scale wins, and only the changes are sent
+    negotiator.surface_enter(&surface, 2);
+    negotiator.surface_enter(&surface, 1);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        events.lock().unwrap().drain(..).skip(1).collect::<Vec<_>>(),
+        [
+            ScaleEvent::BufferScale(2),
+            ScaleEvent::BufferTransform(Transform::Flipped90.to_raw()),
+            ScaleEvent::Fractional(180),
+        ]
+    );
+    let preferred = negotiator.preferred(&surface).unwrap();
+    assert_eq!((preferred.scale, preferred.buffer_scale), (1.5, 2));
+
+    negotiator.surface_leave(&surface, &2);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        events.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            ScaleEvent::BufferScale(1),
+            ScaleEvent::BufferTransform(Transform::Normal.to_raw()),
+            ScaleEvent::Fractional(120),
+        ]
+    );
+
+    // the preference is kept when leaving all outputs, and follows the changes of the outputs
+    negotiator.surface_leave(&surface, &1);
+    negotiator.set_output(1, 2.0, Transform::Normal);
+    assert_eq!(negotiator.preferred(&surface).unwrap().scale, 1.0);
+    negotiator.surface_enter(&surface, 1);
+    negotiator.set_output(1, 1.25, Transform::Normal);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        events.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            ScaleEvent::BufferScale(2),
+            ScaleEvent::Fractional(240),
+            ScaleEvent::Fractional(150),
+        ]
+    );
+
+    // surfaces older than version 6 do not get the wl_surface events
+    negotiator.surface_enter(&old_surface, 2);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(negotiator.preferred(&old_surface).unwrap().buffer_scale, 2);
+    assert!(old_events.lock().unwrap().is_empty());
+
+    // removing an output updates the surfaces displayed on it
+    negotiator.surface_enter(&surface, 2);
+    negotiator.remove_output(&2);
+    assert_eq!(negotiator.preferred(&surface).unwrap().scale, 1.25);
+    negotiator.remove_surface(&surface);
+    assert_eq!(negotiator.preferred(&surface), None);
+}
+
+#[test]
+fn scale_negotiator_first_output() {
+    let mut server = TestServer::new();
+    let surfaces = Arc::new(Mutex::new(Vec::<Resource<ServerSurface>>::new()));
+    let surfaces2 = surfaces.clone();
+    server
+        .display
+        .create_global::<server_compositor::WlCompositor, _>(6, move |compositor, _| {
+            let surfaces = surfaces2.clone();
+            compositor.implement(
+                move |request, _| {
+                    if let server_compositor::Request::CreateSurface { id } = request {
+                        surfaces
+                            .lock()
+                            .unwrap()
+                            .push(id.implement(|_, _| {}, None::<fn(_)>, ()));
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    manager
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap()
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    let surface = surfaces.lock().unwrap()[0].clone();
+
+    let mut negotiator = ScaleNegotiator::new(ScalePolicy::First);
+    negotiator.set_output("low", 1.0, Transform::Normal);
+    negotiator.set_output("high", 2.0, Transform::Normal);
+    negotiator.surface_enter(&surface, "low");
+    negotiator.surface_enter(&surface, "high");
+    assert_eq!(negotiator.preferred(&surface).unwrap().scale, 1.0);
+    negotiator.surface_leave(&surface, &"low");
+    assert_eq!(negotiator.preferred(&surface).unwrap().scale, 2.0);
+    negotiator.surface_enter(&surface, "low");
+    assert_eq!(negotiator.preferred(&surface).unwrap().scale, 2.0);
+}
+
+// the events received by the handles of a workspace manager
+type WorkspaceLog = Arc<Mutex<Vec<String>>>;
+
+// the handles created by a workspace manager
+type WorkspaceHandles = Arc<
+    Mutex<(
+        Vec<wayc::Proxy<group_handle::ExtWorkspaceGroupHandleV1>>,
+        Vec<wayc::Proxy<workspace_handle::ExtWorkspaceHandleV1>>,
+    )>,
+>;
+
+fn bind_workspace_manager(
+    manager: &wayc::GlobalManager,
+    log: &WorkspaceLog,
+    handles: &WorkspaceHandles,
+) -> wayc::Proxy<ExtWorkspaceManagerV1> {
+    let log = log.clone();
+    let handles = handles.clone();
+    manager
+        .instantiate_auto::<ExtWorkspaceManagerV1, _>(move |newp| {
+            newp.implement(
+                move |event, _| match event {
+                    workspace_manager::Event::WorkspaceGroup { workspace_group } => {
+                        log.lock().unwrap().push("group".into());
+                        let log = log.clone();
+                        let group = workspace_group.implement(
+                            move |event, _| {
+                                log.lock().unwrap().push(match event {
+                                    group_handle::Event::Capabilities { capabilities } => {
+                                        format!("group.capabilities {}", capabilities.bits())
+                                    }
+                                    group_handle::Event::WorkspaceEnter { .. } => {
+                                        "group.workspace_enter".into()
+                                    }
+                                    group_handle::Event::WorkspaceLeave { .. } => {
+                                        "group.workspace_leave".into()
+                                    }
+                                    group_handle::Event::Removed => "group.removed".into(),
+                                    _ => "group.output".into(),
+                                })
+                            },
+                            (),
+                        );
+                        handles.lock().unwrap().0.push(group);
+                    }
+                    workspace_manager::Event::Workspace { workspace } => {
+                        log.lock().unwrap().push("workspace".into());
+                        let log = log.clone();
+                        let workspace = workspace.implement(
+                            move |event, _| {
+                                log.lock().unwrap().push(match event {
+                                    workspace_handle::Event::Id { id } => format!("workspace.id {}", id),
+                                    workspace_handle::Event::Name { name } => {
+                                        format!("workspace.name {}", name)
+                                    }
+                                    workspace_handle::Event::Coordinates { coordinates } => {
+                                        format!("workspace.coordinates {:?}", coordinates)
+                                    }
+                                    workspace_handle::Event::State { state } => {
+                                        format!("workspace.state {}", state.bits())
+                                    }
+                                    workspace_handle::Event::Capabilities { capabilities } => {
+                                        format!("workspace.capabilities {}", capabilities.bits())
+                                    }
+                                    workspace_handle::Event::Removed => "workspace.removed".into(),
+                                })
+                            },
+                            (),
+                        );
+                        handles.lock().unwrap().1.push(workspace);
+                    }
+                    workspace_manager::Event::Done => log.lock().unwrap().push("done".into()),
+                    workspace_manager::Event::Finished => log.lock().unwrap().push("finished".into()),
+                },
+                (),
+            )
+        }).unwrap()
+}
+
+#[test]
+fn workspace_manager() {
+    let mut server = TestServer::new();
+    let model = Rc::new(RefCell::new(WorkspaceManager::new()));
+    let (group, workspace) = {
+        let mut model = model.borrow_mut();
+        let group = model.create_group(GroupCapabilities::CreateWorkspace);
+        let workspace = model.create_workspace("one");
+        model.set_capabilities(workspace, WorkspaceCapabilities::Activate | WorkspaceCapabilities::Assign);
+        model.assign(workspace, Some(group));
+        (group, workspace)
+    };
+    let model2 = model.clone();
+    server
+        .display
+        .create_global::<ServerWorkspaceManager, _>(1, move |manager, _| model2.borrow_mut().bind(manager));
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let handles = Arc::new(Mutex::new((Vec::new(), Vec::new())));
+    let manager = bind_workspace_manager(&globals, &log, &handles);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the current model is sent on bind
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            "group",
+            "group.capabilities 1",
+            "workspace",
+            "workspace.name one",
+            "workspace.state 0",
+            "workspace.capabilities 9",
+            "group.workspace_enter",
+            "done",
+        ]
+    );
+    assert_eq!(model.borrow().workspaces(group), [workspace]);
+
+    // the mutations are sent as they happen, and the unchanged states are not
+    {
+        let mut model = model.borrow_mut();
+        model.set_state(workspace, WorkspaceState::Active);
+        model.set_state(workspace, WorkspaceState::Active);
+        model.set_coordinates(workspace, &[1, 2]);
+        model.done();
+        // nothing happened since the previous done
+        model.done();
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+    let coordinates = if cfg!(target_endian = "little") {
+        "[1, 0, 0, 0, 2, 0, 0, 0]"
+    } else {
+        "[0, 0, 0, 1, 0, 0, 0, 2]"
+    };
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            "workspace.state 1".to_owned(),
+            format!("workspace.coordinates {}", coordinates),
+            "done".to_owned(),
+        ]
+    );
+    assert_eq!(model.borrow().state(workspace), Some(WorkspaceState::Active));
+
+    // the requests are given on commit, without the ones lacking the capability
+    {
+        let handles = handles.lock().unwrap();
+        handles.1[0].activate();
+        handles.1[0].remove();
+        handles.0[0].create_workspace("two").unwrap();
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(model.borrow_mut().take_requests().is_empty());
+    manager.commit();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        model.borrow_mut().take_requests(),
+        [
+            WorkspaceRequest::Activate(workspace),
+            WorkspaceRequest::CreateWorkspace {
+                group,
+                name: "two".into(),
+            },
+        ]
+    );
+
+    {
+        let mut model = model.borrow_mut();
+        model.remove_workspace(workspace);
+        model.done();
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        ["group.workspace_leave", "workspace.removed", "done"]
+    );
+    assert_eq!(model.borrow().group(workspace), None);
+
+    // a stopped manager gets no more events
+    manager.stop();
+    roundtrip(&mut client, &mut server).unwrap();
+    {
+        let mut model = model.borrow_mut();
+        model.create_workspace("three");
+        model.done();
+    }
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(log.lock().unwrap().drain(..).collect::<Vec<_>>(), ["finished"]);
+}
+
+#[test]
+fn data_control_sensitive_selection() {
+    assert!(!is_sensitive::<&str>(&[]));
+    assert!(!is_sensitive(&["text/plain", "x-kde-passwordManagerHint-other"]));
+    assert!(is_sensitive(&["text/plain", PASSWORD_MANAGER_HINT]));
+    assert!(is_sensitive(&[String::from(PASSWORD_MANAGER_HINT)]));
+
+    // the filter is given the mime types in order, and the result keeps it
+    let mut seen = Vec::new();
+    let offered = filter_mime_types(&["text/plain", "image/png", "text/html"], |m| {
+        seen.push(m.to_owned());
+        m.starts_with("text/")
+    });
+    assert_eq!(seen, ["text/plain", "image/png", "text/html"]);
+    assert_eq!(offered, ["text/plain", "text/html"]);
+
+    // a sensitive selection is not offered at all, whatever the filter
+    let offered = filter_mime_types(&["text/plain", PASSWORD_MANAGER_HINT], |_| {
+        panic!("the filter should not be called on a sensitive selection")
+    });
+    assert!(offered.is_empty());
+}
+
+// the requests received by the toplevels of a server and their extensions, with the ids of their objects
+type ToplevelLog = Arc<Mutex<Vec<String>>>;
+
+fn toplevel_server(server: &mut TestServer, log: &ToplevelLog) {
+    server
+        .display
+        .create_global::<server_compositor::WlCompositor, _>(1, |compositor, _| {
+            compositor.implement(
+                |request, _| {
+                    if let server_compositor::Request::CreateSurface { id } = request {
+                        id.implement(|_, _| {}, None::<fn(_)>, ());
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    let toplevel_log = log.clone();
+    server
+        .display
+        .create_global::<server_wm_base::XdgWmBase, _>(1, move |wm_base, _| {
+            let log = toplevel_log.clone();
+            wm_base.implement(
+                move |request, _| {
+                    if let server_wm_base::Request::GetXdgSurface { id, .. } = request {
+                        let log = log.clone();
+                        id.implement(
+                            move |request, _| {
+                                if let server_xdg_surface::Request::GetToplevel { id } = request {
+                                    let log = log.clone();
+                                    id.implement(
+                                        move |request, toplevel| match request {
+                                            server_toplevel::Request::SetParent { parent } => {
+                                                log.lock().unwrap().push(format!(
+                                                    "{}.set_parent {:?}",
+                                                    toplevel.id(),
+                                                    parent.map(|p| p.id())
+                                                ))
+                                            }
+                                            server_toplevel::Request::SetAppId { app_id } => log
+                                                .lock()
+                                                .unwrap()
+                                                .push(format!("{}.set_app_id {}", toplevel.id(), app_id)),
+                                            _ => {}
+                                        },
+                                        None::<fn(_)>,
+                                        (),
+                                    );
+                                }
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+}
+
+fn dialog_server(server: &mut TestServer, log: &ToplevelLog) {
+    toplevel_server(server, log);
+    let dialog_log = log.clone();
+    server
+        .display
+        .create_global::<server_wm_dialog::XdgWmDialogV1, _>(1, move |wm_dialog, _| {
+            let log = dialog_log.clone();
+            wm_dialog.implement(
+                move |request, _| {
+                    if let server_wm_dialog::Request::GetXdgDialog { id, toplevel } = request {
+                        log.lock().unwrap().push(format!("{}.get_xdg_dialog", toplevel.id()));
+                        let log = log.clone();
+                        id.implement(
+                            move |request, _| {
+                                log.lock().unwrap().push(
+                                    match request {
+                                        server_dialog::Request::Destroy => "dialog.destroy",
+                                        server_dialog::Request::SetModal => "dialog.set_modal",
+                                        server_dialog::Request::UnsetModal => "dialog.unset_modal",
+                                    }.into(),
+                                )
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+}
+
+fn create_toplevel(globals: &wayc::GlobalManager) -> wayc::Proxy<XdgToplevel> {
+    let compositor = globals
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let wm_base = globals
+        .instantiate_auto::<XdgWmBase, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let xdg_surface = wm_base
+        .get_xdg_surface(&surface, |newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    xdg_surface
+        .get_toplevel(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap()
+}
+
+#[test]
+fn dialog() {
+    let mut server = TestServer::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    dialog_server(&mut server, &log);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let wm_dialog = globals
+        .instantiate_auto::<XdgWmDialogV1, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let parent = create_toplevel(&globals);
+    let other = create_toplevel(&globals);
+    let toplevel = create_toplevel(&globals);
+
+    let mut dialog = Dialog::new(Some(&wm_dialog), &toplevel, &parent, true).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            format!("{}.get_xdg_dialog", toplevel.id()),
+            format!("{}.set_parent Some({})", toplevel.id(), parent.id()),
+            "dialog.set_modal".to_owned(),
+        ]
+    );
+    assert!(dialog.is_modal());
+    assert!(dialog.blocks(&parent));
+    assert!(!dialog.blocks(&other));
+    assert!(!dialog.blocks(&toplevel));
+
+    // unchanged modality is not sent again
+    dialog.set_modal(true);
+    dialog.set_modal(false);
+    dialog.set_modal(false);
+    assert!(!dialog.blocks(&parent));
+    dialog.set_modal(true);
+    dialog.set_parent(&other);
+    assert!(!dialog.blocks(&parent));
+    assert!(dialog.blocks(&other));
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            "dialog.unset_modal".to_owned(),
+            "dialog.set_modal".to_owned(),
+            format!("{}.set_parent Some({})", toplevel.id(), other.id()),
+        ]
+    );
+
+    dialog.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            "dialog.destroy".to_owned(),
+            format!("{}.set_parent None", toplevel.id()),
+        ]
+    );
+}
+
+#[test]
+fn dialog_without_wm_dialog() {
+    let mut server = TestServer::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    dialog_server(&mut server, &log);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let parent = create_toplevel(&globals);
+    let toplevel = create_toplevel(&globals);
+
+    // only the parent is sent, the modality is still tracked
+    let mut dialog = Dialog::new(None, &toplevel, &parent, true).unwrap();
+    dialog.set_modal(false);
+    dialog.set_modal(true);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [format!("{}.set_parent Some({})", toplevel.id(), parent.id())]
+    );
+    assert!(dialog.blocks(&parent));
+
+    // a dead toplevel blocks nothing
+    toplevel.destroy();
+    assert!(!dialog.blocks(&parent));
+}
+
+#[test]
+fn presentation_mode_select() {
+    use PresentationMode::*;
+    for &(preferred, tearing, fifo, mode) in &[
+        (Vsync, true, true, Vsync),
+        (Tearing, true, false, Tearing),
+        (Tearing, false, true, Vsync),
+        (Fifo, false, true, Fifo),
+        (Fifo, true, false, Vsync),
+        (Fifo, false, false, Vsync),
+    ] {
+        assert_eq!(PresentationMode::select(preferred, tearing, fifo), mode);
+    }
+}
+
+// the requests received by the tearing control and fifo objects of a server
+type PresentationLog = Arc<Mutex<Vec<String>>>;
+
+fn presentation_server(server: &mut TestServer, log: &PresentationLog) {
+    server
+        .display
+        .create_global::<server_compositor::WlCompositor, _>(1, |compositor, _| {
+            compositor.implement(
+                |request, _| {
+                    if let server_compositor::Request::CreateSurface { id } = request {
+                        id.implement(|_, _| {}, None::<fn(_)>, ());
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    let tearing_log = log.clone();
+    server
+        .display
+        .create_global::<server_tearing_manager::WpTearingControlManagerV1, _>(1, move |manager, _| {
+            let log = tearing_log.clone();
+            manager.implement(
+                move |request, _| {
+                    if let server_tearing_manager::Request::GetTearingControl { id, .. } = request {
+                        let log = log.clone();
+                        id.implement(
+                            move |request, _| {
+                                log.lock().unwrap().push(match request {
+                                    server_tearing::Request::SetPresentationHint { hint } => {
+                                        format!("tearing.set_presentation_hint {:?}", hint)
+                                    }
+                                    server_tearing::Request::Destroy => "tearing.destroy".into(),
+                                })
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    let fifo_log = log.clone();
+    server
+        .display
+        .create_global::<server_fifo_manager::WpFifoManagerV1, _>(1, move |manager, _| {
+            let log = fifo_log.clone();
+            manager.implement(
+                move |request, _| {
+                    if let server_fifo_manager::Request::GetFifo { id, .. } = request {
+                        let log = log.clone();
+                        id.implement(
+                            move |request, _| {
+                                log.lock().unwrap().push(
+                                    match request {
+                                        server_fifo::Request::SetBarrier => "fifo.set_barrier",
+                                        server_fifo::Request::WaitBarrier => "fifo.wait_barrier",
+                                        server_fifo::Request::Destroy => "fifo.destroy",
+                                    }.into(),
+                                )
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+}
+
+#[test]
+fn surface_presentation() {
+    let mut server = TestServer::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    presentation_server(&mut server, &log);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let compositor = globals
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let tearing_manager = globals
+        .instantiate_auto::<WpTearingControlManagerV1, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let fifo_manager = globals
+        .instantiate_auto::<WpFifoManagerV1, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    let mut presentation = SurfacePresentation::new(
+        Some(&tearing_manager),
+        Some(&fifo_manager),
+        &surface,
+        PresentationMode::Tearing,
+    ).unwrap();
+    assert_eq!(presentation.mode(), PresentationMode::Tearing);
+    // the constraints of fifo are only set in its mode
+    presentation.prepare_commit();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        ["tearing.set_presentation_hint Async"]
+    );
+
+    assert_eq!(presentation.set_preferred(PresentationMode::Fifo), PresentationMode::Fifo);
+    presentation.prepare_commit();
+    // the hint is only sent when it changes
+    assert_eq!(presentation.set_preferred(PresentationMode::Vsync), PresentationMode::Vsync);
+    presentation.prepare_commit();
+    presentation.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            "tearing.set_presentation_hint Vsync",
+            "fifo.wait_barrier",
+            "fifo.set_barrier",
+            "tearing.destroy",
+            "fifo.destroy",
+        ]
+    );
+}
+
+#[test]
+fn surface_presentation_fallback() {
+    let mut server = TestServer::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    presentation_server(&mut server, &log);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let compositor = globals
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let surface = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    // without the managers, the surface stays in the vsync mode
+    let mut presentation = SurfacePresentation::new(None, None, &surface, PresentationMode::Tearing).unwrap();
+    assert_eq!(presentation.preferred(), PresentationMode::Tearing);
+    assert_eq!(presentation.mode(), PresentationMode::Vsync);
+    assert_eq!(presentation.set_preferred(PresentationMode::Fifo), PresentationMode::Vsync);
+    presentation.prepare_commit();
+    presentation.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn icon_image() {
+    // the pixels are premultiplied and stored as little-endian Argb8888
+    let image = IconImage::from_rgba(1, 2, &[255, 128, 0, 128]).unwrap();
+    assert_eq!(image.size(), 1);
+    assert_eq!(image.scale(), 2);
+    assert_eq!(image.data(), [0, 64, 128, 128]);
+
+    assert_eq!(IconImage::from_rgba(2, 1, &[0; 4]), None);
+    assert_eq!(IconImage::from_rgba(0, 1, &[]), None);
+    assert_eq!(IconImage::from_rgba(1, 0, &[0; 4]), None);
+    // the scale and the stride of the buffer must fit in an i32
+    assert_eq!(IconImage::from_rgba(1, 1 << 31, &[0; 4]), None);
+    assert_eq!(IconImage::from_rgba(1 << 29, 1, &[]), None);
+}
+
+// the shm pools created on a server, with the buffers created from them
+type Pools = Arc<Mutex<Vec<(RawFd, i32, Vec<String>)>>>;
+
+fn icon_server(server: &mut TestServer, log: &ToplevelLog) -> Pools {
+    toplevel_server(server, log);
+    let pools = Arc::new(Mutex::new(Vec::new()));
+    let shm_pools = pools.clone();
+    server
+        .display
+        .create_global::<server_shm::WlShm, _>(1, move |shm, _| {
+            let pools = shm_pools.clone();
+            shm.implement(
+                move |request, _| {
+                    let server_shm::Request::CreatePool { id, fd, size } = request;
+                    let index = {
+                        let mut pools = pools.lock().unwrap();
+                        pools.push((fd, size, Vec::new()));
+                        pools.len() - 1
+                    };
+                    let pools = pools.clone();
+                    id.implement(
+                        move |request, _| {
+                            if let server_shm_pool::Request::CreateBuffer {
+                                id,
+                                offset,
+                                width,
+                                height,
+                                stride,
+                                ..
+                            } = request
+                            {
+                                let buffer = id.implement(|_, _| {}, None::<fn(_)>, ());
+                                pools.lock().unwrap()[index].2.push(format!(
+                                    "{} {} {}x{} {}",
+                                    buffer.id(),
+                                    offset,
+                                    width,
+                                    height,
+                                    stride
+                                ));
+                            }
+                        },
+                        None::<fn(_)>,
+                        (),
+                    );
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    let icon_log = log.clone();
+    server
+        .display
+        .create_global::<server_icon_manager::XdgToplevelIconManagerV1, _>(1, move |manager, _| {
+            let log = icon_log.clone();
+            manager.implement(
+                move |request, _| match request {
+                    server_icon_manager::Request::CreateIcon { id } => {
+                        let log = log.clone();
+                        id.implement(
+                            move |request, icon| {
+                                log.lock().unwrap().push(match request {
+                                    server_icon::Request::SetName { icon_name } => {
+                                        format!("{}.set_name {}", icon.id(), icon_name)
+                                    }
+                                    server_icon::Request::AddBuffer { buffer, scale } => {
+                                        format!("{}.add_buffer {} {}", icon.id(), buffer.id(), scale)
+                                    }
+                                    server_icon::Request::Destroy => "icon.destroy".into(),
+                                })
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                    }
+                    server_icon_manager::Request::SetIcon { toplevel, icon } => {
+                        log.lock()
+                            .unwrap()
+                            .push(format!("{}.set_icon {:?}", toplevel.id(), icon.map(|i| i.id())))
+                    }
+                    server_icon_manager::Request::Destroy => {}
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    pools
+}
+
+#[test]
+fn toplevel_icon() {
+    let mut server = TestServer::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let pools = icon_server(&mut server, &log);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let manager = globals
+        .instantiate_auto::<XdgToplevelIconManagerV1, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let shm = globals
+        .instantiate_auto::<WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let toplevel = create_toplevel(&globals);
+
+    // the previous content of the file is replaced, wherever its cursor is
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(&[0xff; 100]).unwrap();
+    let small = IconImage::from_rgba(1, 1, &[255, 0, 0, 255]).unwrap();
+    let blue = [0, 0, 255, 255];
+    let large = IconImage::from_rgba(2, 2, &[blue, blue, blue, blue].concat()).unwrap();
+    let icon = ToplevelIcon::new(
+        &manager,
+        &shm,
+        file.try_clone().unwrap(),
+        Some("app"),
+        &[small.clone(), large.clone()],
+    ).unwrap();
+    apply_icon(Some(&manager), &toplevel, Some(&icon), "app").unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let (fd, size, buffers) = pools.lock().unwrap().pop().unwrap();
+    assert_eq!(size, 20);
+    let mut content = Vec::new();
+    let mut pool_file = unsafe { File::from_raw_fd(fd) };
+    pool_file.seek(SeekFrom::Start(0)).unwrap();
+    pool_file.read_to_end(&mut content).unwrap();
+    assert_eq!(content, [small.data(), large.data()].concat());
+
+    let icon_id = icon.icon().id();
+    let (small_id, large_id) = (buffers[0].split(' ').next().unwrap(), buffers[1].split(' ').next().unwrap());
+    assert_eq!(buffers[0], format!("{} 0 1x1 4", small_id));
+    assert_eq!(buffers[1], format!("{} 4 2x2 8", large_id));
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            format!("{}.set_name app", icon_id),
+            format!("{}.add_buffer {} 1", icon_id, small_id),
+            format!("{}.add_buffer {} 2", icon_id, large_id),
+            format!("{}.set_icon Some({})", toplevel.id(), icon_id),
+        ]
+    );
+
+    apply_icon(Some(&manager), &toplevel, None, "app").unwrap();
+    icon.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [
+            format!("{}.set_icon None", toplevel.id()),
+            "icon.destroy".to_owned(),
+        ]
+    );
+
+    // without the manager, the icon comes from the app id
+    apply_icon(None, &toplevel, None, "app").unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        [format!("{}.set_app_id app", toplevel.id())]
+    );
+}
@@ -0,0 +1,181 @@
+// The helpers of the unstable protocols are only built with their cargo feature
+#![cfg(feature = "unstable_protocols")]
+
+extern crate wayland_protocols;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols::unstable::xdg_foreign::v2::client::zxdg_exporter_v2::ZxdgExporterV2;
+use wayland_protocols::unstable::xdg_foreign::v2::client::zxdg_importer_v2::ZxdgImporterV2;
+use wayland_protocols::unstable::xdg_foreign::v2::server::zxdg_exported_v2 as server_exported;
+use wayland_protocols::unstable::xdg_foreign::v2::server::zxdg_exporter_v2 as server_exporter;
+use wayland_protocols::unstable::xdg_foreign::v2::server::zxdg_imported_v2 as server_imported;
+use wayland_protocols::unstable::xdg_foreign::v2::server::zxdg_importer_v2 as server_importer;
+use wayland_protocols::unstable::xdg_foreign::{ExportedSurface, ImportedSurface};
+
+use wayc::protocol::wl_compositor::{RequestsTrait as CompositorRequests, WlCompositor};
+
+use ways::protocol::wl_compositor as server_compositor;
+use ways::Resource;
+
+// the state of a server exporting surfaces under the handle `exported-<id>`
+#[derive(Default)]
+struct ForeignState {
+    requests: Vec<String>,
+    imported: Vec<Resource<server_imported::ZxdgImportedV2>>,
+}
+
+fn foreign_server(server: &mut TestServer, state: &Arc<Mutex<ForeignState>>) {
+    server
+        .display
+        .create_global::<server_compositor::WlCompositor, _>(1, |compositor, _| {
+            compositor.implement(
+                |request, _| {
+                    if let server_compositor::Request::CreateSurface { id } = request {
+                        id.implement(|_, _| {}, None::<fn(_)>, ());
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    let exporter_state = state.clone();
+    server
+        .display
+        .create_global::<server_exporter::ZxdgExporterV2, _>(1, move |exporter, _| {
+            let state = exporter_state.clone();
+            exporter.implement(
+                move |request, _| {
+                    if let server_exporter::Request::ExportToplevel { id, surface } = request {
+                        let state = state.clone();
+                        let exported = id.implement(
+                            move |request, _| {
+                                if let server_exported::Request::Destroy = request {
+                                    state.lock().unwrap().requests.push("exported.destroy".into());
+                                }
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                        exported.send(server_exported::Event::Handle {
+                            handle: format!("exported-{}", surface.id()),
+                        });
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+    let importer_state = state.clone();
+    server
+        .display
+        .create_global::<server_importer::ZxdgImporterV2, _>(1, move |importer, _| {
+            let state = importer_state.clone();
+            importer.implement(
+                move |request, _| {
+                    if let server_importer::Request::ImportToplevel { id, handle } = request {
+                        let log_state = state.clone();
+                        let imported = id.implement(
+                            move |request, _| {
+                                log_state.lock().unwrap().requests.push(match request {
+                                    server_imported::Request::SetParentOf { surface } => {
+                                        format!("imported.set_parent_of {}", surface.id())
+                                    }
+                                    server_imported::Request::Destroy => "imported.destroy".into(),
+                                })
+                            },
+                            None::<fn(_)>,
+                            (),
+                        );
+                        if handle.starts_with("exported-") {
+                            state.lock().unwrap().imported.push(imported);
+                        } else {
+                            imported.send(server_imported::Event::Destroyed);
+                        }
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+        });
+}
+
+#[test]
+fn xdg_foreign() {
+    let mut server = TestServer::new();
+    let state = Arc::new(Mutex::new(ForeignState::default()));
+    foreign_server(&mut server, &state);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let compositor = globals
+        .instantiate_auto::<WlCompositor, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let exporter = globals
+        .instantiate_auto::<ZxdgExporterV2, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let importer = globals
+        .instantiate_auto::<ZxdgImporterV2, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let parent = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+    let dialog = compositor
+        .create_surface(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    // the handle is only known once the compositor sent it
+    let exported = ExportedSurface::new(&exporter, &parent).unwrap();
+    assert_eq!(exported.handle(), None);
+    roundtrip(&mut client, &mut server).unwrap();
+    let handle = exported.handle().unwrap();
+    assert_eq!(handle, format!("exported-{}", parent.id()));
+
+    let imported = ImportedSurface::new(&importer, handle).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(imported.is_valid());
+    imported.set_parent_of(&dialog);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        state.lock().unwrap().requests.drain(..).collect::<Vec<_>>(),
+        [format!("imported.set_parent_of {}", dialog.id())]
+    );
+
+    // once the compositor invalidated it, the imported surface is not used anymore
+    state.lock().unwrap().imported[0].send(server_imported::Event::Destroyed);
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(!imported.is_valid());
+    imported.set_parent_of(&dialog);
+    imported.destroy();
+    exported.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+    assert_eq!(
+        state.lock().unwrap().requests.drain(..).collect::<Vec<_>>(),
+        ["imported.destroy", "exported.destroy"]
+    );
+}
+
+#[test]
+fn xdg_foreign_invalid_handle() {
+    let mut server = TestServer::new();
+    let state = Arc::new(Mutex::new(ForeignState::default()));
+    foreign_server(&mut server, &state);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let globals = wayc::GlobalManager::new(&client.display);
+    roundtrip(&mut client, &mut server).unwrap();
+    let importer = globals
+        .instantiate_auto::<ZxdgImporterV2, _>(|newp| newp.implement(|_, _| {}, ()))
+        .unwrap();
+
+    // the imported surface is valid until the compositor tells otherwise
+    let imported = ImportedSurface::new(&importer, "unknown".into()).unwrap();
+    assert!(imported.is_valid());
+    roundtrip(&mut client, &mut server).unwrap();
+    assert!(!imported.is_valid());
+}
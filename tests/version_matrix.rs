@@ -0,0 +1,72 @@
+extern crate wayland_client as wayc;
+extern crate wayland_commons;
+extern crate wayland_server as ways;
+
+use wayland_commons::version::VersionError;
+
+#[test]
+fn message_versions() {
+    let matrix = wayc::protocol::VERSION_MATRIX;
+    assert_eq!(matrix.request_since("wl_surface", "attach"), Some(1));
+    assert_eq!(matrix.request_since("wl_surface", "damage_buffer"), Some(4));
+    assert_eq!(matrix.event_since("wl_surface", "preferred_buffer_scale"), Some(6));
+    // requests and events are distinct
+    assert_eq!(matrix.event_since("wl_surface", "damage_buffer"), None);
+    assert_eq!(matrix.request_since("wl_surface", "does_not_exist"), None);
+}
+
+#[test]
+fn enum_entry_versions() {
+    let matrix = wayc::protocol::VERSION_MATRIX;
+    assert_eq!(matrix.enum_entry_since("wl_seat", "capability", "keyboard"), Some(1));
+    // the entries inherit the version of their enum
+    assert_eq!(
+        matrix.enum_entry_since("wl_data_device_manager", "dnd_action", "copy"),
+        Some(3)
+    );
+    assert_eq!(matrix.enum_entry_since("wl_seat", "capability", "joystick"), None);
+}
+
+#[test]
+fn check_bind_version() {
+    let matrix = wayc::protocol::VERSION_MATRIX;
+    assert_eq!(
+        matrix.check_requests("wl_surface", 4, &["attach", "damage_buffer"]),
+        Ok(())
+    );
+    assert_eq!(
+        matrix.check_requests("wl_surface", 3, &["attach", "damage_buffer"]),
+        Err(VersionError::Unsupported {
+            interface: "wl_surface".into(),
+            message: "damage_buffer".into(),
+            since: 4,
+        })
+    );
+    assert_eq!(
+        matrix.check_events("wl_seat", 7, &["capabilities", "teleport"]),
+        Err(VersionError::UnknownMessage {
+            interface: "wl_seat".into(),
+            message: "teleport".into(),
+        })
+    );
+}
+
+#[test]
+fn version_error_display() {
+    let matrix = wayc::protocol::VERSION_MATRIX;
+    let err = matrix.check_requests("wl_surface", 3, &["damage_buffer"]).unwrap_err();
+    assert_eq!(err.to_string(), "message wl_surface.damage_buffer requires version 4");
+    let err = matrix.check_events("wl_seat", 7, &["teleport"]).unwrap_err();
+    assert_eq!(err.to_string(), "interface wl_seat has no message teleport");
+    // usable as a boxed error
+    let _: Box<::std::error::Error> = Box::new(err);
+}
+
+#[test]
+fn server_matrix() {
+    let matrix = ways::protocol::VERSION_MATRIX;
+    assert_eq!(matrix.request_since("wl_surface", "damage_buffer"), Some(4));
+    // the display and the registry are handled by the library server-side
+    assert_eq!(matrix.request_since("wl_display", "sync"), None);
+    assert_eq!(wayc::protocol::VERSION_MATRIX.request_since("wl_display", "sync"), Some(1));
+}
@@ -4,17 +4,39 @@ use std::env::var;
 use std::path::Path;
 use wayland_scanner::*;
 
+// the file descriptors of the core protocol expected to be of a specific kind
+static FD_KINDS: &'static [FdKindHint] = &[FdKindHint {
+    interface: "wl_keyboard",
+    message: "keymap",
+    argument: "fd",
+    kind: "ReadOnlyMappable",
+}];
+
 fn main() {
     let protocol_file = "./wayland.xml";
 
     let out_dir_str = var("OUT_DIR").unwrap();
     let out_dir = Path::new(&out_dir_str);
+    let options = Options {
+        fd_kinds: FD_KINDS,
+        ..Options::default()
+    };
 
     if var("CARGO_FEATURE_NATIVE_LIB").ok().is_some() {
         // generate the C code
-        generate_c_code(protocol_file, out_dir.join("wayland_c_api.rs"), Side::Client);
+        generate_c_code_with_options(
+            protocol_file,
+            out_dir.join("wayland_c_api.rs"),
+            Side::Client,
+            options,
+        );
         generate_c_interfaces(protocol_file, out_dir.join("wayland_c_interfaces.rs"));
     } else {
-        generate_rust_code(protocol_file, out_dir.join("wayland_rust_api.rs"), Side::Client);
+        generate_rust_code_with_options(
+            protocol_file,
+            out_dir.join("wayland_rust_api.rs"),
+            Side::Client,
+            options,
+        );
     }
 }
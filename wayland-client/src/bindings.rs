@@ -0,0 +1,378 @@
+//! C ABI for bindings in other languages
+//!
+//! This module allows code written in other languages to handle wayland objects managed by
+//! this library, through their C FFI (like `ctypes` in Python or the `ffi` module of LuaJIT),
+//! without implementing the wire protocol itself. It works the same way with the rust
+//! implementation and with the `native_lib` feature.
+//!
+//! The rust side of your program hands objects over to the foreign code: `implement_foreign()`
+//! implements a new object with a foreign handler, and `foreign_proxy()` creates a handle to
+//! an existing object, through which the foreign code can send requests.
+//!
+//! The foreign code then uses the `extern "C"` functions of this module, prefixed by `wlrs_`.
+//! The arguments of messages are given as arrays of `ForeignArgument`, to be interpreted
+//! according to the signature of the message. The foreign code is thus expected to know the
+//! protocol of the objects it handles, typically by generating its bindings from the same
+//! protocol XML files.
+//!
+//! Requests creating new objects cannot be sent from foreign code, these objects need to be
+//! created by the rust side. Objects created by events however are given to the handler, which
+//! can implement them using `wlrs_new_proxy_implement()`.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::io::RawFd;
+use std::ptr;
+
+use wayland_commons::map::Object;
+use wayland_commons::wire::ArgumentType;
+
+use imp::{NewProxyInner, ProxyInner};
+use {Interface, NewProxy, Proxy, QueueToken};
+
+/// An argument of a message, as exchanged with foreign code
+///
+/// Only the fields relevant to the type of the argument are meaningful, the others are zeroed.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ForeignArgument {
+    /// Value of `int` arguments, and of `fixed` arguments in 24.8 fixed-point format
+    pub int: i32,
+    /// Value of `uint` arguments, and id of `object` arguments
+    pub uint: u32,
+    /// Value of `fd` arguments
+    pub fd: RawFd,
+    /// Contents of the pointer-like arguments
+    ///
+    /// - a nul-terminated string for `string` arguments, possibly null
+    /// - the contents of `array` arguments
+    /// - a `ForeignProxy` for `object` arguments, null for a null object
+    /// - a `ForeignNewProxy` for `new_id` arguments of events
+    pub data: *const c_void,
+    /// Length in bytes of `array` arguments
+    pub len: usize,
+}
+
+impl ForeignArgument {
+    fn empty() -> ForeignArgument {
+        ForeignArgument {
+            int: 0,
+            uint: 0,
+            fd: -1,
+            data: ptr::null(),
+            len: 0,
+        }
+    }
+}
+
+/// Handler for the events of an object implemented by foreign code
+///
+/// It receives the `data` pointer given when implementing the object, a handle to the
+/// object, and the opcode and arguments of the event. The handles given to this function,
+/// in its arguments or as `proxy`, are only valid for the duration of the call, use
+/// `wlrs_proxy_clone()` to keep them.
+pub type ForeignHandler = unsafe extern "C" fn(
+    data: *mut c_void,
+    proxy: *const ForeignProxy,
+    opcode: u32,
+    args: *const ForeignArgument,
+    nargs: usize,
+);
+
+/// Destructor for the `data` pointer of a foreign handler
+///
+/// It is called once the handler can no longer be invoked.
+pub type ForeignDestroy = unsafe extern "C" fn(data: *mut c_void);
+
+/// A handle to a wayland object, for foreign code
+pub struct ForeignProxy {
+    inner: ProxyInner,
+}
+
+/// A newly created wayland object, given to foreign handlers
+pub struct ForeignNewProxy {
+    inner: Option<NewProxyInner>,
+    desc: Option<Object<()>>,
+}
+
+/// An argument of a message, independent of the backend
+pub(crate) enum RawArgument {
+    Int(i32),
+    Uint(u32),
+    Fixed(i32),
+    Str(Option<CString>),
+    Array(Vec<u8>),
+    Fd(RawFd),
+    Object(Option<ProxyInner>),
+    NewId(NewProxyInner),
+}
+
+struct ForeignImplementation {
+    handler: ForeignHandler,
+    data: *mut c_void,
+    destroy: Option<ForeignDestroy>,
+}
+
+impl Drop for ForeignImplementation {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.destroy {
+            unsafe { destroy(self.data) }
+        }
+    }
+}
+
+unsafe fn implement_with(newp: NewProxyInner, desc: Object<()>, implem: ForeignImplementation) -> ProxyInner {
+    let child_desc = desc.clone();
+    let implementation =
+        Box::new(move |opcode, args, proxy| dispatch_foreign(&implem, &child_desc, opcode, args, proxy));
+    newp.implement_raw(&desc, implementation)
+}
+
+fn dispatch_foreign(
+    implem: &ForeignImplementation,
+    desc: &Object<()>,
+    opcode: u16,
+    args: Vec<RawArgument>,
+    proxy: ProxyInner,
+) {
+    let version = proxy.version();
+    // the arguments given to the handler, which must all outlive its invocation
+    let mut raw_args = Vec::with_capacity(args.len());
+    let mut proxies = Vec::new();
+    let mut new_proxies = Vec::new();
+    let mut foreign_args = Vec::with_capacity(args.len());
+    for arg in args {
+        let mut foreign = ForeignArgument::empty();
+        match arg {
+            RawArgument::Int(i) | RawArgument::Fixed(i) => foreign.int = i,
+            RawArgument::Uint(u) => foreign.uint = u,
+            RawArgument::Fd(fd) => foreign.fd = fd,
+            RawArgument::Str(ref s) => {
+                foreign.data = s.as_ref().map(|s| s.as_ptr() as *const c_void).unwrap_or(ptr::null())
+            }
+            RawArgument::Array(ref a) => {
+                foreign.data = a.as_ptr() as *const c_void;
+                foreign.len = a.len();
+            }
+            RawArgument::Object(Some(ref o)) => {
+                let handle = Box::new(ForeignProxy { inner: o.clone() });
+                foreign.uint = o.id();
+                foreign.data = &*handle as *const ForeignProxy as *const c_void;
+                proxies.push(handle);
+            }
+            RawArgument::Object(None) => {}
+            RawArgument::NewId(_) => {}
+        }
+        if let RawArgument::NewId(newp) = arg {
+            let handle = Box::new(ForeignNewProxy {
+                inner: Some(newp),
+                desc: (desc.childs_from_events)(opcode, version, &()),
+            });
+            foreign.data = &*handle as *const ForeignNewProxy as *const c_void;
+            new_proxies.push(handle);
+        } else {
+            raw_args.push(arg);
+        }
+        foreign_args.push(foreign);
+    }
+    let handle = ForeignProxy { inner: proxy };
+    unsafe {
+        (implem.handler)(
+            implem.data,
+            &handle,
+            u32::from(opcode),
+            foreign_args.as_ptr(),
+            foreign_args.len(),
+        );
+    }
+}
+
+/// Implement a new object with a foreign handler
+///
+/// The handler is invoked with the `data` pointer for each event received by this object.
+/// If provided, `destroy` is invoked with `data` once the object is destroyed.
+///
+/// # Safety
+///
+/// The handler and its data must be valid until the object is destroyed, and follow the
+/// same threading rules as the implementations given to `NewProxy::implement_nonsend()`.
+pub unsafe fn implement_foreign<I: Interface>(
+    mut newp: NewProxy<I>,
+    handler: ForeignHandler,
+    data: *mut c_void,
+    destroy: Option<ForeignDestroy>,
+    queue: &QueueToken,
+) -> Proxy<I> {
+    newp.inner.assign_queue(&queue.inner);
+    let implem = ForeignImplementation { handler, data, destroy };
+    Proxy::wrap(implement_with(
+        newp.inner,
+        Object::from_interface::<I>(I::VERSION, ()),
+        implem,
+    ))
+}
+
+/// Create a handle to an object for foreign code
+///
+/// The handle must be released with `wlrs_proxy_release()`.
+pub fn foreign_proxy<I: Interface>(proxy: &Proxy<I>) -> *mut ForeignProxy {
+    Box::into_raw(Box::new(ForeignProxy {
+        inner: proxy.inner.clone(),
+    }))
+}
+
+/// Send a request through a handle
+///
+/// The arguments must match the signature of the request. Returns 0 on success, or -1 if
+/// the request is unknown, its arguments do not match, or it creates an object.
+///
+/// Requests sent to dead objects are silently ignored.
+///
+/// # Safety
+///
+/// `proxy` must be a valid handle, and `args` must point to `nargs` arguments whose pointers
+/// are valid for their types.
+#[no_mangle]
+pub unsafe extern "C" fn wlrs_proxy_send(
+    proxy: *const ForeignProxy,
+    opcode: u32,
+    args: *const ForeignArgument,
+    nargs: usize,
+) -> c_int {
+    if proxy.is_null() {
+        return -1;
+    }
+    let proxy = &*proxy;
+    let signature = match proxy.inner.requests().and_then(|r| r.get(opcode as usize)) {
+        Some(desc) => desc.signature,
+        None => return -1,
+    };
+    if signature.len() != nargs || (nargs > 0 && args.is_null()) {
+        return -1;
+    }
+    let args = if nargs > 0 {
+        ::std::slice::from_raw_parts(args, nargs)
+    } else {
+        &[]
+    };
+    let mut raw_args = Vec::with_capacity(nargs);
+    for (typ, arg) in signature.iter().zip(args) {
+        raw_args.push(match *typ {
+            ArgumentType::Int => RawArgument::Int(arg.int),
+            ArgumentType::Uint => RawArgument::Uint(arg.uint),
+            ArgumentType::Fixed => RawArgument::Fixed(arg.int),
+            ArgumentType::Fd => RawArgument::Fd(arg.fd),
+            ArgumentType::Str => RawArgument::Str(if arg.data.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(arg.data as *const c_char).to_owned())
+            }),
+            ArgumentType::Array => RawArgument::Array(if arg.data.is_null() {
+                Vec::new()
+            } else {
+                ::std::slice::from_raw_parts(arg.data as *const u8, arg.len).to_owned()
+            }),
+            ArgumentType::Object => RawArgument::Object(if arg.data.is_null() {
+                None
+            } else {
+                Some((*(arg.data as *const ForeignProxy)).inner.clone())
+            }),
+            ArgumentType::NewId | ArgumentType::ArrayU64 => return -1,
+        });
+    }
+    match proxy.inner.send_raw(opcode as u16, raw_args) {
+        Ok(()) => 0,
+        Err(()) => -1,
+    }
+}
+
+/// Id of the object of a handle, 0 if it is dead
+///
+/// # Safety
+///
+/// `proxy` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn wlrs_proxy_id(proxy: *const ForeignProxy) -> u32 {
+    if proxy.is_null() {
+        return 0;
+    }
+    (*proxy).inner.id()
+}
+
+/// Version of the object of a handle, 0 if it is dead
+///
+/// # Safety
+///
+/// `proxy` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn wlrs_proxy_version(proxy: *const ForeignProxy) -> u32 {
+    if proxy.is_null() {
+        return 0;
+    }
+    (*proxy).inner.version()
+}
+
+/// Create a new handle to the object of a handle
+///
+/// The new handle must be released with `wlrs_proxy_release()`.
+///
+/// # Safety
+///
+/// `proxy` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn wlrs_proxy_clone(proxy: *const ForeignProxy) -> *mut ForeignProxy {
+    if proxy.is_null() {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(ForeignProxy {
+        inner: (*proxy).inner.clone(),
+    }))
+}
+
+/// Release a handle
+///
+/// This does not destroy the object, only the handle.
+///
+/// # Safety
+///
+/// `proxy` must be null or a handle which was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn wlrs_proxy_release(proxy: *mut ForeignProxy) {
+    if !proxy.is_null() {
+        let _ = Box::from_raw(proxy);
+    }
+}
+
+/// Implement an object created by an event with a foreign handler
+///
+/// This can only be done once per new object, during the invocation of the handler which
+/// received it. The object is dispatched on the same event queue as the object which
+/// created it.
+///
+/// Returns a handle to the object, which must be released with `wlrs_proxy_release()`,
+/// or null if the object could not be implemented, in which case `destroy` is immediately
+/// invoked if provided.
+///
+/// # Safety
+///
+/// `newp` must be null or a new object given to the currently running handler, and the
+/// handler and its data must respect the same rules as for `implement_foreign()`.
+#[no_mangle]
+pub unsafe extern "C" fn wlrs_new_proxy_implement(
+    newp: *mut ForeignNewProxy,
+    handler: ForeignHandler,
+    data: *mut c_void,
+    destroy: Option<ForeignDestroy>,
+) -> *mut ForeignProxy {
+    let implem = ForeignImplementation { handler, data, destroy };
+    if newp.is_null() {
+        return ptr::null_mut();
+    }
+    let newp = &mut *newp;
+    match (newp.inner.take(), newp.desc.take()) {
+        (Some(inner), Some(desc)) => Box::into_raw(Box::new(ForeignProxy {
+            inner: implement_with(inner, desc, implem),
+        })),
+        _ => ptr::null_mut(),
+    }
+}
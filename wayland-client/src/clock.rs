@@ -0,0 +1,135 @@
+//! Time sources of the timing-dependent helpers
+//!
+//! The helpers of this crate which depend on time, like the input latency
+//! instrumentation and the `FlushPolicy::Deadline` flushing thread, read it from the
+//! `Clock` of their `Display`. By default this is the `SystemClock`, and it can be
+//! replaced using `Display::set_clock()`.
+//!
+//! The `VirtualClock` is meant for tests: its time only changes when you advance it,
+//! so that the behavior of these helpers does not depend on the speed of the machine
+//! running the tests, and the tests do not need to actually wait.
+
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// A source of time
+pub trait Clock: Send + Sync {
+    /// The current instant
+    fn now(&self) -> Instant;
+
+    /// Block the current thread until given instant
+    ///
+    /// The sleep must end early if the thread is unparked, this is how the flushing
+    /// thread of `FlushPolicy::Deadline` is stopped. It can also end early spuriously,
+    /// callers check the time again.
+    fn sleep_until(&self, deadline: Instant);
+}
+
+/// The clock of the system
+///
+/// This is the default clock of a `Display`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let now = Instant::now();
+        if deadline > now {
+            thread::park_timeout(deadline - now);
+        }
+    }
+}
+
+/// A clock whose time only changes when advanced
+///
+/// This clock can be cloned, the clones sharing the same time, so that a test can keep
+/// one to advance the time given to the `Display`. Threads sleeping on this clock wake
+/// up each time it is advanced.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    inner: Arc<Mutex<VirtualTime>>,
+}
+
+#[derive(Debug)]
+struct VirtualTime {
+    now: Instant,
+    // the threads sleeping on the clock, unparked when it is advanced
+    sleepers: Vec<Thread>,
+}
+
+impl VirtualClock {
+    /// Create a new virtual clock
+    ///
+    /// Its time starts at the current instant of the system clock.
+    pub fn new() -> VirtualClock {
+        VirtualClock {
+            inner: Arc::new(Mutex::new(VirtualTime {
+                now: Instant::now(),
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Advance the time of this clock
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.inner.lock().unwrap();
+        time.now += duration;
+        for thread in time.sleepers.drain(..) {
+            thread.unpark();
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> VirtualClock {
+        VirtualClock::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.inner.lock().unwrap().now
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let current = thread::current();
+        {
+            let mut time = self.inner.lock().unwrap();
+            if time.now >= deadline {
+                return;
+            }
+            time.sleepers.push(current.clone());
+        }
+        // an unpark between the registration and this point is not lost
+        thread::park();
+        let mut time = self.inner.lock().unwrap();
+        time.sleepers.retain(|thread| thread.id() != current.id());
+    }
+}
+
+// The clock of a connection, shared with its event queues
+#[derive(Clone)]
+pub(crate) struct SharedClock {
+    clock: Arc<Mutex<Arc<Clock>>>,
+}
+
+impl SharedClock {
+    pub(crate) fn new() -> SharedClock {
+        SharedClock {
+            clock: Arc::new(Mutex::new(Arc::new(SystemClock))),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Arc<Clock> {
+        self.clock.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set(&self, clock: Arc<Clock>) {
+        *self.clock.lock().unwrap() = clock;
+    }
+}
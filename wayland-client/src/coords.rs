@@ -0,0 +1,443 @@
+//! Coordinate spaces of a surface
+//!
+//! The positions on a surface are expressed in several coordinate spaces, and mixing
+//! them up is a common source of hit-testing bugs:
+//!
+//! - `Buffer`: the pixels of the buffer attached to the surface,
+//! - `Surface`: the surface-local coordinates, in which the input events and the damage
+//!   are expressed. They are obtained from the buffer through its transform and scale
+//!   (`wl_surface.set_buffer_transform` and `wl_surface.set_buffer_scale`), then its
+//!   viewport (`wp_viewport`) if any,
+//! - `Window`: the coordinates relative to the window geometry of the surface
+//!   (`xdg_surface.set_window_geometry`), in which the sizes of the `xdg_surface`
+//!   configures and the anchor rectangles of `xdg_positioner` are expressed,
+//! - `Output`: the logical coordinates of the output the surface is shown on, for the
+//!   surfaces whose position is known, like the layer surfaces.
+//!
+//! The points, sizes and rectangles of this module are tagged with their space, so that
+//! they cannot be mixed up, and `SurfaceSpaces` converts them from one space to another.
+
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+use protocol::wl_output::Transform;
+use transform::{swaps_dimensions, Matrix3};
+
+/// A coordinate space of a surface
+///
+/// This trait is implemented by the `Buffer`, `Surface`, `Window` and `Output` spaces,
+/// it is not meant to be implemented outside of this crate.
+pub trait Space: Copy + fmt::Debug + PartialEq {
+    #[doc(hidden)]
+    fn to_surface(spaces: &SurfaceSpaces) -> Matrix3;
+}
+
+/// The pixels of the buffer of a surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Buffer {}
+
+/// The surface-local coordinates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Surface {}
+
+/// The coordinates relative to the window geometry of a surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Window {}
+
+/// The logical coordinates of an output
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Output {}
+
+impl Space for Buffer {
+    fn to_surface(spaces: &SurfaceSpaces) -> Matrix3 {
+        spaces.buffer_to_surface()
+    }
+}
+
+impl Space for Surface {
+    fn to_surface(_: &SurfaceSpaces) -> Matrix3 {
+        Matrix3::identity()
+    }
+}
+
+impl Space for Window {
+    fn to_surface(spaces: &SurfaceSpaces) -> Matrix3 {
+        Matrix3::translation(spaces.geometry.x, spaces.geometry.y)
+    }
+}
+
+impl Space for Output {
+    fn to_surface(spaces: &SurfaceSpaces) -> Matrix3 {
+        Matrix3::translation(-spaces.output_location.x, -spaces.output_location.y)
+    }
+}
+
+/// A point in a coordinate space
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Point<S: Space> {
+    /// The horizontal coordinate
+    pub x: f64,
+    /// The vertical coordinate
+    pub y: f64,
+    space: PhantomData<S>,
+}
+
+impl<S: Space> Point<S> {
+    /// Create a point
+    pub fn new(x: f64, y: f64) -> Point<S> {
+        Point {
+            x,
+            y,
+            space: PhantomData,
+        }
+    }
+}
+
+/// A size in a coordinate space
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Size<S: Space> {
+    /// The width
+    pub width: f64,
+    /// The height
+    pub height: f64,
+    space: PhantomData<S>,
+}
+
+impl<S: Space> Size<S> {
+    /// Create a size
+    pub fn new(width: f64, height: f64) -> Size<S> {
+        Size {
+            width,
+            height,
+            space: PhantomData,
+        }
+    }
+}
+
+/// A rectangle in a coordinate space
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect<S: Space> {
+    /// The horizontal coordinate of the top-left corner
+    pub x: f64,
+    /// The vertical coordinate of the top-left corner
+    pub y: f64,
+    /// The width
+    pub width: f64,
+    /// The height
+    pub height: f64,
+    space: PhantomData<S>,
+}
+
+impl<S: Space> Rect<S> {
+    /// Create a rectangle
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Rect<S> {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+            space: PhantomData,
+        }
+    }
+
+    /// The top-left corner of this rectangle
+    pub fn location(&self) -> Point<S> {
+        Point::new(self.x, self.y)
+    }
+
+    /// The size of this rectangle
+    pub fn size(&self) -> Size<S> {
+        Size::new(self.width, self.height)
+    }
+
+    /// Whether a point is in this rectangle
+    ///
+    /// The left and top edges are in the rectangle, the right and bottom ones are not,
+    /// so that each point of a surface belongs to a single pixel.
+    pub fn contains(&self, point: Point<S>) -> bool {
+        point.x >= self.x
+            && point.y >= self.y
+            && point.x < self.x + self.width
+            && point.y < self.y + self.height
+    }
+}
+
+/// Error returned when the state of a surface given to `SurfaceSpaces` is invalid
+///
+/// The sizes and the scale of a surface must be positive, the `SurfaceSpaces` is left
+/// unchanged when they are not.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InvalidState {
+    /// The size of the buffer is not positive
+    BufferSize(i32, i32),
+    /// The scale of the buffer is not positive
+    BufferScale(i32),
+    /// The size of the source rectangle of the viewport is not positive
+    ViewportSource(f64, f64),
+    /// The destination size of the viewport is not positive
+    ViewportDestination(i32, i32),
+}
+
+impl fmt::Display for InvalidState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvalidState::BufferSize(width, height) => write!(f, "invalid buffer size {}x{}", width, height),
+            InvalidState::BufferScale(scale) => write!(f, "invalid buffer scale {}", scale),
+            InvalidState::ViewportSource(width, height) => {
+                write!(f, "invalid viewport source size {}x{}", width, height)
+            }
+            InvalidState::ViewportDestination(width, height) => {
+                write!(f, "invalid viewport destination size {}x{}", width, height)
+            }
+        }
+    }
+}
+
+impl Error for InvalidState {
+    fn description(&self) -> &str {
+        match *self {
+            InvalidState::BufferSize(..) => "invalid buffer size",
+            InvalidState::BufferScale(_) => "invalid buffer scale",
+            InvalidState::ViewportSource(..) => "invalid viewport source size",
+            InvalidState::ViewportDestination(..) => "invalid viewport destination size",
+        }
+    }
+}
+
+/// The coordinate spaces of a surface
+///
+/// This tracks the state of a surface that the conversions between its coordinate
+/// spaces depend on, which must be updated along with the one of the surface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurfaceSpaces {
+    buffer_width: f64,
+    buffer_height: f64,
+    transform: Transform,
+    scale: i32,
+    source: Option<(f64, f64, f64, f64)>,
+    destination: Option<(i32, i32)>,
+    geometry: Rect<Surface>,
+    explicit_geometry: bool,
+    output_location: Point<Output>,
+}
+
+impl SurfaceSpaces {
+    /// The coordinate spaces of a surface with a buffer of given size
+    ///
+    /// The buffer has no transform, a scale of 1 and no viewport, the window geometry
+    /// covers the whole surface, and the surface is at the origin of its output.
+    ///
+    /// Fails if the size is not positive: the spaces of a surface are only defined
+    /// once a buffer is attached to it.
+    pub fn new(buffer_width: i32, buffer_height: i32) -> Result<SurfaceSpaces, InvalidState> {
+        let mut spaces = SurfaceSpaces {
+            buffer_width: 1.0,
+            buffer_height: 1.0,
+            transform: Transform::Normal,
+            scale: 1,
+            source: None,
+            destination: None,
+            geometry: Rect::new(0.0, 0.0, 0.0, 0.0),
+            explicit_geometry: false,
+            output_location: Point::new(0.0, 0.0),
+        };
+        spaces.set_buffer_size(buffer_width, buffer_height)?;
+        Ok(spaces)
+    }
+
+    /// Set the size of the buffer of the surface
+    ///
+    /// Fails if the size is not positive.
+    pub fn set_buffer_size(&mut self, width: i32, height: i32) -> Result<(), InvalidState> {
+        if width <= 0 || height <= 0 {
+            return Err(InvalidState::BufferSize(width, height));
+        }
+        self.buffer_width = f64::from(width);
+        self.buffer_height = f64::from(height);
+        self.update_geometry();
+        Ok(())
+    }
+
+    /// Set the transform of the buffer, as given to `wl_surface.set_buffer_transform`
+    pub fn set_buffer_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.update_geometry();
+    }
+
+    /// Set the scale of the buffer, as given to `wl_surface.set_buffer_scale`
+    ///
+    /// Fails if the scale is not positive.
+    pub fn set_buffer_scale(&mut self, scale: i32) -> Result<(), InvalidState> {
+        if scale <= 0 {
+            return Err(InvalidState::BufferScale(scale));
+        }
+        self.scale = scale;
+        self.update_geometry();
+        Ok(())
+    }
+
+    /// Set the source rectangle of the viewport, as given to `wp_viewport.set_source`
+    ///
+    /// `None` unsets it, like the `-1` values of the request.
+    ///
+    /// Fails if the size of the rectangle is not positive.
+    pub fn set_viewport_source(&mut self, source: Option<(f64, f64, f64, f64)>) -> Result<(), InvalidState> {
+        if let Some((_, _, width, height)) = source {
+            // also rejects the NaN sizes
+            if !(width > 0.0 && height > 0.0) {
+                return Err(InvalidState::ViewportSource(width, height));
+            }
+        }
+        self.source = source;
+        self.update_geometry();
+        Ok(())
+    }
+
+    /// Set the destination size of the viewport, as given to `wp_viewport.set_destination`
+    ///
+    /// `None` unsets it, like the `-1` values of the request.
+    ///
+    /// Fails if the size is not positive.
+    pub fn set_viewport_destination(&mut self, destination: Option<(i32, i32)>) -> Result<(), InvalidState> {
+        if let Some((width, height)) = destination {
+            if width <= 0 || height <= 0 {
+                return Err(InvalidState::ViewportDestination(width, height));
+            }
+        }
+        self.destination = destination;
+        self.update_geometry();
+        Ok(())
+    }
+
+    /// Set the window geometry, as given to `xdg_surface.set_window_geometry`
+    ///
+    /// `None` resets it to the whole surface.
+    pub fn set_window_geometry(&mut self, geometry: Option<(i32, i32, i32, i32)>) {
+        match geometry {
+            Some((x, y, width, height)) => {
+                self.explicit_geometry = true;
+                self.geometry = Rect::new(
+                    f64::from(x),
+                    f64::from(y),
+                    f64::from(width),
+                    f64::from(height),
+                );
+            }
+            None => {
+                self.explicit_geometry = false;
+                self.update_geometry();
+            }
+        }
+    }
+
+    /// Set the location of the surface on its output
+    pub fn set_output_location(&mut self, location: Point<Output>) {
+        self.output_location = location;
+    }
+
+    /// The size of the surface
+    pub fn surface_size(&self) -> Size<Surface> {
+        if let Some((width, height)) = self.destination {
+            return Size::new(f64::from(width), f64::from(height));
+        }
+        let (_, _, width, height) = self.source_rect();
+        Size::new(width, height)
+    }
+
+    /// The rectangle covered by the surface
+    ///
+    /// This is typically used to check whether a pointer is on the surface.
+    pub fn surface_rect(&self) -> Rect<Surface> {
+        let size = self.surface_size();
+        Rect::new(0.0, 0.0, size.width, size.height)
+    }
+
+    /// The window geometry of the surface
+    pub fn window_geometry(&self) -> Rect<Surface> {
+        self.geometry
+    }
+
+    /// The matrix converting the coordinates of a space into the ones of another
+    ///
+    /// Returns `None` if the conversion cannot be computed, when the scaling of the
+    /// viewport is too large or too small to be represented.
+    pub fn matrix<A: Space, B: Space>(&self) -> Option<Matrix3> {
+        let matrix = match B::to_surface(self).inverse() {
+            Some(inverse) => inverse.multiply(&A::to_surface(self)),
+            None => return None,
+        };
+        if matrix.is_finite() {
+            Some(matrix)
+        } else {
+            None
+        }
+    }
+
+    /// Convert a point into another space
+    ///
+    /// Returns `None` if the conversion cannot be computed, see `matrix()`.
+    pub fn convert_point<A: Space, B: Space>(&self, point: Point<A>) -> Option<Point<B>> {
+        self.matrix::<A, B>().map(|matrix| {
+            let (x, y) = matrix.apply(point.x, point.y);
+            Point::new(x, y)
+        })
+    }
+
+    /// Convert a rectangle into another space
+    ///
+    /// This is notably used to convert the damage of a surface into the one of its
+    /// buffer, or an anchor rectangle of a parent surface into its window geometry.
+    ///
+    /// Returns `None` if the conversion cannot be computed, see `matrix()`.
+    pub fn convert_rect<A: Space, B: Space>(&self, rect: Rect<A>) -> Option<Rect<B>> {
+        let matrix = match self.matrix::<A, B>() {
+            Some(matrix) => matrix,
+            None => return None,
+        };
+        let (x0, y0) = matrix.apply(rect.x, rect.y);
+        let (x1, y1) = matrix.apply(rect.x + rect.width, rect.y + rect.height);
+        // the transforms only swap and flip the axes, the corners stay opposite
+        Some(Rect::new(x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs()))
+    }
+
+    /// Convert a size into another space
+    ///
+    /// Returns `None` if the conversion cannot be computed, see `matrix()`.
+    pub fn convert_size<A: Space, B: Space>(&self, size: Size<A>) -> Option<Size<B>> {
+        self.convert_rect::<A, B>(Rect::new(0.0, 0.0, size.width, size.height))
+            .map(|rect| rect.size())
+    }
+
+    // the source rectangle of the viewport, defaulting to the whole transformed and
+    // scaled buffer
+    fn source_rect(&self) -> (f64, f64, f64, f64) {
+        self.source.unwrap_or_else(|| {
+            let scale = f64::from(self.scale);
+            if swaps_dimensions(self.transform) {
+                (0.0, 0.0, self.buffer_height / scale, self.buffer_width / scale)
+            } else {
+                (0.0, 0.0, self.buffer_width / scale, self.buffer_height / scale)
+            }
+        })
+    }
+
+    fn buffer_to_surface(&self) -> Matrix3 {
+        let (x, y, width, height) = self.source_rect();
+        let size = self.surface_size();
+        let viewport = Matrix3::scale(size.width / width, size.height / height)
+            .multiply(&Matrix3::translation(-x, -y));
+        viewport.multiply(&Matrix3::buffer_to_surface(
+            self.transform,
+            self.scale,
+            self.buffer_width,
+            self.buffer_height,
+        ))
+    }
+
+    fn update_geometry(&mut self) {
+        if !self.explicit_geometry {
+            self.geometry = self.surface_rect();
+        }
+    }
+}
@@ -13,6 +13,10 @@
 //! with the means of knowing which frame of the animation shoudl be
 //! displayed at which time, as well as handles to the buffers containing
 //! these frames, to attach them to a wayland surface.
+//!
+//! Loading a theme decodes all of its images, which can be costly for large themes.
+//! A `CursorTheme` can be shared between threads, and the `CursorThemeCache` allows
+//! your windows to share the themes they use rather than loading them each time.
 
 use protocol::wl_buffer::WlBuffer;
 use protocol::wl_shm::WlShm;
@@ -21,6 +25,7 @@ use std::marker::PhantomData;
 use std::ops::Deref;
 use std::os::raw::c_int;
 use std::ptr;
+use std::sync::{Arc, Mutex, Weak};
 use wayland_sys::cursor::*;
 use Proxy;
 
@@ -33,11 +38,17 @@ pub fn is_available() -> bool {
 }
 
 /// Represents a cursor theme loaded from the system.
+///
+/// The theme can be used from several threads, the accesses to `libwayland-cursor.so`
+/// are serialized internally. The images of the theme are decoded once when loading it,
+/// and their buffers are created on first use and then reused.
 pub struct CursorTheme {
     theme: *mut wl_cursor_theme,
+    lock: Mutex<()>,
 }
 
 unsafe impl Send for CursorTheme {}
+unsafe impl Sync for CursorTheme {}
 
 /// Attempts to load a cursor theme from given name.
 ///
@@ -79,7 +90,10 @@ pub fn load_theme(name: Option<&str>, size: u32, shm: &Proxy<WlShm>) -> CursorTh
 
     assert!(!ptr.is_null(), "Memory allocation failure while loading a theme.");
 
-    CursorTheme { theme: ptr }
+    CursorTheme {
+        theme: ptr,
+        lock: Mutex::new(()),
+    }
 }
 
 impl CursorTheme {
@@ -90,6 +104,7 @@ impl CursorTheme {
     /// Panics if the name contains an interior null.
     pub fn get_cursor(&self, name: &str) -> Option<Cursor> {
         let cstr = CString::new(name).expect("Cursor name contained an interior null.");
+        let _guard = self.lock.lock().unwrap();
         let ptr = unsafe {
             ffi_dispatch!(
                 WAYLAND_CURSOR_HANDLE,
@@ -102,7 +117,7 @@ impl CursorTheme {
             None
         } else {
             Some(Cursor {
-                _theme: PhantomData,
+                theme: self,
                 cursor: ptr,
             })
         }
@@ -119,11 +134,12 @@ impl Drop for CursorTheme {
 
 /// A cursor from a theme. Can contain several images if animated.
 pub struct Cursor<'a> {
-    _theme: PhantomData<&'a CursorTheme>,
+    theme: &'a CursorTheme,
     cursor: *mut wl_cursor,
 }
 
 unsafe impl<'a> Send for Cursor<'a> {}
+unsafe impl<'a> Sync for Cursor<'a> {}
 
 impl<'a> Cursor<'a> {
     /// Retrieve the name of this cursor.
@@ -179,6 +195,8 @@ impl<'a> Cursor<'a> {
         if frame >= self.image_count() {
             None
         } else {
+            // the buffer is lazily created by the lib
+            let _guard = self.theme.lock.lock().unwrap();
             unsafe {
                 let image = *(*self.cursor).images.offset(frame as isize);
                 let ptr = ffi_dispatch!(WAYLAND_CURSOR_HANDLE, wl_cursor_image_get_buffer, image);
@@ -225,6 +243,7 @@ pub struct CursorImageBuffer<'a> {
 }
 
 unsafe impl<'a> Send for CursorImageBuffer<'a> {}
+unsafe impl<'a> Sync for CursorImageBuffer<'a> {}
 
 impl<'a> Deref for CursorImageBuffer<'a> {
     type Target = Proxy<WlBuffer>;
@@ -232,3 +251,64 @@ impl<'a> Deref for CursorImageBuffer<'a> {
         &self.buffer
     }
 }
+
+/// A cache of loaded cursor themes
+///
+/// Windows needing a theme can request it from a shared cache instead of loading it
+/// themselves: a theme is loaded only once for a given name, size and `WlShm` global,
+/// and is then shared for as long as it is in use. You would for example share a single
+/// cache between all your windows, and load the theme at a different size for each
+/// scale factor of your outputs.
+///
+/// As the buffers of a theme belong to a wayland connection, themes are never shared
+/// between different `WlShm` globals.
+pub struct CursorThemeCache {
+    themes: Mutex<Vec<CachedTheme>>,
+}
+
+struct CachedTheme {
+    name: Option<String>,
+    size: u32,
+    shm: Proxy<WlShm>,
+    theme: Weak<CursorTheme>,
+}
+
+impl CursorThemeCache {
+    /// Create a new empty cache
+    pub fn new() -> CursorThemeCache {
+        CursorThemeCache {
+            themes: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get a theme from the cache, loading it if necessary
+    ///
+    /// The arguments and panics are the same as for `load_theme()`.
+    pub fn load(&self, name: Option<&str>, size: u32, shm: &Proxy<WlShm>) -> Arc<CursorTheme> {
+        let mut themes = self.themes.lock().unwrap();
+        // forget the themes which are no longer used
+        themes.retain(|cached| cached.theme.upgrade().is_some() && cached.shm.is_alive());
+        let found = themes
+            .iter()
+            .find(|cached| {
+                cached.name.as_ref().map(|n| &n[..]) == name && cached.size == size && cached.shm == *shm
+            }).and_then(|cached| cached.theme.upgrade());
+        if let Some(theme) = found {
+            return theme;
+        }
+        let theme = Arc::new(load_theme(name, size, shm));
+        themes.push(CachedTheme {
+            name: name.map(|n| n.to_owned()),
+            size,
+            shm: shm.clone(),
+            theme: Arc::downgrade(&theme),
+        });
+        theme
+    }
+}
+
+impl Default for CursorThemeCache {
+    fn default() -> CursorThemeCache {
+        CursorThemeCache::new()
+    }
+}
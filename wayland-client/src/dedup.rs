@@ -0,0 +1,141 @@
+// Dropping of the repeated events of whole interfaces, see `EventQueue::dedup_events()`
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use wayland_commons::wire::Argument;
+
+use imp::ProxyInner;
+use {Interface, MessageGroup};
+
+// the filters of an event queue, indexed by interface name
+pub(crate) type Dedup = RefCell<HashMap<&'static str, Filter>>;
+
+thread_local! {
+    // the filters of the event queue currently dispatching on this thread
+    static ACTIVE: RefCell<Option<Rc<Dedup>>> = RefCell::new(None);
+}
+
+pub(crate) struct Filter {
+    barrier: u16,
+    objects: Vec<Object>,
+}
+
+// the events delivered to an object of a filtered interface
+struct Object {
+    proxy: ProxyInner,
+    // the arguments of the last event of each opcode
+    last: HashMap<u16, Vec<Argument>>,
+    // whether an event was delivered since the last barrier
+    changed: bool,
+}
+
+pub(crate) fn set<I: Interface>(dedup: &Dedup, barrier: &str) {
+    let barrier = match I::Event::opcode_from_name(barrier) {
+        Some(opcode) => opcode,
+        None => panic!("{} has no event named {}.", I::NAME, barrier),
+    };
+    dedup.borrow_mut().insert(
+        I::NAME,
+        Filter {
+            barrier,
+            objects: Vec::new(),
+        },
+    );
+}
+
+pub(crate) fn unset<I: Interface>(dedup: &Dedup) -> bool {
+    dedup.borrow_mut().remove(I::NAME).is_some()
+}
+
+/// Run a dispatching closure with the filters of an event queue active
+pub(crate) fn with_dedup<T, F: FnOnce() -> T>(dedup: &Rc<Dedup>, f: F) -> T {
+    let new_active = if dedup.borrow().is_empty() {
+        None
+    } else {
+        Some(dedup.clone())
+    };
+    let previous = ACTIVE.with(|active| ::std::mem::replace(&mut *active.borrow_mut(), new_active));
+    let ret = f();
+    ACTIVE.with(|active| *active.borrow_mut() = previous);
+    ret
+}
+
+/// Whether the events of an interface are filtered by the event queue currently dispatching
+#[cfg_attr(feature = "native_lib", allow(dead_code))]
+pub(crate) fn is_active(interface: &str) -> bool {
+    ACTIVE.with(|active| match *active.borrow() {
+        Some(ref dedup) => dedup.borrow().contains_key(interface),
+        None => false,
+    })
+}
+
+/// The arguments of an event, if it can be compared to the previous ones
+///
+/// The events carrying file descriptors or creating objects are never repeated.
+#[cfg_attr(feature = "native_lib", allow(dead_code))]
+pub(crate) fn comparable(args: &[Argument]) -> Option<Vec<Argument>> {
+    if args.iter().any(|arg| match *arg {
+        Argument::Fd(_) | Argument::NewId(_) => true,
+        _ => false,
+    }) {
+        None
+    } else {
+        Some(args.to_vec())
+    }
+}
+
+/// Whether an event must be given to the implementation of its proxy
+///
+/// `args` is only invoked if the interface of the proxy is filtered, and returns `None` if the
+/// event can not be compared.
+pub(crate) fn keep<I, F>(proxy: &ProxyInner, opcode: u16, args: F) -> bool
+where
+    I: Interface,
+    F: FnOnce() -> Option<Vec<Argument>>,
+{
+    let dedup = match ACTIVE.with(|active| active.borrow().clone()) {
+        Some(dedup) => dedup,
+        None => return true,
+    };
+    let mut filters = dedup.borrow_mut();
+    let filter = match filters.get_mut(I::NAME) {
+        Some(filter) => filter,
+        None => return true,
+    };
+    // forget the destroyed objects, their ids may be reused
+    filter.objects.retain(|object| object.proxy.is_alive());
+    let index = match filter.objects.iter().position(|object| object.proxy.equals(proxy)) {
+        Some(index) => index,
+        None => {
+            filter.objects.push(Object {
+                proxy: proxy.clone(),
+                last: HashMap::new(),
+                // the first barrier of an object is always delivered
+                changed: true,
+            });
+            filter.objects.len() - 1
+        }
+    };
+    let object = &mut filter.objects[index];
+    if opcode == filter.barrier {
+        return ::std::mem::replace(&mut object.changed, false);
+    }
+    let destructor = I::Event::MESSAGES
+        .get(opcode as usize)
+        .map(|desc| desc.destructor)
+        .unwrap_or(true);
+    match args() {
+        Some(ref args) if !destructor && object.last.get(&opcode) == Some(args) => false,
+        Some(args) => {
+            object.last.insert(opcode, args);
+            object.changed = true;
+            true
+        }
+        None => {
+            object.changed = true;
+            true
+        }
+    }
+}
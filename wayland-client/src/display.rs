@@ -1,17 +1,28 @@
 use std::env;
 use std::ffi::OsString;
+use std::fmt;
 use std::io;
 use std::ops::Deref;
 use std::os::unix::io::{IntoRawFd, RawFd};
 use std::os::unix::net::UnixStream;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 use nix::fcntl;
 
-use {EventQueue, Proxy};
+use wayland_commons::socket::ConnectionStats;
+use wayland_commons::trace::{Direction, LoggedMessage, ProtocolLogger};
 
-use imp::DisplayInner;
+use clock::{Clock, SharedClock};
+use quotas::QuotaExceeded;
+use routing::GlobalFilter;
+use takeover::{Adoption, TakeoverError, TakeoverState};
+use {EventQueue, Interface, Proxy};
+
+use imp::{DisplayInner, EventQueueInner};
 
 #[cfg(feature = "native_lib")]
 use wayland_sys::client::wl_display;
@@ -35,6 +46,114 @@ pub enum ConnectError {
     InvalidFd,
 }
 
+/// A protocol error sent by the server
+///
+/// Protocol errors are fatal to the connection, see `Display::protocol_error()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtocolError {
+    /// The error code, whose meaning depends on the interface of the object
+    ///
+    /// It is typically the raw value of the `Error` enum of this interface, whose
+    /// `summary()` describes it.
+    pub code: u32,
+    /// The id of the object the error was posted to
+    pub object_id: u32,
+    /// The interface of the object the error was posted to
+    pub object_interface: String,
+    /// The message of the error
+    ///
+    /// It is always empty with the `native_lib` feature, as `libwayland-client.so`
+    /// does not keep it.
+    pub message: String,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "protocol error {} on object {}@{}",
+            self.code, self.object_interface, self.object_id
+        )?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl ::std::error::Error for ProtocolError {
+    fn description(&self) -> &str {
+        "wayland protocol error"
+    }
+}
+
+/// Policy deciding when the requests sent on a `Display` are flushed to the server
+///
+/// Requests are buffered by the library, and are only sent to the server when the
+/// connection is flushed. Whatever the policy, the connection is flushed by
+/// `Display::flush()`, before `EventQueue::dispatch()` blocks waiting for events,
+/// and by `EventQueue::sync_roundtrip()`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FlushPolicy {
+    /// Requests are only flushed at the points listed above
+    ///
+    /// This is the default.
+    Manual,
+    /// Requests are also flushed at the end of each dispatching of an event queue,
+    /// and before reading events with a `ReadEventsGuard`
+    ///
+    /// This lets the requests sent by the implementations be batched together, with
+    /// the guarantee that they are sent before the application waits for events.
+    OnDispatch,
+    /// Same as `OnDispatch`, and the requests are in addition flushed at least once
+    /// per given duration
+    ///
+    /// This spawns a thread flushing the connection regularly, until the policy is
+    /// changed or the `Display` is dropped. It ensures requests sent outside of the
+    /// dispatching, for example by worker threads, do not wait for the next dispatch.
+    Deadline(Duration),
+}
+
+// The flush policy of a connection, shared with its event queues
+pub(crate) struct FlushState {
+    policy: Mutex<FlushPolicy>,
+    // incremented on each policy change, stopping the flushing thread of the previous one
+    generation: AtomicUsize,
+    // the current flushing thread, unparked to stop it
+    thread: Mutex<Option<thread::Thread>>,
+    // number of pending `Display::cork()` calls
+    corks: AtomicUsize,
+}
+
+impl FlushState {
+    pub(crate) fn new() -> FlushState {
+        FlushState {
+            policy: Mutex::new(FlushPolicy::Manual),
+            generation: AtomicUsize::new(0),
+            thread: Mutex::new(None),
+            corks: AtomicUsize::new(0),
+        }
+    }
+
+    // stop the current flushing thread, returning the generation of the next one
+    fn stop_thread(&self) -> usize {
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            // ends its sleep, or the next one if it is not sleeping yet
+            thread.unpark();
+        }
+        generation
+    }
+
+    pub(crate) fn flush_on_dispatch(&self) -> bool {
+        !self.is_corked() && *self.policy.lock().unwrap() != FlushPolicy::Manual
+    }
+
+    fn is_corked(&self) -> bool {
+        self.corks.load(Ordering::Acquire) > 0
+    }
+}
+
 /// A connection to a wayland server
 ///
 /// This object both represent the connection to the server, and as such
@@ -44,6 +163,9 @@ pub enum ConnectError {
 /// `Deref`.
 pub struct Display {
     pub(crate) inner: Arc<DisplayInner>,
+    flush: Arc<FlushState>,
+    clock: SharedClock,
+    filter: Arc<GlobalFilter>,
 }
 
 impl Display {
@@ -63,12 +185,7 @@ impl Display {
         if let Ok(txt) = env::var("WAYLAND_SOCKET") {
             // We should connect to the provided WAYLAND_SOCKET
             let fd = txt.parse::<i32>().map_err(|_| ConnectError::InvalidFd)?;
-            // set the CLOEXEC flag on this FD
-            let flags = fcntl::fcntl(fd, fcntl::FcntlArg::F_GETFD);
-            let result = flags
-                .map(|f| fcntl::FdFlag::from_bits(f).unwrap() | fcntl::FdFlag::FD_CLOEXEC)
-                .and_then(|f| fcntl::fcntl(fd, fcntl::FcntlArg::F_SETFD(f)));
-            match result {
+            match set_cloexec(fd) {
                 Ok(_) => {
                     // setting the O_CLOEXEC worked
                     unsafe { Display::from_fd(fd) }
@@ -85,8 +202,7 @@ impl Display {
                 .ok_or(ConnectError::XdgRuntimeDirNotSet)?;
             socket_path.push(env::var_os("WAYLAND_DISPLAY").unwrap_or_else(|| "wayland-0".into()));
 
-            let socket = UnixStream::connect(socket_path).map_err(|_| ConnectError::NoCompositorListening)?;
-            unsafe { Display::from_fd(socket.into_raw_fd()) }
+            Display::connect_to_socket(socket_path)
         }
     }
 
@@ -102,7 +218,19 @@ impl Display {
             .ok_or(ConnectError::XdgRuntimeDirNotSet)?;
         socket_path.push(name.into());
 
-        let socket = UnixStream::connect(socket_path).map_err(|_| ConnectError::NoCompositorListening)?;
+        Display::connect_to_socket(socket_path)
+    }
+
+    /// Attempt to connect to a wayland server socket at given path
+    ///
+    /// Unlike `connect_to_name()`, the path is used as-is and `XDG_RUNTIME_DIR` is not
+    /// needed, this is typically useful to connect to a nested compositor, or to a socket
+    /// given to a sandboxed application.
+    ///
+    /// On success, you are given the `Display` object as well as the main `EventQueue` hosting
+    /// the `WlDisplay` wayland object.
+    pub fn connect_to_socket<P: AsRef<Path>>(path: P) -> Result<(Display, EventQueue), ConnectError> {
+        let socket = UnixStream::connect(path).map_err(|_| ConnectError::NoCompositorListening)?;
         unsafe { Display::from_fd(socket.into_raw_fd()) }
     }
 
@@ -114,7 +242,56 @@ impl Display {
     /// Will take ownership of the FD.
     pub unsafe fn from_fd(fd: RawFd) -> Result<(Display, EventQueue), ConnectError> {
         let (d_inner, evq_inner) = DisplayInner::from_fd(fd)?;
-        Ok((Display { inner: d_inner }, EventQueue::new(evq_inner)))
+        Ok(Display::new(d_inner, evq_inner))
+    }
+
+    /// Adopt a connection handed over by the previous process, through `exec()`
+    ///
+    /// The objects of the connection must then be adopted through the returned `Adoption`,
+    /// see the `takeover` module for details. Will take ownership of the FD of the state.
+    ///
+    /// Fails with `TakeoverError::Unsupported` with the `native_lib` cargo feature.
+    pub unsafe fn adopt(state: TakeoverState) -> Result<(Display, EventQueue, Adoption), TakeoverError> {
+        let objects = state.objects().to_vec();
+        let fd = state.into_raw_fd();
+        if set_cloexec(fd).is_err() {
+            let _ = ::nix::unistd::close(fd);
+            return Err(TakeoverError::Invalid);
+        }
+        let (d_inner, evq_inner) = DisplayInner::adopt(fd, &objects)?;
+        let adoption = Adoption::new(d_inner.clone(), objects);
+        let (display, evq) = Display::new(d_inner, evq_inner);
+        Ok((display, evq, adoption))
+    }
+
+    /// Prepare handing this connection over to a new process
+    ///
+    /// The requests are flushed, and a copy of the socket which is inherited through `exec()`
+    /// is returned with the list of the live objects. No request must be sent on this
+    /// connection afterwards. See the `takeover` module for the whole handshake.
+    ///
+    /// Fails with `TakeoverError::Pending` if events were not dispatched yet, and with
+    /// `TakeoverError::Unsupported` with the `native_lib` cargo feature.
+    pub fn prepare_takeover(&self) -> Result<TakeoverState, TakeoverError> {
+        self.inner.prepare_takeover()
+    }
+
+    /// The protocol error that was sent by the server, if any
+    ///
+    /// Once the server sent a protocol error, the connection is dead and all dispatching
+    /// fails with an `EPROTO` error, this gives access to the error itself.
+    pub fn protocol_error(&self) -> Option<ProtocolError> {
+        self.inner.protocol_error()
+    }
+
+    /// Counters of the data exchanged with the server so far
+    ///
+    /// The `metrics` module renders them for Prometheus.
+    ///
+    /// This is only supported by the rust implementation: with the `native_lib` cargo feature,
+    /// `libwayland-client.so` does not expose them and this returns `None`.
+    pub fn connection_stats(&self) -> Option<ConnectionStats> {
+        self.inner.connection_stats()
     }
 
     /// Non-blocking write to the server
@@ -133,7 +310,183 @@ impl Display {
     /// Create a new event queue associated with this wayland connection
     pub fn create_event_queue(&self) -> EventQueue {
         let evq_inner = DisplayInner::create_event_queue(&self.inner);
-        EventQueue::new(evq_inner)
+        EventQueue::new(
+            evq_inner,
+            self.flush.clone(),
+            self.clock.clone(),
+            self.filter.clone(),
+        )
+    }
+
+    /// Set the policy deciding when the requests are flushed to the server
+    ///
+    /// See `FlushPolicy` for the available policies.
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        let generation = self.flush.stop_thread();
+        *self.flush.policy.lock().unwrap() = policy;
+        if let FlushPolicy::Deadline(deadline) = policy {
+            let display = Arc::downgrade(&self.inner);
+            let flush = self.flush.clone();
+            let clock = self.clock.get();
+            let mut next = clock.now() + deadline;
+            let spawned = thread::Builder::new()
+                .name("wayland-flush".into())
+                .spawn(move || loop {
+                    clock.sleep_until(next);
+                    if flush.generation.load(Ordering::Acquire) != generation {
+                        return;
+                    }
+                    let now = clock.now();
+                    if now < next {
+                        // woken up early
+                        continue;
+                    }
+                    next = now + deadline;
+                    match display.upgrade() {
+                        // errors will be reported by the next flush of the application
+                        Some(display) => if !flush.is_corked() {
+                            let _ = display.flush();
+                        },
+                        None => return,
+                    }
+                });
+            match spawned {
+                Ok(handle) => *self.flush.thread.lock().unwrap() = Some(handle.thread().clone()),
+                // without a thread, still flush on dispatch
+                Err(_) => *self.flush.policy.lock().unwrap() = FlushPolicy::OnDispatch,
+            }
+        }
+    }
+
+    /// The current flush policy of this connection
+    pub fn flush_policy(&self) -> FlushPolicy {
+        *self.flush.policy.lock().unwrap()
+    }
+
+    /// Hold back the flushes of the `FlushPolicy` until `uncork()` is called
+    ///
+    /// While the connection is corked, the requests sent from any thread accumulate in
+    /// the buffers of the library, so that a burst of requests (like the attach, damage,
+    /// frame and commit of a surface) is written to the server with a single system call
+    /// rather than being split by the flushes after dispatching or of the flushing thread.
+    ///
+    /// Calls can be nested, the connection stays corked until each of them is matched by
+    /// an `uncork()`. The explicit flushes, by `Display::flush()`, before
+    /// `EventQueue::dispatch()` blocks and by `EventQueue::sync_roundtrip()`, still happen.
+    /// The requests may also be written earlier if they no longer fit in the buffers.
+    pub fn cork(&self) {
+        self.flush.corks.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// End a `cork()`
+    ///
+    /// Once each `cork()` is matched, the connection is flushed, sending the batched
+    /// requests together.
+    ///
+    /// Panics if the connection is not corked.
+    pub fn uncork(&self) -> io::Result<()> {
+        // never go below 0, so that the connection is not corked forever if the panic is caught
+        let mut previous = self.flush.corks.load(Ordering::Acquire);
+        loop {
+            assert!(previous > 0, "Display::uncork() called on a connection which is not corked.");
+            match self
+                .flush
+                .corks
+                .compare_exchange(previous, previous - 1, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(current) => previous = current,
+            }
+        }
+        if previous == 1 {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Send the requests of a closure as a single batch
+    ///
+    /// The connection is corked while the closure runs, then uncorked, flushing the
+    /// requests it sent. See `cork()` for details. If the closure panics, the connection
+    /// is uncorked as well.
+    pub fn with_batch<T, F: FnOnce() -> T>(&self, f: F) -> io::Result<T> {
+        self.cork();
+        let guard = CorkGuard(self);
+        let ret = f();
+        ::std::mem::forget(guard);
+        self.uncork()?;
+        Ok(ret)
+    }
+
+    /// Set the clock of this connection
+    ///
+    /// This replaces the `SystemClock`, and is used by the timing-dependent helpers
+    /// of this connection and of all of its event queues. The flushing thread of the
+    /// `FlushPolicy::Deadline` policy keeps the clock it was started with: set the
+    /// clock before the policy. See the `clock` module for details.
+    pub fn set_clock<C: Clock + 'static>(&self, clock: C) {
+        self.clock.set(Arc::new(clock));
+    }
+
+    /// Set the soft limit on the number of live objects of interface `I`
+    ///
+    /// Providing `None` removes the limit. See the `quotas` module documentation
+    /// for details.
+    pub fn set_object_quota<I: Interface>(&self, limit: Option<usize>) {
+        self.inner.quotas().set_quota(I::NAME, limit);
+    }
+
+    /// Set the hook invoked when an object quota is exceeded
+    ///
+    /// It replaces the default hook, which prints a warning on stderr. The hook can be
+    /// invoked from any thread creating objects on this connection, and while internal
+    /// locks of the library are held: it must not send requests or dispatch events.
+    pub fn set_quota_hook<F>(&self, hook: F)
+    where
+        F: Fn(&QuotaExceeded) + Send + Sync + 'static,
+    {
+        self.inner.quotas().set_hook(Arc::new(hook));
+    }
+
+    /// Number of live objects of interface `I` on this connection
+    pub fn live_objects<I: Interface>(&self) -> usize {
+        self.inner.quotas().live(I::NAME)
+    }
+
+    /// Set the logger of the messages of this connection
+    ///
+    /// Once set, the requests sent and the events received on this connection are given to
+    /// `logger` rather than printed on stdout when the `WAYLAND_DEBUG` environment variable is
+    /// set, with their trace id. Providing `None` restores the `WAYLAND_DEBUG` log. See the
+    /// `wayland_commons::trace` module for details.
+    ///
+    /// The logger is invoked while internal locks of the library are held: it must not send
+    /// requests or dispatch events.
+    ///
+    /// This is only supported by the rust implementation: with the `native_lib` cargo feature,
+    /// the logger is never invoked.
+    pub fn set_protocol_logger<F>(&self, logger: Option<F>)
+    where
+        F: Fn(Direction, &LoggedMessage) + Send + Sync + 'static,
+    {
+        self.inner
+            .set_protocol_logger(logger.map(|l| Arc::new(l) as ProtocolLogger));
+    }
+
+    /// Pretend that the globals of given interface are not advertised by the server
+    ///
+    /// Their `global` and `global_remove` events are dropped before they reach the
+    /// implementations of the registries of this connection, including the one of a
+    /// `GlobalManager`, which allows testing the fallback paths of an application
+    /// without an other compositor. Only the globals advertised after this call are
+    /// affected.
+    ///
+    /// The interfaces listed in the `WAYLAND_RS_DENY_GLOBALS` environment variable,
+    /// separated by commas, are denied when the connection is created, for example
+    /// `WAYLAND_RS_DENY_GLOBALS=wp_viewporter,zwp_linux_dmabuf_v1`.
+    pub fn deny_global(&self, interface: &str) {
+        self.filter.deny(interface);
     }
 
     #[cfg(feature = "native_lib")]
@@ -151,7 +504,7 @@ impl Display {
     /// its wrapper), you must use the `get_display_ptr()` method.
     pub unsafe fn from_external_display(display_ptr: *mut wl_display) -> (Display, EventQueue) {
         let (d_inner, evq_inner) = DisplayInner::from_external(display_ptr);
-        (Display { inner: d_inner }, EventQueue::new(evq_inner))
+        Display::new(d_inner, evq_inner)
     }
 
     #[cfg(feature = "native_lib")]
@@ -167,6 +520,47 @@ impl Display {
     }
 }
 
+impl Display {
+    fn new(inner: Arc<DisplayInner>, evq_inner: EventQueueInner) -> (Display, EventQueue) {
+        let flush = Arc::new(FlushState::new());
+        let clock = inner.clock();
+        let filter = Arc::new(GlobalFilter::from_env());
+        let evq = EventQueue::new(evq_inner, flush.clone(), clock.clone(), filter.clone());
+        (
+            Display {
+                inner,
+                flush,
+                clock,
+                filter,
+            },
+            evq,
+        )
+    }
+}
+
+impl Drop for Display {
+    fn drop(&mut self) {
+        // the event queues may outlive the display, but not its flushing thread
+        self.flush.stop_thread();
+    }
+}
+
+// uncorks the connection if a batch of `Display::with_batch()` panics, it is forgotten otherwise
+struct CorkGuard<'a>(&'a Display);
+
+impl<'a> Drop for CorkGuard<'a> {
+    fn drop(&mut self) {
+        let _ = self.0.uncork();
+    }
+}
+
+// set the CLOEXEC flag on an inherited FD
+fn set_cloexec(fd: RawFd) -> ::nix::Result<()> {
+    let flags = fcntl::fcntl(fd, fcntl::FcntlArg::F_GETFD)?;
+    let flags = fcntl::FdFlag::from_bits(flags).unwrap() | fcntl::FdFlag::FD_CLOEXEC;
+    fcntl::fcntl(fd, fcntl::FcntlArg::F_SETFD(flags)).map(|_| ())
+}
+
 impl Deref for Display {
     type Target = Proxy<::protocol::wl_display::WlDisplay>;
     fn deref(&self) -> &Proxy<::protocol::wl_display::WlDisplay> {
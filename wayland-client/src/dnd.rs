@@ -0,0 +1,532 @@
+//! Drag-and-drop sources and data offers
+//!
+//! Dragging contents out of a surface involves a `wl_data_source` whose events must be
+//! tracked for the whole operation: the compositor negotiates the action (copy, move or
+//! ask) with the destination, the destination requests the contents in some of the
+//! offered MIME types, each transfer writing to a pipe that may fill up, and the drag
+//! ends either with `dnd_finished` or with `cancelled`.
+//!
+//! A `DragSource` offers contents held in memory and does this bookkeeping. The
+//! transfers are written without blocking the event queue of the connection: the bytes
+//! which do not fit in a pipe are kept until it can accept them, its file descriptor is
+//! listed by `pending_fds()` to be polled for writing, and `write_pending()` resumes
+//! the writes.
+//!
+//! The actions are negotiated starting with version 3 of `wl_data_device_manager`.
+//! With earlier versions, the drag can only end with `cancelled`.
+//!
+//! On the receiving side, the contents of a selection or of a drag are given by a
+//! `wl_data_offer`, announced by the `data_offer` event of `wl_data_device`. A `DataOffer`
+//! collects the MIME types it is offered in, a `MimePolicy` chooses which one to receive,
+//! like a `MimePreference` listing the acceptable types in order, and `receive()` returns
+//! a `DataReader` reading the contents from a pipe. The reads never block the application:
+//! the contents are limited in size, and `read_to_end()` gives up after a timeout, so that a
+//! stalled or misbehaving source cannot hang it.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use nix::errno::Errno;
+use nix::fcntl::{self, OFlag};
+use nix::poll::{poll, EventFlags, PollFd};
+use nix::unistd;
+
+use protocol::wl_data_device::{RequestsTrait as DeviceRequests, WlDataDevice};
+use protocol::wl_data_device_manager::{DndAction, RequestsTrait as ManagerRequests, WlDataDeviceManager};
+use protocol::wl_data_offer::{self, RequestsTrait as OfferRequests, WlDataOffer};
+use protocol::wl_data_source::{Event, RequestsTrait as SourceRequests, WlDataSource};
+use protocol::wl_surface::WlSurface;
+use shm::{dead_object, nix_error};
+use {NewProxy, Proxy};
+
+/// The state of a drag-and-drop operation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DragState {
+    /// The contents are being dragged
+    Dragging,
+    /// The contents were dropped, the destination may still be reading them
+    Dropped,
+    /// The destination is done with the contents, with this final action
+    ///
+    /// If it is `DndAction::Move`, the contents are to be removed from the source.
+    Finished(DndAction),
+    /// The drag was cancelled, by the user or the compositor, or the destination did
+    /// not accept the contents
+    Cancelled,
+}
+
+// the contents written to a pipe given by the destination
+struct Transfer {
+    fd: RawFd,
+    data: Arc<Vec<u8>>,
+    written: usize,
+}
+
+impl Transfer {
+    // write as much as the pipe accepts, returns whether the transfer is over
+    fn write(&mut self) -> bool {
+        while self.written < self.data.len() {
+            match unistd::write(self.fd, &self.data[self.written..]) {
+                Ok(n) => self.written += n,
+                Err(::nix::Error::Sys(Errno::EINTR)) => {}
+                Err(::nix::Error::Sys(Errno::EAGAIN)) => return false,
+                // the destination closed the pipe, it no longer wants the contents
+                Err(_) => return true,
+            }
+        }
+        true
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        let _ = unistd::close(self.fd);
+    }
+}
+
+struct Drag {
+    contents: Vec<(String, Arc<Vec<u8>>)>,
+    state: DragState,
+    target: Option<String>,
+    action: DndAction,
+    transfers: Vec<Transfer>,
+}
+
+impl Drag {
+    fn handle(&mut self, event: Event, source: &Proxy<WlDataSource>) {
+        match event {
+            Event::Target { mime_type } => self.target = mime_type,
+            Event::Action { dnd_action } => self.action = DndAction::from_bits_truncate(dnd_action),
+            Event::Send { mime_type, fd } => {
+                let data = self
+                    .contents
+                    .iter()
+                    .find(|&&(ref offered, _)| *offered == mime_type)
+                    .map(|&(_, ref data)| data.clone());
+                let nonblocking = fcntl::fcntl(fd, fcntl::FcntlArg::F_SETFL(OFlag::O_NONBLOCK));
+                if let (Some(data), Ok(_)) = (data, nonblocking) {
+                    let mut transfer = Transfer { fd, data, written: 0 };
+                    if !transfer.write() {
+                        self.transfers.push(transfer);
+                    }
+                } else {
+                    // this type was not offered, the destination gets no data
+                    let _ = unistd::close(fd);
+                }
+            }
+            Event::DndDropPerformed => self.state = DragState::Dropped,
+            Event::DndFinished => {
+                self.state = DragState::Finished(self.action);
+                source.destroy();
+            }
+            Event::Cancelled => {
+                self.state = DragState::Cancelled;
+                // the destination will not read the rest of the contents
+                self.transfers.clear();
+                source.destroy();
+            }
+        }
+    }
+}
+
+/// The source of a drag-and-drop operation
+///
+/// See the module documentation for details.
+pub struct DragSource {
+    source: Proxy<WlDataSource>,
+    drag: Arc<Mutex<Drag>>,
+}
+
+impl DragSource {
+    /// Create a source offering contents in several MIME types
+    ///
+    /// The contents are given in order of preference, with the actions they can be
+    /// dragged with. Fails if `manager` is dead, or if a MIME type contains a nul byte.
+    pub fn new(
+        manager: &Proxy<WlDataDeviceManager>,
+        actions: DndAction,
+        contents: Vec<(String, Vec<u8>)>,
+    ) -> io::Result<DragSource> {
+        let drag = Arc::new(Mutex::new(Drag {
+            contents: contents
+                .into_iter()
+                .map(|(mime_type, data)| (mime_type, Arc::new(data)))
+                .collect(),
+            state: DragState::Dragging,
+            target: None,
+            action: DndAction::empty(),
+            transfers: Vec::new(),
+        }));
+        let implementation_drag = drag.clone();
+        let source = manager
+            .create_data_source(|newp| {
+                newp.implement(
+                    move |event, source: Proxy<WlDataSource>| {
+                        implementation_drag.lock().unwrap().handle(event, &source)
+                    },
+                    (),
+                )
+            }).map_err(|()| dead_object("wl_data_device_manager"))?;
+        for &(ref mime_type, _) in &drag.lock().unwrap().contents {
+            if let Err(err) = source.offer(mime_type.as_str()) {
+                source.destroy();
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, err));
+            }
+        }
+        if source.version() >= 3 {
+            source.set_actions(actions.to_raw());
+        }
+        Ok(DragSource { source, drag })
+    }
+
+    /// Start dragging the contents
+    ///
+    /// This must follow an implicit grab of the pointer or touch point on `origin`,
+    /// whose serial is given, like a button press. The `icon` surface, if any, is shown
+    /// under the pointer during the drag.
+    pub fn start(
+        &self,
+        device: &Proxy<WlDataDevice>,
+        origin: &Proxy<WlSurface>,
+        icon: Option<&Proxy<WlSurface>>,
+        serial: u32,
+    ) {
+        device.start_drag(Some(&self.source), origin, icon, serial);
+    }
+
+    /// The `wl_data_source` of this drag
+    pub fn source(&self) -> &Proxy<WlDataSource> {
+        &self.source
+    }
+
+    /// The current state of the drag
+    pub fn state(&self) -> DragState {
+        self.drag.lock().unwrap().state
+    }
+
+    /// The MIME type the destination under the pointer accepts, if any
+    pub fn target(&self) -> Option<String> {
+        self.drag.lock().unwrap().target.clone()
+    }
+
+    /// The action currently negotiated with the destination
+    ///
+    /// It is empty until the compositor selects one.
+    pub fn action(&self) -> DndAction {
+        self.drag.lock().unwrap().action
+    }
+
+    /// The file descriptors of the transfers that are waiting for their pipe
+    ///
+    /// They are to be polled for writing, `write_pending()` being called once one of
+    /// them is writable.
+    pub fn pending_fds(&self) -> Vec<RawFd> {
+        self.drag
+            .lock()
+            .unwrap()
+            .transfers
+            .iter()
+            .map(|transfer| transfer.fd)
+            .collect()
+    }
+
+    /// Resume the pending transfers
+    ///
+    /// The pipes are closed as soon as their contents are written. Returns whether
+    /// some transfers are still pending.
+    pub fn write_pending(&self) -> bool {
+        let mut drag = self.drag.lock().unwrap();
+        let mut i = 0;
+        while i < drag.transfers.len() {
+            if drag.transfers[i].write() {
+                drag.transfers.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        !drag.transfers.is_empty()
+    }
+
+    /// Cancel the drag
+    ///
+    /// This destroys the source: the destination no longer receives the contents, and
+    /// the pending transfers are abandoned.
+    pub fn cancel(&self) {
+        let mut drag = self.drag.lock().unwrap();
+        if drag.state == DragState::Dragging || drag.state == DragState::Dropped {
+            drag.state = DragState::Cancelled;
+            drag.transfers.clear();
+            self.source.destroy();
+        }
+    }
+}
+
+/// A policy choosing the MIME type to receive an offer in
+///
+/// It is given the MIME types of the offer, in the order the source offered them, and
+/// returns the one to receive, if any is acceptable. Besides `MimePreference`, it is
+/// implemented by the closures with the same signature.
+pub trait MimePolicy {
+    /// Choose the MIME type to receive among the offered ones
+    fn choose(&self, offered: &[String]) -> Option<String>;
+}
+
+impl<F: Fn(&[String]) -> Option<String>> MimePolicy for F {
+    fn choose(&self, offered: &[String]) -> Option<String> {
+        self(offered)
+    }
+}
+
+/// A `MimePolicy` choosing the first acceptable MIME type, in order of preference
+///
+/// The types are compared regardless of their case and of the spaces around their
+/// parameters, and a type like `image/*` accepts all the types of its category.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MimePreference {
+    types: Vec<String>,
+}
+
+impl MimePreference {
+    /// Accept these MIME types, the first ones being preferred
+    pub fn new(types: Vec<String>) -> MimePreference {
+        MimePreference {
+            types: types.iter().map(|mime_type| normalize(mime_type)).collect(),
+        }
+    }
+
+    /// Prefer UTF-8 text, falling back to the text in an unspecified encoding
+    ///
+    /// The X11 names of the text types are accepted as well, as they are offered by
+    /// XWayland clients.
+    pub fn text() -> MimePreference {
+        MimePreference::new(
+            ["text/plain;charset=utf-8", "UTF8_STRING", "text/plain", "TEXT", "STRING"]
+                .iter()
+                .map(|&mime_type| mime_type.into())
+                .collect(),
+        )
+    }
+
+    /// Prefer a list of URIs, like the files dragged out of a file manager, then text
+    pub fn uri_list() -> MimePreference {
+        let mut preference = MimePreference::text();
+        preference.types.insert(0, "text/uri-list".into());
+        preference
+    }
+
+    /// Prefer the lossless image formats, then any image
+    pub fn images() -> MimePreference {
+        MimePreference::new(
+            ["image/png", "image/webp", "image/jpeg", "image/*"]
+                .iter()
+                .map(|&mime_type| mime_type.into())
+                .collect(),
+        )
+    }
+
+    /// The accepted MIME types, in order of preference
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+}
+
+impl MimePolicy for MimePreference {
+    fn choose(&self, offered: &[String]) -> Option<String> {
+        let normalized = offered.iter().map(|mime_type| normalize(mime_type)).collect::<Vec<_>>();
+        for accepted in &self.types {
+            let position = if accepted.ends_with("/*") {
+                let category = &accepted[..accepted.len() - 1];
+                normalized.iter().position(|mime_type| mime_type.starts_with(category))
+            } else {
+                normalized.iter().position(|mime_type| mime_type == accepted)
+            };
+            if let Some(i) = position {
+                return Some(offered[i].clone());
+            }
+        }
+        None
+    }
+}
+
+// lowercase a MIME type and strip the spaces around its parameters
+fn normalize(mime_type: &str) -> String {
+    mime_type
+        .split(';')
+        .map(|part| part.trim())
+        .collect::<Vec<_>>()
+        .join(";")
+        .to_lowercase()
+}
+
+// the user data of the offers implemented by `DataOffer`
+struct OfferedTypes(Mutex<Vec<String>>);
+
+/// A `wl_data_offer`, collecting the MIME types it is offered in
+///
+/// The offers are announced by the `data_offer` event of `wl_data_device`, their MIME
+/// types following before the `selection` or `enter` event that uses them.
+#[derive(Clone)]
+pub struct DataOffer {
+    offer: Proxy<WlDataOffer>,
+}
+
+impl DataOffer {
+    /// Implement an offer given by the `data_offer` event of `wl_data_device`
+    pub fn new(offer: NewProxy<WlDataOffer>) -> DataOffer {
+        let offer = offer.implement(
+            |event, offer: Proxy<WlDataOffer>| {
+                if let wl_data_offer::Event::Offer { mime_type } = event {
+                    if let Some(types) = offer.user_data::<OfferedTypes>() {
+                        types.0.lock().unwrap().push(mime_type);
+                    }
+                }
+            },
+            OfferedTypes(Mutex::new(Vec::new())),
+        );
+        DataOffer { offer }
+    }
+
+    /// The offer behind a proxy given by the `selection` or `enter` event of `wl_data_device`
+    ///
+    /// Returns `None` if it was not implemented by `DataOffer::new()`.
+    pub fn from_proxy(offer: &Proxy<WlDataOffer>) -> Option<DataOffer> {
+        offer
+            .user_data::<OfferedTypes>()
+            .map(|_| DataOffer { offer: offer.clone() })
+    }
+
+    /// The `wl_data_offer` of this offer
+    pub fn offer(&self) -> &Proxy<WlDataOffer> {
+        &self.offer
+    }
+
+    /// The MIME types of the offer, in the order the source offered them
+    pub fn mime_types(&self) -> Vec<String> {
+        match self.offer.user_data::<OfferedTypes>() {
+            Some(types) => types.0.lock().unwrap().clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The MIME type a policy chooses to receive the offer in, if any
+    pub fn choose<P: MimePolicy + ?Sized>(&self, policy: &P) -> Option<String> {
+        policy.choose(&self.mime_types())
+    }
+
+    /// Request the contents of the offer in a MIME type
+    ///
+    /// The returned `DataReader` reads them from a pipe, once the request is flushed to
+    /// the server, and accepts at most `limit` bytes. Fails if the offer is dead, if the
+    /// pipe cannot be created, or if the MIME type contains a nul byte.
+    pub fn receive(&self, mime_type: &str, limit: usize) -> io::Result<DataReader> {
+        if !self.offer.is_alive() {
+            return Err(dead_object("wl_data_offer"));
+        }
+        let (read_fd, write_fd) = unistd::pipe2(OFlag::O_CLOEXEC).map_err(nix_error)?;
+        // closes the read end on the failures below
+        let reader = DataReader {
+            fd: read_fd,
+            contents: Vec::new(),
+            limit,
+            done: false,
+        };
+        let nonblocking = fcntl::fcntl(read_fd, fcntl::FcntlArg::F_SETFL(OFlag::O_NONBLOCK));
+        // the request carries its own copy of the write end
+        let sent = self.offer.receive(mime_type, write_fd);
+        let _ = unistd::close(write_fd);
+        nonblocking.map_err(nix_error)?;
+        sent.map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        Ok(reader)
+    }
+}
+
+/// The contents of an offer, read from a pipe
+///
+/// The reads do not block: `read_available()` reads what the source wrote so far, the
+/// file descriptor given by `fd()` being polled for reading in between, or
+/// `read_to_end()` waits for the rest of the contents up to a timeout. The pipe is
+/// closed when the reader is dropped.
+pub struct DataReader {
+    fd: RawFd,
+    contents: Vec<u8>,
+    limit: usize,
+    done: bool,
+}
+
+impl DataReader {
+    /// The file descriptor of the pipe
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// The contents read so far
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+
+    /// Read the contents available in the pipe
+    ///
+    /// Returns whether all the contents were read, the source having closed the pipe.
+    /// Fails with `ErrorKind::InvalidData` if the contents exceed the size limit.
+    pub fn read_available(&mut self) -> io::Result<bool> {
+        let mut buffer = [0u8; 4096];
+        while !self.done {
+            match unistd::read(self.fd, &mut buffer) {
+                Ok(0) => self.done = true,
+                Ok(n) => {
+                    if n > self.limit - self.contents.len() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "the offered contents exceed the size limit",
+                        ));
+                    }
+                    self.contents.extend_from_slice(&buffer[..n]);
+                }
+                Err(::nix::Error::Sys(Errno::EINTR)) => {}
+                Err(::nix::Error::Sys(Errno::EAGAIN)) => return Ok(false),
+                Err(err) => return Err(nix_error(err)),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read the rest of the contents, waiting at most `timeout` for the source
+    ///
+    /// The request must have been flushed to the server beforehand. If the source is
+    /// owned by this application, its events must be dispatched from another thread in
+    /// the meantime. Fails with `ErrorKind::TimedOut` if the source did not finish writing
+    /// in time, and with `ErrorKind::InvalidData` if the contents exceed the size limit.
+    pub fn read_to_end(mut self, timeout: Duration) -> io::Result<Vec<u8>> {
+        let deadline = Instant::now() + timeout;
+        while !self.read_available()? {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the source of the offer did not write its contents in time",
+                ));
+            }
+            // rounded up not to wake up early, `Duration::as_millis()` requires rust 1.33
+            let remaining = deadline - now;
+            let millis = remaining
+                .as_secs()
+                .saturating_mul(1000)
+                .saturating_add(u64::from((remaining.subsec_nanos() + 999_999) / 1_000_000));
+            let millis = ::std::cmp::min(millis, ::std::i32::MAX as u64) as i32;
+            match poll(&mut [PollFd::new(self.fd, EventFlags::POLLIN)], millis) {
+                Ok(_) | Err(::nix::Error::Sys(Errno::EINTR)) => {}
+                Err(err) => return Err(nix_error(err)),
+            }
+        }
+        Ok(mem::replace(&mut self.contents, Vec::new()))
+    }
+}
+
+impl Drop for DataReader {
+    fn drop(&mut self) {
+        let _ = unistd::close(self.fd);
+    }
+}
@@ -5,25 +5,120 @@
 //! This library is used to interface with the OpenGL stack, and creating
 //! EGL surfaces from a wayland surface.
 //!
+//! The `wl_egl_window` handed to the EGL implementation is a small structure, whose
+//! layout is shared by `libwayland-egl.so` and the EGL drivers. If the library can not
+//! be loaded, the EGL surfaces are created by an implementation of this structure in
+//! rust instead, so that the library is not needed at runtime.
+//!
+//! The EGL implementations access the wayland surface and display through
+//! `libwayland-client.so`, this module is thus only available with the `native_lib`
+//! feature, which is enabled by the `egl` feature.
+//!
 //! See WlEglSurface documentation for details.
 
 use protocol::wl_surface::WlSurface;
-use std::os::raw::c_void;
+use std::error::Error;
+use std::fmt;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
 use wayland_sys::client::wl_proxy;
 use wayland_sys::egl::*;
 use Proxy;
 
 /// Checks if the wayland-egl lib is available and can be used
 ///
-/// Trying to create an `WlEglSurface` while this function returns
-/// `false` will result in a panic.
+/// If this function returns `false`, the `WlEglSurface`s are created by this library's own
+/// implementation of `libwayland-egl.so`.
 pub fn is_available() -> bool {
     is_lib_available()
 }
 
+// the version of `wl_egl_window` implemented here, which is the one of libwayland-egl 1.15
+const WINDOW_VERSION: isize = 3;
+
+// the layout of `wl_egl_window`, as defined by `wayland-egl-backend.h`
+//
+// The EGL driver registers its callbacks in it, and reads the requested size from it.
+#[repr(C)]
+struct EglWindow {
+    version: isize,
+    width: c_int,
+    height: c_int,
+    dx: c_int,
+    dy: c_int,
+    attached_width: c_int,
+    attached_height: c_int,
+    driver_private: *mut c_void,
+    resize_callback: Option<unsafe extern "C" fn(*mut EglWindow, *mut c_void)>,
+    destroy_window_callback: Option<unsafe extern "C" fn(*mut c_void)>,
+    surface: *mut wl_proxy,
+}
+
+fn window_create(surface: *mut wl_proxy, width: c_int, height: c_int) -> *mut wl_egl_window {
+    if width <= 0 || height <= 0 {
+        return ptr::null_mut();
+    }
+    let window = Box::new(EglWindow {
+        version: WINDOW_VERSION,
+        width,
+        height,
+        dx: 0,
+        dy: 0,
+        attached_width: 0,
+        attached_height: 0,
+        driver_private: ptr::null_mut(),
+        resize_callback: None,
+        destroy_window_callback: None,
+        surface,
+    });
+    Box::into_raw(window) as *mut wl_egl_window
+}
+
+unsafe fn window_resize(window: *mut wl_egl_window, width: c_int, height: c_int, dx: c_int, dy: c_int) {
+    let window = window as *mut EglWindow;
+    if width <= 0 || height <= 0 {
+        return;
+    }
+    (*window).width = width;
+    (*window).height = height;
+    (*window).dx = dx;
+    (*window).dy = dy;
+    if let Some(callback) = (*window).resize_callback {
+        callback(window, (*window).driver_private);
+    }
+}
+
+unsafe fn window_get_attached_size(window: *mut wl_egl_window) -> (c_int, c_int) {
+    let window = window as *mut EglWindow;
+    ((*window).attached_width, (*window).attached_height)
+}
+
+unsafe fn window_destroy(window: *mut wl_egl_window) {
+    let window = Box::from_raw(window as *mut EglWindow);
+    if let Some(callback) = window.destroy_window_callback {
+        callback(window.driver_private);
+    }
+}
+
 unsafe impl Send for WlEglSurface {}
 unsafe impl Sync for WlEglSurface {}
 
+/// Error returned when using an EGL surface whose wayland surface was destroyed
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SurfaceDestroyed;
+
+impl fmt::Display for SurfaceDestroyed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl Error for SurfaceDestroyed {
+    fn description(&self) -> &str {
+        "the wayland surface of the EGL surface was destroyed"
+    }
+}
+
 /// EGL surface
 ///
 /// This object is a simple wrapper around a `WlSurface` to add the EGL
@@ -31,26 +126,65 @@ unsafe impl Sync for WlEglSurface {}
 /// to get the window pointer your OpenGL library is needing to initialize the
 /// EGL context (you'll most likely need the display ptr as well, that you can
 /// get via the `ptr` method of the `Proxy` trait on the `WlDisplay` object).
+///
+/// The EGL surface tracks the liveness of its wayland surface: once the latter
+/// is destroyed, `resize()` and `ptr()` return a `SurfaceDestroyed` error, as using
+/// the EGL surface would make the EGL implementation access a destroyed object.
+/// This tracking is not possible for surfaces not managed by this library (see
+/// `Proxy::is_external()`), which are always considered alive.
 pub struct WlEglSurface {
     ptr: *mut wl_egl_window,
+    surface: Proxy<WlSurface>,
+    // whether the window was created by `libwayland-egl.so`, or by `window_create()`
+    native: bool,
 }
 
 impl WlEglSurface {
     /// Create an EGL surface from a wayland surface
+    ///
+    /// A size which is not positive, like the one of a configure letting the client choose
+    /// the size of its window, is replaced by 1, as the window of an EGL surface cannot be
+    /// empty.
     pub fn new(surface: &Proxy<WlSurface>, width: i32, height: i32) -> WlEglSurface {
-        unsafe { WlEglSurface::new_from_raw(surface.c_ptr(), width, height) }
+        let (width, height) = (width.max(1), height.max(1));
+        let native = is_lib_available();
+        let ptr = if native {
+            unsafe {
+                ffi_dispatch!(
+                    WAYLAND_EGL_HANDLE,
+                    wl_egl_window_create,
+                    surface.c_ptr(),
+                    width,
+                    height
+                )
+            }
+        } else {
+            window_create(surface.c_ptr(), width, height)
+        };
+        WlEglSurface {
+            ptr,
+            surface: surface.clone(),
+            native,
+        }
     }
 
     /// Create an EGL surface from a raw pointer to a wayland surface
     ///
     /// This function is unsafe because `surface` must be a valid wl_surface pointer
     pub unsafe fn new_from_raw(surface: *mut wl_proxy, width: i32, height: i32) -> WlEglSurface {
-        let ptr = ffi_dispatch!(WAYLAND_EGL_HANDLE, wl_egl_window_create, surface, width, height);
-        WlEglSurface { ptr: ptr }
+        WlEglSurface::new(&Proxy::from_c_ptr(surface), width, height)
+    }
+
+    /// Check whether the wayland surface of this EGL surface is still alive
+    pub fn is_alive(&self) -> bool {
+        self.surface.is_alive()
     }
 
     /// Fetch current size of the EGL surface
     pub fn get_size(&self) -> (i32, i32) {
+        if !self.native {
+            return unsafe { window_get_attached_size(self.ptr) };
+        }
         let mut w = 0i32;
         let mut h = 0i32;
         unsafe {
@@ -71,7 +205,17 @@ impl WlEglSurface {
     /// the surface, the two others `(dx, dy)` represent the displacement
     /// of the top-left corner of the surface. It allows you to control the
     /// direction of the resizing if necessary.
-    pub fn resize(&self, width: i32, height: i32, dx: i32, dy: i32) {
+    ///
+    /// The new size is ignored if it is not positive. Fails if the wayland surface was
+    /// destroyed.
+    pub fn resize(&self, width: i32, height: i32, dx: i32, dy: i32) -> Result<(), SurfaceDestroyed> {
+        if !self.is_alive() {
+            return Err(SurfaceDestroyed);
+        }
+        if !self.native {
+            unsafe { window_resize(self.ptr, width, height, dx, dy) };
+            return Ok(());
+        }
         unsafe {
             ffi_dispatch!(
                 WAYLAND_EGL_HANDLE,
@@ -83,21 +227,31 @@ impl WlEglSurface {
                 dy
             )
         }
+        Ok(())
     }
 
     /// Raw pointer to the EGL surface
     ///
     /// You'll need this pointer to initialize the EGL context in your
     /// favourite OpenGL lib.
-    pub fn ptr(&self) -> *const c_void {
-        self.ptr as *const c_void
+    ///
+    /// Fails if the wayland surface was destroyed.
+    pub fn ptr(&self) -> Result<*const c_void, SurfaceDestroyed> {
+        if !self.is_alive() {
+            return Err(SurfaceDestroyed);
+        }
+        Ok(self.ptr as *const c_void)
     }
 }
 
 impl Drop for WlEglSurface {
     fn drop(&mut self) {
         unsafe {
-            ffi_dispatch!(WAYLAND_EGL_HANDLE, wl_egl_window_destroy, self.ptr);
+            if self.native {
+                ffi_dispatch!(WAYLAND_EGL_HANDLE, wl_egl_window_destroy, self.ptr);
+            } else {
+                window_destroy(self.ptr);
+            }
         }
     }
 }
@@ -1,7 +1,48 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "async")]
+use std::future::Future;
 use std::io;
-use std::rc::Rc;
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
+use clock::SharedClock;
+use dedup::{self, Dedup};
+use display::FlushState;
 use imp::EventQueueInner;
+use latency::{self, EventTiming, LatencyHook};
+use routing::{self, Claims, GlobalFilter};
+use {Interface, MessageGroup, Proxy, ProxyMap};
+
+/// A claim of the events of an interface, see `EventQueue::register()`
+///
+/// The claim is released when it is dropped.
+pub struct Registration {
+    claims: Weak<Claims>,
+    interface: &'static str,
+    id: usize,
+}
+
+impl Registration {
+    /// The name of the claimed interface
+    pub fn interface(&self) -> &'static str {
+        self.interface
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(claims) = self.claims.upgrade() {
+            routing::release_claim(&claims, self.interface, self.id);
+        }
+    }
+}
 
 /// An event queue for protocol messages
 ///
@@ -37,9 +78,20 @@ use imp::EventQueueInner;
 /// of event into the event loop of your application.
 pub struct EventQueue {
     // EventQueue is *not* Send
-    pub(crate) inner: Rc<EventQueueInner>,
+    pub(crate) inner: Arc<EventQueueInner>,
+    pub(crate) latency: Rc<LatencyHook>,
+    pub(crate) claims: Rc<Claims>,
+    dedup: Rc<Dedup>,
+    injected: RefCell<VecDeque<InjectedEvent>>,
+    pub(crate) flush: Arc<FlushState>,
+    pub(crate) clock: SharedClock,
+    pub(crate) filter: Arc<GlobalFilter>,
+    wakeup: RefCell<Option<WakeupHandle>>,
 }
 
+// the dispatching of an event given to `EventQueue::inject_event()`
+type InjectedEvent = Box<FnMut(&EventQueueInner) -> io::Result<()>>;
+
 /// A token representing this event queue
 ///
 /// This token can be cloned and is meant to allow easier
@@ -47,13 +99,133 @@ pub struct EventQueue {
 /// require the specification of an event queue, like
 /// `Proxy::make_wrapper` and `NewProxy::implement_nonsend`.
 pub struct QueueToken {
-    pub(crate) inner: Rc<EventQueueInner>,
+    pub(crate) inner: Arc<EventQueueInner>,
+    // QueueToken is *not* Send, as it allows non-Send implementations
+    _not_send: ::std::marker::PhantomData<*const ()>,
+}
+
+/// A thread-safe handle to this event queue
+///
+/// Unlike `QueueToken`, this handle is `Send` and `Sync`, and is cheap to clone. It can
+/// be given to other threads to implement new proxies on this event queue with
+/// `NewProxy::implement_on`, for example to let worker threads create buffers or frame
+/// callbacks whose events are then dispatched by the thread of the queue.
+#[derive(Clone)]
+pub struct QueueHandle {
+    pub(crate) inner: Arc<EventQueueInner>,
+}
+
+// The handle only gives access to the operations of the queue which assign proxies
+// to it, which are thread-safe in both backends.
+unsafe impl Send for QueueHandle {}
+unsafe impl Sync for QueueHandle {}
+
+/// A handle to interrupt the blocking dispatching of an event queue
+///
+/// This handle is `Send` and `Sync`, and can be cloned. See `EventQueue::wakeup_handle()`.
+#[derive(Clone)]
+pub struct WakeupHandle {
+    fd: Arc<WakeupFd>,
+}
+
+// the file descriptors signaled by the wakeup handles of a queue: an eventfd where it is
+// available, the two ends of a pipe otherwise
+struct WakeupFd {
+    read: RawFd,
+    write: RawFd,
+}
+
+impl Drop for WakeupFd {
+    fn drop(&mut self) {
+        let _ = ::nix::unistd::close(self.read);
+        if self.write != self.read {
+            let _ = ::nix::unistd::close(self.write);
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn wakeup_fd() -> ::nix::Result<WakeupFd> {
+    use nix::sys::eventfd::{eventfd, EfdFlags};
+    let fd = eventfd(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+    Ok(WakeupFd { read: fd, write: fd })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn wakeup_fd() -> ::nix::Result<WakeupFd> {
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+    let (read, write) = ::nix::unistd::pipe()?;
+    let fds = WakeupFd { read, write };
+    for &fd in &[read, write] {
+        fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        fcntl(fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+    }
+    Ok(fds)
+}
+
+impl WakeupHandle {
+    fn new() -> io::Result<WakeupHandle> {
+        match wakeup_fd() {
+            Ok(fd) => Ok(WakeupHandle { fd: Arc::new(fd) }),
+            Err(::nix::Error::Sys(errno)) => Err(errno.into()),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Interrupt the dispatching of the event queue
+    ///
+    /// If the event queue is blocked in `dispatch()` or `dispatch_timeout()`, they return
+    /// `Ok(0)` without waiting for events any longer. Otherwise the next of these calls
+    /// returns immediately: the wakeup is not lost if the queue was busy when it was sent.
+    /// Several wakeups sent before the queue is dispatched only interrupt it once.
+    pub fn wakeup(&self) -> io::Result<()> {
+        // the counter of an eventfd is a native-endian u64, a pipe takes any byte
+        let one: [u8; 8] = if cfg!(target_endian = "little") {
+            [1, 0, 0, 0, 0, 0, 0, 0]
+        } else {
+            [0, 0, 0, 0, 0, 0, 0, 1]
+        };
+        match ::nix::unistd::write(self.fd.write, &one) {
+            // the counter or the pipe is full, the queue will wake up anyway
+            Ok(_) | Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => Ok(()),
+            Err(::nix::Error::Sys(errno)) => Err(errno.into()),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    // reset the counter or empty the pipe, returns whether a wakeup was pending
+    fn take(&self) -> io::Result<bool> {
+        let mut buf = [0u8; 64];
+        let mut pending = false;
+        loop {
+            match ::nix::unistd::read(self.fd.read, &mut buf) {
+                Ok(0) => return Ok(pending),
+                Ok(_) => pending = true,
+                Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => return Ok(pending),
+                Err(::nix::Error::Sys(errno)) => return Err(errno.into()),
+                Err(_) => unreachable!(),
+            }
+        }
+    }
 }
 
 impl EventQueue {
-    pub(crate) fn new(inner: EventQueueInner) -> EventQueue {
+    pub(crate) fn new(
+        inner: EventQueueInner,
+        flush: Arc<FlushState>,
+        clock: SharedClock,
+        filter: Arc<GlobalFilter>,
+    ) -> EventQueue {
         EventQueue {
-            inner: Rc::new(inner),
+            inner: Arc::new(inner),
+            latency: Rc::new(RefCell::new(None)),
+            claims: Rc::new(RefCell::new(HashMap::new())),
+            dedup: Rc::new(RefCell::new(HashMap::new())),
+            injected: RefCell::new(VecDeque::new()),
+            flush,
+            clock,
+            filter,
+            wakeup: RefCell::new(None),
         }
     }
     /// Dispatches events from the internal buffer.
@@ -64,10 +236,92 @@ impl EventQueue {
     /// This process can insert events in the internal buffers of
     /// other event queues.
     ///
+    /// If a `WakeupHandle` of this queue was created, its `wakeup()` interrupts the
+    /// waiting, and this method then returns `Ok(0)`.
+    ///
     /// If an error is returned, your connection with the wayland
     /// compositor is probably lost.
     pub fn dispatch(&mut self) -> io::Result<u32> {
-        self.inner.dispatch()
+        let injected = self.dispatch_injected()?;
+        if injected > 0 {
+            // don't block if we already dispatched something
+            return self.dispatch_pending().map(|n| n + injected);
+        }
+        if self.wakeup.borrow().is_some() {
+            // the dispatching of the backends cannot be interrupted
+            return self.dispatch_until(None);
+        }
+        let inner = &self.inner;
+        let ret = self.with_dispatch_state(|| inner.dispatch());
+        self.flush_after_dispatch();
+        ret
+    }
+
+    /// Dispatches events from the internal buffer, waiting for them at most for a duration
+    ///
+    /// Like `dispatch()`, this dispatches the pending events if there are some, and
+    /// otherwise reads events from the server and dispatches them. But if no event for
+    /// this queue arrives before `timeout` elapsed, it returns `Ok(0)`, so that the
+    /// connection can be served along with the frame deadlines of a game loop or a UI
+    /// without writing your own poll loop.
+    ///
+    /// The events read for other event queues are queued for them, and do not stop the
+    /// waiting. A `WakeupHandle` of this queue does.
+    ///
+    /// If an error is returned, your connection with the wayland
+    /// compositor is probably lost.
+    pub fn dispatch_timeout(&mut self, timeout: Duration) -> io::Result<u32> {
+        self.dispatch_until(checked_deadline(timeout))
+    }
+
+    // dispatch events, waiting for them until the deadline if any, or until woken up
+    fn dispatch_until(&mut self, deadline: Option<Instant>) -> io::Result<u32> {
+        let wakeup = self.wakeup.borrow().clone();
+        loop {
+            let dispatched = self.dispatch_pending()?;
+            if dispatched > 0 {
+                return Ok(dispatched);
+            }
+            match self.inner.flush() {
+                Ok(()) => {}
+                // the remaining requests will be sent by the next flush, and the protocol
+                // error explaining a broken pipe still needs to be read
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::BrokenPipe => {}
+                Err(e) => return Err(e),
+            }
+            // if events were queued in the meantime, the read intention is refused and they
+            // are dispatched by the next iteration
+            if let Some(guard) = self.prepare_read() {
+                let now = Instant::now();
+                let remaining = deadline.map(|deadline| {
+                    if deadline > now {
+                        deadline - now
+                    } else {
+                        Duration::from_secs(0)
+                    }
+                });
+                let wakeup_fd = wakeup.as_ref().map(|wakeup| wakeup.fd.read);
+                // dropping the guard cancels the read intention
+                match poll_connection(self.inner.get_connection_fd(), wakeup_fd, remaining) {
+                    Ok(Wake::Readable) => read_available(guard)?,
+                    // poll() waits for 24 days at most, the deadline may still be ahead
+                    Ok(Wake::Timeout) => {
+                        if deadline.map(|deadline| Instant::now() >= deadline).unwrap_or(true) {
+                            return Ok(0);
+                        }
+                    }
+                    Ok(Wake::Wakeup) => {}
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if let Some(ref wakeup) = wakeup {
+                if wakeup.take()? {
+                    return Ok(0);
+                }
+            }
+        }
     }
 
     /// Dispatches pending events from the internal buffer.
@@ -79,7 +333,105 @@ impl EventQueue {
     /// If an error is returned, your connection with the wayland
     /// compositor is probably lost.
     pub fn dispatch_pending(&mut self) -> io::Result<u32> {
-        self.inner.dispatch_pending()
+        let injected = self.dispatch_injected()?;
+        let inner = &self.inner;
+        let ret = self.with_dispatch_state(|| inner.dispatch_pending()).map(|n| n + injected);
+        self.flush_after_dispatch();
+        ret
+    }
+
+    /// Dispatches events like `dispatch()`, giving a state to the implementations
+    ///
+    /// The proxies implemented with `NewProxy::implement_with_state()` are given `data` along
+    /// with their events. See the crate-level documentation for details.
+    pub fn dispatch_with_state<Data: 'static>(&mut self, data: &mut Data) -> io::Result<u32> {
+        ::dispatch_state::with_state(data, || self.dispatch())
+    }
+
+    /// Dispatches pending events like `dispatch_pending()`, giving a state to the implementations
+    ///
+    /// The proxies implemented with `NewProxy::implement_with_state()` are given `data` along
+    /// with their events. See the crate-level documentation for details.
+    pub fn dispatch_pending_with_state<Data: 'static>(&mut self, data: &mut Data) -> io::Result<u32> {
+        ::dispatch_state::with_state(data, || self.dispatch_pending())
+    }
+
+    // run a dispatching closure with the latency hook, the claims and event filters of this
+    // queue and the global filter of its connection active
+    fn with_dispatch_state<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        routing::with_claims(&self.claims, &self.filter, || {
+            dedup::with_dedup(&self.dedup, || latency::with_hook(&self.latency, &self.clock, f))
+        })
+    }
+
+    // send the requests of the implementations, if the flush policy requires it
+    fn flush_after_dispatch(&self) {
+        if self.flush.flush_on_dispatch() {
+            // errors will be reported by the next flush or dispatch
+            let _ = self.inner.flush();
+        }
+    }
+
+    /// Inject a synthetic event in this event queue
+    ///
+    /// The event will be delivered to the implementation of `proxy` by the next call to
+    /// `dispatch()` or `dispatch_pending()`, as if it had been sent by the server, before
+    /// the events actually received from it. The proxy must be attached to this event queue.
+    ///
+    /// This is meant for testing the event handling of your application without a wayland
+    /// server: as requests are simply buffered until the connection is flushed, you can
+    /// create your objects on a connection nobody listens to (for example one end of a
+    /// `UnixStream::pair()` given to `Display::from_fd()`) and inject the events they
+    /// would receive.
+    ///
+    /// An error is returned if the proxy is dead or attached to an other event queue, as its
+    /// implementation may not be callable from the thread dispatching this one. Injecting
+    /// events creating new objects is not supported, nor injecting events to objects which
+    /// are not implemented by this library or moved to an other queue before the dispatching,
+    /// the dispatching then returns an error.
+    pub fn inject_event<I: Interface>(&self, proxy: &Proxy<I>, event: I::Event) -> Result<(), ()>
+    where
+        I::Event: MessageGroup<Map = ProxyMap>,
+    {
+        if !proxy.inner.is_attached_to(&self.inner) {
+            return Err(());
+        }
+        let inner = proxy.inner.clone();
+        let mut event = Some(event);
+        self.injected.borrow_mut().push_back(Box::new(move |queue| {
+            let event = event.take().unwrap();
+            if !inner.is_attached_to(queue) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("An injected event for {} targets an other event queue.", I::NAME),
+                ));
+            }
+            let event_name = I::Event::MESSAGES[event.opcode() as usize].name;
+            let ret = latency::track(I::NAME, event_name, None, || inner.dispatch_injected::<I>(event));
+            ret.map_err(|()| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Dispatch of an injected event for {} errored.", I::NAME),
+                )
+            })
+        }));
+        Ok(())
+    }
+
+    fn dispatch_injected(&self) -> io::Result<u32> {
+        let mut count = 0;
+        loop {
+            // don't keep the borrow, the implementations may inject other events
+            let next = self.injected.borrow_mut().pop_front();
+            match next {
+                Some(mut dispatch) => {
+                    let inner = &self.inner;
+                    self.with_dispatch_state(|| dispatch(inner))?;
+                    count += 1;
+                }
+                None => return Ok(count),
+            }
+        }
     }
 
     /// Synchronous roundtrip
@@ -92,7 +444,228 @@ impl EventQueue {
     ///
     /// On success returns the number of dispatched events.
     pub fn sync_roundtrip(&mut self) -> io::Result<u32> {
-        self.inner.sync_roundtrip()
+        let inner = &self.inner;
+        self.with_dispatch_state(|| inner.sync_roundtrip())
+    }
+
+    /// Set the input latency hook of this event queue
+    ///
+    /// Once set, the input events dispatched by this queue are timestamped, and the hook
+    /// is invoked with their timing information after each of them has been processed
+    /// by its implementation. Providing `None` removes the hook and disables the
+    /// instrumentation.
+    ///
+    /// See the `latency` module for details.
+    pub fn set_latency_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(&EventTiming) + 'static,
+    {
+        self.inner.set_latency_hook(hook.is_some());
+        *self.latency.borrow_mut() = hook.map(|h| Box::new(h) as Box<_>);
+    }
+
+    /// Claim the events of all the objects of an interface
+    ///
+    /// Once an interface is claimed, the events of the objects of this interface dispatched
+    /// by this queue are given to `implementation` instead of the implementations of their
+    /// proxies. This allows a library to handle for example all the `wl_seat` and
+    /// `wl_pointer` objects of an application, whatever the code which created and
+    /// implemented them. These objects still need to be implemented as usual for their
+    /// events to be dispatched. Claiming an interface again replaces the previous
+    /// implementation.
+    ///
+    /// The objects handed over to foreign code with the `bindings` module are not affected,
+    /// nor the events dispatched while `implementation` runs from a nested dispatching of
+    /// this queue: they are given to the implementations of their proxies.
+    pub fn claim_interface<I, F>(&mut self, implementation: F)
+    where
+        I: Interface,
+        F: FnMut(I::Event, Proxy<I>) + 'static,
+    {
+        routing::claim::<I, F>(&self.claims, implementation);
+    }
+
+    /// Stop claiming the events of an interface
+    ///
+    /// The events of its objects are given back to the implementations of their proxies.
+    /// Returns whether the interface was claimed.
+    pub fn release_interface<I: Interface>(&mut self) -> bool {
+        routing::release::<I>(&self.claims)
+    }
+
+    /// Claim the events of all the objects of an interface, for the lifetime of a guard
+    ///
+    /// This is `claim_interface()`, except that the claim is released when the returned
+    /// `Registration` is dropped. A subsystem of an application can keep it alongside its own
+    /// state: once torn down, the events of the objects it claimed go back to the
+    /// implementations of their proxies, rather than to an implementation referring to a
+    /// state that no longer exists.
+    ///
+    /// If the interface is claimed again or released in the meantime, dropping the
+    /// `Registration` has no effect.
+    pub fn register<I, F>(&mut self, implementation: F) -> Registration
+    where
+        I: Interface,
+        F: FnMut(I::Event, Proxy<I>) + 'static,
+    {
+        let id = routing::claim::<I, F>(&self.claims, implementation);
+        Registration {
+            claims: Rc::downgrade(&self.claims),
+            interface: I::NAME,
+            id,
+        }
+    }
+
+    /// Drop the repeated events of the objects of an interface
+    ///
+    /// Some compositors send the whole state of an object again when a part of it changes, like
+    /// all the properties of every `wl_output` followed by their `done` event when a single
+    /// output is reconfigured. Once an interface is filtered, the events of its objects
+    /// dispatched by this queue are dropped if they have the same arguments as the previous event
+    /// with the same opcode of the same object. The `barrier` event, which ends a group of
+    /// changes (like `done`), is dropped if all the events since the previous one were dropped.
+    ///
+    /// This is only meant for the interfaces whose events each replace the value set by their
+    /// previous occurrence: an event describing one of several items, like the `mode` events of
+    /// the first versions of `wl_output`, may be dropped while it is still relevant. The object
+    /// arguments are compared by id. The destructor events, the events carrying file descriptors
+    /// or creating objects, and the events given to `inject_event()` are always delivered.
+    ///
+    /// **Panics** if `barrier` is not an event of the interface.
+    pub fn dedup_events<I: Interface>(&mut self, barrier: &str) {
+        dedup::set::<I>(&self.dedup, barrier);
+    }
+
+    /// Stop dropping the repeated events of an interface
+    ///
+    /// Returns whether the events of the interface were filtered.
+    pub fn stop_dedup_events<I: Interface>(&mut self) -> bool {
+        dedup::unset::<I>(&self.dedup)
+    }
+
+    /// Dispatch the events of `Send` implementations from a pool of threads
+    ///
+    /// Once set, the events of the proxies implemented with a `Send` implementation (using
+    /// `NewProxy::implement()` or `NewProxy::implement_on()`) are dispatched from a pool of
+    /// `threads` threads, while the events of the other proxies are still dispatched from
+    /// the thread dispatching this queue. The dispatching methods only return once all
+    /// the events were processed.
+    ///
+    /// The events of a given proxy are always processed in order, but the events of
+    /// different proxies are processed concurrently. An event creating a new object is
+    /// processed after all the previous events, and before the following ones, so that
+    /// the new object is implemented before its events are dispatched.
+    ///
+    /// The events dispatched from the pool are not reported to the latency hook of this
+    /// queue. Setting `threads` to 0 stops the pool and restores the sequential dispatching,
+    /// which is the default.
+    ///
+    /// This is only supported by the rust implementation: with the `native_lib` cargo
+    /// feature, the events are always dispatched sequentially.
+    pub fn set_dispatch_threads(&mut self, threads: usize) {
+        self.inner.set_dispatch_threads(threads);
+    }
+
+    /// Number of events received from the server this queue dispatched so far
+    ///
+    /// The events given to `inject_event()` are not counted.
+    pub fn dispatched_events(&self) -> usize {
+        self.inner.dispatched_events()
+    }
+
+    /// Number of events waiting to be dispatched by this queue
+    ///
+    /// This is only supported by the rust implementation: with the `native_lib` cargo feature,
+    /// `libwayland-client.so` does not expose the length of its queues and this returns `None`.
+    pub fn pending_events(&self) -> Option<usize> {
+        self.inner.pending_events()
+    }
+
+    /// Get a handle to interrupt the blocking dispatching of this queue
+    ///
+    /// The `wakeup()` method of the returned handle makes `dispatch()` and
+    /// `dispatch_timeout()` return `Ok(0)` without waiting for events any longer. This lets
+    /// an other thread, handling timers or IPC for example, get the thread of the queue to
+    /// do something else than dispatching events, like rendering a new frame.
+    ///
+    /// All the handles of a queue share a single eventfd (a pipe on the platforms without
+    /// eventfd), created by the first call to this method. Once it exists, `dispatch()` waits
+    /// for events with `poll()` rather than with the blocking dispatching of the backend.
+    ///
+    /// Returns an error if the eventfd could not be created.
+    pub fn wakeup_handle(&self) -> io::Result<WakeupHandle> {
+        let mut wakeup = self.wakeup.borrow_mut();
+        if wakeup.is_none() {
+            *wakeup = Some(WakeupHandle::new()?);
+        }
+        Ok(wakeup.as_ref().unwrap().clone())
+    }
+
+    /// Get the file descriptor of the connection of this event queue
+    ///
+    /// See `EventQueueFd` for its use.
+    pub fn connection_fd(&self) -> EventQueueFd {
+        EventQueueFd {
+            fd: self.inner.get_connection_fd(),
+        }
+    }
+
+    /// Dispatch events asynchronously
+    ///
+    /// The returned future dispatches the events of this queue, reading them from the
+    /// connection if none are pending, and resolves to the number of dispatched events
+    /// once at least one was dispatched. The requests are flushed before waiting for
+    /// events.
+    ///
+    /// `readable` is invoked each time the future needs to wait for the connection to
+    /// become readable, and must return a future resolving once it is. This makes this
+    /// method usable with any reactor. For example with `smol` or `async-std`, wrapping
+    /// the `EventQueueFd` of the queue in the `Async` of `async-io`:
+    ///
+    /// ```ignore
+    /// let (display, mut event_queue) = Display::connect_to_env().unwrap();
+    /// let fd = smol::Async::new(event_queue.connection_fd()).unwrap();
+    /// smol::block_on(async {
+    ///     loop {
+    ///         event_queue.dispatch_async(|| fd.readable()).await.unwrap();
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// `readable` is only invoked once the connection has been found to have nothing left
+    /// to read. Reactors caching the readiness of the file descriptors, like the `AsyncFd`
+    /// of `tokio`, can thus clear it before the future resolves:
+    ///
+    /// ```ignore
+    /// let fd = tokio::io::unix::AsyncFd::new(event_queue.connection_fd()).unwrap();
+    /// let fd = &fd;
+    /// event_queue
+    ///     .dispatch_async(move || async move {
+    ///         fd.readable().await?.clear_ready();
+    ///         Ok(())
+    ///     }).await
+    ///     .unwrap();
+    /// ```
+    ///
+    /// The connection is always read and written without blocking, whatever the mode of its
+    /// file descriptor, so that no thread is needed to drive it.
+    ///
+    /// The future holds a read intention (see `prepare_read()`) while waiting. If it is
+    /// dropped before completing, this read intention is cancelled, so that the other
+    /// threads reading the connection are not blocked.
+    ///
+    /// This method requires the `async` cargo feature, and rust 1.36.
+    #[cfg(feature = "async")]
+    pub fn dispatch_async<R, Fut>(&mut self, readable: R) -> DispatchFuture<'_, R, Fut>
+    where
+        R: FnMut() -> Fut,
+        Fut: Future<Output = io::Result<()>>,
+    {
+        DispatchFuture {
+            queue: self,
+            readable,
+            waiting: None,
+        }
     }
 
     /// Create a new token associated with this event queue
@@ -101,6 +674,16 @@ impl EventQueue {
     pub fn get_token(&self) -> QueueToken {
         QueueToken {
             inner: self.inner.clone(),
+            _not_send: ::std::marker::PhantomData,
+        }
+    }
+
+    /// Create a new thread-safe handle associated with this event queue
+    ///
+    /// See `QueueHandle` documentation for its use.
+    pub fn get_handle(&self) -> QueueHandle {
+        QueueHandle {
+            inner: self.inner.clone(),
         }
     }
 
@@ -108,7 +691,8 @@ impl EventQueue {
     ///
     /// Will declare your intention to read events from the server socket.
     ///
-    /// Will return `None` if there are still some events awaiting dispatch on this EventIterator.
+    /// Will return `None` if there are still some events awaiting dispatch on this EventIterator,
+    /// including injected events.
     /// In this case, you need to call `dispatch_pending()` before calling this method again.
     ///
     /// As long as the returned guard is in scope, no events can be dispatched to any event iterator.
@@ -124,9 +708,13 @@ impl EventQueue {
     /// This call will otherwise not block on the server socket if it is empty, and return
     /// an io error `WouldBlock` in such cases.
     pub fn prepare_read(&self) -> Option<ReadEventsGuard> {
+        if !self.injected.borrow().is_empty() {
+            return None;
+        }
         match self.inner.prepare_read() {
             Ok(()) => Some(ReadEventsGuard {
                 inner: self.inner.clone(),
+                flush: self.flush.clone(),
                 done: false,
             }),
             Err(()) => None,
@@ -134,11 +722,175 @@ impl EventQueue {
     }
 }
 
+/// The file descriptor of the connection of an event queue
+///
+/// This handle can be registered in a reactor to know when there are events to read,
+/// for example by wrapping it in the `Async` type of `async-io` and `smol`, and used
+/// along with `EventQueue::dispatch_async()`.
+///
+/// It does not own the file descriptor, which stays open as long as the connection
+/// lives. Reactors can set it non-blocking, which both backends support.
+#[derive(Copy, Clone, Debug)]
+pub struct EventQueueFd {
+    fd: RawFd,
+}
+
+impl AsRawFd for EventQueueFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+/// A future dispatching the events of an event queue
+///
+/// See `EventQueue::dispatch_async()` for details.
+#[cfg(feature = "async")]
+pub struct DispatchFuture<'a, R, Fut> {
+    queue: &'a mut EventQueue,
+    readable: R,
+    // the read intention and the readiness future while waiting for events
+    waiting: Option<(ReadEventsGuard, Pin<Box<Fut>>)>,
+}
+
+// the readiness future is the only part polled in place, and it is boxed
+#[cfg(feature = "async")]
+impl<'a, R, Fut> Unpin for DispatchFuture<'a, R, Fut> {}
+
+#[cfg(feature = "async")]
+impl<'a, R, Fut> Future for DispatchFuture<'a, R, Fut>
+where
+    R: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<()>>,
+{
+    type Output = io::Result<u32>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u32>> {
+        let me = &mut *self;
+        loop {
+            if let Some((guard, mut readable)) = me.waiting.take() {
+                match readable.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        me.waiting = Some((guard, readable));
+                        return Poll::Pending;
+                    }
+                    // dropping the guard cancels the read intention
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Ready(Ok(())) => {
+                        if let Err(e) = read_available(guard) {
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                }
+            }
+            match me.queue.dispatch_pending() {
+                Ok(0) => {}
+                Ok(n) => return Poll::Ready(Ok(n)),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+            match me.queue.inner.flush() {
+                Ok(()) => {}
+                // the remaining requests will be sent by the next flush, and the protocol
+                // error explaining a broken pipe still needs to be read
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::BrokenPipe => {}
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+            // if events were queued in the meantime, the read intention is refused and they
+            // are dispatched by the next iteration
+            if let Some(guard) = me.queue.prepare_read() {
+                // the reactor is only waited on once the connection has nothing left to read,
+                // so that reactors caching an edge-triggered readiness never miss data
+                match has_data(me.queue.inner.get_connection_fd()) {
+                    Ok(true) => {
+                        if let Err(e) = read_available(guard) {
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                    Ok(false) => me.waiting = Some((guard, Box::pin((me.readable)()))),
+                    Err(e) => return Poll::Ready(Err(e)),
+                }
+            }
+        }
+    }
+}
+
+fn read_available(guard: ReadEventsGuard) -> io::Result<()> {
+    match guard.read_events() {
+        Ok(_) => Ok(()),
+        // an other thread read the events first
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// whether there is something to read from the connection, without blocking
+#[cfg(any(feature = "async", feature = "eventloop"))]
+pub(crate) fn has_data(fd: RawFd) -> io::Result<bool> {
+    match poll_connection(fd, None, Some(Duration::from_secs(0))) {
+        Ok(wake) => Ok(wake == Wake::Readable),
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// the instant a timeout elapses, if it can be represented
+//
+// `Instant::checked_add()` is not available on our minimal rust version, the timeouts of more
+// than a century are treated as infinite instead of overflowing the `Instant`.
+fn checked_deadline(timeout: Duration) -> Option<Instant> {
+    const MAX_TIMEOUT_SECS: u64 = 100 * 365 * 24 * 3600;
+    if timeout.as_secs() < MAX_TIMEOUT_SECS {
+        Some(Instant::now() + timeout)
+    } else {
+        None
+    }
+}
+
+// what ended the waiting for the connection
+#[derive(PartialEq)]
+enum Wake {
+    Readable,
+    Wakeup,
+    Timeout,
+}
+
+// wait until there is something to read from the connection, the wakeup fd is signaled,
+// or the timeout elapsed
+fn poll_connection(fd: RawFd, wakeup: Option<RawFd>, timeout: Option<Duration>) -> io::Result<Wake> {
+    use nix::poll::{poll, EventFlags, PollFd};
+    let millis = match timeout {
+        Some(timeout) => {
+            // round up, not to wake up right before the deadline
+            let nanos = timeout.subsec_nanos() + 999_999;
+            let millis = timeout
+                .as_secs()
+                .saturating_mul(1000)
+                .saturating_add(u64::from(nanos / 1_000_000));
+            ::std::cmp::min(millis, 0x7FFF_FFFF) as i32
+        }
+        None => -1,
+    };
+    let mut fds = [
+        PollFd::new(fd, EventFlags::POLLIN),
+        PollFd::new(wakeup.unwrap_or(-1), EventFlags::POLLIN),
+    ];
+    let count = if wakeup.is_some() { 2 } else { 1 };
+    match poll(&mut fds[..count], millis) {
+        Ok(0) => Ok(Wake::Timeout),
+        // an error or a hangup of the connection is reported by reading it
+        Ok(_) if fds[0].revents().map(|flags| !flags.is_empty()).unwrap_or(false) => Ok(Wake::Readable),
+        Ok(_) => Ok(Wake::Wakeup),
+        Err(::nix::Error::Sys(errno)) => Err(errno.into()),
+        Err(_) => unreachable!(),
+    }
+}
+
 /// A guard over a read intention.
 ///
 /// See `EventQueue::prepare_read()` for details about its use.
 pub struct ReadEventsGuard {
-    inner: Rc<EventQueueInner>,
+    inner: Arc<EventQueueInner>,
+    flush: Arc<FlushState>,
     done: bool,
 }
 
@@ -149,6 +901,10 @@ impl ReadEventsGuard {
     /// until they are all consumed or destroyed.
     pub fn read_events(mut self) -> io::Result<i32> {
         self.done = true;
+        if self.flush.flush_on_dispatch() {
+            // errors will be reported by the reading
+            let _ = self.inner.flush();
+        }
         self.inner.read_events()
     }
 
@@ -200,6 +200,34 @@ impl GlobalManager {
         Err(GlobalError::Missing)
     }
 
+    /// Instanciate a global with the highest version in a range
+    ///
+    /// This binds the highest version supported by both the server and the client,
+    /// which can use any version between `min` and `max`. Fails with
+    /// `GlobalError::VersionTooLow` if the server does not support `min`.
+    pub fn instantiate_range<I: Interface, F>(
+        &self,
+        min: u32,
+        max: u32,
+        implementor: F,
+    ) -> Result<Proxy<I>, GlobalError>
+    where
+        F: FnOnce(NewProxy<I>) -> Proxy<I>,
+    {
+        let inner = self.inner.lock().unwrap();
+        for &(id, ref interface, server_version) in &inner.list {
+            if interface == I::NAME {
+                if min > server_version {
+                    return Err(GlobalError::VersionTooLow(server_version));
+                } else {
+                    let version = ::std::cmp::min(max, server_version);
+                    return Ok(self.registry.bind(version, id, implementor).unwrap());
+                }
+            }
+        }
+        Err(GlobalError::Missing)
+    }
+
     /// Retrieve the list of currently known globals
     pub fn list(&self) -> Vec<(u32, String, u32)> {
         self.inner.lock().unwrap().list.clone()
@@ -0,0 +1,160 @@
+//! Detection of unresponsive compositors
+//!
+//! A compositor which freezes does not close its connections: the requests of its clients
+//! are simply never answered, and their windows are not redrawn anymore. A `Keepalive`
+//! lets a client notice it, to warn the user or fall back to an other display server.
+//!
+//! The `Keepalive` periodically issues a `wl_display.sync` request on an event queue of
+//! its own, so that the roundtrip does not interfere with the dispatching of the other
+//! queues, and measures the time the compositor takes to answer it. Its `poll()` method
+//! never blocks, and is meant to be called regularly from the event loop of the client,
+//! like from a timer: the latency it reports is measured at the moment the answer is
+//! dispatched, its resolution is the period of the calls to `poll()`.
+//!
+//! The instants are given by the clock of the `Display`, see the `clock` module.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clock::SharedClock;
+use protocol::wl_callback::Event as CallbackEvent;
+use protocol::wl_display::{RequestsTrait as DisplayRequests, WlDisplay};
+use {Display, EventQueue, Proxy};
+
+/// The responsiveness of the compositor, as reported by a `Keepalive`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Liveness {
+    /// No roundtrip was answered yet, and the first one has not timed out
+    Unknown,
+    /// The compositor answered the last roundtrip, with this latency
+    Responsive(Duration),
+    /// The compositor has not answered the pending roundtrip for longer than the timeout
+    ///
+    /// The duration is the time elapsed since the roundtrip was issued.
+    Hung(Duration),
+}
+
+// a `wl_display.sync` waiting for its answer
+struct Roundtrip {
+    sent: Instant,
+    // the instant at which the answer was dispatched
+    done: Arc<Mutex<Option<Instant>>>,
+}
+
+/// A periodic roundtrip with the compositor
+///
+/// See the module documentation for details.
+pub struct Keepalive {
+    queue: EventQueue,
+    display: Proxy<WlDisplay>,
+    clock: SharedClock,
+    interval: Duration,
+    timeout: Duration,
+    pending: Option<Roundtrip>,
+    last_sent: Option<Instant>,
+    latency: Option<Duration>,
+}
+
+impl Keepalive {
+    /// Create a keepalive for given connection
+    ///
+    /// A roundtrip is issued every `interval`, and the compositor is considered hung if it
+    /// does not answer one within `timeout`. A new roundtrip is only issued once the
+    /// previous one was answered.
+    ///
+    /// Fails if the connection is already closed.
+    pub fn new(display: &Display, interval: Duration, timeout: Duration) -> io::Result<Keepalive> {
+        let queue = display.create_event_queue();
+        let wrapper = display.make_wrapper(&queue.get_token()).map_err(|()| closed())?;
+        let clock = queue.clock.clone();
+        Ok(Keepalive {
+            queue,
+            display: wrapper,
+            clock,
+            interval,
+            timeout,
+            pending: None,
+            last_sent: None,
+            latency: None,
+        })
+    }
+
+    /// Process the answer of the compositor and issue the next roundtrip if it is due
+    ///
+    /// This reads the events already available on the connection without blocking, and
+    /// returns the responsiveness of the compositor. An error means that the connection
+    /// was lost.
+    pub fn poll(&mut self) -> io::Result<Liveness> {
+        if let Some(guard) = self.queue.prepare_read() {
+            match guard.read_events() {
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.queue.dispatch_pending()?;
+
+        let answered = match self.pending {
+            Some(ref roundtrip) => roundtrip
+                .done
+                .lock()
+                .unwrap()
+                .map(|done| done.duration_since(roundtrip.sent)),
+            None => None,
+        };
+        if let Some(latency) = answered {
+            self.latency = Some(latency);
+            self.pending = None;
+        }
+
+        let now = self.clock.get().now();
+        let due = match self.last_sent {
+            Some(sent) => now.duration_since(sent) >= self.interval,
+            None => true,
+        };
+        if self.pending.is_none() && due {
+            let done = Arc::new(Mutex::new(None));
+            let done2 = done.clone();
+            let clock = self.clock.get();
+            self.display
+                .sync(move |newcb| {
+                    newcb.implement(
+                        move |event, _| match event {
+                            CallbackEvent::Done { .. } => *done2.lock().unwrap() = Some(clock.now()),
+                        },
+                        (),
+                    )
+                }).map_err(|()| closed())?;
+            self.queue.inner.flush()?;
+            self.pending = Some(Roundtrip { sent: now, done });
+            self.last_sent = Some(now);
+        }
+
+        Ok(self.liveness())
+    }
+
+    /// The responsiveness of the compositor, from the answers processed by `poll()`
+    pub fn liveness(&self) -> Liveness {
+        if let Some(ref roundtrip) = self.pending {
+            let now = self.clock.get().now();
+            let elapsed = now.duration_since(roundtrip.sent);
+            if elapsed >= self.timeout {
+                return Liveness::Hung(elapsed);
+            }
+        }
+        match self.latency {
+            Some(latency) => Liveness::Responsive(latency),
+            None => Liveness::Unknown,
+        }
+    }
+
+    /// The latency of the last answered roundtrip
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the wayland connection is closed")
+}
@@ -0,0 +1,185 @@
+//! Input latency instrumentation
+//!
+//! This module provides tools to measure the delay introduced by your dispatching
+//! loop between the moment input events are received from the server and the moment
+//! your application handles them.
+//!
+//! The instrumentation is disabled by default, and is enabled on an event queue by
+//! setting a hook with `EventQueue::set_latency_hook()`. Each input event (the events of
+//! `wl_pointer`, `wl_keyboard` and `wl_touch` objects) dispatched by this queue is then
+//! stamped, and once its implementation returns the hook is invoked with the associated
+//! `EventTiming`.
+//!
+//! From within the implementation, you can call `mark_handled()` to record the moment
+//! your application considers the event as handled. If you never call it, the handled
+//! point of the event will be `None`.
+//!
+//! The instants are given by the clock of the `Display`, see the `clock` module.
+//!
+//! The reception of the events is only stamped while a hook is set, the input events read
+//! before it was set are given the moment their event queue started the dispatching as
+//! reception time. When using the `native_lib` feature, `libwayland-client.so` does not
+//! expose the moment messages are read from the socket, this approximation is then used
+//! for all the events.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clock::{Clock, SharedClock};
+
+const INPUT_INTERFACES: &[&str] = &["wl_pointer", "wl_keyboard", "wl_touch"];
+
+/// Timing information about the dispatching of an input event
+#[derive(Clone, Debug)]
+pub struct EventTiming {
+    interface: &'static str,
+    event: &'static str,
+    received: Instant,
+    dispatched: Instant,
+    handled: Option<Instant>,
+}
+
+impl EventTiming {
+    /// Name of the interface of the object which received this event
+    pub fn interface(&self) -> &'static str {
+        self.interface
+    }
+
+    /// Name of this event
+    pub fn event(&self) -> &'static str {
+        self.event
+    }
+
+    /// Instant at which this event was received from the server
+    pub fn received(&self) -> Instant {
+        self.received
+    }
+
+    /// Instant at which this event was given to its implementation
+    pub fn dispatched(&self) -> Instant {
+        self.dispatched
+    }
+
+    /// Instant at which this event was marked as handled
+    ///
+    /// Is `None` if `mark_handled()` was not called during the dispatching of this event.
+    pub fn handled(&self) -> Option<Instant> {
+        self.handled
+    }
+
+    /// Delay between the reception of this event and its dispatching
+    pub fn dispatch_latency(&self) -> Duration {
+        self.dispatched.duration_since(self.received)
+    }
+
+    /// Delay between the reception of this event and the moment it was marked as handled
+    pub fn handled_latency(&self) -> Option<Duration> {
+        self.handled.map(|handled| handled.duration_since(self.received))
+    }
+}
+
+/// Mark the input event currently being dispatched as handled
+///
+/// This is meant to be called from the implementation of an input object, and records
+/// the current instant as the handled point of the event. Calling it several times
+/// during the dispatching of the same event overwrites the previous value.
+///
+/// Does nothing if called outside of the dispatching of an instrumented event.
+pub fn mark_handled() {
+    let now = match ACTIVE.with(|active| active.borrow().as_ref().map(|a| a.clock.now())) {
+        Some(now) => now,
+        None => return,
+    };
+    CURRENT.with(|current| {
+        if let Some(ref mut timing) = *current.borrow_mut() {
+            timing.handled = Some(now);
+        }
+    });
+}
+
+pub(crate) type LatencyHook = RefCell<Option<Box<FnMut(&EventTiming)>>>;
+
+struct ActiveHook {
+    hook: Rc<LatencyHook>,
+    clock: Arc<Clock>,
+    dispatch_start: Instant,
+}
+
+thread_local! {
+    // the hook of the event queue currently dispatching on this thread
+    static ACTIVE: RefCell<Option<ActiveHook>> = RefCell::new(None);
+    // the timing of the input event currently being dispatched on this thread
+    static CURRENT: RefCell<Option<EventTiming>> = RefCell::new(None);
+}
+
+/// Whether the events of an interface are input events
+pub(crate) fn is_input(interface: &str) -> bool {
+    INPUT_INTERFACES.contains(&interface)
+}
+
+/// Run a dispatching closure with the latency hook of an event queue active
+pub(crate) fn with_hook<T, F: FnOnce() -> T>(hook: &Rc<LatencyHook>, clock: &SharedClock, f: F) -> T {
+    let new_active = if hook.borrow().is_some() {
+        let clock = clock.get();
+        Some(ActiveHook {
+            hook: hook.clone(),
+            dispatch_start: clock.now(),
+            clock,
+        })
+    } else {
+        None
+    };
+    let previous = ACTIVE.with(|active| ::std::mem::replace(&mut *active.borrow_mut(), new_active));
+    let ret = f();
+    ACTIVE.with(|active| *active.borrow_mut() = previous);
+    ret
+}
+
+/// Dispatch an event, recording its timing if it is an input event
+///
+/// If the reception time of the event is not known, the start of the dispatching
+/// of the current event queue is used.
+pub(crate) fn track<T, F: FnOnce() -> T>(
+    interface: &'static str,
+    event: &'static str,
+    received: Option<Instant>,
+    f: F,
+) -> T {
+    if !is_input(interface) {
+        return f();
+    }
+    let active = ACTIVE.with(|active| {
+        active
+            .borrow()
+            .as_ref()
+            .map(|a| (a.hook.clone(), received.unwrap_or(a.dispatch_start), a.clock.now()))
+    });
+    let (hook, received, dispatched) = match active {
+        Some(active) => active,
+        None => return f(),
+    };
+    let previous = CURRENT.with(|current| {
+        let previous = current.borrow_mut().take();
+        *current.borrow_mut() = Some(EventTiming {
+            interface,
+            event,
+            received,
+            dispatched,
+            handled: None,
+        });
+        previous
+    });
+    let ret = f();
+    let timing = CURRENT.with(|current| ::std::mem::replace(&mut *current.borrow_mut(), previous));
+    if let Some(timing) = timing {
+        // the hook may be busy if it dispatches events itself, ignore those
+        if let Ok(mut guard) = hook.try_borrow_mut() {
+            if let Some(ref mut hook) = *guard {
+                hook(&timing);
+            }
+        }
+    }
+    ret
+}
@@ -60,6 +60,14 @@
 //! An implementation is just an `FnMut(I::Event, Proxy<I>), where `I` is the interface of
 //! the considered object.
 //!
+//! ### Dispatch state
+//!
+//! The implementations of an application usually all update its state. Rather than sharing it
+//! behind an `Rc<RefCell<_>>` captured by every implementation, you can implement your proxies
+//! with `NewProxy::implement_with_state()`, and dispatch their event queue with
+//! `EventQueue::dispatch_with_state()`: the implementations are then given a `&mut` reference to
+//! the state given to the queue along with each event.
+//!
 //! ## Event Queues
 //!
 //! The wayland client machinnery provides the possibility to have one or more event queues
@@ -94,22 +102,62 @@
 //! - the `cursor` feature will try to load `libwayland-cursor.so`, a library helping with loading
 //!   system themed cursor textures, to integrate your app in the system theme.
 //! - the `egl` feature will try to load `libwayland-egl.so`, a library allowing the creation of
-//!   OpenGL surface from wayland surfaces.
+//!   OpenGL surface from wayland surfaces. If it is not found, its own implementation of this
+//!   library is used instead.
 //!
 //! Both of them will also be loaded at runtime if the `dlopen` feature was provided. See their
 //! respective submodules for details about their use.
 //!
+//! The `xcursor` module is an alternative to `libwayland-cursor.so` written in rust, which is
+//! available without any feature.
+//!
 //! ### Event Loop integration
 //!
 //! The `eventloop` cargo feature adds the necessary implementations to use an `EventQueue`
 //! as a `calloop` event source. If you want to use it, here are a few points to take into
 //! account:
 //!
-//! - The `EventQueue` will not call its associated callback, but rather manage all the
-//!   event dispatching internally. As a result, there is no point registering it to
-//!   `calloop` with anything other than a dummy callback.
-//! - You still need to call `Display::flush()` yourself between `calloop`s dispatches,
-//!   or in the `EventLoop::run()` callback of `calloop`.
+//! - The `EventQueue` manages all the event dispatching internally. The callback it is
+//!   registered with only receives the errors of the connection, when reading or dispatching
+//!   the events fails. The source is not removed from the event loop in this case.
+//! - Each time the connection becomes readable, the source reads and dispatches the events
+//!   until the connection is drained, as `calloop` only reports new readiness.
+//! - Unless a `FlushPolicy` flushes the connection after each dispatch, you still need to
+//!   call `Display::flush()` yourself between `calloop`s dispatches, or in the
+//!   `EventLoop::run()` callback of `calloop`.
+//! - The events given to `EventQueue::inject_event()` are not dispatched by the source.
+//!
+//! ### Async integration
+//!
+//! The `async` cargo feature adds `EventQueue::dispatch_async()`, dispatching the events
+//! from a future, to drive an `EventQueue` from an async executor like `smol`. The
+//! connection file descriptor to register in the reactor is given by
+//! `EventQueue::connection_fd()`. The `smol-dispatch` example of the repository shows their
+//! use. This feature requires rust 1.36 instead of the rust 1.21 supported by the rest of
+//! this crate.
+//!
+//! ### Graphics libraries
+//!
+//! The `window_handle` cargo feature enables the `window_handle` module, implementing the
+//! traits of `raw-window-handle` for a surface and its display, to hand them to graphics
+//! libraries like `wgpu`. It enables the `native_lib` feature, and requires rust 1.64 instead
+//! of the rust 1.21 supported by the rest of this crate.
+//!
+//! ### Metrics
+//!
+//! The `metrics` cargo feature enables the `metrics` module, rendering the counters of the
+//! connection and of its event queues in the Prometheus text format.
+//!
+//! ### Compositor quirks
+//!
+//! The `quirks` cargo feature enables the `quirks` module, identifying the compositor and
+//! listing its known peculiarities.
+//!
+//! ### io_uring
+//!
+//! The experimental `io_uring` cargo feature makes the rust implementation send and receive
+//! the messages through an io_uring rather than with the `sendmsg` and `recvmsg` system
+//! calls, if the kernel allows it. It has no effect with the `native_lib` feature.
 
 #![warn(missing_docs)]
 
@@ -125,30 +173,78 @@ extern crate nix;
 extern crate calloop;
 #[cfg(feature = "eventloop")]
 extern crate mio;
+#[cfg(feature = "window_handle")]
+extern crate raw_window_handle;
 
 extern crate wayland_commons;
 #[cfg(feature = "native_lib")]
 #[macro_use]
 extern crate wayland_sys;
 
+mod dedup;
+mod dispatch_state;
 mod display;
 mod event_queue;
 mod globals;
 mod proxy;
 
-pub use display::{ConnectError, Display};
-pub use event_queue::{EventQueue, QueueToken, ReadEventsGuard};
+pub use display::{ConnectError, Display, FlushPolicy, ProtocolError};
+#[cfg(feature = "async")]
+pub use event_queue::DispatchFuture;
+pub use event_queue::{
+    EventQueue, EventQueueFd, QueueHandle, QueueToken, ReadEventsGuard, Registration, WakeupHandle,
+};
 pub use globals::{GlobalError, GlobalEvent, GlobalImplementor, GlobalManager};
 pub use imp::ProxyMap;
-pub use proxy::{NewProxy, Proxy};
+pub use proxy::{EventStream, NewProxy, Proxy};
+
+pub mod bindings;
+
+pub mod clock;
+
+pub mod coords;
 
 #[cfg(feature = "cursor")]
 pub mod cursor;
 
+pub mod dnd;
+
 #[cfg(feature = "egl")]
 pub mod egl;
 
+pub mod keepalive;
+
+pub mod latency;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "quirks")]
+pub mod quirks;
+
+pub mod quotas;
+
+pub mod reconnect;
+
+pub mod router;
+
+mod routing;
+
+pub mod shm;
+
+pub mod takeover;
+
+pub mod transform;
+
+#[cfg(feature = "window_handle")]
+pub mod window_handle;
+
+pub mod xcursor;
+
 pub use wayland_commons::{AnonymousObject, Interface, MessageGroup, NoMessage};
+pub use wayland_commons::socket::ConnectionStats;
+pub use wayland_commons::trace::{Direction, LoggedMessage};
+pub use wayland_commons::wire::{InteriorNulError, RequestError};
 
 // rust implementation
 #[cfg(not(feature = "native_lib"))]
@@ -189,8 +285,10 @@ mod generated {
     #[cfg(feature = "native_lib")]
     pub mod c_api {
         pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
-        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, FdKind, Message, MessageDesc};
+        pub(crate) use wayland_commons::wire::{string_argument, InteriorNulError, RequestError};
         pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
+        pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
         pub(crate) use wayland_sys as sys;
         pub(crate) use {NewProxy, Proxy, ProxyMap};
         include!(concat!(env!("OUT_DIR"), "/wayland_c_api.rs"));
@@ -198,8 +296,10 @@ mod generated {
     #[cfg(not(feature = "native_lib"))]
     pub mod rust_api {
         pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
-        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, FdKind, Message, MessageDesc};
+        pub(crate) use wayland_commons::wire::{string_argument, InteriorNulError, RequestError};
         pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
+        pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
         pub(crate) use {NewProxy, Proxy, ProxyMap};
         include!(concat!(env!("OUT_DIR"), "/wayland_rust_api.rs"));
     }
@@ -237,7 +337,7 @@ impl ::mio::event::Evented for EventQueue {
 
 #[cfg(feature = "eventloop")]
 impl ::calloop::EventSource for EventQueue {
-    type Event = ();
+    type Event = ::std::io::Error;
 
     fn interest(&self) -> ::mio::Ready {
         ::mio::Ready::readable()
@@ -247,36 +347,81 @@ impl ::calloop::EventSource for EventQueue {
         ::mio::PollOpt::edge()
     }
 
-    fn make_dispatcher<Data: 'static, F: FnMut((), &mut Data) + 'static>(
+    fn make_dispatcher<Data: 'static, F: FnMut(::std::io::Error, &mut Data) + 'static>(
         &self,
-        _callback: F,
+        callback: F,
     ) -> ::std::rc::Rc<::std::cell::RefCell<::calloop::EventDispatcher<Data>>> {
-        struct Dispatcher {
-            inner: ::std::rc::Rc<::imp::EventQueueInner>,
+        struct Dispatcher<F> {
+            inner: ::std::sync::Arc<::imp::EventQueueInner>,
+            latency: ::std::rc::Rc<::latency::LatencyHook>,
+            claims: ::std::rc::Rc<::routing::Claims>,
+            filter: ::std::sync::Arc<::routing::GlobalFilter>,
+            clock: ::clock::SharedClock,
+            flush: ::std::sync::Arc<::display::FlushState>,
+            callback: F,
+        }
+
+        impl<Data, F> ::calloop::EventDispatcher<Data> for Dispatcher<F>
+        where
+            F: FnMut(::std::io::Error, &mut Data),
+        {
+            fn ready(&mut self, _ready: ::mio::Ready, data: &mut Data) {
+                let ret = {
+                    let inner = &self.inner;
+                    let (latency, clock) = (&self.latency, &self.clock);
+                    ::routing::with_claims(&self.claims, &self.filter, || {
+                        ::latency::with_hook(latency, clock, || dispatch(inner))
+                    })
+                };
+                if let Err(e) = ret {
+                    (self.callback)(e, data);
+                } else if self.flush.flush_on_dispatch() {
+                    // errors will be reported by the next flush or dispatch
+                    let _ = self.inner.flush();
+                }
+            }
         }
 
-        impl<Data> ::calloop::EventDispatcher<Data> for Dispatcher {
-            fn ready(&mut self, _ready: ::mio::Ready, _data: &mut Data) {
-                if let Err(()) = self.inner.prepare_read() {
-                    self.inner.dispatch_pending().unwrap();
-                } else {
-                    match self.inner.read_events() {
-                        Ok(_) => {
-                            self.inner.dispatch_pending().unwrap();
-                        }
-                        Err(e) => match e.kind() {
-                            ::std::io::ErrorKind::WouldBlock => {}
-                            _ => {
-                                panic!("Failed to read from wayland socket: {}", e);
-                            }
-                        },
+        fn dispatch(inner: &::imp::EventQueueInner) -> ::std::io::Result<()> {
+            // the source is edge-triggered, its readiness is only reported again
+            // once everything was read from the connection
+            let fd = inner.get_connection_fd();
+            loop {
+                if let Err(()) = inner.prepare_read() {
+                    // events are pending, they must be dispatched before reading
+                    inner.dispatch_pending()?;
+                    continue;
+                }
+                match ::event_queue::has_data(fd) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        inner.cancel_read();
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        inner.cancel_read();
+                        return Err(e);
+                    }
+                }
+                match inner.read_events() {
+                    Ok(_) => {
+                        inner.dispatch_pending()?;
                     }
+                    // an other thread read the events first
+                    Err(ref e) if e.kind() == ::std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
                 }
             }
         }
 
         ::std::rc::Rc::new(::std::cell::RefCell::new(Dispatcher {
             inner: self.inner.clone(),
+            latency: self.latency.clone(),
+            claims: self.claims.clone(),
+            filter: self.filter.clone(),
+            clock: self.clock.clone(),
+            flush: self.flush.clone(),
+            callback,
         }))
     }
 }
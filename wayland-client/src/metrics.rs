@@ -0,0 +1,67 @@
+//! Export of metrics in the Prometheus text format
+//!
+//! This module renders the counters of a connection and of its event queues in the
+//! exposition format of Prometheus, for deployments scraping the metrics of their
+//! applications. It is enabled by the `metrics` cargo feature.
+//!
+//! `render_metrics()` writes the following families:
+//!
+//! - `wayland_client_sent_messages_total`, `wayland_client_received_messages_total`,
+//!   `wayland_client_sent_bytes_total`, `wayland_client_received_bytes_total`,
+//!   `wayland_client_sent_fds_total` and `wayland_client_received_fds_total`: the counters
+//!   of the connection, see `Display::connection_stats()`
+//! - `wayland_client_queue_dispatched_events_total`: the number of events dispatched by each
+//!   queue, see `EventQueue::dispatched_events()`
+//! - `wayland_client_queue_pending_events`: the number of events waiting in each queue, see
+//!   `EventQueue::pending_events()`
+//!
+//! The samples of the queues are labelled by the names given to `render_metrics()`.
+//!
+//! With the `native_lib` cargo feature, `libwayland-client.so` does not expose the counters
+//! of the connection nor the length of the queues, their families are then omitted.
+
+use std::io::{self, Write};
+
+use wayland_commons::metrics::{family, write_connection_stats, MetricType};
+
+use {Display, EventQueue};
+
+/// Render the metrics of a connection and of some of its event queues
+///
+/// Each queue is given with the name labelling its samples, like `("main", &event_queue)`.
+pub fn render_metrics<W: Write>(
+    display: &Display,
+    queues: &[(&str, &EventQueue)],
+    out: &mut W,
+) -> io::Result<()> {
+    if let Some(stats) = display.connection_stats() {
+        write_connection_stats(out, "wayland_client", &[(&[], stats)])?;
+    }
+    {
+        let mut dispatched = family(
+            out,
+            "wayland_client_queue_dispatched_events_total",
+            "Number of events dispatched by the event queue.",
+            MetricType::Counter,
+        )?;
+        for &(name, queue) in queues {
+            dispatched.sample(&[("queue", name)], queue.dispatched_events() as u64)?;
+        }
+    }
+    let pending = queues
+        .iter()
+        .filter_map(|&(name, queue)| queue.pending_events().map(|n| (name, n)))
+        .collect::<Vec<_>>();
+    if !pending.is_empty() {
+        let mut family = family(
+            out,
+            "wayland_client_queue_pending_events",
+            "Number of events waiting to be dispatched by the event queue.",
+            MetricType::Gauge,
+        )?;
+        for (name, count) in pending {
+            family.sample(&[("queue", name)], count as u64)?;
+        }
+    }
+    Ok(())
+}
@@ -1,17 +1,26 @@
+use std::ffi::CStr;
 use std::io;
+use std::ptr;
 use std::os::unix::io::RawFd;
 use std::sync::Arc;
 
 use protocol::wl_display::WlDisplay;
+use wayland_commons::socket::ConnectionStats;
+use wayland_commons::trace::ProtocolLogger;
 use wayland_sys::client::*;
 
-use {ConnectError, Proxy};
+use clock::SharedClock;
+use quotas::ObjectQuotas;
+use takeover::{TakeoverError, TakeoverObject, TakeoverState};
+use {ConnectError, Interface, ProtocolError, Proxy};
 
-use super::EventQueueInner;
+use super::{EventQueueInner, NewProxyInner};
 
 pub(crate) struct DisplayInner {
     proxy: Proxy<WlDisplay>,
     display: *mut wl_display,
+    quotas: Arc<ObjectQuotas>,
+    clock: SharedClock,
 }
 
 unsafe impl Send for DisplayInner {}
@@ -22,9 +31,15 @@ unsafe fn make_display(ptr: *mut wl_display) -> Result<(Arc<DisplayInner>, Event
         return Err(ConnectError::NoCompositorListening);
     }
 
+    let quotas = Arc::new(ObjectQuotas::new());
+    let mut proxy = Proxy::from_c_ptr(ptr as *mut _);
+    proxy.inner.quotas = Some(quotas.clone());
+
     let display = Arc::new(DisplayInner {
-        proxy: Proxy::from_c_ptr(ptr as *mut _),
+        proxy,
         display: ptr,
+        quotas,
+        clock: SharedClock::new(),
     });
 
     let evq = EventQueueInner::new(display.clone(), None);
@@ -43,6 +58,23 @@ impl DisplayInner {
         make_display(display_ptr)
     }
 
+    pub(crate) unsafe fn adopt(
+        fd: RawFd,
+        _objects: &[TakeoverObject],
+    ) -> Result<(Arc<DisplayInner>, EventQueueInner), TakeoverError> {
+        // libwayland-client can not create proxies with given ids
+        let _ = ::nix::unistd::close(fd);
+        Err(TakeoverError::Unsupported)
+    }
+
+    pub(crate) fn adopt_object<I: Interface>(&self, _id: u32, _version: u32) -> Option<NewProxyInner> {
+        None
+    }
+
+    pub(crate) fn prepare_takeover(&self) -> Result<TakeoverState, TakeoverError> {
+        Err(TakeoverError::Unsupported)
+    }
+
     pub(crate) fn ptr(&self) -> *mut wl_display {
         self.display
     }
@@ -67,6 +99,48 @@ impl DisplayInner {
         &self.proxy
     }
 
+    pub(crate) fn quotas(&self) -> &ObjectQuotas {
+        &self.quotas
+    }
+
+    pub(crate) fn clock(&self) -> SharedClock {
+        self.clock.clone()
+    }
+
+    pub(crate) fn set_protocol_logger(&self, _logger: Option<ProtocolLogger>) {
+        // libwayland-client only supports its `WAYLAND_DEBUG` log
+    }
+
+    pub(crate) fn connection_stats(&self) -> Option<ConnectionStats> {
+        // libwayland-client does not count the exchanged data
+        None
+    }
+
+    pub(crate) fn protocol_error(&self) -> Option<ProtocolError> {
+        let mut interface = ptr::null_mut();
+        let mut object_id = 0;
+        let code = unsafe {
+            ffi_dispatch!(
+                WAYLAND_CLIENT_HANDLE,
+                wl_display_get_protocol_error,
+                self.ptr(),
+                &mut interface,
+                &mut object_id
+            )
+        };
+        // the interface is only set if the last error was a protocol error
+        if interface.is_null() {
+            return None;
+        }
+        let object_interface = unsafe { CStr::from_ptr((*interface).name) };
+        Some(ProtocolError {
+            code,
+            object_id,
+            object_interface: object_interface.to_string_lossy().into_owned(),
+            message: String::new(),
+        })
+    }
+
     pub(crate) unsafe fn from_external(display_ptr: *mut wl_display) -> (Arc<DisplayInner>, EventQueueInner) {
         let evq_ptr = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_create_queue, display_ptr);
 
@@ -77,9 +151,15 @@ impl DisplayInner {
         );
         ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_set_queue, wrapper_ptr, evq_ptr);
 
+        let quotas = Arc::new(ObjectQuotas::new());
+        let mut proxy = Proxy::from_c_display_wrapper(wrapper_ptr, evq_ptr);
+        proxy.inner.quotas = Some(quotas.clone());
+
         let display = Arc::new(DisplayInner {
-            proxy: Proxy::from_c_display_wrapper(wrapper_ptr),
+            proxy,
             display: display_ptr,
+            quotas,
+            clock: SharedClock::new(),
         });
 
         let evq = EventQueueInner::new(display.clone(), Some(evq_ptr));
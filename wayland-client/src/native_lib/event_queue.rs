@@ -1,5 +1,6 @@
 use std::io;
 use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use wayland_sys::client::*;
@@ -9,14 +10,35 @@ use super::DisplayInner;
 pub(crate) struct EventQueueInner {
     wlevq: Option<*mut wl_event_queue>,
     inner: Arc<super::DisplayInner>,
+    dispatched: AtomicUsize,
 }
 
 impl EventQueueInner {
     pub(crate) fn new(inner: Arc<DisplayInner>, wlevq: Option<*mut wl_event_queue>) -> EventQueueInner {
-        EventQueueInner { inner, wlevq }
+        EventQueueInner {
+            inner,
+            wlevq,
+            dispatched: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn dispatched_events(&self) -> usize {
+        self.dispatched.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn pending_events(&self) -> Option<usize> {
+        // libwayland-client does not expose the length of its queues
+        None
+    }
+
+    pub(crate) fn set_dispatch_threads(&self, _threads: usize) {
+        // libwayland dispatches the events from the thread dispatching the queue
+    }
+
+    pub(crate) fn set_latency_hook(&self, _set: bool) {
+        // libwayland does not expose the reception time of the events
     }
 
-    #[cfg(feature = "eventloop")]
     pub(crate) fn get_connection_fd(&self) -> ::std::os::unix::io::RawFd {
         unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_get_fd, self.inner.ptr()) }
     }
@@ -34,6 +56,7 @@ impl EventQueueInner {
             None => unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_dispatch, self.inner.ptr()) },
         };
         if ret >= 0 {
+            self.dispatched.fetch_add(ret as usize, Ordering::Relaxed);
             Ok(ret as u32)
         } else {
             Err(io::Error::last_os_error())
@@ -59,6 +82,7 @@ impl EventQueueInner {
             },
         };
         if ret >= 0 {
+            self.dispatched.fetch_add(ret as usize, Ordering::Relaxed);
             Ok(ret as u32)
         } else {
             Err(io::Error::last_os_error())
@@ -78,6 +102,7 @@ impl EventQueueInner {
             }
         };
         if ret >= 0 {
+            self.dispatched.fetch_add(ret as usize, Ordering::Relaxed);
             Ok(ret as u32)
         } else {
             Err(io::Error::last_os_error())
@@ -116,6 +141,14 @@ impl EventQueueInner {
         unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_cancel_read, self.inner.ptr()) }
     }
 
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub(crate) fn c_ptr(&self) -> *mut wl_event_queue {
+        self.wlevq.unwrap_or(ptr::null_mut())
+    }
+
     pub(crate) unsafe fn assign_proxy(&self, proxy: *mut wl_proxy) {
         ffi_dispatch!(
             WAYLAND_CLIENT_HANDLE,
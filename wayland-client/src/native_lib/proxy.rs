@@ -1,10 +1,15 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::CStr;
 use std::os::raw::{c_int, c_void};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 
-use wayland_commons::utils::UserData;
-use wayland_commons::wire::ArgumentType;
+use wayland_commons::utils::{UserData, UserDataMap};
+use wayland_commons::map::Object;
+use wayland_commons::wire::{Argument, ArgumentType, MessageDesc};
 use wayland_commons::MessageGroup;
+use bindings::RawArgument;
+use quotas::ObjectQuotas;
 use {Interface, Proxy};
 
 use super::EventQueueInner;
@@ -15,13 +20,66 @@ use wayland_sys::common::*;
 pub struct ProxyInternal {
     alive: AtomicBool,
     user_data: UserData,
+    data_map: UserDataMap,
+    requests: &'static [MessageDesc],
+    // whether the user data of the proxy is a `RawProxyUserData`
+    raw: bool,
+    interface: &'static str,
+    quotas: Option<Arc<ObjectQuotas>>,
+    // the proxies created from this one, to move them along with it to other queues
+    children: Mutex<Vec<(Weak<ProxyInternal>, *mut wl_proxy)>>,
+    // the queue the proxy was assigned to, null for the default queue, as libwayland
+    // does not tell it
+    queue: AtomicPtr<wl_event_queue>,
 }
 
 impl ProxyInternal {
-    pub fn new(user_data: UserData) -> ProxyInternal {
+    pub(crate) fn new(
+        user_data: UserData,
+        requests: &'static [MessageDesc],
+        interface: &'static str,
+        quotas: Option<Arc<ObjectQuotas>>,
+    ) -> ProxyInternal {
         ProxyInternal {
             alive: AtomicBool::new(true),
             user_data,
+            data_map: UserDataMap::new(),
+            requests,
+            raw: false,
+            interface,
+            quotas,
+            children: Mutex::new(Vec::new()),
+            queue: AtomicPtr::new(::std::ptr::null_mut()),
+        }
+    }
+
+    fn dead() -> ProxyInternal {
+        ProxyInternal {
+            alive: AtomicBool::new(false),
+            user_data: UserData::empty(),
+            data_map: UserDataMap::new(),
+            requests: &[],
+            raw: false,
+            interface: "",
+            quotas: None,
+            children: Mutex::new(Vec::new()),
+            queue: AtomicPtr::new(::std::ptr::null_mut()),
+        }
+    }
+
+    fn add_child(&self, child: &Arc<ProxyInternal>, ptr: *mut wl_proxy) {
+        let mut children = self.children.lock().unwrap();
+        // forget the destroyed children while we are at it
+        children.retain(|&(ref c, _)| c.upgrade().map(|c| c.alive.load(Ordering::Acquire)).unwrap_or(false));
+        children.push((Arc::downgrade(child), ptr));
+    }
+
+    // mark the object as destroyed, releasing its quota the first time
+    fn mark_dead(&self) {
+        if self.alive.swap(false, Ordering::AcqRel) {
+            if let Some(ref quotas) = self.quotas {
+                quotas.destroyed(self.interface);
+            }
         }
     }
 }
@@ -31,6 +89,10 @@ pub(crate) struct ProxyInner {
     internal: Option<Arc<ProxyInternal>>,
     ptr: *mut wl_proxy,
     is_wrapper: bool,
+    // the queue of a wrapper, the other proxies store it in their `ProxyInternal`
+    wrapper_queue: *mut wl_event_queue,
+    // the quotas of the connection, given to the children of this proxy
+    pub(crate) quotas: Option<Arc<ObjectQuotas>>,
 }
 
 unsafe impl Send for ProxyInner {}
@@ -70,6 +132,15 @@ impl ProxyInner {
         }
     }
 
+    // the external proxies have nowhere to store their data
+    pub(crate) fn data_map(&self) -> Option<&UserDataMap> {
+        self.internal.as_ref().map(|inner| &inner.data_map)
+    }
+
+    pub(crate) fn requests(&self) -> Option<&'static [MessageDesc]> {
+        self.internal.as_ref().map(|i| i.requests)
+    }
+
     pub(crate) fn send<I: Interface>(&self, msg: I::Request) {
         if let Some(ref internal) = self.internal {
             // object is managed
@@ -79,7 +150,9 @@ impl ProxyInner {
             }
         }
         let destructor = msg.is_destructor();
+        let mut destroyed_registry = None;
         msg.as_raw_c_in(|opcode, args| unsafe {
+            I::Request::MESSAGES[opcode as usize].check_c_fds(I::NAME, args);
             ffi_dispatch!(
                 WAYLAND_CLIENT_HANDLE,
                 wl_proxy_marshal_array,
@@ -87,17 +160,119 @@ impl ProxyInner {
                 opcode,
                 args.as_ptr() as *mut _
             );
+            // wl_fixes.destroy_registry destroys the registry given as argument
+            if I::NAME == "wl_fixes" && opcode == 1 {
+                destroyed_registry = Some(args[0].o as *mut wl_proxy);
+            }
         });
 
+        if let Some(registry) = destroyed_registry {
+            unsafe {
+                let registry = ProxyInner::from_c_ptr::<::protocol::wl_registry::WlRegistry>(registry);
+                if let Some(ref internal) = registry.internal {
+                    internal.mark_dead();
+                }
+                ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, registry.ptr);
+            }
+        }
+
         if destructor {
             // we need to destroy the proxy now
-            if let Some(ref internal) = self.internal {
-                internal.alive.store(false, Ordering::Release);
+            self.destroy();
+        }
+    }
+
+    pub(crate) fn send_raw(&self, opcode: u16, args: Vec<RawArgument>) -> Result<(), ()> {
+        let desc = match self.requests().and_then(|r| r.get(opcode as usize)) {
+            Some(desc) => desc,
+            None => return Err(()),
+        };
+        let destructor = desc.destructor;
+        if !self.is_alive() {
+            // don't send message to dead objects !
+            return Ok(());
+        }
+        // the arrays must outlive the marshalling
+        let mut arrays = Vec::new();
+        for arg in &args {
+            if let RawArgument::Array(ref a) = *arg {
+                arrays.push(wl_array {
+                    size: a.len(),
+                    alloc: a.capacity(),
+                    data: a.as_ptr() as *mut _,
+                });
             }
-            unsafe {
-                ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, self.ptr);
+        }
+        let mut arrays = arrays.iter();
+        let mut c_args = Vec::with_capacity(args.len());
+        for arg in &args {
+            c_args.push(match *arg {
+                RawArgument::Int(i) => wl_argument { i },
+                RawArgument::Uint(u) => wl_argument { u },
+                RawArgument::Fixed(f) => wl_argument { f },
+                RawArgument::Str(ref s) => wl_argument {
+                    s: s.as_ref().map(|s| s.as_ptr()).unwrap_or(::std::ptr::null()),
+                },
+                RawArgument::Array(_) => wl_argument {
+                    a: arrays.next().unwrap() as *const wl_array,
+                },
+                RawArgument::Fd(h) => wl_argument { h },
+                RawArgument::Object(ref o) => wl_argument {
+                    o: o.as_ref().map(|o| o.ptr as *const c_void).unwrap_or(::std::ptr::null()),
+                },
+                RawArgument::NewId(_) => return Err(()),
+            });
+        }
+        unsafe {
+            let interface = self.internal.as_ref().map(|i| i.interface).unwrap_or("");
+            desc.check_c_fds(interface, &c_args);
+            ffi_dispatch!(
+                WAYLAND_CLIENT_HANDLE,
+                wl_proxy_marshal_array,
+                self.ptr,
+                opcode as u32,
+                c_args.as_mut_ptr()
+            );
+        }
+        if destructor {
+            // we need to destroy the proxy now
+            self.destroy();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn dispatch_injected<I: Interface>(&self, event: I::Event) -> Result<(), ()> {
+        match self.internal {
+            // only proxies implemented with a typed implementation can be dispatched to,
+            // the user data of their wrappers is not ours
+            Some(ref internal) if !internal.raw && !self.is_wrapper => {
+                if !internal.alive.load(Ordering::Acquire) {
+                    return Err(());
+                }
             }
+            _ => return Err(()),
         }
+        let must_destroy = event.is_destructor();
+        unsafe {
+            let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, self.ptr);
+            let proxy_obj = Proxy::<I>::wrap(self.clone());
+            {
+                let user_data = &mut *(user_data as *mut ProxyUserData<I>);
+                let implem = user_data.implem.as_mut().unwrap();
+                if must_destroy {
+                    user_data.internal.mark_dead();
+                    ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, self.ptr);
+                }
+                if let Some((event, proxy_obj)) = ::routing::route(event, proxy_obj) {
+                    implem(event, proxy_obj);
+                }
+            }
+            if must_destroy {
+                // final cleanup
+                let _ = Box::from_raw(user_data as *mut ProxyUserData<I>);
+            }
+        }
+        Ok(())
     }
 
     pub(crate) fn send_constructor<I, J>(
@@ -144,6 +319,7 @@ impl ProxyInner {
                 args[nid_idx].o.is_null(),
                 "Trying to use 'send_constructor' with a non-placeholder object."
             );
+            I::Request::MESSAGES[opcode as usize].check_c_fds(I::NAME, args);
             ffi_dispatch!(
                 WAYLAND_CLIENT_HANDLE,
                 wl_proxy_marshal_array_constructor_versioned,
@@ -157,15 +333,36 @@ impl ProxyInner {
 
         if destructor {
             // we need to destroy the proxy now
-            if let Some(ref internal) = self.internal {
-                internal.alive.store(false, Ordering::Release);
-            }
-            unsafe {
-                ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, self.ptr);
-            }
+            self.destroy();
         }
 
-        Ok(unsafe { NewProxyInner::from_c_ptr(ptr) })
+        Ok(NewProxyInner {
+            ptr,
+            queue: self.queue_ptr(),
+            quotas: self.quotas.clone(),
+            parent: self.internal.clone(),
+        })
+    }
+
+    fn destroy(&self) {
+        if let Some(ref internal) = self.internal {
+            internal.mark_dead();
+            if internal.raw {
+                unsafe {
+                    let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, self.ptr)
+                        as *mut RawProxyUserData;
+                    // if the proxy is destroyed by its own handler, the dispatcher will do the cleanup
+                    if (*user_data).dispatching.get() {
+                        (*user_data).destroyed.set(true);
+                    } else {
+                        let _ = Box::from_raw(user_data);
+                    }
+                }
+            }
+        }
+        unsafe {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, self.ptr);
+        }
     }
 
     pub(crate) fn equals(&self, other: &ProxyInner) -> bool {
@@ -194,13 +391,55 @@ impl ProxyInner {
             internal: self.internal.clone(),
             ptr: wrapper_ptr,
             is_wrapper: true,
+            wrapper_queue: queue.c_ptr(),
+            quotas: self.quotas.clone(),
         })
     }
 
+    // the queue the events of this proxy, or of the objects it creates, are dispatched by
+    fn queue_ptr(&self) -> *mut wl_event_queue {
+        if self.is_wrapper {
+            self.wrapper_queue
+        } else {
+            self.internal
+                .as_ref()
+                .map(|i| i.queue.load(Ordering::Acquire))
+                .unwrap_or(::std::ptr::null_mut())
+        }
+    }
+
+    pub(crate) fn is_attached_to(&self, queue: &EventQueueInner) -> bool {
+        // the events are dispatched to the proxy, not to its wrappers
+        match self.internal {
+            Some(ref internal) => {
+                internal.alive.load(Ordering::Acquire) && internal.queue.load(Ordering::Acquire) == queue.c_ptr()
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn set_queue(&self, queue: &EventQueueInner, descendants: bool) {
+        if self.is_wrapper || !self.is_alive() {
+            return;
+        }
+        unsafe { queue.assign_proxy(self.ptr) };
+        if let Some(ref internal) = self.internal {
+            internal.queue.store(queue.c_ptr(), Ordering::Release);
+            if descendants {
+                set_children_queue(internal, queue);
+            }
+        }
+    }
+
     pub(crate) fn child<I: Interface>(&self) -> NewProxyInner {
         let ptr =
             unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_create, self.ptr, I::c_interface()) };
-        NewProxyInner { ptr: ptr }
+        NewProxyInner {
+            ptr: ptr,
+            queue: self.queue_ptr(),
+            quotas: self.quotas.clone(),
+            parent: self.internal.clone(),
+        }
     }
 
     pub(crate) fn c_ptr(&self) -> *mut wl_proxy {
@@ -210,12 +449,11 @@ impl ProxyInner {
     pub(crate) unsafe fn from_c_ptr<I: Interface>(ptr: *mut wl_proxy) -> Self {
         if ptr.is_null() {
             return ProxyInner {
-                internal: Some(Arc::new(ProxyInternal {
-                    alive: AtomicBool::new(false),
-                    user_data: UserData::empty(),
-                })),
+                internal: Some(Arc::new(ProxyInternal::dead())),
                 ptr: ptr,
                 is_wrapper: false,
+                wrapper_queue: ::std::ptr::null_mut(),
+                quotas: None,
             };
         }
 
@@ -230,38 +468,67 @@ impl ProxyInner {
         } else {
             None
         };
+        let quotas = internal.as_ref().and_then(|i| i.quotas.clone());
         ProxyInner {
             internal: internal,
             ptr: ptr,
             is_wrapper: false,
+            wrapper_queue: ::std::ptr::null_mut(),
+            quotas: quotas,
         }
     }
 
-    pub(crate) unsafe fn from_c_display_wrapper(d: *mut wl_proxy) -> ProxyInner {
+    pub(crate) unsafe fn from_c_display_wrapper(d: *mut wl_proxy, queue: *mut wl_event_queue) -> ProxyInner {
         ProxyInner {
             internal: None,
             ptr: d,
             is_wrapper: true,
+            wrapper_queue: queue,
+            quotas: None,
         }
     }
 
     pub(crate) fn child_placeholder(&self) -> ProxyInner {
         ProxyInner {
-            internal: Some(Arc::new(ProxyInternal {
-                alive: AtomicBool::new(false),
-                user_data: UserData::empty(),
-            })),
+            internal: Some(Arc::new(ProxyInternal::dead())),
             ptr: ::std::ptr::null_mut(),
             is_wrapper: false,
+            wrapper_queue: ::std::ptr::null_mut(),
+            quotas: None,
+        }
+    }
+}
+
+fn set_children_queue(internal: &ProxyInternal, queue: &EventQueueInner) {
+    let children = internal.children.lock().unwrap();
+    for &(ref child, ptr) in children.iter() {
+        if let Some(child) = child.upgrade() {
+            if child.alive.load(Ordering::Acquire) {
+                unsafe { queue.assign_proxy(ptr) };
+                child.queue.store(queue.c_ptr(), Ordering::Release);
+                set_children_queue(&child, queue);
+            }
         }
     }
 }
 
 pub(crate) struct NewProxyInner {
     ptr: *mut wl_proxy,
+    // the queue the proxy was created on, inherited from its parent
+    queue: *mut wl_event_queue,
+    quotas: Option<Arc<ObjectQuotas>>,
+    parent: Option<Arc<ProxyInternal>>,
 }
 
 impl NewProxyInner {
+    // being `Send` only matters to the implementation in rust
+    pub(crate) fn implement_send<I: Interface, F>(self, implementation: F, user_data: UserData) -> ProxyInner
+    where
+        F: FnMut(I::Event, Proxy<I>) + Send + 'static,
+    {
+        unsafe { self.implement::<I, F>(implementation, user_data) }
+    }
+
     pub(crate) unsafe fn implement<I: Interface, F>(
         self,
         implementation: F,
@@ -270,8 +537,15 @@ impl NewProxyInner {
     where
         F: FnMut(I::Event, Proxy<I>) + 'static,
     {
-        let new_user_data = Box::new(ProxyUserData::new(implementation, user_data));
+        if let Some(ref quotas) = self.quotas {
+            quotas.created(I::NAME);
+        }
+        let new_user_data = Box::new(ProxyUserData::new(implementation, user_data, self.quotas.clone()));
         let internal = new_user_data.internal.clone();
+        internal.queue.store(self.queue, Ordering::Release);
+        if let Some(ref parent) = self.parent {
+            parent.add_child(&internal, self.ptr);
+        }
 
         ffi_dispatch!(
             WAYLAND_CLIENT_HANDLE,
@@ -286,30 +560,148 @@ impl NewProxyInner {
             internal: Some(internal),
             ptr: self.ptr,
             is_wrapper: false,
+            wrapper_queue: ::std::ptr::null_mut(),
+            quotas: self.quotas,
+        }
+    }
+
+    pub(crate) unsafe fn implement_raw(
+        self,
+        desc: &Object<()>,
+        implementation: Box<FnMut(u16, Vec<RawArgument>, ProxyInner)>,
+    ) -> ProxyInner {
+        if let Some(ref quotas) = self.quotas {
+            quotas.created(desc.interface);
+        }
+        let new_user_data = Box::new(RawProxyUserData {
+            internal: Arc::new(ProxyInternal {
+                alive: AtomicBool::new(true),
+                user_data: UserData::empty(),
+                data_map: UserDataMap::new(),
+                requests: desc.requests,
+                raw: true,
+                interface: desc.interface,
+                quotas: self.quotas.clone(),
+                children: Mutex::new(Vec::new()),
+                queue: AtomicPtr::new(self.queue),
+            }),
+            interface: desc.interface,
+            events: desc.events,
+            implem: RefCell::new(implementation),
+            dispatching: Cell::new(false),
+            destroyed: Cell::new(false),
+        });
+        let internal = new_user_data.internal.clone();
+        if let Some(ref parent) = self.parent {
+            parent.add_child(&internal, self.ptr);
+        }
+
+        ffi_dispatch!(
+            WAYLAND_CLIENT_HANDLE,
+            wl_proxy_add_dispatcher,
+            self.ptr,
+            raw_proxy_dispatcher,
+            &::wayland_sys::RUST_MANAGED as *const _ as *const _,
+            Box::into_raw(new_user_data) as *mut _
+        );
+
+        ProxyInner {
+            internal: Some(internal),
+            ptr: self.ptr,
+            is_wrapper: false,
+            wrapper_queue: ::std::ptr::null_mut(),
+            quotas: self.quotas,
         }
     }
 
+    /// Racy method, if called, must be called before any event ot this object
+    /// is read from the socket, or it'll end up in the wrong queue...
+    pub(crate) unsafe fn assign_queue(&mut self, queue: &EventQueueInner) {
+        queue.assign_proxy(self.ptr);
+        self.queue = queue.c_ptr();
+    }
+
     pub(crate) fn c_ptr(&self) -> *mut wl_proxy {
         self.ptr
     }
 
     pub(crate) unsafe fn from_c_ptr(ptr: *mut wl_proxy) -> NewProxyInner {
-        NewProxyInner { ptr: ptr }
+        let parent = PARSING_TARGET.with(|t| t.borrow().clone());
+        NewProxyInner {
+            ptr: ptr,
+            queue: parent
+                .as_ref()
+                .map(|p| p.queue.load(Ordering::Acquire))
+                .unwrap_or(::std::ptr::null_mut()),
+            quotas: parent.as_ref().and_then(|p| p.quotas.clone()),
+            parent,
+        }
     }
 }
 
+thread_local! {
+    // The target of the event being parsed, parent of the objects it creates
+    static PARSING_TARGET: RefCell<Option<Arc<ProxyInternal>>> = RefCell::new(None);
+}
+
+// The arguments of an event for `dedup::keep()`, if it can be compared to the previous ones
+unsafe fn comparable_args(signature: &[ArgumentType], args: *const wl_argument) -> Option<Vec<Argument>> {
+    let mut comparable = Vec::with_capacity(signature.len());
+    for (i, typ) in signature.iter().enumerate() {
+        let arg = &*args.offset(i as isize);
+        comparable.push(match *typ {
+            ArgumentType::Int => Argument::Int(arg.i),
+            ArgumentType::Uint => Argument::Uint(arg.u),
+            ArgumentType::Fixed => Argument::Fixed(arg.f),
+            // the null strings are not compared, so that they are not mistaken for empty ones
+            ArgumentType::Str if arg.s.is_null() => return None,
+            ArgumentType::Str => Argument::Str(CStr::from_ptr(arg.s).to_owned()),
+            ArgumentType::Array if arg.a.is_null() => Argument::Array(Vec::new()),
+            ArgumentType::Array => {
+                let a = &*arg.a;
+                Argument::Array(::std::slice::from_raw_parts(a.data as *const u8, a.size).to_owned())
+            }
+            ArgumentType::Object if arg.o.is_null() => Argument::Object(0),
+            ArgumentType::Object => Argument::Object(ffi_dispatch!(
+                WAYLAND_CLIENT_HANDLE,
+                wl_proxy_get_id,
+                arg.o as *mut wl_proxy
+            )),
+            ArgumentType::NewId | ArgumentType::ArrayU64 | ArgumentType::Fd => return None,
+        });
+    }
+    Some(comparable)
+}
+
+// Parse an event, making its target the parent of the objects it creates
+unsafe fn parse_event<I: Interface>(
+    proxy: *mut wl_proxy,
+    opcode: u32,
+    args: *const wl_argument,
+    target: &Arc<ProxyInternal>,
+) -> Result<I::Event, ()> {
+    PARSING_TARGET.with(|t| *t.borrow_mut() = Some(target.clone()));
+    let ret = I::Event::from_raw_c(proxy as *mut _, opcode, args);
+    PARSING_TARGET.with(|t| *t.borrow_mut() = None);
+    ret
+}
+
+// The layout of the user data of all the proxies we manage must start with the
+// `internal` field, as `ProxyInner::from_c_ptr()` accesses it without knowing
+// which kind of user data it is.
+#[repr(C)]
 struct ProxyUserData<I: Interface> {
     internal: Arc<ProxyInternal>,
     implem: Option<Box<FnMut(I::Event, Proxy<I>)>>,
 }
 
 impl<I: Interface> ProxyUserData<I> {
-    fn new<F>(implem: F, user_data: UserData) -> ProxyUserData<I>
+    fn new<F>(implem: F, user_data: UserData, quotas: Option<Arc<ObjectQuotas>>) -> ProxyUserData<I>
     where
         F: FnMut(I::Event, Proxy<I>) + 'static,
     {
         ProxyUserData {
-            internal: Arc::new(ProxyInternal::new(user_data)),
+            internal: Arc::new(ProxyInternal::new(user_data, I::Request::MESSAGES, I::NAME, quotas)),
             implem: Some(Box::new(implem)),
         }
     }
@@ -330,22 +722,32 @@ where
     // We don't need to worry about panic-safeness, because if there is a panic,
     // we'll abort the process, so no access to corrupted data is possible.
     let ret = ::std::panic::catch_unwind(move || {
+        let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, proxy);
         // parse the message:
-        let msg = I::Event::from_raw_c(proxy as *mut _, opcode, args)?;
+        let internal = &(*(user_data as *mut ProxyUserData<I>)).internal;
+        let msg = parse_event::<I>(proxy, opcode, args, internal)?;
         let must_destroy = msg.is_destructor();
         // create the proxy object
         let proxy_obj = ::Proxy::<I>::from_c_ptr(proxy);
+        let signature = I::Event::MESSAGES[opcode as usize].signature;
+        if !::dedup::keep::<I, _>(&proxy_obj.inner, opcode as u16, || comparable_args(signature, args)) {
+            return Ok(());
+        }
         // retrieve the impl
-        let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, proxy);
         {
             let user_data = &mut *(user_data as *mut ProxyUserData<I>);
             let implem = user_data.implem.as_mut().unwrap();
             if must_destroy {
-                user_data.internal.alive.store(false, Ordering::Release);
+                user_data.internal.mark_dead();
                 ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, proxy);
             }
             // call the impl
-            implem(msg, proxy_obj);
+            let event_name = I::Event::MESSAGES[opcode as usize].name;
+            ::latency::track(I::NAME, event_name, None, || {
+                if let Some((msg, proxy_obj)) = ::routing::route(msg, proxy_obj) {
+                    implem(msg, proxy_obj);
+                }
+            });
         }
         if must_destroy {
             // final cleanup
@@ -370,3 +772,105 @@ where
         }
     }
 }
+
+#[repr(C)]
+struct RawProxyUserData {
+    internal: Arc<ProxyInternal>,
+    interface: &'static str,
+    events: &'static [MessageDesc],
+    implem: RefCell<Box<FnMut(u16, Vec<RawArgument>, ProxyInner)>>,
+    dispatching: Cell<bool>,
+    destroyed: Cell<bool>,
+}
+
+unsafe extern "C" fn raw_proxy_dispatcher(
+    _implem: *const c_void,
+    proxy: *mut c_void,
+    opcode: u32,
+    msg: *const wl_message,
+    args: *const wl_argument,
+) -> c_int {
+    let proxy = proxy as *mut wl_proxy;
+
+    // We don't need to worry about panic-safeness, because if there is a panic,
+    // we'll abort the process, so no access to corrupted data is possible.
+    let ret = ::std::panic::catch_unwind(move || {
+        let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, proxy);
+        let user_data = user_data as *mut RawProxyUserData;
+        let desc = (*user_data).events.get(opcode as usize).ok_or(())?;
+        let internal = &(*user_data).internal;
+        // parse the message, the signature may contain version numbers and nullability markers
+        let signature = CStr::from_ptr((*msg).signature).to_bytes();
+        let mut raw_args = Vec::with_capacity(desc.signature.len());
+        for (i, typ) in signature
+            .iter()
+            .filter(|&&c| c != b'?' && !(c as char).is_digit(10))
+            .enumerate()
+        {
+            let arg = &*args.offset(i as isize);
+            raw_args.push(match *typ {
+                b'i' => RawArgument::Int(arg.i),
+                b'u' => RawArgument::Uint(arg.u),
+                b'f' => RawArgument::Fixed(arg.f),
+                b's' => RawArgument::Str(if arg.s.is_null() {
+                    None
+                } else {
+                    Some(CStr::from_ptr(arg.s).to_owned())
+                }),
+                b'a' => RawArgument::Array(if arg.a.is_null() {
+                    Vec::new()
+                } else {
+                    let a = &*arg.a;
+                    ::std::slice::from_raw_parts(a.data as *const u8, a.size).to_owned()
+                }),
+                b'h' => RawArgument::Fd(arg.h),
+                b'o' => RawArgument::Object(if arg.o.is_null() {
+                    None
+                } else {
+                    Some(ProxyInner::from_c_ptr::<::AnonymousObject>(arg.o as *mut _))
+                }),
+                b'n' => RawArgument::NewId(NewProxyInner {
+                    ptr: arg.o as *mut _,
+                    queue: internal.queue.load(Ordering::Acquire),
+                    quotas: internal.quotas.clone(),
+                    parent: Some(internal.clone()),
+                }),
+                _ => return Err(()),
+            });
+        }
+        let proxy_obj = ProxyInner::from_c_ptr::<::AnonymousObject>(proxy);
+        if desc.destructor {
+            (*user_data).internal.mark_dead();
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, proxy);
+        }
+        // call the impl
+        (*user_data).dispatching.set(true);
+        ::latency::track((*user_data).interface, desc.name, None, || {
+            (&mut *(*user_data).implem.borrow_mut())(opcode as u16, raw_args, proxy_obj)
+        });
+        (*user_data).dispatching.set(false);
+        if desc.destructor || (*user_data).destroyed.get() {
+            // final cleanup
+            let _ = Box::from_raw(user_data);
+        }
+        Ok(())
+    });
+    // check the return status
+    let user_data =
+        ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, proxy) as *mut RawProxyUserData;
+    match ret {
+        Ok(Ok(())) => return 0,
+        Ok(Err(())) => {
+            eprintln!(
+                "[wayland-client error] Attempted to dispatch unknown opcode {} for {}, aborting.",
+                opcode,
+                (*user_data).interface
+            );
+            ::libc::abort();
+        }
+        Err(_) => {
+            eprintln!("[wayland-client error] A handler for {} panicked.", (*user_data).interface);
+            ::libc::abort()
+        }
+    }
+}
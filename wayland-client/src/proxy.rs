@@ -1,10 +1,13 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+
 use wayland_commons::utils::UserData;
 use wayland_commons::{AnonymousObject, Interface};
 
 #[cfg(feature = "native_lib")]
 use wayland_sys::client::*;
 
-use event_queue::QueueToken;
+use event_queue::{QueueHandle, QueueToken};
 
 use imp::{NewProxyInner, ProxyInner};
 
@@ -23,6 +26,17 @@ use ProxyMap;
 /// These handles are notably used to send requests to the server. To do
 /// you need to import the associated `RequestsTrait` trait from the module
 /// of this interface.
+///
+/// Requests can be sent from any thread. Sending a request only appends it to
+/// the outgoing buffer of the connection, and the requests are actually written to
+/// the socket when the connection is flushed (by `Display::flush()` or when dispatching
+/// an event queue). Regarding the ordering of requests:
+///
+/// - requests sent from a single thread reach the server in the order they were sent
+/// - requests sent concurrently from several threads are interleaved in an unspecified
+///   order, but each of them is sent atomically
+/// - no request is sent on an object after a destructor request was sent on it, whatever
+///   the thread it was sent from
 pub struct Proxy<I: Interface> {
     _i: ::std::marker::PhantomData<&'static I>,
     pub(crate) inner: ProxyInner,
@@ -121,11 +135,50 @@ impl<I: Interface> Proxy<I> {
     /// you are attempting to access a non `Send + Sync` user data from the
     /// wrong thread.
     ///
-    /// This value is associated to the Proxy when you implement it, and you
-    /// cannot access it mutably afterwards. If you need interior mutability,
-    /// you are responsible for using a `Mutex` or similar type to achieve it.
+    /// This value is associated to the Proxy when you implement it, or
+    /// attached to it afterwards with `set_user_data()`, and you cannot
+    /// access it mutably. If you need interior mutability, you are
+    /// responsible for using a `Mutex` or similar type to achieve it.
     pub fn user_data<UD: 'static>(&self) -> Option<&UD> {
-        self.inner.get_user_data()
+        self.inner
+            .get_user_data()
+            .or_else(|| self.inner.data_map().and_then(|map| map.get::<UD>()))
+    }
+
+    /// Attach an additional payload to this object
+    ///
+    /// An object can hold one payload of each type besides the one given to
+    /// its implementation, so that independent modules, like a toolkit and
+    /// an EGL helper, can each attach their own data to the same surface.
+    /// They are shared by all the handles of the object and accessed with
+    /// `user_data()`.
+    ///
+    /// Returns `false` and drops the value if this object already holds a
+    /// payload of this type, or if it cannot hold additional payloads, which
+    /// is the case of the proxies not created by this library (see
+    /// `is_external()`).
+    pub fn set_user_data<UD: Send + Sync + 'static>(&self, value: UD) -> bool {
+        if self.user_data::<UD>().is_some() {
+            return false;
+        }
+        match self.inner.data_map() {
+            Some(map) => map.insert_if_missing_threadsafe(move || value),
+            None => false,
+        }
+    }
+
+    /// Attach an additional non-`Send` payload to this object
+    ///
+    /// Like `set_user_data()`, but the payload is only accessible from the
+    /// current thread.
+    pub fn set_user_data_nonsend<UD: 'static>(&self, value: UD) -> bool {
+        if self.user_data::<UD>().is_some() {
+            return false;
+        }
+        match self.inner.data_map() {
+            Some(map) => map.insert_if_missing(move || value),
+            None => false,
+        }
     }
 
     /// Check if the other proxy refers to the same underlying wayland object
@@ -181,6 +234,30 @@ impl<I: Interface> Proxy<I> {
         })
     }
 
+    /// Move this proxy to an other event queue
+    ///
+    /// Once this call returns, the events of this proxy are dispatched by the event queue
+    /// associated to the provided handle. If `descendants` is `true`, all the live objects
+    /// created from this proxy (by its requests or its events), and recursively the objects
+    /// created from them, are moved as well.
+    ///
+    /// When using the `rust_imp` backend, the events of the moved proxies which were already
+    /// received but not yet dispatched are moved to the new queue too, in order, so that
+    /// every event is dispatched exactly once by either queue. When using the `native_lib`
+    /// feature, these events are still dispatched by the old queue.
+    ///
+    /// Wrappers created with `make_wrapper()` cannot be moved, and calling this method on
+    /// them or on dead proxies does nothing.
+    ///
+    /// # Safety
+    ///
+    /// The implementations of the moved proxies will be invoked from the thread dispatching
+    /// the new queue, and thus must be `Send` if this is not the current thread (which is
+    /// not the case of the ones given to `NewProxy::implement_nonsend`).
+    pub unsafe fn rebind(&self, queue: &QueueHandle, descendants: bool) {
+        self.inner.set_queue(&queue.inner, descendants);
+    }
+
     /// Create a placeholder object, to be used with `send_constructor`
     ///
     /// **Warning:** This method is mostly intented to be used by code generated
@@ -240,10 +317,11 @@ impl<I: Interface> Proxy<I> {
 impl Proxy<::protocol::wl_display::WlDisplay> {
     pub(crate) unsafe fn from_c_display_wrapper(
         ptr: *mut wl_proxy,
+        queue: *mut wl_event_queue,
     ) -> Proxy<::protocol::wl_display::WlDisplay> {
         Proxy {
             _i: ::std::marker::PhantomData,
-            inner: ProxyInner::from_c_display_wrapper(ptr),
+            inner: ProxyInner::from_c_display_wrapper(ptr, queue),
         }
     }
 }
@@ -280,13 +358,57 @@ impl<I: Interface + 'static> NewProxy<I> {
         UD: Send + Sync + 'static,
         I::Event: MessageGroup<Map = ProxyMap>,
     {
+        let inner = self
+            .inner
+            .implement_send::<I, _>(implementation, UserData::new_threadsafe(user_data));
+        Proxy {
+            _i: ::std::marker::PhantomData,
+            inner: inner,
+        }
+    }
+
+    /// Implement this proxy using given function and user data, with access to the dispatch
+    /// state
+    ///
+    /// The implementation is given the state passed to `EventQueue::dispatch_with_state()` or
+    /// `EventQueue::dispatch_pending_with_state()` along with the events, as a `&mut Data`. See
+    /// the crate-level documentation for details.
+    ///
+    /// The events of this proxy are always dispatched on the thread dispatching its event
+    /// queue, never by its dispatch pool.
+    ///
+    /// # Panics
+    ///
+    /// The dispatching of an event panics if the event queue is dispatched without a state, by
+    /// a method other than `dispatch_with_state()` and `dispatch_pending_with_state()`, or if
+    /// `Data` is not the type of the state given to the event queue. Such an event can not be
+    /// processed, and ignoring it would desynchronize the client from the server.
+    pub fn implement_with_state<Data, F, UD>(self, mut implementation: F, user_data: UD) -> Proxy<I>
+    where
+        Data: 'static,
+        F: FnMut(I::Event, Proxy<I>, &mut Data) + Send + 'static,
+        UD: Send + Sync + 'static,
+        I::Event: MessageGroup<Map = ProxyMap>,
+    {
+        let implementation = move |event, proxy: Proxy<I>| {
+            ::dispatch_state::borrow_state(|state: Option<&mut Data>| match state {
+                Some(state) => implementation(event, proxy, state),
+                None => panic!(
+                    "Dispatched an event to {}@{} without the state of its implementation.",
+                    I::NAME,
+                    proxy.id()
+                ),
+            })
+        };
+        // the implementation is `Send`, but must not be dispatched by the pool: the state is
+        // only available on the thread dispatching the queue
         let inner = unsafe {
             self.inner
                 .implement::<I, _>(implementation, UserData::new_threadsafe(user_data))
         };
         Proxy {
             _i: ::std::marker::PhantomData,
-            inner: inner,
+            inner,
         }
     }
 
@@ -304,7 +426,7 @@ impl<I: Interface + 'static> NewProxy<I> {
     ///
     /// To ensure safety, see `Proxy::make_wrapper`.
     pub unsafe fn implement_nonsend<Impl, UD>(
-        self,
+        mut self,
         implementation: Impl,
         user_data: UD,
         queue: &QueueToken,
@@ -314,14 +436,7 @@ impl<I: Interface + 'static> NewProxy<I> {
         UD: 'static,
         I::Event: MessageGroup<Map = ProxyMap>,
     {
-        #[cfg(feature = "native_lib")]
-        {
-            queue.inner.assign_proxy(self.inner.c_ptr());
-        }
-        #[cfg(not(feature = "native_lib"))]
-        {
-            self.inner.assign_queue(&queue.inner);
-        }
+        self.inner.assign_queue(&queue.inner);
         let inner = self
             .inner
             .implement::<I, _>(implementation, UserData::new(user_data));
@@ -330,6 +445,120 @@ impl<I: Interface + 'static> NewProxy<I> {
             inner: inner,
         }
     }
+
+    /// Implement this proxy on a given event queue, using given function and implementation data.
+    ///
+    /// This method can be called from any thread, the events of this proxy will then
+    /// be dispatched by the event queue associated to the provided handle rather than
+    /// the one of its parent.
+    ///
+    /// Events received by this proxy before this call are still dispatched by the queue
+    /// of its parent, which is why the implementation needs to be `Send`.
+    pub fn implement_on<F, UD>(mut self, implementation: F, user_data: UD, queue: &QueueHandle) -> Proxy<I>
+    where
+        F: FnMut(I::Event, Proxy<I>) + Send + 'static,
+        UD: Send + Sync + 'static,
+        I::Event: MessageGroup<Map = ProxyMap>,
+    {
+        unsafe {
+            self.inner.assign_queue(&queue.inner);
+        }
+        self.implement(implementation, user_data)
+    }
+
+    /// Implement this proxy by storing its events in a stream
+    ///
+    /// Rather than being given to a callback, the events of this proxy are stored in the
+    /// returned `EventStream` as its event queue is dispatched, and can then be handled
+    /// by iterating on it. This allows handling them with access to the state of your
+    /// program, without needing to share this state with a closure.
+    ///
+    /// The events of the interfaces creating new objects are not `Send`, see
+    /// `implement_stream_nonsend()` for them.
+    pub fn implement_stream(self) -> (Proxy<I>, EventStream<I>)
+    where
+        I::Event: MessageGroup<Map = ProxyMap> + Send,
+    {
+        let (stream, sink) = EventStream::new();
+        (self.implement(move |event, _| sink.push(event), ()), stream)
+    }
+
+    /// Implement this proxy by storing its events in a stream
+    ///
+    /// This method is the equivalent of `implement_stream()` for interfaces whose events
+    /// are not `Send`. As for `implement_nonsend()`, it requires a token to the event
+    /// queue this proxy will be implemented on.
+    ///
+    /// # Safety
+    ///
+    /// This call can be racy if the proxy is not already registered on this event queue and its
+    /// old queue is being dispatched from an other thread.
+    ///
+    /// To ensure safety, see `Proxy::make_wrapper`.
+    pub unsafe fn implement_stream_nonsend(self, queue: &QueueToken) -> (Proxy<I>, EventStream<I>)
+    where
+        I::Event: MessageGroup<Map = ProxyMap>,
+    {
+        let (stream, sink) = EventStream::new();
+        (
+            self.implement_nonsend(move |event, _| sink.push(event), (), queue),
+            stream,
+        )
+    }
+}
+
+/// A stream of the events of a proxy
+///
+/// This stream is created by `NewProxy::implement_stream()`, and is an iterator yielding
+/// the events of the proxy in the order they were dispatched. Iterating on it never blocks:
+/// it yields `None` once all the events dispatched so far were handled, and yields the new
+/// events once the event queue of the proxy has been dispatched again.
+///
+/// The events dispatched after this stream was dropped are discarded.
+pub struct EventStream<I: Interface> {
+    events: Arc<Mutex<VecDeque<I::Event>>>,
+}
+
+// the side of the stream held by the implementation, which does not keep the events
+// alive once the stream is dropped
+struct EventSink<E> {
+    events: Weak<Mutex<VecDeque<E>>>,
+}
+
+impl<E> EventSink<E> {
+    fn push(&self, event: E) {
+        if let Some(events) = self.events.upgrade() {
+            events.lock().unwrap().push_back(event);
+        }
+    }
+}
+
+impl<I: Interface> EventStream<I> {
+    fn new() -> (EventStream<I>, EventSink<I::Event>) {
+        let events = Arc::new(Mutex::new(VecDeque::new()));
+        let sink = EventSink {
+            events: Arc::downgrade(&events),
+        };
+        (EventStream { events }, sink)
+    }
+
+    /// The number of events currently stored in this stream
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    /// Whether this stream currently stores no event
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<I: Interface> Iterator for EventStream<I> {
+    type Item = I::Event;
+
+    fn next(&mut self) -> Option<I::Event> {
+        self.events.lock().unwrap().pop_front()
+    }
 }
 
 #[cfg(feature = "native_lib")]
@@ -0,0 +1,160 @@
+//! Compositor identification and known quirks
+//!
+//! Compositors differ in how they interpret some parts of the protocols, and toolkits
+//! tend to work around these differences by checking which compositor they are running
+//! on. This module gathers this knowledge in a single place: `detect()` identifies the
+//! compositor from the globals it advertises, and a `QuirksDb` maps compositors to the
+//! `Quirks` flags describing their known peculiarities.
+//!
+//! The identification from the globals is a heuristic, relying on the private protocols
+//! the compositors expose. If you know which compositor you are running on by other
+//! means (for example from a protocol giving its name), `Compositor::from_name()` gives
+//! the matching `Compositor`.
+//!
+//! The builtin database only contains a few well-known entries, and applications can
+//! complete or override it using `QuirksDb::set()`.
+//!
+//! The behaviours required by the protocols are not quirks, and must be followed on every
+//! compositor: for example acking the initial configure of an `xdg_surface` before its
+//! first commit with a buffer, which the `ConfigureTracker` of `wayland-protocols` does.
+//!
+//! This module requires the `quirks` cargo feature.
+//!
+//! ```no_run
+//! # extern crate wayland_client;
+//! use wayland_client::quirks::{detect, Quirks, QuirksDb};
+//! use wayland_client::{Display, GlobalManager};
+//!
+//! # fn main() {
+//! let (display, mut event_queue) = Display::connect_to_env().unwrap();
+//! let globals = GlobalManager::new(&display);
+//! event_queue.sync_roundtrip().unwrap();
+//!
+//! let compositor = detect(&globals.list());
+//! let quirks = QuirksDb::new().quirks(&compositor);
+//! if quirks.contains(Quirks::SLOW_BUFFER_RELEASE) {
+//!     // allocate an extra buffer
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+/// A known compositor
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Compositor {
+    /// The compositor of GNOME
+    Mutter,
+    /// The compositor of KDE Plasma
+    KWin,
+    /// Hyprland
+    Hyprland,
+    /// A compositor based on wlroots, like Sway
+    Wlroots,
+    /// The reference compositor
+    Weston,
+    /// A compositor identified by a name unknown to this module
+    Other(String),
+    /// The compositor could not be identified
+    Unknown,
+}
+
+impl Compositor {
+    /// Get the compositor matching given name
+    ///
+    /// The name is matched case-insensitively against the names of the compositors
+    /// this module knows about. Names of compositors based on wlroots (like `"sway"`)
+    /// give `Compositor::Wlroots`, other unknown names give `Compositor::Other`.
+    pub fn from_name(name: &str) -> Compositor {
+        match &*name.to_lowercase() {
+            "mutter" | "gnome-shell" | "gnome" => Compositor::Mutter,
+            "kwin" | "kwin_wayland" | "kde" => Compositor::KWin,
+            "hyprland" => Compositor::Hyprland,
+            "wlroots" | "sway" | "river" | "labwc" | "wayfire" => Compositor::Wlroots,
+            "weston" => Compositor::Weston,
+            "" => Compositor::Unknown,
+            _ => Compositor::Other(name.to_owned()),
+        }
+    }
+}
+
+// Globals identifying a compositor, checked in order: a global matches if its interface
+// starts with the prefix. Compositors exposing the protocols of others come first.
+const FINGERPRINTS: &[(&str, Compositor)] = &[
+    ("hyprland_", Compositor::Hyprland),
+    ("org_kde_plasma_", Compositor::KWin),
+    ("org_kde_kwin_", Compositor::KWin),
+    ("gtk_shell", Compositor::Mutter),
+    ("weston_", Compositor::Weston),
+    ("zwlr_", Compositor::Wlroots),
+];
+
+/// Identify the compositor from the globals it advertises
+///
+/// `globals` is the list of `(name, interface, version)` of the globals, as returned by
+/// `GlobalManager::list()`. Returns `Compositor::Unknown` if none of the globals
+/// identifies a compositor.
+pub fn detect(globals: &[(u32, String, u32)]) -> Compositor {
+    for &(prefix, ref compositor) in FINGERPRINTS {
+        if globals.iter().any(|global| global.1.starts_with(prefix)) {
+            return compositor.clone();
+        }
+    }
+    Compositor::Unknown
+}
+
+bitflags! {
+    /// Known peculiarities of a compositor
+    pub struct Quirks: u32 {
+        /// Buffers are not released promptly once replaced by an other one
+        ///
+        /// Clients should allocate an extra buffer rather than waiting for the
+        /// `release` event of the previous one.
+        const SLOW_BUFFER_RELEASE = 1;
+        /// Server-side decorations are not available
+        ///
+        /// Clients are expected to draw their own decorations.
+        const NO_SERVER_DECORATIONS = 2;
+    }
+}
+
+/// A database of the quirks of compositors
+#[derive(Clone, Debug)]
+pub struct QuirksDb {
+    entries: HashMap<Compositor, Quirks>,
+}
+
+impl QuirksDb {
+    /// Create a database with the builtin entries
+    pub fn new() -> QuirksDb {
+        let mut db = QuirksDb::empty();
+        db.set(Compositor::Mutter, Quirks::SLOW_BUFFER_RELEASE | Quirks::NO_SERVER_DECORATIONS);
+        db.set(Compositor::Weston, Quirks::NO_SERVER_DECORATIONS);
+        db
+    }
+
+    /// Create a database without any entry
+    pub fn empty() -> QuirksDb {
+        QuirksDb {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Set the quirks of a compositor, replacing its previous entry
+    pub fn set(&mut self, compositor: Compositor, quirks: Quirks) {
+        self.entries.insert(compositor, quirks);
+    }
+
+    /// The quirks of a compositor
+    ///
+    /// Compositors without an entry have no known quirks.
+    pub fn quirks(&self, compositor: &Compositor) -> Quirks {
+        self.entries.get(compositor).cloned().unwrap_or_else(Quirks::empty)
+    }
+}
+
+impl Default for QuirksDb {
+    fn default() -> QuirksDb {
+        QuirksDb::new()
+    }
+}
@@ -0,0 +1,139 @@
+//! Object quotas
+//!
+//! This module provides soft limits on the number of live objects of a given interface
+//! on a wayland connection, to catch run-away allocation patterns (for example frame
+//! callbacks that are created faster than the server can answer them) before the
+//! compositor decides to disconnect the client.
+//!
+//! The library keeps track of the number of live objects of each interface on each
+//! `Display`, which can be queried with `Display::live_objects()`. A limit is set for
+//! an interface with `Display::set_object_quota()`, and exceeding it does not prevent the
+//! creation of new objects: it invokes the hook set with `Display::set_quota_hook()`,
+//! which by default prints a warning on stderr. The hook is invoked once each time the
+//! limit is crossed, it is re-armed once the number of live objects drops back to the
+//! limit.
+//!
+//! When using the `rust_imp` backend, an object is live from its creation until it is
+//! destroyed by either the client or the server. When using the `native_lib` feature,
+//! only objects implemented by this library are counted, from their implementation until
+//! their destruction.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Information about an object quota which was exceeded
+#[derive(Clone, Debug)]
+pub struct QuotaExceeded {
+    interface: &'static str,
+    live: usize,
+    limit: usize,
+}
+
+impl QuotaExceeded {
+    /// Name of the interface whose quota was exceeded
+    pub fn interface(&self) -> &'static str {
+        self.interface
+    }
+
+    /// Number of live objects of this interface
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    /// The limit set for this interface
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+type QuotaHook = Arc<Fn(&QuotaExceeded) + Send + Sync>;
+
+#[derive(Default)]
+struct Quota {
+    live: usize,
+    limit: Option<usize>,
+    exceeded: bool,
+}
+
+struct QuotasInner {
+    quotas: HashMap<&'static str, Quota>,
+    hook: QuotaHook,
+}
+
+// The live object counts of a connection, shared by all its objects
+pub(crate) struct ObjectQuotas {
+    inner: Mutex<QuotasInner>,
+}
+
+impl ObjectQuotas {
+    pub(crate) fn new() -> ObjectQuotas {
+        ObjectQuotas {
+            inner: Mutex::new(QuotasInner {
+                quotas: HashMap::new(),
+                hook: Arc::new(default_hook),
+            }),
+        }
+    }
+
+    pub(crate) fn set_quota(&self, interface: &'static str, limit: Option<usize>) {
+        let mut inner = self.inner.lock().unwrap();
+        let quota = inner.quotas.entry(interface).or_insert_with(Default::default);
+        quota.limit = limit;
+        quota.exceeded = false;
+    }
+
+    pub(crate) fn set_hook(&self, hook: QuotaHook) {
+        self.inner.lock().unwrap().hook = hook;
+    }
+
+    pub(crate) fn live(&self, interface: &str) -> usize {
+        self.inner
+            .lock()
+            .unwrap()
+            .quotas
+            .get(interface)
+            .map(|q| q.live)
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn created(&self, interface: &'static str) {
+        let (hook, exceeded) = {
+            let mut inner = self.inner.lock().unwrap();
+            let exceeded = {
+                let quota = inner.quotas.entry(interface).or_insert_with(Default::default);
+                quota.live += 1;
+                match quota.limit {
+                    Some(limit) if quota.live > limit && !quota.exceeded => {
+                        quota.exceeded = true;
+                        QuotaExceeded {
+                            interface,
+                            live: quota.live,
+                            limit,
+                        }
+                    }
+                    _ => return,
+                }
+            };
+            (inner.hook.clone(), exceeded)
+        };
+        // don't hold the lock while running the hook, it may query the quotas
+        hook(&exceeded);
+    }
+
+    pub(crate) fn destroyed(&self, interface: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(quota) = inner.quotas.get_mut(interface) {
+            quota.live = quota.live.saturating_sub(1);
+            if quota.limit.map(|limit| quota.live <= limit).unwrap_or(true) {
+                quota.exceeded = false;
+            }
+        }
+    }
+}
+
+fn default_hook(exceeded: &QuotaExceeded) {
+    eprintln!(
+        "[wayland-client] Object quota exceeded: {} live objects of interface {} (limit is {}).",
+        exceeded.live, exceeded.interface, exceeded.limit
+    );
+}
@@ -0,0 +1,134 @@
+//! Proxies surviving a reconnection to the server
+//!
+//! When the compositor restarts, all the objects of its clients are lost along with their
+//! connection. A client which must keep running through it, like the applications of an
+//! embedded HMI, has to connect again and re-create all of its objects, and every part of
+//! its code holding a `Proxy` then needs the new one.
+//!
+//! `VirtualProxies` avoids this restructuring: each object is registered with a recipe, a
+//! closure creating the real object from the globals of a connection, and the application
+//! holds `VirtualProxy` handles instead of proxies. The handles stay the same across
+//! reconnections, `VirtualProxy::get()` giving the real proxy of the current connection.
+//!
+//! Once the connection is lost, connect again with `Display::connect_to_env()`, create a
+//! `GlobalManager` on the new `Display` and do a roundtrip so that it lists the globals,
+//! then give it to `VirtualProxies::reconnect()`. It invokes again the recipes in the order
+//! they were registered, so the recipe of an object can create it from the handles of the
+//! objects registered before it, like a `wl_surface` from a `wl_compositor`. A recipe also
+//! restores the state of its object, like the title of a toplevel surface.
+//!
+//! An object is re-created as long as some handle to it is alive: drop its handles once the
+//! object is destroyed.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use {GlobalManager, Interface, Proxy};
+
+// recreate an object, returns whether its recipe succeeded, or `None` if there are
+// no handles to it anymore
+type Rebuild = Box<FnMut(&GlobalManager) -> Option<bool>>;
+
+/// A handle to an object re-created on each reconnection
+///
+/// See the module documentation for details.
+pub struct VirtualProxy<I: Interface> {
+    proxy: Rc<RefCell<Option<Proxy<I>>>>,
+}
+
+impl<I: Interface> VirtualProxy<I> {
+    /// The real proxy of the object on the current connection
+    ///
+    /// Returns `None` if its recipe failed on this connection.
+    pub fn get(&self) -> Option<Proxy<I>> {
+        self.proxy.borrow().clone()
+    }
+}
+
+impl<I: Interface> Clone for VirtualProxy<I> {
+    fn clone(&self) -> VirtualProxy<I> {
+        VirtualProxy {
+            proxy: self.proxy.clone(),
+        }
+    }
+}
+
+/// The objects to re-create on each reconnection
+///
+/// See the module documentation for details.
+pub struct VirtualProxies {
+    globals: GlobalManager,
+    objects: Vec<(&'static str, Rebuild)>,
+}
+
+impl VirtualProxies {
+    /// Create an empty set of objects, on the connection of given globals
+    pub fn new(globals: &GlobalManager) -> VirtualProxies {
+        VirtualProxies {
+            globals: globals.clone(),
+            objects: Vec::new(),
+        }
+    }
+
+    /// The globals of the current connection
+    pub fn globals(&self) -> &GlobalManager {
+        &self.globals
+    }
+
+    /// Register an object created by given recipe
+    ///
+    /// The recipe is invoked right away to create the object on the current connection,
+    /// then on each reconnection. It returns `None` if the object could not be created,
+    /// like when a required global is not advertised.
+    pub fn add<I, F>(&mut self, mut recipe: F) -> VirtualProxy<I>
+    where
+        I: Interface,
+        F: FnMut(&GlobalManager) -> Option<Proxy<I>> + 'static,
+    {
+        let proxy = Rc::new(RefCell::new(recipe(&self.globals)));
+        let weak: Weak<RefCell<Option<Proxy<I>>>> = Rc::downgrade(&proxy);
+        self.objects.push((
+            I::NAME,
+            Box::new(move |globals| {
+                let proxy = match weak.upgrade() {
+                    Some(proxy) => proxy,
+                    None => return None,
+                };
+                let new = recipe(globals);
+                let created = new.is_some();
+                *proxy.borrow_mut() = new;
+                Some(created)
+            }),
+        ));
+        VirtualProxy { proxy }
+    }
+
+    /// Re-create all the objects on a new connection
+    ///
+    /// The globals must already be listed by the `GlobalManager`. The objects without
+    /// handles are forgotten. Returns the interfaces of the objects whose recipe failed,
+    /// their handles give no proxy until the next reconnection.
+    pub fn reconnect(&mut self, globals: &GlobalManager) -> Vec<&'static str> {
+        self.globals = globals.clone();
+        let mut failed = Vec::new();
+        let mut i = 0;
+        while i < self.objects.len() {
+            let rebuilt = {
+                let rebuild = &mut self.objects[i].1;
+                rebuild(globals)
+            };
+            match rebuilt {
+                Some(created) => {
+                    if !created {
+                        failed.push(self.objects[i].0);
+                    }
+                    i += 1;
+                }
+                None => {
+                    drop(self.objects.remove(i));
+                }
+            }
+        }
+        failed
+    }
+}
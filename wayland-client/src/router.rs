@@ -0,0 +1,358 @@
+//! Routing content between several connections
+//!
+//! The proxies belong to the connection they were created on. An application holding
+//! connections to two servers, like the preview window of a nested session (connected
+//! both to the host compositor and to the nested one) or a portal, must recreate on
+//! each server the objects the contents it moves between them need.
+//!
+//! A `Router` holds such connections, along with the globals they advertise. Its
+//! `rebind()` method binds on a connection the global a proxy of another connection
+//! was bound from, and a `SharedBuffer` is a shared memory buffer imported on every
+//! connection it is attached on: it is drawn to once, and shown by all servers.
+
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::unix::io::AsRawFd;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use nix::sys::mman;
+
+use protocol::wl_buffer::{self, RequestsTrait as BufferRequests, WlBuffer};
+use protocol::wl_shm::{Format, RequestsTrait as ShmRequests, WlShm};
+use protocol::wl_shm_pool::{RequestsTrait as PoolRequests, WlShmPool};
+use shm::{bytes_per_pixel, create_file, dead_object, map};
+use {Display, EventQueue, GlobalError, GlobalManager, Interface, NewProxy, Proxy};
+
+/// The identifier of a connection of a `Router`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConnectionId(usize);
+
+struct Connection {
+    display: Display,
+    event_queue: EventQueue,
+    globals: GlobalManager,
+    shm: Option<Proxy<WlShm>>,
+}
+
+/// A set of connections to several servers
+///
+/// See the module documentation for details.
+pub struct Router {
+    connections: Vec<Connection>,
+}
+
+impl Router {
+    /// Create a router without connections
+    pub fn new() -> Router {
+        Router {
+            connections: Vec::new(),
+        }
+    }
+
+    /// Add a connection to this router
+    ///
+    /// The globals of the connection are tracked from now on, they are known once its
+    /// event queue has been dispatched after a roundtrip with the server.
+    pub fn add(&mut self, display: Display, event_queue: EventQueue) -> ConnectionId {
+        let globals = GlobalManager::new(&display);
+        self.connections.push(Connection {
+            display,
+            event_queue,
+            globals,
+            shm: None,
+        });
+        ConnectionId(self.connections.len() - 1)
+    }
+
+    /// The identifiers of the connections of this router, in the order they were added
+    pub fn connections(&self) -> Connections {
+        Connections {
+            ids: 0..self.connections.len(),
+        }
+    }
+
+    /// The display of a connection
+    pub fn display(&self, id: ConnectionId) -> &Display {
+        &self.connections[id.0].display
+    }
+
+    /// The event queue of a connection
+    pub fn event_queue(&mut self, id: ConnectionId) -> &mut EventQueue {
+        &mut self.connections[id.0].event_queue
+    }
+
+    /// The globals advertised on a connection
+    pub fn globals(&self, id: ConnectionId) -> &GlobalManager {
+        &self.connections[id.0].globals
+    }
+
+    /// Bind on a connection the global interface of a proxy, with the same version
+    ///
+    /// This is meant for the globals existing once on each server, like `wl_compositor`
+    /// or `wl_shm`: the requests the application sends to `proxy` can then be sent to the
+    /// returned one. Fails with `GlobalError::VersionTooLow` if the connection does not
+    /// advertise the version of `proxy`.
+    pub fn rebind<I: Interface, F>(
+        &self,
+        proxy: &Proxy<I>,
+        to: ConnectionId,
+        implementor: F,
+    ) -> Result<Proxy<I>, GlobalError>
+    where
+        F: FnOnce(NewProxy<I>) -> Proxy<I>,
+    {
+        self.globals(to).instantiate_exact(proxy.version(), implementor)
+    }
+
+    /// Flush all the connections
+    ///
+    /// The first error is returned, after all the connections were flushed.
+    pub fn flush_all(&self) -> io::Result<()> {
+        let mut ret = Ok(());
+        for connection in &self.connections {
+            let flushed = connection.display.flush();
+            if ret.is_ok() {
+                ret = flushed;
+            }
+        }
+        ret
+    }
+
+    /// Dispatch the pending events of all the connections
+    ///
+    /// On success returns the total number of dispatched events.
+    pub fn dispatch_pending(&mut self) -> io::Result<u32> {
+        let mut dispatched = 0;
+        for connection in &mut self.connections {
+            dispatched += connection.event_queue.dispatch_pending()?;
+        }
+        Ok(dispatched)
+    }
+
+    // the `wl_shm` global of a connection, bound on first use
+    fn shm(&mut self, id: ConnectionId) -> io::Result<Proxy<WlShm>> {
+        let connection = &mut self.connections[id.0];
+        if let Some(ref shm) = connection.shm {
+            if shm.is_alive() {
+                return Ok(shm.clone());
+            }
+        }
+        let shm = connection
+            .globals
+            .instantiate_auto::<WlShm, _>(|newp| newp.implement(|_, _| {}, ()))
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "no wl_shm global"))?;
+        connection.shm = Some(shm.clone());
+        Ok(shm)
+    }
+}
+
+impl Default for Router {
+    fn default() -> Router {
+        Router::new()
+    }
+}
+
+/// Iterator over the identifiers of the connections of a `Router`
+///
+/// It is created by `Router::connections()`.
+pub struct Connections {
+    ids: Range<usize>,
+}
+
+impl Iterator for Connections {
+    type Item = ConnectionId;
+
+    fn next(&mut self) -> Option<ConnectionId> {
+        self.ids.next().map(ConnectionId)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
+    }
+}
+
+// the import of a shared buffer on a connection
+struct Import {
+    connection: ConnectionId,
+    pool: Proxy<WlShmPool>,
+    buffer: Proxy<WlBuffer>,
+    // set when the buffer is handed out, until the server releases it
+    busy: Arc<AtomicBool>,
+}
+
+impl Import {
+    fn destroy(self) {
+        self.buffer.destroy();
+        self.pool.destroy();
+    }
+}
+
+/// A shared memory buffer shown on several connections of a `Router`
+///
+/// Its memory file is sent to each server the first time the buffer is requested for
+/// its connection. Each server releases its `wl_buffer` independently: the contents
+/// must not be drawn to until all the servers it was attached on released it, which
+/// `is_released()` tells.
+pub struct SharedBuffer {
+    file: File,
+    map: *mut u8,
+    len: usize,
+    width: i32,
+    height: i32,
+    stride: i32,
+    format: Format,
+    imports: Vec<Import>,
+}
+
+impl SharedBuffer {
+    /// Allocate a buffer of given size and format
+    ///
+    /// An error is returned if the memory could not be allocated, or if the format is not
+    /// in `shm::bytes_per_pixel()`.
+    pub fn new(width: i32, height: i32, format: Format) -> io::Result<SharedBuffer> {
+        let bpp = match bytes_per_pixel(format) {
+            Some(bpp) => bpp,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported shm format",
+                ))
+            }
+        };
+        if width <= 0 || height <= 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid buffer size"));
+        }
+        let stride = width * bpp as i32;
+        let len = stride as usize * height as usize;
+        let file = create_file()?;
+        file.set_len(len as u64)?;
+        let map = map(file.as_raw_fd(), len)?;
+        Ok(SharedBuffer {
+            file,
+            map,
+            len,
+            width,
+            height,
+            stride,
+            format,
+            imports: Vec::new(),
+        })
+    }
+
+    /// The width of this buffer, in pixels
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// The height of this buffer, in pixels
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// The number of bytes between the starts of two rows of this buffer
+    pub fn stride(&self) -> i32 {
+        self.stride
+    }
+
+    /// The format of this buffer
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The bytes of this buffer, row by row
+    pub fn data(&mut self) -> &mut [u8] {
+        // the mapping lives as long as this buffer, which is borrowed mutably
+        unsafe { slice::from_raw_parts_mut(self.map, self.len) }
+    }
+
+    /// The `wl_buffer` of this buffer on a connection
+    ///
+    /// It is created on the first call for this connection, from the `wl_shm` global
+    /// of the router, and kept until `forget()` is called or this buffer is dropped.
+    ///
+    /// The returned `wl_buffer` is expected to be attached to a surface: it is considered
+    /// held by the server from this call until the server releases it.
+    pub fn buffer(
+        &mut self,
+        router: &mut Router,
+        connection: ConnectionId,
+    ) -> io::Result<Proxy<WlBuffer>> {
+        if let Some(import) = self.imports.iter().find(|import| import.connection == connection) {
+            import.busy.store(true, Ordering::Release);
+            return Ok(import.buffer.clone());
+        }
+        let shm = router.shm(connection)?;
+        let pool = shm
+            .create_pool(self.file.as_raw_fd(), self.len as i32, |newp| {
+                newp.implement(|_, _| {}, ())
+            }).map_err(|()| dead_object("wl_shm"))?;
+        let busy = Arc::new(AtomicBool::new(true));
+        let released = busy.clone();
+        let buffer = match pool.create_buffer(
+            0,
+            self.width,
+            self.height,
+            self.stride,
+            self.format,
+            move |newp| {
+                newp.implement(
+                    move |event, _| match event {
+                        wl_buffer::Event::Release => released.store(false, Ordering::Release),
+                    },
+                    (),
+                )
+            },
+        ) {
+            Ok(buffer) => buffer,
+            Err(()) => {
+                pool.destroy();
+                return Err(dead_object("wl_shm_pool"));
+            }
+        };
+        self.imports.push(Import {
+            connection,
+            pool,
+            buffer: buffer.clone(),
+            busy,
+        });
+        Ok(buffer)
+    }
+
+    /// Whether all the servers released this buffer
+    ///
+    /// This is the case when each server sent a `wl_buffer.release` event since the last
+    /// call to `buffer()` for its connection, the contents can then be drawn to again. The
+    /// release events are processed when the event queues of the router are dispatched.
+    pub fn is_released(&self) -> bool {
+        self.imports
+            .iter()
+            .all(|import| !import.busy.load(Ordering::Acquire))
+    }
+
+    /// Destroy the `wl_buffer` of this buffer on a connection, if any
+    ///
+    /// This is typically done before the connection is closed.
+    pub fn forget(&mut self, connection: ConnectionId) {
+        if let Some(index) = self
+            .imports
+            .iter()
+            .position(|import| import.connection == connection)
+        {
+            self.imports.remove(index).destroy();
+        }
+    }
+}
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        for import in self.imports.drain(..) {
+            import.destroy();
+        }
+        unsafe {
+            let _ = mman::munmap(self.map as *mut _, self.len);
+        }
+    }
+}
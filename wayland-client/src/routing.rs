@@ -0,0 +1,215 @@
+// Routing of the events of whole interfaces, see `EventQueue::claim_interface()`, and
+// filtering of the globals denied with `Display::deny_global()`
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use protocol::wl_registry;
+use {Interface, Proxy};
+
+// the implementations claiming interfaces, indexed by interface name
+pub(crate) type Claims = RefCell<HashMap<&'static str, ClaimSlot>>;
+
+pub(crate) struct ClaimSlot {
+    // identifies the claim, so that a `Registration` only releases its own
+    id: usize,
+    // a `Box<FnMut(I::Event, Proxy<I>)>`, taken out of its slot while it runs
+    implementation: Option<Box<Any>>,
+}
+
+// `AtomicUsize::new()` is only const since rust 1.24
+#[allow(deprecated)]
+static NEXT_CLAIM: AtomicUsize = ::std::sync::atomic::ATOMIC_USIZE_INIT;
+
+type Claim<I> = Box<FnMut(<I as Interface>::Event, Proxy<I>)>;
+
+thread_local! {
+    // the claims of the event queue currently dispatching on this thread
+    static ACTIVE: RefCell<Option<Rc<Claims>>> = RefCell::new(None);
+    // the global filter of the connection currently dispatching on this thread
+    static FILTER: RefCell<Option<Arc<GlobalFilter>>> = RefCell::new(None);
+}
+
+/// The environment variable listing the interfaces of the globals to deny
+pub(crate) const DENY_GLOBALS_VAR: &str = "WAYLAND_RS_DENY_GLOBALS";
+
+// the globals hidden from the registries of a connection
+pub(crate) struct GlobalFilter {
+    denied: Mutex<HashSet<String>>,
+    // the names of the globals which were hidden, so that their removal is hidden too
+    hidden: Mutex<HashSet<u32>>,
+}
+
+impl GlobalFilter {
+    // a filter denying the interfaces listed in `WAYLAND_RS_DENY_GLOBALS`, separated by commas
+    pub(crate) fn from_env() -> GlobalFilter {
+        let denied = env::var(DENY_GLOBALS_VAR)
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|interface| !interface.is_empty())
+                    .map(String::from)
+                    .collect()
+            }).unwrap_or_default();
+        GlobalFilter {
+            denied: Mutex::new(denied),
+            hidden: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub(crate) fn deny(&self, interface: &str) {
+        self.denied.lock().unwrap().insert(interface.into());
+    }
+
+    fn is_active(&self) -> bool {
+        !self.denied.lock().unwrap().is_empty()
+    }
+
+    // whether an event of a registry must be hidden from its implementation
+    fn hides(&self, event: &wl_registry::Event) -> bool {
+        match *event {
+            wl_registry::Event::Global {
+                name, ref interface, ..
+            } => {
+                if self.denied.lock().unwrap().contains(interface) {
+                    self.hidden.lock().unwrap().insert(name);
+                    true
+                } else {
+                    false
+                }
+            }
+            wl_registry::Event::GlobalRemove { name } => self.hidden.lock().unwrap().contains(&name),
+        }
+    }
+}
+
+// claim an interface, returning the id of the claim
+pub(crate) fn claim<I, F>(claims: &Claims, implementation: F) -> usize
+where
+    I: Interface,
+    F: FnMut(I::Event, Proxy<I>) + 'static,
+{
+    let implementation: Claim<I> = Box::new(implementation);
+    let id = NEXT_CLAIM.fetch_add(1, Ordering::Relaxed);
+    claims.borrow_mut().insert(
+        I::NAME,
+        ClaimSlot {
+            id,
+            implementation: Some(Box::new(implementation) as Box<Any>),
+        },
+    );
+    id
+}
+
+pub(crate) fn release<I: Interface>(claims: &Claims) -> bool {
+    claims.borrow_mut().remove(I::NAME).is_some()
+}
+
+// release an interface if it is still claimed by the claim `id`
+pub(crate) fn release_claim(claims: &Claims, interface: &str, id: usize) {
+    let mut claims = claims.borrow_mut();
+    let current = match claims.get(interface) {
+        Some(slot) => slot.id == id,
+        None => false,
+    };
+    if current {
+        claims.remove(interface);
+    }
+}
+
+// restores the previous claims and global filter when dropped, even if an implementation panics
+struct Restore(Option<Rc<Claims>>, Option<Arc<GlobalFilter>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| *active.borrow_mut() = self.0.take());
+        FILTER.with(|active| *active.borrow_mut() = self.1.take());
+    }
+}
+
+/// Run a dispatching closure with the claims of an event queue and the global filter of
+/// its connection active
+pub(crate) fn with_claims<T, F: FnOnce() -> T>(claims: &Rc<Claims>, filter: &Arc<GlobalFilter>, f: F) -> T {
+    let new_active = if claims.borrow().is_empty() {
+        None
+    } else {
+        Some(claims.clone())
+    };
+    let new_filter = if filter.is_active() {
+        Some(filter.clone())
+    } else {
+        None
+    };
+    let previous = ACTIVE.with(|active| ::std::mem::replace(&mut *active.borrow_mut(), new_active));
+    let previous_filter = FILTER.with(|active| ::std::mem::replace(&mut *active.borrow_mut(), new_filter));
+    let _restore = Restore(previous, previous_filter);
+    f()
+}
+
+/// Whether the events of an interface are claimed by the event queue currently dispatching
+#[cfg_attr(feature = "native_lib", allow(dead_code))]
+pub(crate) fn is_claimed(interface: &str) -> bool {
+    ACTIVE.with(|active| match *active.borrow() {
+        Some(ref claims) => claims.borrow().contains_key(interface),
+        None => false,
+    })
+}
+
+/// Whether the events of an interface are filtered on the connection currently dispatching
+#[cfg_attr(feature = "native_lib", allow(dead_code))]
+pub(crate) fn is_filtered(interface: &str) -> bool {
+    interface == wl_registry::WlRegistry::NAME && FILTER.with(|active| active.borrow().is_some())
+}
+
+/// Give an event to the implementation claiming its interface, if any
+///
+/// Returns the event back if it is not claimed, it must then be given to the implementation
+/// of its proxy. The registry events of the denied globals are dropped.
+pub(crate) fn route<I: Interface>(event: I::Event, proxy: Proxy<I>) -> Option<(I::Event, Proxy<I>)> {
+    if I::NAME == wl_registry::WlRegistry::NAME {
+        let hidden = FILTER.with(|active| match *active.borrow() {
+            Some(ref filter) => match (&event as &Any).downcast_ref::<wl_registry::Event>() {
+                Some(event) => filter.hides(event),
+                None => false,
+            },
+            None => false,
+        });
+        if hidden {
+            return None;
+        }
+    }
+    let claims = match ACTIVE.with(|active| active.borrow().clone()) {
+        Some(claims) => claims,
+        None => return Some((event, proxy)),
+    };
+    // don't keep the claims borrowed while the implementation runs, it may dispatch the
+    // queue again or change the claims
+    let claim = match claims.borrow_mut().get_mut(I::NAME) {
+        Some(slot) => slot.implementation.take().map(|claim| (slot.id, claim)),
+        None => None,
+    };
+    let (id, mut claim) = match claim {
+        Some(claim) => claim,
+        None => return Some((event, proxy)),
+    };
+    let ret = match claim.downcast_mut::<Claim<I>>() {
+        Some(implementation) => {
+            implementation(event, proxy);
+            None
+        }
+        // an other interface with the same name, like the anonymous objects
+        None => Some((event, proxy)),
+    };
+    if let Some(slot) = claims.borrow_mut().get_mut(I::NAME) {
+        // unless the interface was released or claimed again in the meantime
+        if slot.id == id {
+            slot.implementation = Some(claim);
+        }
+    }
+    ret
+}
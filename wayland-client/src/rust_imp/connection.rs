@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::os::unix::io::{FromRawFd, RawFd};
 use std::sync::{Arc, Mutex};
 
@@ -6,11 +7,92 @@ use nix::Result as NixResult;
 
 use wayland_commons::map::{Object, ObjectMap};
 use wayland_commons::socket::{BufferedSocket, Socket};
-use wayland_commons::wire::{Argument, ArgumentType, Message, MessageParseError};
+use wayland_commons::trace::ProtocolLogger;
+use wayland_commons::wire::{dup_fd_cloexec, Argument, ArgumentType, Message, MessageParseError};
+
+use clock::SharedClock;
+use ProtocolError;
 
 use super::proxy::ObjectMeta;
 use super::queues::QueueBuffer;
 
+/// Requests waiting to be written to the socket
+///
+/// Proxies append their requests here rather than writing them directly, so that
+/// sending from several threads does not contend on the connection lock (which is
+/// also held while events are read). The buffer is then moved into the socket each
+/// time the connection is flushed.
+///
+/// The buffer is a single queue rather than one per thread: the requests of all the
+/// threads need a total order, so that the ids of new objects reach the server in the
+/// order they were allocated and no request follows the destructor of its object.
+/// The lock is only held to append a request, which keeps the contention low.
+///
+/// The requests with file descriptors are written to the socket directly by
+/// `Connection::write_request()`, as the socket keeps its own copies of their fds.
+/// Only the ones which could not be written, when the socket is full, are stored in
+/// the buffer with copies of their fds.
+pub(crate) type OutgoingBuffer = Arc<Mutex<Outgoing>>;
+
+pub(crate) fn create_outgoing_buffer() -> OutgoingBuffer {
+    Arc::new(Mutex::new(Outgoing {
+        messages: VecDeque::new(),
+        queued: 0,
+        logger: None,
+    }))
+}
+
+pub(crate) struct Outgoing {
+    pub(crate) messages: VecDeque<Message>,
+    // number of requests ever queued, they are written to the socket in this order
+    queued: u64,
+    // the logger of the messages of the connection, see `Display::set_protocol_logger()`
+    pub(crate) logger: Option<ProtocolLogger>,
+}
+
+impl Outgoing {
+    /// The trace id the next queued request will have
+    pub(crate) fn next_trace_id(&self) -> u64 {
+        self.queued + 1
+    }
+
+    /// Append a request without file descriptors, see `Connection::write_request()` otherwise
+    pub(crate) fn push(&mut self, msg: Message) {
+        self.messages.push_back(msg);
+        self.queued += 1;
+    }
+}
+
+// the file descriptors of a message stored in the outgoing buffer are `dup()`-ed, as the
+// caller is free to close them as soon as the message is sent
+fn prepare_outgoing(mut msg: Message) -> NixResult<Message> {
+    for i in 0..msg.args.len() {
+        if let Argument::Fd(fd) = msg.args[i] {
+            match dup_fd_cloexec(fd) {
+                Ok(copy) => msg.args[i] = Argument::Fd(copy),
+                Err(e) => {
+                    // do not leak the copies made so far
+                    for arg in &msg.args[..i] {
+                        if let Argument::Fd(copy) = *arg {
+                            let _ = ::nix::unistd::close(copy);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+    Ok(msg)
+}
+
+fn close_outgoing_fds(msg: &Message) {
+    for arg in &msg.args {
+        if let Argument::Fd(fd) = *arg {
+            let _ = ::nix::unistd::close(fd);
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) enum Error {
     Protocol,
@@ -23,15 +105,29 @@ pub(crate) struct Connection {
     pub(crate) map: Arc<Mutex<ObjectMap<ObjectMeta>>>,
     pub(crate) last_error: Arc<Mutex<Option<Error>>>,
     pub(crate) display_buffer: QueueBuffer,
+    pub(crate) outgoing: OutgoingBuffer,
+    pub(crate) clock: SharedClock,
+    pub(crate) protocol_error: Option<ProtocolError>,
+    // number of event queues with a latency hook, the input events are only stamped if
+    // there are some
+    pub(crate) latency_hooks: usize,
 }
 
 impl Connection {
     pub(crate) unsafe fn new(fd: RawFd, display_object: Object<ObjectMeta>) -> Connection {
-        let socket = BufferedSocket::new(Socket::from_raw_fd(fd));
+        #[allow(unused_mut)]
+        let mut socket = Socket::from_raw_fd(fd);
+        #[cfg(feature = "io_uring")]
+        {
+            // without io_uring support, keep using the direct system calls
+            let _ = socket.enable_io_uring();
+        }
+        let socket = BufferedSocket::new(socket);
 
         let mut map = ObjectMap::new();
         // Insert first pre-existing object
         let display_buffer = display_object.meta.buffer.clone();
+        let outgoing = display_object.meta.outgoing.clone();
         map.insert_at(1, display_object).unwrap();
 
         Connection {
@@ -39,14 +135,90 @@ impl Connection {
             map: Arc::new(Mutex::new(map)),
             last_error: Arc::new(Mutex::new(None)),
             display_buffer,
+            outgoing,
+            clock: SharedClock::new(),
+            protocol_error: None,
+            latency_hooks: 0,
         }
     }
 
-    pub(crate) fn write_message(&mut self, msg: &Message) -> NixResult<()> {
-        self.socket.write_message(msg)
+    /// Move the pending requests of the outgoing buffer into the socket
+    ///
+    /// This may flush the socket if its buffer gets full. If it fails with `EAGAIN`,
+    /// the requests that could not be written are kept in the outgoing buffer.
+    pub(crate) fn write_outgoing(&mut self) -> NixResult<()> {
+        // take the whole buffer, so that senders are not blocked while we write to the socket
+        let mut pending = VecDeque::new();
+        ::std::mem::swap(&mut pending, &mut self.outgoing.lock().unwrap().messages);
+        let ret = self.write_pending(&mut pending);
+        if !pending.is_empty() {
+            // put the leftover back in front of the requests sent in the meantime,
+            // we hold the connection lock so no other thread is writing
+            let mut outgoing = self.outgoing.lock().unwrap();
+            pending.extend(outgoing.messages.drain(..));
+            outgoing.messages = pending;
+        }
+        ret
+    }
+
+    /// Write a request with file descriptors to the socket
+    ///
+    /// The pending requests of the outgoing buffer are written first. `outgoing` is the
+    /// locked outgoing buffer, which the caller holds since it checked that the object
+    /// sending the request is alive.
+    ///
+    /// If the socket is full, the request is stored in the outgoing buffer with copies of
+    /// its file descriptors, and `EAGAIN` is returned. If they cannot be copied, like when
+    /// the process ran out of file descriptors, the request is lost: the connection is then
+    /// dead, as for the fatal errors of `read_events()`, and the error is returned.
+    pub(crate) fn write_request(&mut self, outgoing: &mut Outgoing, msg: Message) -> NixResult<()> {
+        let mut pending = VecDeque::new();
+        ::std::mem::swap(&mut pending, &mut outgoing.messages);
+        let mut ret = self.write_pending(&mut pending);
+        if ret.is_ok() {
+            ret = self.socket.write_message(&msg);
+        }
+        match ret {
+            Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => match prepare_outgoing(msg) {
+                Ok(msg) => pending.push_back(msg),
+                Err(e) => {
+                    *self.last_error.lock().unwrap() = Some(Error::Nix(e));
+                    ret = Err(e);
+                }
+            },
+            // the socket dup()-ed the fds if the message was written, or the connection is dead
+            _ => {}
+        }
+        outgoing.messages = pending;
+        outgoing.queued += 1;
+        ret
+    }
+
+    // write the given requests to the socket, the ones that could not be written because
+    // the socket is full are left in `pending`
+    fn write_pending(&mut self, pending: &mut VecDeque<Message>) -> NixResult<()> {
+        while let Some(msg) = pending.pop_front() {
+            match self.socket.write_message(&msg) {
+                Ok(()) => close_outgoing_fds(&msg),
+                Err(e @ ::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => {
+                    pending.push_front(msg);
+                    return Err(e);
+                }
+                Err(e) => {
+                    // fatal error, the connection is dead
+                    close_outgoing_fds(&msg);
+                    for msg in pending.drain(..) {
+                        close_outgoing_fds(&msg);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
     }
 
     pub(crate) fn flush(&mut self) -> NixResult<()> {
+        self.write_outgoing()?;
         self.socket.flush()
     }
 
@@ -60,6 +232,13 @@ impl Connection {
         // wrap it in a RefCell for cheap sharing in the two closures below
         let map = RefCell::new(&mut *map);
         let mut last_error = self.last_error.lock().unwrap();
+        let clock = if self.latency_hooks > 0 {
+            Some(self.clock.get())
+        } else {
+            None
+        };
+        let mut trace_id = self.socket.received_messages();
+        let mut protocol_error = None;
         // read messages
         let ret = self.socket.read_messages(
             |id, opcode| {
@@ -69,6 +248,7 @@ impl Connection {
                     .map(|desc| desc.signature)
             },
             |msg| {
+                trace_id += 1;
                 let mut map = map.borrow_mut();
                 let object = match map.find(msg.sender_id) {
                     Some(obj) => obj,
@@ -86,7 +266,30 @@ impl Connection {
                     }
                 };
 
-                // create a new object if applicable
+                // protocol errors are recorded as soon as they are read, the connection is
+                // usually dead by the time the error event would be dispatched
+                if msg.sender_id == 1 && msg.opcode == 0 {
+                    if let (
+                        Some(&Argument::Object(id)),
+                        Some(&Argument::Uint(code)),
+                        Some(&Argument::Str(ref text)),
+                    ) = (msg.args.first(), msg.args.get(1), msg.args.get(2))
+                    {
+                        let interface = map.find(id).map(|o| o.interface).unwrap_or("<unknown>");
+                        let error = ProtocolError {
+                            code,
+                            object_id: id,
+                            object_interface: interface.into(),
+                            message: text.to_string_lossy().into_owned(),
+                        };
+                        eprintln!("[wayland-client] {}", error);
+                        protocol_error = Some(error);
+                        *last_error = Some(Error::Protocol);
+                    }
+                }
+
+                // create a new object if applicable, as in libwayland it has the version
+                // of the object which received the event
                 if let Some(child) = object.event_child(msg.opcode) {
                     let new_id = msg
                         .args
@@ -101,6 +304,7 @@ impl Connection {
                         .next()
                         .unwrap();
                     let child_interface = child.interface;
+                    let child_quotas = child.meta.quotas.clone();
                     if let Err(()) = map.insert_at(new_id, child) {
                         eprintln!(
                             "[wayland-client] Protocol error: server tried to create an object \"{}\" with invalid id \"{}\".",
@@ -111,18 +315,26 @@ impl Connection {
                         *last_error = Some(Error::Protocol);
                         return false;
                     }
+                    child_quotas.created(child_interface);
                 } else {
                     // debug assert: if this opcode does not define a child, then there should be no
                     // NewId argument
                     debug_assert!(msg.args.iter().any(|a| a.get_type() == ArgumentType::NewId) == false);
                 }
 
+                let received = match clock {
+                    Some(ref clock) if ::latency::is_input(object.interface) => Some(clock.now()),
+                    _ => None,
+                };
                 // send the message to the appropriate pending queue
-                object.meta.buffer.lock().unwrap().push_back(msg);
+                object.meta.buffer.lock().unwrap().push_back((msg, received, trace_id));
                 // continue parsing
                 true
             },
         );
+        if protocol_error.is_some() {
+            self.protocol_error = protocol_error;
+        }
         match ret {
             Ok(Ok(n)) => if let Some(ref e) = *last_error {
                 Err(e.clone())
@@ -143,3 +355,13 @@ impl Connection {
         }
     }
 }
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // release the fds of the requests that were never sent
+        let mut outgoing = self.outgoing.lock().unwrap();
+        for msg in outgoing.messages.drain(..) {
+            close_outgoing_fds(&msg);
+        }
+    }
+}
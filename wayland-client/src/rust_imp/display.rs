@@ -1,13 +1,18 @@
 use std::io;
-use std::os::unix::io::RawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync::{Arc, Mutex};
 
-use wayland_commons::map::Object;
+use wayland_commons::map::{Object, ObjectMetadata, SERVER_ID_LIMIT};
+use wayland_commons::socket::ConnectionStats;
+use wayland_commons::trace::ProtocolLogger;
 use wayland_commons::utils::UserData;
 
 use protocol::wl_display::{self, WlDisplay};
 
-use {ConnectError, Proxy};
+use clock::SharedClock;
+use quotas::ObjectQuotas;
+use takeover::{TakeoverError, TakeoverObject, TakeoverState};
+use {ConnectError, Interface, ProtocolError, Proxy};
 
 use super::connection::Connection;
 use super::proxy::{NewProxyInner, ObjectMeta};
@@ -18,6 +23,20 @@ pub(crate) struct DisplayInner {
     proxy: Proxy<WlDisplay>,
 }
 
+// libwayland does not allocate more objects in each namespace, larger ids in a takeover state
+// are not ones of a real connection, and would make the map allocate them all
+const MAX_OBJECTS: u32 = 0x00f0_0000;
+
+// whether an object of a takeover state can have this id
+fn valid_adopted_id(id: u32) -> bool {
+    if id > SERVER_ID_LIMIT {
+        id - SERVER_ID_LIMIT <= MAX_OBJECTS
+    } else {
+        // the display has the id 1, and `SERVER_ID_LIMIT` is the id of no object
+        id > 1 && id <= MAX_OBJECTS
+    }
+}
+
 impl DisplayInner {
     pub unsafe fn from_fd(fd: RawFd) -> Result<(Arc<DisplayInner>, EventQueueInner), ConnectError> {
         // The special buffer for display events
@@ -33,24 +52,12 @@ impl DisplayInner {
 
         // give access to the map to the display impl
         let impl_map = map;
-        let impl_last_error = connection.lock().unwrap().last_error.clone();
+
         // our implementation is Send, we are safe
         let display_proxy = display_newproxy.implement::<WlDisplay, _>(
             move |event, _| match event {
-                wl_display::Event::Error {
-                    object_id,
-                    code,
-                    message,
-                } => {
-                    eprintln!(
-                        "[wayland-client] Protocol error {} on object {}@{}: {}",
-                        code,
-                        object_id.inner.object.interface,
-                        object_id.id(),
-                        message
-                    );
-                    *impl_last_error.lock().unwrap() = Some(super::connection::Error::Protocol);
-                }
+                // protocol errors are handled by the connection, as they are read
+                wl_display::Event::Error { .. } => {}
                 wl_display::Event::DeleteId { id } => {
                     // cleanup the map as appropriate
                     let mut map = impl_map.lock().unwrap();
@@ -77,6 +84,97 @@ impl DisplayInner {
         Ok((Arc::new(display), default_event_queue))
     }
 
+    pub(crate) unsafe fn adopt(
+        fd: RawFd,
+        objects: &[TakeoverObject],
+    ) -> Result<(Arc<DisplayInner>, EventQueueInner), TakeoverError> {
+        let mut ids = objects.iter().map(|object| object.id).collect::<Vec<_>>();
+        ids.sort();
+        // the display has the id 1, and the ids are unique
+        if !ids.iter().all(|&id| valid_adopted_id(id)) || ids.windows(2).any(|w| w[0] == w[1]) {
+            let _ = ::nix::unistd::close(fd);
+            return Err(TakeoverError::Invalid);
+        }
+        let (display, event_queue) = DisplayInner::from_fd(fd).map_err(|_| TakeoverError::Invalid)?;
+        {
+            let connection = display.connection.lock().unwrap();
+            let mut map = connection.map.lock().unwrap();
+            let meta = map.find(1).unwrap().meta;
+            // reserve the ids of the objects, they are filled in by `adopt_object()`, the
+            // ones in between are left free for the new objects
+            let (mut next_client, mut next_server) = (2, SERVER_ID_LIMIT + 1);
+            let mut gaps = Vec::new();
+            for id in ids {
+                let next = if id > SERVER_ID_LIMIT {
+                    &mut next_server
+                } else {
+                    &mut next_client
+                };
+                while *next <= id {
+                    map.insert_at(*next, Object::placeholder(meta.child()))
+                        .map_err(|()| TakeoverError::Invalid)?;
+                    if *next != id {
+                        gaps.push(*next);
+                    }
+                    *next += 1;
+                }
+            }
+            for id in gaps {
+                map.remove(id);
+            }
+        }
+        Ok((display, event_queue))
+    }
+
+    pub(crate) fn adopt_object<I: Interface>(&self, id: u32, version: u32) -> Option<NewProxyInner> {
+        let map = self.connection.lock().unwrap().map.clone();
+        let adopted = map.lock().unwrap().with(id, |object| {
+            *object = Object::from_interface::<I>(version, object.meta.clone());
+            object.meta.quotas.created(I::NAME);
+        });
+        match adopted {
+            Ok(()) => NewProxyInner::from_id(id, map, self.connection.clone()),
+            Err(()) => None,
+        }
+    }
+
+    pub(crate) fn prepare_takeover(&self) -> Result<TakeoverState, TakeoverError> {
+        let mut connection = self.connection.lock().unwrap();
+        match connection.flush() {
+            Ok(()) => {}
+            Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => return Err(TakeoverError::Pending),
+            Err(::nix::Error::Sys(errno)) => return Err(TakeoverError::Io(errno.into())),
+            Err(_) => unreachable!(),
+        }
+        if connection.socket.has_incoming_data() || !connection.display_buffer.lock().unwrap().is_empty() {
+            return Err(TakeoverError::Pending);
+        }
+        let mut objects = Vec::new();
+        let mut pending = false;
+        connection.map.lock().unwrap().with_all(|id, object| {
+            // destroyed objects must get their `delete_id` before their id can be handed over
+            pending |= object.meta.client_destroyed
+                || object.meta.server_destroyed
+                || !object.meta.buffer.lock().unwrap().is_empty();
+            if id != 1 {
+                objects.push(TakeoverObject {
+                    id,
+                    interface: object.interface.into(),
+                    version: object.version,
+                });
+            }
+        });
+        if pending {
+            return Err(TakeoverError::Pending);
+        }
+        // the copy of the socket is not `CLOEXEC`, to be inherited by the new process
+        match ::nix::unistd::dup(connection.socket.get_socket().as_raw_fd()) {
+            Ok(fd) => Ok(TakeoverState::new(fd, objects)),
+            Err(::nix::Error::Sys(errno)) => Err(TakeoverError::Io(errno.into())),
+            Err(_) => unreachable!(),
+        }
+    }
+
     pub(crate) fn flush(&self) -> io::Result<()> {
         match self.connection.lock().unwrap().flush() {
             Ok(()) => Ok(()),
@@ -92,4 +190,25 @@ impl DisplayInner {
     pub(crate) fn get_proxy(&self) -> &Proxy<WlDisplay> {
         &self.proxy
     }
+
+    pub(crate) fn quotas(&self) -> &ObjectQuotas {
+        &self.proxy.inner.object.meta.quotas
+    }
+
+    pub(crate) fn clock(&self) -> SharedClock {
+        self.connection.lock().unwrap().clock.clone()
+    }
+
+    pub(crate) fn set_protocol_logger(&self, logger: Option<ProtocolLogger>) {
+        let outgoing = self.connection.lock().unwrap().outgoing.clone();
+        outgoing.lock().unwrap().logger = logger;
+    }
+
+    pub(crate) fn connection_stats(&self) -> Option<ConnectionStats> {
+        Some(self.connection.lock().unwrap().socket.stats())
+    }
+
+    pub(crate) fn protocol_error(&self) -> Option<ProtocolError> {
+        self.connection.lock().unwrap().protocol_error.clone()
+    }
 }
@@ -1,16 +1,18 @@
-use std::sync::atomic::Ordering;
+use std::any::Any;
 use std::sync::{Arc, Mutex};
 
 use downcast::Downcast;
 
 use wayland_commons::map::ObjectMap;
-use wayland_commons::wire::Message;
+use wayland_commons::wire::{Argument, Message};
 use wayland_commons::MessageGroup;
 
+use bindings::RawArgument;
 use {Interface, NewProxy, Proxy};
 
 mod connection;
 mod display;
+mod pool;
 mod proxy;
 mod queues;
 
@@ -60,6 +62,10 @@ impl ProxyMap {
 
 pub(crate) trait Dispatcher: Downcast + Send {
     fn dispatch(&mut self, msg: Message, proxy: ProxyInner, map: &mut ProxyMap) -> Result<(), ()>;
+    // dispatch an already parsed event, which must be a `Box<I::Event>`
+    fn dispatch_injected(&mut self, _event: Box<Any>, _proxy: ProxyInner) -> Result<(), ()> {
+        Err(())
+    }
 }
 
 mod dispatcher_impl {
@@ -92,32 +98,33 @@ where
     I::Event: MessageGroup<Map = ProxyMap>,
 {
     fn dispatch(&mut self, msg: Message, proxy: ProxyInner, map: &mut ProxyMap) -> Result<(), ()> {
-        if ::std::env::var_os("WAYLAND_DEBUG").is_some() {
-            println!(
-                " <- {}@{}: {} {:?}",
-                proxy.object.interface, proxy.id, proxy.object.events[msg.opcode as usize].name, msg.args
-            );
+        if !::dedup::keep::<I, _>(&proxy, msg.opcode, || ::dedup::comparable(&msg.args)) {
+            return Ok(());
         }
         let message = I::Event::from_raw(msg, map)?;
+        self.call(message, proxy);
+        Ok(())
+    }
+
+    fn dispatch_injected(&mut self, event: Box<Any>, proxy: ProxyInner) -> Result<(), ()> {
+        let message = event.downcast::<I::Event>().map_err(|_| ())?;
+        self.call(*message, proxy);
+        Ok(())
+    }
+}
+
+impl<I, F> ImplDispatcher<I, F>
+where
+    I: Interface,
+    F: FnMut(I::Event, Proxy<I>) + 'static,
+{
+    fn call(&mut self, message: I::Event, proxy: ProxyInner) {
         if message.is_destructor() {
-            proxy.object.meta.alive.store(false, Ordering::Release);
-            {
-                // cleanup the map as appropriate
-                let mut map = proxy.map.lock().unwrap();
-                let server_destroyed = map
-                    .with(proxy.id, |obj| {
-                        obj.meta.client_destroyed = true;
-                        obj.meta.server_destroyed
-                    }).unwrap_or(false);
-                if server_destroyed {
-                    map.remove(proxy.id);
-                }
-            }
-            (self.implementation)(message, Proxy::<I>::wrap(proxy.clone()));
-        } else {
-            (self.implementation)(message, Proxy::<I>::wrap(proxy));
+            proxy.cleanup_destroyed(proxy.id);
+        }
+        if let Some((message, proxy)) = ::routing::route(message, Proxy::<I>::wrap(proxy)) {
+            (self.implementation)(message, proxy);
         }
-        Ok(())
     }
 }
 
@@ -133,6 +140,49 @@ where
     }))
 }
 
+pub(crate) struct RawDispatcher {
+    implementation: Box<FnMut(u16, Vec<RawArgument>, ProxyInner)>,
+}
+
+// Same as for ImplDispatcher, NewProxyInner::implement_raw requires to be
+// called from the thread of the event queue of the object.
+unsafe impl Send for RawDispatcher {}
+
+impl Dispatcher for RawDispatcher {
+    fn dispatch(&mut self, msg: Message, proxy: ProxyInner, map: &mut ProxyMap) -> Result<(), ()> {
+        let mut args = Vec::with_capacity(msg.args.len());
+        for arg in msg.args {
+            args.push(match arg {
+                Argument::Int(i) => RawArgument::Int(i),
+                Argument::Uint(u) => RawArgument::Uint(u),
+                Argument::Fixed(f) => RawArgument::Fixed(f),
+                Argument::Str(s) => RawArgument::Str(Some(s)),
+                Argument::Array(a) => RawArgument::Array(a),
+                Argument::ArrayU64(_) => return Err(()),
+                Argument::Fd(fd) => RawArgument::Fd(fd),
+                Argument::Object(0) => RawArgument::Object(None),
+                Argument::Object(id) => RawArgument::Object(Some(
+                    ProxyInner::from_id(id, map.map.clone(), map.connection.clone()).ok_or(())?,
+                )),
+                Argument::NewId(id) => RawArgument::NewId(
+                    NewProxyInner::from_id(id, map.map.clone(), map.connection.clone()).ok_or(())?,
+                ),
+            });
+        }
+        if proxy.object.events[msg.opcode as usize].destructor {
+            proxy.cleanup_destroyed(proxy.id);
+        }
+        (self.implementation)(msg.opcode, args, proxy);
+        Ok(())
+    }
+}
+
+pub(crate) unsafe fn make_raw_dispatcher(
+    implementation: Box<FnMut(u16, Vec<RawArgument>, ProxyInner)>,
+) -> Arc<Mutex<Dispatcher + Send>> {
+    Arc::new(Mutex::new(RawDispatcher { implementation }))
+}
+
 pub(crate) fn default_dispatcher() -> Arc<Mutex<Dispatcher + Send>> {
     struct DefaultDisp;
     impl Dispatcher for DefaultDisp {
@@ -0,0 +1,110 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use wayland_commons::wire::Message;
+
+use super::{ProxyInner, ProxyMap};
+
+// an event to dispatch from a worker thread
+struct Job {
+    msg: Message,
+    proxy: ProxyInner,
+    map: ProxyMap,
+}
+
+// the outcome of a job: whether the dispatching failed, or the panic of the implementation
+type Outcome = thread::Result<Result<(), (&'static str, u32)>>;
+
+/// A pool of threads dispatching the events of `Send` implementations
+///
+/// The events of an object are always given to the same worker, which processes them in
+/// order, so the events of each object are handled in the order they were received. The
+/// events of different objects however run concurrently.
+///
+/// The workers stop once the pool is dropped, after processing the events they were given.
+pub(crate) struct DispatchPool {
+    workers: Vec<Sender<Job>>,
+    outcomes: Receiver<Outcome>,
+    pending: u32,
+}
+
+impl DispatchPool {
+    pub(crate) fn new(threads: usize) -> DispatchPool {
+        let (outcome_sender, outcomes) = mpsc::channel();
+        let workers = (0..threads)
+            .map(|_| {
+                let (sender, jobs) = mpsc::channel();
+                let outcome_sender = outcome_sender.clone();
+                thread::spawn(move || work(jobs, outcome_sender));
+                sender
+            }).collect();
+        DispatchPool {
+            workers,
+            outcomes,
+            pending: 0,
+        }
+    }
+
+    /// Dispatch an event of an object with a `Send` implementation from a worker
+    pub(crate) fn dispatch(&mut self, msg: Message, proxy: ProxyInner, map: ProxyMap) {
+        let worker = proxy.id as usize % self.workers.len();
+        let job = Job { msg, proxy, map };
+        // the workers only stop once the pool is dropped
+        self.workers[worker].send(job).unwrap();
+        self.pending += 1;
+    }
+
+    /// Wait for all the dispatched events to be processed
+    ///
+    /// Returns the number of processed events, or the interface and id of an object whose
+    /// dispatching failed. If an implementation panicked, the panic is resumed here.
+    pub(crate) fn wait(&mut self) -> Result<u32, (&'static str, u32)> {
+        let mut processed = 0;
+        let mut ret = Ok(());
+        let mut panic: Option<Box<Any + Send>> = None;
+        while self.pending > 0 {
+            let outcome = match self.outcomes.recv() {
+                Ok(outcome) => outcome,
+                // the workers catch the panics of the implementations, they cannot be gone
+                Err(_) => unreachable!(),
+            };
+            self.pending -= 1;
+            match outcome {
+                Ok(Ok(())) => processed += 1,
+                Ok(Err(object)) => {
+                    if ret.is_ok() {
+                        ret = Err(object);
+                    }
+                }
+                Err(payload) => {
+                    if panic.is_none() {
+                        panic = Some(payload);
+                    }
+                }
+            }
+        }
+        if let Some(payload) = panic {
+            panic::resume_unwind(payload);
+        }
+        ret.map(|()| processed)
+    }
+}
+
+fn work(jobs: Receiver<Job>, outcomes: Sender<Outcome>) {
+    for job in jobs {
+        let Job { msg, proxy, mut map } = job;
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let object = proxy.object.clone();
+            let id = proxy.id;
+            let mut dispatcher = object.meta.dispatcher.lock().unwrap();
+            dispatcher
+                .dispatch(msg, proxy, &mut map)
+                .map_err(|()| (object.interface, id))
+        }));
+        if outcomes.send(outcome).is_err() {
+            return;
+        }
+    }
+}
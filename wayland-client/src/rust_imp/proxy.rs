@@ -1,35 +1,59 @@
+use std::collections::VecDeque;
+use std::os::unix::io::AsRawFd;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use nix::poll::{poll, EventFlags, PollFd};
 
 use wayland_commons::map::{Object, ObjectMap, ObjectMetadata};
-use wayland_commons::utils::UserData;
-use wayland_commons::wire::{Argument, ArgumentType};
+use wayland_commons::trace::{self, Direction, LoggedMessage};
+use wayland_commons::utils::{UserData, UserDataMap};
+use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
 use wayland_commons::MessageGroup;
 
-use super::connection::Connection;
+use super::connection::{create_outgoing_buffer, Connection, Outgoing, OutgoingBuffer};
 use super::queues::QueueBuffer;
 use super::{Dispatcher, EventQueueInner};
+use bindings::RawArgument;
+use quotas::ObjectQuotas;
 use {Interface, Proxy};
 
+// number of pending requests above which the sender moves them to the socket itself
+const OUTGOING_THRESHOLD: usize = 64;
+// number of pending requests above which the sender waits for the socket to take them
+const OUTGOING_LIMIT: usize = 4096;
+
 #[derive(Clone)]
 pub(crate) struct ObjectMeta {
     pub(crate) buffer: QueueBuffer,
+    pub(crate) outgoing: OutgoingBuffer,
     pub(crate) alive: Arc<AtomicBool>,
     user_data: Arc<UserData>,
+    data_map: Arc<UserDataMap>,
     pub(crate) dispatcher: Arc<Mutex<Dispatcher>>,
     pub(crate) server_destroyed: bool,
     pub(crate) client_destroyed: bool,
+    // whether the implementation is `Send`, and can be dispatched from any thread
+    pub(crate) send: bool,
+    pub(crate) quotas: Arc<ObjectQuotas>,
+    // the liveness flag of the object this one was created from
+    parent: Option<Arc<AtomicBool>>,
 }
 
 impl ObjectMetadata for ObjectMeta {
     fn child(&self) -> ObjectMeta {
         ObjectMeta {
             buffer: self.buffer.clone(),
+            outgoing: self.outgoing.clone(),
             alive: Arc::new(AtomicBool::new(true)),
             user_data: Arc::new(UserData::empty()),
+            data_map: Arc::new(UserDataMap::new()),
             dispatcher: super::default_dispatcher(),
             server_destroyed: false,
             client_destroyed: false,
+            send: false,
+            quotas: self.quotas.clone(),
+            parent: Some(self.alive.clone()),
         }
     }
 }
@@ -38,22 +62,32 @@ impl ObjectMeta {
     pub(crate) fn new(buffer: QueueBuffer) -> ObjectMeta {
         ObjectMeta {
             buffer,
+            outgoing: create_outgoing_buffer(),
             alive: Arc::new(AtomicBool::new(true)),
             user_data: Arc::new(UserData::empty()),
+            data_map: Arc::new(UserDataMap::new()),
             dispatcher: super::default_dispatcher(),
             server_destroyed: false,
             client_destroyed: false,
+            send: false,
+            quotas: Arc::new(ObjectQuotas::new()),
+            parent: None,
         }
     }
 
     fn dead() -> ObjectMeta {
         ObjectMeta {
             buffer: super::queues::create_queue_buffer(),
+            outgoing: create_outgoing_buffer(),
             alive: Arc::new(AtomicBool::new(false)),
             user_data: Arc::new(UserData::empty()),
+            data_map: Arc::new(UserDataMap::new()),
             dispatcher: super::default_dispatcher(),
             server_destroyed: true,
             client_destroyed: true,
+            send: false,
+            quotas: Arc::new(ObjectQuotas::new()),
+            parent: None,
         }
     }
 }
@@ -64,6 +98,8 @@ pub(crate) struct ProxyInner {
     pub(crate) connection: Arc<Mutex<Connection>>,
     pub(crate) object: Object<ObjectMeta>,
     pub(crate) id: u32,
+    // wrappers keep their own queue, other handles follow the queue of the object
+    is_wrapper: bool,
 }
 
 impl ProxyInner {
@@ -78,6 +114,7 @@ impl ProxyInner {
             connection,
             id,
             object: obj,
+            is_wrapper: false,
         })
     }
 
@@ -90,7 +127,11 @@ impl ProxyInner {
     }
 
     pub fn version(&self) -> u32 {
-        self.object.version
+        if self.is_alive() {
+            self.object.version
+        } else {
+            0
+        }
     }
 
     pub(crate) fn id(&self) -> u32 {
@@ -105,45 +146,105 @@ impl ProxyInner {
         self.object.meta.user_data.get::<UD>()
     }
 
+    pub(crate) fn data_map(&self) -> Option<&UserDataMap> {
+        Some(&self.object.meta.data_map)
+    }
+
+    pub(crate) fn requests(&self) -> Option<&'static [MessageDesc]> {
+        Some(self.object.requests)
+    }
+
     pub(crate) fn send<I: Interface>(&self, msg: I::Request) {
-        // grab the connection lock before anything else
+        let destructor = msg.is_destructor();
+        self.send_message(msg.into_raw(self.id), destructor);
+    }
+
+    pub(crate) fn send_raw(&self, opcode: u16, args: Vec<RawArgument>) -> Result<(), ()> {
+        let destructor = match self.object.requests.get(opcode as usize) {
+            Some(desc) => desc.destructor,
+            None => return Err(()),
+        };
+        let args = args
+            .into_iter()
+            .map(|arg| match arg {
+                RawArgument::Int(i) => Ok(Argument::Int(i)),
+                RawArgument::Uint(u) => Ok(Argument::Uint(u)),
+                RawArgument::Fixed(f) => Ok(Argument::Fixed(f)),
+                RawArgument::Str(s) => Ok(Argument::Str(s.unwrap_or_default())),
+                RawArgument::Array(a) => Ok(Argument::Array(a)),
+                RawArgument::Fd(fd) => Ok(Argument::Fd(fd)),
+                RawArgument::Object(o) => Ok(Argument::Object(o.map(|o| o.id).unwrap_or(0))),
+                RawArgument::NewId(_) => Err(()),
+            }).collect::<Result<Vec<_>, ()>>()?;
+        let msg = Message {
+            sender_id: self.id,
+            opcode,
+            args,
+        };
+        self.send_message(msg, destructor);
+        Ok(())
+    }
+
+    fn send_message(&self, msg: Message, destructor: bool) {
+        // wl_fixes.destroy_registry destroys the registry given as argument
+        let destroyed_registry = match msg.args.first() {
+            Some(&Argument::Object(id)) if self.object.interface == "wl_fixes" && msg.opcode == 1 => Some(id),
+            _ => None,
+        };
+        let mut connection = self.lock_connection_for(&msg);
+        // grab the outgoing lock before checking we are alive
         // this avoids the risk of marking ourselve dead while an other
         // thread is sending a message an accidentaly sending that message
         // after ours if ours is a destructor
-        let mut conn_lock = self.connection.lock().unwrap();
+        let mut outgoing = self.object.meta.outgoing.lock().unwrap();
         if !self.is_alive() {
             return;
         }
-        let destructor = msg.is_destructor();
-        let msg = msg.into_raw(self.id);
-        if ::std::env::var_os("WAYLAND_DEBUG").is_some() {
-            println!(
-                " -> {}@{}: {} {:?}",
-                I::NAME,
-                self.id,
-                self.object.requests[msg.opcode as usize].name,
-                msg.args
-            );
-        }
-        // TODO: figure our if this can fail and still be recoverable ?
-        let _ = conn_lock.write_message(&msg).expect("Sending a message failed.");
+        trace::log(
+            outgoing.logger.as_ref(),
+            Direction::Sent,
+            &LoggedMessage {
+                interface: self.object.interface,
+                name: self.object.requests[msg.opcode as usize].name,
+                trace_id: outgoing.next_trace_id(),
+                message: &msg,
+            },
+        );
+        self.object.requests[msg.opcode as usize].check_fds(self.object.interface, msg.fds());
+        queue_request(&mut outgoing, connection.as_mut(), msg);
         if destructor {
             self.object.meta.alive.store(false, Ordering::Release);
-            {
-                // cleanup the map as appropriate
-                let mut map = conn_lock.map.lock().unwrap();
-                let server_destroyed = map
-                    .with(self.id, |obj| {
-                        obj.meta.client_destroyed = true;
-                        obj.meta.server_destroyed
-                    }).unwrap_or(false);
-                if server_destroyed {
-                    map.remove(self.id);
-                }
-            }
+            self.cleanup_destroyed(self.id);
+        }
+        if let Some(id) = destroyed_registry {
+            self.cleanup_destroyed(id);
+        }
+        let pending = outgoing.messages.len();
+        drop(outgoing);
+        drop(connection);
+        self.write_outgoing_if_needed(pending);
+    }
+
+    pub(crate) fn is_attached_to(&self, queue: &EventQueueInner) -> bool {
+        // the events are dispatched to the object, not to its wrappers
+        match self.map.lock().unwrap().find(self.id) {
+            Some(ref obj) if self.is_alive() => Arc::ptr_eq(&obj.meta.buffer, &queue.buffer),
+            _ => false,
         }
     }
 
+    pub(crate) fn dispatch_injected<I: Interface>(&self, event: I::Event) -> Result<(), ()> {
+        // fetch the current state of the object, it may have been implemented since
+        // this handle was created
+        let proxy = match ProxyInner::from_id(self.id, self.map.clone(), self.connection.clone()) {
+            Some(ref proxy) if self.is_alive() && proxy.equals(self) => proxy.clone(),
+            _ => return Err(()),
+        };
+        let dispatcher = proxy.object.meta.dispatcher.clone();
+        let mut dispatcher = dispatcher.lock().unwrap();
+        dispatcher.dispatch_injected(Box::new(event), proxy)
+    }
+
     pub(crate) fn send_constructor<I, J>(
         &self,
         msg: I::Request,
@@ -153,25 +254,29 @@ impl ProxyInner {
         I: Interface,
         J: Interface,
     {
-        // grab the connection lock before anything else
-        // this avoids the risk or races during object creation
-        let mut conn_lock = self.connection.lock().unwrap();
-        if !self.is_alive() {
-            return Err(());
-        }
         let destructor = msg.is_destructor();
         let mut msg = msg.into_raw(self.id);
-        if ::std::env::var_os("WAYLAND_DEBUG").is_some() {
-            println!(
-                " -> {}@{}: {} {:?}",
-                I::NAME,
-                self.id,
-                self.object.requests[msg.opcode as usize].name,
-                msg.args
-            );
+        let mut connection = self.lock_connection_for(&msg);
+        // grab the outgoing lock before anything else
+        // this avoids the risk or races during object creation: the ids
+        // must reach the server in the order they were allocated
+        let mut outgoing = self.object.meta.outgoing.lock().unwrap();
+        if !self.is_alive() {
+            return Err(());
         }
+        trace::log(
+            outgoing.logger.as_ref(),
+            Direction::Sent,
+            &LoggedMessage {
+                interface: I::NAME,
+                name: self.object.requests[msg.opcode as usize].name,
+                trace_id: outgoing.next_trace_id(),
+                message: &msg,
+            },
+        );
 
         let opcode = msg.opcode;
+        I::Request::MESSAGES[opcode as usize].check_fds(I::NAME, msg.fds());
 
         // sanity check
         let mut nid_idx = I::Request::MESSAGES[opcode as usize]
@@ -202,43 +307,169 @@ impl ProxyInner {
             _ => unreachable!(),
         };
 
-        let _ = conn_lock.write_message(&msg).expect("Sending a message failed.");
+        queue_request(&mut outgoing, connection.as_mut(), msg);
         if destructor {
             self.object.meta.alive.store(false, Ordering::Release);
-            {
-                // cleanup the map as appropriate
-                let mut map = conn_lock.map.lock().unwrap();
-                let server_destroyed = map
-                    .with(self.id, |obj| {
-                        obj.meta.client_destroyed = true;
-                        obj.meta.server_destroyed
-                    }).unwrap_or(false);
-                if server_destroyed {
-                    map.remove(self.id);
-                }
-            }
+            self.cleanup_destroyed(self.id);
         }
+        let pending = outgoing.messages.len();
+        drop(outgoing);
+        drop(connection);
+        self.write_outgoing_if_needed(pending);
 
         Ok(newproxy)
     }
 
+    // cleanup the map as appropriate after a destructor
+    pub(crate) fn cleanup_destroyed(&self, id: u32) {
+        let mut map = self.map.lock().unwrap();
+        let server_destroyed = map
+            .with(id, |obj| {
+                obj.meta.alive.store(false, Ordering::Release);
+                if !obj.meta.client_destroyed {
+                    obj.meta.quotas.destroyed(obj.interface);
+                }
+                obj.meta.client_destroyed = true;
+                obj.meta.server_destroyed
+            }).unwrap_or(false);
+        if server_destroyed {
+            map.remove(id);
+        }
+    }
+
+    // requests with file descriptors are written directly to the socket, which needs the
+    // connection lock, taken before the outgoing one like when flushing
+    fn lock_connection_for(&self, msg: &Message) -> Option<MutexGuard<Connection>> {
+        if msg.fds().next().is_some() {
+            Some(self.connection.lock().unwrap())
+        } else {
+            None
+        }
+    }
+
+    // move the outgoing buffer to the socket if it grew large, unless an other
+    // thread currently holds the connection (it'll be done at next flush then)
+    //
+    // past OUTGOING_LIMIT, the sender waits for the connection and for the socket
+    // to take the requests, so that the buffer does not grow without bounds if the
+    // server does not keep up
+    fn write_outgoing_if_needed(&self, pending: usize) {
+        if pending < OUTGOING_THRESHOLD {
+            return;
+        }
+        if pending < OUTGOING_LIMIT {
+            if let Ok(mut connection) = self.connection.try_lock() {
+                // errors are not fatal here, they'll be reported by the next flush
+                let _ = connection.write_outgoing();
+            }
+            return;
+        }
+        loop {
+            let socket_fd = {
+                let mut connection = self.connection.lock().unwrap();
+                match connection.write_outgoing() {
+                    Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => {
+                        connection.socket.get_socket().as_raw_fd()
+                    }
+                    // other errors are reported by the next flush
+                    _ => return,
+                }
+            };
+            // the connection is not locked while waiting, so that the other threads can read
+            // the events and flush in the meantime
+            if poll(&mut [PollFd::new(socket_fd, EventFlags::POLLOUT)], -1).is_err() {
+                return;
+            }
+        }
+    }
+
     pub(crate) fn equals(&self, other: &ProxyInner) -> bool {
         self.is_alive() && Arc::ptr_eq(&self.object.meta.alive, &other.object.meta.alive)
     }
 
+    fn equals_object(&self, object: &Object<ObjectMeta>) -> bool {
+        self.is_alive() && Arc::ptr_eq(&self.object.meta.alive, &object.meta.alive)
+    }
+
     pub(crate) fn make_wrapper(&self, queue: &EventQueueInner) -> Result<ProxyInner, ()> {
         let mut wrapper = self.clone();
         wrapper.object.meta.buffer = queue.buffer.clone();
+        wrapper.is_wrapper = true;
         Ok(wrapper)
     }
 
+    pub(crate) fn set_queue(&self, queue: &EventQueueInner, descendants: bool) {
+        // the display is dispatched by all queues
+        if !self.is_alive() || self.is_wrapper || self.id == 1 {
+            return;
+        }
+        // holding the map prevents events from being read while the objects are moved
+        let mut map = self.map.lock().unwrap();
+        let mut moved = vec![self.object.meta.alive.clone()];
+        if descendants {
+            // the objects created from the moved ones, until there are no more
+            let mut found = true;
+            while found {
+                found = false;
+                map.with_all(|_, obj| {
+                    let is_child = obj
+                        .meta
+                        .parent
+                        .as_ref()
+                        .map(|p| moved.iter().any(|m| Arc::ptr_eq(m, p)))
+                        .unwrap_or(false);
+                    if is_child && !moved.iter().any(|m| Arc::ptr_eq(m, &obj.meta.alive)) {
+                        moved.push(obj.meta.alive.clone());
+                        found = true;
+                    }
+                });
+            }
+        }
+        let mut ids = Vec::with_capacity(moved.len());
+        let mut old_buffers: Vec<QueueBuffer> = Vec::new();
+        map.with_all(|id, obj| {
+            if !moved.iter().any(|m| Arc::ptr_eq(m, &obj.meta.alive)) {
+                return;
+            }
+            ids.push(id);
+            if !Arc::ptr_eq(&obj.meta.buffer, &queue.buffer)
+                && !old_buffers.iter().any(|b| Arc::ptr_eq(b, &obj.meta.buffer))
+            {
+                old_buffers.push(obj.meta.buffer.clone());
+            }
+            obj.meta.buffer = queue.buffer.clone();
+        });
+        // move the events not dispatched yet, keeping their order
+        for old in old_buffers {
+            let mut old = old.lock().unwrap();
+            let mut new = queue.buffer.lock().unwrap();
+            let mut kept = VecDeque::with_capacity(old.len());
+            for event in old.drain(..) {
+                if ids.contains(&event.0.sender_id) {
+                    new.push_back(event);
+                } else {
+                    kept.push_back(event);
+                }
+            }
+            *old = kept;
+        }
+    }
+
     pub(crate) fn child<I: Interface>(&self) -> NewProxyInner {
         self.child_versioned::<I>(self.object.version)
     }
 
     pub(crate) fn child_versioned<I: Interface>(&self, version: u32) -> NewProxyInner {
-        let new_object = Object::from_interface::<I>(version, self.object.meta.child());
-        let new_id = self.map.lock().unwrap().client_insert_new(new_object);
+        let mut map = self.map.lock().unwrap();
+        // the object may have been moved to an other queue since this handle was created
+        let meta = match map.find(self.id) {
+            Some(ref obj) if !self.is_wrapper && self.equals_object(obj) => obj.meta.child(),
+            _ => self.object.meta.child(),
+        };
+        let new_object = Object::from_interface::<I>(version, meta);
+        let new_id = map.client_insert_new(new_object);
+        drop(map);
+        self.object.meta.quotas.created(I::NAME);
         NewProxyInner {
             map: self.map.clone(),
             connection: self.connection.clone(),
@@ -252,6 +483,7 @@ impl ProxyInner {
             connection: self.connection.clone(),
             object: Object::placeholder(self.object.meta.child()),
             id: 0,
+            is_wrapper: false,
         }
     }
 }
@@ -277,7 +509,7 @@ impl NewProxyInner {
 
     /// Racy method, if called, must be called before any event ot this object
     /// is read from the socket, or it'll end up in the wrong queue...
-    pub(crate) unsafe fn assign_queue(&self, queue: &EventQueueInner) {
+    pub(crate) unsafe fn assign_queue(&mut self, queue: &EventQueueInner) {
         let _ = self.map.lock().unwrap().with(self.id, |obj| {
             obj.meta.buffer = queue.buffer.clone();
         });
@@ -293,8 +525,28 @@ impl NewProxyInner {
         F: FnMut(I::Event, Proxy<I>) + 'static,
         I::Event: MessageGroup<Map = super::ProxyMap>,
     {
+        self.implement_dispatcher::<I>(super::make_dispatcher(implementation), false, user_data)
+    }
+
+    // A `Send` implementation can be dispatched by the pool of its event queue
+    pub(crate) fn implement_send<I: Interface, F>(self, implementation: F, user_data: UserData) -> ProxyInner
+    where
+        F: FnMut(I::Event, Proxy<I>) + Send + 'static,
+        I::Event: MessageGroup<Map = super::ProxyMap>,
+    {
+        let dispatcher = unsafe { super::make_dispatcher(implementation) };
+        self.implement_dispatcher::<I>(dispatcher, true, user_data)
+    }
+
+    fn implement_dispatcher<I: Interface>(
+        self,
+        dispatcher: Arc<Mutex<Dispatcher>>,
+        send: bool,
+        user_data: UserData,
+    ) -> ProxyInner {
         let object = self.map.lock().unwrap().with(self.id, |obj| {
-            obj.meta.dispatcher = super::make_dispatcher(implementation);
+            obj.meta.dispatcher = dispatcher;
+            obj.meta.send = send;
             obj.meta.user_data = Arc::new(user_data);
             obj.clone()
         });
@@ -307,7 +559,7 @@ impl NewProxyInner {
                 // have been possible) or an object was created and the server destroyed it
                 // before it could be implemented.
                 // Thus, we just create a dummy already-dead Proxy
-                Object::from_interface::<I>(1, ObjectMeta::dead())
+                Object::from_interface::<I>(0, ObjectMeta::dead())
             }
         };
 
@@ -316,6 +568,44 @@ impl NewProxyInner {
             connection: self.connection,
             id: self.id,
             object,
+            is_wrapper: false,
+        }
+    }
+
+    // Invariants: same as `implement()`
+    pub(crate) unsafe fn implement_raw(
+        self,
+        _desc: &Object<()>,
+        implementation: Box<FnMut(u16, Vec<RawArgument>, ProxyInner)>,
+    ) -> ProxyInner {
+        let object = self.map.lock().unwrap().with(self.id, |obj| {
+            obj.meta.dispatcher = super::make_raw_dispatcher(implementation);
+            obj.clone()
+        });
+
+        let object = match object {
+            Ok(obj) => obj,
+            Err(()) => Object::from_interface::<::AnonymousObject>(1, ObjectMeta::dead()),
+        };
+
+        ProxyInner {
+            map: self.map,
+            connection: self.connection,
+            id: self.id,
+            object,
+            is_wrapper: false,
+        }
+    }
+}
+
+// append a request to the outgoing buffer, or write it to the socket if it has file descriptors
+fn queue_request(outgoing: &mut Outgoing, connection: Option<&mut MutexGuard<Connection>>, msg: Message) {
+    match connection {
+        // EAGAIN leaves the request in the outgoing buffer, and other errors are reported by
+        // the next flush
+        Some(connection) => {
+            let _ = connection.write_request(outgoing, msg);
         }
+        None => outgoing.push(msg),
     }
 }
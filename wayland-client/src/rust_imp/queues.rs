@@ -3,18 +3,23 @@ use std::collections::VecDeque;
 use std::io;
 use std::os::unix::io::AsRawFd;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use nix::poll::{poll, EventFlags, PollFd};
 
 use wayland_commons::map::ObjectMap;
+use wayland_commons::trace::{self, Direction, LoggedMessage};
 use wayland_commons::utils::UserData;
-use wayland_commons::wire::Message;
+use wayland_commons::wire::{ArgumentType, Message};
 
 use super::connection::{Connection, Error as CError};
+use super::pool::DispatchPool;
 use super::proxy::{ObjectMeta, ProxyInner};
 
-pub(crate) type QueueBuffer = Arc<Mutex<VecDeque<Message>>>;
+// the received events, with their time of arrival if it was recorded, and trace id
+pub(crate) type QueueBuffer = Arc<Mutex<VecDeque<(Message, Option<Instant>, u64)>>>;
 
 pub(crate) fn create_queue_buffer() -> QueueBuffer {
     Arc::new(Mutex::new(VecDeque::new()))
@@ -25,6 +30,9 @@ pub(crate) struct EventQueueInner {
     pub(crate) map: Arc<Mutex<ObjectMap<ObjectMeta>>>,
     pub(crate) buffer: QueueBuffer,
     display_buffer: QueueBuffer,
+    pool: Mutex<Option<DispatchPool>>,
+    dispatched: AtomicUsize,
+    latency_hook: AtomicBool,
 }
 
 impl EventQueueInner {
@@ -38,10 +46,40 @@ impl EventQueueInner {
             map,
             buffer: buffer.unwrap_or_else(create_queue_buffer),
             display_buffer,
+            pool: Mutex::new(None),
+            dispatched: AtomicUsize::new(0),
+            latency_hook: AtomicBool::new(false),
         }
     }
 
-    #[cfg(feature = "eventloop")]
+    // record the reception time of the input events while this queue has a latency hook
+    pub(crate) fn set_latency_hook(&self, set: bool) {
+        if self.latency_hook.swap(set, Ordering::AcqRel) != set {
+            let mut connection = self.connection.lock().unwrap();
+            if set {
+                connection.latency_hooks += 1;
+            } else {
+                connection.latency_hooks -= 1;
+            }
+        }
+    }
+
+    pub(crate) fn set_dispatch_threads(&self, threads: usize) {
+        *self.pool.lock().unwrap() = if threads > 0 {
+            Some(DispatchPool::new(threads))
+        } else {
+            None
+        };
+    }
+
+    pub(crate) fn dispatched_events(&self) -> usize {
+        self.dispatched.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn pending_events(&self) -> Option<usize> {
+        Some(self.buffer.lock().unwrap().len())
+    }
+
     pub(crate) fn get_connection_fd(&self) -> ::std::os::unix::io::RawFd {
         self.connection.lock().unwrap().socket.get_socket().as_raw_fd()
     }
@@ -110,15 +148,81 @@ impl EventQueueInner {
         self.dispatch_pending()
     }
 
-    fn dispatch_buffer(&self, buffer: &mut VecDeque<Message>) -> io::Result<u32> {
+    fn dispatch_buffer(&self, buffer: &QueueBuffer) -> io::Result<u32> {
+        // don't keep the pool locked while dispatching, the implementations may dispatch
+        // the queue again, they then do it sequentially
+        let mut pool = self.pool.lock().unwrap().take();
+        let ret = self.dispatch_buffer_with(buffer, pool.as_mut());
+        // all the events given to the pool are processed once we return, even on error
+        let ret = match pool {
+            Some(ref mut pool) => {
+                let processed = wait_pool(pool);
+                ret.and_then(|count| processed.map(|n| count + n))
+            }
+            None => ret,
+        };
+        let mut current = self.pool.lock().unwrap();
+        // unless the implementations changed it in the meantime
+        if current.is_none() {
+            *current = pool;
+        }
+        if let Ok(count) = ret {
+            self.dispatched.fetch_add(count as usize, Ordering::Relaxed);
+        }
+        ret
+    }
+
+    fn dispatch_buffer_with(
+        &self,
+        buffer: &QueueBuffer,
+        mut pool: Option<&mut DispatchPool>,
+    ) -> io::Result<u32> {
         let mut count = 0;
         let mut proxymap = super::ProxyMap::make(self.map.clone(), self.connection.clone());
-        for msg in buffer.drain(..) {
+        loop {
+            // don't keep the buffer locked while dispatching, implementations may move
+            // their proxies to an other queue
+            let next = buffer.lock().unwrap().pop_front();
+            let (msg, received, trace_id) = match next {
+                Some(event) => event,
+                None => break,
+            };
             let id = msg.sender_id;
             if let Some(proxy) = ProxyInner::from_id(id, self.map.clone(), self.connection.clone()) {
                 let object = proxy.object.clone();
+                let event_name = object.events[msg.opcode as usize].name;
+                let logger = object.meta.outgoing.lock().unwrap().logger.clone();
+                trace::log(
+                    logger.as_ref(),
+                    Direction::Received,
+                    &LoggedMessage {
+                        interface: object.interface,
+                        name: event_name,
+                        trace_id,
+                        message: &msg,
+                    },
+                );
+                if let Some(ref mut pool) = pool {
+                    // the events of the objects created by an event may follow it, it must be
+                    // dispatched once the previous events are processed, and before the next ones
+                    let creates = msg.args.iter().any(|a| a.get_type() == ArgumentType::NewId);
+                    // the implementations claiming interfaces, the global filter and the event
+                    // filters are run from this thread
+                    let claimed = ::routing::is_claimed(object.interface)
+                        || ::routing::is_filtered(object.interface)
+                        || ::dedup::is_active(object.interface);
+                    if object.meta.send && !creates && !claimed {
+                        let map = super::ProxyMap::make(self.map.clone(), self.connection.clone());
+                        pool.dispatch(msg, proxy, map);
+                        continue;
+                    }
+                    count += wait_pool(pool)?;
+                }
                 let mut dispatcher = object.meta.dispatcher.lock().unwrap();
-                if let Err(()) = dispatcher.dispatch(msg, proxy, &mut proxymap) {
+                let ret = ::latency::track(object.interface, event_name, received, || {
+                    dispatcher.dispatch(msg, proxy, &mut proxymap)
+                });
+                if let Err(()) = ret {
                     return Err(io::Error::new(
                         io::ErrorKind::Other,
                         format!("Dispatch for object {}@{} errored.", object.interface, id),
@@ -138,16 +242,10 @@ impl EventQueueInner {
 
     pub(crate) fn dispatch_pending(&self) -> io::Result<u32> {
         // First always dispatch the display buffer
-        let display_dispatched = {
-            let mut buffer = self.display_buffer.lock().unwrap();
-            self.dispatch_buffer(&mut *buffer)
-        }?;
+        let display_dispatched = self.dispatch_buffer(&self.display_buffer)?;
 
         // Then our actual buffer
-        let self_dispatched = {
-            let mut buffer = self.buffer.lock().unwrap();
-            self.dispatch_buffer(&mut *buffer)
-        }?;
+        let self_dispatched = self.dispatch_buffer(&self.buffer)?;
 
         Ok(display_dispatched + self_dispatched)
     }
@@ -214,4 +312,28 @@ impl EventQueueInner {
     pub(crate) fn cancel_read(&self) {
         // TODO: un-mock
     }
+
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        match self.connection.lock().unwrap().flush() {
+            Ok(()) => Ok(()),
+            Err(::nix::Error::Sys(errno)) => Err(errno.into()),
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+// wait for the events being dispatched by a pool
+fn wait_pool(pool: &mut DispatchPool) -> io::Result<u32> {
+    pool.wait().map_err(|(interface, id)| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("Dispatch for object {}@{} errored.", interface, id),
+        )
+    })
+}
+
+impl Drop for EventQueueInner {
+    fn drop(&mut self) {
+        self.set_latency_hook(false);
+    }
 }
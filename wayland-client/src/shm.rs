@@ -0,0 +1,440 @@
+//! Shared memory buffers
+//!
+//! Clients drawing on the CPU share their buffers with the compositor through `wl_shm`:
+//! the pixels are written in a file mapped both by the client and the compositor, and
+//! the compositor reads them until it sends `wl_buffer.release`. A buffer must not be
+//! drawn to until then, so clients keep two or three of them and draw to whichever is
+//! free.
+//!
+//! `BufferPool` automates this: it keeps a fixed number of slots in a single memory
+//! file, marks a slot as busy once its buffer is attached to a surface, and gives it
+//! back once the compositor releases it. `BufferPool::next_buffer()` returns a free slot,
+//! whose buffer is recreated if the requested size or format changed, the pool growing
+//! as needed.
+//!
+//! The release events are processed by the event queue of the `wl_shm` global the pool
+//! was created from, it must be dispatched for the slots to become free again.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use nix::sys::mman;
+
+use protocol::wl_buffer::{self, RequestsTrait as BufferRequests, WlBuffer};
+use protocol::wl_shm::{Format, RequestsTrait as ShmRequests, WlShm};
+use protocol::wl_shm_pool::{RequestsTrait as PoolRequests, WlShmPool};
+use protocol::wl_surface::{RequestsTrait as SurfaceRequests, WlSurface};
+use Proxy;
+
+// the slots are page aligned, so that the pixels can be accessed as `u32`
+const PAGE_SIZE: usize = 4096;
+
+/// The number of bytes per pixel of a format
+///
+/// Returns `None` for the formats with several planes, or whose pixels are not
+/// stored on a whole number of bytes.
+pub fn bytes_per_pixel(format: Format) -> Option<u32> {
+    match format {
+        Format::C8 | Format::Rgb332 | Format::Bgr233 => Some(1),
+        Format::Xrgb4444
+        | Format::Xbgr4444
+        | Format::Rgbx4444
+        | Format::Bgrx4444
+        | Format::Argb4444
+        | Format::Abgr4444
+        | Format::Rgba4444
+        | Format::Bgra4444
+        | Format::Xrgb1555
+        | Format::Xbgr1555
+        | Format::Rgbx5551
+        | Format::Bgrx5551
+        | Format::Argb1555
+        | Format::Abgr1555
+        | Format::Rgba5551
+        | Format::Bgra5551
+        | Format::Rgb565
+        | Format::Bgr565 => Some(2),
+        Format::Rgb888 | Format::Bgr888 => Some(3),
+        Format::Argb8888
+        | Format::Xrgb8888
+        | Format::Xbgr8888
+        | Format::Rgbx8888
+        | Format::Bgrx8888
+        | Format::Abgr8888
+        | Format::Rgba8888
+        | Format::Bgra8888
+        | Format::Xrgb2101010
+        | Format::Xbgr2101010
+        | Format::Rgbx1010102
+        | Format::Bgrx1010102
+        | Format::Argb2101010
+        | Format::Abgr2101010
+        | Format::Rgba1010102
+        | Format::Bgra1010102 => Some(4),
+        _ => None,
+    }
+}
+
+pub(crate) fn nix_error(err: ::nix::Error) -> io::Error {
+    match err {
+        ::nix::Error::Sys(errno) => errno.into(),
+        other => io::Error::new(io::ErrorKind::Other, other),
+    }
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "buffer size overflow")
+}
+
+pub(crate) fn dead_object(interface: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, format!("the {} object is dead", interface))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn create_file() -> io::Result<File> {
+    use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+    let name = CString::new("wayland-rs-shm").unwrap();
+    let fd = memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC).map_err(nix_error)?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn create_file() -> io::Result<File> {
+    use nix::fcntl::OFlag;
+    use nix::sys::stat::Mode;
+    use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+    static COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+    loop {
+        let name = CString::new(format!(
+            "/wayland-rs-shm-{}-{}",
+            ::std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        )).unwrap();
+        let flags = OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR | OFlag::O_CLOEXEC;
+        match mman::shm_open(name.as_c_str(), flags, Mode::S_IRUSR | Mode::S_IWUSR) {
+            Ok(fd) => {
+                // the file only needs to live as long as its descriptors
+                let _ = mman::shm_unlink(name.as_c_str());
+                return Ok(unsafe { File::from_raw_fd(fd) });
+            }
+            Err(::nix::Error::Sys(::nix::errno::Errno::EEXIST)) => continue,
+            Err(e) => return Err(nix_error(e)),
+        }
+    }
+}
+
+// a memory file shared with the compositor, and its mapping
+struct Pool {
+    proxy: Proxy<WlShmPool>,
+    map: *mut u8,
+    slot_size: usize,
+    len: usize,
+    generation: u32,
+}
+
+impl Pool {
+    fn new(shm: &Proxy<WlShm>, slot_size: usize, slots: usize, generation: u32) -> io::Result<Pool> {
+        // the size of a pool is sent as an i32, which also bounds the offsets of the slots
+        let len = match slot_size.checked_mul(slots) {
+            Some(len) if len <= i32::max_value() as usize => len,
+            _ => return Err(too_large()),
+        };
+        let file = create_file()?;
+        file.set_len(len as u64)?;
+        let map = map(file.as_raw_fd(), len)?;
+        let proxy = match shm.create_pool(file.as_raw_fd(), len as i32, |newp| {
+            newp.implement(|_, _| {}, ())
+        }) {
+            Ok(proxy) => proxy,
+            Err(()) => {
+                unsafe {
+                    let _ = mman::munmap(map as *mut _, len);
+                }
+                return Err(dead_object("wl_shm"));
+            }
+        };
+        // the descriptor is duplicated when sent, the mapping keeps the memory alive
+        Ok(Pool {
+            proxy,
+            map,
+            slot_size,
+            len,
+            generation,
+        })
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // the buffers created from the pool keep its memory alive on the compositor side
+        self.proxy.destroy();
+        unsafe {
+            let _ = mman::munmap(self.map as *mut _, self.len);
+        }
+    }
+}
+
+pub(crate) fn map(fd: RawFd, len: usize) -> io::Result<*mut u8> {
+    let map = unsafe {
+        mman::mmap(
+            ptr::null_mut(),
+            len,
+            mman::ProtFlags::PROT_READ | mman::ProtFlags::PROT_WRITE,
+            mman::MapFlags::MAP_SHARED,
+            fd,
+            0,
+        )
+    };
+    map.map(|map| map as *mut u8).map_err(nix_error)
+}
+
+// the buffer currently stored in a slot
+struct SlotBuffer {
+    proxy: Proxy<WlBuffer>,
+    generation: u32,
+    width: i32,
+    height: i32,
+    stride: i32,
+    format: Format,
+}
+
+struct Slot {
+    buffer: Option<SlotBuffer>,
+    busy: Arc<AtomicBool>,
+}
+
+impl Slot {
+    fn destroy_buffer(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            buffer.proxy.destroy();
+        }
+    }
+}
+
+/// A set of shared memory buffers, drawn to in turn
+///
+/// See the module documentation for details.
+pub struct BufferPool {
+    shm: Proxy<WlShm>,
+    slots: Vec<Slot>,
+    pool: Option<Pool>,
+    generations: u32,
+}
+
+impl BufferPool {
+    /// Create a pool of `slots` buffers
+    ///
+    /// Two slots give a double buffering and three a triple buffering. No memory is
+    /// allocated until the first buffer is requested.
+    ///
+    /// Panics if `slots` is 0.
+    pub fn new(shm: &Proxy<WlShm>, slots: usize) -> BufferPool {
+        assert!(slots > 0, "A buffer pool needs at least one slot.");
+        BufferPool {
+            shm: shm.clone(),
+            slots: (0..slots)
+                .map(|_| Slot {
+                    buffer: None,
+                    busy: Arc::new(AtomicBool::new(false)),
+                }).collect(),
+            pool: None,
+            generations: 0,
+        }
+    }
+
+    /// The number of buffers currently held by the compositor
+    pub fn busy(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.busy.load(Ordering::Acquire))
+            .count()
+    }
+
+    /// Get a free buffer of given size and format
+    ///
+    /// Returns `Ok(None)` if all the buffers are held by the compositor, the event queue
+    /// then needs to be dispatched until one of them is released.
+    ///
+    /// The contents of the returned buffer are the ones it had the last time it was
+    /// drawn to, if it kept the same size and format, and are otherwise unspecified.
+    /// An error is returned if the memory could not be allocated, if the format is not
+    /// in `bytes_per_pixel()`, if the pool would not fit in the `i32` sizes of the
+    /// protocol or if the `wl_shm` global is dead.
+    pub fn next_buffer<'a>(
+        &'a mut self,
+        width: i32,
+        height: i32,
+        format: Format,
+    ) -> io::Result<Option<Buffer<'a>>> {
+        let bpp = match bytes_per_pixel(format) {
+            Some(bpp) => bpp,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported shm format",
+                ))
+            }
+        };
+        if width <= 0 || height <= 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid buffer size"));
+        }
+        let stride = width.checked_mul(bpp as i32).ok_or_else(too_large)?;
+        let len = (stride as usize)
+            .checked_mul(height as usize)
+            .ok_or_else(too_large)?;
+        let index = match self
+            .slots
+            .iter()
+            .position(|slot| !slot.busy.load(Ordering::Acquire))
+        {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let needs_pool = match self.pool {
+            Some(ref pool) => pool.slot_size < len,
+            None => true,
+        };
+        if needs_pool {
+            // the buffers still held by the compositor keep the memory of the old pool
+            // alive, they are recreated in the new one once released
+            let slot_size = len.checked_add(PAGE_SIZE - 1).ok_or_else(too_large)? & !(PAGE_SIZE - 1);
+            self.generations = self.generations.wrapping_add(1);
+            self.pool = Some(Pool::new(&self.shm, slot_size, self.slots.len(), self.generations)?);
+        }
+        let pool = self.pool.as_ref().unwrap();
+        let slot = &mut self.slots[index];
+
+        let reusable = match slot.buffer {
+            Some(ref buffer) => {
+                buffer.generation == pool.generation
+                    && buffer.width == width
+                    && buffer.height == height
+                    && buffer.format == format
+            }
+            None => false,
+        };
+        let start = match index.checked_mul(pool.slot_size) {
+            Some(start) if start <= i32::max_value() as usize => start,
+            _ => return Err(too_large()),
+        };
+        if !reusable {
+            slot.destroy_buffer();
+            let busy = slot.busy.clone();
+            let proxy = pool
+                .proxy
+                .create_buffer(
+                    start as i32,
+                    width,
+                    height,
+                    stride,
+                    format,
+                    move |newp| {
+                        newp.implement(
+                            move |event, _| match event {
+                                wl_buffer::Event::Release => busy.store(false, Ordering::Release),
+                            },
+                            (),
+                        )
+                    },
+                ).map_err(|()| dead_object("wl_shm_pool"))?;
+            slot.buffer = Some(SlotBuffer {
+                proxy,
+                generation: pool.generation,
+                width,
+                height,
+                stride,
+                format,
+            });
+        }
+
+        let buffer = slot.buffer.as_ref().unwrap();
+        // the slot is not busy, and the returned buffer borrows the pool mutably
+        let data = unsafe { &mut slice::from_raw_parts_mut(pool.map, pool.len)[start..start + len] };
+        Ok(Some(Buffer {
+            data,
+            buffer,
+            busy: &slot.busy,
+        }))
+    }
+}
+
+impl Drop for BufferPool {
+    fn drop(&mut self) {
+        for slot in &mut self.slots {
+            slot.destroy_buffer();
+        }
+    }
+}
+
+/// A free buffer of a `BufferPool`
+///
+/// The buffer goes back to its pool once dropped, unless it is attached to a surface.
+pub struct Buffer<'a> {
+    data: &'a mut [u8],
+    buffer: &'a SlotBuffer,
+    busy: &'a Arc<AtomicBool>,
+}
+
+impl<'a> Buffer<'a> {
+    /// The width of this buffer, in pixels
+    pub fn width(&self) -> i32 {
+        self.buffer.width
+    }
+
+    /// The height of this buffer, in pixels
+    pub fn height(&self) -> i32 {
+        self.buffer.height
+    }
+
+    /// The number of bytes between the starts of two rows of this buffer
+    pub fn stride(&self) -> i32 {
+        self.buffer.stride
+    }
+
+    /// The format of this buffer
+    pub fn format(&self) -> Format {
+        self.buffer.format
+    }
+
+    /// Access the underlying buffer object
+    pub fn buffer(&self) -> &Proxy<WlBuffer> {
+        &self.buffer.proxy
+    }
+
+    /// The bytes of this buffer, row by row
+    pub fn data(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    /// The pixels of this buffer, row by row
+    ///
+    /// The values are in native endianness, as the `wl_shm` formats are little-endian
+    /// ones on little-endian machines: a `Format::Argb8888` pixel is `0xAARRGGBB`.
+    ///
+    /// Panics if the pixels of the format of this buffer do not have 4 bytes.
+    pub fn pixels(&mut self) -> &mut [u32] {
+        assert!(
+            bytes_per_pixel(self.buffer.format) == Some(4),
+            "The pixels of the format {:?} do not have 4 bytes.",
+            self.buffer.format
+        );
+        // the slots are page aligned, and this buffer borrows the memory of the slot
+        unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr() as *mut u32, self.data.len() / 4) }
+    }
+
+    /// Attach this buffer to a surface
+    ///
+    /// The buffer is held by the compositor until it sends `wl_buffer.release`, which
+    /// can only happen once the surface is committed.
+    pub fn attach(self, surface: &Proxy<WlSurface>, x: i32, y: i32) {
+        self.busy.store(true, Ordering::Release);
+        surface.attach(Some(&self.buffer.proxy), x, y);
+    }
+}
@@ -0,0 +1,209 @@
+//! Handing a connection over to a new process
+//!
+//! A client can replace itself with a new version of its program, through `exec()`,
+//! without closing its connection to the server: its windows stay on screen, and the
+//! new process carries on with the objects of the old one. This is only supported by
+//! the rust implementation, `libwayland-client.so` cannot adopt existing objects.
+//!
+//! The handshake is the following:
+//!
+//! 1. The old process dispatches all of its event queues, then does a
+//!    `sync_roundtrip()` on the queue of the `Display`: this processes the
+//!    `delete_id` events of the objects it destroyed.
+//! 2. It calls `Display::prepare_takeover()`, which flushes the requests and
+//!    checks that no event was left undispatched, which would be lost. It
+//!    gives a `TakeoverState`: a duplicate of the socket without `CLOEXEC`,
+//!    and the list of the live objects.
+//! 3. It passes `TakeoverState::serialize()` to the new process, as an
+//!    argument or in an environment variable, and calls `exec()`. It must
+//!    send no request between the two steps, and keep the `TakeoverState`
+//!    until `exec()`: dropping it closes its copy of the socket.
+//! 4. The new process creates its `Display` with `Display::adopt()`, then
+//!    implements each listed object through `Adoption::proxy()`, and calls
+//!    `Adoption::finish()` to check that none of them was forgotten. It can
+//!    then dispatch its event queue: the events which arrived in the meantime
+//!    were kept in the socket.
+//!
+//! All the objects must be adopted before events are read, an event sent to an
+//! object that was not would be a fatal error for the connection. A new
+//! `GlobalManager` can be created, the server advertises the globals again to
+//! its registry.
+
+use std::fmt;
+use std::io;
+use std::os::unix::io::{IntoRawFd, RawFd};
+use std::sync::Arc;
+
+use imp::DisplayInner;
+use {Interface, NewProxy};
+
+/// An object handed over to a new process
+#[derive(Clone, Debug, PartialEq)]
+pub struct TakeoverObject {
+    /// The protocol id of the object
+    pub id: u32,
+    /// The name of the interface of the object
+    pub interface: String,
+    /// The version of the object
+    pub version: u32,
+}
+
+/// The state of a connection handed over to a new process
+///
+/// It owns its file descriptor, which is closed when it is dropped: if `exec()` does not
+/// happen, the copy of the socket is not leaked. See the module documentation for details.
+#[derive(Debug, PartialEq)]
+pub struct TakeoverState {
+    fd: RawFd,
+    objects: Vec<TakeoverObject>,
+}
+
+impl TakeoverState {
+    pub(crate) fn new(fd: RawFd, objects: Vec<TakeoverObject>) -> TakeoverState {
+        TakeoverState { fd, objects }
+    }
+
+    /// The file descriptor of the socket, which is inherited through `exec()`
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// The live objects of the connection, excluding the `wl_display`
+    pub fn objects(&self) -> &[TakeoverObject] {
+        &self.objects
+    }
+
+    /// Serialize this state into a string, to be given to the new process
+    ///
+    /// It contains the socket fd, then each object as `interface:id:version`,
+    /// separated by spaces.
+    pub fn serialize(&self) -> String {
+        let mut text = self.fd.to_string();
+        for object in &self.objects {
+            text.push_str(&format!(" {}:{}:{}", object.interface, object.id, object.version));
+        }
+        text
+    }
+
+    /// Parse a state given by the old process
+    ///
+    /// The returned state owns the file descriptor it names.
+    pub fn deserialize(text: &str) -> Result<TakeoverState, TakeoverError> {
+        let mut words = text.split_whitespace();
+        let fd = words
+            .next()
+            .and_then(|fd| fd.parse().ok())
+            .ok_or(TakeoverError::Invalid)?;
+        let mut objects = Vec::new();
+        for word in words {
+            let mut fields = word.split(':');
+            let object = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(interface), Some(id), Some(version), None) => TakeoverObject {
+                    interface: interface.into(),
+                    id: id.parse().map_err(|_| TakeoverError::Invalid)?,
+                    version: version.parse().map_err(|_| TakeoverError::Invalid)?,
+                },
+                _ => return Err(TakeoverError::Invalid),
+            };
+            objects.push(object);
+        }
+        Ok(TakeoverState { fd, objects })
+    }
+}
+
+impl IntoRawFd for TakeoverState {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.fd;
+        ::std::mem::forget(self);
+        fd
+    }
+}
+
+impl Drop for TakeoverState {
+    fn drop(&mut self) {
+        let _ = ::nix::unistd::close(self.fd);
+    }
+}
+
+/// The reasons why a connection could not be handed over
+#[derive(Debug)]
+pub enum TakeoverError {
+    /// The library uses `libwayland-client.so`, which does not support it
+    Unsupported,
+    /// Events were received but not dispatched, requests could not be flushed, or
+    /// destroyed objects are still waiting for their `delete_id`
+    ///
+    /// Dispatch the event queues and do a roundtrip before trying again.
+    Pending,
+    /// The connection failed
+    Io(io::Error),
+    /// The state given to `deserialize()` or `Display::adopt()` is invalid
+    Invalid,
+    /// These objects were not adopted by the new process
+    NotAdopted(Vec<TakeoverObject>),
+}
+
+impl fmt::Display for TakeoverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TakeoverError::Unsupported => f.write_str("connection takeover is not supported by libwayland"),
+            TakeoverError::Pending => f.write_str("the connection has pending messages"),
+            TakeoverError::Io(ref err) => write!(f, "connection failed: {}", err),
+            TakeoverError::Invalid => f.write_str("invalid takeover state"),
+            TakeoverError::NotAdopted(ref objects) => {
+                write!(f, "{} objects were not adopted", objects.len())
+            }
+        }
+    }
+}
+
+impl ::std::error::Error for TakeoverError {
+    fn description(&self) -> &str {
+        "connection takeover failed"
+    }
+}
+
+/// The objects of a connection to adopt, see `Display::adopt()`
+pub struct Adoption {
+    inner: Arc<DisplayInner>,
+    // the objects which were not adopted yet
+    objects: Vec<TakeoverObject>,
+}
+
+impl Adoption {
+    pub(crate) fn new(inner: Arc<DisplayInner>, objects: Vec<TakeoverObject>) -> Adoption {
+        Adoption { inner, objects }
+    }
+
+    /// The objects which were not adopted yet
+    pub fn remaining(&self) -> &[TakeoverObject] {
+        &self.objects
+    }
+
+    /// Adopt an object, which must then be implemented
+    ///
+    /// Returns `None` if there is no object of interface `I` with this id left to adopt.
+    pub fn proxy<I: Interface>(&mut self, id: u32) -> Option<NewProxy<I>> {
+        let index = match self
+            .objects
+            .iter()
+            .position(|object| object.id == id && object.interface == I::NAME)
+        {
+            Some(index) => index,
+            None => return None,
+        };
+        let object = self.objects.remove(index);
+        self.inner
+            .adopt_object::<I>(object.id, object.version)
+            .map(NewProxy::wrap)
+    }
+
+    /// Check that all the objects were adopted
+    pub fn finish(self) -> Result<(), TakeoverError> {
+        if self.objects.is_empty() {
+            Ok(())
+        } else {
+            Err(TakeoverError::NotAdopted(self.objects))
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Buffer transform math
+//!
+//! The `wl_output.transform` values describe a rotation by a multiple of 90 degrees
+//! counter-clockwise, optionally preceded by a flip around the vertical axis. They
+//! are used both to advertise the transform of an output, and to tell the compositor
+//! how the contents of a buffer were transformed with `wl_surface.set_buffer_transform`.
+//!
+//! This module provides the matrices of these transforms, to be used by the renderers
+//! of clients. All matrices act on column vectors in the coordinate system of wayland,
+//! the y axis pointing down: the matrix of `Transform::_90` maps the x axis to the top.
+//!
+//! The buffer of a surface with a buffer transform and scale contains the surface
+//! contents transformed by this transform then scaled up, `Matrix3::surface_to_buffer()`
+//! gives the matrix of this mapping, and `Matrix3::buffer_to_surface()` its inverse.
+
+use protocol::wl_output::Transform;
+
+const TRANSFORMS: [Transform; 8] = [
+    Transform::Normal,
+    Transform::_90,
+    Transform::_180,
+    Transform::_270,
+    Transform::Flipped,
+    Transform::Flipped90,
+    Transform::Flipped180,
+    Transform::Flipped270,
+];
+
+/// The 2x2 matrix of a transform, stored by rows
+pub fn matrix2(transform: Transform) -> [[f64; 2]; 2] {
+    match transform {
+        Transform::Normal => [[1.0, 0.0], [0.0, 1.0]],
+        Transform::_90 => [[0.0, 1.0], [-1.0, 0.0]],
+        Transform::_180 => [[-1.0, 0.0], [0.0, -1.0]],
+        Transform::_270 => [[0.0, -1.0], [1.0, 0.0]],
+        Transform::Flipped => [[-1.0, 0.0], [0.0, 1.0]],
+        Transform::Flipped90 => [[0.0, 1.0], [1.0, 0.0]],
+        Transform::Flipped180 => [[1.0, 0.0], [0.0, -1.0]],
+        Transform::Flipped270 => [[0.0, -1.0], [-1.0, 0.0]],
+    }
+}
+
+/// The inverse of a transform
+pub fn inverse(transform: Transform) -> Transform {
+    match transform {
+        Transform::_90 => Transform::_270,
+        Transform::_270 => Transform::_90,
+        // the other rotations and all the flipped transforms are their own inverse
+        other => other,
+    }
+}
+
+/// The transform applying `first`, then `then`
+pub fn compose(first: Transform, then: Transform) -> Transform {
+    let (a, b) = (matrix2(then), matrix2(first));
+    let product = [
+        [
+            a[0][0] * b[0][0] + a[0][1] * b[1][0],
+            a[0][0] * b[0][1] + a[0][1] * b[1][1],
+        ],
+        [
+            a[1][0] * b[0][0] + a[1][1] * b[1][0],
+            a[1][0] * b[0][1] + a[1][1] * b[1][1],
+        ],
+    ];
+    // the transforms are a group, the product is always one of them
+    *TRANSFORMS
+        .iter()
+        .find(|&&t| matrix2(t) == product)
+        .unwrap()
+}
+
+/// Whether a transform swaps the width and the height
+pub fn swaps_dimensions(transform: Transform) -> bool {
+    match transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => true,
+        _ => false,
+    }
+}
+
+/// The size of a `width` x `height` rectangle once transformed
+pub fn transformed_size(transform: Transform, width: i32, height: i32) -> (i32, i32) {
+    if swaps_dimensions(transform) {
+        (height, width)
+    } else {
+        (width, height)
+    }
+}
+
+/// A 3x3 matrix acting on homogeneous 2D coordinates, stored by rows
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix3(pub [[f64; 3]; 3]);
+
+impl Matrix3 {
+    /// The identity matrix
+    pub fn identity() -> Matrix3 {
+        Matrix3([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    /// The matrix of a transform, without translation
+    pub fn from_transform(transform: Transform) -> Matrix3 {
+        let m = matrix2(transform);
+        Matrix3([[m[0][0], m[0][1], 0.0], [m[1][0], m[1][1], 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    /// The matrix of a transform applied to a `width` x `height` rectangle
+    ///
+    /// This matrix maps the rectangle with its top-left corner at the origin to the
+    /// transformed rectangle, also with its top-left corner at the origin.
+    pub fn from_transform_in(transform: Transform, width: f64, height: f64) -> Matrix3 {
+        let m = matrix2(transform);
+        // the top-left corner of the transformed rectangle, moved back to the origin
+        let min_x = (m[0][0] * width).min(0.0) + (m[0][1] * height).min(0.0);
+        let min_y = (m[1][0] * width).min(0.0) + (m[1][1] * height).min(0.0);
+        Matrix3::translation(-min_x, -min_y).multiply(&Matrix3::from_transform(transform))
+    }
+
+    /// A scaling matrix
+    pub fn scale(sx: f64, sy: f64) -> Matrix3 {
+        Matrix3([[sx, 0.0, 0.0], [0.0, sy, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    /// A translation matrix
+    pub fn translation(x: f64, y: f64) -> Matrix3 {
+        Matrix3([[1.0, 0.0, x], [0.0, 1.0, y], [0.0, 0.0, 1.0]])
+    }
+
+    /// The matrix flipping a rectangle of given width around its vertical axis
+    pub fn flip_horizontal(width: f64) -> Matrix3 {
+        Matrix3([[-1.0, 0.0, width], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    /// The matrix flipping a rectangle of given height around its horizontal axis
+    ///
+    /// This is notably required to go from the coordinate system of wayland to the one
+    /// of OpenGL textures, whose y axis points up.
+    pub fn flip_vertical(height: f64) -> Matrix3 {
+        Matrix3([[1.0, 0.0, 0.0], [0.0, -1.0, height], [0.0, 0.0, 1.0]])
+    }
+
+    /// The matrix mapping the coordinates of a surface to the ones of its buffer
+    ///
+    /// `transform` and `scale` are the ones set with `wl_surface.set_buffer_transform`
+    /// and `wl_surface.set_buffer_scale`, and `width` x `height` is the size of the
+    /// surface.
+    pub fn surface_to_buffer(transform: Transform, scale: i32, width: f64, height: f64) -> Matrix3 {
+        let scale = f64::from(scale);
+        Matrix3::scale(scale, scale).multiply(&Matrix3::from_transform_in(transform, width, height))
+    }
+
+    /// The matrix mapping the coordinates of a buffer to the ones of its surface
+    ///
+    /// `transform` and `scale` are the ones set with `wl_surface.set_buffer_transform`
+    /// and `wl_surface.set_buffer_scale`, and `width` x `height` is the size of the
+    /// buffer.
+    pub fn buffer_to_surface(transform: Transform, scale: i32, width: f64, height: f64) -> Matrix3 {
+        let scale = f64::from(scale);
+        Matrix3::from_transform_in(inverse(transform), width / scale, height / scale)
+            .multiply(&Matrix3::scale(1.0 / scale, 1.0 / scale))
+    }
+
+    /// The product of this matrix by an other one
+    ///
+    /// The resulting matrix applies `other` first, then `self`.
+    pub fn multiply(&self, other: &Matrix3) -> Matrix3 {
+        let (a, b) = (&self.0, &other.0);
+        let mut result = [[0.0; 3]; 3];
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+            }
+        }
+        Matrix3(result)
+    }
+
+    /// The inverse of this matrix
+    ///
+    /// Returns `None` if this matrix is not invertible, or if its determinant is too large
+    /// to be represented.
+    pub fn inverse(&self) -> Option<Matrix3> {
+        let m = &self.0;
+        // the cofactors, transposed
+        let mut adj = [[0.0; 3]; 3];
+        for (i, row) in adj.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+                let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+                *value = m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+            }
+        }
+        let det = m[0][0] * adj[0][0] + m[0][1] * adj[1][0] + m[0][2] * adj[2][0];
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        for value in adj.iter_mut().flat_map(|row| row.iter_mut()) {
+            *value /= det;
+        }
+        Some(Matrix3(adj))
+    }
+
+    /// Whether all the coefficients of this matrix are finite
+    pub fn is_finite(&self) -> bool {
+        self.0.iter().all(|row| row.iter().all(|value| value.is_finite()))
+    }
+
+    /// Apply this matrix to a point
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let m = &self.0;
+        (
+            m[0][0] * x + m[0][1] * y + m[0][2],
+            m[1][0] * x + m[1][1] * y + m[1][2],
+        )
+    }
+}
+
+impl Default for Matrix3 {
+    fn default() -> Matrix3 {
+        Matrix3::identity()
+    }
+}
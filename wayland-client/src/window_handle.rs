@@ -0,0 +1,66 @@
+//! Integration with `raw-window-handle`
+//!
+//! Graphics libraries like `wgpu` or `glutin` create their surfaces from the handles defined
+//! by the `raw-window-handle` crate. A `WaylandHandle` pairs a `wl_surface` with the `Display`
+//! it belongs to, and implements `HasRawWindowHandle` and `HasRawDisplayHandle`: the surfaces
+//! of this crate can be handed to these libraries directly, without going through a windowing
+//! library like `winit`.
+//!
+//! These libraries access the surface and display through `libwayland-client.so`, this module
+//! is thus only available with the `native_lib` feature, which is enabled by the
+//! `window_handle` feature.
+
+use std::sync::Arc;
+
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle,
+    WaylandWindowHandle,
+};
+
+use imp::DisplayInner;
+use protocol::wl_surface::WlSurface;
+use {Display, Proxy};
+
+/// A surface and its display, as handed to graphics libraries
+///
+/// The handle keeps the connection open, but not the surface: the surface must stay alive
+/// as long as the graphics library uses it.
+#[derive(Clone)]
+pub struct WaylandHandle {
+    display: Arc<DisplayInner>,
+    surface: Proxy<WlSurface>,
+}
+
+impl WaylandHandle {
+    /// Create a handle to a surface of given display
+    ///
+    /// Panics if the surface is already destroyed.
+    pub fn new(display: &Display, surface: &Proxy<WlSurface>) -> WaylandHandle {
+        assert!(surface.is_alive(), "The surface of a WaylandHandle must be alive.");
+        WaylandHandle {
+            display: display.inner.clone(),
+            surface: surface.clone(),
+        }
+    }
+
+    /// The surface of this handle
+    pub fn surface(&self) -> &Proxy<WlSurface> {
+        &self.surface
+    }
+}
+
+unsafe impl HasRawWindowHandle for WaylandHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        let mut handle = WaylandWindowHandle::empty();
+        handle.surface = self.surface.c_ptr() as *mut _;
+        RawWindowHandle::Wayland(handle)
+    }
+}
+
+unsafe impl HasRawDisplayHandle for WaylandHandle {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        let mut handle = WaylandDisplayHandle::empty();
+        handle.display = self.display.ptr() as *mut _;
+        RawDisplayHandle::Wayland(handle)
+    }
+}
@@ -0,0 +1,388 @@
+//! Cursor themes, loaded without `libwayland-cursor.so`
+//!
+//! This module is a rust counterpart to the `cursor` module: it finds the Xcursor themes
+//! installed on the system, decodes their files, and uploads the images of their cursors
+//! into `wl_shm` buffers, which can be attached to the surface of a pointer.
+//!
+//! The themes are searched in the directories listed by `XCURSOR_PATH`, or by default in
+//! `~/.local/share/icons`, `~/.icons`, `/usr/share/icons` and `/usr/share/pixmaps`. A theme
+//! which does not provide a cursor falls back to the themes it inherits from, as listed in
+//! its `index.theme` file, and finally to the `default` theme. `CursorTheme::load()` loads
+//! the theme and size selected by the user with `XCURSOR_THEME` and `XCURSOR_SIZE`.
+//!
+//! The cursors are loaded on first use, each of them in its own `wl_shm_pool`. An animated
+//! cursor has several images, `Cursor::frame_and_duration()` tells which of them to display
+//! at a given time of the animation.
+
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use protocol::wl_buffer::{RequestsTrait as BufferRequests, WlBuffer};
+use protocol::wl_shm::{Format, RequestsTrait as ShmRequests, WlShm};
+use protocol::wl_shm_pool::RequestsTrait as PoolRequests;
+use shm::create_file;
+use Proxy;
+
+// the size of the cursors when `XCURSOR_SIZE` is not set, which is the one of libXcursor
+const DEFAULT_SIZE: u32 = 24;
+
+// the magic number of Xcursor files, "Xcur" in little endian
+const XCURSOR_MAGIC: u32 = 0x7275_6358;
+// the type of the image chunks of Xcursor files
+const XCURSOR_IMAGE_TYPE: u32 = 0xfffd_0002;
+// the maximum width and height of an image
+const XCURSOR_IMAGE_MAX_SIZE: u32 = 0x7fff;
+
+/// An image of an Xcursor file
+#[derive(Clone, Debug, PartialEq)]
+pub struct XcursorImage {
+    /// The nominal size of the image, which the cursor sizes are matched against
+    pub size: u32,
+    /// The width of the image, in pixels
+    pub width: u32,
+    /// The height of the image, in pixels
+    pub height: u32,
+    /// The horizontal position of the hotspot
+    pub xhot: u32,
+    /// The vertical position of the hotspot
+    pub yhot: u32,
+    /// Delay until the next image of an animation, in milliseconds
+    pub delay: u32,
+    /// The pixels, in the `Argb8888` format of `wl_shm`, with premultiplied alpha
+    pub pixels: Vec<u8>,
+}
+
+/// Decode the images of an Xcursor file
+///
+/// The images are returned in the order of the file, in which the frames of an animation
+/// follow each other. Returns `None` if the file is not a valid Xcursor file.
+pub fn parse_xcursor(data: &[u8]) -> Option<Vec<XcursorImage>> {
+    parse_images(data).ok()
+}
+
+fn parse_images(data: &[u8]) -> Result<Vec<XcursorImage>, ()> {
+    let read = |offset: usize| -> Result<u32, ()> {
+        let bytes = data.get(offset..offset + 4).ok_or(())?;
+        Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | u32::from(b)))
+    };
+    if read(0)? != XCURSOR_MAGIC {
+        return Err(());
+    }
+    let header = read(4)? as usize;
+    let ntoc = read(12)? as usize;
+    let mut images = Vec::new();
+    for i in 0..ntoc {
+        let entry = header.checked_add(i * 12).ok_or(())?;
+        if read(entry)? != XCURSOR_IMAGE_TYPE {
+            continue;
+        }
+        let position = read(entry + 8)? as usize;
+        let chunk = |field: usize| read(position.checked_add(field * 4).ok_or(())?);
+        let (chunk_header, width, height) = (chunk(0)? as usize, chunk(4)?, chunk(5)?);
+        let (xhot, yhot) = (chunk(6)?, chunk(7)?);
+        if chunk(1)? != XCURSOR_IMAGE_TYPE
+            || width > XCURSOR_IMAGE_MAX_SIZE
+            || height > XCURSOR_IMAGE_MAX_SIZE
+            || xhot > width
+            || yhot > height
+        {
+            return Err(());
+        }
+        let start = position.checked_add(chunk_header).ok_or(())?;
+        let pixels = data
+            .get(start..start + 4 * width as usize * height as usize)
+            .ok_or(())?;
+        images.push(XcursorImage {
+            size: chunk(2)?,
+            width,
+            height,
+            xhot,
+            yhot,
+            delay: chunk(8)?,
+            pixels: pixels.to_vec(),
+        });
+    }
+    Ok(images)
+}
+
+/// An image of a cursor, in a `wl_shm` buffer
+pub struct CursorImage {
+    buffer: Proxy<WlBuffer>,
+    width: u32,
+    height: u32,
+    hotspot: (u32, u32),
+    delay: u32,
+}
+
+impl CursorImage {
+    /// The buffer holding the image
+    ///
+    /// It is destroyed along with its theme.
+    pub fn buffer(&self) -> &Proxy<WlBuffer> {
+        &self.buffer
+    }
+
+    /// The size of the image, in pixels
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The position of the hotspot of the pointer in the image
+    pub fn hotspot(&self) -> (u32, u32) {
+        self.hotspot
+    }
+
+    /// Delay until the next image of the animation, in milliseconds
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+}
+
+/// A cursor of a theme, with the images of its animation
+pub struct Cursor {
+    name: String,
+    images: Vec<CursorImage>,
+}
+
+impl Cursor {
+    /// The name of the cursor
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The images of the cursor, a single one if it is not animated
+    pub fn images(&self) -> &[CursorImage] {
+        &self.images
+    }
+
+    /// The image to display at a time of the animation, and how long it stays displayed
+    ///
+    /// `time` is the number of milliseconds since the start of the animation, which loops.
+    /// Returns the index of the image in `images()`, with the number of milliseconds until
+    /// the next one. This duration is 0 if the cursor is not animated.
+    pub fn frame_and_duration(&self, time: u32) -> (usize, u32) {
+        let total = self.images.iter().fold(0u32, |acc, image| acc.saturating_add(image.delay));
+        if self.images.len() <= 1 || total == 0 {
+            return (0, 0);
+        }
+        let mut time = time % total;
+        for (i, image) in self.images.iter().enumerate() {
+            if time < image.delay {
+                return (i, image.delay - time);
+            }
+            time -= image.delay;
+        }
+        unreachable!()
+    }
+}
+
+impl Drop for Cursor {
+    fn drop(&mut self) {
+        for image in &self.images {
+            image.buffer.destroy();
+        }
+    }
+}
+
+/// A cursor theme, whose cursors are loaded on demand
+///
+/// See the module documentation for details.
+pub struct CursorTheme {
+    shm: Proxy<WlShm>,
+    name: String,
+    size: u32,
+    cursors: Vec<Cursor>,
+}
+
+impl CursorTheme {
+    /// Load the theme selected by the user
+    ///
+    /// The theme and the size of its cursors are read from `XCURSOR_THEME` and
+    /// `XCURSOR_SIZE`, defaulting to the `default` theme at size 24.
+    pub fn load(shm: &Proxy<WlShm>) -> CursorTheme {
+        let name = env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".into());
+        let size = env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_SIZE);
+        CursorTheme::load_from_name(shm, &name, size)
+    }
+
+    /// Load a theme, with the size of its cursors
+    pub fn load_from_name(shm: &Proxy<WlShm>, name: &str, size: u32) -> CursorTheme {
+        CursorTheme {
+            shm: shm.clone(),
+            name: name.into(),
+            size,
+            cursors: Vec::new(),
+        }
+    }
+
+    /// The name of the theme
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The size of the cursors of the theme
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Get a cursor of the theme, loading it if necessary
+    ///
+    /// Its images are the ones whose nominal size is the closest to the size of the theme.
+    /// Returns `None` if neither the theme nor the ones it falls back to provide this
+    /// cursor, or if it could not be uploaded to the compositor.
+    pub fn get_cursor(&mut self, name: &str) -> Option<&Cursor> {
+        let index = match self.cursors.iter().position(|cursor| cursor.name == name) {
+            Some(index) => index,
+            None => {
+                let cursor = match self.load_cursor(name) {
+                    Ok(cursor) => cursor,
+                    Err(()) => return None,
+                };
+                self.cursors.push(cursor);
+                self.cursors.len() - 1
+            }
+        };
+        Some(&self.cursors[index])
+    }
+
+    fn load_cursor(&self, name: &str) -> Result<Cursor, ()> {
+        let search_path = search_path();
+        let mut visited = Vec::new();
+        let path = find_cursor(&search_path, &self.name, name, &mut visited)
+            .or_else(|| find_cursor(&search_path, "default", name, &mut visited))
+            .ok_or(())?;
+        let mut data = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|_| ())?;
+        let images = parse_images(&data)?;
+        let size = images
+            .iter()
+            .map(|image| image.size)
+            .min_by_key(|&size| (i64::from(size) - i64::from(self.size)).abs())
+            .ok_or(())?;
+        let images = images
+            .into_iter()
+            .filter(|image| image.size == size)
+            .collect::<Vec<_>>();
+        self.upload(name, &images)
+    }
+
+    // upload the images of a cursor into a new pool
+    fn upload(&self, name: &str, images: &[XcursorImage]) -> Result<Cursor, ()> {
+        let mut file = create_file().map_err(|_| ())?;
+        for image in images {
+            file.write_all(&image.pixels).map_err(|_| ())?;
+        }
+        let len = images.iter().map(|image| image.pixels.len()).sum::<usize>();
+        let pool = self
+            .shm
+            .create_pool(file.as_raw_fd(), len as i32, |newp| newp.implement(|_, _| {}, ()))?;
+        let mut offset = 0;
+        let mut cursor = Cursor {
+            name: name.into(),
+            images: Vec::with_capacity(images.len()),
+        };
+        for image in images {
+            let buffer = pool.create_buffer(
+                offset,
+                image.width as i32,
+                image.height as i32,
+                4 * image.width as i32,
+                Format::Argb8888,
+                |newp| newp.implement(|_, _| {}, ()),
+            );
+            match buffer {
+                Ok(buffer) => cursor.images.push(CursorImage {
+                    buffer,
+                    width: image.width,
+                    height: image.height,
+                    hotspot: (image.xhot, image.yhot),
+                    delay: image.delay,
+                }),
+                // the pool died with the connection, the buffers are destroyed with the cursor
+                Err(()) => return Err(()),
+            }
+            offset += image.pixels.len() as i32;
+        }
+        // the buffers keep the memory of the pool alive
+        pool.destroy();
+        Ok(cursor)
+    }
+}
+
+// the directories the themes are searched in, as libXcursor does
+fn search_path() -> Vec<PathBuf> {
+    let path = env::var("XCURSOR_PATH").unwrap_or_else(|_| {
+        "~/.local/share/icons:~/.icons:/usr/share/icons:/usr/share/pixmaps".into()
+    });
+    let home = env::var_os("HOME").map(PathBuf::from);
+    path.split(':')
+        .filter(|dir| !dir.is_empty())
+        .filter_map(|dir| {
+            if dir.starts_with("~/") {
+                home.as_ref().map(|home| home.join(&dir[2..]))
+            } else {
+                Some(PathBuf::from(dir))
+            }
+        }).collect()
+}
+
+// find the file of a cursor in a theme or in the themes it inherits from
+fn find_cursor(
+    search_path: &[PathBuf],
+    theme: &str,
+    name: &str,
+    visited: &mut Vec<String>,
+) -> Option<PathBuf> {
+    if visited.iter().any(|t| t == theme) {
+        return None;
+    }
+    visited.push(theme.into());
+    for dir in search_path {
+        let path = dir.join(theme).join("cursors").join(name);
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+    for dir in search_path {
+        for parent in inherited_themes(&dir.join(theme).join("index.theme")) {
+            if let Some(path) = find_cursor(search_path, &parent, name, visited) {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+// the themes listed by the `Inherits` key of an `index.theme` file
+fn inherited_themes(index: &Path) -> Vec<String> {
+    let file = match File::open(index) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let mut parts = line.splitn(2, '=');
+        if parts.next().map(str::trim) == Some("Inherits") {
+            return parts
+                .next()
+                .unwrap_or("")
+                .split(&[',', ';'][..])
+                .map(str::trim)
+                .filter(|theme| !theme.is_empty())
+                .map(String::from)
+                .collect();
+        }
+    }
+    Vec::new()
+}
@@ -0,0 +1,62 @@
+// Compare the throughput of the direct system calls and of io_uring for the socket IO
+//
+// Run with `cargo run --release --features io_uring --example io_uring_throughput`.
+
+extern crate wayland_commons as wc;
+
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+use std::time::Instant;
+
+use wc::socket::{BufferedSocket, Socket};
+use wc::wire::{Argument, ArgumentType, Message};
+
+const ROUNDS: usize = 20_000;
+const MESSAGES_PER_ROUND: usize = 16;
+
+static SIGNATURE: &'static [ArgumentType] = &[ArgumentType::Int, ArgumentType::Int, ArgumentType::Uint];
+
+fn socket_pair(io_uring: bool) -> (BufferedSocket, BufferedSocket) {
+    let (client, server) = UnixStream::pair().unwrap();
+    let mut client = unsafe { Socket::from_raw_fd(client.into_raw_fd()) };
+    let mut server = unsafe { Socket::from_raw_fd(server.into_raw_fd()) };
+    if io_uring {
+        client.enable_io_uring().expect("io_uring is not available");
+        server.enable_io_uring().expect("io_uring is not available");
+    }
+    (BufferedSocket::new(client), BufferedSocket::new(server))
+}
+
+fn measure(io_uring: bool) -> f64 {
+    let (mut client, mut server) = socket_pair(io_uring);
+    // a batch of small messages, like the input events of a frame
+    let msg = Message {
+        sender_id: 3,
+        opcode: 0,
+        args: vec![Argument::Int(12), Argument::Int(34), Argument::Uint(56)],
+    };
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for _ in 0..MESSAGES_PER_ROUND {
+            client.write_message(&msg).unwrap();
+        }
+        client.flush().unwrap();
+        let mut received = 0;
+        while received < MESSAGES_PER_ROUND {
+            received += server
+                .read_messages(|_, _| Some(SIGNATURE), |_| true)
+                .unwrap()
+                .unwrap();
+        }
+    }
+    let elapsed = start.elapsed();
+    let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+    (ROUNDS * MESSAGES_PER_ROUND) as f64 / secs
+}
+
+fn main() {
+    let direct = measure(false);
+    println!("direct system calls: {:.0} messages/s", direct);
+    let io_uring = measure(true);
+    println!("io_uring:            {:.0} messages/s ({:+.1}%)", io_uring, 100. * (io_uring / direct - 1.));
+}
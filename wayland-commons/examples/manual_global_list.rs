@@ -50,4 +50,6 @@ const GLOBAL_EVENT: MessageDesc = MessageDesc {
     name: "global",
     signature: &[ArgumentType::Uint, ArgumentType::Str, ArgumentType::Uint],
     since: 1,
+    destructor: false,
+    fd_kinds: &[],
 };
@@ -0,0 +1,519 @@
+//! Protocols described at runtime
+//!
+//! The interfaces of `wayland-client` and `wayland-server` are generated at compile time
+//! by `wayland-scanner`. This module instead loads protocol XML files at runtime, which
+//! lets protocol debuggers, proxies and test tools handle interfaces they were not
+//! compiled with.
+//!
+//! A `Protocol` is parsed from an XML file with `Protocol::parse()`, using the parser of
+//! `wayland-scanner`, then its interfaces are registered in a `DynObjectMap`, along with
+//! the ones of the core protocol. The map tracks the objects of a connection from one of
+//! its sides: it provides the signatures needed to read the messages from a
+//! `BufferedSocket`, turns them into `DynMessage`s, and builds the messages to send from
+//! the names of the requests or events and their arguments. The messages are sent or
+//! received relative to this side, a client sends requests and receives events.
+//!
+//! ```no_run
+//! # extern crate wayland_commons;
+//! # use std::fs::File;
+//! # use wayland_commons::dynamic::{DynObjectMap, Protocol, Side};
+//! # use wayland_commons::socket::BufferedSocket;
+//! # use wayland_commons::trace::Direction;
+//! # fn main() {}
+//! # fn read(socket: &mut BufferedSocket) {
+//! let wayland = Protocol::parse(File::open("wayland.xml").unwrap()).unwrap();
+//! let mut map = DynObjectMap::new(&[wayland], Side::Client);
+//! // read the events sent by the server
+//! loop {
+//!     let msg = socket.read_one_message(|id, opcode| map.signature(id, opcode, Direction::Received));
+//!     match msg {
+//!         Ok(msg) => println!("{}", map.receive(msg, Direction::Received).unwrap()),
+//!         Err(_) => break,
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! This requires the `dynamic` cargo feature.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+use std::io::Read;
+use std::sync::Arc;
+
+use wayland_scanner::parse_protocol;
+use wayland_scanner::protocol::{Arg, Message as MessageDesc, Type};
+
+pub use wayland_scanner::{ParseError, Side};
+
+use trace::Direction;
+use wire::{Argument, ArgumentType, Message};
+
+// whether the messages of given side and direction are requests
+fn is_request(side: Side, direction: Direction) -> bool {
+    match (side, direction) {
+        (Side::Client, Direction::Sent) | (Side::Server, Direction::Received) => true,
+        (Side::Client, Direction::Received) | (Side::Server, Direction::Sent) => false,
+    }
+}
+
+/// The description of an argument of a message
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynArgumentDesc {
+    /// The name of the argument
+    pub name: String,
+    /// The type of the argument on the wire
+    pub kind: ArgumentType,
+    /// The interface of the object of `object` and `new_id` arguments, if given
+    pub interface: Option<String>,
+    /// Whether the argument may be a null string or object
+    pub allow_null: bool,
+}
+
+/// The description of a message
+///
+/// The `new_id` arguments without an interface are preceded by the name of this
+/// interface and its version on the wire, like the `bind` request of `wl_registry`,
+/// these two arguments are included in `args` as `interface` and `version`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynMessageDesc {
+    /// The name of the message
+    pub name: String,
+    /// The version of the interface this message appeared in
+    pub since: u32,
+    /// Whether this message destroys its object
+    pub destructor: bool,
+    /// The arguments of the message, in their order on the wire
+    pub args: Vec<DynArgumentDesc>,
+    signature: Vec<ArgumentType>,
+}
+
+impl DynMessageDesc {
+    /// The signature of the message, as given to `Message::from_raw()`
+    pub fn signature(&self) -> &[ArgumentType] {
+        &self.signature
+    }
+}
+
+/// The description of an interface
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynInterface {
+    /// The name of the interface
+    pub name: String,
+    /// The latest version of the interface
+    pub version: u32,
+    /// The requests of the interface, indexed by their opcode
+    pub requests: Vec<DynMessageDesc>,
+    /// The events of the interface, indexed by their opcode
+    pub events: Vec<DynMessageDesc>,
+}
+
+impl DynInterface {
+    /// The messages of the interface sent or received by given side, indexed by their opcode
+    pub fn messages(&self, side: Side, direction: Direction) -> &[DynMessageDesc] {
+        if is_request(side, direction) {
+            &self.requests
+        } else {
+            &self.events
+        }
+    }
+
+    /// The opcode and description of the message with given name, sent or received by given side
+    pub fn message(&self, name: &str, side: Side, direction: Direction) -> Option<(u16, &DynMessageDesc)> {
+        self.messages(side, direction)
+            .iter()
+            .enumerate()
+            .find(|&(_, desc)| desc.name == name)
+            .map(|(opcode, desc)| (opcode as u16, desc))
+    }
+}
+
+/// A protocol, parsed from its XML description
+#[derive(Clone, Debug, PartialEq)]
+pub struct Protocol {
+    /// The name of the protocol
+    pub name: String,
+    /// The interfaces of the protocol
+    pub interfaces: Vec<Arc<DynInterface>>,
+}
+
+fn argument_type(arg: &Arg) -> Result<ArgumentType, ParseError> {
+    Ok(match arg.typ {
+        Type::Int => ArgumentType::Int,
+        Type::Uint => ArgumentType::Uint,
+        Type::Fixed => ArgumentType::Fixed,
+        Type::String => ArgumentType::Str,
+        Type::Object => ArgumentType::Object,
+        Type::NewId => ArgumentType::NewId,
+        Type::Array => ArgumentType::Array,
+        Type::Fd => ArgumentType::Fd,
+        Type::Destructor => {
+            return Err(ParseError::Invalid(format!(
+                "argument `{}` has type `destructor`",
+                arg.name
+            )))
+        }
+    })
+}
+
+fn message_desc(message: MessageDesc) -> Result<DynMessageDesc, ParseError> {
+    let mut args = Vec::new();
+    for arg in message.args {
+        let kind = argument_type(&arg)?;
+        if kind == ArgumentType::NewId && arg.interface.is_none() {
+            for &(name, kind) in &[("interface", ArgumentType::Str), ("version", ArgumentType::Uint)] {
+                args.push(DynArgumentDesc {
+                    name: name.into(),
+                    kind,
+                    interface: None,
+                    allow_null: false,
+                });
+            }
+        }
+        args.push(DynArgumentDesc {
+            name: arg.name,
+            kind,
+            interface: arg.interface,
+            allow_null: arg.allow_null,
+        });
+    }
+    Ok(DynMessageDesc {
+        name: message.name,
+        since: u32::from(message.since),
+        destructor: message.typ == Some(Type::Destructor),
+        signature: args.iter().map(|arg| arg.kind).collect(),
+        args,
+    })
+}
+
+impl Protocol {
+    /// Parse a protocol from its XML description
+    pub fn parse<R: Read>(source: R) -> Result<Protocol, ParseError> {
+        let protocol = parse_protocol(source)?;
+        let mut interfaces = Vec::new();
+        for interface in protocol.interfaces {
+            let mut requests = Vec::new();
+            for request in interface.requests {
+                requests.push(message_desc(request)?);
+            }
+            let mut events = Vec::new();
+            for event in interface.events {
+                events.push(message_desc(event)?);
+            }
+            interfaces.push(Arc::new(DynInterface {
+                name: interface.name,
+                version: interface.version,
+                requests,
+                events,
+            }));
+        }
+        Ok(Protocol {
+            name: protocol.name,
+            interfaces,
+        })
+    }
+
+    /// The interface of this protocol with given name
+    pub fn interface(&self, name: &str) -> Option<&Arc<DynInterface>> {
+        self.interfaces.iter().find(|interface| interface.name == name)
+    }
+}
+
+/// A message of a dynamically described interface
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynMessage {
+    /// The id of the object sending the message
+    pub sender_id: u32,
+    /// The interface of this object
+    pub interface: Arc<DynInterface>,
+    /// The version of this object
+    pub version: u32,
+    /// The side of the connection which sent or received the message
+    pub side: Side,
+    /// The direction of the message, relative to `side`
+    pub direction: Direction,
+    /// The opcode of the message
+    pub opcode: u16,
+    /// The arguments of the message, matching the ones of its description
+    pub args: Vec<Argument>,
+}
+
+impl DynMessage {
+    /// The description of this message
+    pub fn desc(&self) -> &DynMessageDesc {
+        &self.interface.messages(self.side, self.direction)[self.opcode as usize]
+    }
+
+    /// The argument of this message with given name
+    pub fn arg(&self, name: &str) -> Option<&Argument> {
+        self.desc()
+            .args
+            .iter()
+            .position(|arg| arg.name == name)
+            .map(|index| &self.args[index])
+    }
+
+    /// Turn this message into its wire representation
+    pub fn into_raw(self) -> Message {
+        Message {
+            sender_id: self.sender_id,
+            opcode: self.opcode,
+            args: self.args,
+        }
+    }
+}
+
+impl fmt::Display for DynMessage {
+    /// Formats the message like the `WAYLAND_DEBUG` log, as `wl_surface@3.attach(...)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}@{}.{}(",
+            self.interface.name,
+            self.sender_id,
+            self.desc().name
+        )?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match *arg {
+                Argument::Int(value) => write!(f, "{}", value)?,
+                Argument::Uint(value) => write!(f, "{}", value)?,
+                Argument::Fixed(value) => write!(f, "{}", f64::from(value) / 256.)?,
+                Argument::Str(ref value) => write!(f, "{:?}", value)?,
+                Argument::Object(0) => f.write_str("nil")?,
+                Argument::Object(id) => write!(f, "@{}", id)?,
+                Argument::NewId(id) => write!(f, "new id @{}", id)?,
+                Argument::Array(ref value) => write!(f, "array[{}]", value.len())?,
+                Argument::ArrayU64(ref value) => write!(f, "array[{}]", 8 * value.len())?,
+                Argument::Fd(fd) => write!(f, "fd {}", fd)?,
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+/// An error handling a message of a dynamically described interface
+#[derive(Debug, PartialEq)]
+pub enum DynError {
+    /// The object with given id does not exist
+    UnknownObject(u32),
+    /// The interface with given name was not registered
+    UnknownInterface(String),
+    /// The interface of an object has no message with given name or opcode
+    UnknownMessage {
+        /// The interface of the object
+        interface: String,
+        /// The name or opcode of the message
+        message: String,
+    },
+    /// The arguments do not match the signature of the message
+    InvalidArguments,
+    /// A message tried to create an object with an id already in use
+    InvalidNewId(u32),
+}
+
+/// The objects of a connection using dynamically described interfaces
+///
+/// The map handles the messages from one side of the connection. Objects are created by
+/// the `new_id` arguments of the messages given to `receive()` or built with `build()`,
+/// and removed by their destructors.
+#[derive(Clone, Debug)]
+pub struct DynObjectMap {
+    side: Side,
+    interfaces: HashMap<String, Arc<DynInterface>>,
+    objects: HashMap<u32, (Arc<DynInterface>, u32)>,
+}
+
+impl DynObjectMap {
+    /// Create a map with the interfaces of given protocols, for given side of a connection
+    ///
+    /// If they include the core protocol, the `wl_display` object is created with id 1.
+    pub fn new(protocols: &[Protocol], side: Side) -> DynObjectMap {
+        let mut map = DynObjectMap {
+            side,
+            interfaces: HashMap::new(),
+            objects: HashMap::new(),
+        };
+        for protocol in protocols {
+            map.add_protocol(protocol);
+        }
+        let _ = map.insert(1, "wl_display", 1);
+        map
+    }
+
+    /// Register the interfaces of a protocol
+    pub fn add_protocol(&mut self, protocol: &Protocol) {
+        for interface in &protocol.interfaces {
+            self.interfaces.insert(interface.name.clone(), interface.clone());
+        }
+    }
+
+    /// The side of the connection this map handles the messages of
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// The registered interface with given name
+    pub fn interface(&self, name: &str) -> Option<&Arc<DynInterface>> {
+        self.interfaces.get(name)
+    }
+
+    /// Create an object with given id, interface and version
+    ///
+    /// This is typically used for the objects created before the messages were
+    /// tracked by this map.
+    pub fn insert(&mut self, id: u32, interface: &str, version: u32) -> Result<(), DynError> {
+        let interface = self
+            .interfaces
+            .get(interface)
+            .ok_or_else(|| DynError::UnknownInterface(interface.into()))?
+            .clone();
+        if id == 0 || self.objects.contains_key(&id) {
+            return Err(DynError::InvalidNewId(id));
+        }
+        self.objects.insert(id, (interface, version));
+        Ok(())
+    }
+
+    /// Remove an object
+    ///
+    /// Returns whether the object existed.
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.objects.remove(&id).is_some()
+    }
+
+    /// The interface and version of an object
+    pub fn object(&self, id: u32) -> Option<(&Arc<DynInterface>, u32)> {
+        self.objects
+            .get(&id)
+            .map(|&(ref interface, version)| (interface, version))
+    }
+
+    /// The signature of a message, as needed to read it from a `BufferedSocket`
+    pub fn signature(&self, id: u32, opcode: u16, direction: Direction) -> Option<&[ArgumentType]> {
+        self.objects
+            .get(&id)
+            .and_then(|&(ref interface, _)| {
+                interface
+                    .messages(self.side, direction)
+                    .get(opcode as usize)
+            })
+            .map(|desc| desc.signature())
+    }
+
+    /// Process a message read from the connection
+    ///
+    /// `direction` is relative to the side of this map: the messages sent by the other side
+    /// are `Direction::Received`, while a proxy also reads the ones sent by its own side as
+    /// `Direction::Sent`. The objects created or destroyed by the message are added to or removed from
+    /// this map.
+    pub fn receive(&mut self, msg: Message, direction: Direction) -> Result<DynMessage, DynError> {
+        let (interface, version) = self
+            .objects
+            .get(&msg.sender_id)
+            .cloned()
+            .ok_or(DynError::UnknownObject(msg.sender_id))?;
+        if interface.messages(self.side, direction).len() <= msg.opcode as usize {
+            return Err(DynError::UnknownMessage {
+                interface: interface.name.clone(),
+                message: msg.opcode.to_string(),
+            });
+        }
+        let msg = DynMessage {
+            sender_id: msg.sender_id,
+            interface,
+            version,
+            side: self.side,
+            direction,
+            opcode: msg.opcode,
+            args: msg.args,
+        };
+        self.track(&msg)?;
+        Ok(msg)
+    }
+
+    /// Build a message to send on the connection
+    ///
+    /// The arguments must match the description of the message. The objects created or
+    /// destroyed by the message are added to or removed from this map.
+    pub fn build(
+        &mut self,
+        sender_id: u32,
+        name: &str,
+        args: Vec<Argument>,
+        direction: Direction,
+    ) -> Result<DynMessage, DynError> {
+        let (interface, version) = self
+            .objects
+            .get(&sender_id)
+            .cloned()
+            .ok_or(DynError::UnknownObject(sender_id))?;
+        let opcode = match interface.message(name, self.side, direction) {
+            Some((opcode, _)) => opcode,
+            None => {
+                return Err(DynError::UnknownMessage {
+                    interface: interface.name.clone(),
+                    message: name.into(),
+                })
+            }
+        };
+        let msg = DynMessage {
+            sender_id,
+            interface,
+            version,
+            side: self.side,
+            direction,
+            opcode,
+            args,
+        };
+        self.track(&msg)?;
+        Ok(msg)
+    }
+
+    // check the arguments of a message, and apply its effects on the objects
+    fn track(&mut self, msg: &DynMessage) -> Result<(), DynError> {
+        let desc = msg.desc();
+        if desc.args.len() != msg.args.len()
+            || desc
+                .args
+                .iter()
+                .zip(&msg.args)
+                .any(|(desc, arg)| desc.kind != arg.get_type())
+        {
+            return Err(DynError::InvalidArguments);
+        }
+        for (i, arg) in desc.args.iter().enumerate() {
+            let id = match msg.args[i] {
+                Argument::NewId(id) => id,
+                _ => continue,
+            };
+            let (interface, version) = match arg.interface {
+                Some(ref interface) => (interface.clone(), msg.version),
+                // the interface and version precede the generic new_id arguments
+                None => match (&msg.args[i - 2], &msg.args[i - 1]) {
+                    (&Argument::Str(ref interface), &Argument::Uint(version)) => {
+                        (interface_name(interface), version)
+                    }
+                    _ => return Err(DynError::InvalidArguments),
+                },
+            };
+            self.insert(id, &interface, version)?;
+        }
+        if desc.destructor {
+            self.objects.remove(&msg.sender_id);
+        }
+        if msg.interface.name == "wl_display" && desc.name == "delete_id" {
+            if let Argument::Uint(id) = msg.args[0] {
+                self.objects.remove(&id);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn interface_name(name: &CString) -> String {
+    name.to_string_lossy().into_owned()
+}
@@ -15,7 +15,11 @@
 
 #![warn(missing_docs)]
 
+#[cfg(feature = "io_uring")]
+extern crate libc;
 extern crate nix;
+#[cfg(feature = "dynamic")]
+extern crate wayland_scanner;
 #[cfg(feature = "native_lib")]
 extern crate wayland_sys;
 #[cfg(feature = "native_lib")]
@@ -23,9 +27,17 @@ use std::os::raw::c_void;
 #[cfg(feature = "native_lib")]
 use wayland_sys::common as syscom;
 
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
 pub mod map;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod socket;
+pub mod trace;
+#[cfg(feature = "io_uring")]
+mod uring;
 pub mod utils;
+pub mod version;
 pub mod wire;
 
 /// A group of messages
@@ -69,6 +81,23 @@ pub trait MessageGroup: Sized {
     fn as_raw_c_in<F, T>(self, f: F) -> T
     where
         F: FnOnce(u32, &mut [syscom::wl_argument]) -> T;
+    /// Wire description of the message with given opcode
+    ///
+    /// Returns `None` if this opcode does not belong to this group.
+    fn message_desc(opcode: u16) -> Option<&'static wire::MessageDesc> {
+        Self::MESSAGES.get(opcode as usize)
+    }
+    /// Name of the message with given opcode
+    fn name(opcode: u16) -> Option<&'static str> {
+        Self::message_desc(opcode).map(|desc| desc.name)
+    }
+    /// Opcode of the message with given name
+    fn opcode_from_name(name: &str) -> Option<u16> {
+        Self::MESSAGES
+            .iter()
+            .position(|desc| desc.name == name)
+            .map(|opcode| opcode as u16)
+    }
 }
 
 /// The description of a wayland interface
@@ -88,6 +117,12 @@ pub trait Interface: 'static {
     const NAME: &'static str;
     /// Maximum supported version of this interface
     const VERSION: u32;
+    /// A stable hash of the messages of this interface
+    ///
+    /// See `wire::fingerprint()` for details.
+    fn fingerprint() -> u64 {
+        wire::fingerprint(Self::NAME, Self::VERSION, &[Self::Request::MESSAGES, Self::Event::MESSAGES])
+    }
     #[cfg(feature = "native_lib")]
     /// Pointer to the C representation of this interface
     fn c_interface() -> *const ::syscom::wl_interface;
@@ -148,3 +183,42 @@ impl MessageGroup for NoMessage {
         match self {}
     }
 }
+
+/// Assert at compile time that two generated protocol modules are compatible
+///
+/// The two modules, like `wayland_client::protocol::wl_seat` and a module generated by the
+/// scanner of an other crate of the dependency graph, must have the same requests and the
+/// same events, as checked by `wire::messages_compatible()`. Compilation fails otherwise,
+/// which catches the mix of modules generated from different protocol descriptions or by
+/// different versions of the scanner.
+///
+/// ```rust,ignore
+/// assert_protocol_compatible!(my_protocol::wl_seat, wayland_client::protocol::wl_seat);
+/// ```
+///
+/// This macro requires the `const_checks` cargo feature, and rust 1.57.
+#[cfg(feature = "const_checks")]
+#[macro_export]
+macro_rules! assert_protocol_compatible {
+    ($($a:ident)::+, $($b:ident)::+) => {
+        const _: () = {
+            let requests = $crate::wire::messages_compatible(
+                <$($a)::+::Request as $crate::MessageGroup>::MESSAGES,
+                <$($b)::+::Request as $crate::MessageGroup>::MESSAGES,
+            );
+            let events = $crate::wire::messages_compatible(
+                <$($a)::+::Event as $crate::MessageGroup>::MESSAGES,
+                <$($b)::+::Event as $crate::MessageGroup>::MESSAGES,
+            );
+            if !(requests && events) {
+                panic!(concat!(
+                    "the protocol modules `",
+                    stringify!($($a)::+),
+                    "` and `",
+                    stringify!($($b)::+),
+                    "` are not compatible"
+                ));
+            }
+        };
+    };
+}
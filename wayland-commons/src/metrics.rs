@@ -0,0 +1,176 @@
+//! Rendering of metrics in the Prometheus text format
+//!
+//! This module writes the counters of the library in the
+//! [exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/) read
+//! by Prometheus and compatible scrapers. It is used by the `metrics` modules of
+//! `wayland-client` and `wayland-server`, which collect the counters of their connections.
+//!
+//! Each metric family is written at once: its `# HELP` and `# TYPE` lines are followed by
+//! all its samples, as the format requires.
+
+use std::io::{self, Write};
+
+use socket::ConnectionStats;
+
+/// The type of a metric family
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetricType {
+    /// A value that only increases, like a number of messages
+    Counter,
+    /// A value that can increase and decrease, like a number of clients
+    Gauge,
+}
+
+/// A metric family being written
+pub struct Family<'a, W: Write + 'a> {
+    out: &'a mut W,
+    name: &'a str,
+}
+
+/// Start writing a metric family
+///
+/// This writes the `# HELP` and `# TYPE` lines of the family, its samples are then written
+/// with `Family::sample()`.
+pub fn family<'a, W: Write>(
+    out: &'a mut W,
+    name: &'a str,
+    help: &str,
+    kind: MetricType,
+) -> io::Result<Family<'a, W>> {
+    writeln!(out, "# HELP {} {}", name, escape(help, false))?;
+    writeln!(
+        out,
+        "# TYPE {} {}",
+        name,
+        match kind {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+        }
+    )?;
+    Ok(Family { out, name })
+}
+
+impl<'a, W: Write> Family<'a, W> {
+    /// Write a sample of this family, with given labels
+    pub fn sample(&mut self, labels: &[(&str, &str)], value: u64) -> io::Result<()> {
+        write!(self.out, "{}", self.name)?;
+        for (i, &(name, value)) in labels.iter().enumerate() {
+            write!(
+                self.out,
+                "{}{}=\"{}\"",
+                if i == 0 { "{" } else { "," },
+                name,
+                escape(value, true)
+            )?;
+        }
+        if !labels.is_empty() {
+            write!(self.out, "}}")?;
+        }
+        writeln!(self.out, " {}", value)
+    }
+}
+
+// the families of `write_connection_stats()`, in the order of `counter_values()`
+const CONNECTION_COUNTERS: [(&str, &str); 6] = [
+    ("sent_messages_total", "Number of messages sent."),
+    ("received_messages_total", "Number of messages received."),
+    ("sent_bytes_total", "Number of bytes sent."),
+    ("received_bytes_total", "Number of bytes received."),
+    ("sent_fds_total", "Number of file descriptors sent."),
+    ("received_fds_total", "Number of file descriptors received."),
+];
+
+fn counter_values(stats: &ConnectionStats) -> [u64; 6] {
+    [
+        stats.sent_messages,
+        stats.received_messages,
+        stats.sent_bytes,
+        stats.received_bytes,
+        stats.sent_fds,
+        stats.received_fds,
+    ]
+}
+
+/// Write the counters of connections
+///
+/// The families are named after `prefix`, like `<prefix>_sent_messages_total`, and have a
+/// sample for each of the given connections, with its labels.
+pub fn write_connection_stats<W: Write>(
+    out: &mut W,
+    prefix: &str,
+    connections: &[(&[(&str, &str)], ConnectionStats)],
+) -> io::Result<()> {
+    for (i, &(suffix, help)) in CONNECTION_COUNTERS.iter().enumerate() {
+        let name = format!("{}_{}", prefix, suffix);
+        let mut family = family(out, &name, help, MetricType::Counter)?;
+        for &(labels, ref stats) in connections {
+            family.sample(labels, counter_values(stats)[i])?;
+        }
+    }
+    Ok(())
+}
+
+// escape a help text or a label value
+fn escape(text: &str, quotes: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quotes => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_family() {
+        let mut out = Vec::new();
+        {
+            let mut family = family(&mut out, "clients", "Connected\nclients.", MetricType::Gauge).unwrap();
+            family.sample(&[], 3).unwrap();
+            family.sample(&[("name", "a \"b\"\\"), ("id", "1")], 4).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# HELP clients Connected\\nclients.\n\
+             # TYPE clients gauge\n\
+             clients 3\n\
+             clients{name=\"a \\\"b\\\"\\\\\",id=\"1\"} 4\n"
+        );
+    }
+
+    #[test]
+    fn render_connection_stats() {
+        let stats = ConnectionStats {
+            sent_messages: 1,
+            received_messages: 2,
+            sent_bytes: 3,
+            received_bytes: 4,
+            sent_fds: 5,
+            received_fds: 6,
+        };
+        let mut out = Vec::new();
+        write_connection_stats(&mut out, "test", &[(&[("client", "0")], stats)]).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let samples = out.lines().filter(|l| !l.starts_with('#')).collect::<Vec<_>>();
+        assert_eq!(
+            samples,
+            [
+                "test_sent_messages_total{client=\"0\"} 1",
+                "test_received_messages_total{client=\"0\"} 2",
+                "test_sent_bytes_total{client=\"0\"} 3",
+                "test_received_bytes_total{client=\"0\"} 4",
+                "test_sent_fds_total{client=\"0\"} 5",
+                "test_received_fds_total{client=\"0\"} 6",
+            ]
+        );
+        assert!(out.starts_with("# HELP test_sent_messages_total Number of messages sent.\n"));
+        assert_eq!(out.lines().filter(|l| l.starts_with("# TYPE")).count(), 6);
+    }
+}
@@ -1,11 +1,15 @@
 //! Wayland socket manipulation
 
+#[cfg(feature = "io_uring")]
+use std::cell::RefCell;
 use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 
 use nix::sys::socket;
 use nix::sys::uio;
 use nix::Result as NixResult;
 
+#[cfg(feature = "io_uring")]
+use uring::Ring;
 use wire::{ArgumentType, Message, MessageParseError, MessageWriteError};
 
 /// Maximum number of FD that can be sent in a single socket message
@@ -20,9 +24,37 @@ pub const MAX_BYTES_OUT: usize = 4096;
 /// A wayland socket
 pub struct Socket {
     fd: RawFd,
+    #[cfg(feature = "io_uring")]
+    ring: Option<RefCell<Ring>>,
 }
 
 impl Socket {
+    /// Submit the IO of this socket through an io_uring
+    ///
+    /// This is an experimental alternative to the direct `sendmsg` and `recvmsg` system
+    /// calls, requiring the `io_uring` cargo feature. The socket keeps its non-blocking
+    /// semantics, so it is still polled for readiness as usual. As each operation is
+    /// submitted and waited for on its own, this does not save system calls yet, the
+    /// `io_uring_throughput` example compares both paths.
+    ///
+    /// Errors if the kernel does not support io_uring or forbids it, the socket then
+    /// keeps using the direct system calls.
+    #[cfg(feature = "io_uring")]
+    pub fn enable_io_uring(&mut self) -> NixResult<()> {
+        if self.ring.is_none() {
+            self.ring = Some(RefCell::new(Ring::new()?));
+        }
+        Ok(())
+    }
+
+    /// Whether the IO of this socket is submitted through an io_uring
+    ///
+    /// See `enable_io_uring()`.
+    #[cfg(feature = "io_uring")]
+    pub fn uses_io_uring(&self) -> bool {
+        self.ring.is_some()
+    }
+
     /// Send a single message to the socket
     ///
     /// A single socket message can contain several wayland messages
@@ -31,6 +63,12 @@ impl Socket {
     /// slice should not be longer than `MAX_BYTES_OUT` otherwise the receiving
     /// end may lose some data.
     pub fn send_msg(&self, bytes: &[u8], fds: &[RawFd]) -> NixResult<()> {
+        #[cfg(feature = "io_uring")]
+        {
+            if let Some(ref ring) = self.ring {
+                return ring.borrow_mut().send_msg(self.fd, bytes, fds);
+            }
+        }
         let iov = [uio::IoVec::from_slice(bytes)];
         if fds.len() > 0 {
             let cmsgs = [socket::ControlMessage::ScmRights(fds)];
@@ -53,10 +91,16 @@ impl Socket {
     /// slice `MAX_FDS_OUT` long, otherwise some data of the received message may
     /// be lost.
     pub fn rcv_msg(&self, buffer: &mut [u8], fds: &mut [RawFd]) -> NixResult<(usize, usize)> {
+        #[cfg(feature = "io_uring")]
+        {
+            if let Some(ref ring) = self.ring {
+                return ring.borrow_mut().rcv_msg(self.fd, buffer, fds);
+            }
+        }
         let mut cmsg = socket::CmsgSpace::<[RawFd; MAX_FDS_OUT]>::new();
         let iov = [uio::IoVec::from_mut_slice(buffer)];
 
-        let msg = socket::recvmsg(self.fd, &iov[..], Some(&mut cmsg), socket::MsgFlags::MSG_DONTWAIT)?;
+        let msg = socket::recvmsg(self.fd, &iov[..], Some(&mut cmsg), recv_flags())?;
 
         let mut fd_count = 0;
         let received_fds = msg.cmsgs().flat_map(|cmsg| {
@@ -69,13 +113,70 @@ impl Socket {
             fd_count += 1;
             *place = *fd;
         }
+        set_cloexec(&fds[..fd_count]);
         Ok((msg.bytes, fd_count))
     }
 }
 
+// The received fds are close-on-exec, like the ones received by libwayland, so that
+// they do not leak into the child processes. The flag is set atomically by `recvmsg`
+// where the platform supports it, and afterwards otherwise.
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn recv_flags() -> socket::MsgFlags {
+    socket::MsgFlags::MSG_DONTWAIT | socket::MsgFlags::MSG_CMSG_CLOEXEC
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn set_cloexec(_: &[RawFd]) {}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn recv_flags() -> socket::MsgFlags {
+    socket::MsgFlags::MSG_DONTWAIT
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn set_cloexec(fds: &[RawFd]) {
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+    for &fd in fds {
+        let _ = fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC));
+    }
+}
+
 impl FromRawFd for Socket {
     unsafe fn from_raw_fd(fd: RawFd) -> Socket {
-        Socket { fd }
+        Socket {
+            fd,
+            #[cfg(feature = "io_uring")]
+            ring: None,
+        }
     }
 }
 
@@ -109,6 +210,38 @@ pub struct BufferedSocket {
     in_fds: Buffer<RawFd>,
     out_data: Buffer<u32>,
     out_fds: Buffer<RawFd>,
+    stats: ConnectionStats,
+}
+
+/// Counters of the data exchanged through a `BufferedSocket`
+///
+/// The messages are counted as they are written to or read from the buffers, while the bytes
+/// and file descriptors are counted as they are actually sent to or received from the socket.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Number of messages written
+    pub sent_messages: u64,
+    /// Number of messages read
+    pub received_messages: u64,
+    /// Number of bytes sent
+    pub sent_bytes: u64,
+    /// Number of bytes received
+    pub received_bytes: u64,
+    /// Number of file descriptors sent
+    pub sent_fds: u64,
+    /// Number of file descriptors received
+    pub received_fds: u64,
+}
+
+impl ::std::ops::AddAssign for ConnectionStats {
+    fn add_assign(&mut self, other: ConnectionStats) {
+        self.sent_messages += other.sent_messages;
+        self.received_messages += other.received_messages;
+        self.sent_bytes += other.sent_bytes;
+        self.received_bytes += other.received_bytes;
+        self.sent_fds += other.sent_fds;
+        self.received_fds += other.received_fds;
+    }
 }
 
 impl BufferedSocket {
@@ -120,6 +253,7 @@ impl BufferedSocket {
             in_fds: Buffer::new(2 * MAX_FDS_OUT),        // able to store leftover data if needed
             out_data: Buffer::new(MAX_BYTES_OUT / 4),
             out_fds: Buffer::new(MAX_FDS_OUT),
+            stats: ConnectionStats::default(),
         }
     }
 
@@ -128,6 +262,30 @@ impl BufferedSocket {
         &mut self.socket
     }
 
+    /// Number of messages written to this socket so far
+    ///
+    /// This is the trace id of the last written message, see the `trace` module.
+    pub fn sent_messages(&self) -> u64 {
+        self.stats.sent_messages
+    }
+
+    /// Number of messages read from this socket so far
+    ///
+    /// This is the trace id of the last read message, see the `trace` module.
+    pub fn received_messages(&self) -> u64 {
+        self.stats.received_messages
+    }
+
+    /// Counters of the data exchanged through this socket so far
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats
+    }
+
+    /// Whether the incoming buffers hold data which was not read as messages yet
+    pub fn has_incoming_data(&self) -> bool {
+        self.in_data.has_content() || self.in_fds.has_content()
+    }
+
     /// Retreive ownership of the underlying Socket
     ///
     /// Any leftover content in the internal buffers will be lost
@@ -142,6 +300,12 @@ impl BufferedSocket {
             let bytes = unsafe { ::std::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4) };
             let fds = self.out_fds.get_contents();
             self.socket.send_msg(bytes, fds)?;
+            self.stats.sent_bytes += bytes.len() as u64;
+            self.stats.sent_fds += fds.len() as u64;
+            // the fds were dup-ed when writing the messages, the peer now has its own copy
+            for &fd in fds {
+                let _ = ::nix::unistd::close(fd);
+            }
         }
         self.out_data.clear();
         self.out_fds.clear();
@@ -187,19 +351,17 @@ impl BufferedSocket {
                 return Err(::nix::Error::Sys(::nix::errno::Errno::E2BIG));
             }
         }
+        self.stats.sent_messages += 1;
         Ok(())
     }
 
     /// Try to fill the incoming buffers of this socket, to prepare
     /// a new round of parsing.
     pub fn fill_incoming_buffers(&mut self) -> NixResult<()> {
-        // clear the buffers if they have no content
-        if !self.in_data.has_content() {
-            self.in_data.clear();
-        }
-        if !self.in_fds.has_content() {
-            self.in_fds.clear();
-        }
+        // make room after the unread content, a partial message may have been left at the
+        // end of the buffers by `read_one_message()`
+        self.in_data.move_to_front();
+        self.in_fds.move_to_front();
         // receive a message
         let (in_bytes, in_fds) = {
             let words = self.in_data.get_writable_storage();
@@ -212,6 +374,8 @@ impl BufferedSocket {
             // the other end of the socket was closed
             return Err(::nix::Error::Sys(::nix::errno::Errno::EPIPE));
         }
+        self.stats.received_bytes += in_bytes as u64;
+        self.stats.received_fds += in_fds as u64;
         // advance the storage
         self.in_data
             .advance(in_bytes / 4 + if in_bytes % 4 > 0 { 1 } else { 0 });
@@ -223,7 +387,7 @@ impl BufferedSocket {
     ///
     /// This method requires one closure that given an object id and an opcode,
     /// must provide the signature of the associated request/event, in the form of
-    /// a `&'static [ArgumentType]`. If it returns `None`, meaning that
+    /// a `&[ArgumentType]`. If it returns `None`, meaning that
     /// the couple object/opcode does not exist, an error will be returned.
     ///
     /// There are 3 possibilities of return value:
@@ -234,9 +398,9 @@ impl BufferedSocket {
     /// - `Err(e)`: an I/O error occured reading from the socked, details are in `e`
     ///   (this can be a "wouldblock" error, which just means that no message is available
     ///   to read)
-    pub fn read_one_message<F>(&mut self, mut signature: F) -> Result<Message, MessageParseError>
+    pub fn read_one_message<'a, F>(&mut self, mut signature: F) -> Result<Message, MessageParseError>
     where
-        F: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
+        F: FnMut(u32, u16) -> Option<&'a [ArgumentType]>,
     {
         let (msg, read_data, read_fd) = {
             let mut data = self.in_data.get_contents();
@@ -251,7 +415,6 @@ impl BufferedSocket {
                     Ok((msg, rest_data, rest_fds)) => {
                         (msg, data.len() - rest_data.len(), fds.len() - rest_fds.len())
                     }
-                    // TODO: gracefully handle wayland messages split accross unix messages ?
                     Err(e) => return Err(e),
                 }
             } else {
@@ -262,6 +425,7 @@ impl BufferedSocket {
 
         self.in_data.offset(read_data);
         self.in_fds.offset(read_fd);
+        self.stats.received_messages += 1;
 
         Ok(msg)
     }
@@ -272,7 +436,7 @@ impl BufferedSocket {
     ///
     /// - The first one, given an object id and an opcode, must provide
     ///   the signature of the associated request/event, in the form of
-    ///   a `&'static [ArgumentType]`. If it returns `None`, meaning that
+    ///   a `&[ArgumentType]`. If it returns `None`, meaning that
     ///   the couple object/opcode does not exist, the parsing will be
     ///   prematurely interrupted and this method will return a
     ///   `MessageParseError::Malformed` error.
@@ -291,13 +455,13 @@ impl BufferedSocket {
     /// - `Err(e)`: an I/O error occured reading from the socked, details are in `e`
     ///   (this can be a "wouldblock" error, which just means that no message is available
     ///   to read)
-    pub fn read_messages<F1, F2>(
+    pub fn read_messages<'a, F1, F2>(
         &mut self,
         mut signature: F1,
         mut callback: F2,
     ) -> NixResult<Result<usize, MessageParseError>>
     where
-        F1: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
+        F1: FnMut(u32, u16) -> Option<&'a [ArgumentType]>,
         F2: FnMut(Message) -> bool,
     {
         // message parsing
@@ -413,6 +577,11 @@ impl<T: Copy + Default> Buffer<T> {
     /// Move the unread contents of the buffer to the front, to ensure
     /// maximal write space availability
     fn move_to_front(&mut self) {
+        if !self.has_content() {
+            // the offset may be at the end of the storage
+            self.clear();
+            return;
+        }
         unsafe {
             ::std::ptr::copy(
                 &self.storage[self.offset] as *const T,
@@ -546,6 +715,275 @@ mod tests {
         assert_eq!(ret, 1);
     }
 
+    #[test]
+    fn read_one_message_across_buffers() {
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        // the messages do not fill the incoming buffers exactly, so some are split between two reads
+        for i in 0..1000 {
+            client
+                .write_message(&Message {
+                    sender_id: 42,
+                    opcode: 0,
+                    args: vec![Argument::Uint(i)],
+                }).unwrap();
+        }
+        client.flush().unwrap();
+
+        static SIGNATURE: &'static [ArgumentType] = &[ArgumentType::Uint];
+
+        for i in 0..1000 {
+            let msg = match server.read_one_message(|_, _| Some(SIGNATURE)) {
+                Ok(msg) => msg,
+                Err(MessageParseError::MissingData) => {
+                    server.fill_incoming_buffers().unwrap();
+                    server.read_one_message(|_, _| Some(SIGNATURE)).unwrap()
+                }
+                Err(e) => panic!("Unexpected error: {:?}", e),
+            };
+            assert_eq!(msg.args, vec![Argument::Uint(i)]);
+        }
+    }
+
+    #[test]
+    fn read_messages_filling_buffers() {
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        // messages of 4 words filling the incoming buffers exactly, twice
+        let count = 2 * server.in_data.storage.len() / 4;
+        for i in 0..count {
+            client
+                .write_message(&Message {
+                    sender_id: 42,
+                    opcode: 0,
+                    args: vec![Argument::Uint(i as u32), Argument::Int(0)],
+                }).unwrap();
+        }
+        client.flush().unwrap();
+
+        static SIGNATURE: &'static [ArgumentType] = &[ArgumentType::Uint, ArgumentType::Int];
+
+        let mut received = 0;
+        while received < count {
+            let ret = server
+                .read_messages(
+                    |_, _| Some(SIGNATURE),
+                    |message| {
+                        assert_eq!(message.args[0], Argument::Uint(received as u32));
+                        received += 1;
+                        true
+                    },
+                ).unwrap();
+            ret.unwrap();
+        }
+    }
+
+    #[test]
+    fn incoming_data_is_tracked() {
+        let msg = Message {
+            sender_id: 42,
+            opcode: 0,
+            args: vec![],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        client.write_message(&msg).unwrap();
+        client.write_message(&msg).unwrap();
+        client.flush().unwrap();
+
+        assert!(!server.has_incoming_data());
+        server.fill_incoming_buffers().unwrap();
+        assert!(server.has_incoming_data());
+        server.read_one_message(|_, _| Some(&[])).unwrap();
+        assert!(server.has_incoming_data());
+        server.read_one_message(|_, _| Some(&[])).unwrap();
+        assert!(!server.has_incoming_data());
+    }
+
+    #[test]
+    fn stats_are_counted() {
+        let msg = Message {
+            sender_id: 42,
+            opcode: 0,
+            args: vec![Argument::Uint(3), Argument::Fd(0)],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        client.write_message(&msg).unwrap();
+        client.write_message(&msg).unwrap();
+        // nothing is sent until the buffers are flushed
+        assert_eq!(client.stats().sent_messages, 2);
+        assert_eq!(client.stats().sent_bytes, 0);
+        client.flush().unwrap();
+
+        static SIGNATURE: &'static [ArgumentType] = &[ArgumentType::Uint, ArgumentType::Fd];
+        server
+            .read_messages(
+                |_, _| Some(SIGNATURE),
+                |message| {
+                    for arg in message.args {
+                        if let Argument::Fd(fd) = arg {
+                            ::nix::unistd::close(fd).unwrap();
+                        }
+                    }
+                    true
+                },
+            ).unwrap()
+            .unwrap();
+
+        let expected = ConnectionStats {
+            sent_messages: 2,
+            received_messages: 0,
+            sent_bytes: 24,
+            received_bytes: 0,
+            sent_fds: 2,
+            received_fds: 0,
+        };
+        assert_eq!(client.stats(), expected);
+        assert_eq!(
+            server.stats(),
+            ConnectionStats {
+                sent_messages: 0,
+                received_messages: 2,
+                sent_bytes: 0,
+                received_bytes: 24,
+                sent_fds: 0,
+                received_fds: 2,
+            }
+        );
+
+        let mut total = expected;
+        total += server.stats();
+        assert_eq!(total.sent_messages, 2);
+        assert_eq!(total.received_fds, 2);
+    }
+
+    #[test]
+    fn sent_fds_are_closed() {
+        use nix::fcntl::OFlag;
+
+        let (pipe_read, pipe_write) = ::nix::unistd::pipe2(OFlag::O_NONBLOCK).unwrap();
+        let msg = Message {
+            sender_id: 42,
+            opcode: 0,
+            args: vec![Argument::Fd(pipe_write)],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        client.write_message(&msg).unwrap();
+        client.flush().unwrap();
+        ::nix::unistd::close(pipe_write).unwrap();
+
+        static SIGNATURE: &'static [ArgumentType] = &[ArgumentType::Fd];
+        server
+            .read_messages(
+                |_, _| Some(SIGNATURE),
+                |message| {
+                    if let Argument::Fd(fd) = message.args[0] {
+                        ::nix::unistd::close(fd).unwrap();
+                    }
+                    true
+                },
+            ).unwrap()
+            .unwrap();
+
+        // all the write ends are closed, the pipe reaches its end instead of waiting for data
+        let mut buffer = [0u8; 1];
+        assert_eq!(::nix::unistd::read(pipe_read, &mut buffer), Ok(0));
+        ::nix::unistd::close(pipe_read).unwrap();
+    }
+
+    #[test]
+    fn received_fds_are_cloexec() {
+        use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let client = unsafe { Socket::from_raw_fd(client.into_raw_fd()) };
+        let server = unsafe { Socket::from_raw_fd(server.into_raw_fd()) };
+
+        client.send_msg(&[0, 0, 0, 0], &[0]).unwrap();
+        let mut buffer = [0; 4];
+        let mut fds = [0; MAX_FDS_OUT];
+        assert_eq!(server.rcv_msg(&mut buffer, &mut fds).unwrap(), (4, 1));
+        let flags = FdFlag::from_bits_truncate(fcntl(fds[0], FcntlArg::F_GETFD).unwrap());
+        assert!(flags.contains(FdFlag::FD_CLOEXEC));
+        ::nix::unistd::close(fds[0]).unwrap();
+    }
+
+    #[cfg(feature = "io_uring")]
+    #[test]
+    fn write_read_cycle_io_uring() {
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: vec![
+                Argument::Str(CString::new(&b"I like rings"[..]).unwrap()),
+                Argument::Fd(1), // stdin
+                Argument::Fd(0), // stdout
+            ],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = unsafe { Socket::from_raw_fd(client.into_raw_fd()) };
+        match client.enable_io_uring() {
+            Ok(()) => {}
+            // io_uring is missing from the kernel, or forbidden by a seccomp filter or sysctl
+            Err(::nix::Error::Sys(::nix::errno::Errno::ENOSYS))
+            | Err(::nix::Error::Sys(::nix::errno::Errno::EPERM)) => {
+                eprintln!("io_uring is not available, skipping the test");
+                return;
+            }
+            Err(e) => panic!("Failed to set up an io_uring: {:?}", e),
+        }
+        assert!(client.uses_io_uring());
+        let mut client = BufferedSocket::new(client);
+        let mut server = unsafe { Socket::from_raw_fd(server.into_raw_fd()) };
+        server.enable_io_uring().unwrap();
+        let mut server = BufferedSocket::new(server);
+
+        // nothing to read yet, the socket does not block
+        assert_eq!(
+            server.fill_incoming_buffers(),
+            Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN))
+        );
+
+        client.write_message(&msg).unwrap();
+        client.flush().unwrap();
+
+        static SIGNATURE: &'static [ArgumentType] = &[ArgumentType::Str, ArgumentType::Fd, ArgumentType::Fd];
+
+        let ret = server
+            .read_messages(
+                |sender_id, opcode| {
+                    if sender_id == 42 && opcode == 7 {
+                        Some(SIGNATURE)
+                    } else {
+                        None
+                    }
+                },
+                |message| {
+                    assert_eq_msgs(&message, &msg);
+                    true
+                },
+            ).unwrap()
+            .unwrap();
+
+        assert_eq!(ret, 1);
+    }
+
     #[test]
     fn write_read_cycle_multiple() {
         let messages = [
@@ -612,6 +1050,11 @@ mod tests {
         for (msg1, msg2) in messages.iter().zip(recv_msgs.iter()) {
             assert_eq_msgs(msg1, msg2);
         }
+        // both ends agree on the trace ids
+        assert_eq!(client.sent_messages(), 3);
+        assert_eq!(server.received_messages(), 3);
+        assert_eq!(client.received_messages(), 0);
+        assert_eq!(server.sent_messages(), 0);
     }
 
     #[test]
@@ -0,0 +1,97 @@
+//! Trace ids of the protocol messages
+//!
+//! Setting the `WAYLAND_DEBUG` environment variable to `trace` (rather than any other value)
+//! adds a trace id to each message of the debug log of the rust implementation. The trace id
+//! of a message is its sequence number, starting at 1, among the messages sent in the same
+//! direction on its connection.
+//!
+//! As a connection delivers the messages in the order they were sent, both ends give the
+//! same trace id to a message: the line ` -> [trace 12] wl_surface@3: commit []` of the log
+//! of a client matches the line ` <- [trace 12] wl_surface@3: commit []` of the log of its
+//! server, which allows to correlate the requests and events of logs captured separately.
+//!
+//! The C libraries only support the plain `WAYLAND_DEBUG` log, without trace ids.
+//!
+//! Rather than printed, the messages can be given to a `ProtocolLogger` set on the `Display`,
+//! to be routed to a logging framework or captured for a bug report. The lines of the
+//! `WAYLAND_DEBUG` log are then formatted by `log_line()`. Only the server side supports
+//! them with the C libraries, with a trace id of 0.
+
+use std::sync::Arc;
+
+use wire::Message;
+
+/// The direction of a message, relative to one side of the connection
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// A message sent to the other end of the connection
+    Sent,
+    /// A message received from the other end of the connection
+    Received,
+}
+
+/// A message given to a protocol logger
+#[derive(Debug)]
+pub struct LoggedMessage<'a> {
+    /// The interface of the object of the message
+    pub interface: &'static str,
+    /// The name of the message
+    pub name: &'static str,
+    /// The trace id of the message
+    pub trace_id: u64,
+    /// The message, with the id of its object and its arguments
+    pub message: &'a Message,
+}
+
+/// A logger receiving the messages of a connection
+///
+/// It is invoked from the threads sending and dispatching the messages, and while internal
+/// locks of the library are held: it must not send messages itself.
+pub type ProtocolLogger = Arc<Fn(Direction, &LoggedMessage) + Send + Sync>;
+
+/// Log a message with given logger, or print it if `WAYLAND_DEBUG` is set
+pub fn log(logger: Option<&ProtocolLogger>, direction: Direction, message: &LoggedMessage) {
+    match logger {
+        Some(logger) => logger(direction, message),
+        None => {
+            if ::std::env::var_os("WAYLAND_DEBUG").is_some() {
+                println!("{}", log_line(direction, message));
+            }
+        }
+    }
+}
+
+/// The line of the `WAYLAND_DEBUG` log of a message
+pub fn log_line(direction: Direction, message: &LoggedMessage) -> String {
+    format!(
+        " {} {}{}@{}: {} {:?}",
+        match direction {
+            Direction::Sent => "->",
+            Direction::Received => "<-",
+        },
+        tag(message.trace_id),
+        message.interface,
+        message.message.sender_id,
+        message.name,
+        message.message.args
+    )
+}
+
+/// Whether the trace ids are enabled
+pub fn enabled() -> bool {
+    match ::std::env::var_os("WAYLAND_DEBUG") {
+        Some(value) => value.to_str() == Some("trace"),
+        None => false,
+    }
+}
+
+/// The tag of the log line of a message with given trace id
+///
+/// This is an empty string if the trace ids are not enabled.
+pub fn tag(id: u64) -> String {
+    if enabled() {
+        format!("[trace {}] ", id)
+    } else {
+        String::new()
+    }
+}
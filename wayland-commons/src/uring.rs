@@ -0,0 +1,344 @@
+// A minimal io_uring, submitting the sendmsg and recvmsg calls of a `Socket`
+//
+// Operations are submitted one at a time and waited for, with `MSG_DONTWAIT` like the
+// direct system calls, so that the socket keeps its non-blocking semantics and the
+// readiness of the connection is still polled by the event loops.
+//
+// This does not batch submissions: a socket only has one message to send or receive at
+// a time, so each operation costs an `io_uring_enter` call, like the system call it
+// replaces. Batching would require the socket to queue its messages across flushes.
+//
+// The submitted entries point to buffers on the stack of `send_msg()` and `rcv_msg()`,
+// so `run()` never returns while the kernel may still access them.
+
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::sync::atomic::{fence, Ordering};
+
+use libc;
+use nix::errno::Errno;
+use nix::{Error, Result as NixResult};
+
+use socket::MAX_FDS_OUT;
+
+const IORING_OP_SENDMSG: u8 = 9;
+const IORING_OP_RECVMSG: u8 = 10;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+const ENTRIES: u32 = 4;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    msg_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> NixResult<Mapping> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last());
+        }
+        Ok(Mapping {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    // a pointer to the field of the ring at given offset
+    fn at<T>(&self, offset: u32) -> *mut T {
+        self.ptr.wrapping_offset(offset as isize) as *mut T
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut _, self.len);
+        }
+    }
+}
+
+pub(crate) struct Ring {
+    fd: RawFd,
+    sq: Mapping,
+    sq_off: SqringOffsets,
+    sqes: Mapping,
+    cq: Mapping,
+    cq_off: CqringOffsets,
+}
+
+// the mappings are only accessed through `&mut Ring`
+unsafe impl Send for Ring {}
+
+impl Ring {
+    pub(crate) fn new() -> NixResult<Ring> {
+        let mut params = Params::default();
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, ENTRIES, &mut params as *mut Params) };
+        if fd < 0 {
+            return Err(Error::last());
+        }
+        let fd = fd as RawFd;
+        let rings = (|| -> NixResult<_> {
+            let sq_len = params.sq_off.array as usize + params.sq_entries as usize * mem::size_of::<u32>();
+            let cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+            let sq = Mapping::new(fd, sq_len, IORING_OFF_SQ_RING)?;
+            let sqes = Mapping::new(
+                fd,
+                params.sq_entries as usize * mem::size_of::<Sqe>(),
+                IORING_OFF_SQES,
+            )?;
+            let cq = Mapping::new(fd, cq_len, IORING_OFF_CQ_RING)?;
+            Ok((sq, sqes, cq))
+        })();
+        match rings {
+            Ok((sq, sqes, cq)) => Ok(Ring {
+                fd,
+                sq,
+                sq_off: params.sq_off,
+                sqes,
+                cq,
+                cq_off: params.cq_off,
+            }),
+            Err(e) => {
+                let _ = ::nix::unistd::close(fd);
+                Err(e)
+            }
+        }
+    }
+
+    // submit an operation and wait for its result
+    //
+    // Safety: the buffers referenced by the entry must be valid until this returns
+    unsafe fn run(&mut self, sqe: Sqe) -> NixResult<usize> {
+        let tail = ptr::read_volatile(self.sq.at::<u32>(self.sq_off.tail));
+        let mask = *self.sq.at::<u32>(self.sq_off.ring_mask);
+        let index = tail & mask;
+        ptr::write(self.sqes.at::<Sqe>(index * mem::size_of::<Sqe>() as u32), sqe);
+        ptr::write_volatile(self.sq.at::<u32>(self.sq_off.array + index * 4), index);
+        // the entry must be visible before the kernel sees the new tail
+        fence(Ordering::Release);
+        ptr::write_volatile(self.sq.at::<u32>(self.sq_off.tail), tail.wrapping_add(1));
+
+        let mut to_submit = 1;
+        loop {
+            let head = ptr::read_volatile(self.cq.at::<u32>(self.cq_off.head));
+            fence(Ordering::Acquire);
+            if head != ptr::read_volatile(self.cq.at::<u32>(self.cq_off.tail)) {
+                let mask = *self.cq.at::<u32>(self.cq_off.ring_mask);
+                let cqe = self
+                    .cq
+                    .at::<Cqe>(self.cq_off.cqes + (head & mask) * mem::size_of::<Cqe>() as u32);
+                let res = (*cqe).res;
+                fence(Ordering::Release);
+                ptr::write_volatile(self.cq.at::<u32>(self.cq_off.head), head.wrapping_add(1));
+                return if res < 0 {
+                    Err(Error::Sys(Errno::from_i32(-res)))
+                } else {
+                    Ok(res as usize)
+                };
+            }
+            let ret = libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd,
+                to_submit,
+                1,
+                IORING_ENTER_GETEVENTS,
+                ptr::null::<libc::sigset_t>(),
+                0,
+            );
+            if ret >= 0 {
+                to_submit -= ret as u32;
+            } else if Errno::last() == Errno::EINTR {
+                continue;
+            } else if to_submit > 0 {
+                // the kernel did not consume the entry, withdraw it so that it does not
+                // outlive the buffers it points to
+                let err = Error::last();
+                ptr::write_volatile(self.sq.at::<u32>(self.sq_off.tail), tail);
+                return Err(err);
+            } else {
+                // the operation is in flight and may still access the buffers, its
+                // completion must be reaped before returning, whatever the error
+                ::std::thread::yield_now();
+            }
+        }
+    }
+
+    pub(crate) fn send_msg(&mut self, fd: RawFd, bytes: &[u8], fds: &[RawFd]) -> NixResult<()> {
+        let mut iov = libc::iovec {
+            iov_base: bytes.as_ptr() as *mut _,
+            iov_len: bytes.len(),
+        };
+        let mut control = [0u64; 32];
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        if !fds.is_empty() {
+            let fds_len = mem::size_of_val(fds) as u32;
+            msg.msg_control = control.as_mut_ptr() as *mut _;
+            msg.msg_controllen = unsafe { libc::CMSG_SPACE(fds_len) } as _;
+            unsafe {
+                let cmsg = libc::CMSG_FIRSTHDR(&msg);
+                (*cmsg).cmsg_level = libc::SOL_SOCKET;
+                (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+                ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+            }
+        }
+        unsafe {
+            self.run(Sqe {
+                opcode: IORING_OP_SENDMSG,
+                fd,
+                addr: &msg as *const libc::msghdr as u64,
+                len: 1,
+                msg_flags: libc::MSG_DONTWAIT as u32,
+                ..Sqe::default()
+            })?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rcv_msg(
+        &mut self,
+        fd: RawFd,
+        buffer: &mut [u8],
+        fds: &mut [RawFd],
+    ) -> NixResult<(usize, usize)> {
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut _,
+            iov_len: buffer.len(),
+        };
+        let mut control = [0u64; 32];
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut _;
+        msg.msg_controllen =
+            unsafe { libc::CMSG_SPACE((MAX_FDS_OUT * mem::size_of::<RawFd>()) as u32) } as _;
+        let bytes = unsafe {
+            self.run(Sqe {
+                opcode: IORING_OP_RECVMSG,
+                fd,
+                addr: &mut msg as *mut libc::msghdr as u64,
+                len: 1,
+                msg_flags: (libc::MSG_DONTWAIT | libc::MSG_CMSG_CLOEXEC) as u32,
+                ..Sqe::default()
+            })?
+        };
+
+        let mut fd_count = 0;
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                    let mut received_fds = vec![0 as RawFd; len / mem::size_of::<RawFd>()];
+                    // the data of the control message may not be aligned
+                    ptr::copy_nonoverlapping(
+                        libc::CMSG_DATA(cmsg),
+                        received_fds.as_mut_ptr() as *mut u8,
+                        received_fds.len() * mem::size_of::<RawFd>(),
+                    );
+                    for received in received_fds {
+                        if fd_count < fds.len() {
+                            fds[fd_count] = received;
+                            fd_count += 1;
+                        } else {
+                            // the fds which do not fit would otherwise leak
+                            let _ = ::nix::unistd::close(received);
+                        }
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+        Ok((bytes, fd_count))
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        let _ = ::nix::unistd::close(self.fd);
+    }
+}
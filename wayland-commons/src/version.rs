@@ -0,0 +1,170 @@
+//! Version compatibility of the protocol messages
+//!
+//! The messages and enum entries of an interface can be more recent than the interface
+//! itself: they are only available if the object was created with a version at least
+//! equal to their `since` version. The `wayland-scanner` crate generates for each
+//! protocol a `VERSION_MATRIX` constant listing these versions, which can be used to
+//! verify at runtime that a bind version supports the messages an application requires.
+
+use std::error::Error;
+use std::fmt;
+
+/// The minimum version of an interface supporting a message
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MessageVersion {
+    /// Name of the interface of the message
+    pub interface: &'static str,
+    /// Name of the message
+    pub name: &'static str,
+    /// Minimum required version of the interface
+    pub since: u32,
+}
+
+/// The minimum version of an interface supporting an enum entry
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EnumEntryVersion {
+    /// Name of the interface of the enum
+    pub interface: &'static str,
+    /// Name of the enum
+    pub enumeration: &'static str,
+    /// Name of the entry
+    pub entry: &'static str,
+    /// Minimum required version of the interface
+    ///
+    /// This accounts for the version of the enum itself.
+    pub since: u32,
+}
+
+/// The versions of the messages and enum entries of a protocol
+///
+/// All names are the ones of the protocol XML file, like `"wl_surface"` and
+/// `"damage_buffer"`.
+#[derive(Copy, Clone, Debug)]
+pub struct VersionMatrix {
+    /// The requests of the interfaces of the protocol
+    pub requests: &'static [MessageVersion],
+    /// The events of the interfaces of the protocol
+    pub events: &'static [MessageVersion],
+    /// The enum entries of the interfaces of the protocol
+    pub enum_entries: &'static [EnumEntryVersion],
+}
+
+fn message_since(messages: &[MessageVersion], interface: &str, name: &str) -> Option<u32> {
+    messages
+        .iter()
+        .find(|msg| msg.interface == interface && msg.name == name)
+        .map(|msg| msg.since)
+}
+
+fn check(
+    messages: &[MessageVersion],
+    interface: &str,
+    version: u32,
+    required: &[&str],
+) -> Result<(), VersionError> {
+    for name in required {
+        match message_since(messages, interface, name) {
+            Some(since) if since <= version => {}
+            Some(since) => {
+                return Err(VersionError::Unsupported {
+                    interface: interface.into(),
+                    message: (*name).into(),
+                    since,
+                })
+            }
+            None => {
+                return Err(VersionError::UnknownMessage {
+                    interface: interface.into(),
+                    message: (*name).into(),
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+impl VersionMatrix {
+    /// The minimum version of an interface supporting a request
+    ///
+    /// Returns `None` if the interface has no such request in this protocol.
+    pub fn request_since(&self, interface: &str, request: &str) -> Option<u32> {
+        message_since(self.requests, interface, request)
+    }
+
+    /// The minimum version of an interface supporting an event
+    ///
+    /// Returns `None` if the interface has no such event in this protocol.
+    pub fn event_since(&self, interface: &str, event: &str) -> Option<u32> {
+        message_since(self.events, interface, event)
+    }
+
+    /// The minimum version of an interface supporting an enum entry
+    ///
+    /// Returns `None` if the interface has no such enum entry in this protocol.
+    pub fn enum_entry_since(&self, interface: &str, enumeration: &str, entry: &str) -> Option<u32> {
+        self.enum_entries
+            .iter()
+            .find(|e| e.interface == interface && e.enumeration == enumeration && e.entry == entry)
+            .map(|e| e.since)
+    }
+
+    /// Verify that a version of an interface supports a set of requests
+    ///
+    /// Fails on the first of the requests that is not supported by `version`.
+    pub fn check_requests(&self, interface: &str, version: u32, requests: &[&str]) -> Result<(), VersionError> {
+        check(self.requests, interface, version, requests)
+    }
+
+    /// Verify that a version of an interface supports a set of events
+    ///
+    /// Fails on the first of the events that is not supported by `version`.
+    pub fn check_events(&self, interface: &str, version: u32, events: &[&str]) -> Result<(), VersionError> {
+        check(self.events, interface, version, events)
+    }
+}
+
+/// A message not supported by an interface version
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionError {
+    /// The interface has no message of this name
+    UnknownMessage {
+        /// Name of the interface
+        interface: String,
+        /// Name of the message
+        message: String,
+    },
+    /// The message requires a more recent version of the interface
+    Unsupported {
+        /// Name of the interface
+        interface: String,
+        /// Name of the message
+        message: String,
+        /// Minimum required version of the interface
+        since: u32,
+    },
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VersionError::UnknownMessage {
+                ref interface,
+                ref message,
+            } => write!(f, "interface {} has no message {}", interface, message),
+            VersionError::Unsupported {
+                ref interface,
+                ref message,
+                since,
+            } => write!(f, "message {}.{} requires version {}", interface, message, since),
+        }
+    }
+}
+
+impl Error for VersionError {
+    fn description(&self) -> &str {
+        match *self {
+            VersionError::UnknownMessage { .. } => "unknown message",
+            VersionError::Unsupported { .. } => "message not supported by the interface version",
+        }
+    }
+}
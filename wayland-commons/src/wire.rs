@@ -1,6 +1,9 @@
 //! Types and routines used to manipulate arguments from the wire format
 
+use std::borrow::Cow;
+use std::error::Error;
 use std::ffi::{CStr, CString};
+use std::fmt;
 use std::os::unix::io::RawFd;
 use std::ptr;
 
@@ -15,8 +18,258 @@ pub struct MessageDesc {
     pub signature: &'static [ArgumentType],
     /// Minimum required version of the interface
     pub since: u32,
+    /// Whether this message is a destructor
+    pub destructor: bool,
+    /// Kinds of the file descriptors expected by this message, one for each `Fd` argument
+    ///
+    /// Empty if the message has no expectations about its file descriptors.
+    pub fd_kinds: &'static [FdKind],
+}
+
+impl MessageDesc {
+    /// Check file descriptors of this message against the kinds it expects
+    ///
+    /// The file descriptors are given in the order of the `Fd` arguments of the message, the
+    /// first one not matching its expected kind is returned along with this kind.
+    pub fn mismatched_fd<I: IntoIterator<Item = RawFd>>(&self, fds: I) -> Option<(RawFd, FdKind)> {
+        fds.into_iter()
+            .zip(self.fd_kinds.iter())
+            .find(|&(fd, kind)| !kind.matches(fd))
+            .map(|(fd, &kind)| (fd, kind))
+    }
+
+    /// Warn on stderr about file descriptors not matching the kinds expected by this message
+    ///
+    /// This check is meant to catch mistakes when developping, and is only done in debug
+    /// builds. See `mismatched_fd()` for details.
+    pub fn check_fds<I: IntoIterator<Item = RawFd>>(&self, interface: &str, fds: I) {
+        if cfg!(debug_assertions) {
+            if let Some((fd, kind)) = self.mismatched_fd(fds) {
+                eprintln!(
+                    "[wayland] {}.{}: file descriptor {} is not {}.",
+                    interface,
+                    self.name,
+                    fd,
+                    kind.description()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(feature = "native_lib")]
+impl MessageDesc {
+    /// Warn about the file descriptors of a message given as C arguments
+    ///
+    /// See `check_fds()`, the arguments must match the signature of this message.
+    pub unsafe fn check_c_fds(&self, interface: &str, args: &[::wayland_sys::common::wl_argument]) {
+        let fds = self
+            .signature
+            .iter()
+            .zip(args)
+            .filter(|&(&typ, _)| typ == ArgumentType::Fd)
+            .map(|(_, arg)| arg.h);
+        self.check_fds(interface, fds);
+    }
+}
+
+/// Kind of file descriptor expected by a message
+///
+/// The wire format does not distinguish file descriptors, but some protocols expect their
+/// file descriptors to have specific properties. These expectations are hints emitted by
+/// `wayland-scanner` for the messages known to have them.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FdKind {
+    /// Any file descriptor
+    Any,
+    /// A file that the receiver can map but not modify, either opened read-only or a memfd
+    /// sealed against writing and shrinking (like keymaps)
+    ReadOnlyMappable,
+    /// A listening unix socket (like the ones of security contexts)
+    ListeningSocket,
 }
 
+impl FdKind {
+    /// Check if a file descriptor is of this kind
+    ///
+    /// File descriptors whose properties cannot be queried are considered not matching.
+    pub fn matches(&self, fd: RawFd) -> bool {
+        use nix::fcntl::{fcntl, FcntlArg, OFlag};
+        use nix::sys::socket::{getsockopt, sockopt};
+        match *self {
+            FdKind::Any => true,
+            FdKind::ReadOnlyMappable => {
+                let read_only = fcntl(fd, FcntlArg::F_GETFL)
+                    .map(|flags| OFlag::from_bits_truncate(flags) & OFlag::O_ACCMODE == OFlag::O_RDONLY)
+                    .unwrap_or(false);
+                read_only || is_sealed(fd)
+            }
+            FdKind::ListeningSocket => getsockopt(fd, sockopt::AcceptConn).unwrap_or(false),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match *self {
+            FdKind::Any => "a file descriptor",
+            FdKind::ReadOnlyMappable => "read-only or sealed against writing",
+            FdKind::ListeningSocket => "a listening socket",
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_sealed(fd: RawFd) -> bool {
+    use nix::fcntl::{fcntl, FcntlArg, SealFlag};
+    let required = SealFlag::F_SEAL_WRITE | SealFlag::F_SEAL_SHRINK;
+    fcntl(fd, FcntlArg::F_GET_SEALS)
+        .map(|seals| SealFlag::from_bits_truncate(seals).contains(required))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn is_sealed(_fd: RawFd) -> bool {
+    false
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// 64-bit FNV-1a, whose values do not depend on the platform or the version of rust
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&[
+            value as u8,
+            (value >> 8) as u8,
+            (value >> 16) as u8,
+            (value >> 24) as u8,
+        ]);
+    }
+
+    fn write_str(&mut self, value: &str) {
+        self.write(value.as_bytes());
+        self.write(&[0]);
+    }
+}
+
+/// A stable hash of the description of an interface
+///
+/// It covers the name and version of the interface, and the name, version, destructor
+/// status and signature of each message of the given groups, in order. The value only
+/// changes if one of them does, so two programs can compare the fingerprints of their
+/// interfaces to detect that they were generated from different protocol descriptions.
+///
+/// This is what `Interface::fingerprint()` returns, with the requests and events of
+/// the interface as groups.
+pub fn fingerprint(name: &str, version: u32, groups: &[&[MessageDesc]]) -> u64 {
+    let mut hash = Fnv(FNV_OFFSET_BASIS);
+    hash.write_str(name);
+    hash.write_u32(version);
+    for messages in groups {
+        hash.write_u32(messages.len() as u32);
+        for msg in messages.iter() {
+            hash.write_str(msg.name);
+            hash.write_u32(msg.since);
+            hash.write(&[msg.destructor as u8]);
+            // the signature as written in the protocol files, the arrays of u64 are
+            // arrays on the wire
+            let signature = msg
+                .signature
+                .iter()
+                .map(|&typ| signature_code(typ))
+                .collect::<Vec<u8>>();
+            hash.write(&signature);
+            hash.write(&[0]);
+        }
+    }
+    hash.0
+}
+
+// Loops in const functions require rust 1.46, these functions are thus only const with the
+// `const_checks` feature
+macro_rules! compatibility_fns {
+    ($($const:tt)*) => {
+        /// Whether two groups of messages describe the same messages
+        ///
+        /// The messages must have the same names, versions, destructor status and signatures, in
+        /// the same order, as covered by `fingerprint()`. With the `const_checks` cargo feature, this
+        /// function can be evaluated at compile time, it is what `assert_protocol_compatible!` relies on.
+        pub $($const)* fn messages_compatible(a: &[MessageDesc], b: &[MessageDesc]) -> bool {
+            if a.len() != b.len() {
+                return false;
+            }
+            let mut i = 0;
+            while i < a.len() {
+                let (x, y) = (&a[i], &b[i]);
+                if !bytes_equal(x.name.as_bytes(), y.name.as_bytes())
+                    || x.since != y.since
+                    || x.destructor != y.destructor
+                    || !signatures_equal(x.signature, y.signature)
+                {
+                    return false;
+                }
+                i += 1;
+            }
+            true
+        }
+
+        $($const)* fn bytes_equal(a: &[u8], b: &[u8]) -> bool {
+            if a.len() != b.len() {
+                return false;
+            }
+            let mut i = 0;
+            while i < a.len() {
+                if a[i] != b[i] {
+                    return false;
+                }
+                i += 1;
+            }
+            true
+        }
+
+        $($const)* fn signatures_equal(a: &[ArgumentType], b: &[ArgumentType]) -> bool {
+            if a.len() != b.len() {
+                return false;
+            }
+            let mut i = 0;
+            while i < a.len() {
+                if signature_code(a[i]) != signature_code(b[i]) {
+                    return false;
+                }
+                i += 1;
+            }
+            true
+        }
+
+        // as for the fingerprints, the arrays of u64 are arrays on the wire
+        $($const)* fn signature_code(typ: ArgumentType) -> u8 {
+            match typ {
+                ArgumentType::Int => b'i',
+                ArgumentType::Uint => b'u',
+                ArgumentType::Fixed => b'f',
+                ArgumentType::Str => b's',
+                ArgumentType::Object => b'o',
+                ArgumentType::NewId => b'n',
+                ArgumentType::Array | ArgumentType::ArrayU64 => b'a',
+                ArgumentType::Fd => b'h',
+            }
+        }
+    };
+}
+
+#[cfg(feature = "const_checks")]
+compatibility_fns!(const);
+#[cfg(not(feature = "const_checks"))]
+compatibility_fns!();
+
 /// Enum of possible argument types as recognized by the wire
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum ArgumentType {
@@ -89,6 +342,26 @@ pub struct Message {
     pub args: Vec<Argument>,
 }
 
+/// Iterator over the file descriptors of a message
+///
+/// See `Message::fds()`.
+pub struct MessageFds<'a> {
+    args: ::std::slice::Iter<'a, Argument>,
+}
+
+impl<'a> Iterator for MessageFds<'a> {
+    type Item = RawFd;
+
+    fn next(&mut self) -> Option<RawFd> {
+        while let Some(arg) = self.args.next() {
+            if let Argument::Fd(fd) = *arg {
+                return Some(fd);
+            }
+        }
+        None
+    }
+}
+
 /// Error generated when trying to serialize a message into buffers
 #[derive(Debug, Clone)]
 pub enum MessageWriteError {
@@ -109,7 +382,97 @@ pub enum MessageParseError {
     Malformed,
 }
 
+/// Error returned by a request given a string argument containing a nul byte
+///
+/// The strings of the wayland protocol are nul-terminated, they cannot contain nul bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InteriorNulError {
+    position: usize,
+}
+
+impl InteriorNulError {
+    /// The position of the first nul byte in the string
+    pub fn nul_position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for InteriorNulError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nul byte found in string argument at position {}", self.position)
+    }
+}
+
+impl Error for InteriorNulError {
+    fn description(&self) -> &str {
+        "nul byte found in string argument"
+    }
+}
+
+/// Error returned by a request creating an object, given a string argument
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RequestError {
+    /// The proxy sending the request is dead
+    DeadProxy,
+    /// A string argument contains a nul byte
+    InteriorNul(InteriorNulError),
+}
+
+impl From<InteriorNulError> for RequestError {
+    fn from(err: InteriorNulError) -> RequestError {
+        RequestError::InteriorNul(err)
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RequestError::DeadProxy => f.write_str("the proxy sending the request is dead"),
+            RequestError::InteriorNul(ref err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl Error for RequestError {
+    fn description(&self) -> &str {
+        match *self {
+            RequestError::DeadProxy => "the proxy sending the request is dead",
+            RequestError::InteriorNul(_) => "nul byte found in string argument",
+        }
+    }
+}
+
+/// Prepare a string argument of a request
+///
+/// This checks that the string does not contain any nul byte. An owned string is then
+/// kept as is, while a borrowed one is copied into a `String` with room for the nul
+/// terminator, so that its conversion into a `CString` when the message is sent does
+/// not reallocate it.
+///
+/// This is used by the generated request methods.
+pub fn string_argument<'a, S: Into<Cow<'a, str>>>(s: S) -> Result<String, InteriorNulError> {
+    let s = s.into();
+    if let Some(position) = s.bytes().position(|b| b == 0) {
+        return Err(InteriorNulError { position });
+    }
+    Ok(match s {
+        Cow::Borrowed(s) => {
+            let mut string = String::with_capacity(s.len() + 1);
+            string.push_str(s);
+            string
+        }
+        Cow::Owned(s) => s,
+    })
+}
+
 impl Message {
+    /// The file descriptors of this message, in the order of its `Fd` arguments
+    pub fn fds(&self) -> MessageFds {
+        MessageFds {
+            args: self.args.iter(),
+        }
+    }
+
     /// Serialize the contents of this message into provided buffers
     ///
     /// Returns the number of elements writtent in each buffer
@@ -238,10 +601,15 @@ impl Message {
         let opcode = (word_2 & 0x0000FFFF) as u16;
         let len = (word_2 >> 16) as usize / 4;
 
-        if len < 2 || len > raw.len() {
+        if len < 2 {
             return Err(MessageParseError::Malformed);
         }
 
+        if len > raw.len() {
+            // the rest of the message was not received yet
+            return Err(MessageParseError::MissingData);
+        }
+
         let (mut payload, rest) = raw.split_at(len);
         payload = &payload[2..];
         let mut fds = fds;
@@ -367,6 +735,95 @@ impl Drop for FdStore {
 mod tests {
     use super::*;
 
+    #[test]
+    fn fingerprint_is_stable() {
+        const MESSAGES: &[MessageDesc] = &[
+            MessageDesc {
+                name: "destroy",
+                signature: &[],
+                since: 1,
+                destructor: true,
+                fd_kinds: &[],
+            },
+            MessageDesc {
+                name: "attach",
+                signature: &[ArgumentType::Object, ArgumentType::Int, ArgumentType::Int],
+                since: 1,
+                destructor: false,
+                fd_kinds: &[],
+            },
+        ];
+        let fingerprint = fingerprint("wl_test", 2, &[MESSAGES, &[]]);
+        // this value must never change
+        assert_eq!(fingerprint, 0x78ff_1731_8cb3_a30c);
+
+        assert_ne!(fingerprint, super::fingerprint("wl_test", 3, &[MESSAGES, &[]]));
+        // moving a message from the requests to the events changes it
+        let moved = super::fingerprint("wl_test", 2, &[&MESSAGES[..1], &MESSAGES[1..]]);
+        assert_ne!(fingerprint, moved);
+        // as does the version of a message
+        let changed = [MessageDesc {
+            name: "destroy",
+            signature: &[],
+            since: 2,
+            destructor: true,
+            fd_kinds: &[],
+        }];
+        assert_ne!(moved, super::fingerprint("wl_test", 2, &[&changed, &MESSAGES[1..]]));
+        // or its signature
+        let changed = [MessageDesc {
+            name: "attach",
+            signature: &[ArgumentType::Object, ArgumentType::Int, ArgumentType::Uint],
+            since: 1,
+            destructor: false,
+            fd_kinds: &[],
+        }];
+        assert_ne!(moved, super::fingerprint("wl_test", 2, &[&MESSAGES[..1], &changed]));
+    }
+
+    #[test]
+    fn compatible_messages() {
+        const MESSAGES: &[MessageDesc] = &[
+            MessageDesc {
+                name: "set",
+                signature: &[ArgumentType::Uint, ArgumentType::Array],
+                since: 1,
+                destructor: false,
+                fd_kinds: &[],
+            },
+            MessageDesc {
+                name: "destroy",
+                signature: &[],
+                since: 2,
+                destructor: true,
+                fd_kinds: &[],
+            },
+        ];
+        assert!(messages_compatible(MESSAGES, MESSAGES));
+        assert!(!messages_compatible(MESSAGES, &MESSAGES[..1]));
+        // the arrays of u64 are arrays on the wire
+        let wide = [MessageDesc {
+            signature: &[ArgumentType::Uint, ArgumentType::ArrayU64],
+            ..MESSAGES[0]
+        }];
+        assert!(messages_compatible(&MESSAGES[..1], &wide));
+        let changed = [MessageDesc {
+            signature: &[ArgumentType::Int, ArgumentType::Array],
+            ..MESSAGES[0]
+        }];
+        assert!(!messages_compatible(&MESSAGES[..1], &changed));
+        let renamed = [MessageDesc {
+            name: "sets",
+            ..MESSAGES[0]
+        }];
+        assert!(!messages_compatible(&MESSAGES[..1], &renamed));
+        let newer = [MessageDesc {
+            since: 3,
+            ..MESSAGES[1]
+        }];
+        assert!(!messages_compatible(&MESSAGES[1..], &newer));
+    }
+
     #[test]
     fn into_from_raw_cycle() {
         let mut bytes_buffer = vec![0; 1024];
@@ -404,4 +861,96 @@ mod tests {
         ).unwrap();
         assert_eq!(rebuilt, msg);
     }
+
+    #[test]
+    fn from_raw_truncated() {
+        let mut bytes_buffer = vec![0; 1024];
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: vec![Argument::Array(vec![1, 2, 3, 4, 5, 6, 7, 8, 9])],
+        };
+        let (len, _) = msg.write_to_buffers(&mut bytes_buffer[..], &mut []).unwrap();
+
+        // the rest of the message was not received yet
+        for end in 2..len {
+            match Message::from_raw(&bytes_buffer[..end], &[ArgumentType::Array], &[]) {
+                Err(MessageParseError::MissingData) => {}
+                other => panic!("Unexpected result for {} words: {:?}", end, other),
+            }
+        }
+        let (rebuilt, rest, _) =
+            Message::from_raw(&bytes_buffer[..len + 1], &[ArgumentType::Array], &[]).unwrap();
+        assert_eq!(rebuilt, msg);
+        assert_eq!(rest.len(), 1);
+
+        // but a length shorter than the header is malformed
+        bytes_buffer[1] = 7 | (4 << 16);
+        match Message::from_raw(&bytes_buffer[..len], &[ArgumentType::Array], &[]) {
+            Err(MessageParseError::Malformed) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fd_kinds() {
+        use std::fs::{self, File, OpenOptions};
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        let read_only_file = File::open("/dev/null").unwrap();
+        let writable_file = OpenOptions::new().read(true).write(true).open("/dev/null").unwrap();
+        let path = ::std::env::temp_dir().join(format!("wayland-commons-fd-kinds-{}", ::std::process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let (connected, _) = UnixStream::pair().unwrap();
+        let read_only = read_only_file.as_raw_fd();
+        let writable = writable_file.as_raw_fd();
+
+        assert!(FdKind::Any.matches(writable));
+        assert!(FdKind::ReadOnlyMappable.matches(read_only));
+        assert!(!FdKind::ReadOnlyMappable.matches(writable));
+        assert!(FdKind::ListeningSocket.matches(listener.as_raw_fd()));
+        assert!(!FdKind::ListeningSocket.matches(connected.as_raw_fd()));
+        assert!(!FdKind::ListeningSocket.matches(read_only));
+
+        let desc = MessageDesc {
+            name: "keymap",
+            signature: &[ArgumentType::Uint, ArgumentType::Fd, ArgumentType::Uint],
+            since: 1,
+            destructor: false,
+            fd_kinds: &[FdKind::ReadOnlyMappable],
+        };
+        assert_eq!(desc.mismatched_fd(vec![read_only]), None);
+        assert_eq!(desc.mismatched_fd(vec![writable]), Some((writable, FdKind::ReadOnlyMappable)));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn string_argument_checks_nul() {
+        let string = string_argument("I like trains!").unwrap();
+        assert_eq!(string, "I like trains!");
+        // the conversion into a CString does not reallocate
+        let capacity = string.capacity();
+        let ptr = string.as_ptr();
+        let c_string = unsafe { CString::from_vec_unchecked(string.into()) };
+        assert_eq!(c_string.as_bytes_with_nul().len(), capacity);
+        assert_eq!(c_string.as_ptr() as *const u8, ptr);
+
+        assert_eq!(string_argument("trains\0").unwrap_err().nul_position(), 6);
+    }
+
+    #[test]
+    fn string_argument_keeps_owned_strings() {
+        let owned = String::from("I like trains!");
+        let ptr = owned.as_ptr();
+        let string = string_argument(owned).unwrap();
+        // the string is not copied
+        assert_eq!(string.as_ptr(), ptr);
+
+        let err = string_argument(String::from("\0trains")).unwrap_err();
+        assert_eq!(RequestError::from(err), RequestError::InteriorNul(err));
+        assert_eq!(err.nul_position(), 0);
+    }
 }
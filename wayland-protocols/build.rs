@@ -32,39 +32,70 @@ static WLR_UNSTABLE_PROTOCOLS: &'static [(&'static str, &'static [&'static str])
     ("wlr-screencopy", &["v1"]),
 ];
 
+static STAGING_PROTOCOLS: &'static [(&'static str, &'static [&'static str])] = &[
+    ("drm-lease", &["v1"]),
+    ("ext-data-control", &["v1"]),
+    ("ext-session-lock", &["v1"]),
+    ("ext-workspace", &["v1"]),
+    ("fifo", &["v1"]),
+    ("fractional-scale", &["v1"]),
+    ("security-context", &["v1"]),
+    ("single-pixel-buffer", &["v1"]),
+    ("tearing-control", &["v1"]),
+    ("xdg-dialog", &["v1"]),
+    ("xdg-session-management", &["v1"]),
+    ("xdg-toplevel-icon", &["v1"]),
+];
+
+// the file descriptors of the protocols expected to be of a specific kind
+static FD_KINDS: &'static [FdKindHint] = &[FdKindHint {
+    interface: "wp_security_context_manager_v1",
+    message: "create_listener",
+    argument: "listen_fd",
+    kind: "ListeningSocket",
+}];
+
 fn generate_protocol(name: &str, protocol_file: &Path, out_dir: &Path, client: bool, server: bool) {
+    let options = Options {
+        fd_kinds: FD_KINDS,
+        ..Options::default()
+    };
     if var("CARGO_FEATURE_NATIVE_LIB").ok().is_some() {
         generate_c_interfaces(&protocol_file, out_dir.join(&format!("{}_c_interfaces.rs", name)));
 
         if client {
-            generate_c_code(
+            generate_c_code_with_options(
                 &protocol_file,
                 out_dir.join(&format!("{}_c_client_api.rs", name)),
                 Side::Client,
+                options,
             );
         }
 
         if server {
-            generate_c_code(
+            generate_c_code_with_options(
                 &protocol_file,
                 out_dir.join(&format!("{}_c_server_api.rs", name)),
                 Side::Server,
+                options,
             );
         }
     } else {
         if client {
-            generate_rust_code(
+            generate_rust_code_with_options(
                 &protocol_file,
                 out_dir.join(&format!("{}_rust_client_api.rs", name)),
                 Side::Client,
+                options,
             );
         }
 
         if server {
-            generate_rust_code(
+            generate_rust_code_with_options(
                 &protocol_file,
                 out_dir.join(&format!("{}_rust_server_api.rs", name)),
                 Side::Server,
+                options,
             );
         }
     }
@@ -79,13 +110,15 @@ fn main() {
 
     for name in STABLE_PROTOCOLS {
         let file = format!("{name}/{name}.xml", name = name);
-        generate_protocol(
-            name,
-            &Path::new("./protocols/stable").join(&file),
-            out_dir,
-            client,
-            server,
-        );
+        // the protocols more recent than the ones of the wayland-protocols submodule
+        // are vendored in `stable`
+        let vendored = Path::new("./stable").join(&file);
+        let protocol_file = if vendored.exists() {
+            vendored
+        } else {
+            Path::new("./protocols/stable").join(&file)
+        };
+        generate_protocol(name, &protocol_file, out_dir, client, server);
     }
 
     if var("CARGO_FEATURE_UNSTABLE_PROTOCOLS").ok().is_some() {
@@ -118,4 +151,19 @@ fn main() {
             }
         }
     }
+
+    if var("CARGO_FEATURE_STAGING_PROTOCOLS").ok().is_some() {
+        for &(name, versions) in STAGING_PROTOCOLS {
+            for version in versions {
+                let file = format!("{name}/{name}-{version}.xml", name = name, version = version);
+                generate_protocol(
+                    &format!("{name}-{version}", name = name, version = version),
+                    &Path::new("./staging").join(file),
+                    out_dir,
+                    client,
+                    server,
+                );
+            }
+        }
+    }
 }
@@ -11,6 +11,10 @@
 //! to protocols that are not yet considered stable. As such, no stability guarantee is
 //! given for these protocols.
 //!
+//! The cargo feature `staging_protocols` adds a `staging` module, containing bindings
+//! to protocols from the staging area of wayland-protocols. They are expected to be
+//! stable in practice, but may still receive backward incompatible changes.
+//!
 //! Some protocols require unstable rust features, the inclusion of them is controlled
 //! by the cargo feature `nightly`.
 
@@ -36,6 +40,9 @@ mod protocol_macro;
 #[cfg(feature = "unstable_protocols")]
 pub mod unstable;
 
+#[cfg(feature = "staging_protocols")]
+pub mod staging;
+
 pub mod wlr;
 
 mod stable;
@@ -22,7 +22,9 @@ macro_rules! wayland_protocol(
                 pub(crate) use wayland_client::{NewProxy, Proxy, ProxyMap};
                 pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
                 pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
-                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+                pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
+                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, FdKind, Message};
+                pub(crate) use wayland_commons::wire::{string_argument, InteriorNulError, RequestError};
                 pub(crate) use wayland_client::protocol::{$($import),*};
                 $(
                     pub(crate) use ::$prot_name::client::$prot_import;
@@ -36,7 +38,8 @@ macro_rules! wayland_protocol(
                 pub(crate) use wayland_server::{NewResource, Resource, ResourceMap};
                 pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
                 pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
-                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+                pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
+                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, FdKind, Message};
                 pub(crate) use wayland_server::protocol::{$($import),*};
                 $(
                     pub(crate) use ::$prot_name::server::$prot_import;
@@ -71,7 +74,9 @@ macro_rules! wayland_protocol(
                 pub(crate) use wayland_client::{NewProxy, Proxy, ProxyMap};
                 pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
                 pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
-                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+                pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
+                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, FdKind, Message};
+                pub(crate) use wayland_commons::wire::{string_argument, InteriorNulError, RequestError};
                 pub(crate) use wayland_sys as sys;
                 pub(crate) use wayland_client::protocol::{$($import),*};
                 $(
@@ -86,7 +91,8 @@ macro_rules! wayland_protocol(
                 pub(crate) use wayland_server::{NewResource, Resource, ResourceMap};
                 pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
                 pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
-                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+                pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
+                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, FdKind, Message};
                 pub(crate) use wayland_sys as sys;
                 pub(crate) use wayland_server::protocol::{$($import),*};
                 $(
@@ -98,7 +104,7 @@ macro_rules! wayland_protocol(
     }
 );
 
-#[cfg(feature = "unstable_protocols")]
+#[cfg(any(feature = "unstable_protocols", feature = "staging_protocols"))]
 #[macro_escape]
 macro_rules! wayland_protocol_versioned(
     ($name: expr, [$($version: ident),*], $std_imports:tt, $prot_imports:tt) => {
@@ -19,6 +19,38 @@ pub mod xdg_shell {
     //! XDG Shell protocol
     //!
     //! Exposes the `xdg_wm_base` global, which deprecates and replace `wl_shell`.
+    //!
+    //! Client-side, the `PresentationTracker` type tells whether a toplevel is being
+    //! presented to the user, from its `suspended` state and from the delivery of its
+    //! frame callbacks, so that applications like video players can stop rendering and
+    //! decoding while it is not.
+    //!
+    //! The `Toplevel` type sets the properties of a toplevel after checking them, so that
+    //! questionable values are reported to the application rather than sent to the
+    //! compositor, which may reject them with a protocol error.
+    //!
+    //! The `ConfigureTracker` type acks the `configure` events of an `xdg_surface` along
+    //! with the commit applying them, and reports the commits and acks which do not match,
+    //! a common reason for windows which do not resize.
+
+    #[cfg(feature = "client")]
+    use std::error::Error;
+    #[cfg(feature = "client")]
+    use std::fmt;
+    #[cfg(feature = "client")]
+    use std::time::{Duration, Instant};
+
+    #[cfg(feature = "client")]
+    use wayland_client::clock::Clock;
+    #[cfg(feature = "client")]
+    use wayland_client::protocol::wl_surface::{RequestsTrait as SurfaceRequests, WlSurface};
+    #[cfg(feature = "client")]
+    use wayland_client::Proxy;
+
+    #[cfg(feature = "client")]
+    use self::client::xdg_surface::{RequestsTrait as XdgSurfaceRequests, XdgSurface};
+    #[cfg(feature = "client")]
+    use self::client::xdg_toplevel::{RequestsTrait as ToplevelRequests, XdgToplevel};
 
     wayland_protocol!(
         "xdg-shell",
@@ -29,6 +61,455 @@ pub mod xdg_shell {
         ],
         []
     );
+
+    /// Tracker of whether a toplevel is presented to the user
+    ///
+    /// A toplevel is considered presented unless the compositor set its `suspended` state
+    /// (available with version 6 of `xdg_wm_base`), or one of its frame callbacks was not
+    /// delivered within the starvation delay: compositors typically stop delivering them
+    /// for surfaces which are not visible, without necessarily supporting the suspended
+    /// state.
+    ///
+    /// The tracker needs to be fed the states of the `configure` events of the toplevel
+    /// and the frame callbacks of its surface. As a starved frame callback produces no
+    /// event, `update()` also needs to be called once `deadline()` is reached, typically
+    /// from a timer of the event loop of the application.
+    #[cfg(feature = "client")]
+    pub struct PresentationTracker {
+        clock: Box<Clock>,
+        starvation: Duration,
+        suspended: bool,
+        pending_frame: Option<Instant>,
+        presenting: bool,
+        callback: Option<Box<FnMut(bool)>>,
+    }
+
+    #[cfg(feature = "client")]
+    impl PresentationTracker {
+        /// Create a tracker of a toplevel
+        ///
+        /// The toplevel is considered as no longer presented once one of its frame
+        /// callbacks is pending for longer than `starvation`. The time is read from
+        /// `clock`, usually the `SystemClock`.
+        pub fn new<C: Clock + 'static>(clock: C, starvation: Duration) -> PresentationTracker {
+            PresentationTracker {
+                clock: Box::new(clock),
+                starvation,
+                suspended: false,
+                pending_frame: None,
+                presenting: true,
+                callback: None,
+            }
+        }
+
+        /// Set a callback invoked when the toplevel starts or stops being presented
+        ///
+        /// It is given the new value of `is_presenting()`.
+        pub fn set_callback<F: FnMut(bool) + 'static>(&mut self, callback: F) {
+            self.callback = Some(Box::new(callback));
+        }
+
+        /// Whether the toplevel is presented to the user
+        pub fn is_presenting(&self) -> bool {
+            self.presenting
+        }
+
+        /// Whether the compositor set the suspended state of the toplevel
+        pub fn is_suspended(&self) -> bool {
+            self.suspended
+        }
+
+        /// Track the states of a `configure` event of the toplevel
+        ///
+        /// `states` is the array given by the event, containing the states as 32-bit
+        /// integers in native endianness.
+        pub fn configure(&mut self, states: &[u8]) {
+            let suspended = client::xdg_toplevel::State::Suspended.to_raw();
+            self.suspended = states
+                .chunks(4)
+                .any(|chunk| chunk.len() == 4 && native_u32(chunk) == suspended);
+            self.update();
+        }
+
+        /// Track the request of a frame callback for the surface of the toplevel
+        ///
+        /// This should be called when committing a surface state with a frame callback.
+        /// Only the oldest of the pending frame callbacks is taken into account.
+        pub fn frame_requested(&mut self) {
+            if self.pending_frame.is_none() {
+                self.pending_frame = Some(self.clock.now());
+            }
+            self.update();
+        }
+
+        /// Track the delivery of a frame callback of the surface of the toplevel
+        ///
+        /// This should be called on the `done` event of the callback.
+        pub fn frame_done(&mut self) {
+            self.pending_frame = None;
+            self.update();
+        }
+
+        /// The instant at which the pending frame callback will be considered starved
+        ///
+        /// Returns `None` if no frame callback is pending, or if it is already starved.
+        pub fn deadline(&self) -> Option<Instant> {
+            match self.pending_frame {
+                Some(requested) if self.clock.now() < requested + self.starvation => {
+                    Some(requested + self.starvation)
+                }
+                _ => None,
+            }
+        }
+
+        /// Update the tracker with the current time
+        ///
+        /// This invokes the callback if the toplevel stopped being presented because its
+        /// pending frame callback is now starved.
+        pub fn update(&mut self) {
+            let starved = match self.pending_frame {
+                Some(requested) => self.clock.now() >= requested + self.starvation,
+                None => false,
+            };
+            let presenting = !self.suspended && !starved;
+            if presenting != self.presenting {
+                self.presenting = presenting;
+                if let Some(ref mut callback) = self.callback {
+                    callback(presenting);
+                }
+            }
+        }
+    }
+
+    /// The maximum length of a title, in bytes
+    ///
+    /// The messages of the protocol are limited to 4096 bytes, and compositors typically
+    /// cut the titles to far less when displaying them.
+    pub const MAX_TITLE_LENGTH: usize = 1024;
+
+    /// The maximum length of an app id, in bytes, which is the one of D-Bus names
+    pub const MAX_APP_ID_LENGTH: usize = 255;
+
+    /// Error returned by the setters of `Toplevel`
+    #[cfg(feature = "client")]
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum PropertyError {
+        /// The string contains a nul byte, at this position
+        InteriorNul(usize),
+        /// The app id is not a name of the form `org.example.App`, see `Toplevel::set_app_id()`
+        InvalidAppId(String),
+        /// The width or the height of a size is negative
+        NegativeSize(i32, i32),
+        /// The minimum size exceeds the maximum size in one dimension
+        InconsistentSizes {
+            /// The minimum size
+            min: (i32, i32),
+            /// The maximum size
+            max: (i32, i32),
+        },
+    }
+
+    #[cfg(feature = "client")]
+    impl fmt::Display for PropertyError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                PropertyError::InteriorNul(position) => {
+                    write!(f, "nul byte found in string at position {}", position)
+                }
+                PropertyError::InvalidAppId(ref app_id) => write!(f, "invalid app id {:?}", app_id),
+                PropertyError::NegativeSize(width, height) => write!(f, "negative size {}x{}", width, height),
+                PropertyError::InconsistentSizes { min, max } => write!(
+                    f,
+                    "minimum size {}x{} exceeds maximum size {}x{}",
+                    min.0, min.1, max.0, max.1
+                ),
+            }
+        }
+    }
+
+    #[cfg(feature = "client")]
+    impl Error for PropertyError {
+        fn description(&self) -> &str {
+            match *self {
+                PropertyError::InteriorNul(_) => "nul byte found in string",
+                PropertyError::InvalidAppId(_) => "invalid app id",
+                PropertyError::NegativeSize(_, _) => "negative size",
+                PropertyError::InconsistentSizes { .. } => "minimum size exceeds maximum size",
+            }
+        }
+    }
+
+    /// A toplevel whose properties are checked before being sent
+    ///
+    /// As for the requests of `xdg_toplevel`, the properties are applied by the compositor
+    /// on the next commit of the surface.
+    #[cfg(feature = "client")]
+    pub struct Toplevel {
+        toplevel: Proxy<XdgToplevel>,
+        min_size: (i32, i32),
+        max_size: (i32, i32),
+    }
+
+    #[cfg(feature = "client")]
+    impl Toplevel {
+        /// Wrap a toplevel, whose sizes were not set yet
+        pub fn new(toplevel: Proxy<XdgToplevel>) -> Toplevel {
+            Toplevel {
+                toplevel,
+                min_size: (0, 0),
+                max_size: (0, 0),
+            }
+        }
+
+        /// Access the underlying toplevel
+        pub fn toplevel(&self) -> &Proxy<XdgToplevel> {
+            &self.toplevel
+        }
+
+        /// Set the title of the toplevel
+        ///
+        /// A title longer than `MAX_TITLE_LENGTH` is truncated, at a character boundary.
+        /// Returns the title which was sent, or an error if it contains a nul byte.
+        pub fn set_title<'a>(&self, title: &'a str) -> Result<&'a str, PropertyError> {
+            if let Some(position) = title.find('\0') {
+                return Err(PropertyError::InteriorNul(position));
+            }
+            let mut end = title.len().min(MAX_TITLE_LENGTH);
+            while !title.is_char_boundary(end) {
+                end -= 1;
+            }
+            let title = &title[..end];
+            let _ = self.toplevel.set_title(title);
+            Ok(title)
+        }
+
+        /// Set the app id of the toplevel
+        ///
+        /// The app id identifies the application to the compositor, which typically uses
+        /// it to find its desktop entry: it is expected to be the D-Bus name of the
+        /// application, like `org.example.App`, which is also the basename of its desktop
+        /// entry. It is thus made of elements separated by dots, each of them made of
+        /// ASCII letters, digits, `_` and `-` and not starting with a digit, and is at most
+        /// `MAX_APP_ID_LENGTH` bytes long. A single element is accepted, as some desktop
+        /// entries are named so.
+        pub fn set_app_id(&self, app_id: &str) -> Result<(), PropertyError> {
+            if !valid_app_id(app_id) {
+                return Err(PropertyError::InvalidAppId(app_id.into()));
+            }
+            let _ = self.toplevel.set_app_id(app_id);
+            Ok(())
+        }
+
+        /// Set the minimum size of the toplevel
+        ///
+        /// A dimension of 0 means that it has no minimum. Fails if a dimension is
+        /// negative, or exceeds the one of the maximum size.
+        pub fn set_min_size(&mut self, width: i32, height: i32) -> Result<(), PropertyError> {
+            check_sizes((width, height), self.max_size)?;
+            self.min_size = (width, height);
+            self.toplevel.set_min_size(width, height);
+            Ok(())
+        }
+
+        /// Set the maximum size of the toplevel
+        ///
+        /// A dimension of 0 means that it has no maximum. Fails if a dimension is
+        /// negative, or is below the one of the minimum size.
+        pub fn set_max_size(&mut self, width: i32, height: i32) -> Result<(), PropertyError> {
+            check_sizes(self.min_size, (width, height))?;
+            self.max_size = (width, height);
+            self.toplevel.set_max_size(width, height);
+            Ok(())
+        }
+
+        /// The minimum size of the toplevel, as last set
+        pub fn min_size(&self) -> (i32, i32) {
+            self.min_size
+        }
+
+        /// The maximum size of the toplevel, as last set
+        pub fn max_size(&self) -> (i32, i32) {
+            self.max_size
+        }
+    }
+
+    /// Error returned by the checks of `ConfigureTracker`
+    #[cfg(feature = "client")]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum ConfigureError {
+        /// There is no configure to ack, it was already acked
+        NoPendingConfigure,
+        /// The configure with this serial was acked, but no commit applied it yet
+        AckWithoutCommit(u32),
+        /// The configure with this serial was not acked before the commit
+        UnackedConfigure(u32),
+    }
+
+    #[cfg(feature = "client")]
+    impl fmt::Display for ConfigureError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                ConfigureError::NoPendingConfigure => f.write_str("no pending configure to ack"),
+                ConfigureError::AckWithoutCommit(serial) => {
+                    write!(f, "configure {} was acked but not committed", serial)
+                }
+                ConfigureError::UnackedConfigure(serial) => {
+                    write!(f, "configure {} was not acked before the commit", serial)
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "client")]
+    impl Error for ConfigureError {
+        fn description(&self) -> &str {
+            match *self {
+                ConfigureError::NoPendingConfigure => "no pending configure",
+                ConfigureError::AckWithoutCommit(_) => "configure acked but not committed",
+                ConfigureError::UnackedConfigure(_) => "configure not acked before the commit",
+            }
+        }
+    }
+
+    /// Tracker of the `configure` events of an `xdg_surface`
+    ///
+    /// Each `configure` event must be acked with `ack_configure` by the commit which applies
+    /// the state it configured, otherwise the compositor keeps waiting for it: a window
+    /// which is not resized is often a configure acked without a commit, or a commit which
+    /// forgot the ack. The tracker needs to be fed the serials of the `configure` events,
+    /// `commit()` then acks the last of them along with the commit of the surface.
+    ///
+    /// When the commits are not done by the tracker, like with `eglSwapBuffers()`, the
+    /// configure can be acked with `ack()`, and the commit checked with `check_commit()`
+    /// before and reported with `committed()` after.
+    #[cfg(feature = "client")]
+    pub struct ConfigureTracker {
+        xdg_surface: Proxy<XdgSurface>,
+        surface: Proxy<WlSurface>,
+        // the serial of the last configure, if it was not acked yet
+        pending: Option<u32>,
+        // the serial of the last ack, if it was not committed yet
+        acked: Option<u32>,
+        configured: bool,
+    }
+
+    #[cfg(feature = "client")]
+    impl ConfigureTracker {
+        /// Track the configures of an `xdg_surface`, the role of `surface`
+        pub fn new(xdg_surface: Proxy<XdgSurface>, surface: Proxy<WlSurface>) -> ConfigureTracker {
+            ConfigureTracker {
+                xdg_surface,
+                surface,
+                pending: None,
+                acked: None,
+                configured: false,
+            }
+        }
+
+        /// Track a `configure` event, given its serial
+        ///
+        /// Only the last configure needs to be acked, the previous ones which were not are
+        /// superseded.
+        pub fn configure(&mut self, serial: u32) {
+            self.pending = Some(serial);
+        }
+
+        /// The serial of the last configure, if it was not acked yet
+        pub fn pending(&self) -> Option<u32> {
+            self.pending
+        }
+
+        /// Whether a configure was ever acked
+        ///
+        /// A buffer cannot be attached to the surface before.
+        pub fn is_configured(&self) -> bool {
+            self.configured
+        }
+
+        /// Ack the last configure, returning its serial
+        ///
+        /// The next commit of the surface must apply its state. Fails without sending
+        /// anything if there is no configure to ack, or if the previous ack was not
+        /// committed yet: the state it acked would never be applied.
+        pub fn ack(&mut self) -> Result<u32, ConfigureError> {
+            if let Some(serial) = self.acked {
+                return Err(ConfigureError::AckWithoutCommit(serial));
+            }
+            let serial = self.pending.take().ok_or(ConfigureError::NoPendingConfigure)?;
+            self.xdg_surface.ack_configure(serial);
+            self.acked = Some(serial);
+            self.configured = true;
+            Ok(serial)
+        }
+
+        /// Commit the surface, acking the last configure first if it was not yet
+        ///
+        /// Returns the serial of the configure applied by this commit, if any.
+        pub fn commit(&mut self) -> Option<u32> {
+            if self.acked.is_none() {
+                let _ = self.ack();
+            }
+            self.surface.commit();
+            self.acked.take()
+        }
+
+        /// Check that the surface can be committed without leaving a configure unacked
+        ///
+        /// This is to be called before committing it without the tracker.
+        pub fn check_commit(&self) -> Result<(), ConfigureError> {
+            match self.pending {
+                Some(serial) => Err(ConfigureError::UnackedConfigure(serial)),
+                None => Ok(()),
+            }
+        }
+
+        /// Report that the surface was committed without the tracker
+        ///
+        /// Returns the serial of the configure applied by this commit, if any.
+        pub fn committed(&mut self) -> Option<u32> {
+            self.acked.take()
+        }
+    }
+
+    #[cfg(feature = "client")]
+    fn valid_app_id(app_id: &str) -> bool {
+        // the ascii methods of `char` require rust 1.24
+        let letter = |c: char| (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_' || c == '-';
+        !app_id.is_empty()
+            && app_id.len() <= MAX_APP_ID_LENGTH
+            && app_id.split('.').all(|element| {
+                let mut chars = element.chars();
+                match chars.next() {
+                    Some(c) if letter(c) => chars.all(|c| letter(c) || (c >= '0' && c <= '9')),
+                    _ => false,
+                }
+            })
+    }
+
+    // check a minimum and a maximum size, whose dimensions are unbounded when they are 0
+    #[cfg(feature = "client")]
+    fn check_sizes(min: (i32, i32), max: (i32, i32)) -> Result<(), PropertyError> {
+        for &(width, height) in &[min, max] {
+            if width < 0 || height < 0 {
+                return Err(PropertyError::NegativeSize(width, height));
+            }
+        }
+        if (max.0 != 0 && min.0 > max.0) || (max.1 != 0 && min.1 > max.1) {
+            return Err(PropertyError::InconsistentSizes { min, max });
+        }
+        Ok(())
+    }
+
+    // decode an integer of an array argument, which are in native endianness
+    #[cfg(feature = "client")]
+    fn native_u32(bytes: &[u8]) -> u32 {
+        let le = bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | u32::from(b));
+        if cfg!(target_endian = "little") {
+            le
+        } else {
+            le.swap_bytes()
+        }
+    }
 }
 
 pub mod viewporter {
@@ -0,0 +1,1947 @@
+//! Staging protocols from wayland-protocols
+//!
+//! The protocols described in this module are in the staging area of
+//! wayland-protocols. They are intended to become stable, but backward
+//! incompatible changes may still be made by bumping the version number
+//! in the protocol and interface names.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+pub mod drm_lease {
+    //! Lease DRM resources to clients
+    //!
+    //! This protocol is used by compositors which are DRM masters to lease DRM
+    //! connectors to their clients, typically to let VR applications drive a
+    //! head-mounted display directly.
+
+    wayland_protocol_versioned!("drm-lease", [v1], [], []);
+}
+
+pub mod ext_data_control {
+    //! Control data devices
+    //!
+    //! This protocol allows a privileged client to control data devices. In
+    //! particular, the client will be able to manage the current selection and
+    //! take the role of a clipboard manager.
+    //!
+    //! As this gives access to everything the user copies, a compositor should
+    //! only advertise the `ext_data_control_manager_v1` global to trusted clients,
+    //! for example using `Display::create_global_with_filter()`. It should also
+    //! avoid forwarding selections that are flagged as sensitive, see
+    //! `is_sensitive()`.
+
+    wayland_protocol_versioned!(
+        "ext-data-control",
+        [v1],
+        [
+            (wl_seat, wl_seat_interface)
+        ],
+        []
+    );
+
+    /// Mime type offered by password managers alongside their secrets
+    ///
+    /// Its presence in the list of mime types offered by a data source indicates
+    /// that its content should not be recorded by clipboard managers.
+    pub const PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+    /// Check whether a selection offering these mime types is flagged as sensitive
+    ///
+    /// Compositors can use this to decide not to send a selection to data control
+    /// clients. This returns `true` if the source offers the `PASSWORD_MANAGER_HINT`
+    /// mime type.
+    pub fn is_sensitive<S: AsRef<str>>(mime_types: &[S]) -> bool {
+        mime_types.iter().any(|m| m.as_ref() == PASSWORD_MANAGER_HINT)
+    }
+
+    /// Compute the mime types of a selection that can be offered to a data control client
+    ///
+    /// Returns an empty list if the selection is sensitive (see `is_sensitive()`),
+    /// otherwise the mime types for which `filter` returns `true`. An empty list
+    /// means the selection should be sent to the client as NULL.
+    pub fn filter_mime_types<S, F>(mime_types: &[S], mut filter: F) -> Vec<String>
+    where
+        S: AsRef<str>,
+        F: FnMut(&str) -> bool,
+    {
+        if is_sensitive(mime_types) {
+            return Vec::new();
+        }
+        mime_types
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|m| filter(m))
+            .map(|m| m.to_owned())
+            .collect()
+    }
+}
+
+pub mod ext_session_lock {
+    //! Secure session locking with arbitrary graphics
+    //!
+    //! This protocol allows a privileged client to lock the session and display
+    //! arbitrary graphics while it is locked. The client authenticates the user and
+    //! tells the compositor when to unlock the session.
+    //!
+    //! A compositor should only advertise the `ext_session_lock_manager_v1` global
+    //! to trusted clients, for example using `Display::create_global_with_filter()`.
+
+    wayland_protocol_versioned!(
+        "ext-session-lock",
+        [v1],
+        [
+            (wl_surface, wl_surface_interface),
+            (wl_output, wl_output_interface)
+        ],
+        []
+    );
+}
+
+pub mod ext_workspace {
+    //! Protocol to list and control workspaces
+    //!
+    //! This protocol allows privileged clients like taskbars and docks to list the
+    //! workspaces of the compositor, organized in groups assigned to sets of outputs,
+    //! and to ask for them to be activated, deactivated, moved to an other group or
+    //! removed.
+    //!
+    //! Server-side, the `WorkspaceManager` type holds the model of the workspaces of
+    //! the compositor. Its mutations are sent as events to all bound managers, and the
+    //! requests of the clients are collected until their `commit`, to be applied by the
+    //! compositor.
+
+    wayland_protocol_versioned!(
+        "ext-workspace",
+        [v1],
+        [
+            (wl_output, wl_output_interface)
+        ],
+        []
+    );
+
+    #[cfg(feature = "server")]
+    use std::sync::{Arc, Mutex};
+
+    #[cfg(feature = "server")]
+    use wayland_server::protocol::wl_output::WlOutput;
+    #[cfg(feature = "server")]
+    use wayland_server::{NewResource, Resource};
+
+    #[cfg(feature = "server")]
+    use self::v1::server::ext_workspace_group_handle_v1::{
+        Event as GroupEvent, ExtWorkspaceGroupHandleV1, GroupCapabilities, Request as GroupRequest,
+    };
+    #[cfg(feature = "server")]
+    use self::v1::server::ext_workspace_handle_v1::{
+        Event as WorkspaceEvent, ExtWorkspaceHandleV1, Request as WorkspaceRequestMsg, State,
+        WorkspaceCapabilities,
+    };
+    #[cfg(feature = "server")]
+    use self::v1::server::ext_workspace_manager_v1::{
+        Event as ManagerEvent, ExtWorkspaceManagerV1, Request as ManagerRequest,
+    };
+
+    /// Identifier of a workspace of a `WorkspaceManager`
+    #[cfg(feature = "server")]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct WorkspaceId(u32);
+
+    /// Identifier of a workspace group of a `WorkspaceManager`
+    #[cfg(feature = "server")]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct GroupId(u32);
+
+    /// A request of a client, to be applied by the compositor
+    #[cfg(feature = "server")]
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum WorkspaceRequest {
+        /// Activate a workspace
+        Activate(WorkspaceId),
+        /// Deactivate a workspace
+        Deactivate(WorkspaceId),
+        /// Assign a workspace to a group
+        Assign {
+            /// The workspace
+            workspace: WorkspaceId,
+            /// The group it should be assigned to
+            group: GroupId,
+        },
+        /// Remove a workspace
+        Remove(WorkspaceId),
+        /// Create a workspace in a group
+        CreateWorkspace {
+            /// The group of the new workspace
+            group: GroupId,
+            /// The name the client would like the workspace to have
+            name: String,
+        },
+    }
+
+    #[cfg(feature = "server")]
+    struct Group {
+        id: GroupId,
+        capabilities: GroupCapabilities,
+        outputs: Vec<Resource<WlOutput>>,
+    }
+
+    #[cfg(feature = "server")]
+    struct Workspace {
+        id: WorkspaceId,
+        stable_id: Option<String>,
+        name: String,
+        coordinates: Vec<u32>,
+        state: State,
+        capabilities: WorkspaceCapabilities,
+        group: Option<GroupId>,
+    }
+
+    // the requests of a client since its last commit
+    #[cfg(feature = "server")]
+    struct Pending {
+        requests: Vec<WorkspaceRequest>,
+        stopped: bool,
+    }
+
+    // a bound workspace manager, and the handles of the groups and workspaces of its client
+    #[cfg(feature = "server")]
+    struct Instance {
+        manager: Resource<ExtWorkspaceManagerV1>,
+        pending: Arc<Mutex<Pending>>,
+        groups: Vec<(GroupId, Resource<ExtWorkspaceGroupHandleV1>)>,
+        workspaces: Vec<(WorkspaceId, Resource<ExtWorkspaceHandleV1>)>,
+        dirty: bool,
+    }
+
+    /// The model of the workspaces of a compositor
+    ///
+    /// The compositor binds the `ext_workspace_manager_v1` global with `bind()`, and
+    /// describes its groups and workspaces with the other methods of this type. Each
+    /// mutation is sent to the bound managers right away, and `done()` is to be called
+    /// once a batch of mutations is complete, so that the clients see it atomically.
+    ///
+    /// The requests of the clients are only made available by `take_requests()` once they
+    /// commit them. The compositor decides whether to apply them, through the mutations of
+    /// this model: for example, an `Activate` request can be applied by setting the
+    /// `Active` state of the workspace and removing it from the other workspaces of its
+    /// group. The requests the workspace or group does not have the capability for are
+    /// dropped.
+    ///
+    /// Workspaces are created outside of any group, and must be assigned to one with
+    /// `assign()` to be displayed by most clients.
+    #[cfg(feature = "server")]
+    pub struct WorkspaceManager {
+        groups: Vec<Group>,
+        workspaces: Vec<Workspace>,
+        instances: Vec<Instance>,
+        committed: Arc<Mutex<Vec<WorkspaceRequest>>>,
+        serial: u32,
+    }
+
+    #[cfg(feature = "server")]
+    impl WorkspaceManager {
+        /// Create a new model, without groups nor workspaces
+        pub fn new() -> WorkspaceManager {
+            WorkspaceManager {
+                groups: Vec::new(),
+                workspaces: Vec::new(),
+                instances: Vec::new(),
+                committed: Arc::new(Mutex::new(Vec::new())),
+                serial: 0,
+            }
+        }
+
+        /// Implement a newly bound workspace manager
+        ///
+        /// This is to be called from the bind callback of the global. The current groups and
+        /// workspaces are sent to the manager, followed by a `done` event.
+        pub fn bind(&mut self, manager: NewResource<ExtWorkspaceManagerV1>) {
+            let pending = Arc::new(Mutex::new(Pending {
+                requests: Vec::new(),
+                stopped: false,
+            }));
+            let committed = self.committed.clone();
+            let manager_pending = pending.clone();
+            let manager = manager.implement(
+                move |request, manager: Resource<ExtWorkspaceManagerV1>| {
+                    let mut pending = manager_pending.lock().unwrap();
+                    match request {
+                        ManagerRequest::Commit => {
+                            committed.lock().unwrap().extend(pending.requests.drain(..));
+                        }
+                        ManagerRequest::Stop => {
+                            if !pending.stopped {
+                                pending.stopped = true;
+                                manager.send(ManagerEvent::Finished);
+                            }
+                        }
+                    }
+                },
+                None::<fn(_)>,
+                (),
+            );
+            let mut instance = Instance {
+                manager,
+                pending,
+                groups: Vec::new(),
+                workspaces: Vec::new(),
+                dirty: true,
+            };
+            for group in &self.groups {
+                instance.add_group(group);
+            }
+            for workspace in &self.workspaces {
+                instance.add_workspace(workspace);
+            }
+            instance.done();
+            self.instances.push(instance);
+        }
+
+        /// Create a workspace group with given capabilities
+        pub fn create_group(&mut self, capabilities: GroupCapabilities) -> GroupId {
+            self.serial += 1;
+            let group = Group {
+                id: GroupId(self.serial),
+                capabilities,
+                outputs: Vec::new(),
+            };
+            self.cleanup();
+            for instance in &mut self.instances {
+                instance.add_group(&group);
+            }
+            self.groups.push(group);
+            GroupId(self.serial)
+        }
+
+        /// Remove a workspace group
+        ///
+        /// Its workspaces leave it and are no longer assigned to any group.
+        pub fn remove_group(&mut self, group: GroupId) {
+            let workspaces = self
+                .workspaces
+                .iter()
+                .filter(|w| w.group == Some(group))
+                .map(|w| w.id)
+                .collect::<Vec<_>>();
+            for workspace in workspaces {
+                self.assign(workspace, None);
+            }
+            self.groups.retain(|g| g.id != group);
+            for instance in &mut self.instances {
+                if let Some(handle) = instance.group_handle(group).cloned() {
+                    handle.send(GroupEvent::Removed);
+                    instance.dirty = true;
+                }
+                instance.groups.retain(|&(id, _)| id != group);
+            }
+        }
+
+        /// Set the capabilities of a workspace group
+        pub fn set_group_capabilities(&mut self, group: GroupId, capabilities: GroupCapabilities) {
+            if let Some(g) = self.groups.iter_mut().find(|g| g.id == group) {
+                g.capabilities = capabilities;
+            } else {
+                return;
+            }
+            self.send_group(group, || GroupEvent::Capabilities { capabilities });
+        }
+
+        /// Report that an output is assigned to a workspace group
+        ///
+        /// This is to be called for each `wl_output` resource bound by the clients, it is
+        /// only sent to the managers of the client of the resource.
+        pub fn group_output_enter(&mut self, group: GroupId, output: &Resource<WlOutput>) {
+            match self.groups.iter_mut().find(|g| g.id == group) {
+                Some(g) => {
+                    g.outputs.retain(|o| o.is_alive());
+                    if g.outputs.iter().any(|o| o.equals(output)) {
+                        return;
+                    }
+                    g.outputs.push(output.clone());
+                }
+                None => return,
+            }
+            for instance in &mut self.instances {
+                if let Some(handle) = instance.group_handle(group).cloned() {
+                    if handle.same_client_as(output) {
+                        handle.send(GroupEvent::OutputEnter {
+                            output: output.clone(),
+                        });
+                        instance.dirty = true;
+                    }
+                }
+            }
+        }
+
+        /// Report that an output is no longer assigned to a workspace group
+        pub fn group_output_leave(&mut self, group: GroupId, output: &Resource<WlOutput>) {
+            match self.groups.iter_mut().find(|g| g.id == group) {
+                Some(g) => {
+                    if !g.outputs.iter().any(|o| o.equals(output)) {
+                        return;
+                    }
+                    g.outputs.retain(|o| o.is_alive() && !o.equals(output));
+                }
+                None => return,
+            }
+            if !output.is_alive() {
+                return;
+            }
+            for instance in &mut self.instances {
+                if let Some(handle) = instance.group_handle(group).cloned() {
+                    if handle.same_client_as(output) {
+                        handle.send(GroupEvent::OutputLeave {
+                            output: output.clone(),
+                        });
+                        instance.dirty = true;
+                    }
+                }
+            }
+        }
+
+        /// Create a workspace with given name, outside of any group
+        ///
+        /// The workspace is inactive and has no capabilities.
+        pub fn create_workspace(&mut self, name: &str) -> WorkspaceId {
+            self.serial += 1;
+            let workspace = Workspace {
+                id: WorkspaceId(self.serial),
+                stable_id: None,
+                name: name.to_owned(),
+                coordinates: Vec::new(),
+                state: State::empty(),
+                capabilities: WorkspaceCapabilities::empty(),
+                group: None,
+            };
+            self.cleanup();
+            for instance in &mut self.instances {
+                instance.add_workspace(&workspace);
+            }
+            self.workspaces.push(workspace);
+            WorkspaceId(self.serial)
+        }
+
+        /// Remove a workspace
+        ///
+        /// It leaves its group first, if any.
+        pub fn remove_workspace(&mut self, workspace: WorkspaceId) {
+            self.assign(workspace, None);
+            self.workspaces.retain(|w| w.id != workspace);
+            for instance in &mut self.instances {
+                if let Some(handle) = instance.workspace_handle(workspace).cloned() {
+                    handle.send(WorkspaceEvent::Removed);
+                    instance.dirty = true;
+                }
+                instance.workspaces.retain(|&(id, _)| id != workspace);
+            }
+        }
+
+        /// Set the stable identifier of a workspace
+        ///
+        /// This identifier is for the workspaces likely to persist across sessions, and can
+        /// only be set once: does nothing if the workspace already has one.
+        pub fn set_workspace_id(&mut self, workspace: WorkspaceId, id: &str) {
+            match self.workspaces.iter_mut().find(|w| w.id == workspace) {
+                Some(ref mut w) if w.stable_id.is_none() => w.stable_id = Some(id.to_owned()),
+                _ => return,
+            }
+            self.send_workspace(workspace, || WorkspaceEvent::Id { id: id.to_owned() });
+        }
+
+        /// Set the name of a workspace
+        pub fn set_name(&mut self, workspace: WorkspaceId, name: &str) {
+            match self.workspaces.iter_mut().find(|w| w.id == workspace) {
+                Some(w) => w.name = name.to_owned(),
+                None => return,
+            }
+            self.send_workspace(workspace, || WorkspaceEvent::Name {
+                name: name.to_owned(),
+            });
+        }
+
+        /// Set the coordinates of a workspace in the grid of its group
+        ///
+        /// Empty coordinates mean that the workspace is not ordered geometrically.
+        pub fn set_coordinates(&mut self, workspace: WorkspaceId, coordinates: &[u32]) {
+            match self.workspaces.iter_mut().find(|w| w.id == workspace) {
+                Some(w) => w.coordinates = coordinates.to_vec(),
+                None => return,
+            }
+            self.send_workspace(workspace, || WorkspaceEvent::Coordinates {
+                coordinates: native_bytes(coordinates),
+            });
+        }
+
+        /// Set the state of a workspace
+        ///
+        /// Nothing is sent if the state does not change.
+        pub fn set_state(&mut self, workspace: WorkspaceId, state: State) {
+            match self.workspaces.iter_mut().find(|w| w.id == workspace) {
+                Some(ref mut w) if w.state != state => w.state = state,
+                _ => return,
+            }
+            self.send_workspace(workspace, || WorkspaceEvent::State { state });
+        }
+
+        /// Set the capabilities of a workspace
+        pub fn set_capabilities(&mut self, workspace: WorkspaceId, capabilities: WorkspaceCapabilities) {
+            match self.workspaces.iter_mut().find(|w| w.id == workspace) {
+                Some(w) => w.capabilities = capabilities,
+                None => return,
+            }
+            self.send_workspace(workspace, || WorkspaceEvent::Capabilities { capabilities });
+        }
+
+        /// Assign a workspace to a group, or remove it from its group with `None`
+        pub fn assign(&mut self, workspace: WorkspaceId, group: Option<GroupId>) {
+            if let Some(group) = group {
+                if !self.groups.iter().any(|g| g.id == group) {
+                    return;
+                }
+            }
+            let previous = match self.workspaces.iter_mut().find(|w| w.id == workspace) {
+                Some(ref mut w) if w.group != group => ::std::mem::replace(&mut w.group, group),
+                _ => return,
+            };
+            for instance in &mut self.instances {
+                let handle = match instance.workspace_handle(workspace) {
+                    Some(handle) => handle.clone(),
+                    None => continue,
+                };
+                if let Some(previous) = previous.and_then(|g| instance.group_handle(g)) {
+                    previous.send(GroupEvent::WorkspaceLeave {
+                        workspace: handle.clone(),
+                    });
+                }
+                if let Some(group) = group.and_then(|g| instance.group_handle(g)) {
+                    group.send(GroupEvent::WorkspaceEnter { workspace: handle });
+                }
+                instance.dirty = true;
+            }
+        }
+
+        /// The state of a workspace
+        pub fn state(&self, workspace: WorkspaceId) -> Option<State> {
+            self.workspaces
+                .iter()
+                .find(|w| w.id == workspace)
+                .map(|w| w.state)
+        }
+
+        /// The group a workspace is assigned to
+        pub fn group(&self, workspace: WorkspaceId) -> Option<GroupId> {
+            self.workspaces
+                .iter()
+                .find(|w| w.id == workspace)
+                .and_then(|w| w.group)
+        }
+
+        /// The workspaces assigned to a group
+        pub fn workspaces(&self, group: GroupId) -> Vec<WorkspaceId> {
+            self.workspaces
+                .iter()
+                .filter(|w| w.group == Some(group))
+                .map(|w| w.id)
+                .collect()
+        }
+
+        /// Send the `done` event, completing a batch of mutations
+        ///
+        /// It is only sent to the managers which received events since the previous one.
+        pub fn done(&mut self) {
+            self.cleanup();
+            for instance in &mut self.instances {
+                instance.done();
+            }
+        }
+
+        /// Take the requests the clients committed since the previous call
+        ///
+        /// The requests on removed workspaces or groups, and the ones they do not have the
+        /// capability for, are dropped.
+        pub fn take_requests(&mut self) -> Vec<WorkspaceRequest> {
+            let requests = ::std::mem::replace(&mut *self.committed.lock().unwrap(), Vec::new());
+            requests
+                .into_iter()
+                .filter(|request| self.allows(request))
+                .collect()
+        }
+
+        fn allows(&self, request: &WorkspaceRequest) -> bool {
+            let workspace_can = |id: WorkspaceId, capability: WorkspaceCapabilities| {
+                self.workspaces
+                    .iter()
+                    .any(|w| w.id == id && w.capabilities.contains(capability))
+            };
+            match *request {
+                WorkspaceRequest::Activate(id) => workspace_can(id, WorkspaceCapabilities::Activate),
+                WorkspaceRequest::Deactivate(id) => workspace_can(id, WorkspaceCapabilities::Deactivate),
+                WorkspaceRequest::Remove(id) => workspace_can(id, WorkspaceCapabilities::Remove),
+                WorkspaceRequest::Assign { workspace, group } => {
+                    workspace_can(workspace, WorkspaceCapabilities::Assign)
+                        && self.groups.iter().any(|g| g.id == group)
+                }
+                WorkspaceRequest::CreateWorkspace { group, .. } => self
+                    .groups
+                    .iter()
+                    .any(|g| g.id == group && g.capabilities.contains(GroupCapabilities::CreateWorkspace)),
+            }
+        }
+
+        // the events are built for each manager, as they cannot be cloned
+        fn send_group<F: Fn() -> GroupEvent>(&mut self, group: GroupId, event: F) {
+            for instance in &mut self.instances {
+                if let Some(handle) = instance.group_handle(group).cloned() {
+                    handle.send(event());
+                    instance.dirty = true;
+                }
+            }
+        }
+
+        fn send_workspace<F: Fn() -> WorkspaceEvent>(&mut self, workspace: WorkspaceId, event: F) {
+            for instance in &mut self.instances {
+                if let Some(handle) = instance.workspace_handle(workspace).cloned() {
+                    handle.send(event());
+                    instance.dirty = true;
+                }
+            }
+        }
+
+        // forget the managers which were destroyed or stopped
+        fn cleanup(&mut self) {
+            self.instances
+                .retain(|i| i.manager.is_alive() && !i.pending.lock().unwrap().stopped);
+        }
+    }
+
+    #[cfg(feature = "server")]
+    impl Default for WorkspaceManager {
+        fn default() -> WorkspaceManager {
+            WorkspaceManager::new()
+        }
+    }
+
+    #[cfg(feature = "server")]
+    impl Instance {
+        fn group_handle(&self, group: GroupId) -> Option<&Resource<ExtWorkspaceGroupHandleV1>> {
+            self.groups
+                .iter()
+                .find(|&&(id, ref handle)| id == group && handle.is_alive())
+                .map(|&(_, ref handle)| handle)
+        }
+
+        fn workspace_handle(&self, workspace: WorkspaceId) -> Option<&Resource<ExtWorkspaceHandleV1>> {
+            self.workspaces
+                .iter()
+                .find(|&&(id, ref handle)| id == workspace && handle.is_alive())
+                .map(|&(_, ref handle)| handle)
+        }
+
+        fn add_group(&mut self, group: &Group) {
+            let client = match self.manager.client() {
+                Some(client) => client,
+                None => return,
+            };
+            let handle = match client.create_resource::<ExtWorkspaceGroupHandleV1>(self.manager.version()) {
+                Some(handle) => handle,
+                None => return,
+            };
+            let id = group.id;
+            let pending = self.pending.clone();
+            let handle = handle.implement(
+                move |request, _| match request {
+                    GroupRequest::CreateWorkspace { workspace } => pending
+                        .lock()
+                        .unwrap()
+                        .requests
+                        .push(WorkspaceRequest::CreateWorkspace {
+                            group: id,
+                            name: workspace,
+                        }),
+                    GroupRequest::Destroy => {}
+                },
+                None::<fn(_)>,
+                id,
+            );
+            self.manager.send(ManagerEvent::WorkspaceGroup {
+                workspace_group: handle.clone(),
+            });
+            handle.send(GroupEvent::Capabilities {
+                capabilities: group.capabilities,
+            });
+            for output in &group.outputs {
+                if output.is_alive() && handle.same_client_as(output) {
+                    handle.send(GroupEvent::OutputEnter {
+                        output: output.clone(),
+                    });
+                }
+            }
+            self.groups.push((id, handle));
+            self.dirty = true;
+        }
+
+        fn add_workspace(&mut self, workspace: &Workspace) {
+            let client = match self.manager.client() {
+                Some(client) => client,
+                None => return,
+            };
+            let handle = match client.create_resource::<ExtWorkspaceHandleV1>(self.manager.version()) {
+                Some(handle) => handle,
+                None => return,
+            };
+            let id = workspace.id;
+            let pending = self.pending.clone();
+            let handle = handle.implement(
+                move |request, _| {
+                    let request = match request {
+                        WorkspaceRequestMsg::Activate => WorkspaceRequest::Activate(id),
+                        WorkspaceRequestMsg::Deactivate => WorkspaceRequest::Deactivate(id),
+                        WorkspaceRequestMsg::Remove => WorkspaceRequest::Remove(id),
+                        WorkspaceRequestMsg::Assign { workspace_group } => {
+                            match workspace_group.user_data::<GroupId>() {
+                                Some(&group) => WorkspaceRequest::Assign { workspace: id, group },
+                                None => return,
+                            }
+                        }
+                        WorkspaceRequestMsg::Destroy => return,
+                    };
+                    pending.lock().unwrap().requests.push(request);
+                },
+                None::<fn(_)>,
+                id,
+            );
+            self.manager.send(ManagerEvent::Workspace {
+                workspace: handle.clone(),
+            });
+            if let Some(ref stable_id) = workspace.stable_id {
+                handle.send(WorkspaceEvent::Id {
+                    id: stable_id.clone(),
+                });
+            }
+            handle.send(WorkspaceEvent::Name {
+                name: workspace.name.clone(),
+            });
+            if !workspace.coordinates.is_empty() {
+                handle.send(WorkspaceEvent::Coordinates {
+                    coordinates: native_bytes(&workspace.coordinates),
+                });
+            }
+            handle.send(WorkspaceEvent::State {
+                state: workspace.state,
+            });
+            handle.send(WorkspaceEvent::Capabilities {
+                capabilities: workspace.capabilities,
+            });
+            if let Some(group) = workspace.group.and_then(|g| self.group_handle(g)) {
+                group.send(GroupEvent::WorkspaceEnter {
+                    workspace: handle.clone(),
+                });
+            }
+            self.workspaces.push((id, handle));
+            self.dirty = true;
+        }
+
+        fn done(&mut self) {
+            if self.dirty && self.manager.is_alive() {
+                self.manager.send(ManagerEvent::Done);
+            }
+            self.dirty = false;
+        }
+    }
+
+    // encode integers for an array argument, which are in native endianness
+    #[cfg(feature = "server")]
+    fn native_bytes(values: &[u32]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(values.len() * 4);
+        for &value in values {
+            let le = if cfg!(target_endian = "little") {
+                value
+            } else {
+                value.swap_bytes()
+            };
+            bytes.extend((0..4).map(|i| (le >> (8 * i)) as u8));
+        }
+        bytes
+    }
+}
+
+pub mod fifo {
+    //! Protocol for fifo constraints on the content updates of surfaces
+    //!
+    //! This protocol lets a client use the completion of a display refresh cycle as a
+    //! readiness constraint of its content updates: committing a buffer with a barrier
+    //! and waiting for the previous one presents each buffer for at least one refresh
+    //! cycle, in order, like the FIFO presentation mode of Vulkan.
+    //!
+    //! Client-side, see `tearing_control::SurfacePresentation` to choose between this
+    //! and the other presentation modes of a surface.
+
+    wayland_protocol_versioned!(
+        "fifo",
+        [v1],
+        [
+            (wl_surface, wl_surface_interface)
+        ],
+        []
+    );
+}
+
+pub mod fractional_scale {
+    //! Protocol for requesting fractional surface scales
+    //!
+    //! This protocol allows a compositor to suggest surfaces to render at fractional
+    //! scales, the client then uses `wp_viewport` to present its scaled buffers.
+    //!
+    //! Server-side, the `ScaleNegotiator` type decides the scale and transform each
+    //! surface should be rendered with from the outputs it is displayed on, and sends
+    //! them to the client with the `preferred_scale` event of this protocol and the
+    //! `preferred_buffer_scale` and `preferred_buffer_transform` events of `wl_surface`,
+    //! only when they change.
+
+    wayland_protocol_versioned!(
+        "fractional-scale",
+        [v1],
+        [
+            (wl_surface, wl_surface_interface)
+        ],
+        []
+    );
+
+    #[cfg(feature = "server")]
+    use wayland_server::protocol::wl_output::Transform;
+    #[cfg(feature = "server")]
+    use wayland_server::protocol::wl_surface::{Event as SurfaceEvent, WlSurface};
+    #[cfg(feature = "server")]
+    use wayland_server::Resource;
+
+    #[cfg(feature = "server")]
+    use self::v1::server::wp_fractional_scale_v1::{Event as FractionalEvent, WpFractionalScaleV1};
+
+    /// How the scale of a surface displayed on several outputs is chosen
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum ScalePolicy {
+        /// Use the output with the highest scale, the surface looks sharp on all outputs
+        Highest,
+        /// Use the output the surface entered first, until it leaves it
+        First,
+    }
+
+    /// The scale and transform a surface should be rendered with
+    #[cfg(feature = "server")]
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub struct Preferred {
+        /// The fractional scale
+        pub scale: f64,
+        /// The integer scale, as sent to surfaces not using this protocol
+        pub buffer_scale: i32,
+        /// The transform of the buffers
+        pub transform: Transform,
+    }
+
+    #[cfg(feature = "server")]
+    impl Preferred {
+        fn new(scale: f64, transform: Transform) -> Preferred {
+            Preferred {
+                scale,
+                buffer_scale: (scale.ceil() as i32).max(1),
+                transform,
+            }
+        }
+
+        // the numerator of the scale over 120, as sent in `preferred_scale`
+        fn scale_120(&self) -> u32 {
+            (self.scale * 120.0).round().max(1.0) as u32
+        }
+    }
+
+    #[cfg(feature = "server")]
+    struct SurfaceScale<O> {
+        surface: Resource<WlSurface>,
+        fractional: Option<Resource<WpFractionalScaleV1>>,
+        // the entered outputs, in the order they were entered
+        outputs: Vec<O>,
+        current: Preferred,
+        fractional_sent: bool,
+    }
+
+    /// Negotiation of the scale and transform of surfaces
+    ///
+    /// The outputs are identified by values of type `O` chosen by the compositor, and
+    /// described with `set_output()`. Surfaces are added with `add_surface()`, and the
+    /// compositor reports the outputs they enter and leave with `surface_enter()` and
+    /// `surface_leave()`, alongside the corresponding `wl_surface` events.
+    ///
+    /// The preferred scale and transform of a surface are the ones of the output chosen
+    /// by the `ScalePolicy`. They are sent to the surface whenever they change, and are
+    /// kept when the surface leaves all outputs, so that a surface moved between outputs
+    /// is not asked to render at an intermediate scale. Before entering any output, a
+    /// surface keeps the default scale of 1 and the normal transform.
+    #[cfg(feature = "server")]
+    pub struct ScaleNegotiator<O> {
+        policy: ScalePolicy,
+        outputs: Vec<(O, f64, Transform)>,
+        surfaces: Vec<SurfaceScale<O>>,
+    }
+
+    #[cfg(feature = "server")]
+    impl<O: PartialEq + Clone> ScaleNegotiator<O> {
+        /// Create a new negotiator, without outputs nor surfaces
+        pub fn new(policy: ScalePolicy) -> ScaleNegotiator<O> {
+            ScaleNegotiator {
+                policy,
+                outputs: Vec::new(),
+                surfaces: Vec::new(),
+            }
+        }
+
+        /// Set the scale and transform of an output
+        ///
+        /// The surfaces displayed on this output are updated accordingly.
+        pub fn set_output(&mut self, output: O, scale: f64, transform: Transform) {
+            match self.outputs.iter().position(|o| o.0 == output) {
+                Some(index) => {
+                    self.outputs[index].1 = scale;
+                    self.outputs[index].2 = transform;
+                }
+                None => self.outputs.push((output, scale, transform)),
+            }
+            self.update_all();
+        }
+
+        /// Remove an output
+        ///
+        /// The surfaces displayed on this output leave it.
+        pub fn remove_output(&mut self, output: &O) {
+            self.outputs.retain(|o| o.0 != *output);
+            for surface in &mut self.surfaces {
+                surface.outputs.retain(|o| o != output);
+            }
+            self.update_all();
+        }
+
+        /// Start tracking a surface
+        pub fn add_surface(&mut self, surface: &Resource<WlSurface>) {
+            self.surfaces.retain(|s| s.surface.is_alive());
+            if self.find(surface).is_none() {
+                self.surfaces.push(SurfaceScale {
+                    surface: surface.clone(),
+                    fractional: None,
+                    outputs: Vec::new(),
+                    current: Preferred::new(1.0, Transform::Normal),
+                    fractional_sent: false,
+                });
+            }
+        }
+
+        /// Stop tracking a surface
+        pub fn remove_surface(&mut self, surface: &Resource<WlSurface>) {
+            self.surfaces.retain(|s| s.surface.is_alive() && !s.surface.equals(surface));
+        }
+
+        /// Set the fractional scale object of a surface
+        ///
+        /// This is to be called on the `get_fractional_scale` request. The current
+        /// preferred scale of the surface is sent to it if the surface is displayed on
+        /// an output.
+        pub fn set_fractional_scale(
+            &mut self,
+            surface: &Resource<WlSurface>,
+            fractional: &Resource<WpFractionalScaleV1>,
+        ) {
+            self.add_surface(surface);
+            if let Some(idx) = self.find(surface) {
+                let state = &mut self.surfaces[idx];
+                state.fractional = Some(fractional.clone());
+                state.fractional_sent = false;
+                if !state.outputs.is_empty() {
+                    send_fractional(state);
+                }
+            }
+        }
+
+        /// Report that a surface entered an output
+        pub fn surface_enter(&mut self, surface: &Resource<WlSurface>, output: O) {
+            self.add_surface(surface);
+            if let Some(idx) = self.find(surface) {
+                if !self.surfaces[idx].outputs.contains(&output) {
+                    self.surfaces[idx].outputs.push(output);
+                }
+                self.update(idx);
+            }
+        }
+
+        /// Report that a surface left an output
+        pub fn surface_leave(&mut self, surface: &Resource<WlSurface>, output: &O) {
+            if let Some(idx) = self.find(surface) {
+                self.surfaces[idx].outputs.retain(|o| o != output);
+                self.update(idx);
+            }
+        }
+
+        /// The current preferred scale and transform of a surface
+        ///
+        /// Returns `None` if the surface is not tracked.
+        pub fn preferred(&self, surface: &Resource<WlSurface>) -> Option<Preferred> {
+            self.find(surface).map(|idx| self.surfaces[idx].current)
+        }
+
+        fn find(&self, surface: &Resource<WlSurface>) -> Option<usize> {
+            self.surfaces.iter().position(|s| s.surface.equals(surface))
+        }
+
+        fn update_all(&mut self) {
+            self.surfaces.retain(|s| s.surface.is_alive());
+            for idx in 0..self.surfaces.len() {
+                self.update(idx);
+            }
+        }
+
+        fn update(&mut self, idx: usize) {
+            let chosen = {
+                let outputs = &self.outputs;
+                let mut entered = self.surfaces[idx]
+                    .outputs
+                    .iter()
+                    .filter_map(|o| outputs.iter().find(|output| output.0 == *o));
+                match self.policy {
+                    ScalePolicy::First => entered.next(),
+                    ScalePolicy::Highest => {
+                        let mut best: Option<&(O, f64, Transform)> = None;
+                        for o in entered {
+                            match best {
+                                Some(b) if b.1 >= o.1 => {}
+                                _ => best = Some(o),
+                            }
+                        }
+                        best
+                    }
+                }
+            };
+            let preferred = match chosen {
+                Some(&(_, scale, transform)) => Preferred::new(scale, transform),
+                // keep the current preference until the surface enters an other output
+                None => return,
+            };
+            let state = &mut self.surfaces[idx];
+            let previous = state.current;
+            state.current = preferred;
+            // wl_surface.preferred_buffer_* are available since version 6
+            if state.surface.version() >= 6 {
+                if preferred.buffer_scale != previous.buffer_scale {
+                    state.surface.send(SurfaceEvent::PreferredBufferScale {
+                        factor: preferred.buffer_scale,
+                    });
+                }
+                if preferred.transform != previous.transform {
+                    state.surface.send(SurfaceEvent::PreferredBufferTransform {
+                        transform: preferred.transform,
+                    });
+                }
+            }
+            if !state.fractional_sent || preferred.scale_120() != previous.scale_120() {
+                send_fractional(state);
+            }
+        }
+    }
+
+    #[cfg(feature = "server")]
+    fn send_fractional<O>(state: &mut SurfaceScale<O>) {
+        if let Some(ref fractional) = state.fractional {
+            if fractional.is_alive() {
+                fractional.send(FractionalEvent::PreferredScale {
+                    scale: state.current.scale_120(),
+                });
+                state.fractional_sent = true;
+            }
+        }
+    }
+}
+
+pub mod security_context {
+    //! Protocol to attach a security context to the clients of a sandbox
+    //!
+    //! This protocol allows sandbox engines to register a listening socket to the
+    //! compositor, the clients connecting to it being given a security context made of
+    //! the name of the engine, and identifiers of the application and of its instance.
+    //! The compositor can then restrict the features these clients can use.
+    //!
+    //! Client-side, `SecurityContextListener` creates the listening socket a sandboxed
+    //! application connects to, and registers it with its security context. The
+    //! compositor stops accepting connections on it once the listener is dropped.
+
+    wayland_protocol_versioned!("security-context", [v1], [], []);
+
+    #[cfg(feature = "client")]
+    use std::fs;
+    #[cfg(feature = "client")]
+    use std::io;
+    #[cfg(feature = "client")]
+    use std::os::unix::io::AsRawFd;
+    #[cfg(feature = "client")]
+    use std::os::unix::net::{UnixListener, UnixStream};
+    #[cfg(feature = "client")]
+    use std::path::{Path, PathBuf};
+
+    #[cfg(feature = "client")]
+    use wayland_client::{InteriorNulError, Proxy};
+
+    #[cfg(feature = "client")]
+    use self::v1::client::wp_security_context_manager_v1::{
+        RequestsTrait as ManagerRequests, WpSecurityContextManagerV1,
+    };
+    #[cfg(feature = "client")]
+    use self::v1::client::wp_security_context_v1::RequestsTrait as ContextRequests;
+
+    /// Metadata of a security context
+    ///
+    /// Which fields are required depends on the sandbox engine, and the compositor
+    /// decides whether the metadata of a security context is valid.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct SecurityContext {
+        /// Name of the sandbox engine, in reverse-DNS style (like `org.flatpak`)
+        pub sandbox_engine: Option<String>,
+        /// Identifier of the sandboxed application
+        pub app_id: Option<String>,
+        /// Identifier of the running instance of the sandboxed application
+        pub instance_id: Option<String>,
+    }
+
+    /// A listening socket registered to the compositor with a security context
+    ///
+    /// The compositor accepts connections on this socket for as long as this listener
+    /// is alive, and attaches its security context to them. When the listener is
+    /// dropped, the compositor stops accepting new connections, the already connected
+    /// clients are left untouched, and the socket file is removed.
+    #[cfg(feature = "client")]
+    #[derive(Debug)]
+    pub struct SecurityContextListener {
+        path: PathBuf,
+        // the compositor stops listening once it sees the hangup of the other end
+        _close: UnixStream,
+    }
+
+    #[cfg(feature = "client")]
+    impl SecurityContextListener {
+        /// Create a listening socket at `path` and register it with a security context
+        ///
+        /// `path` must not exist yet. The requests registering the socket are sent when
+        /// the connection of `manager` is flushed, the sandboxed application should only
+        /// be started afterwards, with its `WAYLAND_DISPLAY` environment variable set to
+        /// `path()`.
+        ///
+        /// The protocol forbids nesting security contexts: compositors do not advertize
+        /// the manager to clients which already have a security context.
+        pub fn new<P: AsRef<Path>>(
+            manager: &Proxy<WpSecurityContextManagerV1>,
+            path: P,
+            context: &SecurityContext,
+        ) -> io::Result<SecurityContextListener> {
+            let path = path.as_ref().to_path_buf();
+            let listener = UnixListener::bind(&path)?;
+            let (close, close_peer) = match UnixStream::pair() {
+                Ok(pair) => pair,
+                Err(e) => {
+                    let _ = fs::remove_file(&path);
+                    return Err(e);
+                }
+            };
+            // the file descriptors are duplicated when the requests are sent, ours can be
+            // closed once this function returns
+            let created = manager.create_listener(listener.as_raw_fd(), close_peer.as_raw_fd(), |newp| {
+                newp.implement(|_, _| {}, ())
+            });
+            let security_context = match created {
+                Ok(security_context) => security_context,
+                Err(()) => {
+                    let _ = fs::remove_file(&path);
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "The wp_security_context_manager_v1 object is dead.",
+                    ));
+                }
+            };
+            let set = (|| -> Result<(), InteriorNulError> {
+                if let Some(ref engine) = context.sandbox_engine {
+                    security_context.set_sandbox_engine(engine.as_str())?;
+                }
+                if let Some(ref app_id) = context.app_id {
+                    security_context.set_app_id(app_id.as_str())?;
+                }
+                if let Some(ref instance_id) = context.instance_id {
+                    security_context.set_instance_id(instance_id.as_str())?;
+                }
+                Ok(())
+            })();
+            if let Err(e) = set {
+                // the socket is not registered without the commit
+                security_context.destroy();
+                let _ = fs::remove_file(&path);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+            }
+            security_context.commit();
+            security_context.destroy();
+            Ok(SecurityContextListener { path, _close: close })
+        }
+
+        /// Path of the listening socket
+        ///
+        /// This is the value to give to the sandboxed application as its
+        /// `WAYLAND_DISPLAY` environment variable.
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    #[cfg(feature = "client")]
+    impl Drop for SecurityContextListener {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+pub mod single_pixel_buffer {
+    //! Single pixel buffer factory
+    //!
+    //! This protocol allows clients to create buffers of a single pixel of a given
+    //! color, which are typically scaled to the needed size with `wp_viewport`.
+
+    wayland_protocol_versioned!(
+        "single-pixel-buffer",
+        [v1],
+        [
+            (wl_buffer, wl_buffer_interface)
+        ],
+        []
+    );
+}
+
+pub mod tearing_control {
+    //! Protocol for tearing control
+    //!
+    //! This protocol lets a client hint the compositor that the content of a surface may
+    //! be presented with tearing, through asynchronous page flips, to reduce latency.
+    //!
+    //! Client-side, the `SurfacePresentation` type selects how the content updates of a
+    //! surface are presented, from the `PresentationMode` preferred by the application
+    //! and the protocols supported by the compositor: tearing through this protocol, and
+    //! fifo through the `fifo` protocol.
+
+    wayland_protocol_versioned!(
+        "tearing-control",
+        [v1],
+        [
+            (wl_surface, wl_surface_interface)
+        ],
+        []
+    );
+
+    #[cfg(feature = "client")]
+    use wayland_client::protocol::wl_surface::WlSurface;
+    #[cfg(feature = "client")]
+    use wayland_client::Proxy;
+
+    #[cfg(feature = "client")]
+    use staging::fifo::v1::client::wp_fifo_manager_v1::{
+        RequestsTrait as FifoManagerRequests, WpFifoManagerV1,
+    };
+    #[cfg(feature = "client")]
+    use staging::fifo::v1::client::wp_fifo_v1::{RequestsTrait as FifoRequests, WpFifoV1};
+
+    #[cfg(feature = "client")]
+    use self::v1::client::wp_tearing_control_manager_v1::{
+        RequestsTrait as TearingManagerRequests, WpTearingControlManagerV1,
+    };
+    #[cfg(feature = "client")]
+    use self::v1::client::wp_tearing_control_v1::{
+        PresentationHint, RequestsTrait as TearingRequests, WpTearingControlV1,
+    };
+
+    /// How the content updates of a surface are presented
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum PresentationMode {
+        /// Synchronized to the vertical blank, without tearing
+        ///
+        /// A content update may replace the previous one before it was displayed, the
+        /// client throttles itself with frame callbacks.
+        Vsync,
+        /// As soon as possible, tearing is acceptable
+        ///
+        /// This gives the lowest latency, for latency-sensitive games.
+        Tearing,
+        /// Synchronized to the vertical blank, each content update being displayed for at
+        /// least one refresh cycle, in order
+        Fifo,
+    }
+
+    impl PresentationMode {
+        /// The mode used for a surface, from the preferred mode and the protocols supported
+        /// by the compositor
+        ///
+        /// The preferred mode is used if the compositor supports its protocol, the surface
+        /// falls back to `Vsync` otherwise, which needs no protocol.
+        pub fn select(preferred: PresentationMode, tearing: bool, fifo: bool) -> PresentationMode {
+            match preferred {
+                PresentationMode::Tearing if tearing => PresentationMode::Tearing,
+                PresentationMode::Fifo if fifo => PresentationMode::Fifo,
+                _ => PresentationMode::Vsync,
+            }
+        }
+    }
+
+    /// The presentation of the content updates of a surface
+    ///
+    /// It creates the tearing control and fifo objects of the surface, for the managers
+    /// the compositor supports, and uses them according to the selected mode. Only one
+    /// object of each kind can exist for a surface: this must not be used on a surface
+    /// managed by EGL or Vulkan, which use these protocols themselves.
+    #[cfg(feature = "client")]
+    pub struct SurfacePresentation {
+        tearing: Option<Proxy<WpTearingControlV1>>,
+        fifo: Option<Proxy<WpFifoV1>>,
+        preferred: PresentationMode,
+        mode: PresentationMode,
+    }
+
+    #[cfg(feature = "client")]
+    impl SurfacePresentation {
+        /// Manage the presentation of a surface
+        ///
+        /// `tearing_manager` and `fifo_manager` are the globals of the protocols the
+        /// compositor supports, if any. The presentation hint of the selected mode is
+        /// applied on the next commit of the surface.
+        ///
+        /// Returns an error if one of the managers is dead.
+        pub fn new(
+            tearing_manager: Option<&Proxy<WpTearingControlManagerV1>>,
+            fifo_manager: Option<&Proxy<WpFifoManagerV1>>,
+            surface: &Proxy<WlSurface>,
+            preferred: PresentationMode,
+        ) -> Result<SurfacePresentation, ()> {
+            let tearing = match tearing_manager {
+                Some(manager) => Some(
+                    manager.get_tearing_control(surface, |newp| newp.implement(|_, _| {}, ()))?,
+                ),
+                None => None,
+            };
+            let fifo = match fifo_manager {
+                Some(manager) => Some(manager.get_fifo(surface, |newp| newp.implement(|_, _| {}, ()))?),
+                None => None,
+            };
+            let mut presentation = SurfacePresentation {
+                tearing,
+                fifo,
+                preferred,
+                // the default of a surface without tearing control
+                mode: PresentationMode::Vsync,
+            };
+            presentation.update();
+            Ok(presentation)
+        }
+
+        /// The mode preferred by the application
+        pub fn preferred(&self) -> PresentationMode {
+            self.preferred
+        }
+
+        /// The mode actually used, see `PresentationMode::select()`
+        pub fn mode(&self) -> PresentationMode {
+            self.mode
+        }
+
+        /// Change the mode preferred by the application
+        ///
+        /// Like the initial one, the new presentation hint is applied on the next commit of
+        /// the surface. Returns the mode now used.
+        pub fn set_preferred(&mut self, preferred: PresentationMode) -> PresentationMode {
+            self.preferred = preferred;
+            self.update();
+            self.mode
+        }
+
+        /// Add the constraints of the mode to the next content update
+        ///
+        /// This must be called before each commit of a new buffer to the surface. In the
+        /// `Fifo` mode, it makes the content update wait for the barrier of the previous
+        /// one, and sets a new barrier. It does nothing in the other modes.
+        pub fn prepare_commit(&self) {
+            if let (PresentationMode::Fifo, Some(fifo)) = (self.mode, self.fifo.as_ref()) {
+                fifo.wait_barrier();
+                fifo.set_barrier();
+            }
+        }
+
+        /// Destroy the protocol objects of the surface
+        ///
+        /// The surface reverts to the `Vsync` mode on its next commit. This must be called
+        /// before the surface is destroyed.
+        pub fn destroy(self) {
+            if let Some(tearing) = self.tearing {
+                tearing.destroy();
+            }
+            if let Some(fifo) = self.fifo {
+                fifo.destroy();
+            }
+        }
+
+        fn update(&mut self) {
+            let mode = PresentationMode::select(self.preferred, self.tearing.is_some(), self.fifo.is_some());
+            if let Some(ref tearing) = self.tearing {
+                if presentation_hint(mode) != presentation_hint(self.mode) {
+                    tearing.set_presentation_hint(presentation_hint(mode));
+                }
+            }
+            self.mode = mode;
+        }
+    }
+
+    // only tearing needs a hint, the fifo constraints are given with each commit
+    #[cfg(feature = "client")]
+    fn presentation_hint(mode: PresentationMode) -> PresentationHint {
+        if mode == PresentationMode::Tearing {
+            PresentationHint::Async
+        } else {
+            PresentationHint::Vsync
+        }
+    }
+}
+
+pub mod xdg_dialog {
+    //! Protocol to mark toplevels as dialogs
+    //!
+    //! This protocol allows clients to hint the compositor that a toplevel is a dialog
+    //! of an other toplevel, its parent, and whether this dialog is modal: the user
+    //! needs to address it before interacting with its parent again.
+    //!
+    //! Client-side, the `Dialog` type sets both the parent of a toplevel and its dialog
+    //! hints, and keeps track of them. As the protocol leaves the filtering of the input
+    //! events of the parents of modal dialogs to the clients, `Dialog::blocks()` tells
+    //! toolkits which toplevels should not handle them.
+
+    wayland_protocol_versioned!(
+        "xdg-dialog",
+        [v1],
+        [],
+        [
+            (xdg_shell, xdg_toplevel, xdg_toplevel_interface)
+        ]
+    );
+
+    #[cfg(feature = "client")]
+    use wayland_client::Proxy;
+
+    #[cfg(feature = "client")]
+    use xdg_shell::client::xdg_toplevel::{RequestsTrait as ToplevelRequests, XdgToplevel};
+
+    #[cfg(feature = "client")]
+    use self::v1::client::xdg_dialog_v1::{RequestsTrait as DialogRequests, XdgDialogV1};
+    #[cfg(feature = "client")]
+    use self::v1::client::xdg_wm_dialog_v1::{RequestsTrait as WmDialogRequests, XdgWmDialogV1};
+
+    /// A toplevel used as a dialog of an other toplevel
+    #[cfg(feature = "client")]
+    pub struct Dialog {
+        toplevel: Proxy<XdgToplevel>,
+        parent: Proxy<XdgToplevel>,
+        dialog: Option<Proxy<XdgDialogV1>>,
+        modal: bool,
+    }
+
+    #[cfg(feature = "client")]
+    impl Dialog {
+        /// Make a toplevel a dialog of an other toplevel
+        ///
+        /// `parent` is set as the parent of `toplevel` and, if the compositor supports
+        /// this protocol (`wm_dialog` is `Some`), `toplevel` is hinted as a dialog, modal
+        /// if `modal` is `true`. Otherwise only the parent is set, the modality still
+        /// being tracked for `blocks()`.
+        ///
+        /// As for the other properties of a toplevel, the changes are applied on the next
+        /// commit of its surface. Making the toplevel a dialog before its initial commit
+        /// lets the compositor take it into account for its first configure. The protocol
+        /// does not allow a toplevel to be made a dialog twice.
+        ///
+        /// Returns an error if `wm_dialog` is dead.
+        pub fn new(
+            wm_dialog: Option<&Proxy<XdgWmDialogV1>>,
+            toplevel: &Proxy<XdgToplevel>,
+            parent: &Proxy<XdgToplevel>,
+            modal: bool,
+        ) -> Result<Dialog, ()> {
+            let dialog = match wm_dialog {
+                Some(wm_dialog) => {
+                    Some(wm_dialog.get_xdg_dialog(toplevel, |newp| newp.implement(|_, _| {}, ()))?)
+                }
+                None => None,
+            };
+            toplevel.set_parent(Some(parent));
+            if let (true, Some(dialog)) = (modal, dialog.as_ref()) {
+                dialog.set_modal();
+            }
+            Ok(Dialog {
+                toplevel: toplevel.clone(),
+                parent: parent.clone(),
+                dialog,
+                modal,
+            })
+        }
+
+        /// The toplevel of this dialog
+        pub fn toplevel(&self) -> &Proxy<XdgToplevel> {
+            &self.toplevel
+        }
+
+        /// The parent of this dialog
+        pub fn parent(&self) -> &Proxy<XdgToplevel> {
+            &self.parent
+        }
+
+        /// Whether this dialog is modal
+        pub fn is_modal(&self) -> bool {
+            self.modal
+        }
+
+        /// Change whether this dialog is modal
+        pub fn set_modal(&mut self, modal: bool) {
+            if modal == self.modal {
+                return;
+            }
+            self.modal = modal;
+            if let Some(ref dialog) = self.dialog {
+                if modal {
+                    dialog.set_modal();
+                } else {
+                    dialog.unset_modal();
+                }
+            }
+        }
+
+        /// Attach this dialog to an other parent
+        ///
+        /// Toolkits typically need this when the parent of a dialog is closed, to attach
+        /// the dialog to the parent of its former parent.
+        pub fn set_parent(&mut self, parent: &Proxy<XdgToplevel>) {
+            self.toplevel.set_parent(Some(parent));
+            self.parent = parent.clone();
+        }
+
+        /// Check whether this dialog blocks the input of a toplevel
+        ///
+        /// This is the case if the dialog is modal, its toplevel is alive, and `toplevel`
+        /// is its parent. The events of the pointer, keyboard and touch on the surface of
+        /// a blocked toplevel should then be ignored by the client.
+        pub fn blocks(&self, toplevel: &Proxy<XdgToplevel>) -> bool {
+            self.modal && self.toplevel.is_alive() && self.parent.equals(toplevel)
+        }
+
+        /// Stop using the toplevel as a dialog
+        ///
+        /// The dialog hints are removed from the toplevel and it is detached from its
+        /// parent. The toplevel itself is not destroyed, this should be called before
+        /// destroying it if it is destroyed before its parent.
+        pub fn destroy(self) {
+            if let Some(dialog) = self.dialog {
+                dialog.destroy();
+            }
+            self.toplevel.set_parent(None);
+        }
+    }
+}
+
+pub mod xdg_session_management {
+    //! Protocol for managing application sessions
+    //!
+    //! This protocol allows clients to restore the state of their toplevels from
+    //! previous executions. A client obtains a session, identified by a string the
+    //! compositor gives it in the `created` event, and adds its toplevels to it
+    //! under names of its choosing. When run again, it can ask for the same session
+    //! and for its toplevels to be restored under the same names.
+    //!
+    //! The `SessionStore` type is provided to help compositors keep track of the
+    //! sessions they gave out and of the state of the toplevels they contain.
+
+    use std::collections::{hash_map, HashMap};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    wayland_protocol_versioned!(
+        "xdg-session-management",
+        [v1],
+        [],
+        [
+            (xdg_shell, xdg_toplevel, xdg_toplevel_interface)
+        ]
+    );
+
+    /// Storage of the sessions of a compositor
+    ///
+    /// Each session is identified by a string generated by `create_session()`, and stores
+    /// a value of type `T` (typically the position and size of the window) for each
+    /// toplevel name the client used in this session.
+    ///
+    /// To restore sessions across compositor restarts, this storage can be rebuilt from a
+    /// persistent storage using `insert_session()` and `set_toplevel()`.
+    pub struct SessionStore<T> {
+        sessions: HashMap<String, HashMap<String, T>>,
+        serial: u32,
+    }
+
+    impl<T> SessionStore<T> {
+        /// Create a new empty storage
+        pub fn new() -> SessionStore<T> {
+            SessionStore {
+                sessions: HashMap::new(),
+                serial: 0,
+            }
+        }
+
+        /// Create a new session, returning its identifier
+        ///
+        /// The identifier is meant to be sent to the client in the `created` event.
+        pub fn create_session(&mut self) -> String {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            loop {
+                self.serial = self.serial.wrapping_add(1);
+                let id = format!("{:x}-{:x}", time, self.serial);
+                if !self.sessions.contains_key(&id) {
+                    self.sessions.insert(id.clone(), HashMap::new());
+                    return id;
+                }
+            }
+        }
+
+        /// Insert a session with given identifier, if it does not exist yet
+        pub fn insert_session(&mut self, id: String) {
+            self.sessions.entry(id).or_insert_with(HashMap::new);
+        }
+
+        /// Resolve the session requested by a `get_session` request
+        ///
+        /// If `requested` identifies a known session, it is returned along with `true`, and
+        /// the client should receive the `restored` event. Otherwise a new session is created
+        /// and returned along with `false`, and the client should receive the `created` event.
+        pub fn get_session(&mut self, requested: Option<&str>) -> (String, bool) {
+            match requested {
+                Some(id) if self.sessions.contains_key(id) => (id.to_owned(), true),
+                _ => (self.create_session(), false),
+            }
+        }
+
+        /// Whether a session with given identifier exists
+        pub fn has_session(&self, id: &str) -> bool {
+            self.sessions.contains_key(id)
+        }
+
+        /// Remove a session and the state of all its toplevels
+        ///
+        /// This is the expected behavior on the `remove` request of a session.
+        pub fn remove_session(&mut self, id: &str) {
+            self.sessions.remove(id);
+        }
+
+        /// Store the state of a toplevel of a session
+        ///
+        /// Does nothing if the session does not exist.
+        pub fn set_toplevel(&mut self, session: &str, name: &str, state: T) {
+            if let Some(toplevels) = self.sessions.get_mut(session) {
+                toplevels.insert(name.to_owned(), state);
+            }
+        }
+
+        /// Retrieve the stored state of a toplevel of a session
+        pub fn toplevel(&self, session: &str, name: &str) -> Option<&T> {
+            self.sessions.get(session).and_then(|toplevels| toplevels.get(name))
+        }
+
+        /// Remove the state of a toplevel of a session, returning it
+        ///
+        /// This is the expected behavior on the `remove` request of a toplevel session.
+        pub fn remove_toplevel(&mut self, session: &str, name: &str) -> Option<T> {
+            self.sessions
+                .get_mut(session)
+                .and_then(|toplevels| toplevels.remove(name))
+        }
+
+        /// Iterate over the sessions and the state of their toplevels
+        ///
+        /// This can be used to save the sessions to a persistent storage.
+        pub fn sessions(&self) -> Sessions<T> {
+            Sessions {
+                inner: self.sessions.iter(),
+            }
+        }
+    }
+
+    /// Iterator over the sessions of a `SessionStore`
+    ///
+    /// It is created by `SessionStore::sessions()`.
+    pub struct Sessions<'a, T: 'a> {
+        inner: hash_map::Iter<'a, String, HashMap<String, T>>,
+    }
+
+    impl<'a, T> Iterator for Sessions<'a, T> {
+        type Item = (&'a str, &'a HashMap<String, T>);
+
+        fn next(&mut self) -> Option<(&'a str, &'a HashMap<String, T>)> {
+            self.inner.next().map(|(id, toplevels)| (id.as_str(), toplevels))
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.inner.size_hint()
+        }
+    }
+
+    impl<T> Default for SessionStore<T> {
+        fn default() -> SessionStore<T> {
+            SessionStore::new()
+        }
+    }
+}
+
+pub mod xdg_toplevel_icon {
+    //! Protocol to assign icons to toplevels
+    //!
+    //! This protocol allows clients to set icons for their toplevel surfaces, either
+    //! using an icon name from the XDG icon theme, or from pixel data provided with
+    //! `wl_shm` buffers.
+    //!
+    //! Client-side, the `ToplevelIcon` type creates icons from raster images given as
+    //! `IconImage`s, and `apply_icon()` sets them on a toplevel, falling back to the
+    //! icon derived from the app id of the toplevel when the compositor does not
+    //! support this protocol. The sizes the compositor would like the icon to be
+    //! available in are given by the `icon_size` events of the manager.
+
+    wayland_protocol_versioned!(
+        "xdg-toplevel-icon",
+        [v1],
+        [
+            (wl_buffer, wl_buffer_interface)
+        ],
+        [
+            (xdg_shell, xdg_toplevel, xdg_toplevel_interface)
+        ]
+    );
+
+    #[cfg(feature = "client")]
+    use std::fs::File;
+    #[cfg(feature = "client")]
+    use std::io::{self, Seek, SeekFrom, Write};
+    #[cfg(feature = "client")]
+    use std::os::unix::io::AsRawFd;
+
+    #[cfg(feature = "client")]
+    use wayland_client::protocol::wl_buffer::{RequestsTrait as BufferRequests, WlBuffer};
+    #[cfg(feature = "client")]
+    use wayland_client::protocol::wl_shm::{Format, RequestsTrait as ShmRequests, WlShm};
+    #[cfg(feature = "client")]
+    use wayland_client::protocol::wl_shm_pool::RequestsTrait as PoolRequests;
+    #[cfg(feature = "client")]
+    use wayland_client::{InteriorNulError, Proxy};
+
+    #[cfg(feature = "client")]
+    use xdg_shell::client::xdg_toplevel::{RequestsTrait as ToplevelRequests, XdgToplevel};
+
+    #[cfg(feature = "client")]
+    use self::v1::client::xdg_toplevel_icon_manager_v1::{
+        RequestsTrait as ManagerRequests, XdgToplevelIconManagerV1,
+    };
+    #[cfg(feature = "client")]
+    use self::v1::client::xdg_toplevel_icon_v1::{RequestsTrait as IconRequests, XdgToplevelIconV1};
+
+    /// A raster image of an icon
+    ///
+    /// The pixels are stored in the `Argb8888` format of `wl_shm`, with premultiplied
+    /// alpha.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct IconImage {
+        size: i32,
+        scale: i32,
+        data: Vec<u8>,
+    }
+
+    impl IconImage {
+        /// Create an image from RGBA pixels
+        ///
+        /// `size` is the edge of the square image, in pixels, and `rgba` its pixels in
+        /// row order, 4 bytes each, with straight (not premultiplied) alpha. `scale` is
+        /// the scale factor the image is meant for: an image of size 64 and scale 2
+        /// provides a 32x32 icon for HiDPI outputs.
+        ///
+        /// Returns `None` if the length of `rgba` does not match `size`, if `size` or
+        /// `scale` is zero, or if they are too large to be given to the protocol.
+        pub fn from_rgba(size: u32, scale: u32, rgba: &[u8]) -> Option<IconImage> {
+            // the stride of the buffer is an i32
+            let max = i32::max_value() as u32;
+            if size == 0 || size > max / 4 || scale == 0 || scale > max {
+                return None;
+            }
+            if rgba.len() as u64 != 4 * u64::from(size) * u64::from(size) {
+                return None;
+            }
+            let premultiply = |c: u8, a: u8| ((u32::from(c) * u32::from(a) + 127) / 255) as u8;
+            let mut data = Vec::with_capacity(rgba.len());
+            for pixel in rgba.chunks(4) {
+                let (r, g, b, a) = (pixel[0], pixel[1], pixel[2], pixel[3]);
+                // Argb8888 is little-endian
+                data.push(premultiply(b, a));
+                data.push(premultiply(g, a));
+                data.push(premultiply(r, a));
+                data.push(a);
+            }
+            Some(IconImage {
+                size: size as i32,
+                scale: scale as i32,
+                data,
+            })
+        }
+
+        /// Edge of the square image, in pixels
+        pub fn size(&self) -> i32 {
+            self.size
+        }
+
+        /// Scale factor the image is meant for
+        pub fn scale(&self) -> i32 {
+            self.scale
+        }
+
+        /// Pixels of the image, in the `Argb8888` format of `wl_shm`
+        pub fn data(&self) -> &[u8] {
+            &self.data
+        }
+    }
+
+    /// A toplevel icon, with the buffers holding its images
+    ///
+    /// The protocol requires the buffers of an icon to stay alive until the icon is
+    /// destroyed, they are thus destroyed along with it by `destroy()`.
+    #[cfg(feature = "client")]
+    pub struct ToplevelIcon {
+        icon: Proxy<XdgToplevelIconV1>,
+        buffers: Vec<Proxy<WlBuffer>>,
+    }
+
+    #[cfg(feature = "client")]
+    impl ToplevelIcon {
+        /// Create an icon from an icon name and raster images
+        ///
+        /// `name` is looked up in the XDG icon theme by the compositor, which may prefer
+        /// either the name or the images, and falls back to the images if it cannot
+        /// resolve the name.
+        ///
+        /// The images are written to `file`, which is used as the shared memory of
+        /// their buffers: it must be a regular file, typically a temporary file, and
+        /// can be dropped once this function returns. Its previous content is replaced.
+        ///
+        /// Returns an error of kind `InvalidInput` if the images do not fit in a `wl_shm`
+        /// pool, whose size is an i32.
+        pub fn new(
+            manager: &Proxy<XdgToplevelIconManagerV1>,
+            shm: &Proxy<WlShm>,
+            mut file: File,
+            name: Option<&str>,
+            images: &[IconImage],
+        ) -> io::Result<ToplevelIcon> {
+            let mut pool_size = 0i32;
+            for image in images {
+                pool_size = match image.data.len() {
+                    len if len <= i32::max_value() as usize => pool_size.checked_add(len as i32),
+                    _ => None,
+                }.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The icon images are too large."))?;
+            }
+            if !images.is_empty() {
+                file.seek(SeekFrom::Start(0))?;
+                file.set_len(pool_size as u64)?;
+                for image in images {
+                    file.write_all(&image.data)?;
+                }
+                file.flush()?;
+            }
+            let icon = manager
+                .create_icon(|newp| newp.implement(|_, _| {}, ()))
+                .map_err(|()| dead_object("xdg_toplevel_icon_manager_v1"))?;
+            if let Some(name) = name {
+                if let Err(e) = icon.set_name(name) {
+                    icon.destroy();
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+                }
+            }
+            let mut buffers = Vec::with_capacity(images.len());
+            if !images.is_empty() {
+                let pool = shm
+                    .create_pool(file.as_raw_fd(), pool_size, |newp| newp.implement(|_, _| {}, ()))
+                    .map_err(|()| dead_object("wl_shm"))?;
+                let mut offset = 0;
+                for image in images {
+                    let buffer = pool
+                        .create_buffer(
+                            offset,
+                            image.size,
+                            image.size,
+                            image.size * 4,
+                            Format::Argb8888,
+                            |newp| newp.implement(|_, _| {}, ()),
+                        ).map_err(|()| dead_object("wl_shm_pool"))?;
+                    icon.add_buffer(&buffer, image.scale);
+                    buffers.push(buffer);
+                    // the offsets are bounded by the size of the pool
+                    offset += image.data.len() as i32;
+                }
+                // the buffers keep the memory of the pool alive
+                pool.destroy();
+            }
+            Ok(ToplevelIcon { icon, buffers })
+        }
+
+        /// Access the underlying icon object
+        pub fn icon(&self) -> &Proxy<XdgToplevelIconV1> {
+            &self.icon
+        }
+
+        /// Destroy the icon and its buffers
+        ///
+        /// The toplevels this icon was set on keep it.
+        pub fn destroy(self) {
+            self.icon.destroy();
+            for buffer in self.buffers {
+                buffer.destroy();
+            }
+        }
+    }
+
+    /// Set the icon of a toplevel
+    ///
+    /// If the compositor supports this protocol (`manager` is `Some`), `icon` is set on
+    /// the toplevel, or its icon is reset to its default if `icon` is `None`. Otherwise,
+    /// the app id of the toplevel is set to `app_id`, so that the compositor can use the
+    /// icon of the associated desktop entry.
+    ///
+    /// As for the other properties of a toplevel, the change is applied on the next
+    /// commit of its surface.
+    ///
+    /// Returns an error if the app id is used and contains a nul byte.
+    #[cfg(feature = "client")]
+    pub fn apply_icon(
+        manager: Option<&Proxy<XdgToplevelIconManagerV1>>,
+        toplevel: &Proxy<XdgToplevel>,
+        icon: Option<&ToplevelIcon>,
+        app_id: &str,
+    ) -> Result<(), InteriorNulError> {
+        match manager {
+            Some(manager) => {
+                manager.set_icon(toplevel, icon.map(|icon| &icon.icon));
+                Ok(())
+            }
+            None => toplevel.set_app_id(app_id),
+        }
+    }
+
+    #[cfg(feature = "client")]
+    fn dead_object(interface: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("The {} object is dead.", interface),
+        )
+    }
+}
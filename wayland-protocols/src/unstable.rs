@@ -30,6 +30,10 @@ pub mod fullscreen_shell {
 
 pub mod idle_inhibit {
     //! Screensaver inhibition protocol
+    //!
+    //! Client-side, the `IdleInhibitor` type manages the inhibitor of a surface from a
+    //! boolean, like a video player inhibiting the screensaver while it plays, and
+    //! creates it again when the surface is replaced.
 
     wayland_protocol_versioned!(
         "idle-inhibit",
@@ -37,6 +41,119 @@ pub mod idle_inhibit {
         [(wl_surface, wl_surface_interface)],
         []
     );
+
+    #[cfg(feature = "client")]
+    use wayland_client::protocol::wl_surface::WlSurface;
+    #[cfg(feature = "client")]
+    use wayland_client::Proxy;
+
+    #[cfg(feature = "client")]
+    use self::v1::client::zwp_idle_inhibit_manager_v1::{
+        RequestsTrait as ManagerRequests, ZwpIdleInhibitManagerV1,
+    };
+    #[cfg(feature = "client")]
+    use self::v1::client::zwp_idle_inhibitor_v1::{RequestsTrait as InhibitorRequests, ZwpIdleInhibitorV1};
+
+    /// The inhibition of the idle behavior of the compositor, tied to a surface
+    ///
+    /// The compositor only inhibits its idle behavior (like blanking the screen or locking
+    /// the session) while the surface is visible. The inhibitor is created once both a surface
+    /// is set and the inhibition is requested, and destroyed once either of them is unset or
+    /// this helper is dropped.
+    #[cfg(feature = "client")]
+    pub struct IdleInhibitor {
+        manager: Proxy<ZwpIdleInhibitManagerV1>,
+        surface: Option<Proxy<WlSurface>>,
+        inhibitor: Option<Proxy<ZwpIdleInhibitorV1>>,
+        requested: bool,
+    }
+
+    #[cfg(feature = "client")]
+    impl IdleInhibitor {
+        /// Create a helper without surface, the inhibition not being requested
+        pub fn new(manager: &Proxy<ZwpIdleInhibitManagerV1>) -> IdleInhibitor {
+            IdleInhibitor {
+                manager: manager.clone(),
+                surface: None,
+                inhibitor: None,
+                requested: false,
+            }
+        }
+
+        /// Request or cancel the inhibition
+        pub fn set_inhibited(&mut self, inhibited: bool) {
+            self.requested = inhibited;
+            self.update();
+        }
+
+        /// Whether the inhibition is requested
+        pub fn is_inhibited(&self) -> bool {
+            self.requested
+        }
+
+        /// Set the surface the inhibition is tied to
+        ///
+        /// This is to be called again when the surface is destroyed and created again, like
+        /// when a player switches to a fullscreen window: the inhibitor of the previous
+        /// surface is destroyed, and the inhibition is acquired again on the new one if it
+        /// is requested.
+        pub fn set_surface(&mut self, surface: Option<&Proxy<WlSurface>>) {
+            let same = match (self.surface.as_ref(), surface) {
+                (Some(current), Some(surface)) => current.equals(surface),
+                (None, None) => true,
+                _ => false,
+            };
+            if !same {
+                self.release();
+                self.surface = surface.cloned();
+            }
+            self.update();
+        }
+
+        /// Whether an inhibitor currently exists
+        ///
+        /// This is `false` while the inhibition is requested if there is no surface, if the
+        /// surface was destroyed, or if the manager is dead. It does not tell whether the
+        /// surface is visible, and thus whether the compositor actually honors it.
+        pub fn is_active(&self) -> bool {
+            let surface_alive = self.surface.as_ref().map(|s| s.is_alive()).unwrap_or(false);
+            surface_alive && self.inhibitor.as_ref().map(|i| i.is_alive()).unwrap_or(false)
+        }
+
+        // create or destroy the inhibitor to match the request and the surface
+        fn update(&mut self) {
+            if self.inhibitor.is_some() && !self.is_active() {
+                // the surface was destroyed, its inhibitor is of no use anymore
+                self.release();
+            }
+            let surface = match self.surface {
+                Some(ref surface) if self.requested && surface.is_alive() => surface.clone(),
+                _ => {
+                    self.release();
+                    return;
+                }
+            };
+            if self.inhibitor.is_none() {
+                self.inhibitor = self
+                    .manager
+                    .create_inhibitor(&surface, |newp| newp.implement(|_, _| {}, ()))
+                    .ok();
+            }
+        }
+
+        fn release(&mut self) {
+            if let Some(inhibitor) = self.inhibitor.take() {
+                inhibitor.destroy();
+            }
+        }
+    }
+
+    #[cfg(feature = "client")]
+    impl Drop for IdleInhibitor {
+        fn drop(&mut self) {
+            self.release();
+        }
+    }
 }
 
 
@@ -282,7 +399,6 @@ pub mod xdg_foreign {
     //! Protocol for exporting xdg surface handles
     //!
     //! This protocol specifies a way for making it possible to reference a surface
-    //! aOaOa
     //! of a different client. With such a reference, a client can, by using the
     //! interfaces provided by this protocol, manipulate the relationship between
     //! its own surfaces and the surface of some other client. For example, stack
@@ -302,6 +418,11 @@ pub mod xdg_foreign {
     //! surface, passing the exported surface handle to an unsandboxed process that
     //! can show a file browser dialog and stack it above the sandboxed client's
     //! surface.
+    //!
+    //! Client-side, the `ExportedSurface` and `ImportedSurface` types wrap the objects
+    //! of the second version of this protocol: the first keeps the handle given by the
+    //! compositor to the exporting client, the second uses such a handle to set the
+    //! parent of the surfaces of the importing client, and tracks its validity.
 
     wayland_protocol_versioned!(
         "xdg-foreign",
@@ -309,6 +430,141 @@ pub mod xdg_foreign {
         [(wl_surface, wl_surface_interface)],
         []
     );
+
+    #[cfg(feature = "client")]
+    use std::sync::atomic::{AtomicBool, Ordering};
+    #[cfg(feature = "client")]
+    use std::sync::{Arc, Mutex};
+
+    #[cfg(feature = "client")]
+    use wayland_client::protocol::wl_surface::WlSurface;
+    #[cfg(feature = "client")]
+    use wayland_client::{Proxy, RequestError};
+
+    #[cfg(feature = "client")]
+    use self::v2::client::zxdg_exported_v2::{
+        Event as ExportedEvent, RequestsTrait as ExportedRequests, ZxdgExportedV2,
+    };
+    #[cfg(feature = "client")]
+    use self::v2::client::zxdg_exporter_v2::{RequestsTrait as ExporterRequests, ZxdgExporterV2};
+    #[cfg(feature = "client")]
+    use self::v2::client::zxdg_imported_v2::{
+        Event as ImportedEvent, RequestsTrait as ImportedRequests, ZxdgImportedV2,
+    };
+    #[cfg(feature = "client")]
+    use self::v2::client::zxdg_importer_v2::{RequestsTrait as ImporterRequests, ZxdgImporterV2};
+
+    /// A toplevel surface exported to other clients
+    #[cfg(feature = "client")]
+    pub struct ExportedSurface {
+        exported: Proxy<ZxdgExportedV2>,
+        handle: Arc<Mutex<Option<String>>>,
+    }
+
+    #[cfg(feature = "client")]
+    impl ExportedSurface {
+        /// Export a toplevel surface
+        ///
+        /// `surface` must have the xdg_toplevel role. The compositor sends the handle of
+        /// the exported surface in response, it is available from `handle()` once the
+        /// event queue of `exporter` has dispatched it, typically after a roundtrip.
+        ///
+        /// Returns an error if `exporter` is dead.
+        pub fn new(
+            exporter: &Proxy<ZxdgExporterV2>,
+            surface: &Proxy<WlSurface>,
+        ) -> Result<ExportedSurface, ()> {
+            let handle = Arc::new(Mutex::new(None));
+            let exported = {
+                let handle = handle.clone();
+                exporter.export_toplevel(surface, move |newp| {
+                    newp.implement(
+                        move |event, _| match event {
+                            ExportedEvent::Handle { handle: h } => *handle.lock().unwrap() = Some(h),
+                        },
+                        (),
+                    )
+                })?
+            };
+            Ok(ExportedSurface { exported, handle })
+        }
+
+        /// The handle of the exported surface
+        ///
+        /// This is the string to pass out-of-band to the importing client, for example
+        /// in the `parent_window` argument of the portal requests (prefixed by
+        /// `wayland:`). Returns `None` as long as the compositor did not send it.
+        pub fn handle(&self) -> Option<String> {
+            self.handle.lock().unwrap().clone()
+        }
+
+        /// Revoke the export of the surface
+        ///
+        /// The clients which imported it are notified that their imported objects became
+        /// invalid, and the relationships they set with it are cancelled.
+        pub fn destroy(self) {
+            self.exported.destroy();
+        }
+    }
+
+    /// A toplevel surface of an other client, imported from its handle
+    #[cfg(feature = "client")]
+    pub struct ImportedSurface {
+        imported: Proxy<ZxdgImportedV2>,
+        valid: Arc<AtomicBool>,
+    }
+
+    #[cfg(feature = "client")]
+    impl ImportedSurface {
+        /// Import a toplevel surface from a handle given by the client which exported it
+        ///
+        /// If the handle is not valid, or becomes invalid later (the surface was unmapped
+        /// or its export revoked), the compositor notifies it and `is_valid()` returns
+        /// `false` afterwards.
+        ///
+        /// Returns an error if `importer` is dead, or if `handle` contains a nul byte.
+        pub fn new(importer: &Proxy<ZxdgImporterV2>, handle: String) -> Result<ImportedSurface, RequestError> {
+            let valid = Arc::new(AtomicBool::new(true));
+            let imported = {
+                let valid = valid.clone();
+                importer.import_toplevel(handle, move |newp| {
+                    newp.implement(
+                        move |event, _| match event {
+                            ImportedEvent::Destroyed => valid.store(false, Ordering::Release),
+                        },
+                        (),
+                    )
+                })?
+            };
+            Ok(ImportedSurface { imported, valid })
+        }
+
+        /// Whether the imported surface can still be used
+        ///
+        /// Once invalid, the relationships set with `set_parent_of()` are cancelled by the
+        /// compositor and this object should be destroyed.
+        pub fn is_valid(&self) -> bool {
+            self.valid.load(Ordering::Acquire) && self.imported.is_alive()
+        }
+
+        /// Set the imported surface as the parent of a toplevel surface of this client
+        ///
+        /// `surface` must have the xdg_toplevel role, it is then typically stacked above
+        /// the imported surface, as a dialog. This has no effect if the imported surface
+        /// is not valid anymore.
+        pub fn set_parent_of(&self, surface: &Proxy<WlSurface>) {
+            if self.is_valid() {
+                self.imported.set_parent_of(surface);
+            }
+        }
+
+        /// Stop using the imported surface
+        ///
+        /// The relationships set with `set_parent_of()` are cancelled.
+        pub fn destroy(self) {
+            self.imported.destroy();
+        }
+    }
 }
 
 pub mod xdg_output {
@@ -4,10 +4,15 @@ use std::io::Write;
 use common_gen::*;
 use protocol::*;
 use util::*;
-use Side;
+use {Options, Side};
 
-pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -> IOResult<()> {
-    write_prefix(&protocol, out)?;
+pub(crate) fn write_protocol_client<O: Write>(
+    mut protocol: Protocol,
+    out: &mut O,
+    options: &Options,
+) -> IOResult<()> {
+    options.apply_fd_kinds(&mut protocol);
+    write_prefix(&protocol, out, options)?;
 
     for iface in &protocol.interfaces {
         writeln!(out, "pub mod {} {{", iface.name)?;
@@ -28,13 +33,14 @@ pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -
 
         let iface_name = snake_to_camel(&iface.name);
 
-        write_enums(&iface.enums, out)?;
+        write_enums(&iface.enums, out, options, false)?;
         write_messagegroup(
             "Request",
             Side::Client,
             false,
             &iface.requests,
             out,
+            options,
             Some(|out: &mut _| messagegroup_c_addon("Request", Side::Client, false, &iface.requests, out)),
         )?;
         write_messagegroup(
@@ -43,6 +49,7 @@ pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -
             true,
             &iface.events,
             out,
+            options,
             Some(|out: &mut _| messagegroup_c_addon("Event", Side::Client, true, &iface.events, out)),
         )?;
         write_interface(
@@ -52,20 +59,27 @@ pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -
             out,
             Some(|out: &mut _| interface_c_addon(&iface.name, out)),
         )?;
-        write_client_methods(&iface_name, &iface.requests, out)?;
+        write_client_methods(&iface_name, &iface.requests, out, options)?;
 
         writeln!(out, "}}\n")?;
     }
 
+    write_version_matrix(&protocol, Side::Client, out)?;
+
     Ok(())
 }
 
-pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -> IOResult<()> {
-    write_prefix(&protocol, out)?;
+pub(crate) fn write_protocol_server<O: Write>(
+    mut protocol: Protocol,
+    out: &mut O,
+    options: &Options,
+) -> IOResult<()> {
+    options.apply_fd_kinds(&mut protocol);
+    write_prefix(&protocol, out, options)?;
 
     for iface in &protocol.interfaces {
         // display and registry are handled specially
-        if iface.name == "wl_display" || iface.name == "wl_registry" {
+        if !Side::Server.generates(&iface.name) {
             continue;
         }
 
@@ -87,13 +101,14 @@ pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -
 
         let iface_name = snake_to_camel(&iface.name);
 
-        write_enums(&iface.enums, out)?;
+        write_enums(&iface.enums, out, options, false)?;
         write_messagegroup(
             "Request",
             Side::Server,
             true,
             &iface.requests,
             out,
+            options,
             Some(|out: &mut _| messagegroup_c_addon("Request", Side::Server, true, &iface.requests, out)),
         )?;
         write_messagegroup(
@@ -102,6 +117,7 @@ pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -
             false,
             &iface.events,
             out,
+            options,
             Some(|out: &mut _| messagegroup_c_addon("Event", Side::Server, false, &iface.events, out)),
         )?;
         write_interface(
@@ -115,6 +131,8 @@ pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -
         writeln!(out, "}}\n")?;
     }
 
+    write_version_matrix(&protocol, Side::Server, out)?;
+
     Ok(())
 }
 
@@ -204,22 +222,21 @@ pub fn messagegroup_c_addon<O: Write>(
                             if a.allow_null {
                                 write!(out, "if _args[{}].o.is_null() {{ None }} else {{ Some(", j)?;
                             }
-                            if let Some(ref iface) = a.interface {
-                                write!(
+                            match a.interface {
+                                Some(ref iface) if side.generates(iface) => write!(
                                     out,
                                     "{}::<super::{}::{}>::from_c_ptr(_args[{}].o as *mut _)",
                                     side.object_name(),
                                     iface,
                                     snake_to_camel(iface),
                                     j
-                                )?;
-                            } else {
-                                write!(
+                                )?,
+                                _ => write!(
                                     out,
                                     "{}::<AnonymousObject>::from_c_ptr(_args[{}].o as *mut _)",
                                     side.object_name(),
                                     j
-                                )?;
+                                )?,
                             }
                             if a.allow_null {
                                 write!(out, ") }}")?;
@@ -325,7 +342,12 @@ pub fn messagegroup_c_addon<O: Write>(
                                 j, a.name
                             )?;
                             write!(out, "                    ")?;
-                            writeln!(out, "_args_array[{}].s = _arg_{}.map(|s| s.as_ptr()).unwrap_or(::std::ptr::null());", j, j)?;
+                            // borrow the string, it must live until the message is sent
+                            writeln!(
+                                out,
+                                "_args_array[{}].s = _arg_{}.as_ref().map(|s| s.as_ptr()).unwrap_or(::std::ptr::null());",
+                                j, j
+                            )?;
                         } else {
                             writeln!(
                                 out,
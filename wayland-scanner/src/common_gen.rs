@@ -3,9 +3,9 @@ use std::io::Write;
 
 use protocol::*;
 use util::*;
-use Side;
+use {Options, Side};
 
-pub(crate) fn write_prefix<O: Write>(protocol: &Protocol, out: &mut O) -> IOResult<()> {
+pub(crate) fn write_prefix<O: Write>(protocol: &Protocol, out: &mut O, options: &Options) -> IOResult<()> {
     writeln!(
         out,
         r#"
@@ -14,12 +14,60 @@ pub(crate) fn write_prefix<O: Write>(protocol: &Protocol, out: &mut O) -> IOResu
 //
 "#
     )?;
+    if options.version_header {
+        writeln!(out, "// wayland-scanner codegen version: {}\n", ::CODEGEN_VERSION)?;
+    }
     if let Some(ref text) = protocol.copyright {
         writeln!(out, "/*\n{}\n*/\n", text)?;
     }
     Ok(())
 }
 
+pub(crate) fn write_version_matrix<O: Write>(protocol: &Protocol, side: Side, out: &mut O) -> IOResult<()> {
+    let interfaces = protocol
+        .interfaces
+        .iter()
+        .filter(|iface| side.generates(&iface.name))
+        .collect::<Vec<_>>();
+    writeln!(
+        out,
+        "/// The minimum versions of the interfaces supporting the messages and enum entries of this protocol"
+    )?;
+    writeln!(out, "pub const VERSION_MATRIX: VersionMatrix = VersionMatrix {{")?;
+    for &(field, requests) in &[("requests", true), ("events", false)] {
+        writeln!(out, "    {}: &[", field)?;
+        for iface in &interfaces {
+            let messages = if requests { &iface.requests } else { &iface.events };
+            for msg in messages {
+                writeln!(
+                    out,
+                    "        MessageVersion {{ interface: \"{}\", name: \"{}\", since: {} }},",
+                    iface.name, msg.name, msg.since
+                )?;
+            }
+        }
+        writeln!(out, "    ],")?;
+    }
+    writeln!(out, "    enum_entries: &[")?;
+    for iface in &interfaces {
+        for enu in &iface.enums {
+            for entry in &enu.entries {
+                writeln!(
+                    out,
+                    "        EnumEntryVersion {{ interface: \"{}\", enumeration: \"{}\", entry: \"{}\", since: {} }},",
+                    iface.name,
+                    enu.name,
+                    entry.name,
+                    ::std::cmp::max(enu.since, entry.since)
+                )?;
+            }
+        }
+    }
+    writeln!(out, "    ],")?;
+    writeln!(out, "}};")?;
+    Ok(())
+}
+
 pub(crate) fn write_interface<O: Write, F: FnOnce(&mut O) -> IOResult<()>>(
     name: &str,
     low_name: &str,
@@ -55,12 +103,22 @@ pub(crate) fn write_messagegroup<O: Write, F: FnOnce(&mut O) -> IOResult<()>>(
     receiver: bool,
     messages: &[Message],
     out: &mut O,
+    options: &Options,
     addon: Option<F>,
 ) -> IOResult<()> {
     /*
      * Enum definition
      */
 
+    // objects and file descriptors cannot be serialized, so only derive for messages without any
+    let serializable = messages.iter().all(|m| {
+        m.args
+            .iter()
+            .all(|a| a.typ != Type::Object && a.typ != Type::NewId && a.typ != Type::Fd)
+    });
+    if options.serde && serializable {
+        writeln!(out, "    #[derive(Serialize, Deserialize)]")?;
+    }
     writeln!(out, "    pub enum {} {{", name)?;
     for m in messages {
         if let Some((ref short, ref long)) = m.description {
@@ -83,8 +141,12 @@ pub(crate) fn write_messagegroup<O: Write, F: FnOnce(&mut O) -> IOResult<()>>(
 
         write!(out, "        {}", snake_to_camel(&m.name))?;
         if m.args.len() > 0 {
-            write!(out, " {{")?;
+            writeln!(out, " {{")?;
             for a in &m.args {
+                if let Some(ref summary) = a.summary {
+                    writeln!(out, "            /// {}", summary)?;
+                }
+                write!(out, "            ")?;
                 write!(out, "{}: ", a.name)?;
                 if a.allow_null {
                     write!(out, "Option<")?;
@@ -99,19 +161,16 @@ pub(crate) fn write_messagegroup<O: Write, F: FnOnce(&mut O) -> IOResult<()>>(
                         Type::String => write!(out, "String")?,
                         Type::Array => write!(out, "Vec<u8>")?,
                         Type::Fd => write!(out, "::std::os::unix::io::RawFd")?,
-                        Type::Object => {
-                            if let Some(ref iface) = a.interface {
-                                write!(
-                                    out,
-                                    "{}<super::{}::{}>",
-                                    side.object_name(),
-                                    iface,
-                                    snake_to_camel(iface)
-                                )?;
-                            } else {
-                                write!(out, "{}<AnonymousObject>", side.object_name())?;
-                            }
-                        }
+                        Type::Object => match a.interface {
+                            Some(ref iface) if side.generates(iface) => write!(
+                                out,
+                                "{}<super::{}::{}>",
+                                side.object_name(),
+                                iface,
+                                snake_to_camel(iface)
+                            )?,
+                            _ => write!(out, "{}<AnonymousObject>", side.object_name())?,
+                        },
                         Type::NewId => {
                             if let Some(ref iface) = a.interface {
                                 write!(
@@ -138,9 +197,9 @@ pub(crate) fn write_messagegroup<O: Write, F: FnOnce(&mut O) -> IOResult<()>>(
                 if a.allow_null {
                     write!(out, ">")?;
                 }
-                write!(out, ", ")?;
+                writeln!(out, ",")?;
             }
-            write!(out, "}}")?;
+            write!(out, "        }}")?;
         }
         writeln!(out, ",")?
     }
@@ -166,7 +225,26 @@ pub(crate) fn write_messagegroup<O: Write, F: FnOnce(&mut O) -> IOResult<()>>(
                 arg.typ.common_type()
             )?;
         }
-        writeln!(out, "                ]")?;
+        writeln!(out, "                ],")?;
+        writeln!(
+            out,
+            "                destructor: {},",
+            msg.typ == Some(Type::Destructor)
+        )?;
+        let fds = msg.args.iter().filter(|a| a.typ == Type::Fd).collect::<Vec<_>>();
+        if fds.iter().any(|a| a.fd_kind.is_some()) {
+            writeln!(out, "                fd_kinds: &[")?;
+            for arg in fds {
+                writeln!(
+                    out,
+                    "                    super::FdKind::{},",
+                    arg.fd_kind.unwrap_or("Any")
+                )?;
+            }
+            writeln!(out, "                ],")?;
+        } else {
+            writeln!(out, "                fd_kinds: &[],")?;
+        }
         writeln!(out, "            }},")?;
     }
     writeln!(out, "        ];")?;
@@ -482,7 +560,12 @@ pub(crate) fn write_messagegroup<O: Write, F: FnOnce(&mut O) -> IOResult<()>>(
     Ok(())
 }
 
-pub(crate) fn write_enums<O: Write>(enums: &[Enum], out: &mut O) -> IOResult<()> {
+pub(crate) fn write_enums<O: Write>(
+    enums: &[Enum],
+    out: &mut O,
+    options: &Options,
+    no_std: bool,
+) -> IOResult<()> {
     // generate contents
     for enu in enums {
         if enu.bitfield {
@@ -490,6 +573,9 @@ pub(crate) fn write_enums<O: Write>(enums: &[Enum], out: &mut O) -> IOResult<()>
             if let Some((ref short, ref long)) = enu.description {
                 write_doc(Some(short), long, false, out, 2)?;
             }
+            if options.serde {
+                writeln!(out, "        #[derive(Serialize, Deserialize)]")?;
+            }
             writeln!(out, "        pub struct {}: u32 {{", snake_to_camel(&enu.name))?;
             for entry in &enu.entries {
                 if let Some((ref short, ref long)) = entry.description {
@@ -512,6 +598,10 @@ pub(crate) fn write_enums<O: Write>(enums: &[Enum], out: &mut O) -> IOResult<()>
             writeln!(out, "        }}")?;
             writeln!(out, "    }}")?;
             writeln!(out, "    impl {} {{", snake_to_camel(&enu.name))?;
+            writeln!(
+                out,
+                "        /// Create a set of flags from its raw value, ignoring the unknown bits"
+            )?;
             writeln!(
                 out,
                 "        pub fn from_raw(n: u32) -> Option<{}> {{",
@@ -524,8 +614,8 @@ pub(crate) fn write_enums<O: Write>(enums: &[Enum], out: &mut O) -> IOResult<()>
             )?;
             writeln!(
                 out,
-                r#"
-        }}
+                r#"        }}
+        /// The raw value of this set of flags
         pub fn to_raw(&self) -> u32 {{
             self.bits()
         }}
@@ -541,8 +631,9 @@ pub(crate) fn write_enums<O: Write>(enums: &[Enum], out: &mut O) -> IOResult<()>
                 out,
                 r#"
     #[repr(u32)]
-    #[derive(Copy,Clone,Debug,PartialEq)]
+    #[derive(Copy,Clone,Debug,PartialEq{})]
     pub enum {} {{"#,
+                if options.serde { ",Serialize,Deserialize" } else { "" },
                 snake_to_camel(&enu.name)
             )?;
             for entry in &enu.entries {
@@ -566,6 +657,10 @@ pub(crate) fn write_enums<O: Write>(enums: &[Enum], out: &mut O) -> IOResult<()>
             writeln!(out, "    }}")?;
 
             writeln!(out, "    impl {} {{", snake_to_camel(&enu.name))?;
+            writeln!(
+                out,
+                "        /// The entry of this enum with a given raw value, if any"
+            )?;
             writeln!(
                 out,
                 "        pub fn from_raw(n: u32) -> Option<{}> {{",
@@ -592,12 +687,74 @@ pub(crate) fn write_enums<O: Write>(enums: &[Enum], out: &mut O) -> IOResult<()>
                 _ => Option::None
             }}
         }}
+        /// The raw value of this entry
         pub fn to_raw(&self) -> u32 {{
             *self as u32
         }}
     }}
 "#
             )?;
+
+            // error enums can be used directly to post protocol errors, and are described
+            // by the summaries of their entries
+            if enu.name == "error" {
+                writeln!(
+                    out,
+                    r#"    impl From<Error> for u32 {{
+        fn from(err: Error) -> u32 {{
+            err.to_raw()
+        }}
+    }}
+    impl Error {{
+        /// The summary of this error, from the protocol description
+        pub fn summary(&self) -> &'static str {{
+            match *self {{"#
+                )?;
+                for entry in &enu.entries {
+                    let summary = entry
+                        .summary
+                        .as_ref()
+                        .or_else(|| entry.description.as_ref().map(|description| &description.0))
+                        .map(|summary| summary.split_whitespace().collect::<Vec<_>>().join(" "))
+                        .unwrap_or_else(|| entry.name.replace('_', " "));
+                    writeln!(
+                        out,
+                        "                Error::{}{} => {:?},",
+                        if entry.name.chars().next().unwrap().is_numeric() {
+                            "_"
+                        } else {
+                            ""
+                        },
+                        snake_to_camel(&entry.name),
+                        summary
+                    )?;
+                }
+                // without std, there is no Error trait to implement
+                let fmt = if no_std { "::core::fmt" } else { "::std::fmt" };
+                writeln!(
+                    out,
+                    r#"            }}
+        }}
+    }}
+    impl {fmt}::Display for Error {{
+        fn fmt(&self, f: &mut {fmt}::Formatter) -> {fmt}::Result {{
+            f.write_str(self.summary())
+        }}
+    }}"#,
+                    fmt = fmt
+                )?;
+                if !no_std {
+                    writeln!(
+                        out,
+                        r#"    impl ::std::error::Error for Error {{
+        fn description(&self) -> &str {{
+            self.summary()
+        }}
+    }}"#
+                    )?;
+                }
+                writeln!(out)?;
+            }
         }
     }
     Ok(())
@@ -648,93 +805,165 @@ pub fn print_method_prototype<'a, O: Write>(
         }
     }
 
-    // method start
+    // method start, the string arguments take anything convertible into a `Cow<str>`
+    let mut generics = msg
+        .args
+        .iter()
+        .enumerate()
+        .filter(|&(_, arg)| arg.typ == Type::String)
+        .map(|(i, _)| format!("S{}: Into<::std::borrow::Cow<'s, str>>", i))
+        .collect::<Vec<_>>();
+    if !generics.is_empty() {
+        generics.insert(0, "'s".into());
+    }
     match newid {
-        Some(arg) => if arg.interface.is_none() {
-            write!(
-                out,
-                "        fn {}{}<T: Interface, F>(&self, version: u32",
-                if is_keyword(&msg.name) { "_" } else { "" },
-                msg.name,
-            )?;
-        } else {
-            write!(
-                out,
-                "        fn {}{}<F>(&self",
-                if is_keyword(&msg.name) { "_" } else { "" },
-                msg.name
-            )?;
-        },
-        _ => {
-            write!(
-                out,
-                "        fn {}{}(&self",
-                if is_keyword(&msg.name) { "_" } else { "" },
-                msg.name
-            )?;
+        Some(arg) if arg.interface.is_none() => generics.extend(vec!["T: Interface".into(), "F".into()]),
+        Some(_) => generics.push("F".into()),
+        None => {}
+    }
+    write!(
+        out,
+        "        fn {}{}",
+        if is_keyword(&msg.name) { "_" } else { "" },
+        msg.name
+    )?;
+    if !generics.is_empty() {
+        write!(out, "<{}>", generics.join(", "))?;
+    }
+    write!(out, "(&self")?;
+    if let Some(arg) = newid {
+        if arg.interface.is_none() {
+            write!(out, ", version: u32")?;
         }
     }
 
     // print args
-    for arg in &msg.args {
-        write!(
-            out,
-            ", {}{}: {}{}{}",
-            if is_keyword(&arg.name) { "_" } else { "" },
-            arg.name,
-            if arg.allow_null { "Option<" } else { "" },
-            if let Some(ref name) = arg.enum_ {
-                dotted_to_relname(name)
+    for (i, arg) in msg.args.iter().enumerate() {
+        let typ = if arg.typ == Type::String && arg.enum_.is_none() {
+            if arg.allow_null {
+                Some(format!("Option<S{}>", i))
             } else {
-                match arg.typ {
-                    Type::Object => arg
-                        .interface
-                        .as_ref()
-                        .map(|s| format!("&Proxy<super::{}::{}>", s, snake_to_camel(s)))
-                        .unwrap_or(format!("&Proxy<super::AnonymousObject>")),
-                    Type::NewId => {
-                        // client-side, the return-type handles that
-                        continue;
-                    }
-                    _ => arg.typ.rust_type().into(),
-                }
-            },
-            if arg.allow_null { ">" } else { "" }
-        )?;
+                Some(format!("S{}", i))
+            }
+        } else {
+            // client-side, the return-type handles the new_id
+            request_arg_type(arg, "")
+        };
+        if let Some(typ) = typ {
+            write!(out, ", {}{}: {}", if is_keyword(&arg.name) { "_" } else { "" }, arg.name, typ)?;
+        }
     }
     if newid.is_some() {
         write!(out, ", implementor: F")?;
     }
     write!(out, ") ->")?;
 
-    // return type and bound
+    write_request_return_type(msg, newid, out)?;
+
+    Ok(newid)
+}
+
+// the type of an argument of a request method, the references having the given lifetime
+fn request_arg_type(arg: &Arg, lifetime: &str) -> Option<String> {
+    let typ = if let Some(ref name) = arg.enum_ {
+        dotted_to_relname(name)
+    } else {
+        match arg.typ {
+            Type::Object => arg
+                .interface
+                .as_ref()
+                .map(|s| format!("&{}Proxy<super::{}::{}>", lifetime, s, snake_to_camel(s)))
+                .unwrap_or(format!("&{}Proxy<super::AnonymousObject>", lifetime)),
+            Type::NewId => return None,
+            Type::String => format!("&{}str", lifetime),
+            _ => arg.typ.rust_type().into(),
+        }
+    };
+    if arg.allow_null {
+        Some(format!("Option<{}>", typ))
+    } else {
+        Some(typ)
+    }
+}
+
+fn write_request_return_type<O: Write>(msg: &Message, newid: Option<&Arg>, out: &mut O) -> IOResult<()> {
+    // return type and bound, the creation of an object can only fail if the proxy is dead,
+    // or if a string argument is invalid
+    let error = if has_string_args(msg) { "super::RequestError" } else { "()" };
     if let Some(ref arg) = newid {
         match arg.interface {
             Some(ref iface) => {
                 write!(
                     out,
-                    "Result<Proxy<super::{module}::{name}>, ()>
+                    "Result<Proxy<super::{module}::{name}>, {error}>
             where F: FnOnce(NewProxy<super::{module}::{name}>) -> Proxy<super::{module}::{name}>",
                     module = iface,
-                    name = snake_to_camel(iface)
+                    name = snake_to_camel(iface),
+                    error = error
                 )?;
             }
             None => {
                 write!(
                     out,
-                    "Result<Proxy<T>, ()>
-            where F: FnOnce(NewProxy<T>) -> Proxy<T>"
+                    "Result<Proxy<T>, {}>
+            where F: FnOnce(NewProxy<T>) -> Proxy<T>",
+                    error
                 )?;
             }
         }
+    } else if has_string_args(msg) {
+        write!(out, "Result<(), super::InteriorNulError>")?;
     } else {
         write!(out, "()")?;
     }
 
-    Ok(newid)
+    Ok(())
+}
+
+// list the summaries of the arguments of a request method, the new_id ones are
+// replaced by the implementor of the created object
+fn write_args_doc<O: Write>(msg: &Message, out: &mut O) -> IOResult<()> {
+    let args = msg
+        .args
+        .iter()
+        .filter(|arg| arg.typ != Type::NewId)
+        .filter_map(|arg| arg.summary.as_ref().map(|summary| (&arg.name, summary)))
+        .collect::<Vec<_>>();
+    if args.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "        ///\n        /// Arguments:\n        ///")?;
+    for (name, summary) in args {
+        writeln!(out, "        /// - `{}`: {}", name, summary)?;
+    }
+    Ok(())
 }
 
-pub(crate) fn write_client_methods<O: Write>(name: &str, messages: &[Message], out: &mut O) -> IOResult<()> {
+fn has_string_args(msg: &Message) -> bool {
+    msg.args.iter().any(|arg| arg.typ == Type::String)
+}
+
+// the requests which get a builder with the `request_builders` option
+fn has_builder(msg: &Message) -> bool {
+    msg.args.iter().filter(|arg| arg.typ != Type::NewId).count() >= 4
+}
+
+fn builder_name(msg: &Message) -> String {
+    format!("{}RequestBuilder", snake_to_camel(&msg.name))
+}
+
+pub(crate) fn write_client_methods<O: Write>(
+    name: &str,
+    messages: &[Message],
+    out: &mut O,
+    options: &Options,
+) -> IOResult<()> {
+    let builders = if options.request_builders {
+        messages.iter().filter(|msg| has_builder(msg)).collect()
+    } else {
+        Vec::new()
+    };
+
     writeln!(out, "    pub trait RequestsTrait {{")?;
     for msg in messages {
         if let Some((ref short, ref long)) = msg.description {
@@ -753,9 +982,24 @@ pub(crate) fn write_client_methods<O: Write>(name: &str, messages: &[Message], o
                 msg.since
             )?;
         }
+        write_args_doc(msg, out)?;
         print_method_prototype(name, &msg, out)?;
         writeln!(out, ";")?;
     }
+    for msg in &builders {
+        writeln!(
+            out,
+            "        /// Build a `{}` request with named arguments, see `{}`",
+            msg.name,
+            builder_name(msg)
+        )?;
+        writeln!(
+            out,
+            "        fn {}_builder<'a>(&'a self) -> {}<'a>;",
+            msg.name,
+            builder_name(msg)
+        )?;
+    }
     writeln!(out, "    }}\n")?;
 
     writeln!(out, "    impl RequestsTrait for Proxy<{}> {{", name)?;
@@ -789,6 +1033,17 @@ pub(crate) fn write_client_methods<O: Write>(name: &str, messages: &[Message], o
                     } else {
                         writeln!(out, "{0}: {0}.clone(),", a.name)?;
                     }
+                } else if a.typ == Type::String {
+                    // `RequestError` converts from `InteriorNulError` for the creating requests
+                    if a.allow_null {
+                        writeln!(
+                            out,
+                            "{0}: match {0} {{ Some(s) => Some(super::string_argument(s)?), None => None }},",
+                            a.name
+                        )?;
+                    } else {
+                        writeln!(out, "{0}: super::string_argument({0})?,", a.name)?;
+                    }
                 } else {
                     writeln!(out, "{0}: {0},", a.name)?;
                 }
@@ -796,22 +1051,146 @@ pub(crate) fn write_client_methods<O: Write>(name: &str, messages: &[Message], o
             write!(out, "            }}")?;
         }
         writeln!(out, ";")?;
+        let map_err = if has_string_args(msg) {
+            ".map_err(|()| super::RequestError::DeadProxy)"
+        } else {
+            ""
+        };
         match return_type {
             Some(ret_type) if ret_type.interface.is_none() => {
                 writeln!(
                     out,
-                    "            self.send_constructor(msg, implementor, Some(version))"
+                    "            self.send_constructor(msg, implementor, Some(version)){}",
+                    map_err
                 )?;
             }
             Some(_) => {
-                writeln!(out, "            self.send_constructor(msg, implementor, None)")?;
+                writeln!(
+                    out,
+                    "            self.send_constructor(msg, implementor, None){}",
+                    map_err
+                )?;
             }
             None => {
                 writeln!(out, "            self.send(msg);")?;
+                if has_string_args(msg) {
+                    writeln!(out, "            Ok(())")?;
+                }
             }
         }
         writeln!(out, "        }}\n")?;
     }
+    for msg in &builders {
+        writeln!(
+            out,
+            "        fn {}_builder<'a>(&'a self) -> {}<'a> {{",
+            msg.name,
+            builder_name(msg)
+        )?;
+        writeln!(out, "            {} {{", builder_name(msg))?;
+        writeln!(out, "                proxy: self,")?;
+        for arg in msg.args.iter().filter(|arg| arg.typ != Type::NewId) {
+            let field = if is_keyword(&arg.name) { "_" } else { "" };
+            writeln!(out, "                {}{}: None,", field, arg.name)?;
+        }
+        writeln!(out, "            }}")?;
+        writeln!(out, "        }}\n")?;
+    }
+    writeln!(out, "    }}")?;
+
+    for msg in builders {
+        write_request_builder(name, msg, out)?;
+    }
+
+    Ok(())
+}
+
+// a builder struct with a setter for each argument, and a `send()` method calling the
+// method of the request
+fn write_request_builder<O: Write>(name: &str, msg: &Message, out: &mut O) -> IOResult<()> {
+    let args = msg
+        .args
+        .iter()
+        .filter(|arg| arg.typ != Type::NewId)
+        .map(|arg| {
+            let field = format!("{}{}", if is_keyword(&arg.name) { "_" } else { "" }, arg.name);
+            (arg, field, request_arg_type(arg, "'a ").unwrap())
+        }).collect::<Vec<_>>();
+    let newid = msg.args.iter().find(|arg| arg.typ == Type::NewId);
+
+    writeln!(
+        out,
+        "
+    /// A `{}` request with named arguments
+    ///
+    /// It is created by `RequestsTrait::{}_builder()`, and sent by `send()`. All the
+    /// arguments must be set before, except the nullable ones which default to `None`.",
+        msg.name, msg.name
+    )?;
+    writeln!(out, "    pub struct {}<'a> {{", builder_name(msg))?;
+    writeln!(out, "        proxy: &'a Proxy<{}>,", name)?;
+    for &(arg, ref field, ref typ) in &args {
+        if arg.allow_null {
+            writeln!(out, "        {}: {},", field, typ)?;
+        } else {
+            writeln!(out, "        {}: Option<{}>,", field, typ)?;
+        }
+    }
+    writeln!(out, "    }}\n")?;
+
+    writeln!(out, "    impl<'a> {}<'a> {{", builder_name(msg))?;
+    for &(arg, ref field, ref typ) in &args {
+        match arg.summary {
+            Some(ref summary) => writeln!(out, "        /// Set `{}`: {}", arg.name, summary)?,
+            None => writeln!(out, "        /// Set `{}`", arg.name)?,
+        }
+        writeln!(out, "        pub fn {0}(mut self, {0}: {1}) -> Self {{", field, typ)?;
+        if arg.allow_null {
+            writeln!(out, "            self.{0} = {0};", field)?;
+        } else {
+            writeln!(out, "            self.{0} = Some({0});", field)?;
+        }
+        writeln!(out, "            self\n        }}\n")?;
+    }
+
+    writeln!(out, "        /// Send the request\n        ///")?;
+    writeln!(out, "        /// Panics if an argument was not set.")?;
+    match newid {
+        Some(arg) if arg.interface.is_none() => {
+            write!(out, "        pub fn send<T: Interface, F>(self, version: u32, implementor: F) ->")?
+        }
+        Some(_) => write!(out, "        pub fn send<F>(self, implementor: F) ->")?,
+        None => write!(out, "        pub fn send(self) ->")?,
+    }
+    write_request_return_type(msg, newid, out)?;
+    writeln!(out, " {{")?;
+    write!(
+        out,
+        "            self.proxy.{}{}(",
+        if is_keyword(&msg.name) { "_" } else { "" },
+        msg.name
+    )?;
+    let mut params = args
+        .iter()
+        .map(|&(arg, ref field, _)| {
+            if arg.allow_null {
+                format!("self.{}", field)
+            } else {
+                format!(
+                    "self.{}.expect(\"missing argument `{}` of request `{}`\")",
+                    field, arg.name, msg.name
+                )
+            }
+        }).collect::<Vec<_>>();
+    match newid {
+        Some(arg) if arg.interface.is_none() => params.extend(vec!["version".into(), "implementor".into()]),
+        Some(_) => params.push("implementor".into()),
+        None => {}
+    }
+    for (i, param) in params.iter().enumerate() {
+        write!(out, "\n                {}{}", param, if i + 1 < params.len() { "," } else { "" })?;
+    }
+    writeln!(out, "\n            )\n        }}")?;
     writeln!(out, "    }}")?;
 
     Ok(())
@@ -90,6 +90,34 @@
 //!     }
 //! }
 //! ```
+//!
+//! ## Wire serialization without `std`
+//!
+//! `generate_wire_code` generates, for each interface, a `Request` and an `Event` enum
+//! which can be parsed from and serialized to the words of the wire protocol. This code
+//! does not depend on `wayland_client` or `wayland_server`, and only uses `core` and
+//! `alloc`: it can be used by `no_std` environments, which handle the socket themselves.
+//! The crate including it needs `extern crate alloc;`, which requires rust 1.36, and
+//! `#[macro_use] extern crate bitflags;` if the protocol has bitfield enums. The enum arguments are kept as their
+//! raw `u32` values.
+//!
+//! ## Stability of the generated code
+//!
+//! The code generated by this crate is identified by `CODEGEN_VERSION`. For a given
+//! protocol file and set of `Options`, all releases of the scanner sharing the same
+//! `CODEGEN_VERSION` produce byte-for-byte identical output. Any change to the generated
+//! code, even a cosmetic one, comes with an increment of `CODEGEN_VERSION`.
+//!
+//! The output only depends on the contents of the protocol file and on the `Options`: it
+//! contains no timestamp nor path, follows the order of the interfaces, messages and enums
+//! in the protocol file, and the line endings of this file are normalized. Generating the
+//! code twice, or on different platforms, gives identical files, which build caches and
+//! diffs of vendored code can rely on.
+//!
+//! If you vendor the generated code rather than generating it from a build script, you
+//! can enable the `version_header` option to record this version at the top of the
+//! generated files: regenerating them is then a no-op as long as this version does not
+//! change, and any diff after a bump reflects an actual change of the generated code.
 
 #![warn(missing_docs)]
 
@@ -102,20 +130,43 @@ use std::path::Path;
 mod c_code_gen;
 mod c_interface_gen;
 mod common_gen;
+mod options;
 mod parse;
-mod protocol;
+pub mod protocol;
 mod rust_code_gen;
 mod side;
 mod util;
+mod wire_code_gen;
 
+pub use options::{FdKindHint, Options};
+pub use parse::ParseError;
 pub use side::Side;
 
+/// Version of the generated code
+///
+/// See this crate toplevel documentation for the stability guarantees attached to it.
+pub const CODEGEN_VERSION: u32 = 12;
+
 fn load_xml<P: AsRef<Path>>(prot: P) -> protocol::Protocol {
     let pfile = File::open(prot.as_ref()).expect(&format!(
         "Unable to open protocol file `{}`.",
         prot.as_ref().display()
     ));
-    parse::parse_stream(pfile)
+    parse(pfile)
+}
+
+fn parse<R: Read>(source: R) -> protocol::Protocol {
+    parse::parse_stream(source).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Parse a protocol file
+///
+/// This gives the description of the protocol the code generators work from, which
+/// lets other tools, like the `dynamic` module of `wayland-commons`, handle protocols
+/// without reimplementing the parsing of their XML files. Like the code generators,
+/// this marks the `done` event of `wl_callback` as a destructor.
+pub fn parse_protocol<R: Read>(source: R) -> Result<protocol::Protocol, ParseError> {
+    parse::parse_stream(source)
 }
 
 /// Generate the interfaces for a protocol
@@ -149,6 +200,18 @@ pub fn generate_c_interfaces<P1: AsRef<Path>, P2: AsRef<Path>>(protocol: P1, tar
 /// - `target`: the path of the file to store the code in.
 /// - `side`: the side (client or server) to generate code for.
 pub fn generate_rust_code<P1: AsRef<Path>, P2: AsRef<Path>>(prot: P1, target: P2, side: Side) {
+    generate_rust_code_with_options(prot, target, side, Options::default())
+}
+
+/// Generate the code for a protocol using the Rust implementation, with custom options
+///
+/// Like `generate_rust_code`, but the generated code is customized by `options`.
+pub fn generate_rust_code_with_options<P1: AsRef<Path>, P2: AsRef<Path>>(
+    prot: P1,
+    target: P2,
+    side: Side,
+    options: Options,
+) {
     let protocol = load_xml(prot);
     let mut out = OpenOptions::new()
         .write(true)
@@ -157,8 +220,8 @@ pub fn generate_rust_code<P1: AsRef<Path>, P2: AsRef<Path>>(prot: P1, target: P2
         .open(target)
         .unwrap();
     match side {
-        Side::Client => rust_code_gen::write_protocol_client(protocol, &mut out).unwrap(),
-        Side::Server => rust_code_gen::write_protocol_server(protocol, &mut out).unwrap(),
+        Side::Client => rust_code_gen::write_protocol_client(protocol, &mut out, &options).unwrap(),
+        Side::Server => rust_code_gen::write_protocol_server(protocol, &mut out, &options).unwrap(),
     }
 }
 
@@ -173,6 +236,18 @@ pub fn generate_rust_code<P1: AsRef<Path>, P2: AsRef<Path>>(prot: P1, target: P2
 /// - `target`: the path of the file to store the code in.
 /// - `side`: the side (client or server) to generate code for.
 pub fn generate_c_code<P1: AsRef<Path>, P2: AsRef<Path>>(prot: P1, target: P2, side: Side) {
+    generate_c_code_with_options(prot, target, side, Options::default())
+}
+
+/// Generate the code for a protocol using the C system libs, with custom options
+///
+/// Like `generate_c_code`, but the generated code is customized by `options`.
+pub fn generate_c_code_with_options<P1: AsRef<Path>, P2: AsRef<Path>>(
+    prot: P1,
+    target: P2,
+    side: Side,
+    options: Options,
+) {
     let protocol = load_xml(prot);
     let mut out = OpenOptions::new()
         .write(true)
@@ -181,11 +256,31 @@ pub fn generate_c_code<P1: AsRef<Path>, P2: AsRef<Path>>(prot: P1, target: P2, s
         .open(target)
         .unwrap();
     match side {
-        Side::Client => c_code_gen::write_protocol_client(protocol, &mut out).unwrap(),
-        Side::Server => c_code_gen::write_protocol_server(protocol, &mut out).unwrap(),
+        Side::Client => c_code_gen::write_protocol_client(protocol, &mut out, &options).unwrap(),
+        Side::Server => c_code_gen::write_protocol_server(protocol, &mut out, &options).unwrap(),
     }
 }
 
+/// Generate the wire serialization code of a protocol, without dependencies on `std`
+///
+/// See this crate toplevel documentation for details.
+///
+/// Args:
+///
+/// - `protocol`: a path to the XML file describing the protocol, absolute or relative to
+///   the build script using this function.
+/// - `target`: the path of the file to store the code in.
+pub fn generate_wire_code<P1: AsRef<Path>, P2: AsRef<Path>>(prot: P1, target: P2) {
+    let protocol = load_xml(prot);
+    let mut out = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(target)
+        .unwrap();
+    wire_code_gen::write_protocol_wire(protocol, &mut out).unwrap()
+}
+
 /// Generate the interfaces for a protocol from/to IO streams
 ///
 /// Like `generate_c_interfaces`, but takes IO Streams directly rather than filenames
@@ -195,7 +290,7 @@ pub fn generate_c_code<P1: AsRef<Path>, P2: AsRef<Path>>(prot: P1, target: P2, s
 /// - `protocol`: an object `Read`-able containing the XML protocol file
 /// - `target`: a `Write`-able object to which the generated code will be outputed to
 pub fn generate_c_interfaces_streams<P1: Read, P2: Write>(protocol: P1, target: &mut P2) {
-    let protocol = parse::parse_stream(protocol);
+    let protocol = parse(protocol);
     c_interface_gen::generate_interfaces(protocol, target).unwrap();
 }
 
@@ -209,10 +304,22 @@ pub fn generate_c_interfaces_streams<P1: Read, P2: Write>(protocol: P1, target:
 /// - `target`: a `Write`-able object to which the generated code will be outputed to
 /// - `side`: the side (client or server) to generate code for.
 pub fn generate_rust_code_streams<P1: Read, P2: Write>(protocol: P1, target: &mut P2, side: Side) {
-    let protocol = parse::parse_stream(protocol);
+    generate_rust_code_streams_with_options(protocol, target, side, Options::default())
+}
+
+/// Generate the code for a protocol from/to IO streams using the rust implementation, with custom options
+///
+/// Like `generate_rust_code_streams`, but the generated code is customized by `options`.
+pub fn generate_rust_code_streams_with_options<P1: Read, P2: Write>(
+    protocol: P1,
+    target: &mut P2,
+    side: Side,
+    options: Options,
+) {
+    let protocol = parse(protocol);
     match side {
-        Side::Client => rust_code_gen::write_protocol_client(protocol, target).unwrap(),
-        Side::Server => rust_code_gen::write_protocol_server(protocol, target).unwrap(),
+        Side::Client => rust_code_gen::write_protocol_client(protocol, target, &options).unwrap(),
+        Side::Server => rust_code_gen::write_protocol_server(protocol, target, &options).unwrap(),
     }
 }
 
@@ -226,9 +333,34 @@ pub fn generate_rust_code_streams<P1: Read, P2: Write>(protocol: P1, target: &mu
 /// - `target`: a `Write`-able object to which the generated code will be outputed to
 /// - `side`: the side (client or server) to generate code for.
 pub fn generate_c_code_streams<P1: Read, P2: Write>(protocol: P1, target: &mut P2, side: Side) {
-    let protocol = parse::parse_stream(protocol);
+    generate_c_code_streams_with_options(protocol, target, side, Options::default())
+}
+
+/// Generate the code for a protocol from/to IO streams using the C system libs, with custom options
+///
+/// Like `generate_c_code_streams`, but the generated code is customized by `options`.
+pub fn generate_c_code_streams_with_options<P1: Read, P2: Write>(
+    protocol: P1,
+    target: &mut P2,
+    side: Side,
+    options: Options,
+) {
+    let protocol = parse(protocol);
     match side {
-        Side::Client => c_code_gen::write_protocol_client(protocol, target).unwrap(),
-        Side::Server => c_code_gen::write_protocol_server(protocol, target).unwrap(),
+        Side::Client => c_code_gen::write_protocol_client(protocol, target, &options).unwrap(),
+        Side::Server => c_code_gen::write_protocol_server(protocol, target, &options).unwrap(),
     }
 }
+
+/// Generate the wire serialization code of a protocol from/to IO streams
+///
+/// Like `generate_wire_code`, but takes IO Streams directly rather than filenames
+///
+/// Args:
+///
+/// - `protocol`: an object `Read`-able containing the XML protocol file
+/// - `target`: a `Write`-able object to which the generated code will be outputed to
+pub fn generate_wire_code_streams<P1: Read, P2: Write>(protocol: P1, target: &mut P2) {
+    let protocol = parse(protocol);
+    wire_code_gen::write_protocol_wire(protocol, target).unwrap();
+}
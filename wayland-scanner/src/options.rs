@@ -0,0 +1,87 @@
+use protocol::{Protocol, Type};
+
+/// Options of the code generation
+///
+/// These options allow you to customize the code generated by the
+/// `*_with_options` functions of this crate. The `Default` value
+/// produces the same code as the functions without options.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Options {
+    /// Derive `Serialize` and `Deserialize` from `serde` on the generated types
+    ///
+    /// The derives are added to the enums of the protocol, as well as to the
+    /// `Request` and `Event` enums of the interfaces whose messages do not contain
+    /// any object, new-id or file descriptor argument.
+    ///
+    /// The crate including the generated code must then provide the `Serialize`
+    /// and `Deserialize` derives, using `#[macro_use] extern crate serde_derive;`.
+    pub serde: bool,
+    /// Write the version of the generated code at the top of the generated files
+    ///
+    /// The header is a comment line containing `CODEGEN_VERSION`, allowing to check
+    /// which version of the code generation produced vendored files.
+    pub version_header: bool,
+    /// Generate a builder with named arguments for the requests with four arguments or more
+    ///
+    /// For such a request `foo` of an interface, the `RequestsTrait` of the client side gets
+    /// a `foo_builder()` method, returning a `FooRequestBuilder` with a setter for each
+    /// argument and a `send()` method, so that long argument lists can be given by name.
+    pub request_builders: bool,
+    /// Give access to the C descriptions of the interfaces from the code of the rust implementation
+    ///
+    /// Each interface then gets a `c_interface()` function, returning a pointer to its
+    /// `wl_interface` table, for C libraries expecting one. The tables are generated by
+    /// `generate_c_interfaces()`, and are expected in a `c_interfaces` module next to the module
+    /// including the generated code, like for the code using the C system libs. They are typed
+    /// from `wayland_sys`, which must be imported as `sys` in the module including the code.
+    ///
+    /// The proxies and resources of the rust implementation have no C counterpart, so they
+    /// get no `as_c_ptr()` method: handing the objects themselves over to C libraries, like
+    /// a surface to EGL, still requires the `native_lib` feature and `Proxy::c_ptr()`.
+    /// This option has no effect on the code using the C system libs, whose interfaces
+    /// already have a `c_interface()` function.
+    pub c_interfaces: bool,
+    /// The kinds of file descriptors expected by some arguments of the protocol
+    ///
+    /// The protocol files do not describe the file descriptors they expect, the
+    /// `MessageDesc::fd_kinds` of the generated code is filled from these hints. The
+    /// arguments without hint accept any file descriptor.
+    pub fd_kinds: &'static [FdKindHint],
+}
+
+/// The kind of file descriptor expected by an argument of a message
+///
+/// See `Options::fd_kinds`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FdKindHint {
+    /// Name of the interface, like `"wl_keyboard"`
+    pub interface: &'static str,
+    /// Name of the request or event, like `"keymap"`
+    pub message: &'static str,
+    /// Name of the argument, like `"fd"`
+    pub argument: &'static str,
+    /// Name of the variant of `FdKind` expected, like `"ReadOnlyMappable"`
+    pub kind: &'static str,
+}
+
+impl Options {
+    // fill the expected kinds of the file descriptors of a protocol
+    pub(crate) fn apply_fd_kinds(&self, protocol: &mut Protocol) {
+        for interface in &mut protocol.interfaces {
+            let hints = self
+                .fd_kinds
+                .iter()
+                .filter(|h| h.interface == interface.name)
+                .collect::<Vec<_>>();
+            for msg in interface.requests.iter_mut().chain(interface.events.iter_mut()) {
+                let msg_name = &msg.name;
+                for arg in msg.args.iter_mut().filter(|a| a.typ == Type::Fd) {
+                    arg.fd_kind = hints
+                        .iter()
+                        .find(|h| h.message == *msg_name && h.argument == arg.name)
+                        .map(|h| h.kind);
+                }
+            }
+        }
+    }
+}
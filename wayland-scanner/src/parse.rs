@@ -1,15 +1,55 @@
 use protocol::*;
+use std::fmt;
 use std::io::Read;
 use xml::attribute::OwnedAttribute;
-use xml::reader::ParserConfig;
+use xml::reader::{Error as XmlError, ParserConfig};
 use xml::reader::XmlEvent;
 use xml::EventReader;
 
+/// An error parsing a protocol file
+#[derive(Debug)]
+pub enum ParseError {
+    /// The file is not valid XML
+    Xml(XmlError),
+    /// The file is not a valid protocol description
+    Invalid(String),
+}
+
+impl From<XmlError> for ParseError {
+    fn from(err: XmlError) -> ParseError {
+        ParseError::Xml(err)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Xml(ref err) => write!(f, "Ill-formed protocol file: {}", err),
+            ParseError::Invalid(ref msg) => write!(f, "Ill-formed protocol file: {}", msg),
+        }
+    }
+}
+
+impl ::std::error::Error for ParseError {
+    fn description(&self) -> &str {
+        match *self {
+            ParseError::Xml(_) => "protocol file is not valid XML",
+            ParseError::Invalid(_) => "invalid protocol description",
+        }
+    }
+}
+
+macro_rules! invalid(
+    ($($arg: tt)*) => (
+        return Err(ParseError::Invalid(format!($($arg)*)))
+    )
+);
+
 macro_rules! extract_from(
     ($it: expr => $pattern: pat => $result: expr) => (
-        match $it.next() {
-            Ok($pattern) => { $result },
-            e => panic!("Ill-formed protocol file: {:?}", e)
+        match $it.next()? {
+            $pattern => { $result },
+            e => invalid!("unexpected {:?}", e)
         }
     )
 );
@@ -17,15 +57,17 @@ macro_rules! extract_from(
 macro_rules! extract_end_tag(
     ($it: expr => $tag: expr) => (
         extract_from!($it => XmlEvent::EndElement { name } => {
-            assert!(name.local_name == $tag, "Ill-formed protocol file");
+            if name.local_name != $tag {
+                invalid!("unexpected closing tag `{}`", name.local_name);
+            }
         });
     )
 );
 
-pub fn parse_stream<S: Read>(stream: S) -> Protocol {
+pub fn parse_stream<S: Read>(stream: S) -> Result<Protocol, ParseError> {
     let mut reader = EventReader::new_with_config(stream, ParserConfig::new().trim_whitespace(true));
-    reader.next().expect("Could not read from event reader");
-    let mut protocol = parse_protocol(reader);
+    reader.next()?;
+    let mut protocol = parse_protocol(reader)?;
 
     // yay, hardcoding things
     if protocol.name == "wayland" {
@@ -34,94 +76,124 @@ pub fn parse_stream<S: Read>(stream: S) -> Protocol {
         // Luckily, wayland-scanner does, so we inject it
         for interface in &mut protocol.interfaces {
             if interface.name == "wl_callback" {
-                let done_event = &mut interface.events[0];
-                assert!(done_event.name == "done");
-                done_event.typ = Some(Type::Destructor);
+                match interface.events.first_mut() {
+                    Some(ref mut done_event) if done_event.name == "done" => {
+                        done_event.typ = Some(Type::Destructor)
+                    }
+                    _ => invalid!("`wl_callback` must start with the `done` event"),
+                }
             }
         }
     }
 
-    protocol
+    Ok(protocol)
+}
+
+// XML requires the line endings to be normalized, which xml-rs does not do: without this,
+// the output would depend on the platform the protocol file was checked out on
+fn normalize_newlines(text: String) -> String {
+    if text.contains('\r') {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    } else {
+        text
+    }
+}
+
+fn number<T: ::std::str::FromStr>(value: &str) -> Result<T, ParseError> {
+    match value.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => invalid!("invalid number `{}`", value),
+    }
 }
 
-fn parse_protocol<R: Read>(mut reader: EventReader<R>) -> Protocol {
+fn parse_protocol<R: Read>(mut reader: EventReader<R>) -> Result<Protocol, ParseError> {
     let mut protocol = extract_from!(
         reader => XmlEvent::StartElement { name, attributes, .. } => {
-            assert!(name.local_name == "protocol", "Missing protocol toplevel tag");
-            assert!(attributes[0].name.local_name == "name", "Protocol must have a name");
-            Protocol::new(attributes[0].value.clone())
+            if name.local_name != "protocol" {
+                invalid!("missing protocol toplevel tag");
+            }
+            match attributes.into_iter().find(|attr| attr.name.local_name == "name") {
+                Some(attr) => Protocol::new(attr.value),
+                None => invalid!("protocol must have a name"),
+            }
         }
     );
 
     loop {
-        match reader.next() {
-            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+        match reader.next()? {
+            XmlEvent::StartElement { name, attributes, .. } => {
                 match &name.local_name[..] {
                     "copyright" => {
                         // parse the copyright
-                        let copyright = match reader.next() {
-                            Ok(XmlEvent::Characters(copyright)) | Ok(XmlEvent::CData(copyright)) => copyright,
-                            e => panic!("Ill-formed protocol file: {:?}", e),
+                        let copyright = match reader.next()? {
+                            XmlEvent::Characters(copyright) | XmlEvent::CData(copyright) => copyright,
+                            e => invalid!("unexpected {:?}", e),
                         };
 
                         extract_end_tag!(reader => "copyright");
-                        protocol.copyright = Some(copyright);
+                        protocol.copyright = Some(normalize_newlines(copyright));
                     }
                     "interface" => {
-                        protocol.interfaces.push(parse_interface(&mut reader, attributes));
+                        protocol.interfaces.push(parse_interface(&mut reader, attributes)?);
                     }
                     "description" => {
-                        protocol.description = Some(parse_description(&mut reader, attributes));
+                        protocol.description = Some(parse_description(&mut reader, attributes)?);
                     }
-                    _ => panic!(
-                        "Ill-formed protocol file: unexpected token `{}` in protocol {}",
-                        name.local_name, protocol.name
+                    _ => invalid!(
+                        "unexpected token `{}` in protocol {}",
+                        name.local_name,
+                        protocol.name
                     ),
                 }
             }
-            Ok(XmlEvent::EndElement { name }) => {
-                assert!(
-                    name.local_name == "protocol",
-                    "Unexpected closing token `{}`",
-                    name.local_name
-                );
+            XmlEvent::EndElement { name } => {
+                if name.local_name != "protocol" {
+                    invalid!("unexpected closing token `{}`", name.local_name);
+                }
                 break;
             }
-            e => panic!("Ill-formed protocol file: {:?}", e),
+            e => invalid!("unexpected {:?}", e),
         }
     }
 
-    protocol
+    Ok(protocol)
 }
 
-fn parse_interface<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Interface {
+fn parse_interface<R: Read>(
+    reader: &mut EventReader<R>,
+    attrs: Vec<OwnedAttribute>,
+) -> Result<Interface, ParseError> {
     let mut interface = Interface::new();
     for attr in attrs {
         match &attr.name.local_name[..] {
             "name" => interface.name = attr.value,
-            "version" => interface.version = attr.value.parse().unwrap(),
+            "version" => interface.version = number(&attr.value)?,
             _ => {}
         }
     }
 
     loop {
-        match reader.next() {
-            Ok(XmlEvent::StartElement { name, attributes, .. }) => match &name.local_name[..] {
-                "description" => interface.description = Some(parse_description(reader, attributes)),
-                "request" => interface.requests.push(parse_request(reader, attributes)),
-                "event" => interface.events.push(parse_event(reader, attributes)),
-                "enum" => interface.enums.push(parse_enum(reader, attributes)),
-                _ => panic!("Unexpected tocken: `{}`", name.local_name),
+        match reader.next()? {
+            XmlEvent::StartElement { name, attributes, .. } => match &name.local_name[..] {
+                "description" => interface.description = Some(parse_description(reader, attributes)?),
+                "request" => interface.requests.push(parse_request(reader, attributes)?),
+                "event" => interface.events.push(parse_event(reader, attributes)?),
+                "enum" => interface.enums.push(parse_enum(reader, attributes)?),
+                _ => invalid!("unexpected token `{}`", name.local_name),
             },
-            Ok(XmlEvent::EndElement { ref name }) if name.local_name == "interface" => break,
+            XmlEvent::EndElement { ref name } if name.local_name == "interface" => break,
+            XmlEvent::EndDocument => invalid!("unexpected end of document"),
             _ => {}
         }
     }
 
-    interface
+    Ok(interface)
 }
 
-fn parse_description<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> (String, String) {
+fn parse_description<R: Read>(
+    reader: &mut EventReader<R>,
+    attrs: Vec<OwnedAttribute>,
+) -> Result<(String, String), ParseError> {
     let mut summary = String::new();
     for attr in attrs {
         match &attr.name.local_name[..] {
@@ -130,50 +202,54 @@ fn parse_description<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttri
         }
     }
 
-    let description = match reader.next() {
-        Ok(XmlEvent::Characters(txt)) => {
+    let description = match reader.next()? {
+        XmlEvent::Characters(txt) => {
             extract_end_tag!(reader => "description");
-            txt
+            normalize_newlines(txt)
         }
-        Ok(XmlEvent::EndElement { ref name }) if name.local_name == "description" => String::new(),
-        e => panic!("Ill-formed protocol file: {:?}", e),
+        XmlEvent::EndElement { ref name } if name.local_name == "description" => String::new(),
+        e => invalid!("unexpected {:?}", e),
     };
 
-    (summary, description)
+    Ok((summary, description))
 }
 
-fn parse_request<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Message {
+fn parse_request<R: Read>(
+    reader: &mut EventReader<R>,
+    attrs: Vec<OwnedAttribute>,
+) -> Result<Message, ParseError> {
     let mut request = Message::new();
     for attr in attrs {
         match &attr.name.local_name[..] {
             "name" => request.name = attr.value,
-            "type" => request.typ = Some(parse_type(&attr.value)),
-            "since" => request.since = attr.value.parse().unwrap(),
+            "type" => request.typ = Some(parse_type(&attr.value)?),
+            "since" => request.since = number(&attr.value)?,
             _ => {}
         }
     }
 
     loop {
-        match reader.next() {
-            Ok(XmlEvent::StartElement { name, attributes, .. }) => match &name.local_name[..] {
-                "description" => request.description = Some(parse_description(reader, attributes)),
-                "arg" => request.args.push(parse_arg(reader, attributes)),
-                _ => panic!("Unexpected tocken: `{}`", name.local_name),
+        match reader.next()? {
+            XmlEvent::StartElement { name, attributes, .. } => match &name.local_name[..] {
+                "description" => request.description = Some(parse_description(reader, attributes)?),
+                "arg" => request.args.push(parse_arg(reader, attributes)?),
+                _ => invalid!("unexpected token `{}`", name.local_name),
             },
-            Ok(XmlEvent::EndElement { ref name }) if name.local_name == "request" => break,
+            XmlEvent::EndElement { ref name } if name.local_name == "request" => break,
+            XmlEvent::EndDocument => invalid!("unexpected end of document"),
             _ => {}
         }
     }
 
-    request
+    Ok(request)
 }
 
-fn parse_enum<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Enum {
+fn parse_enum<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Result<Enum, ParseError> {
     let mut enu = Enum::new();
     for attr in attrs {
         match &attr.name.local_name[..] {
             "name" => enu.name = attr.value,
-            "since" => enu.since = attr.value.parse().unwrap(),
+            "since" => enu.since = number(&attr.value)?,
             "bitfield" => if &attr.value[..] == "true" {
                 enu.bitfield = true
             },
@@ -182,51 +258,56 @@ fn parse_enum<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>)
     }
 
     loop {
-        match reader.next() {
-            Ok(XmlEvent::StartElement { name, attributes, .. }) => match &name.local_name[..] {
-                "description" => enu.description = Some(parse_description(reader, attributes)),
-                "entry" => enu.entries.push(parse_entry(reader, attributes)),
-                _ => panic!("Unexpected tocken: `{}`", name.local_name),
+        match reader.next()? {
+            XmlEvent::StartElement { name, attributes, .. } => match &name.local_name[..] {
+                "description" => enu.description = Some(parse_description(reader, attributes)?),
+                "entry" => enu.entries.push(parse_entry(reader, attributes)?),
+                _ => invalid!("unexpected token `{}`", name.local_name),
             },
-            Ok(XmlEvent::EndElement { ref name }) if name.local_name == "enum" => break,
+            XmlEvent::EndElement { ref name } if name.local_name == "enum" => break,
+            XmlEvent::EndDocument => invalid!("unexpected end of document"),
             _ => {}
         }
     }
 
-    enu
+    Ok(enu)
 }
 
-fn parse_event<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Message {
+fn parse_event<R: Read>(
+    reader: &mut EventReader<R>,
+    attrs: Vec<OwnedAttribute>,
+) -> Result<Message, ParseError> {
     let mut event = Message::new();
     for attr in attrs {
         match &attr.name.local_name[..] {
             "name" => event.name = attr.value,
-            "since" => event.since = attr.value.parse().unwrap(),
+            "since" => event.since = number(&attr.value)?,
             _ => {}
         }
     }
 
     loop {
-        match reader.next() {
-            Ok(XmlEvent::StartElement { name, attributes, .. }) => match &name.local_name[..] {
-                "description" => event.description = Some(parse_description(reader, attributes)),
-                "arg" => event.args.push(parse_arg(reader, attributes)),
-                _ => panic!("Unexpected tocken: `{}`", name.local_name),
+        match reader.next()? {
+            XmlEvent::StartElement { name, attributes, .. } => match &name.local_name[..] {
+                "description" => event.description = Some(parse_description(reader, attributes)?),
+                "arg" => event.args.push(parse_arg(reader, attributes)?),
+                _ => invalid!("unexpected token `{}`", name.local_name),
             },
-            Ok(XmlEvent::EndElement { ref name }) if name.local_name == "event" => break,
+            XmlEvent::EndElement { ref name } if name.local_name == "event" => break,
+            XmlEvent::EndDocument => invalid!("unexpected end of document"),
             _ => {}
         }
     }
 
-    event
+    Ok(event)
 }
 
-fn parse_arg<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Arg {
+fn parse_arg<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Result<Arg, ParseError> {
     let mut arg = Arg::new();
     for attr in attrs {
         match &attr.name.local_name[..] {
             "name" => arg.name = attr.value,
-            "type" => arg.typ = parse_type(&attr.value),
+            "type" => arg.typ = parse_type(&attr.value)?,
             "summary" => arg.summary = Some(attr.value.split_whitespace().collect::<Vec<_>>().join(" ")),
             "interface" => arg.interface = Some(attr.value),
             "allow-null" => if attr.value == "true" {
@@ -238,21 +319,22 @@ fn parse_arg<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -
     }
 
     loop {
-        match reader.next() {
-            Ok(XmlEvent::StartElement { name, attributes, .. }) => match &name.local_name[..] {
-                "description" => arg.description = Some(parse_description(reader, attributes)),
-                _ => panic!("Unexpected tocken: `{}`", name.local_name),
+        match reader.next()? {
+            XmlEvent::StartElement { name, attributes, .. } => match &name.local_name[..] {
+                "description" => arg.description = Some(parse_description(reader, attributes)?),
+                _ => invalid!("unexpected token `{}`", name.local_name),
             },
-            Ok(XmlEvent::EndElement { ref name }) if name.local_name == "arg" => break,
+            XmlEvent::EndElement { ref name } if name.local_name == "arg" => break,
+            XmlEvent::EndDocument => invalid!("unexpected end of document"),
             _ => {}
         }
     }
 
-    arg
+    Ok(arg)
 }
 
-fn parse_type(txt: &str) -> Type {
-    match txt {
+fn parse_type(txt: &str) -> Result<Type, ParseError> {
+    Ok(match txt {
         "int" => Type::Int,
         "uint" => Type::Uint,
         "fixed" => Type::Fixed,
@@ -262,32 +344,33 @@ fn parse_type(txt: &str) -> Type {
         "array" => Type::Array,
         "fd" => Type::Fd,
         "destructor" => Type::Destructor,
-        e => panic!("Unexpected type: {}", e),
-    }
+        e => invalid!("unexpected type `{}`", e),
+    })
 }
 
-fn parse_entry<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Entry {
+fn parse_entry<R: Read>(reader: &mut EventReader<R>, attrs: Vec<OwnedAttribute>) -> Result<Entry, ParseError> {
     let mut entry = Entry::new();
     for attr in attrs {
         match &attr.name.local_name[..] {
             "name" => entry.name = attr.value,
             "value" => entry.value = attr.value,
-            "since" => entry.since = attr.value.parse().unwrap(),
+            "since" => entry.since = number(&attr.value)?,
             "summary" => entry.summary = Some(attr.value.split_whitespace().collect::<Vec<_>>().join(" ")),
             _ => {}
         }
     }
 
     loop {
-        match reader.next() {
-            Ok(XmlEvent::StartElement { name, attributes, .. }) => match &name.local_name[..] {
-                "description" => entry.description = Some(parse_description(reader, attributes)),
-                _ => panic!("Unexpected tocken: `{}`", name.local_name),
+        match reader.next()? {
+            XmlEvent::StartElement { name, attributes, .. } => match &name.local_name[..] {
+                "description" => entry.description = Some(parse_description(reader, attributes)?),
+                _ => invalid!("unexpected token `{}`", name.local_name),
             },
-            Ok(XmlEvent::EndElement { ref name }) if name.local_name == "entry" => break,
+            XmlEvent::EndElement { ref name } if name.local_name == "entry" => break,
+            XmlEvent::EndDocument => invalid!("unexpected end of document"),
             _ => {}
         }
     }
 
-    entry
+    Ok(entry)
 }
@@ -1,13 +1,23 @@
+//! Description of a protocol, as parsed from its XML file
+//!
+//! The descriptions, as `(summary, description)` pairs, and the copyright are kept as
+//! written in the file, with normalized line endings.
+
+/// A protocol
 #[derive(Debug)]
 pub struct Protocol {
+    /// The name of the protocol
     pub name: String,
+    /// The copyright notice of the protocol
     pub copyright: Option<String>,
+    /// The description of the protocol
     pub description: Option<(String, String)>,
+    /// The interfaces of the protocol, in their order in the file
     pub interfaces: Vec<Interface>,
 }
 
 impl Protocol {
-    pub fn new(name: String) -> Protocol {
+    pub(crate) fn new(name: String) -> Protocol {
         Protocol {
             name: name,
             copyright: None,
@@ -17,18 +27,25 @@ impl Protocol {
     }
 }
 
+/// An interface
 #[derive(Debug)]
 pub struct Interface {
+    /// The name of the interface
     pub name: String,
+    /// The latest version of the interface
     pub version: u32,
+    /// The description of the interface
     pub description: Option<(String, String)>,
+    /// The requests of the interface, indexed by their opcode
     pub requests: Vec<Message>,
+    /// The events of the interface, indexed by their opcode
     pub events: Vec<Message>,
+    /// The enums of the interface
     pub enums: Vec<Enum>,
 }
 
 impl Interface {
-    pub fn new() -> Interface {
+    pub(crate) fn new() -> Interface {
         Interface {
             name: String::new(),
             version: 1,
@@ -40,48 +57,65 @@ impl Interface {
     }
 }
 
+/// A request or an event
 #[derive(Debug)]
 pub struct Message {
+    /// The name of the message
     pub name: String,
+    /// `Some(Type::Destructor)` if this message destroys its object
     pub typ: Option<Type>,
+    /// The version of the interface this message appeared in
     pub since: u16,
+    /// The description of the message
     pub description: Option<(String, String)>,
+    /// The arguments of the message, in their order on the wire
     pub args: Vec<Arg>,
-    pub type_index: usize,
 }
 
 impl Message {
-    pub fn new() -> Message {
+    pub(crate) fn new() -> Message {
         Message {
             name: String::new(),
             typ: None,
             since: 1,
             description: None,
             args: Vec::new(),
-            type_index: 0,
         }
     }
 
-    pub fn all_null(&self) -> bool {
+    pub(crate) fn all_null(&self) -> bool {
         self.args
             .iter()
             .all(|a| !((a.typ == Type::Object || a.typ == Type::NewId) && a.interface.is_some()))
     }
 }
 
+/// An argument of a message
+///
+/// On the wire, the `new_id` arguments without an interface are preceded by the name of
+/// this interface, as a string, and its version, as an unsigned integer.
 #[derive(Debug)]
 pub struct Arg {
+    /// The name of the argument
     pub name: String,
+    /// The type of the argument
     pub typ: Type,
+    /// The interface of the object of `object` and `new_id` arguments, if given
     pub interface: Option<String>,
+    /// The summary of the argument
     pub summary: Option<String>,
+    /// The description of the argument
     pub description: Option<(String, String)>,
+    /// Whether the argument may be a null string or object
     pub allow_null: bool,
+    /// The enum giving the values of this argument, if any
     pub enum_: Option<String>,
+    // the variant of `FdKind` expected by this fd argument
+    pub(crate) fd_kind: Option<&'static str>,
 }
 
 impl Arg {
-    pub fn new() -> Arg {
+    pub(crate) fn new() -> Arg {
         Arg {
             name: String::new(),
             typ: Type::Object,
@@ -90,21 +124,28 @@ impl Arg {
             description: None,
             allow_null: false,
             enum_: None,
+            fd_kind: None,
         }
     }
 }
 
+/// An enum
 #[derive(Debug)]
 pub struct Enum {
+    /// The name of the enum
     pub name: String,
+    /// The version of the interface this enum appeared in
     pub since: u16,
+    /// The description of the enum
     pub description: Option<(String, String)>,
+    /// The entries of the enum
     pub entries: Vec<Entry>,
+    /// Whether the entries of the enum are flags
     pub bitfield: bool,
 }
 
 impl Enum {
-    pub fn new() -> Enum {
+    pub(crate) fn new() -> Enum {
         Enum {
             name: String::new(),
             since: 1,
@@ -115,17 +156,23 @@ impl Enum {
     }
 }
 
+/// An entry of an enum
 #[derive(Debug)]
 pub struct Entry {
+    /// The name of the entry
     pub name: String,
+    /// The value of the entry, as written in the file
     pub value: String,
+    /// The version of the interface this entry appeared in
     pub since: u16,
+    /// The description of the entry
     pub description: Option<(String, String)>,
+    /// The summary of the entry
     pub summary: Option<String>,
 }
 
 impl Entry {
-    pub fn new() -> Entry {
+    pub(crate) fn new() -> Entry {
         Entry {
             name: String::new(),
             value: "0".to_owned(),
@@ -136,28 +183,38 @@ impl Entry {
     }
 }
 
+/// The type of an argument, or of a message
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Type {
+    /// A signed integer
     Int,
+    /// An unsigned integer
     Uint,
+    /// A fixed point number
     Fixed,
+    /// A string
     String,
+    /// An existing object
     Object,
+    /// A new object
     NewId,
+    /// An array of bytes
     Array,
+    /// A file descriptor
     Fd,
+    /// The type of the messages destroying their object
     Destructor,
 }
 
 impl Type {
-    pub fn nullable(&self) -> bool {
+    pub(crate) fn nullable(&self) -> bool {
         match *self {
             Type::String | Type::Object | Type::NewId | Type::Array => true,
             _ => false,
         }
     }
 
-    pub fn rust_type(&self) -> &'static str {
+    pub(crate) fn rust_type(&self) -> &'static str {
         match *self {
             Type::Int => "i32",
             Type::Uint => "u32",
@@ -170,7 +227,7 @@ impl Type {
         }
     }
 
-    pub fn common_type(&self) -> &'static str {
+    pub(crate) fn common_type(&self) -> &'static str {
         match *self {
             Type::Int => "Int",
             Type::Uint => "Uint",
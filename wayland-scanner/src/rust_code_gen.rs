@@ -4,10 +4,15 @@ use std::io::Write;
 use common_gen::*;
 use protocol::*;
 use util::*;
-use Side;
+use {Options, Side};
 
-pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -> IOResult<()> {
-    write_prefix(&protocol, out)?;
+pub(crate) fn write_protocol_client<O: Write>(
+    mut protocol: Protocol,
+    out: &mut O,
+    options: &Options,
+) -> IOResult<()> {
+    options.apply_fd_kinds(&mut protocol);
+    write_prefix(&protocol, out, options)?;
 
     for iface in &protocol.interfaces {
         writeln!(out, "pub mod {} {{", iface.name)?;
@@ -22,13 +27,14 @@ pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -
         )?;
         let iface_name = snake_to_camel(&iface.name);
 
-        write_enums(&iface.enums, out)?;
+        write_enums(&iface.enums, out, options, false)?;
         write_messagegroup(
             "Request",
             Side::Client,
             false,
             &iface.requests,
             out,
+            options,
             None::<fn(_: &mut _) -> _>,
         )?;
         write_messagegroup(
@@ -37,6 +43,7 @@ pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -
             true,
             &iface.events,
             out,
+            options,
             None::<fn(_: &mut _) -> _>,
         )?;
         write_interface(
@@ -46,20 +53,30 @@ pub(crate) fn write_protocol_client<O: Write>(protocol: Protocol, out: &mut O) -
             out,
             None::<fn(_: &mut _) -> _>,
         )?;
-        write_client_methods(&iface_name, &iface.requests, out)?;
+        if options.c_interfaces {
+            write_c_interface_fn(&iface_name, &iface.name, out)?;
+        }
+        write_client_methods(&iface_name, &iface.requests, out, options)?;
 
         writeln!(out, "}}\n")?;
     }
 
+    write_version_matrix(&protocol, Side::Client, out)?;
+
     Ok(())
 }
 
-pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -> IOResult<()> {
-    write_prefix(&protocol, out)?;
+pub(crate) fn write_protocol_server<O: Write>(
+    mut protocol: Protocol,
+    out: &mut O,
+    options: &Options,
+) -> IOResult<()> {
+    options.apply_fd_kinds(&mut protocol);
+    write_prefix(&protocol, out, options)?;
 
     for iface in &protocol.interfaces {
         // display and registry are handled specially
-        if iface.name == "wl_display" || iface.name == "wl_registry" {
+        if !Side::Server.generates(&iface.name) {
             continue;
         }
 
@@ -75,13 +92,14 @@ pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -
         )?;
         let iface_name = snake_to_camel(&iface.name);
 
-        write_enums(&iface.enums, out)?;
+        write_enums(&iface.enums, out, options, false)?;
         write_messagegroup(
             "Request",
             Side::Server,
             true,
             &iface.requests,
             out,
+            options,
             None::<fn(_: &mut _) -> _>,
         )?;
         write_messagegroup(
@@ -90,6 +108,7 @@ pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -
             false,
             &iface.events,
             out,
+            options,
             None::<fn(_: &mut _) -> _>,
         )?;
         write_interface(
@@ -99,9 +118,30 @@ pub(crate) fn write_protocol_server<O: Write>(protocol: Protocol, out: &mut O) -
             out,
             None::<fn(_: &mut _) -> _>,
         )?;
+        if options.c_interfaces {
+            write_c_interface_fn(&iface_name, &iface.name, out)?;
+        }
 
         writeln!(out, "}}\n")?;
     }
 
+    write_version_matrix(&protocol, Side::Server, out)?;
+
     Ok(())
 }
+
+fn write_c_interface_fn<O: Write>(name: &str, low_name: &str, out: &mut O) -> IOResult<()> {
+    writeln!(
+        out,
+        r#"
+    impl {name} {{
+        /// The C description of this interface
+        pub fn c_interface() -> *const super::sys::common::wl_interface {{
+            unsafe {{ &super::super::c_interfaces::{low_name}_interface }}
+        }}
+    }}
+"#,
+        name = name,
+        low_name = low_name,
+    )
+}
@@ -19,4 +19,11 @@ impl Side {
             Server => "Resource",
         }
     }
+
+    /// Whether code is generated for this interface on this side
+    ///
+    /// Server-side, the display and the registry are handled by the library itself.
+    pub(crate) fn generates(&self, interface: &str) -> bool {
+        *self == Client || (interface != "wl_display" && interface != "wl_registry")
+    }
 }
@@ -0,0 +1,404 @@
+use std::io::Result as IOResult;
+use std::io::Write;
+
+use common_gen::*;
+use protocol::*;
+use util::*;
+use Options;
+
+pub(crate) fn write_protocol_wire<O: Write>(protocol: Protocol, out: &mut O) -> IOResult<()> {
+    write_prefix(&protocol, out, &Options::default())?;
+    write_wire_support(out)?;
+
+    for iface in &protocol.interfaces {
+        writeln!(out, "pub mod {} {{", iface.name)?;
+
+        if let Some((ref short, ref long)) = iface.description {
+            write_doc(Some(short), long, true, out, 1)?;
+        }
+
+        writeln!(out, "    use ::alloc::string::String;")?;
+        writeln!(out, "    use ::alloc::vec::Vec;")?;
+        writeln!(
+            out,
+            "    use super::{{wire_write_array, wire_write_string, WireError, WireReader}};\n"
+        )?;
+        writeln!(out, "    /// The name of this interface")?;
+        writeln!(out, "    pub const NAME: &'static str = \"{}\";", iface.name)?;
+        writeln!(out, "    /// The version of this interface described by the protocol")?;
+        writeln!(out, "    pub const VERSION: u32 = {};\n", iface.version)?;
+
+        write_enums(&iface.enums, out, &Options::default(), true)?;
+        write_wire_messagegroup("Request", &iface.requests, out)?;
+        write_wire_messagegroup("Event", &iface.events, out)?;
+
+        writeln!(out, "}}\n")?;
+    }
+
+    Ok(())
+}
+
+// the helpers shared by all the interfaces of the protocol
+fn write_wire_support<O: Write>(out: &mut O) -> IOResult<()> {
+    writeln!(
+        out,
+        r#"/// The reasons why a message could not be parsed
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WireError {{
+    /// The opcode does not match any message of the interface
+    UnknownOpcode,
+    /// The message ended before all of its arguments were read
+    MissingArgument,
+    /// The message is longer than its arguments
+    TrailingData,
+    /// A string is not nul-terminated or is not valid UTF-8
+    InvalidString,
+    /// A null object or string was given to an argument which does not allow it
+    NullArgument,
+    /// There are less file descriptors than the fd arguments of the message
+    MissingFd,
+}}
+
+/// Parse the header of the message at the start of `words`
+///
+/// Returns the id of the sender object, the opcode, and the length of the whole
+/// message in words, or `None` if `words` is shorter than the header.
+pub fn parse_header(words: &[u32]) -> Option<(u32, u16, usize)> {{
+    if words.len() < 2 {{
+        return None;
+    }}
+    Some((words[0], words[1] as u16, (words[1] >> 16) as usize / 4))
+}}
+
+// the bytes of a string or an array, stored in native endianness like the words
+fn wire_bytes_from_words(words: &[u32], len: usize) -> ::alloc::vec::Vec<u8> {{
+    let mut bytes = ::alloc::vec::Vec::with_capacity(4 * words.len());
+    for &word in words {{
+        for i in 0..4 {{
+            let shift = if cfg!(target_endian = "little") {{ 8 * i }} else {{ 24 - 8 * i }};
+            bytes.push((word >> shift) as u8);
+        }}
+    }}
+    bytes.truncate(len);
+    bytes
+}}
+
+fn wire_write_array(words: &mut ::alloc::vec::Vec<u32>, bytes: &[u8]) {{
+    words.push(bytes.len() as u32);
+    for chunk in bytes.chunks(4) {{
+        let mut word = 0;
+        for (i, &byte) in chunk.iter().enumerate() {{
+            let shift = if cfg!(target_endian = "little") {{ 8 * i }} else {{ 24 - 8 * i }};
+            word |= u32::from(byte) << shift;
+        }}
+        words.push(word);
+    }}
+}}
+
+fn wire_write_string(words: &mut ::alloc::vec::Vec<u32>, string: Option<&str>) {{
+    match string {{
+        Some(string) => {{
+            let mut bytes = ::alloc::vec::Vec::with_capacity(string.len() + 1);
+            bytes.extend_from_slice(string.as_bytes());
+            bytes.push(0);
+            wire_write_array(words, &bytes);
+        }}
+        None => words.push(0),
+    }}
+}}
+
+// reads the arguments of a message
+struct WireReader<'a> {{
+    words: &'a [u32],
+}}
+
+impl<'a> WireReader<'a> {{
+    fn word(&mut self) -> Result<u32, WireError> {{
+        match self.words.split_first() {{
+            Some((&word, rest)) => {{
+                self.words = rest;
+                Ok(word)
+            }}
+            None => Err(WireError::MissingArgument),
+        }}
+    }}
+
+    fn object(&mut self) -> Result<Option<u32>, WireError> {{
+        self.word().map(|id| if id == 0 {{ None }} else {{ Some(id) }})
+    }}
+
+    fn array(&mut self) -> Result<::alloc::vec::Vec<u8>, WireError> {{
+        let len = self.word()? as usize;
+        let count = (len + 3) / 4;
+        if self.words.len() < count {{
+            return Err(WireError::MissingArgument);
+        }}
+        let (data, rest) = self.words.split_at(count);
+        self.words = rest;
+        Ok(wire_bytes_from_words(data, len))
+    }}
+
+    fn string(&mut self) -> Result<Option<::alloc::string::String>, WireError> {{
+        let mut bytes = self.array()?;
+        if bytes.is_empty() {{
+            return Ok(None);
+        }}
+        if bytes.pop() != Some(0) {{
+            return Err(WireError::InvalidString);
+        }}
+        ::alloc::string::String::from_utf8(bytes)
+            .map(Some)
+            .map_err(|_| WireError::InvalidString)
+    }}
+
+    fn finish(&self) -> Result<(), WireError> {{
+        if self.words.is_empty() {{
+            Ok(())
+        }} else {{
+            Err(WireError::TrailingData)
+        }}
+    }}
+}}
+"#
+    )
+}
+
+fn variant(name: &str, msg: &Message) -> String {
+    format!("{}::{}", name, snake_to_camel(&msg.name))
+}
+
+fn write_wire_messagegroup<O: Write>(name: &str, messages: &[Message], out: &mut O) -> IOResult<()> {
+    /*
+     * Enum definition
+     */
+
+    writeln!(out, "    #[derive(Clone, Debug, PartialEq)]")?;
+    writeln!(out, "    pub enum {} {{", name)?;
+    for m in messages {
+        if let Some((ref short, ref long)) = m.description {
+            write_doc(Some(short), long, false, out, 2)?;
+        }
+        if m.since > 1 {
+            writeln!(
+                out,
+                "        ///\n        /// Only available since version {} of the interface",
+                m.since
+            )?;
+        }
+
+        write!(out, "        {}", snake_to_camel(&m.name))?;
+        if !m.args.is_empty() {
+            writeln!(out, " {{")?;
+            for a in &m.args {
+                if let Some(ref summary) = a.summary {
+                    writeln!(out, "            /// {}", summary)?;
+                }
+                write!(out, "            {}: ", a.name)?;
+                let nullable = a.allow_null && is_nullable(a.typ);
+                if nullable {
+                    write!(out, "Option<")?;
+                }
+                match a.typ {
+                    Type::Uint | Type::Object => write!(out, "u32")?,
+                    Type::Int | Type::Fd => write!(out, "i32")?,
+                    Type::Fixed => write!(out, "f64")?,
+                    Type::String => write!(out, "String")?,
+                    Type::Array => write!(out, "Vec<u8>")?,
+                    // the interface, version and id of a bind-like new_id
+                    Type::NewId if a.interface.is_none() => write!(out, "(String, u32, u32)")?,
+                    Type::NewId => write!(out, "u32")?,
+                    Type::Destructor => panic!("An argument cannot have type \"destructor\"."),
+                }
+                if nullable {
+                    write!(out, ">")?;
+                }
+                writeln!(out, ",")?;
+            }
+            write!(out, "        }}")?;
+        }
+        writeln!(out, ",")?
+    }
+    writeln!(out, "    }}\n")?;
+
+    writeln!(out, "    impl {} {{", name)?;
+
+    /*
+     * opcode
+     */
+
+    writeln!(out, "        /// The opcode of this message")?;
+    writeln!(out, "        pub fn opcode(&self) -> u16 {{")?;
+    writeln!(out, "            match *self {{")?;
+    for (i, msg) in messages.iter().enumerate() {
+        write!(out, "                {} ", variant(name, msg))?;
+        if !msg.args.is_empty() {
+            write!(out, "{{ .. }} ")?;
+        }
+        writeln!(out, "=> {},", i)?;
+    }
+    writeln!(out, "            }}")?;
+    writeln!(out, "        }}\n")?;
+
+    /*
+     * parse
+     */
+
+    writeln!(
+        out,
+        r#"        /// Parse a message from its opcode and the words of its arguments
+        ///
+        /// The file descriptors of the message are taken from `fds`, in order."#
+    )?;
+    writeln!(out, "        pub fn parse<F: Iterator<Item = i32>>(")?;
+    writeln!(out, "            opcode: u16,")?;
+    writeln!(out, "            args: &[u32],")?;
+    writeln!(out, "            fds: &mut F,")?;
+    writeln!(out, "        ) -> Result<{}, WireError> {{", name)?;
+    if messages.is_empty() {
+        // no message to parse, and the enum cannot be instantiated
+        writeln!(out, "            Err(WireError::UnknownOpcode)")?;
+        writeln!(out, "        }}\n")?;
+    } else {
+        write_wire_parse_body(name, messages, out)?;
+    }
+
+    /*
+     * serialize
+     */
+
+    writeln!(
+        out,
+        r#"        /// Serialize this message, with its header, at the end of `words`
+        ///
+        /// The file descriptors of the message are appended to `fds`."#
+    )?;
+    writeln!(
+        out,
+        "        pub fn serialize(&self, sender_id: u32, words: &mut Vec<u32>, fds: &mut Vec<i32>) {{"
+    )?;
+    if messages.is_empty() {
+        writeln!(out, "            match *self {{}}")?;
+        writeln!(out, "        }}")?;
+    } else {
+        write_wire_serialize_body(name, messages, out)?;
+    }
+    writeln!(out, "    }}\n")?;
+
+    Ok(())
+}
+
+fn write_wire_parse_body<O: Write>(name: &str, messages: &[Message], out: &mut O) -> IOResult<()> {
+    writeln!(out, "            let mut reader = WireReader {{ words: args }};")?;
+    writeln!(out, "            let message = match opcode {{")?;
+    for (i, msg) in messages.iter().enumerate() {
+        if msg.args.is_empty() {
+            writeln!(out, "                {} => {},", i, variant(name, msg))?;
+            continue;
+        }
+        writeln!(out, "                {} => {} {{", i, variant(name, msg))?;
+        for a in &msg.args {
+            let nullable = a.allow_null && is_nullable(a.typ);
+            let value = match a.typ {
+                Type::Uint => "reader.word()?".to_owned(),
+                Type::Int => "reader.word()? as i32".to_owned(),
+                Type::Fixed => "f64::from(reader.word()? as i32) / 256.0".to_owned(),
+                Type::Fd => "fds.next().ok_or(WireError::MissingFd)?".to_owned(),
+                Type::Array => "reader.array()?".to_owned(),
+                Type::String if nullable => "reader.string()?".to_owned(),
+                Type::String => "reader.string()?.ok_or(WireError::NullArgument)?".to_owned(),
+                Type::Object if nullable => "reader.object()?".to_owned(),
+                Type::Object => "reader.object()?.ok_or(WireError::NullArgument)?".to_owned(),
+                Type::NewId if a.interface.is_none() => {
+                    "(reader.string()?.ok_or(WireError::NullArgument)?, reader.word()?, reader.word()?)"
+                        .to_owned()
+                }
+                Type::NewId => "reader.word()?".to_owned(),
+                Type::Destructor => panic!("An argument cannot have type \"destructor\"."),
+            };
+            writeln!(out, "                    {}: {},", a.name, value)?;
+        }
+        writeln!(out, "                }},")?;
+    }
+    writeln!(out, "                _ => return Err(WireError::UnknownOpcode),")?;
+    writeln!(out, "            }};")?;
+    writeln!(out, "            reader.finish()?;")?;
+    writeln!(out, "            Ok(message)")?;
+    writeln!(out, "        }}\n")?;
+    Ok(())
+}
+
+fn write_wire_serialize_body<O: Write>(name: &str, messages: &[Message], out: &mut O) -> IOResult<()> {
+    writeln!(out, "            let start = words.len();")?;
+    writeln!(out, "            words.push(sender_id);")?;
+    writeln!(out, "            words.push(0);")?;
+    writeln!(out, "            match *self {{")?;
+    for msg in messages {
+        if msg.args.is_empty() {
+            writeln!(out, "                {} => {{}}", variant(name, msg))?;
+            continue;
+        }
+        let bindings = msg
+            .args
+            .iter()
+            .map(|a| format!("ref {}", a.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "                {} {{ {} }} => {{", variant(name, msg), bindings)?;
+        for a in &msg.args {
+            let name = &a.name;
+            let nullable = a.allow_null && is_nullable(a.typ);
+            match a.typ {
+                Type::Uint => writeln!(out, "                    words.push(*{});", name)?,
+                Type::NewId if a.interface.is_some() => {
+                    writeln!(out, "                    words.push(*{});", name)?
+                }
+                Type::Int => writeln!(out, "                    words.push(*{} as u32);", name)?,
+                Type::Fixed => writeln!(
+                    out,
+                    "                    words.push((*{} * 256.0) as i32 as u32);",
+                    name
+                )?,
+                Type::Fd => writeln!(out, "                    fds.push(*{});", name)?,
+                Type::Array => writeln!(out, "                    wire_write_array(words, {});", name)?,
+                Type::String if nullable => writeln!(
+                    out,
+                    "                    wire_write_string(words, {}.as_ref().map(|s| &s[..]));",
+                    name
+                )?,
+                Type::String => writeln!(
+                    out,
+                    "                    wire_write_string(words, Some(&{}[..]));",
+                    name
+                )?,
+                Type::Object if nullable => {
+                    writeln!(out, "                    words.push({}.unwrap_or(0));", name)?
+                }
+                Type::Object => writeln!(out, "                    words.push(*{});", name)?,
+                Type::NewId => {
+                    writeln!(
+                        out,
+                        "                    wire_write_string(words, Some(&{}.0[..]));",
+                        name
+                    )?;
+                    writeln!(out, "                    words.push({}.1);", name)?;
+                    writeln!(out, "                    words.push({}.2);", name)?;
+                }
+                Type::Destructor => panic!("An argument cannot have type \"destructor\"."),
+            }
+        }
+        writeln!(out, "                }}")?;
+    }
+    writeln!(out, "            }}")?;
+    writeln!(out, "            let size = (4 * (words.len() - start)) as u32;")?;
+    writeln!(
+        out,
+        "            words[start + 1] = (size << 16) | u32::from(self.opcode());"
+    )?;
+    writeln!(out, "        }}")?;
+    Ok(())
+}
+
+// only strings and objects can be null on the wire
+fn is_nullable(typ: Type) -> bool {
+    typ == Type::String || typ == Type::Object
+}
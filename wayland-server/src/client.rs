@@ -1,9 +1,28 @@
 #[cfg(feature = "native_lib")]
 use wayland_sys::server::wl_client;
 
+use wayland_commons::socket::ConnectionStats;
+
 use imp::ClientInner;
 
-use {Interface, NewResource, UserDataMap};
+use {Interface, NewResource, Resource, UserDataMap};
+
+/// Credentials of the process of a client
+///
+/// These are the credentials of the process which connected to the server, as they
+/// were when it connected.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    /// Process id
+    ///
+    /// It is 0 with the rust implementation on FreeBSD, NetBSD, DragonFly and macOS,
+    /// which do not provide it.
+    pub pid: i32,
+    /// User id
+    pub uid: u32,
+    /// Group id
+    pub gid: u32,
+}
 
 /// A handle to a client connected to your server
 ///
@@ -48,6 +67,22 @@ impl Client {
         self.inner.flush()
     }
 
+    /// Retrieve the credentials of the process of this client
+    ///
+    /// They are obtained from the socket of the client. Returns `None` if the client is
+    /// dead or its socket cannot provide them.
+    pub fn credentials(&self) -> Option<Credentials> {
+        self.inner.credentials()
+    }
+
+    /// Counters of the data exchanged with this client so far
+    ///
+    /// Returns `None` if the client is dead. This is only supported by the rust
+    /// implementation: with the `native_lib` cargo feature, this always returns `None`.
+    pub fn connection_stats(&self) -> Option<ConnectionStats> {
+        self.inner.connection_stats()
+    }
+
     /// Kills this client
     ///
     /// Does nothing if the client is already dead
@@ -62,6 +97,26 @@ impl Client {
         self.inner.user_data_map()
     }
 
+    /// Access the payload of a given type associated to this client
+    ///
+    /// Returns `None` if no payload of this type is attached to this client, or if it
+    /// is not accessible from this thread. This is a shorthand for `data_map().get()`.
+    pub fn data<UD: 'static>(&self) -> Option<&UD> {
+        self.data_map().get::<UD>()
+    }
+
+    /// Attach a payload to this client
+    ///
+    /// This lets the compositor associate its per-client state, like the focus of its
+    /// seats or the list of its toplevels, to the client handle. A client can hold one
+    /// payload of each type, shared by all its handles and accessed with `data()`.
+    ///
+    /// Returns `false` and drops the value if this client already holds a payload of
+    /// this type.
+    pub fn set_user_data<UD: Send + Sync + 'static>(&self, value: UD) -> bool {
+        self.data_map().insert_if_missing_threadsafe(move || value)
+    }
+
     /// Add a destructor for this client
     ///
     /// This closure will be called when the client disconnects or is killed,
@@ -85,4 +140,17 @@ impl Client {
     pub fn create_resource<I: Interface>(&self, version: u32) -> Option<NewResource<I>> {
         self.inner.create_resource::<I>(version).map(NewResource::wrap)
     }
+
+    /// List the live resources of this client with a given interface
+    ///
+    /// This includes the resources which are not implemented yet. The list is empty once
+    /// the client is dead: to clean up after a disconnected client, rely on the destructors
+    /// of its resources, or on the state attached with `set_user_data()`.
+    pub fn resources_for_interface<I: Interface>(&self) -> Vec<Resource<I>> {
+        self.inner
+            .resources_for_interface::<I>()
+            .into_iter()
+            .map(Resource::wrap)
+            .collect()
+    }
 }
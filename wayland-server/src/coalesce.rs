@@ -0,0 +1,187 @@
+//! Coalescing of high-rate input events
+//!
+//! A compositor forwarding raw input can send `wl_pointer.motion` and `wl_pointer.axis`
+//! events at the rate of the input devices, much faster than clients render. The
+//! `PointerCoalescer` holds the events of a pointer back until the compositor flushes
+//! it, typically once per output frame, and merges the consecutive pointer frames that
+//! only contain motion and axis events: the motions are replaced by the last one, and
+//! the axis values are summed. The other events, like buttons, are never merged, and the
+//! order of the events is preserved around them.
+//!
+//! Pointers of version 5 or higher group their events with `wl_pointer.frame`, a merged
+//! frame then contains one motion and one axis event per axis at most. The events sent
+//! to older pointers are merged one by one.
+
+use protocol::wl_pointer::{Event, WlPointer};
+use Resource;
+
+// the events of a pointer frame
+struct Group {
+    events: Vec<Event>,
+    // whether the frame was terminated by a `wl_pointer.frame` event
+    framed: bool,
+}
+
+impl Group {
+    // whether this frame can be merged with an other one
+    fn mergeable(&self) -> bool {
+        self.framed
+            && self.events.iter().all(|event| match *event {
+                Event::Motion { .. } | Event::Axis { .. } | Event::Frame => true,
+                _ => false,
+            })
+    }
+
+    // merge a following frame into this one
+    fn merge(&mut self, next: Group) {
+        for event in next.events {
+            match event {
+                Event::Motion {
+                    time,
+                    surface_x,
+                    surface_y,
+                } => {
+                    let previous = self.events.iter().position(|event| match *event {
+                        Event::Motion { .. } => true,
+                        _ => false,
+                    });
+                    match previous {
+                        Some(index) => {
+                            self.events[index] = Event::Motion {
+                                time,
+                                surface_x,
+                                surface_y,
+                            }
+                        }
+                        None => self.insert(Event::Motion {
+                            time,
+                            surface_x,
+                            surface_y,
+                        }),
+                    }
+                }
+                Event::Axis { time, axis, value } => {
+                    let previous = self.events.iter().position(|event| match *event {
+                        Event::Axis { axis: a, .. } => a == axis,
+                        _ => false,
+                    });
+                    match previous {
+                        Some(index) => {
+                            let sum = match self.events[index] {
+                                Event::Axis { value: previous, .. } => previous + value,
+                                _ => unreachable!(),
+                            };
+                            self.events[index] = Event::Axis {
+                                time,
+                                axis,
+                                value: sum,
+                            }
+                        }
+                        None => self.insert(Event::Axis { time, axis, value }),
+                    }
+                }
+                // only the frame events remain, this group already has its own
+                _ => {}
+            }
+        }
+    }
+
+    // add an event to this frame, before its `wl_pointer.frame`
+    fn insert(&mut self, event: Event) {
+        let index = match self.events.last() {
+            Some(&Event::Frame) => self.events.len() - 1,
+            _ => self.events.len(),
+        };
+        self.events.insert(index, event);
+    }
+}
+
+/// A `wl_pointer` whose motion and axis events are coalesced
+///
+/// See the module documentation for details.
+pub struct PointerCoalescer {
+    pointer: Resource<WlPointer>,
+    groups: Vec<Group>,
+}
+
+impl PointerCoalescer {
+    /// Coalesce the events sent to a pointer
+    pub fn new(pointer: Resource<WlPointer>) -> PointerCoalescer {
+        PointerCoalescer {
+            pointer,
+            groups: Vec::new(),
+        }
+    }
+
+    /// The pointer the events are sent to
+    pub fn pointer(&self) -> &Resource<WlPointer> {
+        &self.pointer
+    }
+
+    /// Queue an event, it is sent by the next `flush()`
+    pub fn send(&mut self, event: Event) {
+        let frame = match event {
+            Event::Frame => true,
+            // the events of the older pointers are not grouped
+            _ => self.pointer.version() < 5,
+        };
+        let open = match self.groups.last() {
+            Some(group) => !group.framed,
+            None => false,
+        };
+        if open {
+            self.groups.last_mut().unwrap().events.push(event);
+        } else {
+            self.groups.push(Group {
+                events: vec![event],
+                framed: false,
+            });
+        }
+        if frame {
+            self.groups.last_mut().unwrap().framed = true;
+            self.merge_last();
+        }
+    }
+
+    /// Send the queued events to the pointer
+    ///
+    /// This is typically done at the frame boundaries of the compositor, before
+    /// `Display::flush_clients()`. The events of a pointer frame which was not terminated
+    /// yet are kept for the next flush, as the protocol requires the events of a frame to
+    /// be sent together.
+    pub fn flush(&mut self) {
+        let open = match self.groups.last() {
+            Some(group) => !group.framed,
+            None => false,
+        };
+        let kept = if open { self.groups.pop() } else { None };
+        for group in self.groups.drain(..) {
+            for event in group.events {
+                self.pointer.send(event);
+            }
+        }
+        self.groups.extend(kept);
+    }
+
+    // merge the last frame with the previous one if possible
+    fn merge_last(&mut self) {
+        let len = self.groups.len();
+        if len < 2 || !self.groups[len - 2].mergeable() || !self.groups[len - 1].mergeable() {
+            return;
+        }
+        let last = self.groups.pop().unwrap();
+        self.groups.last_mut().unwrap().merge(last);
+    }
+}
+
+impl Drop for PointerCoalescer {
+    fn drop(&mut self) {
+        // the events of an unterminated frame are sent anyway, the protocol does not let
+        // them be dropped
+        for group in self.groups.drain(..) {
+            for event in group.events {
+                self.pointer.send(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// The data of the event loop, made available to the implementations while the event loop
+// dispatches the requests of the clients.
+
+use std::any::TypeId;
+use std::cell::Cell;
+
+type RawState = Option<(TypeId, *mut ())>;
+
+thread_local! {
+    // the data of the event loop currently dispatching on this thread, if any
+    static STATE: Cell<RawState> = Cell::new(None);
+}
+
+// restores the previous state when dropped, even if an implementation panics
+struct Restore(RawState);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        STATE.with(|state| state.set(self.0));
+    }
+}
+
+/// Run a closure with the data of the event loop available as dispatch state
+pub(crate) fn with_state<Data: 'static, T, F: FnOnce() -> T>(data: &mut Data, f: F) -> T {
+    let new_state = Some((TypeId::of::<Data>(), data as *mut Data as *mut ()));
+    let _restore = Restore(STATE.with(|state| {
+        let previous = state.get();
+        state.set(new_state);
+        previous
+    }));
+    f()
+}
+
+/// Borrow the dispatch state
+///
+/// The closure is given `None` if no event loop is dispatching on this thread, or if its
+/// state is already borrowed: the state is unavailable for the duration of the closure.
+///
+/// **Panics** if the data of the event loop is not of type `Data`.
+pub(crate) fn borrow_state<Data: 'static, T, F: FnOnce(Option<&mut Data>) -> T>(f: F) -> T {
+    let current = STATE.with(|state| {
+        let current = state.get();
+        state.set(None);
+        current
+    });
+    let _restore = Restore(current);
+    match current {
+        Some((type_id, ptr)) => {
+            if type_id != TypeId::of::<Data>() {
+                panic!("Attempted to borrow the dispatch state with an other type than the event loop data.");
+            }
+            // the pointer comes from the `&mut Data` given to `with_state()`, which is still
+            // borrowed as it has not returned yet, and was removed from `STATE` until we return
+            f(Some(unsafe { &mut *(ptr as *mut Data) }))
+        }
+        None => f(None),
+    }
+}
@@ -2,19 +2,55 @@ use std::cell::RefCell;
 use std::env;
 use std::ffi::{OsStr, OsString};
 use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::{IntoRawFd, RawFd};
 use std::path::PathBuf;
 use std::rc::{Rc, Weak};
+use std::sync::Arc;
 
 #[cfg(feature = "native_lib")]
 use wayland_sys::server::wl_display;
 
 use imp::DisplayInner;
 
+use wayland_commons::socket::ConnectionStats;
+use wayland_commons::trace::{Direction, LoggedMessage, ProtocolLogger};
+
+use protocol::wl_fixes::{self, WlFixes};
+
+use ratelimit::{RateLimits, RateLimitsError};
 use {Client, Global, Interface, NewResource};
 
 use calloop::LoopHandle;
 
+/// Strategy used by `Display::flush_clients()` to send the pending events to the clients
+///
+/// Flushing never blocks: the events of a client which does not read its socket fast enough
+/// stay buffered once its socket is full, until it is flushed again. The strategies decide
+/// in which order the clients are served, and whether the events left behind wait for the
+/// next `Display::flush_clients()`.
+///
+/// This is only supported by the rust implementation: with the `native_lib` feature,
+/// `libwayland-server` flushes the clients in its own order, and the strategy has no effect.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FlushScheduling {
+    /// Flush the clients one after the other
+    ///
+    /// This is the default.
+    Sequential,
+    /// Flush first the clients that are ready to receive data, then the others
+    ///
+    /// The clients whose socket fills up while being flushed are flushed again after the
+    /// others. The clients are also flushed in a rotating order, so that none of them is
+    /// always served last.
+    Fair,
+    /// Like `Fair`, but the clients that are not ready to receive data, or whose socket fills
+    /// up, are flushed by a writer thread as soon as they are ready
+    ///
+    /// Their events then do not wait for the next `Display::flush_clients()`.
+    Threaded,
+}
+
 /// The wayland display
 ///
 /// This is the core of your wayland server, this object must
@@ -127,6 +163,23 @@ impl Display {
         ))
     }
 
+    /// Create the `wl_fixes` global
+    ///
+    /// This global is fully handled by this library, and allows clients to destroy
+    /// their `wl_registry` objects, which is not possible otherwise.
+    pub fn create_fixes_global(&mut self) -> Global<WlFixes> {
+        self.create_global::<WlFixes, _>(1, |fixes, _| {
+            fixes.implement(
+                |request, _| match request {
+                    wl_fixes::Request::Destroy => {}
+                    wl_fixes::Request::DestroyRegistry { registry } => registry.inner.destroy_registry(),
+                },
+                None::<fn(_)>,
+                (),
+            );
+        })
+    }
+
     /// Flush events to the clients
     ///
     /// Will send as many pending events as possible to the respective sockets of the clients.
@@ -134,6 +187,67 @@ impl Display {
     pub fn flush_clients(&self) {
         self.inner.borrow_mut().flush_clients()
     }
+
+    /// Set the strategy used by `flush_clients()`
+    ///
+    /// See `FlushScheduling` for the available strategies. With the `native_lib` cargo
+    /// feature, this does nothing.
+    pub fn set_flush_scheduling(&mut self, scheduling: FlushScheduling) {
+        self.inner.borrow_mut().set_flush_scheduling(scheduling)
+    }
+
+    /// Set the logger of the messages exchanged with the clients
+    ///
+    /// Once set, the requests received from the clients and the events sent to them are given
+    /// to `logger` rather than printed on stdout when the `WAYLAND_DEBUG` environment variable
+    /// is set, with their trace id. Providing `None` restores the `WAYLAND_DEBUG` log. See the
+    /// `wayland_commons::trace` module for details.
+    ///
+    /// The logger is invoked while internal locks of the library are held: it must not send
+    /// events.
+    ///
+    /// With the `native_lib` cargo feature, the logger is given the messages by the protocol
+    /// logger of `libwayland-server`: their trace ids are always 0, and `WAYLAND_DEBUG` still
+    /// prints them.
+    pub fn set_protocol_logger<F>(&mut self, logger: Option<F>)
+    where
+        F: Fn(Direction, &LoggedMessage) + Send + Sync + 'static,
+    {
+        self.inner
+            .borrow_mut()
+            .set_protocol_logger(logger.map(|l| Arc::new(l) as ProtocolLogger))
+    }
+
+    /// Set the limits of the rate of the requests of the clients
+    ///
+    /// Providing `None` removes the limits. Setting new limits refills the buckets of all
+    /// the clients. See the `ratelimit` module for details. On error, the previous limits
+    /// are kept.
+    ///
+    /// This is only supported by the rust implementation: with the `native_lib` cargo feature,
+    /// this fails with `RateLimitsError::Unsupported`.
+    pub fn set_rate_limits(&mut self, limits: Option<RateLimits>) -> Result<(), RateLimitsError> {
+        self.inner.borrow_mut().set_rate_limits(limits)
+    }
+
+    /// Number of clients currently connected to this display
+    ///
+    /// This is only supported by the rust implementation: with the `native_lib` cargo feature,
+    /// this returns `None`.
+    pub fn client_count(&self) -> Option<usize> {
+        self.inner.borrow().client_count()
+    }
+
+    /// Counters of the data exchanged with the clients so far
+    ///
+    /// They add up the counters of all the clients that ever connected to this display,
+    /// including the dead ones. The `metrics` module renders them for Prometheus.
+    ///
+    /// This is only supported by the rust implementation: with the `native_lib` cargo feature,
+    /// this returns `None`.
+    pub fn connection_stats(&self) -> Option<ConnectionStats> {
+        self.inner.borrow().connection_stats()
+    }
 }
 
 impl Display {
@@ -147,6 +261,10 @@ impl Display {
     /// If a name is provided, it is used. Otherwise, if `WAYLAND_DISPLAY` environment
     /// variable is set, its contents are used as socket name. Otherwise, `wayland-0` is used.
     ///
+    /// The name is reserved by a `.lock` file next to the socket, which allows replacing a
+    /// socket left behind by a compositor which crashed. Both are removed when the display
+    /// is dropped. A display can listen on several sockets.
+    ///
     /// Errors if `name` contains an interior null, or if `XDG_RUNTIME_DIR` is not set,
     /// or if specified could not be bound (either it is already used or the compositor
     /// does not have the rights to create it).
@@ -196,10 +314,93 @@ impl Display {
     /// The fd must be properly set to CLOEXEC and bound to a socket file
     /// with both bind() and listen() already called. An error is returned
     /// otherwise.
+    ///
+    /// As wayland clients need to be able to pass file descriptors to the server,
+    /// the socket must be a unix stream socket. Other kinds of sockets (like TCP
+    /// sockets) are refused with an `InvalidInput` error.
     pub unsafe fn add_socket_fd(&self, fd: RawFd) -> IoResult<()> {
+        check_listening_socket(fd)?;
         self.inner.borrow_mut().add_socket_fd(fd)
     }
 
+    /// Add a listening socket in the abstract namespace to this display
+    ///
+    /// Wayland clients will be able to connect to your compositor from this socket,
+    /// using the abstract unix socket address `name`. As such sockets do not live on
+    /// the filesystem, `XDG_RUNTIME_DIR` does not need to be set.
+    ///
+    /// Errors if the name is already in use.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn add_socket_abstract<S>(&mut self, name: S) -> IoResult<()>
+    where
+        S: AsRef<OsStr>,
+    {
+        use nix::sys::socket;
+
+        let addr = socket::UnixAddr::new_abstract(name.as_ref().as_bytes()).map_err(nix_to_io)?;
+        let fd = socket::socket(
+            socket::AddressFamily::Unix,
+            socket::SockType::Stream,
+            socket::SockFlag::SOCK_CLOEXEC,
+            None,
+        ).map_err(nix_to_io)?;
+        let ret = socket::bind(fd, &socket::SockAddr::Unix(addr)).and_then(|()| socket::listen(fd, 128));
+        if let Err(e) = ret {
+            let _ = ::nix::unistd::close(fd);
+            return Err(nix_to_io(e));
+        }
+        unsafe { self.add_socket_fd(fd) }
+    }
+
+    /// Add the listening sockets passed by the service manager to this display
+    ///
+    /// This implements the socket activation protocol of systemd: if the `LISTEN_PID`
+    /// environment variable matches the pid of the compositor, the `LISTEN_FDS` file
+    /// descriptors starting at fd 3 are inherited listening sockets, and are all added
+    /// to this display. Once they are validated, these variables are removed from the
+    /// environment, so that they are not inherited by child processes.
+    ///
+    /// Returns the number of sockets added, which is 0 if no sockets were passed.
+    ///
+    /// Errors if the variables are malformed or if one of the passed fds is not a
+    /// listening unix stream socket. The environment is left untouched in this case.
+    pub fn add_sockets_from_env(&mut self) -> IoResult<usize> {
+        const LISTEN_FDS_START: RawFd = 3;
+
+        let pid = match env_number("LISTEN_PID")? {
+            Some(pid) => pid,
+            None => return Ok(0),
+        };
+        // pids are positive, so a value out of the range of pid_t never matches
+        if ::nix::unistd::Pid::from_raw(pid as ::libc::pid_t) != ::nix::unistd::getpid() {
+            // these sockets are not meant for us
+            return Ok(0);
+        }
+        let count = env_number("LISTEN_FDS")?.unwrap_or(0);
+        let end = if count <= (RawFd::max_value() - LISTEN_FDS_START) as u32 {
+            LISTEN_FDS_START + count as RawFd
+        } else {
+            return Err(IoError::new(ErrorKind::InvalidInput, "LISTEN_FDS is out of range."));
+        };
+        for fd in LISTEN_FDS_START..end {
+            check_listening_socket(fd)?;
+        }
+
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+
+        for fd in LISTEN_FDS_START..end {
+            // the service manager does not set CLOEXEC on the fds it passes
+            ::nix::fcntl::fcntl(
+                fd,
+                ::nix::fcntl::FcntlArg::F_SETFD(::nix::fcntl::FdFlag::FD_CLOEXEC),
+            ).map_err(nix_to_io)?;
+            unsafe { self.add_socket_fd(fd)? };
+        }
+        Ok(count as usize)
+    }
+
     /// Create a new client to this display from an already-existing connected Fd
     pub unsafe fn create_client(&self, fd: RawFd) -> Client {
         Client::make(self.inner.borrow_mut().create_client(fd))
@@ -214,6 +415,65 @@ impl Display {
     }
 }
 
+fn nix_to_io(err: ::nix::Error) -> IoError {
+    match err {
+        ::nix::Error::Sys(errno) => errno.into(),
+        other => IoError::new(ErrorKind::InvalidInput, other),
+    }
+}
+
+// read a socket activation variable, which must be a decimal number if it is set
+fn env_number(name: &str) -> IoResult<Option<u32>> {
+    match env::var(name) {
+        Ok(value) => value.parse::<u32>().map(Some).map_err(|_| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a valid number: {:?}", name, value),
+            )
+        }),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not a valid number.", name),
+        )),
+    }
+}
+
+// Wayland connections need to support fd passing, so only allow listening
+// unix stream sockets
+fn check_listening_socket(fd: RawFd) -> IoResult<()> {
+    use nix::sys::socket::{getsockname, getsockopt, sockopt, SockAddr};
+
+    // query SO_TYPE by hand, the nix getter cannot represent all socket types
+    let mut sock_type: ::libc::c_int = 0;
+    let mut len = ::std::mem::size_of::<::libc::c_int>() as ::libc::socklen_t;
+    let ret = unsafe {
+        ::libc::getsockopt(
+            fd,
+            ::libc::SOL_SOCKET,
+            ::libc::SO_TYPE,
+            &mut sock_type as *mut ::libc::c_int as *mut _,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(IoError::last_os_error());
+    }
+    match getsockname(fd).map_err(nix_to_io)? {
+        SockAddr::Unix(_) if sock_type == ::libc::SOCK_STREAM => {}
+        _ => {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "Wayland sockets must be unix stream sockets, to allow passing file descriptors.",
+            ))
+        }
+    }
+    if !getsockopt(fd, sockopt::AcceptConn).map_err(nix_to_io)? {
+        return Err(IoError::new(ErrorKind::InvalidInput, "The socket is not listening."));
+    }
+    Ok(())
+}
+
 pub(crate) fn get_runtime_dir() -> IoResult<PathBuf> {
     match env::var_os("XDG_RUNTIME_DIR") {
         Some(s) => Ok(s.into()),
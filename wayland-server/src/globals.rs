@@ -1,4 +1,4 @@
-use Interface;
+use {Interface, Resource};
 
 use imp::GlobalInner;
 
@@ -23,4 +23,22 @@ impl<I: Interface> Global<I> {
     pub fn destroy(self) {
         self.inner.destroy()
     }
+
+    /// Destroy the associated global object and invalidate its resources
+    ///
+    /// In addition to what `destroy()` does, all resources that clients
+    /// bound from this global and that are still alive are marked as defunct:
+    /// the requests they receive afterwards are no longer dispatched to their
+    /// implementation, but destructor requests still free the protocol objects.
+    ///
+    /// The provided hook is invoked once for each invalidated resource, giving
+    /// you the occasion to release any compositor state associated with it.
+    pub fn destroy_and_invalidate<F>(self, mut hook: F)
+    where
+        F: FnMut(Resource<I>),
+    {
+        for resource in self.inner.destroy_and_invalidate() {
+            hook(Resource::wrap(resource));
+        }
+    }
 }
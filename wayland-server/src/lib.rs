@@ -55,6 +55,30 @@
 //! a request), unless the exact message received is a destructor (which is indicated in the API
 //! documentations).
 //!
+//! ### Validation of the requests
+//!
+//! The arguments of the requests are checked as they are parsed, before reaching your
+//! implementations: the objects must exist, the non-nullable ones must be given, and the enum
+//! arguments must be known values of their enum. A request failing these checks is not
+//! dispatched, and the client is sent an `invalid_method` protocol error on the object
+//! receiving it.
+//!
+//! The protocol descriptions do not bound the sizes of the array arguments, only the size of
+//! the messages limits them. The checks depending on the semantics of an array, like its length
+//! being a multiple of the size of its elements, are left to the implementations, which post the
+//! error defined by their protocol.
+//!
+//! ### Dispatch state
+//!
+//! The implementations of a server typically all need access to its global state. Rather than
+//! sharing it behind an `Arc<Mutex<_>>` locked by every implementation, you can make it the data
+//! of the event loop the `Display` is inserted in, and implement your resources with
+//! `NewResource::implement_with_state()`: the implementations are then given a `&mut` reference
+//! to this data along with each request.
+//!
+//! The implementation of each resource itself is still kept behind a lock, as the resources can
+//! be implemented and destroyed from any thread.
+//!
 //! ## Event loops and general structure
 //!
 //! The core of your server is the `Display` object. It represent the ability of your program to
@@ -64,6 +88,31 @@
 //! To properly function, this wayland implementation also needs an event loop structure,
 //! which is here provided by the `calloop` crate. It is a public dependency and is reexported
 //! as `wayland_server::calloop`.
+//!
+//! ## io_uring
+//!
+//! The experimental `io_uring` cargo feature makes the rust implementation send and receive
+//! the messages of the clients through io_urings rather than with the `sendmsg` and
+//! `recvmsg` system calls, if the kernel allows it. It has no effect with the `native_lib`
+//! feature.
+//!
+//! ## Auxiliary modules
+//!
+//! The `region` module provides the region algebra needed to track the `wl_region` objects,
+//! the opaque and input regions and the damage of the surfaces.
+//!
+//! The `coalesce` module provides the `PointerCoalescer`, which merges the pointer motion
+//! and axis events sent to a client within a frame, to reduce the traffic of compositors
+//! forwarding raw input.
+//!
+//! The `transfer` module relays the data of the selection between the pipes of two clients
+//! without blocking the event loop, with limits on its size and duration.
+//!
+//! The `ratelimit` module limits the rate at which the clients can send requests, to throttle
+//! the clients flooding the compositor with them.
+//!
+//! The `metrics` module, enabled by the `metrics` cargo feature, renders the counters of the
+//! clients in the Prometheus text format.
 
 #![warn(missing_docs)]
 
@@ -83,15 +132,27 @@ extern crate wayland_commons;
 extern crate wayland_sys;
 
 mod client;
+mod dispatch_state;
 mod display;
 mod globals;
 mod resource;
+mod versioned;
+
+pub mod coalesce;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod ratelimit;
+pub mod region;
+pub mod transfer;
 
-pub use client::Client;
-pub use display::{Display, DisplayToken};
+pub use client::{Client, Credentials};
+pub use display::{Display, DisplayToken, FlushScheduling};
 pub use globals::Global;
 pub use resource::{NewResource, Resource};
+pub use versioned::Versioned;
 
+pub use wayland_commons::socket::ConnectionStats;
+pub use wayland_commons::trace::{Direction, LoggedMessage};
 pub use wayland_commons::utils::UserDataMap;
 pub use wayland_commons::{AnonymousObject, Interface, MessageGroup, NoMessage};
 
@@ -136,8 +197,9 @@ mod generated {
     #[cfg(feature = "native_lib")]
     pub mod c_api {
         pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
-        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, FdKind, Message, MessageDesc};
         pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
+        pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
         pub(crate) use wayland_sys as sys;
         pub(crate) use {NewResource, Resource, ResourceMap};
         include!(concat!(env!("OUT_DIR"), "/wayland_c_api.rs"));
@@ -145,8 +207,9 @@ mod generated {
     #[cfg(not(feature = "native_lib"))]
     pub mod rust_api {
         pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
-        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, FdKind, Message, MessageDesc};
         pub(crate) use wayland_commons::{AnonymousObject, Interface, MessageGroup};
+        pub(crate) use wayland_commons::version::{EnumEntryVersion, MessageVersion, VersionMatrix};
         pub(crate) use {NewResource, Resource, ResourceMap};
         include!(concat!(env!("OUT_DIR"), "/wayland_rust_api.rs"));
     }
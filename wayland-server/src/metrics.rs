@@ -0,0 +1,39 @@
+//! Export of metrics in the Prometheus text format
+//!
+//! This module renders the counters of the clients of a display in the exposition format
+//! of Prometheus, for deployments scraping the metrics of their compositor. It is enabled by
+//! the `metrics` cargo feature.
+//!
+//! `render_metrics()` writes the following families:
+//!
+//! - `wayland_server_clients`: the number of connected clients, see `Display::client_count()`
+//! - `wayland_server_sent_messages_total`, `wayland_server_received_messages_total`,
+//!   `wayland_server_sent_bytes_total`, `wayland_server_received_bytes_total`,
+//!   `wayland_server_sent_fds_total` and `wayland_server_received_fds_total`: the counters
+//!   of all the clients that ever connected, see `Display::connection_stats()`
+//!
+//! With the `native_lib` cargo feature, `libwayland-server.so` exposes none of them, and
+//! nothing is written.
+
+use std::io::{self, Write};
+
+use wayland_commons::metrics::{family, write_connection_stats, MetricType};
+
+use Display;
+
+/// Render the metrics of a display
+pub fn render_metrics<W: Write>(display: &Display, out: &mut W) -> io::Result<()> {
+    if let Some(count) = display.client_count() {
+        family(
+            out,
+            "wayland_server_clients",
+            "Number of connected clients.",
+            MetricType::Gauge,
+        )?
+        .sample(&[], count as u64)?;
+    }
+    if let Some(stats) = display.connection_stats() {
+        write_connection_stats(out, "wayland_server", &[(&[], stats)])?;
+    }
+    Ok(())
+}
@@ -1,12 +1,14 @@
+use std::ffi::CStr;
 use std::os::raw::c_void;
 use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use wayland_commons::socket::ConnectionStats;
 use wayland_sys::server::*;
 
-use super::resource::NewResourceInner;
-use {Interface, UserDataMap};
+use super::resource::{NewResourceInner, ResourceInner};
+use {Credentials, Interface, UserDataMap};
 
 pub(crate) struct ClientInternal {
     alive: AtomicBool,
@@ -85,6 +87,29 @@ impl ClientInner {
         }
     }
 
+    pub(crate) fn credentials(&self) -> Option<Credentials> {
+        if !self.alive() {
+            return None;
+        }
+        let (mut pid, mut uid, mut gid) = (0, 0, 0);
+        unsafe {
+            ffi_dispatch!(
+                WAYLAND_SERVER_HANDLE,
+                wl_client_get_credentials,
+                self.ptr,
+                &mut pid,
+                &mut uid,
+                &mut gid
+            );
+        }
+        Some(Credentials { pid, uid, gid })
+    }
+
+    pub(crate) fn connection_stats(&self) -> Option<ConnectionStats> {
+        // libwayland-server does not count the exchanged data
+        None
+    }
+
     pub(crate) fn kill(&self) {
         if !self.alive() {
             return;
@@ -129,6 +154,34 @@ impl ClientInner {
             Some(NewResourceInner::from_c_ptr(ptr))
         }
     }
+
+    pub(crate) fn resources_for_interface<I: Interface>(&self) -> Vec<ResourceInner> {
+        if !self.alive() {
+            return Vec::new();
+        }
+        let mut resources: Vec<*mut wl_resource> = Vec::new();
+        unsafe {
+            ffi_dispatch!(
+                WAYLAND_SERVER_HANDLE,
+                wl_client_for_each_resource,
+                self.ptr,
+                collect_resource,
+                &mut resources as *mut Vec<*mut wl_resource> as *mut c_void
+            );
+            resources
+                .into_iter()
+                .filter(|&ptr| {
+                    let class = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_class, ptr);
+                    CStr::from_ptr(class).to_bytes() == I::NAME.as_bytes()
+                }).map(|ptr| ResourceInner::from_c_ptr::<I>(ptr))
+                .collect()
+        }
+    }
+}
+
+unsafe extern "C" fn collect_resource(resource: *mut wl_resource, data: *mut c_void) -> wl_iterator_result {
+    (*(data as *mut Vec<*mut wl_resource>)).push(resource);
+    wl_iterator_result::WL_ITERATOR_CONTINUE
 }
 
 unsafe extern "C" fn client_destroy(listener: *mut wl_listener, _data: *mut c_void) {
@@ -7,6 +7,10 @@ use std::os::unix::io::RawFd;
 use std::ptr;
 use std::rc::Rc;
 
+use wayland_commons::socket::ConnectionStats;
+use wayland_commons::trace::{Direction, LoggedMessage, ProtocolLogger};
+use wayland_commons::wire::{Argument, Message};
+use wayland_sys::common::wl_argument;
 use wayland_sys::server::*;
 
 use calloop::generic::Generic;
@@ -17,19 +21,22 @@ use Fd;
 use super::globals::GlobalData;
 use super::{ClientInner, GlobalInner};
 
-use display::get_runtime_dir;
+use display::{get_runtime_dir, FlushScheduling};
+use ratelimit::{RateLimits, RateLimitsError};
 use {Interface, NewResource};
 
 pub(crate) struct DisplayInner {
     pub(crate) ptr: *mut wl_display,
     source: Option<Source<Generic<Fd>>>,
     rust_globals: Rc<RefCell<Vec<*mut wl_global>>>,
+    logger: Option<(*mut wl_protocol_logger, Box<ProtocolLogger>)>,
 }
 
 impl Drop for DisplayInner {
     fn drop(&mut self) {
         {
             self.source.take().map(|s| s.remove());
+            self.set_protocol_logger(None);
             unsafe {
                 ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_destroy, self.ptr);
             }
@@ -68,8 +75,10 @@ impl DisplayInner {
 
             let source = Some(
                 handle
-                    .insert_source(evtsrc, move |_, _| {
-                        ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_loop_dispatch, evl_ptr, 0);
+                    .insert_source(evtsrc, move |_, data| {
+                        ::dispatch_state::with_state(data, || {
+                            ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_loop_dispatch, evl_ptr, 0);
+                        })
                     }).unwrap(),
             );
 
@@ -77,6 +86,7 @@ impl DisplayInner {
                 ptr,
                 source,
                 rust_globals,
+                logger: None,
             }))
         }
     }
@@ -118,6 +128,44 @@ impl DisplayInner {
         unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_flush_clients, self.ptr) };
     }
 
+    pub(crate) fn set_protocol_logger(&mut self, logger: Option<ProtocolLogger>) {
+        if let Some((ptr, _)) = self.logger.take() {
+            unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_protocol_logger_destroy, ptr) };
+        }
+        if let Some(logger) = logger {
+            let data = Box::new(logger);
+            let ptr = unsafe {
+                ffi_dispatch!(
+                    WAYLAND_SERVER_HANDLE,
+                    wl_display_add_protocol_logger,
+                    self.ptr,
+                    protocol_logger,
+                    &*data as *const ProtocolLogger as *mut _
+                )
+            };
+            self.logger = Some((ptr, data));
+        }
+    }
+
+    pub(crate) fn set_flush_scheduling(&mut self, _scheduling: FlushScheduling) {
+        // libwayland never blocks when flushing clients
+    }
+
+    pub(crate) fn client_count(&self) -> Option<usize> {
+        // libwayland-server does not expose its list of clients
+        None
+    }
+
+    pub(crate) fn connection_stats(&self) -> Option<ConnectionStats> {
+        // libwayland-server does not count the exchanged data
+        None
+    }
+
+    pub(crate) fn set_rate_limits(&mut self, _limits: Option<RateLimits>) -> Result<(), RateLimitsError> {
+        // libwayland-server does not let the dispatching of a client be suspended
+        Err(RateLimitsError::Unsupported)
+    }
+
     pub(crate) fn add_socket<S>(&mut self, name: Option<S>) -> IoResult<()>
     where
         S: AsRef<OsStr>,
@@ -190,3 +238,83 @@ unsafe extern "C" fn client_created(_listener: *mut wl_listener, data: *mut c_vo
     // init the client
     let _client = ClientInner::from_ptr(data as *mut wl_client);
 }
+
+unsafe extern "C" fn protocol_logger(
+    data: *mut c_void,
+    direction: wl_protocol_logger_type,
+    message: *const wl_protocol_logger_message,
+) {
+    // We don't need to worry about panic-safeness, because if there is a panic,
+    // we'll abort the process, so no access to corrupted data is possible.
+    let ret = ::std::panic::catch_unwind(move || {
+        let logger = &*(data as *const ProtocolLogger);
+        let message = &*message;
+        let desc = &*message.message;
+        let interface = CStr::from_ptr(ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_resource_get_class,
+            message.resource
+        ));
+        let name = CStr::from_ptr(desc.name);
+        let args = if message.arguments_count > 0 {
+            ::std::slice::from_raw_parts(message.arguments, message.arguments_count as usize)
+        } else {
+            &[]
+        };
+        let signature = CStr::from_ptr(desc.signature).to_bytes();
+        let msg = Message {
+            sender_id: ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_id, message.resource),
+            opcode: message.message_opcode as u16,
+            args: signature
+                .iter()
+                // skip the versions and the nullable markers
+                .filter(|&&c| c != b'?' && (c < b'0' || c > b'9'))
+                .zip(args)
+                .map(|(&c, arg)| parse_argument(c, arg))
+                .collect(),
+        };
+        logger(
+            match direction {
+                wl_protocol_logger_type::WL_PROTOCOL_LOGGER_REQUEST => Direction::Received,
+                wl_protocol_logger_type::WL_PROTOCOL_LOGGER_EVENT => Direction::Sent,
+            },
+            &LoggedMessage {
+                // the interfaces and their messages are static tables, which outlive the display
+                interface: &*(interface.to_str().unwrap_or("<invalid>") as *const str),
+                name: &*(name.to_str().unwrap_or("<invalid>") as *const str),
+                // libwayland-server does not number the messages
+                trace_id: 0,
+                message: &msg,
+            },
+        );
+    });
+
+    if let Err(_) = ret {
+        eprintln!("[wayland-server error] The protocol logger panicked.");
+        ::libc::abort()
+    }
+}
+
+// convert an argument of a message logged by libwayland-server, of type `kind` in its signature
+unsafe fn parse_argument(kind: u8, arg: &wl_argument) -> Argument {
+    match kind {
+        b'i' => Argument::Int(arg.i),
+        b'u' => Argument::Uint(arg.u),
+        b'f' => Argument::Fixed(arg.f),
+        b's' if arg.s.is_null() => Argument::Str(CString::default()),
+        b's' => Argument::Str(CStr::from_ptr(arg.s).to_owned()),
+        b'o' if arg.o.is_null() => Argument::Object(0),
+        b'o' => Argument::Object(ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_resource_get_id,
+            arg.o as *mut wl_resource
+        )),
+        b'n' => Argument::NewId(arg.n),
+        b'a' if arg.a.is_null() => Argument::Array(Vec::new()),
+        b'a' => {
+            let array = &*arg.a;
+            Argument::Array(::std::slice::from_raw_parts(array.data as *const u8, array.size).to_vec())
+        }
+        _ => Argument::Fd(arg.h),
+    }
+}
@@ -6,12 +6,13 @@ use wayland_commons::Interface;
 
 use wayland_sys::server::*;
 
-use super::ClientInner;
+use super::{ClientInner, ResourceInner};
 use NewResource;
 
 pub(crate) struct GlobalData<I: Interface> {
     pub(crate) bind: Box<FnMut(NewResource<I>, u32)>,
     pub(crate) filter: Option<Box<FnMut(ClientInner) -> bool>>,
+    pub(crate) resources: Vec<ResourceInner>,
 }
 
 impl<I: Interface> GlobalData<I> {
@@ -23,6 +24,7 @@ impl<I: Interface> GlobalData<I> {
         GlobalData {
             bind: Box::new(bind) as Box<_>,
             filter: filter.map(|f| Box::new(f) as Box<_>),
+            resources: Vec::new(),
         }
     }
 }
@@ -57,6 +59,19 @@ impl<I: Interface> GlobalInner<I> {
             drop(data);
         }
     }
+
+    pub fn destroy_and_invalidate(self) -> Vec<ResourceInner> {
+        let resources: Vec<ResourceInner> = unsafe { (*self.data).resources.drain(..).collect() };
+        self.destroy();
+        let mut invalidated = Vec::new();
+        for resource in resources {
+            if resource.is_alive() {
+                resource.invalidate();
+                invalidated.push(resource);
+            }
+        }
+        invalidated
+    }
 }
 
 pub(crate) unsafe extern "C" fn global_bind<I: Interface>(
@@ -78,6 +93,12 @@ pub(crate) unsafe extern "C" fn global_bind<I: Interface>(
         );
         let resource = NewResource::from_c_ptr(ptr as *mut wl_resource);
         (data.bind)(resource, version);
+        // keep track of the resources bound from this global, in case it gets invalidated
+        let bound = ResourceInner::from_c_ptr::<I>(ptr as *mut wl_resource);
+        data.resources.retain(|r| r.is_alive());
+        if !bound.is_external() {
+            data.resources.push(bound);
+        }
     });
     match ret {
         Ok(()) => (), // all went well
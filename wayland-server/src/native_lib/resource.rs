@@ -1,6 +1,6 @@
 use std::os::raw::{c_int, c_void};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use wayland_sys::common::*;
 use wayland_sys::server::*;
@@ -11,16 +11,24 @@ use {Interface, MessageGroup, Resource};
 
 use super::ClientInner;
 
+// an additional destructor of a resource
+type Destructor = Box<FnMut(ResourceInner) + Send>;
+
 pub(crate) struct ResourceInternal {
     alive: AtomicBool,
+    defunct: AtomicBool,
     user_data: Arc<UserData>,
+    // the destructors assigned after the implementation, `None` once they were run
+    destructors: Mutex<Option<Vec<Destructor>>>,
 }
 
 impl ResourceInternal {
     fn new(user_data: UserData) -> ResourceInternal {
         ResourceInternal {
             alive: AtomicBool::new(true),
+            defunct: AtomicBool::new(false),
             user_data: Arc::new(user_data),
+            destructors: Mutex::new(Some(Vec::new())),
         }
     }
 }
@@ -48,6 +56,7 @@ impl ResourceInner {
         let destructor = msg.is_destructor();
 
         msg.as_raw_c_in(|opcode, args| unsafe {
+            I::Event::MESSAGES[opcode as usize].check_c_fds(I::NAME, args);
             ffi_dispatch!(
                 WAYLAND_SERVER_HANDLE,
                 wl_resource_post_event_array,
@@ -75,6 +84,26 @@ impl ResourceInner {
             .unwrap_or(false)
     }
 
+    pub(crate) fn is_defunct(&self) -> bool {
+        self.internal
+            .as_ref()
+            .map(|i| i.defunct.load(Ordering::Acquire))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn invalidate(&self) {
+        if let Some(ref internal) = self.internal {
+            internal.defunct.store(true, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn destroy_registry(&self) {
+        // registries are managed by libwayland, which takes care of the cleanup
+        unsafe {
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_destroy, self.ptr);
+        }
+    }
+
     pub(crate) fn version(&self) -> u32 {
         if !self.is_alive() {
             return 0;
@@ -106,20 +135,37 @@ impl ResourceInner {
     }
 
     pub(crate) fn post_error(&self, error_code: u32, msg: String) {
-        // If `str` contains an interior null, the actual transmitted message will
-        // be truncated at this point.
+        // interior nul bytes were already removed by `Resource::post_error()`
+        let cstring = ::std::ffi::CString::new(msg).unwrap();
         unsafe {
-            let cstring = ::std::ffi::CString::from_vec_unchecked(msg.into());
+            // the message is the argument of a printf-like format, it may contain '%'
             ffi_dispatch!(
                 WAYLAND_SERVER_HANDLE,
                 wl_resource_post_error,
                 self.ptr,
                 error_code,
+                b"%s\0".as_ptr() as *const ::std::os::raw::c_char,
                 cstring.as_ptr()
             )
         }
     }
 
+    pub(crate) fn assign_destructor(&self, destructor: Destructor) -> bool {
+        match self.internal {
+            Some(ref internal) => {
+                // the destructors are taken under this lock, once the resource is marked dead
+                match *internal.destructors.lock().unwrap() {
+                    Some(ref mut destructors) if internal.alive.load(Ordering::Acquire) => {
+                        destructors.push(destructor);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            None => false,
+        }
+    }
+
     pub(crate) fn get_user_data<UD>(&self) -> Option<&UD>
     where
         UD: 'static,
@@ -159,14 +205,18 @@ impl ResourceInner {
             return ResourceInner {
                 internal: Some(Arc::new(ResourceInternal {
                     alive: AtomicBool::new(false),
+                    defunct: AtomicBool::new(false),
                     user_data: Arc::new(UserData::empty()),
+                    destructors: Mutex::new(Some(Vec::new())),
                 })),
                 ptr: ptr,
                 _hack: (false, false),
             };
         }
 
-        let is_managed = {
+        // anonymous objects have no interface to check against, they are
+        // handled as external objects
+        let is_managed = !I::c_interface().is_null() && {
             ffi_dispatch!(
                 WAYLAND_SERVER_HANDLE,
                 wl_resource_instance_of,
@@ -306,8 +356,10 @@ where
             if must_destroy {
                 user_data.internal.alive.store(false, Ordering::Release);
             }
-            // call the impl
-            implem_func(msg, resource_obj);
+            // call the impl, unless the global of this resource was invalidated
+            if !user_data.internal.defunct.load(Ordering::Acquire) {
+                implem_func(msg, resource_obj);
+            }
         }
         if must_destroy {
             // final cleanup
@@ -319,12 +371,17 @@ where
     match ret {
         Ok(Ok(())) => return 0,
         Ok(Err(())) => {
-            eprintln!(
-                "[wayland-client error] Attempted to dispatch unknown opcode {} for {}, aborting.",
-                opcode,
-                I::NAME
+            // libwayland already checked the opcode and the signature, the arguments were
+            // rejected by the generated parsing, like a null object or an invalid enum value
+            let msg = format!(
+                "invalid arguments for {}@{}.{}",
+                I::NAME,
+                ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_id, resource),
+                I::Request::MESSAGES[opcode as usize].name
             );
-            ::libc::abort();
+            // wl_display.error.invalid_method
+            ResourceInner::from_c_ptr::<I>(resource).post_error(1, msg);
+            0
         }
         Err(_) => {
             eprintln!("[wayland-client error] A handler for {} panicked.", I::NAME);
@@ -341,11 +398,24 @@ pub(crate) unsafe extern "C" fn resource_destroy<I: Interface>(resource: *mut wl
     let ret = ::std::panic::catch_unwind(move || {
         let mut user_data = Box::from_raw(user_data as *mut ResourceUserData<I>);
         user_data.internal.alive.store(false, Ordering::Release);
-        let implem = user_data.implem.as_mut().unwrap();
-        let &mut (_, ref mut destructor) = implem;
-        if let Some(mut dest_func) = destructor.take() {
-            let resource_obj = Resource::<I>::from_c_ptr(resource);
-            dest_func(resource_obj);
+        {
+            let implem = user_data.implem.as_mut().unwrap();
+            let &mut (_, ref mut destructor) = implem;
+            if let Some(mut dest_func) = destructor.take() {
+                let resource_obj = Resource::<I>::from_c_ptr(resource);
+                dest_func(resource_obj);
+            }
+        }
+        // then the assigned ones
+        let destructors = user_data
+            .internal
+            .destructors
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_default();
+        for mut destructor in destructors {
+            destructor(ResourceInner::from_c_ptr::<I>(resource));
         }
     });
 
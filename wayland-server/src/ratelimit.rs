@@ -0,0 +1,148 @@
+//! Rate limiting of the requests of the clients
+//!
+//! A client sending requests at a pathological rate, like thousands of
+//! `wl_pointer.set_cursor` per frame, can keep the compositor busy dispatching them.
+//! `RateLimits` bound the rate at which each client can send the requests of given
+//! interfaces, and are set with `Display::set_rate_limits()`.
+//!
+//! The interfaces are grouped in classes, each class having a `RateLimit`. Every client
+//! has a token bucket per class, holding up to `burst` tokens and refilled with `rate`
+//! tokens per second: each request of an interface of the class takes a token. Once a
+//! bucket is empty, the policy given to the `RateLimits` decides what to do with the next
+//! request of the class: dispatch it anyway, throttle the client, or kill it.
+//!
+//! Throttling a client does not drop its requests, which would break the protocol state:
+//! the dispatching of all the requests of the client is suspended until its bucket has a
+//! token again. Meanwhile, its requests are left in its socket.
+//!
+//! This is only supported by the rust implementation: with the `native_lib` cargo feature,
+//! `Display::set_rate_limits()` fails with `RateLimitsError::Unsupported`.
+
+use std::{fmt, io};
+
+use Client;
+
+/// The limit of the rate of the requests of a class
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    /// The number of requests per second, in the long run
+    ///
+    /// It must not be zero.
+    pub rate: u32,
+    /// The number of requests which can be sent at once, before the rate applies
+    ///
+    /// A burst of zero is handled as a burst of one.
+    pub burst: u32,
+}
+
+/// What to do with a request exceeding its rate limit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RateAction {
+    /// Dispatch the request anyway
+    Allow,
+    /// Suspend the dispatching of the requests of the client until the rate allows it
+    Throttle,
+    /// Kill the client
+    Kill,
+}
+
+/// A request exceeding its rate limit, as given to the policy of the `RateLimits`
+#[derive(Copy, Clone, Debug)]
+pub struct RateLimited {
+    /// Name of the interface of the object receiving the request
+    pub interface: &'static str,
+    /// Name of the request
+    pub request: &'static str,
+}
+
+/// The reasons why rate limits could not be set
+#[derive(Debug)]
+pub enum RateLimitsError {
+    /// The library uses `libwayland-server.so`, which does not support them
+    Unsupported,
+    /// The timer refilling the buckets could not be inserted in the event loop
+    Io(io::Error),
+}
+
+impl fmt::Display for RateLimitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RateLimitsError::Unsupported => f.write_str("rate limits are not supported by libwayland"),
+            RateLimitsError::Io(ref err) => write!(f, "failed to insert the rate limits timer: {}", err),
+        }
+    }
+}
+
+impl ::std::error::Error for RateLimitsError {
+    fn description(&self) -> &str {
+        "setting the rate limits failed"
+    }
+}
+
+/// The rate limits of the requests of the clients
+///
+/// See the module documentation for details.
+pub struct RateLimits {
+    classes: Vec<(Vec<String>, RateLimit)>,
+    default: Option<RateLimit>,
+    policy: Policy,
+}
+
+type Policy = Box<FnMut(&Client, &RateLimited) -> RateAction>;
+
+impl RateLimits {
+    /// Create rate limits with given policy, and no classes
+    ///
+    /// The policy is invoked for each request exceeding the limit of its class, with the
+    /// client which sent it. It must not change the rate limits of the display.
+    pub fn new<F>(policy: F) -> RateLimits
+    where
+        F: FnMut(&Client, &RateLimited) -> RateAction + 'static,
+    {
+        RateLimits {
+            classes: Vec::new(),
+            default: None,
+            policy: Box::new(policy),
+        }
+    }
+
+    /// Add a class of interfaces sharing a limit
+    ///
+    /// An interface belongs to the first class listing it.
+    ///
+    /// Panics if the rate of the limit is zero.
+    pub fn limit(mut self, interfaces: &[&str], limit: RateLimit) -> RateLimits {
+        assert!(limit.rate > 0, "The rate of a limit must not be zero.");
+        self.classes
+            .push((interfaces.iter().map(|&i| i.to_owned()).collect(), limit));
+        self
+    }
+
+    /// Set the limit of the interfaces which belong to no class
+    ///
+    /// They form a class of their own. Without a default limit, their requests are not
+    /// limited.
+    ///
+    /// Panics if the rate of the limit is zero.
+    pub fn default_limit(mut self, limit: RateLimit) -> RateLimits {
+        assert!(limit.rate > 0, "The rate of a limit must not be zero.");
+        self.default = Some(limit);
+        self
+    }
+}
+
+#[cfg(not(feature = "native_lib"))]
+impl RateLimits {
+    // the index and the limit of the class of an interface
+    pub(crate) fn class_of(&self, interface: &str) -> Option<(usize, RateLimit)> {
+        self.classes
+            .iter()
+            .position(|&(ref interfaces, _)| interfaces.iter().any(|i| i == interface))
+            .map(|i| (i, self.classes[i].1))
+            .or_else(|| self.default.map(|limit| (self.classes.len(), limit)))
+    }
+
+    pub(crate) fn policy(&mut self, client: &Client, limited: &RateLimited) -> RateAction {
+        (self.policy)(client, limited)
+    }
+}
@@ -0,0 +1,339 @@
+//! Region algebra
+//!
+//! A compositor tracks several sets of pixels for each surface: the contents of its
+//! `wl_region` objects, which become its opaque and input regions, and the damage
+//! accumulated by `wl_surface.damage` until the next commit. This module provides the
+//! `Region` type to represent them, with union, intersection and subtraction, so that
+//! this protocol-level state does not require a separate C region library.
+//!
+//! Like the regions of pixman, a `Region` is stored as a list of horizontal bands, from
+//! top to bottom, each made of disjoint spans sorted from left to right. Vertically
+//! adjacent bands with the same spans are merged, so that the representation of a set
+//! of pixels is unique: two regions are equal if and only if they cover the same pixels.
+//!
+//! The `wl_region` objects created by the clients can be implemented with
+//! `implement_region()`, their contents are then given by `region_of()`.
+
+use std::sync::Mutex;
+
+use protocol::wl_region::{Request, WlRegion};
+use {NewResource, Resource};
+
+/// A rectangle, its origin being its top-left corner
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rectangle {
+    /// The horizontal coordinate of the left edge
+    pub x: i32,
+    /// The vertical coordinate of the top edge
+    pub y: i32,
+    /// The width, a rectangle of negative or null width is empty
+    pub width: i32,
+    /// The height, a rectangle of negative or null height is empty
+    pub height: i32,
+}
+
+impl Rectangle {
+    /// Create a rectangle from its origin and its size
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether this rectangle contains no pixel
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    /// Whether this rectangle contains the pixel at `(x, y)`
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+}
+
+// a horizontal band of a region, covering the lines from y1 included to y2 excluded
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Band {
+    y1: i32,
+    y2: i32,
+    // the sorted and disjoint spans of the band, from x1 included to x2 excluded
+    spans: Vec<(i32, i32)>,
+}
+
+/// A set of pixels
+///
+/// See the module documentation for details.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Region {
+    bands: Vec<Band>,
+}
+
+#[derive(Copy, Clone)]
+enum Op {
+    Union,
+    Intersect,
+    Subtract,
+}
+
+impl Op {
+    fn apply(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            Op::Union => in_a || in_b,
+            Op::Intersect => in_a && in_b,
+            Op::Subtract => in_a && !in_b,
+        }
+    }
+}
+
+impl Region {
+    /// Create an empty region
+    pub fn new() -> Region {
+        Region { bands: Vec::new() }
+    }
+
+    /// Create a region made of a single rectangle
+    pub fn from_rect(rect: Rectangle) -> Region {
+        if rect.is_empty() {
+            return Region::new();
+        }
+        Region {
+            bands: vec![Band {
+                y1: rect.y,
+                y2: rect.y + rect.height,
+                spans: vec![(rect.x, rect.x + rect.width)],
+            }],
+        }
+    }
+
+    /// Whether this region contains no pixel
+    pub fn is_empty(&self) -> bool {
+        self.bands.is_empty()
+    }
+
+    /// Make this region empty
+    pub fn clear(&mut self) {
+        self.bands.clear();
+    }
+
+    /// The smallest rectangle containing this region, `None` if it is empty
+    pub fn extents(&self) -> Option<Rectangle> {
+        let (first, last) = match (self.bands.first(), self.bands.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return None,
+        };
+        let x1 = self.bands.iter().map(|band| band.spans[0].0).min().unwrap();
+        let x2 = self.bands.iter().map(|band| band.spans[band.spans.len() - 1].1).max().unwrap();
+        Some(Rectangle::new(x1, first.y1, x2 - x1, last.y2 - first.y1))
+    }
+
+    /// Whether this region contains the pixel at `(x, y)`
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.bands
+            .iter()
+            .find(|band| y >= band.y1 && y < band.y2)
+            .map(|band| band.spans.iter().any(|&(x1, x2)| x >= x1 && x < x2))
+            .unwrap_or(false)
+    }
+
+    /// The pixels of this region or of `other`
+    pub fn union(&self, other: &Region) -> Region {
+        combine(self, other, Op::Union)
+    }
+
+    /// The pixels of both this region and `other`
+    pub fn intersect(&self, other: &Region) -> Region {
+        combine(self, other, Op::Intersect)
+    }
+
+    /// The pixels of this region which are not in `other`
+    pub fn subtract(&self, other: &Region) -> Region {
+        combine(self, other, Op::Subtract)
+    }
+
+    /// Add a rectangle to this region, like `wl_region.add`
+    pub fn add_rect(&mut self, rect: Rectangle) {
+        *self = self.union(&Region::from_rect(rect));
+    }
+
+    /// Remove a rectangle from this region, like `wl_region.subtract`
+    pub fn subtract_rect(&mut self, rect: Rectangle) {
+        *self = self.subtract(&Region::from_rect(rect));
+    }
+
+    /// Restrict this region to a rectangle, typically the bounds of a surface
+    pub fn clip(&mut self, rect: Rectangle) {
+        *self = self.intersect(&Region::from_rect(rect));
+    }
+
+    /// Move this region by `(dx, dy)`
+    pub fn translate(&mut self, dx: i32, dy: i32) {
+        for band in &mut self.bands {
+            band.y1 += dy;
+            band.y2 += dy;
+            for span in &mut band.spans {
+                span.0 += dx;
+                span.1 += dx;
+            }
+        }
+    }
+
+    /// Iterate over disjoint rectangles covering this region
+    ///
+    /// The rectangles are given from top to bottom then from left to right, those of a
+    /// same band sharing their vertical extent.
+    pub fn rects<'a>(&'a self) -> Rects<'a> {
+        Rects {
+            bands: &self.bands,
+            band: 0,
+            span: 0,
+        }
+    }
+}
+
+/// An iterator over the rectangles of a region, see `Region::rects()`
+pub struct Rects<'a> {
+    bands: &'a [Band],
+    band: usize,
+    span: usize,
+}
+
+impl<'a> Iterator for Rects<'a> {
+    type Item = Rectangle;
+
+    fn next(&mut self) -> Option<Rectangle> {
+        let band = match self.bands.get(self.band) {
+            Some(band) => band,
+            None => return None,
+        };
+        let (x1, x2) = band.spans[self.span];
+        self.span += 1;
+        if self.span == band.spans.len() {
+            self.band += 1;
+            self.span = 0;
+        }
+        Some(Rectangle::new(x1, band.y1, x2 - x1, band.y2 - band.y1))
+    }
+}
+
+// sweep the horizontal edges of both regions, combining the spans of each slab between them
+fn combine(a: &Region, b: &Region, op: Op) -> Region {
+    let mut ys = Vec::with_capacity(2 * (a.bands.len() + b.bands.len()));
+    for band in a.bands.iter().chain(&b.bands) {
+        ys.push(band.y1);
+        ys.push(band.y2);
+    }
+    ys.sort();
+    ys.dedup();
+
+    let mut bands: Vec<Band> = Vec::new();
+    let (mut ia, mut ib) = (0, 0);
+    for slab in ys.windows(2) {
+        let (y1, y2) = (slab[0], slab[1]);
+        let spans_a = band_spans(&a.bands, &mut ia, y1);
+        let spans_b = band_spans(&b.bands, &mut ib, y1);
+        let spans = combine_spans(spans_a, spans_b, op);
+        if spans.is_empty() {
+            continue;
+        }
+        // merge with the band above if it has the same spans
+        if let Some(last) = bands.last_mut() {
+            if last.y2 == y1 && last.spans == spans {
+                last.y2 = y2;
+                continue;
+            }
+        }
+        bands.push(Band { y1, y2, spans });
+    }
+    Region { bands }
+}
+
+// the spans of the band covering the line y, the bands before it being skipped
+fn band_spans<'a>(bands: &'a [Band], index: &mut usize, y: i32) -> &'a [(i32, i32)] {
+    while *index < bands.len() && bands[*index].y2 <= y {
+        *index += 1;
+    }
+    match bands.get(*index) {
+        Some(band) if band.y1 <= y => &band.spans,
+        _ => &[],
+    }
+}
+
+fn combine_spans(a: &[(i32, i32)], b: &[(i32, i32)], op: Op) -> Vec<(i32, i32)> {
+    let mut xs = Vec::with_capacity(2 * (a.len() + b.len()));
+    for &(x1, x2) in a.iter().chain(b) {
+        xs.push(x1);
+        xs.push(x2);
+    }
+    xs.sort();
+    xs.dedup();
+
+    let mut spans: Vec<(i32, i32)> = Vec::new();
+    let (mut ia, mut ib) = (0, 0);
+    for segment in xs.windows(2) {
+        let (x1, x2) = (segment[0], segment[1]);
+        while ia < a.len() && a[ia].1 <= x1 {
+            ia += 1;
+        }
+        while ib < b.len() && b[ib].1 <= x1 {
+            ib += 1;
+        }
+        let in_a = ia < a.len() && a[ia].0 <= x1;
+        let in_b = ib < b.len() && b[ib].0 <= x1;
+        if !op.apply(in_a, in_b) {
+            continue;
+        }
+        let extends_last = spans.last().map(|last| last.1 == x1).unwrap_or(false);
+        if extends_last {
+            spans.last_mut().unwrap().1 = x2;
+        } else {
+            spans.push((x1, x2));
+        }
+    }
+    spans
+}
+
+/// Implement a `wl_region`, tracking its contents
+///
+/// Its contents can then be retrieved with `region_of()`, typically when it is given to
+/// `wl_surface.set_opaque_region` or `wl_surface.set_input_region`.
+pub fn implement_region(region: NewResource<WlRegion>) -> Resource<WlRegion> {
+    region.implement(
+        |request, region: Resource<WlRegion>| {
+            let mut contents = match region.user_data::<Mutex<Region>>() {
+                Some(contents) => contents.lock().unwrap(),
+                None => return,
+            };
+            match request {
+                Request::Add {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => contents.add_rect(Rectangle::new(x, y, width, height)),
+                Request::Subtract {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => contents.subtract_rect(Rectangle::new(x, y, width, height)),
+                Request::Destroy => {}
+            }
+        },
+        None::<fn(_)>,
+        Mutex::new(Region::new()),
+    )
+}
+
+/// The current contents of a `wl_region`
+///
+/// The contents are copied: the region set on a surface is not affected by later changes of
+/// the `wl_region`, as required by the protocol. Returns `None` if the `wl_region` was not
+/// implemented with `implement_region()`.
+pub fn region_of(region: &Resource<WlRegion>) -> Option<Region> {
+    region
+        .user_data::<Mutex<Region>>()
+        .map(|contents| contents.lock().unwrap().clone())
+}
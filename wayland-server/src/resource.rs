@@ -1,3 +1,5 @@
+use std::fmt;
+
 use wayland_commons::utils::UserData;
 use wayland_commons::{Interface, MessageGroup};
 
@@ -21,7 +23,7 @@ use imp::{NewResourceInner, ResourceInner};
 /// via the `send` method.
 pub struct Resource<I: Interface> {
     _i: ::std::marker::PhantomData<&'static I>,
-    inner: ResourceInner,
+    pub(crate) inner: ResourceInner,
 }
 
 impl<I: Interface> PartialEq for Resource<I> {
@@ -59,6 +61,15 @@ impl<I: Interface> Resource<I> {
         self.inner.is_alive()
     }
 
+    /// Check if this resource has been invalidated
+    ///
+    /// This is the case once the global it was bound from has been destroyed
+    /// using `Global::destroy_and_invalidate()`. Requests sent by the client to
+    /// a defunct object are ignored.
+    pub fn is_defunct(&self) -> bool {
+        self.inner.is_defunct()
+    }
+
     /// Retrieve the interface version of this wayland object instance
     ///
     /// Returns 0 on dead objects
@@ -80,13 +91,47 @@ impl<I: Interface> Resource<I> {
 
     /// Posts a protocol error to this resource
     ///
-    /// The error code can be obtained from the various `Error` enums of the protocols.
+    /// The error code is the raw value of an entry of the `Error` enum of the interface. The
+    /// message is typically built with `format!()`, it is sent as is, and truncated at its
+    /// first nul byte if it contains any.
     ///
     /// An error is fatal to the client that caused it.
-    pub fn post_error(&self, error_code: u32, msg: String) {
+    pub fn post_error(&self, error_code: u32, mut msg: String) {
+        if let Some(nul) = msg.find('\0') {
+            msg.truncate(nul);
+        }
         self.inner.post_error(error_code, msg)
     }
 
+    /// Posts a protocol error to this resource, given as a variant of an error enum
+    ///
+    /// Like `post_error()`, but the error is given directly as a variant of the `Error` enum
+    /// of the interface, like `resource.post_error_enum(xdg_surface::Error::NotConstructed, msg)`.
+    ///
+    /// An error is fatal to the client that caused it.
+    pub fn post_error_enum<E: Into<u32>>(&self, error: E, msg: String) {
+        self.post_error(error.into(), msg)
+    }
+
+    /// Posts a protocol error to this resource, described by its summary
+    ///
+    /// The message sent is the summary of the error from the protocol description,
+    /// followed by `details` if not empty, like `"buffer format is not known: 42"` for
+    /// `resource.post_error_with_summary(wl_shm::Error::InvalidFormat, "42")`.
+    ///
+    /// An error is fatal to the client that caused it.
+    pub fn post_error_with_summary<E>(&self, error: E, details: &str)
+    where
+        E: Into<u32> + fmt::Display,
+    {
+        let msg = if details.is_empty() {
+            error.to_string()
+        } else {
+            format!("{}: {}", error, details)
+        };
+        self.post_error_enum(error, msg)
+    }
+
     /// Access the arbitrary payload associated to this object
     ///
     /// You need to specify the expected type of this payload, and this
@@ -101,6 +146,31 @@ impl<I: Interface> Resource<I> {
         self.inner.get_user_data()
     }
 
+    /// Assign an additional destructor to this resource
+    ///
+    /// It is run once the object is destroyed, be it by a destructor request or event, or
+    /// by the disconnection of its client, after the destructor given to its implementation.
+    /// This lets cleanup logic, like freeing the backing storage of a buffer, be attached
+    /// to a resource after the fact. Several destructors can be assigned, they are run in
+    /// the order they were assigned.
+    ///
+    /// Like the implementation of the resource, they are run on the thread dispatching the
+    /// event loop of the `Display`, whatever the thread this method was called from.
+    ///
+    /// Returns `false` and drops the destructor if this resource is no longer alive, or
+    /// if it is not managed by the library (see `is_external()`).
+    pub fn assign_destructor<F>(&self, destructor: F) -> bool
+    where
+        F: FnOnce(Resource<I>) + Send + 'static,
+    {
+        let mut destructor = Some(destructor);
+        self.inner.assign_destructor(Box::new(move |inner| {
+            if let Some(destructor) = destructor.take() {
+                destructor(Resource::wrap(inner));
+            }
+        }))
+    }
+
     /// Retrieve an handle to the client associated with this resource
     ///
     /// Returns `None` if the resource is no longer alive.
@@ -209,6 +279,56 @@ impl<I: Interface + 'static> NewResource<I> {
         }
     }
 
+    /// Implement this resource using given function, destructor, and user data, with access
+    /// to the dispatch state
+    ///
+    /// The implementation is given the data of the event loop of the `Display` along with the
+    /// requests, as a `&mut Data`: this allows the implementations to share the state of the
+    /// server without a `Mutex` per object. See the crate-level documentation for details.
+    ///
+    /// The state is only available to the requests dispatched by the event loop. The requests
+    /// processed outside of it, which are the first ones of a client created by
+    /// `Display::create_client()`, can not be processed: the client is sent a protocol error,
+    /// as for a request whose arguments are invalid.
+    ///
+    /// # Panics
+    ///
+    /// The dispatching of a request panics if `Data` is not the type of the data of the event
+    /// loop.
+    pub fn implement_with_state<Data, F, Dest, UD>(
+        self,
+        mut implementation: F,
+        destructor: Option<Dest>,
+        user_data: UD,
+    ) -> Resource<I>
+    where
+        Data: 'static,
+        F: FnMut(I::Request, Resource<I>, &mut Data) + Send + 'static,
+        Dest: FnMut(Resource<I>) + Send + 'static,
+        UD: Send + Sync + 'static,
+        I::Request: MessageGroup<Map = ::imp::ResourceMap>,
+    {
+        self.implement(
+            move |request, resource: Resource<I>| {
+                ::dispatch_state::borrow_state(|state: Option<&mut Data>| match state {
+                    Some(state) => implementation(request, resource, state),
+                    None => {
+                        let msg = format!(
+                            "request {}@{}.{} dispatched without the state of its implementation",
+                            I::NAME,
+                            resource.id(),
+                            I::Request::MESSAGES[request.opcode() as usize].name
+                        );
+                        // wl_display.error.invalid_method
+                        resource.post_error(1, msg);
+                    }
+                })
+            },
+            destructor,
+            user_data,
+        )
+    }
+
     /// Implement this resource using given function and implementation data.
     ///
     /// This method allows the implementation data to not be `Send`, but requires for
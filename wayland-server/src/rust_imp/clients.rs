@@ -1,21 +1,30 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ffi::CString;
-use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use nix::poll::{poll, EventFlags, PollFd};
 use nix::Result as NixResult;
 
 use calloop::generic::Generic;
+use calloop::timer::{Timer, TimerHandle};
 use calloop::Source;
 
 use wayland_commons::map::{Object, ObjectMap, ObjectMetadata};
-use wayland_commons::socket::{BufferedSocket, Socket};
+use wayland_commons::socket::{BufferedSocket, ConnectionStats, Socket};
+use wayland_commons::trace::{self, Direction, LoggedMessage, ProtocolLogger};
 use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc, MessageParseError};
 
-use {Fd, Interface, UserDataMap};
+use display::FlushScheduling;
+use ratelimit::{RateAction, RateLimit, RateLimited, RateLimits, RateLimitsError};
+use {Client, Credentials, Fd, Interface, UserDataMap};
 
-use super::event_loop_glue::WSLoopHandle;
+use super::event_loop_glue::{DeferredCall, WSLoopHandle};
 use super::globals::GlobalManager;
 use super::resources::{NewResourceInner, ObjectMeta, ResourceInner};
 
@@ -26,6 +35,50 @@ pub(crate) enum Error {
     Nix(::nix::Error),
 }
 
+// the protocol logger of a display, shared with its clients
+pub(crate) type SharedLogger = Arc<Mutex<Option<ProtocolLogger>>>;
+
+// the rate limits of a display, shared with its clients
+struct SharedLimits {
+    limits: RefCell<Option<RateLimits>>,
+    // incremented when the limits change, resetting the buckets of the clients
+    generation: Cell<u64>,
+    // resumes the dispatching of the throttled clients
+    timer: RefCell<Option<TimerHandle<DeferredCall>>>,
+}
+
+// the token bucket of a client for a class of interfaces
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Bucket {
+        Bucket {
+            tokens: f64::from(limit.burst.max(1)),
+            last: Instant::now(),
+        }
+    }
+
+    // take a token, or return the delay until one is available
+    fn take(&mut self, limit: RateLimit) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+        let rate = f64::from(limit.rate);
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(limit.burst.max(1)));
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let nanos = ((1.0 - self.tokens) / rate * 1e9).ceil() as u64;
+            Err(Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32))
+        }
+    }
+}
+
 pub(crate) struct ClientConnection {
     socket: BufferedSocket,
     pub(crate) map: Arc<Mutex<ObjectMap<ObjectMeta>>>,
@@ -34,6 +87,9 @@ pub(crate) struct ClientConnection {
     last_error: Option<Error>,
     pending_destructors: Vec<ResourceInner>,
     zombie_clients: Arc<Mutex<Vec<ClientConnection>>>,
+    // the counters of the dead clients of the display
+    closed_stats: Arc<Mutex<ConnectionStats>>,
+    pub(crate) logger: SharedLogger,
 }
 
 impl ClientConnection {
@@ -41,8 +97,17 @@ impl ClientConnection {
         fd: RawFd,
         display_object: Object<ObjectMeta>,
         zombies: Arc<Mutex<Vec<ClientConnection>>>,
+        closed_stats: Arc<Mutex<ConnectionStats>>,
+        logger: SharedLogger,
     ) -> ClientConnection {
-        let socket = BufferedSocket::new(Socket::from_raw_fd(fd));
+        #[allow(unused_mut)]
+        let mut socket = Socket::from_raw_fd(fd);
+        #[cfg(feature = "io_uring")]
+        {
+            // without io_uring support, keep using the direct system calls
+            let _ = socket.enable_io_uring();
+        }
+        let socket = BufferedSocket::new(socket);
 
         let mut map = ObjectMap::new();
         // Insert first pre-existing object
@@ -56,6 +121,8 @@ impl ClientConnection {
             last_error: None,
             pending_destructors: Vec::new(),
             zombie_clients: zombies,
+            closed_stats,
+            logger,
         }
     }
 
@@ -72,10 +139,28 @@ impl ClientConnection {
                 .lock()
                 .unwrap()
                 .destroy(resource.clone());
+            resource.run_destructors();
         }
     }
 
-    pub(crate) fn write_message(&mut self, msg: &Message) -> NixResult<()> {
+    // log an event with the logger of the display, and write it to the socket
+    pub(crate) fn write_event(
+        &mut self,
+        interface: &'static str,
+        name: &'static str,
+        msg: &Message,
+    ) -> NixResult<()> {
+        let logger = self.logger.lock().unwrap().clone();
+        trace::log(
+            logger.as_ref(),
+            Direction::Sent,
+            &LoggedMessage {
+                interface,
+                name,
+                trace_id: self.next_trace_id(),
+                message: msg,
+            },
+        );
         self.socket.write_message(msg)
     }
 
@@ -83,14 +168,37 @@ impl ClientConnection {
         self.socket.flush()
     }
 
+    // the trace id the next message written to the socket will have
+    pub(crate) fn next_trace_id(&self) -> u64 {
+        self.socket.sent_messages() + 1
+    }
+
+    fn raw_fd(&mut self) -> RawFd {
+        self.socket.get_socket().as_raw_fd()
+    }
+
+    // whether the socket can be written to without blocking
+    fn writable(&mut self) -> bool {
+        let mut fds = [PollFd::new(self.raw_fd(), EventFlags::POLLOUT)];
+        match poll(&mut fds, 0) {
+            Ok(_) => fds[0].revents().map(|r| !r.is_empty()).unwrap_or(false),
+            // let the flush report the error
+            Err(_) => true,
+        }
+    }
+
     pub(crate) fn delete_id(&mut self, id: u32) -> NixResult<()> {
         self.map.lock().unwrap().remove(id);
 
-        self.write_message(&Message {
-            sender_id: 1,
-            opcode: 1,
-            args: vec![Argument::Uint(id)],
-        })
+        self.write_event(
+            "wl_display",
+            "delete_id",
+            &Message {
+                sender_id: 1,
+                opcode: 1,
+                args: vec![Argument::Uint(id)],
+            },
+        )
     }
 
     pub(crate) fn read_request(&mut self) -> Result<Option<Message>, Error> {
@@ -199,7 +307,8 @@ impl ClientConnection {
                 map: self.map.clone(),
                 client: dummy_client.clone(),
             };
-            obj.meta.dispatcher.lock().unwrap().destroy(resource);
+            obj.meta.dispatcher.lock().unwrap().destroy(resource.clone());
+            resource.run_destructors();
         });
         let _ = ::nix::unistd::close(self.socket.into_socket().into_raw_fd());
         for mut destructor in self.destructors.drain(..) {
@@ -229,9 +338,22 @@ impl ClientInner {
         }
     }
 
+    pub(crate) fn credentials(&self) -> Option<Credentials> {
+        let fd = match *self.data.lock().unwrap() {
+            Some(ref mut data) => data.raw_fd(),
+            None => return None,
+        };
+        peer_credentials(fd)
+    }
+
+    pub(crate) fn connection_stats(&self) -> Option<ConnectionStats> {
+        self.data.lock().unwrap().as_ref().map(|data| data.socket.stats())
+    }
+
     pub(crate) fn kill(&self) {
         if let Some(mut clientconn) = self.data.lock().unwrap().take() {
             let _ = clientconn.socket.flush();
+            *clientconn.closed_stats.lock().unwrap() += clientconn.socket.stats();
             // call all objects destructors
             let zombies = clientconn.zombie_clients.clone();
             zombies.lock().unwrap().push(clientconn);
@@ -257,15 +379,19 @@ impl ClientInner {
 
     pub(crate) fn post_error(&self, object: u32, error_code: u32, msg: String) {
         if let Some(ref mut data) = *self.data.lock().unwrap() {
-            let _ = data.write_message(&Message {
-                sender_id: 1,
-                opcode: 0,
-                args: vec![
-                    Argument::Object(object),
-                    Argument::Uint(error_code),
-                    Argument::Str(CString::new(msg).unwrap()),
-                ],
-            });
+            let _ = data.write_event(
+                "wl_display",
+                "error",
+                &Message {
+                    sender_id: 1,
+                    opcode: 0,
+                    args: vec![
+                        Argument::Object(object),
+                        Argument::Uint(error_code),
+                        Argument::Str(CString::new(msg).unwrap()),
+                    ],
+                },
+            );
         }
         self.kill();
     }
@@ -286,13 +412,40 @@ impl ClientInner {
         };
         Some(NewResourceInner::from_id(id, map, self.clone()).unwrap())
     }
+
+    pub(crate) fn resources_for_interface<I: Interface>(&self) -> Vec<ResourceInner> {
+        let map = match *self.data.lock().unwrap() {
+            Some(ref cx) => cx.map.clone(),
+            None => return Vec::new(),
+        };
+        let mut resources = Vec::new();
+        map.lock().unwrap().with_all(|id, object| {
+            if object.is_interface::<I>() && object.meta.alive.load(Ordering::Acquire) {
+                resources.push(ResourceInner {
+                    id,
+                    object: object.clone(),
+                    client: self.clone(),
+                    map: map.clone(),
+                });
+            }
+        });
+        resources
+    }
 }
 
 pub(crate) struct ClientManager {
     loophandle: Box<WSLoopHandle>,
     clients: Vec<(RefCell<Option<Source<Generic<Fd>>>>, ClientInner)>,
     zombie_clients: Arc<Mutex<Vec<ClientConnection>>>,
+    closed_stats: Arc<Mutex<ConnectionStats>>,
     global_mgr: Rc<RefCell<GlobalManager>>,
+    scheduling: FlushScheduling,
+    // index of the client flushed first by the next fair flush
+    rotation: usize,
+    writer: Option<Sender<ClientInner>>,
+    logger: SharedLogger,
+    limits: Rc<SharedLimits>,
+    limits_timer: Option<Source<Timer<DeferredCall>>>,
 }
 
 impl ClientManager {
@@ -304,7 +457,18 @@ impl ClientManager {
             loophandle,
             clients: Vec::new(),
             zombie_clients: Arc::new(Mutex::new(Vec::new())),
+            closed_stats: Arc::new(Mutex::new(ConnectionStats::default())),
             global_mgr,
+            scheduling: FlushScheduling::Sequential,
+            rotation: 0,
+            writer: None,
+            logger: Arc::new(Mutex::new(None)),
+            limits: Rc::new(SharedLimits {
+                limits: RefCell::new(None),
+                generation: Cell::new(0),
+                timer: RefCell::new(None),
+            }),
+            limits_timer: None,
         }
     }
 
@@ -321,7 +485,13 @@ impl ClientManager {
             childs_from_requests: display_req_child,
         };
 
-        let cx = ClientConnection::new(fd, display_object, self.zombie_clients.clone());
+        let cx = ClientConnection::new(
+            fd,
+            display_object,
+            self.zombie_clients.clone(),
+            self.closed_stats.clone(),
+            self.logger.clone(),
+        );
         let map = cx.map.clone();
         let user_data_map = cx.user_data_map.clone();
 
@@ -330,13 +500,18 @@ impl ClientManager {
             user_data_map,
         };
 
-        let implementation = ClientImplementation {
+        let implementation = Rc::new(ClientImplementation {
             inner: client.clone(),
             map,
-        };
+            logger: self.logger.clone(),
+            limits: self.limits.clone(),
+            buckets: RefCell::new((0, Vec::new())),
+            deferred: RefCell::new(None),
+            throttled: Cell::new(false),
+        });
 
         // process any pending messages before inserting it into the event loop
-        implementation.process_messages();
+        ClientImplementation::process_messages(&implementation);
 
         if !client.alive() {
             // client already made a protocol error and we killed it, there is no point
@@ -350,7 +525,10 @@ impl ClientManager {
 
         let source = match self
             .loophandle
-            .add_socket(evtsrc, Box::new(move |_| implementation.process_messages()))
+            .add_socket(
+                evtsrc,
+                Box::new(move |_| ClientImplementation::process_messages(&implementation)),
+            )
         {
             Ok(source) => Some(source),
             Err(e) => {
@@ -379,25 +557,209 @@ impl ClientManager {
         false
     }
 
+    pub(crate) fn set_protocol_logger(&mut self, logger: Option<ProtocolLogger>) {
+        *self.logger.lock().unwrap() = logger;
+    }
+
+    // the number of live clients, and the counters of all the clients ever connected
+    pub(crate) fn stats(&self) -> (usize, ConnectionStats) {
+        let mut count = 0;
+        let mut total = *self.closed_stats.lock().unwrap();
+        for &(_, ref client) in &self.clients {
+            if let Some(stats) = client.connection_stats() {
+                count += 1;
+                total += stats;
+            }
+        }
+        (count, total)
+    }
+
+    pub(crate) fn set_rate_limits(&mut self, limits: Option<RateLimits>) -> Result<(), RateLimitsError> {
+        if limits.is_some() && self.limits_timer.is_none() {
+            let timer = Timer::with_resolution(Duration::from_millis(1));
+            let handle = timer.handle();
+            let source = self.loophandle.add_timer(timer).map_err(RateLimitsError::Io)?;
+            self.limits_timer = Some(source);
+            *self.limits.timer.borrow_mut() = Some(handle);
+        }
+        *self.limits.limits.borrow_mut() = limits;
+        self.limits.generation.set(self.limits.generation.get() + 1);
+        Ok(())
+    }
+
+    pub(crate) fn set_flush_scheduling(&mut self, scheduling: FlushScheduling) {
+        self.scheduling = scheduling;
+        if scheduling == FlushScheduling::Threaded {
+            if self.writer.is_none() {
+                let (sender, receiver) = mpsc::channel();
+                let spawned = thread::Builder::new()
+                    .name("wayland-server-writer".into())
+                    .spawn(move || flush_writer(receiver));
+                match spawned {
+                    Ok(_) => self.writer = Some(sender),
+                    Err(e) => {
+                        eprintln!("[wayland-server] Failed to spawn the writer thread: {:?}", e);
+                        self.scheduling = FlushScheduling::Fair;
+                    }
+                }
+            }
+        } else {
+            // dropping the sender stops the thread
+            self.writer = None;
+        }
+    }
+
     pub(crate) fn flush_all(&mut self) {
-        // flush all clients and cleanup dead ones
-        self.clients.retain(|&(ref s, ref c)| {
-            if let Some(ref mut data) = *c.data.lock().unwrap() {
+        // resume the announcements of globals interrupted by full sockets
+        if let Ok(global_mgr) = self.global_mgr.try_borrow() {
+            global_mgr.announce_pending();
+        }
+
+        if self.scheduling == FlushScheduling::Sequential {
+            // flush all clients and cleanup dead ones
+            self.clients.retain(|&(ref s, ref c)| {
+                if let Some(ref mut data) = *c.data.lock().unwrap() {
+                    data.call_destructors();
+                    flushed(data.flush())
+                } else {
+                    // This is a dead client, clean it up
+                    if let Some(source) = s.borrow_mut().take() {
+                        source.remove();
+                    }
+                    false
+                }
+            });
+        } else {
+            self.flush_fair();
+        }
+
+        let mut guard = self.zombie_clients.lock().unwrap();
+        for zombie in guard.drain(..) {
+            zombie.cleanup();
+        }
+    }
+}
+
+impl ClientManager {
+    fn flush_fair(&mut self) {
+        let count = self.clients.len();
+        if count == 0 {
+            return;
+        }
+        let start = self.rotation % count;
+        self.rotation = self.rotation.wrapping_add(1);
+        let mut remove = vec![false; count];
+        let mut not_ready = Vec::new();
+        // first pass, flush the clients ready to receive data
+        for i in (start..count).chain(0..start) {
+            let (ref source, ref client) = self.clients[i];
+            if let Some(ref mut data) = *client.data.lock().unwrap() {
                 data.call_destructors();
-                data.flush().is_ok()
+                if data.writable() {
+                    match data.flush() {
+                        Ok(()) => {}
+                        // the socket filled up, try again with the slow clients
+                        Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => not_ready.push(i),
+                        Err(_) => remove[i] = true,
+                    }
+                } else {
+                    not_ready.push(i);
+                }
             } else {
                 // This is a dead client, clean it up
-                if let Some(source) = s.borrow_mut().take() {
+                if let Some(source) = source.borrow_mut().take() {
                     source.remove();
                 }
-                false
+                remove[i] = true;
             }
+        }
+        // second pass, the slow clients, their events stay buffered until the next
+        // flush if their socket is still full
+        for i in not_ready {
+            let client = &self.clients[i].1;
+            if let Some(ref writer) = self.writer {
+                if writer.send(client.clone()).is_ok() {
+                    continue;
+                }
+            }
+            if let Some(ref mut data) = *client.data.lock().unwrap() {
+                remove[i] = !flushed(data.flush());
+            }
+        }
+        let mut i = 0;
+        self.clients.retain(|_| {
+            i += 1;
+            !remove[i - 1]
         });
+    }
+}
 
-        let mut guard = self.zombie_clients.lock().unwrap();
-        for zombie in guard.drain(..) {
-            zombie.cleanup();
+// Whether a client is still alive after being flushed, a full socket being flushed again by the
+// next flush
+fn flushed(result: NixResult<()>) -> bool {
+    match result {
+        Ok(()) | Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => true,
+        Err(_) => false,
+    }
+}
+
+// Body of the writer thread of `FlushScheduling::Threaded`
+//
+// Flushes the clients it receives once their socket is writable, until the sender is dropped.
+fn flush_writer(receiver: Receiver<ClientInner>) {
+    let mut pending: Vec<ClientInner> = Vec::new();
+    loop {
+        if pending.is_empty() {
+            match receiver.recv() {
+                Ok(client) => pending.push(client),
+                Err(_) => return,
+            }
+        }
+        loop {
+            match receiver.try_recv() {
+                Ok(client) => if !pending.iter().any(|c| c.equals(&client)) {
+                    pending.push(client)
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+        // forget the clients that died in the meantime
+        let mut fds = Vec::with_capacity(pending.len());
+        pending.retain(|client| match *client.data.lock().unwrap() {
+            Some(ref mut data) => {
+                fds.push(PollFd::new(data.raw_fd(), EventFlags::POLLOUT));
+                true
+            }
+            None => false,
+        });
+        if pending.is_empty() {
+            continue;
+        }
+        // don't wait forever, to take new clients into account
+        if poll(&mut fds, 100).is_err() {
+            continue;
         }
+        let mut i = 0;
+        pending.retain(|client| {
+            i += 1;
+            let ready = fds[i - 1].revents().map(|r| !r.is_empty()).unwrap_or(false);
+            if !ready {
+                return true;
+            }
+            let ret = match *client.data.lock().unwrap() {
+                Some(ref mut data) => data.flush(),
+                None => return false,
+            };
+            match ret {
+                Ok(()) => false,
+                Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => true,
+                Err(_) => {
+                    client.kill();
+                    false
+                }
+            }
+        });
     }
 }
 
@@ -406,11 +768,15 @@ const DISPLAY_REQUESTS: &'static [MessageDesc] = &[
         name: "sync",
         since: 1,
         signature: &[ArgumentType::NewId],
+        destructor: false,
+        fd_kinds: &[],
     },
     MessageDesc {
         name: "get_registry",
         since: 1,
         signature: &[ArgumentType::NewId],
+        destructor: false,
+        fd_kinds: &[],
     },
 ];
 
@@ -419,11 +785,15 @@ const DISPLAY_EVENTS: &'static [MessageDesc] = &[
         name: "error",
         since: 1,
         signature: &[ArgumentType::Object, ArgumentType::Uint, ArgumentType::Str],
+        destructor: false,
+        fd_kinds: &[],
     },
     MessageDesc {
         name: "delete_id",
         since: 1,
         signature: &[ArgumentType::Uint],
+        destructor: false,
+        fd_kinds: &[],
     },
 ];
 
@@ -436,6 +806,8 @@ const REGISTRY_REQUESTS: &'static [MessageDesc] = &[MessageDesc {
         ArgumentType::Uint,
         ArgumentType::NewId,
     ],
+    destructor: false,
+    fd_kinds: &[],
 }];
 
 const REGISTRY_EVENTS: &'static [MessageDesc] = &[
@@ -443,11 +815,15 @@ const REGISTRY_EVENTS: &'static [MessageDesc] = &[
         name: "global",
         since: 1,
         signature: &[ArgumentType::Uint, ArgumentType::Str, ArgumentType::Uint],
+        destructor: false,
+        fd_kinds: &[],
     },
     MessageDesc {
         name: "global_remove",
         since: 1,
         signature: &[ArgumentType::Uint],
+        destructor: false,
+        fd_kinds: &[],
     },
 ];
 
@@ -479,15 +855,33 @@ fn no_child(_: u16, _: u32, _: &ObjectMeta) -> Option<Object<ObjectMeta>> {
 struct ClientImplementation {
     inner: ClientInner,
     map: Arc<Mutex<ObjectMap<ObjectMeta>>>,
+    logger: SharedLogger,
+    limits: Rc<SharedLimits>,
+    // the generation of the limits they were created for, and the buckets of the classes
+    buckets: RefCell<(u64, Vec<Option<Bucket>>)>,
+    // the request left undispatched when the client was throttled, with its trace id
+    deferred: RefCell<Option<(Message, u64)>>,
+    // whether the dispatching is suspended until the timer resumes it
+    throttled: Cell<bool>,
 }
 
 impl ClientImplementation {
-    fn process_messages(&self) {
+    fn process_messages(this: &Rc<ClientImplementation>) {
+        if this.throttled.get() {
+            // the requests are left in the socket until the client is resumed
+            return;
+        }
         loop {
             // we must process the messages one by one, because message parsing depends
             // on the contents of the object map, which each message can change...
-            let ret = if let Some(ref mut data) = *self.inner.data.lock().unwrap() {
-                data.read_request()
+            let ret = if let Some(ref mut data) = *this.inner.data.lock().unwrap() {
+                match this.deferred.borrow_mut().take() {
+                    Some(deferred) => Ok(Some(deferred)),
+                    // the message is dispatched right away, it is the last one read from the socket
+                    None => data
+                        .read_request()
+                        .map(|msg| msg.map(|msg| (msg, data.socket.received_messages()))),
+                }
             } else {
                 // client is now dead, abort
                 return;
@@ -498,24 +892,53 @@ impl ClientImplementation {
                     // nothing more to read
                     return;
                 }
-                Ok(Some(msg)) => {
+                Ok(Some((msg, trace_id))) => {
                     // there is a message to dispatch
-                    let mut resourcemap = super::ResourceMap::make(self.map.clone(), self.inner.clone());
+                    let mut resourcemap = super::ResourceMap::make(this.map.clone(), this.inner.clone());
                     let id = msg.sender_id;
                     let opcode = msg.opcode;
-                    if let Some(res) = ResourceInner::from_id(id, self.map.clone(), self.inner.clone()) {
+                    if let Some(res) = ResourceInner::from_id(id, this.map.clone(), this.inner.clone()) {
                         let object = res.object.clone();
+                        let request = object.requests[opcode as usize].name;
+                        match this.admit(object.interface, request) {
+                            Ok(()) => {}
+                            Err(Some(delay)) => {
+                                *this.deferred.borrow_mut() = Some((msg, trace_id));
+                                ClientImplementation::throttle(this, delay);
+                                return;
+                            }
+                            Err(None) => {
+                                this.inner.kill();
+                                return;
+                            }
+                        }
+                        let logger = this.logger.lock().unwrap().clone();
+                        trace::log(
+                            logger.as_ref(),
+                            Direction::Received,
+                            &LoggedMessage {
+                                interface: object.interface,
+                                name: object.requests[opcode as usize].name,
+                                trace_id,
+                                message: &msg,
+                            },
+                        );
                         let mut dispatcher = object.meta.dispatcher.lock().unwrap();
                         if let Err(()) = dispatcher.dispatch(msg, res, &mut resourcemap) {
-                            self.inner.post_error(
-                                1,
+                            // the arguments were rejected by the generated parsing, like libwayland
+                            // report it on the object receiving the request
+                            this.inner.post_error(
+                                id,
                                 super::display::DISPLAY_ERROR_INVALID_METHOD,
-                                format!("invalid method {}, object {}@{}", opcode, object.interface, id),
+                                format!(
+                                    "invalid arguments for {}@{}.{}",
+                                    object.interface, id, object.requests[opcode as usize].name
+                                ),
                             );
                             return;
                         }
                     } else {
-                        self.inner.post_error(
+                        this.inner.post_error(
                             1,
                             super::display::DISPLAY_ERROR_INVALID_OBJECT,
                             format!("invalid object {}", id),
@@ -525,12 +948,63 @@ impl ClientImplementation {
                 }
                 Err(_) => {
                     // on error, kill the client
-                    self.inner.kill();
+                    this.inner.kill();
                     return;
                 }
             }
         }
     }
+
+    // take a token for a request, or return what to do with it: throttle the client for
+    // the given delay, or kill it
+    fn admit(&self, interface: &'static str, request: &'static str) -> Result<(), Option<Duration>> {
+        let mut limits = self.limits.limits.borrow_mut();
+        let limits = match *limits {
+            Some(ref mut limits) => limits,
+            None => return Ok(()),
+        };
+        let (class, limit) = match limits.class_of(interface) {
+            Some(class) => class,
+            None => return Ok(()),
+        };
+        let delay = {
+            let mut buckets = self.buckets.borrow_mut();
+            let generation = self.limits.generation.get();
+            if buckets.0 != generation {
+                *buckets = (generation, Vec::new());
+            }
+            while buckets.1.len() <= class {
+                buckets.1.push(None);
+            }
+            let bucket = buckets.1[class].get_or_insert_with(|| Bucket::new(limit));
+            match bucket.take(limit) {
+                Ok(()) => return Ok(()),
+                Err(delay) => delay,
+            }
+        };
+        let client = Client::make(self.inner.clone());
+        match limits.policy(&client, &RateLimited { interface, request }) {
+            RateAction::Allow => Ok(()),
+            RateAction::Throttle => Err(Some(delay)),
+            RateAction::Kill => Err(None),
+        }
+    }
+
+    // suspend the dispatching of the requests until the delay expires
+    fn throttle(this: &Rc<ClientImplementation>, delay: Duration) {
+        let weak = Rc::downgrade(this);
+        let resume: DeferredCall = Box::new(move || {
+            if let Some(this) = weak.upgrade() {
+                this.throttled.set(false);
+                ClientImplementation::process_messages(&this);
+            }
+        });
+        if let Some(ref timer) = *this.limits.timer.borrow() {
+            if timer.add_timeout(delay, resume).is_ok() {
+                this.throttled.set(true);
+            }
+        }
+    }
 }
 
 struct DisplayDispatcher {
@@ -581,10 +1055,16 @@ impl super::Dispatcher for DisplayDispatcher {
     fn destroy(&mut self, _resource: ResourceInner) {}
 }
 
-struct RegistryDispatcher {
+pub(crate) struct RegistryDispatcher {
     global_mgr: Rc<RefCell<GlobalManager>>,
 }
 
+impl RegistryDispatcher {
+    pub(crate) fn remove_registry(&self, id: u32, client: &ClientInner) {
+        self.global_mgr.borrow_mut().remove_registry(id, client);
+    }
+}
+
 impl super::Dispatcher for RegistryDispatcher {
     fn dispatch(
         &mut self,
@@ -626,3 +1106,62 @@ impl super::Dispatcher for RegistryDispatcher {
 // reasons as super::ImplDispatcher
 unsafe impl Send for DisplayDispatcher {}
 unsafe impl Send for RegistryDispatcher {}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(fd: RawFd) -> Option<Credentials> {
+    let mut cred: ::libc::ucred = unsafe { ::std::mem::zeroed() };
+    let mut len = ::std::mem::size_of::<::libc::ucred>() as ::libc::socklen_t;
+    let ret = unsafe {
+        ::libc::getsockopt(
+            fd,
+            ::libc::SOL_SOCKET,
+            ::libc::SO_PEERCRED,
+            &mut cred as *mut _ as *mut ::libc::c_void,
+            &mut len,
+        )
+    };
+    if ret == 0 {
+        Some(Credentials {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "openbsd")]
+fn peer_credentials(fd: RawFd) -> Option<Credentials> {
+    let mut cred: ::libc::sockpeercred = unsafe { ::std::mem::zeroed() };
+    let mut len = ::std::mem::size_of::<::libc::sockpeercred>() as ::libc::socklen_t;
+    let ret = unsafe {
+        ::libc::getsockopt(
+            fd,
+            ::libc::SOL_SOCKET,
+            ::libc::SO_PEERCRED,
+            &mut cred as *mut _ as *mut ::libc::c_void,
+            &mut len,
+        )
+    };
+    if ret == 0 {
+        Some(Credentials {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        })
+    } else {
+        None
+    }
+}
+
+// the other BSDs only provide the user and group of the peer
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "openbsd")))]
+fn peer_credentials(fd: RawFd) -> Option<Credentials> {
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { ::libc::getpeereid(fd, &mut uid, &mut gid) } == 0 {
+        Some(Credentials { pid: 0, uid, gid })
+    } else {
+        None
+    }
+}
@@ -1,15 +1,23 @@
 use std::cell::RefCell;
 use std::env;
 use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
 use std::io;
-use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::os::unix::net::UnixListener;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use calloop::{LoopHandle, Source};
 
-use display::get_runtime_dir;
+use nix::fcntl::{flock, FlockArg};
+
+use wayland_commons::socket::ConnectionStats;
+use wayland_commons::trace::ProtocolLogger;
+
+use display::{get_runtime_dir, FlushScheduling};
+use ratelimit::{RateLimits, RateLimitsError};
 use {Interface, NewResource};
 
 use super::clients::ClientManager;
@@ -27,6 +35,56 @@ pub(crate) struct DisplayInner {
     pub(crate) clients_mgr: Rc<RefCell<ClientManager>>,
     global_mgr: Rc<RefCell<GlobalManager>>,
     listeners: Vec<Source<WaylandListener>>,
+    sockets: Vec<SocketLock>,
+}
+
+// a socket of the runtime directory, with the lock file reserving its name
+//
+// As with libwayland-server, the socket and its lock file are removed along with the display.
+struct SocketLock {
+    path: PathBuf,
+    lock_path: PathBuf,
+    _lock: File,
+}
+
+impl SocketLock {
+    // lock the name of a socket, removing the one left behind by a compositor which crashed
+    fn acquire(path: PathBuf) -> io::Result<SocketLock> {
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .mode(0o660)
+            .open(&lock_path)?;
+        if flock(lock.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("socket {} is already in use", path.to_string_lossy()),
+            ));
+        }
+        // we hold the lock, an existing socket is stale
+        match fs::remove_file(&path) {
+            Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(io::Error::new(e.kind(), format!("could not remove stale socket: {}", e)))
+            }
+            _ => {}
+        }
+        Ok(SocketLock {
+            path,
+            lock_path,
+            _lock: lock,
+        })
+    }
+}
+
+impl Drop for SocketLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_file(&self.lock_path);
+    }
 }
 
 impl DisplayInner {
@@ -41,6 +99,7 @@ impl DisplayInner {
             ))),
             global_mgr,
             listeners: Vec::new(),
+            sockets: Vec::new(),
         }));
 
         display
@@ -65,6 +124,26 @@ impl DisplayInner {
         self.clients_mgr.borrow_mut().flush_all()
     }
 
+    pub(crate) fn set_protocol_logger(&mut self, logger: Option<ProtocolLogger>) {
+        self.clients_mgr.borrow_mut().set_protocol_logger(logger)
+    }
+
+    pub(crate) fn set_flush_scheduling(&mut self, scheduling: FlushScheduling) {
+        self.clients_mgr.borrow_mut().set_flush_scheduling(scheduling)
+    }
+
+    pub(crate) fn set_rate_limits(&mut self, limits: Option<RateLimits>) -> Result<(), RateLimitsError> {
+        self.clients_mgr.borrow_mut().set_rate_limits(limits)
+    }
+
+    pub(crate) fn client_count(&self) -> Option<usize> {
+        Some(self.clients_mgr.borrow().stats().0)
+    }
+
+    pub(crate) fn connection_stats(&self) -> Option<ConnectionStats> {
+        Some(self.clients_mgr.borrow().stats().1)
+    }
+
     fn add_unix_listener(&mut self, listener: UnixListener) -> io::Result<()> {
         listener.set_nonblocking(true)?;
 
@@ -101,9 +180,12 @@ impl DisplayInner {
             path.push("wayland-0");
         }
 
-        let listener = UnixListener::bind(path)?;
+        let socket = SocketLock::acquire(path)?;
+        let listener = UnixListener::bind(&socket.path)?;
 
-        self.add_unix_listener(listener)
+        self.add_unix_listener(listener)?;
+        self.sockets.push(socket);
+        Ok(())
     }
 
     pub(crate) fn add_socket_auto(&mut self) -> io::Result<OsString> {
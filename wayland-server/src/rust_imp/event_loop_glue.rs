@@ -5,6 +5,7 @@ use std::os::unix::net::{UnixListener, UnixStream};
 use std::rc::Rc;
 
 use calloop::generic::{Event, Generic};
+use calloop::timer::Timer;
 use calloop::{EventDispatcher, EventSource, LoopHandle, Source};
 
 use mio::{Evented, Poll, PollOpt, Ready, Token};
@@ -19,15 +20,20 @@ pub(crate) trait WSLoopHandle {
     ) -> io::Result<Source<WaylandListener>>;
 
     fn add_socket(&self, source: Generic<Fd>, cb: Box<FnMut(Event<Fd>)>) -> io::Result<Source<Generic<Fd>>>;
+
+    // a timer whose timeouts carry the closure to run once they expire
+    fn add_timer(&self, source: Timer<DeferredCall>) -> io::Result<Source<Timer<DeferredCall>>>;
 }
 
+pub(crate) type DeferredCall = Box<FnMut()>;
+
 impl<Data: 'static> WSLoopHandle for LoopHandle<Data> {
     fn add_listener(
         &self,
         source: WaylandListener,
         mut cb: Box<FnMut(UnixStream)>,
     ) -> io::Result<Source<WaylandListener>> {
-        self.insert_source(source, move |evt, _| cb(evt))
+        self.insert_source(source, move |evt, data| ::dispatch_state::with_state(data, || cb(evt)))
     }
 
     fn add_socket(
@@ -35,7 +41,11 @@ impl<Data: 'static> WSLoopHandle for LoopHandle<Data> {
         source: Generic<Fd>,
         mut cb: Box<FnMut(Event<Fd>)>,
     ) -> io::Result<Source<Generic<Fd>>> {
-        self.insert_source(source, move |evt, _| cb(evt))
+        self.insert_source(source, move |evt, data| ::dispatch_state::with_state(data, || cb(evt)))
+    }
+
+    fn add_timer(&self, source: Timer<DeferredCall>) -> io::Result<Source<Timer<DeferredCall>>> {
+        self.insert_source(source, |(mut call, _), data| ::dispatch_state::with_state(data, || call()))
     }
 }
 
@@ -8,14 +8,15 @@ use wayland_commons::wire::{Argument, Message};
 use {Interface, NewResource};
 
 use super::resources::ObjectMeta;
-use super::{ClientInner, NewResourceInner};
+use super::{ClientInner, NewResourceInner, ResourceInner};
 
 pub(crate) struct GlobalInner<I: Interface> {
     _i: ::std::marker::PhantomData<*const I>,
     destroyed_marker: Rc<Cell<bool>>,
     id: u32,
-    registries: Rc<RefCell<Vec<(u32, ClientInner)>>>,
+    registries: Rc<RefCell<Vec<Registry>>>,
     filter: Option<Rc<RefCell<FnMut(ClientInner) -> bool>>>,
+    resources: Rc<RefCell<Vec<ResourceInner>>>,
 }
 
 impl<I: Interface> GlobalInner<I> {
@@ -27,6 +28,29 @@ impl<I: Interface> GlobalInner<I> {
             self.filter.as_ref().map(|f| &**f),
         );
     }
+
+    pub fn destroy_and_invalidate(self) -> Vec<ResourceInner> {
+        let resources = ::std::mem::replace(&mut *self.resources.borrow_mut(), Vec::new());
+        self.destroy();
+        let mut invalidated = Vec::new();
+        for resource in resources {
+            if resource.is_alive() {
+                resource.invalidate();
+                invalidated.push(resource);
+            }
+        }
+        invalidated
+    }
+}
+
+// number of globals announced to a new registry between two flushes of its client
+const ANNOUNCE_CHUNK: usize = 16;
+
+struct Registry {
+    id: u32,
+    client: ClientInner,
+    // while the existing globals are announced, the id of the next one to announce
+    announcing: Option<u32>,
 }
 
 struct GlobalData {
@@ -38,7 +62,7 @@ struct GlobalData {
 }
 
 pub(crate) struct GlobalManager {
-    registries: Rc<RefCell<Vec<(u32, ClientInner)>>>,
+    registries: Rc<RefCell<Vec<Registry>>>,
     globals: Vec<GlobalData>,
 }
 
@@ -61,6 +85,8 @@ impl GlobalManager {
         F2: FnMut(ClientInner) -> bool + 'static,
     {
         let implem = RefCell::new(implementation);
+        let resources = Rc::new(RefCell::new(Vec::<ResourceInner>::new()));
+        let bound_resources = resources.clone();
         let data = GlobalData {
             version,
             interface: I::NAME,
@@ -81,9 +107,13 @@ impl GlobalManager {
                 };
                 if let Some(map) = map {
                     (&mut *implem.borrow_mut())(
-                        NewResource::wrap(NewResourceInner::from_id(newid, map, client.clone()).unwrap()),
+                        NewResource::wrap(NewResourceInner::from_id(newid, map.clone(), client.clone()).unwrap()),
                         version,
-                    )
+                    );
+                    // keep track of the resources bound from this global, in case it gets invalidated
+                    let mut bound = bound_resources.borrow_mut();
+                    bound.retain(|r| r.is_alive());
+                    bound.extend(ResourceInner::from_id(newid, map, client));
                 }
                 Ok(())
             }),
@@ -113,29 +143,37 @@ impl GlobalManager {
             id,
             registries: self.registries.clone(),
             filter,
+            resources,
         }
     }
 
     pub(crate) fn new_registry(&mut self, id: u32, client: ClientInner) {
-        let reg = (id, client);
-        for (id, global) in self.globals.iter_mut().enumerate() {
-            if global.destroyed.get() {
-                continue;
-            }
-            if let Some(ref filter) = global.filter {
-                if !(&mut *filter.borrow_mut())(reg.1.clone()) {
-                    continue;
-                }
-            }
-            let interface = CString::new(global.interface.as_bytes().to_owned()).unwrap();
-            send_global_msg(&reg, id as u32 + 1, interface, global.version);
-        }
+        let mut reg = Registry {
+            id,
+            client,
+            announcing: Some(1),
+        };
+        announce_globals(&self.globals, &mut reg);
         self.registries.borrow_mut().push(reg);
 
         // cleanup destroyed clients, to avoid accumulating stale connections
         self.self_cleanup();
     }
 
+    // resume the announcements to the registries whose clients could not receive all the
+    // globals at once
+    pub(crate) fn announce_pending(&self) {
+        for reg in self.registries.borrow_mut().iter_mut() {
+            announce_globals(&self.globals, reg);
+        }
+    }
+
+    pub(crate) fn remove_registry(&mut self, id: u32, client: &ClientInner) {
+        self.registries
+            .borrow_mut()
+            .retain(|reg| reg.id != id || !reg.client.equals(client));
+    }
+
     pub(crate) fn bind(
         &self,
         registry_id: u32,
@@ -202,74 +240,150 @@ impl GlobalManager {
     fn self_cleanup(&self) {
         self.registries
             .borrow_mut()
-            .retain(|&(_, ref client)| client.alive());
+            .retain(|reg| reg.client.alive());
     }
 }
 
-fn send_global_msg(reg: &(u32, ClientInner), global_id: u32, interface: CString, version: u32) {
-    if let Some(ref mut clientconn) = *reg.1.data.lock().unwrap() {
-        let _ = clientconn.write_message(&Message {
-            sender_id: reg.0,
-            opcode: 0,
-            args: vec![
-                Argument::Uint(global_id),
-                Argument::Str(interface),
-                Argument::Uint(version),
-            ],
-        });
+// Announce the existing globals to a new registry
+//
+// The globals are sent by chunks, flushing the client in between, so that a client with a small
+// socket buffer does not lose them: if its socket is full, the announcement stops and is resumed
+// by `GlobalManager::announce_pending()`.
+fn announce_globals(globals: &[GlobalData], reg: &mut Registry) {
+    while let Some(next) = reg.announcing {
+        let chunk = globals
+            .iter()
+            .enumerate()
+            .skip(next as usize - 1)
+            .take(ANNOUNCE_CHUNK);
+        for (i, global) in chunk {
+            let skipped = global.destroyed.get()
+                || global
+                    .filter
+                    .as_ref()
+                    .map(|filter| !(&mut *filter.borrow_mut())(reg.client.clone()))
+                    .unwrap_or(false);
+            if !skipped {
+                let interface = CString::new(global.interface.as_bytes().to_owned()).unwrap();
+                match send_global_msg(reg.id, &reg.client, i as u32 + 1, interface, global.version) {
+                    Ok(()) => {}
+                    // the socket is full, try again later
+                    Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => return,
+                    Err(_) => {
+                        reg.announcing = None;
+                        return;
+                    }
+                }
+            }
+            reg.announcing = Some(i as u32 + 2);
+        }
+        if reg.announcing.map(|next| next as usize > globals.len()).unwrap_or(true) {
+            // all the globals were announced, the new ones will be sent as they are created
+            reg.announcing = None;
+            return;
+        }
+        let flushed = match *reg.client.data.lock().unwrap() {
+            Some(ref mut clientconn) => clientconn.flush(),
+            None => Err(::nix::Error::Sys(::nix::errno::Errno::EPIPE)),
+        };
+        match flushed {
+            Ok(()) => {}
+            Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => return,
+            Err(_) => reg.announcing = None,
+        }
+    }
+}
+
+fn send_global_msg(
+    registry_id: u32,
+    client: &ClientInner,
+    global_id: u32,
+    interface: CString,
+    version: u32,
+) -> ::nix::Result<()> {
+    if let Some(ref mut clientconn) = *client.data.lock().unwrap() {
+        clientconn.write_event(
+            "wl_registry",
+            "global",
+            &Message {
+                sender_id: registry_id,
+                opcode: 0,
+                args: vec![
+                    Argument::Uint(global_id),
+                    Argument::Str(interface),
+                    Argument::Uint(version),
+                ],
+            },
+        )
+    } else {
+        Err(::nix::Error::Sys(::nix::errno::Errno::EPIPE))
     }
 }
 
 fn send_new_global(
-    registries: &[(u32, ClientInner)],
+    registries: &[Registry],
     global_id: u32,
     interface: &str,
     version: u32,
     filter: Option<&RefCell<FnMut(ClientInner) -> bool>>,
 ) {
     let iface = CString::new(interface.as_bytes().to_owned()).unwrap();
+    // the registries still being announced the existing globals will reach this one
+    let registries = registries.iter().filter(|reg| reg.announcing.is_none());
     if let Some(filter) = filter {
         let mut filter = filter.borrow_mut();
         for reg in registries {
-            if !(&mut *filter)(reg.1.clone()) {
+            if !(&mut *filter)(reg.client.clone()) {
                 continue;
             }
-            send_global_msg(reg, global_id, iface.clone(), version)
+            let _ = send_global_msg(reg.id, &reg.client, global_id, iface.clone(), version);
         }
     } else {
         for reg in registries {
-            send_global_msg(reg, global_id, iface.clone(), version)
+            let _ = send_global_msg(reg.id, &reg.client, global_id, iface.clone(), version);
         }
     }
 }
 
 fn send_destroyed_global(
-    registries: &[(u32, ClientInner)],
+    registries: &[Registry],
     global_id: u32,
     filter: Option<&RefCell<FnMut(ClientInner) -> bool>>,
 ) {
+    // the registries still being announced the existing globals may not know this one yet
+    let registries = registries
+        .iter()
+        .filter(|reg| reg.announcing.map(|next| global_id < next).unwrap_or(true));
     if let Some(filter) = filter {
         let mut filter = filter.borrow_mut();
-        for &(id, ref client) in registries {
-            if !(&mut *filter)(client.clone()) {
+        for reg in registries {
+            if !(&mut *filter)(reg.client.clone()) {
                 continue;
             }
-            if let Some(ref mut clientconn) = *client.data.lock().unwrap() {
-                let _ = clientconn.write_message(&Message {
-                    sender_id: id,
-                    opcode: 1,
-                    args: vec![Argument::Uint(global_id)],
-                });
+            if let Some(ref mut clientconn) = *reg.client.data.lock().unwrap() {
+                let _ = clientconn.write_event(
+                    "wl_registry",
+                    "global_remove",
+                    &Message {
+                        sender_id: reg.id,
+                        opcode: 1,
+                        args: vec![Argument::Uint(global_id)],
+                    },
+                );
             }
         }
     } else {
-        for &(id, ref client) in registries {
-            if let Some(ref mut clientconn) = *client.data.lock().unwrap() {
-                let _ = clientconn.write_message(&Message {
-                    sender_id: id,
-                    opcode: 1,
-                    args: vec![Argument::Uint(global_id)],
-                });
+        for reg in registries {
+            if let Some(ref mut clientconn) = *reg.client.data.lock().unwrap() {
+                let _ = clientconn.write_event(
+                    "wl_registry",
+                    "global_remove",
+                    &Message {
+                        sender_id: reg.id,
+                        opcode: 1,
+                        args: vec![Argument::Uint(global_id)],
+                    },
+                );
             }
         }
     }
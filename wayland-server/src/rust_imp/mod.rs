@@ -93,16 +93,10 @@ where
     I::Request: MessageGroup<Map = ResourceMap>,
 {
     fn dispatch(&mut self, msg: Message, resource: ResourceInner, map: &mut ResourceMap) -> Result<(), ()> {
-        if ::std::env::var_os("WAYLAND_DEBUG").is_some() {
-            println!(
-                " <- {}@{}: {} {:?}",
-                resource.object.interface,
-                resource.id,
-                resource.object.requests[msg.opcode as usize].name,
-                msg.args
-            );
-        }
         let message = I::Request::from_raw(msg, map)?;
+        // requests to objects whose global was invalidated are dropped,
+        // but destructors still need to free the object
+        let defunct = resource.is_defunct();
         if message.is_destructor() {
             resource.object.meta.alive.store(false, Ordering::Release);
             let mut kill = false;
@@ -113,8 +107,10 @@ where
             if kill {
                 resource.client.kill();
             }
-            self.implementation.as_mut().unwrap()(message, Resource::<I>::wrap(resource.clone()));
-        } else {
+            if !defunct {
+                self.implementation.as_mut().unwrap()(message, Resource::<I>::wrap(resource.clone()));
+            }
+        } else if !defunct {
             self.implementation.as_mut().unwrap()(message, Resource::<I>::wrap(resource));
         }
         Ok(())
@@ -7,13 +7,20 @@ use wayland_commons::map::{Object, ObjectMap, ObjectMetadata};
 use wayland_commons::utils::UserData;
 use wayland_commons::MessageGroup;
 
+use super::clients::RegistryDispatcher;
 use super::{ClientInner, Dispatcher};
 
+// an additional destructor of a resource
+type Destructor = Box<FnMut(ResourceInner) + Send>;
+
 #[derive(Clone)]
 pub(crate) struct ObjectMeta {
     pub(crate) dispatcher: Arc<Mutex<Dispatcher>>,
     pub(crate) alive: Arc<AtomicBool>,
+    pub(crate) defunct: Arc<AtomicBool>,
     user_data: Arc<UserData>,
+    // the destructors assigned after the implementation, `None` once they were run
+    destructors: Arc<Mutex<Option<Vec<Destructor>>>>,
 }
 
 impl ObjectMetadata for ObjectMeta {
@@ -26,7 +33,9 @@ impl ObjectMeta {
     pub(crate) fn new() -> ObjectMeta {
         ObjectMeta {
             alive: Arc::new(AtomicBool::new(true)),
+            defunct: Arc::new(AtomicBool::new(false)),
             user_data: Arc::new(UserData::empty()),
+            destructors: Arc::new(Mutex::new(Some(Vec::new()))),
             dispatcher: super::default_dispatcher(),
         }
     }
@@ -34,7 +43,9 @@ impl ObjectMeta {
     pub(crate) fn dead() -> ObjectMeta {
         ObjectMeta {
             alive: Arc::new(AtomicBool::new(false)),
+            defunct: Arc::new(AtomicBool::new(false)),
             user_data: Arc::new(UserData::empty()),
+            destructors: Arc::new(Mutex::new(Some(Vec::new()))),
             dispatcher: super::default_dispatcher(),
         }
     }
@@ -42,7 +53,9 @@ impl ObjectMeta {
     pub(crate) fn with_dispatcher<D: Dispatcher>(disp: D) -> ObjectMeta {
         ObjectMeta {
             alive: Arc::new(AtomicBool::new(true)),
+            defunct: Arc::new(AtomicBool::new(false)),
             user_data: Arc::new(UserData::empty()),
+            destructors: Arc::new(Mutex::new(Some(Vec::new()))),
             dispatcher: Arc::new(Mutex::new(disp)),
         }
     }
@@ -76,30 +89,29 @@ impl ResourceInner {
     }
 
     pub(crate) fn send<I: Interface>(&self, msg: I::Event) {
-        if let Some(ref mut conn_lock) = *self.client.data.lock().unwrap() {
+        let failed = if let Some(ref mut conn_lock) = *self.client.data.lock().unwrap() {
             if !self.is_alive() {
                 return;
             }
             let destructor = msg.is_destructor();
             let msg = msg.into_raw(self.id);
-            if ::std::env::var_os("WAYLAND_DEBUG").is_some() {
-                println!(
-                    " -> {}@{}: {} {:?}",
-                    I::NAME,
-                    self.id,
-                    self.object.events[msg.opcode as usize].name,
-                    msg.args
-                );
-            }
-            // TODO: figure our if this can fail and still be recoverable ?
-            let _ = conn_lock.write_message(&msg).expect("Sending a message failed.");
-            if destructor {
+            self.object.events[msg.opcode as usize].check_fds(I::NAME, msg.fds());
+            let written = conn_lock.write_event(I::NAME, self.object.events[msg.opcode as usize].name, &msg);
+            if written.is_ok() && destructor {
                 self.object.meta.alive.store(false, Ordering::Release);
                 // schedule a destructor
                 conn_lock.schedule_destructor(self.clone());
                 // send delete_id
                 let _ = conn_lock.delete_id(self.id);
             }
+            written.is_err()
+        } else {
+            false
+        };
+        if failed {
+            // the socket of the client is full, or broken: the event is lost and the client
+            // would go out of sync with the server, disconnect it
+            self.client.kill();
         }
     }
 
@@ -107,6 +119,69 @@ impl ResourceInner {
         self.object.meta.alive.load(Ordering::Acquire)
     }
 
+    pub(crate) fn is_defunct(&self) -> bool {
+        self.object.meta.defunct.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn assign_destructor(&self, destructor: Destructor) -> bool {
+        // the destructors are taken under this lock when they are run
+        match *self.object.meta.destructors.lock().unwrap() {
+            Some(ref mut destructors) if self.is_alive() => {
+                destructors.push(destructor);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // run the assigned destructors, after the one of the implementation
+    pub(crate) fn run_destructors(&self) {
+        let destructors = self
+            .object
+            .meta
+            .destructors
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_default();
+        for mut destructor in destructors {
+            destructor(self.clone());
+        }
+    }
+
+    pub(crate) fn invalidate(&self) {
+        self.object.meta.defunct.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn destroy_registry(&self) {
+        if !self.is_alive() {
+            return;
+        }
+        if self.object.interface != "wl_registry" {
+            self.client.post_error(
+                1,
+                super::display::DISPLAY_ERROR_INVALID_OBJECT,
+                format!("object {}@{} is not a registry", self.object.interface, self.id),
+            );
+            return;
+        }
+        self.object.meta.alive.store(false, Ordering::Release);
+        {
+            // stop advertizing globals to this registry
+            let dispatcher = self.object.meta.dispatcher.lock().unwrap();
+            if let Some(registry) = dispatcher.downcast_ref::<RegistryDispatcher>() {
+                registry.remove_registry(self.id, &self.client);
+            }
+        }
+        let mut kill = false;
+        if let Some(ref mut data) = *self.client.data.lock().unwrap() {
+            kill = data.delete_id(self.id).is_err();
+        }
+        if kill {
+            self.client.kill();
+        }
+    }
+
     pub(crate) fn version(&self) -> u32 {
         self.object.version
     }
@@ -0,0 +1,326 @@
+//! Forwarding of data transfers between clients
+//!
+//! The contents of the selection or of a drag-and-drop operation are written by the source
+//! client to the pipe given by the destination client with `wl_data_offer.receive`. The
+//! compositor usually just hands this pipe over with `wl_data_source.send`, but sometimes
+//! has to relay the data itself, between a pipe it reads from and a pipe it writes to: to
+//! keep the selection of a client that exits, or to translate between the selections of
+//! several seats, of nested sessions or of X11 clients.
+//!
+//! Reading such a pipe must not block the event loop of the compositor: a client may be slow
+//! to write its data, or never write it at all. `start_transfer()` inserts the two ends in
+//! the event loop and moves the data as they become ready, without ever blocking. The size
+//! of the data and the time the transfer can stall are limited by a `TransferLimits`, and the
+//! outcome of the transfer is reported to a callback once it is over.
+
+use std::cell::RefCell;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use calloop::generic::{EventedRawFd, Generic};
+use calloop::timer::Timer;
+use calloop::{LoopHandle, Source};
+
+use mio::{PollOpt, Ready};
+
+use nix::errno::Errno;
+use nix::fcntl::{self, OFlag};
+use nix::unistd;
+
+// the default capacity of a pipe
+const BUFFER_SIZE: usize = 65536;
+
+/// The limits of a transfer
+///
+/// The default value does not limit transfers.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TransferLimits {
+    /// The maximum number of bytes to transfer
+    ///
+    /// The transfer fails with `TransferResult::TooLarge` once the source has written more.
+    pub max_size: Option<usize>,
+    /// The maximum time the transfer can stay without progress
+    ///
+    /// The transfer fails with `TransferResult::Stalled` if no data could be read or
+    /// written for that long.
+    pub timeout: Option<Duration>,
+}
+
+/// The outcome of a transfer
+#[derive(Debug)]
+pub enum TransferResult {
+    /// All the data was transferred, this many bytes
+    Done(usize),
+    /// The source wrote more data than the maximum size
+    TooLarge,
+    /// The transfer made no progress during the timeout, after this many bytes
+    Stalled(usize),
+    /// Reading or writing failed, like when the destination closes its pipe early
+    Failed(io::Error),
+}
+
+// the state of a transfer, shared by its event sources
+struct Pump {
+    from: RawFd,
+    to: RawFd,
+    limits: TransferLimits,
+    // the data read but not written yet is `buffer[start..end]`
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+    read: usize,
+    written: usize,
+    eof: bool,
+    last_progress: Instant,
+    finished: bool,
+    from_source: Option<Source<Generic<EventedRawFd>>>,
+    to_source: Option<Source<Generic<EventedRawFd>>>,
+    timer: Option<Source<Timer<()>>>,
+}
+
+impl Pump {
+    // move as much data as possible without blocking, returns the outcome of the transfer
+    // if it is over
+    fn pump(&mut self) -> Option<TransferResult> {
+        loop {
+            let mut progress = false;
+            while self.start < self.end {
+                match unistd::write(self.to, &self.buffer[self.start..self.end]) {
+                    Ok(n) => {
+                        self.start += n;
+                        self.written += n;
+                        progress = true;
+                    }
+                    Err(::nix::Error::Sys(Errno::EINTR)) => {}
+                    Err(::nix::Error::Sys(Errno::EAGAIN)) => break,
+                    Err(::nix::Error::Sys(errno)) => return Some(TransferResult::Failed(errno.into())),
+                    Err(_) => unreachable!(),
+                }
+            }
+            if self.start == self.end {
+                self.start = 0;
+                self.end = 0;
+                if self.eof {
+                    return Some(TransferResult::Done(self.written));
+                }
+            }
+            if !self.eof && self.end < self.buffer.len() {
+                match unistd::read(self.from, &mut self.buffer[self.end..]) {
+                    Ok(0) => {
+                        self.eof = true;
+                        progress = true;
+                    }
+                    Ok(n) => {
+                        self.end += n;
+                        self.read += n;
+                        progress = true;
+                        if self.limits.max_size.map(|max| self.read > max).unwrap_or(false) {
+                            return Some(TransferResult::TooLarge);
+                        }
+                    }
+                    Err(::nix::Error::Sys(Errno::EINTR)) => progress = true,
+                    Err(::nix::Error::Sys(Errno::EAGAIN)) => {}
+                    Err(::nix::Error::Sys(errno)) => return Some(TransferResult::Failed(errno.into())),
+                    Err(_) => unreachable!(),
+                }
+            }
+            if !progress {
+                return None;
+            }
+            self.last_progress = Instant::now();
+        }
+    }
+
+    // remove the event sources, then close the pipes
+    fn close(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        if let Some(source) = self.from_source.take() {
+            source.remove();
+        }
+        if let Some(source) = self.to_source.take() {
+            source.remove();
+        }
+        if let Some(timer) = self.timer.take() {
+            timer.remove();
+        }
+        let _ = unistd::close(self.from);
+        let _ = unistd::close(self.to);
+    }
+}
+
+impl Drop for Pump {
+    fn drop(&mut self) {
+        // the event loop was dropped before the end of the transfer
+        if !self.finished {
+            let _ = unistd::close(self.from);
+            let _ = unistd::close(self.to);
+        }
+    }
+}
+
+/// A transfer in progress, see `start_transfer()`
+///
+/// Dropping it does not stop the transfer.
+pub struct Transfer {
+    pump: Rc<RefCell<Pump>>,
+}
+
+impl Transfer {
+    /// The number of bytes written to the destination so far
+    pub fn transferred(&self) -> usize {
+        self.pump.borrow().written
+    }
+
+    /// Whether the transfer is over
+    pub fn is_finished(&self) -> bool {
+        self.pump.borrow().finished
+    }
+
+    /// Stop the transfer, closing both pipes
+    ///
+    /// The callback of the transfer is not invoked.
+    pub fn cancel(self) {
+        self.pump.borrow_mut().close();
+    }
+}
+
+/// Start relaying the data read from `from` to `to`
+///
+/// Both file descriptors are typically pipes: `from` the read end of a pipe whose write end
+/// was given to a source client with `wl_data_source.send`, and `to` the pipe given by a
+/// destination client with `wl_data_offer.receive`. They are made non-blocking, and both are
+/// closed once the transfer is over: when all the data read from `from` until its end was
+/// written to `to`, or when it failed. They are also closed if this function fails.
+///
+/// `callback` is invoked from the event loop with the outcome of the transfer, unless it is
+/// cancelled before.
+pub fn start_transfer<Data: 'static, F>(
+    handle: &LoopHandle<Data>,
+    from: RawFd,
+    to: RawFd,
+    limits: TransferLimits,
+    callback: F,
+) -> io::Result<Transfer>
+where
+    F: FnOnce(TransferResult, &mut Data) + 'static,
+{
+    for &fd in &[from, to] {
+        match fcntl::fcntl(fd, fcntl::FcntlArg::F_SETFL(OFlag::O_NONBLOCK)) {
+            Ok(_) => {}
+            Err(::nix::Error::Sys(errno)) => {
+                let _ = unistd::close(from);
+                let _ = unistd::close(to);
+                return Err(errno.into());
+            }
+            Err(_) => unreachable!(),
+        }
+    }
+
+    let pump = Rc::new(RefCell::new(Pump {
+        from,
+        to,
+        limits,
+        buffer: vec![0; BUFFER_SIZE],
+        start: 0,
+        end: 0,
+        read: 0,
+        written: 0,
+        eof: false,
+        last_progress: Instant::now(),
+        finished: false,
+        from_source: None,
+        to_source: None,
+        timer: None,
+    }));
+    let callback = Rc::new(RefCell::new(Some(callback)));
+
+    match insert_sources(handle, &pump, &callback) {
+        Ok(()) => Ok(Transfer { pump }),
+        Err(err) => {
+            pump.borrow_mut().close();
+            Err(err)
+        }
+    }
+}
+
+fn insert_sources<Data: 'static, F>(
+    handle: &LoopHandle<Data>,
+    pump: &Rc<RefCell<Pump>>,
+    callback: &Rc<RefCell<Option<F>>>,
+) -> io::Result<()>
+where
+    F: FnOnce(TransferResult, &mut Data) + 'static,
+{
+    let (from, to, timeout) = {
+        let pump = pump.borrow();
+        (pump.from, pump.to, pump.limits.timeout)
+    };
+
+    // the pipes are polled edge-triggered, the pump always reading or writing them until they
+    // would block
+    let mut from_source = Generic::from_raw_fd(from);
+    from_source.set_interest(Ready::readable());
+    from_source.set_pollopts(PollOpt::edge());
+    let (pump2, callback2) = (pump.clone(), callback.clone());
+    let from_source = handle.insert_source(from_source, move |_, data| ready(&pump2, &callback2, data))?;
+    pump.borrow_mut().from_source = Some(from_source);
+
+    let mut to_source = Generic::from_raw_fd(to);
+    to_source.set_interest(Ready::writable());
+    to_source.set_pollopts(PollOpt::edge());
+    let (pump2, callback2) = (pump.clone(), callback.clone());
+    let to_source = handle.insert_source(to_source, move |_, data| ready(&pump2, &callback2, data))?;
+    pump.borrow_mut().to_source = Some(to_source);
+
+    if let Some(timeout) = timeout {
+        let (pump2, callback2) = (pump.clone(), callback.clone());
+        let timer = handle.insert_source(Timer::new(), move |((), timer), data| {
+            let (elapsed, written) = {
+                let pump = pump2.borrow();
+                (pump.last_progress.elapsed(), pump.written)
+            };
+            if elapsed >= timeout {
+                finish(&pump2, &callback2, TransferResult::Stalled(written), data);
+            } else {
+                // there was some progress, wait for the rest of the timeout
+                let _ = timer.add_timeout(timeout - elapsed, ());
+            }
+        })?;
+        let _ = timer.handle().add_timeout(timeout, ());
+        pump.borrow_mut().timer = Some(timer);
+    }
+    Ok(())
+}
+
+fn ready<Data, F>(pump: &Rc<RefCell<Pump>>, callback: &Rc<RefCell<Option<F>>>, data: &mut Data)
+where
+    F: FnOnce(TransferResult, &mut Data),
+{
+    if pump.borrow().finished {
+        return;
+    }
+    let result = pump.borrow_mut().pump();
+    if let Some(result) = result {
+        finish(pump, callback, result, data);
+    }
+}
+
+fn finish<Data, F>(
+    pump: &Rc<RefCell<Pump>>,
+    callback: &Rc<RefCell<Option<F>>>,
+    result: TransferResult,
+    data: &mut Data,
+) where
+    F: FnOnce(TransferResult, &mut Data),
+{
+    pump.borrow_mut().close();
+    let callback = callback.borrow_mut().take();
+    if let Some(callback) = callback {
+        callback(result, data);
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use {Interface, MessageGroup, NewResource, Resource};
+
+// the handler of a range of versions, bounds included
+struct Handler<I: Interface> {
+    min: u32,
+    max: u32,
+    handler: Arc<Fn(I::Request, Resource<I>) + Send + Sync>,
+}
+
+impl<I: Interface> Clone for Handler<I> {
+    fn clone(&self) -> Handler<I> {
+        Handler {
+            min: self.min,
+            max: self.max,
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+/// Implementations of an interface, each handling a range of its versions
+///
+/// A compositor supporting clients written against several versions of a protocol
+/// often handles the requests differently depending on the version an object was
+/// bound with: the semantics of a request may have changed, or a new request may
+/// replace a combination of older ones. A `Versioned` holds one handler per range of
+/// versions, and the resources it implements send each of their requests to the
+/// handler of their own version.
+///
+/// The ranges are declared in increasing order and do not overlap, so that adding the
+/// support of a new version is done by adding a handler, the ones of the older versions
+/// being left untouched. When the state of the compositor is updated for the new
+/// version, an older handler can translate its requests and forward them to the
+/// newer one, which is shared between them:
+///
+/// ```ignore
+/// let handlers = Versioned::new()
+///     .handle(1, 3, move |request, surface| new_handler(translate(request), surface))
+///     .handle(4, 4, new_handler);
+/// display.create_global::<WlCompositor, _>(handlers.max_version(), move |new, _| {
+///     handlers.implement(new, None::<fn(_)>, ());
+/// });
+/// ```
+pub struct Versioned<I: Interface> {
+    handlers: Vec<Handler<I>>,
+}
+
+impl<I: Interface> Clone for Versioned<I> {
+    fn clone(&self) -> Versioned<I> {
+        Versioned {
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+impl<I: Interface> Versioned<I> {
+    /// Create a set without handlers
+    pub fn new() -> Versioned<I> {
+        Versioned { handlers: Vec::new() }
+    }
+
+    /// Add the handler of the versions from `min` to `max`, both included
+    ///
+    /// Panics if the range is empty, if it goes beyond the version of the interface
+    /// known to this library, or if it does not start after the range of the previous
+    /// handler.
+    pub fn handle<F>(mut self, min: u32, max: u32, handler: F) -> Versioned<I>
+    where
+        F: Fn(I::Request, Resource<I>) + Send + Sync + 'static,
+    {
+        assert!(
+            min >= 1 && min <= max,
+            "Invalid version range {}-{} for {}.",
+            min,
+            max,
+            I::NAME
+        );
+        assert!(
+            max <= I::VERSION,
+            "Version {} of {} is not supported, the maximum is {}.",
+            max,
+            I::NAME,
+            I::VERSION
+        );
+        if let Some(last) = self.handlers.last() {
+            assert!(
+                min > last.max,
+                "The versions of {} must be handled in increasing order, {} follows {}.",
+                I::NAME,
+                min,
+                last.max
+            );
+        }
+        self.handlers.push(Handler {
+            min,
+            max,
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    /// Whether a version is handled
+    pub fn handles(&self, version: u32) -> bool {
+        self.handlers.iter().any(|h| h.min <= version && version <= h.max)
+    }
+
+    /// The highest version up to which all versions are handled
+    ///
+    /// This is the version to advertise the global of the interface with, it is 0 if
+    /// the first version is not handled.
+    pub fn max_version(&self) -> u32 {
+        let mut version = 0;
+        for h in &self.handlers {
+            if h.min != version + 1 {
+                break;
+            }
+            version = h.max;
+        }
+        version
+    }
+
+    /// Implement a new resource with these handlers
+    ///
+    /// Its requests are sent to the handler of the version it was created with. If
+    /// there is none, the requests can not be processed: the client is sent a protocol
+    /// error, as for a request whose arguments are invalid.
+    pub fn implement<Dest, UD>(
+        &self,
+        new: NewResource<I>,
+        destructor: Option<Dest>,
+        user_data: UD,
+    ) -> Resource<I>
+    where
+        Dest: FnMut(Resource<I>) + Send + 'static,
+        UD: Send + Sync + 'static,
+        I::Request: MessageGroup<Map = ::imp::ResourceMap>,
+    {
+        let handlers = self.handlers.clone();
+        new.implement(
+            move |request, resource: Resource<I>| {
+                let version = resource.version();
+                match handlers.iter().find(|h| h.min <= version && version <= h.max) {
+                    Some(h) => (h.handler)(request, resource),
+                    None => {
+                        let msg = format!(
+                            "request {}@{}.{} has no handler for version {}",
+                            I::NAME,
+                            resource.id(),
+                            I::Request::MESSAGES[request.opcode() as usize].name,
+                            version
+                        );
+                        // wl_display.error.invalid_method
+                        resource.post_error(1, msg);
+                    }
+                }
+            },
+            destructor,
+            user_data,
+        )
+    }
+}
+
+impl<I: Interface> Default for Versioned<I> {
+    fn default() -> Versioned<I> {
+        Versioned::new()
+    }
+}
@@ -13,6 +13,7 @@ pub enum wl_display {}
 pub enum wl_event_loop {}
 pub enum wl_event_source {}
 pub enum wl_global {}
+pub enum wl_protocol_logger {}
 pub enum wl_resource {}
 pub enum wl_shm_buffer {}
 
@@ -24,6 +25,29 @@ pub type wl_global_bind_func_t = unsafe extern "C" fn(*mut wl_client, *mut c_voi
 pub type wl_notify_func_t = unsafe extern "C" fn(*mut wl_listener, *mut c_void) -> ();
 pub type wl_resource_destroy_func_t = unsafe extern "C" fn(*mut wl_resource) -> ();
 pub type wl_display_global_filter_func_t = unsafe extern "C" fn(*const wl_client, *const wl_global, *mut c_void) -> bool;
+pub type wl_client_for_each_resource_iterator_func_t = unsafe extern "C" fn(*mut wl_resource, *mut c_void) -> wl_iterator_result;
+pub type wl_protocol_logger_func_t = unsafe extern "C" fn(*mut c_void, wl_protocol_logger_type, *const wl_protocol_logger_message) -> ();
+
+#[repr(C)]
+pub enum wl_iterator_result {
+    WL_ITERATOR_STOP,
+    WL_ITERATOR_CONTINUE,
+}
+
+#[repr(C)]
+pub enum wl_protocol_logger_type {
+    WL_PROTOCOL_LOGGER_REQUEST,
+    WL_PROTOCOL_LOGGER_EVENT,
+}
+
+#[repr(C)]
+pub struct wl_protocol_logger_message {
+    pub resource: *mut wl_resource,
+    pub message_opcode: c_int,
+    pub message: *const wl_message,
+    pub arguments_count: c_int,
+    pub arguments: *const wl_argument,
+}
 
 #[repr(C)]
 pub struct wl_listener {
@@ -47,6 +71,7 @@ external_library!(WaylandServer, "wayland-server",
         fn wl_client_add_destroy_listener(*mut wl_client, *mut wl_listener) -> (),
         fn wl_client_get_destroy_listener(*mut wl_client, wl_notify_func_t) -> *mut wl_listener,
         fn wl_client_post_no_memory(*mut wl_client) -> (),
+        fn wl_client_for_each_resource(*mut wl_client, wl_client_for_each_resource_iterator_func_t, *mut c_void) -> (),
         fn wl_resource_create(*mut wl_client, *const wl_interface, c_int, u32) -> *mut wl_resource,
     // wl_display
         fn wl_client_create(*mut wl_display, c_int) -> *mut wl_client,
@@ -68,6 +93,9 @@ external_library!(WaylandServer, "wayland-server",
         fn wl_display_init_shm(*mut wl_display) -> c_int,
         fn wl_display_add_client_created_listener(*mut wl_display, *mut wl_listener) -> (),
         fn wl_display_set_global_filter(*mut wl_display, wl_display_global_filter_func_t, *mut c_void) -> (),
+        fn wl_display_add_protocol_logger(*mut wl_display, wl_protocol_logger_func_t, *mut c_void) -> *mut wl_protocol_logger,
+    // wl_protocol_logger
+        fn wl_protocol_logger_destroy(*mut wl_protocol_logger) -> (),
     // wl_event_loop
         fn wl_event_loop_create() -> *mut wl_event_loop,
         fn wl_event_loop_destroy(*mut wl_event_loop) -> (),
@@ -103,6 +131,7 @@ external_library!(WaylandServer, "wayland-server",
         fn wl_resource_set_user_data(*mut wl_resource, *mut c_void) -> (),
         fn wl_resource_get_user_data(*mut wl_resource) -> *mut c_void,
         fn wl_resource_get_version(*mut wl_resource) -> c_int,
+        fn wl_resource_get_class(*mut wl_resource) -> *const c_char,
         fn wl_resource_set_destructor(*mut wl_resource, Option<wl_resource_destroy_func_t>) -> (),
         fn wl_resource_instance_of(*mut wl_resource, *const wl_interface, *const c_void) -> c_int,
         fn wl_resource_add_destroy_listener(*mut wl_resource, wl_notify_func_t) -> (),